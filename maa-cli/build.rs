@@ -1,5 +1,11 @@
 fn main() {
     println!("cargo:rerun-if-env-changed=MAA_VERSION");
+    println!("cargo:rerun-if-env-changed=MAA_PACKAGE_MANAGED");
+    println!("cargo:rerun-if-env-changed=MAA_USER_AGENT_SUFFIX");
+    println!(
+        "cargo:rustc-env=MAA_TARGET={}",
+        std::env::var("TARGET").expect("TARGET is set by cargo")
+    );
     if let Ok(version) = std::env::var("MAA_VERSION") {
         println!("cargo:rustc-env=MAA_VERSION={}", version);
     } else {
@@ -63,7 +63,7 @@ complete -c maa -n "__fish_seen_subcommand_from self; and __fish_seen_subcommand
 complete -c maa -n "__fish_seen_subcommand_from self; and __fish_seen_subcommand_from update" -l download-url -d 'URL of maa-cli to download' -r
 
 # Maa directory navigation
-set -l maa_dirs data lib config cache resource hot-update log
+set -l maa_dirs data lib config cache state resource hot-update log
 complete -c maa -n "__fish_seen_subcommand_from dir" -f -a "$maa_dirs"
 
 # Maa run related options
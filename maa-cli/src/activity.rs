@@ -163,9 +163,9 @@ fn parse_time(time: &str, tz: i32) -> Option<DateTime<FixedOffset>> {
 fn load_item_index(client: ClientType) -> Result<JsonValue> {
     let hot_update_resource_dir = dirs::hot_update().join("resource");
     let base_resource_dir = if hot_update_resource_dir.exists() {
-        hot_update_resource_dir.into()
-    } else if let Some(resource_dir) = dirs::find_resource() {
-        resource_dir
+        hot_update_resource_dir
+    } else if let Some(resource_dir) = crate::locate::find_resource() {
+        resource_dir.into_path()
     } else {
         bail!("Failed to find resource dir");
     };
@@ -1,5 +1,5 @@
 use crate::{
-    dirs::{cache, log, state},
+    dirs::{self, cache, log, state, CacheFile, CacheUsage},
     value::userinput::{BoolInput, UserInput},
 };
 
@@ -8,14 +8,17 @@ use std::{
     fs::{read_dir, DirEntry},
     path::{Path, PathBuf},
     sync::OnceLock,
+    time::{Duration, SystemTime},
 };
 
-use anyhow::{bail, Result};
-use log::warn;
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Local};
+use log::{info, warn};
+use prettytable::{format, row, Table};
 
 pub trait PathProvider {
     /// Path to a directory to be cleaned up
-    fn target_dir(&self) -> Cow<Path>;
+    fn target_dir(&self) -> Cow<'_, Path>;
 
     /// Determine whether an entry in the directory should be deleted
     ///
@@ -59,7 +62,7 @@ pub enum CleanupTarget {
 use CleanupTarget::*;
 
 impl PathProvider for CleanupTarget {
-    fn target_dir(&self) -> Cow<Path> {
+    fn target_dir(&self) -> Cow<'_, Path> {
         // Show warning for deprecated targets
         match *self {
             Avatars => warn!("Cleanup target avatars is deprecated, use core-cache instead."),
@@ -189,6 +192,201 @@ where
     Ok(())
 }
 
+/// Parse an age like `30d`, `12h`, `90m` or `2w`, a number without unit is in days.
+pub fn parse_age(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (num, unit) = s.split_at(split);
+    let num: u64 = num
+        .parse()
+        .map_err(|_| format!("invalid age `{s}`, expected e.g. `30d`"))?;
+    let secs = match unit.trim() {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "" | "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        unit => {
+            return Err(format!(
+                "unknown unit `{unit}` of age, expected s, m, h, d or w"
+            ))
+        }
+    };
+    Ok(Duration::from_secs(num.saturating_mul(secs)))
+}
+
+/// Parse a size like `2G`, `500M`, `512K` or `1024`, units are powers of 1024.
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+    let (num, unit) = s.split_at(split);
+    let num: f64 = num
+        .parse()
+        .map_err(|_| format!("invalid size `{s}`, expected e.g. `2G`"))?;
+    let exp = match unit
+        .trim()
+        .to_ascii_uppercase()
+        .trim_end_matches(['B', 'I'])
+    {
+        "" => 0,
+        "K" => 1,
+        "M" => 2,
+        "G" => 3,
+        "T" => 4,
+        _ => {
+            return Err(format!(
+                "unknown unit `{unit}` of size, expected K, M, G or T"
+            ))
+        }
+    };
+    Ok((num * 1024f64.powi(exp)) as u64)
+}
+
+/// Format a size in bytes with binary units, e.g. `1.5 MiB`
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
+
+fn format_time(time: SystemTime) -> String {
+    DateTime::<Local>::from(time)
+        .format("%Y-%m-%d %H:%M")
+        .to_string()
+}
+
+/// Print the usage of each component of the cache directory
+pub fn report() -> Result<()> {
+    let usages = dirs::cache_usage()
+        .with_context(|| format!("Failed to read cache directory {}", cache().display()))?;
+    println!("Cache directory: {}", cache().display());
+    if usages.is_empty() {
+        println!("The cache directory is empty.");
+        return Ok(());
+    }
+    usage_table(&usages).printstd();
+    Ok(())
+}
+
+fn usage_table(usages: &[CacheUsage]) -> Table {
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
+    table.set_titles(row!["COMPONENT", "FILES", "SIZE", "OLDEST", "NEWEST"]);
+    for usage in usages {
+        table.add_row(row![
+            usage.component,
+            usage.count,
+            format_size(usage.bytes),
+            format_time(usage.oldest),
+            format_time(usage.newest)
+        ]);
+    }
+    let total: u64 = usages.iter().map(|usage| usage.bytes).sum();
+    let count: usize = usages.iter().map(|usage| usage.count).sum();
+    table.add_row(row!["total", count, format_size(total), "", ""]);
+    table
+}
+
+/// Select files to prune from the cache.
+///
+/// Files modified earlier than `older_than` ago are selected first, then the oldest files
+/// of the rest are selected until the total size of remaining files is at most `max_size`.
+/// The selected files are sorted from the oldest to the newest.
+fn select_prune(
+    mut files: Vec<CacheFile>,
+    now: SystemTime,
+    older_than: Option<Duration>,
+    max_size: Option<u64>,
+) -> Vec<CacheFile> {
+    files.sort_by(|a, b| {
+        a.modified
+            .cmp(&b.modified)
+            .then_with(|| a.path.cmp(&b.path))
+    });
+
+    let mut total: u64 = files.iter().map(|file| file.size).sum();
+    let max_size = max_size.unwrap_or(u64::MAX);
+    let expired = |file: &CacheFile| {
+        older_than.is_some_and(|age| {
+            now.duration_since(file.modified)
+                .is_ok_and(|elapsed| elapsed > age)
+        })
+    };
+
+    let mut selected = Vec::new();
+    for file in files {
+        if expired(&file) || total > max_size {
+            total -= file.size;
+            selected.push(file);
+        }
+    }
+    selected
+}
+
+/// Prune files in the cache directory by age and total size, see [`select_prune`]
+pub fn prune(older_than: Option<Duration>, max_size: Option<u64>) -> Result<()> {
+    let files = dirs::cache_files()
+        .with_context(|| format!("Failed to read cache directory {}", cache().display()))?;
+    let selected = select_prune(files, SystemTime::now(), older_than, max_size);
+
+    if selected.is_empty() {
+        println!("No cache files to prune.");
+        return Ok(());
+    }
+
+    let total: u64 = selected.iter().map(|file| file.size).sum();
+    for (i, file) in selected.iter().enumerate() {
+        println!(
+            "{}. {} ({}, {})",
+            i + 1,
+            file.path.display(),
+            format_size(file.size),
+            format_time(file.modified)
+        );
+    }
+
+    let prompt = format!("delete files mentioned above ({})", format_size(total));
+    if !BoolInput::new(Some(false), Some(&prompt)).value()? {
+        println!("Canceled.");
+        return Ok(());
+    }
+
+    let mut has_err = false;
+    for file in selected {
+        match std::fs::remove_file(&file.path) {
+            Ok(()) => {
+                info!(
+                    "Deleted cache file {} ({})",
+                    file.path.display(),
+                    format_size(file.size)
+                );
+                dirs::remove_empty_parents(&file.path, cache());
+            }
+            Err(e) => {
+                warn!("Failed to delete cache file {}: {e}", file.path.display());
+                has_err = true;
+            }
+        }
+    }
+
+    if has_err {
+        bail!("Some errors occurred during pruning, at least one file failed to be deleted.");
+    }
+
+    Ok(())
+}
+
 /// Delete a file or directory
 fn del_item(path: &Path) -> Result<(), std::io::Error> {
     if path.is_dir() {
@@ -309,7 +507,7 @@ mod tests {
         struct All;
 
         impl PathProvider for All {
-            fn target_dir(&self) -> Cow<Path> {
+            fn target_dir(&self) -> Cow<'_, Path> {
                 join!(temp_dir(), "maa-cli-test-cleanup").into()
             }
         }
@@ -317,7 +515,7 @@ mod tests {
         struct BlackList(Vec<&'static str>);
 
         impl PathProvider for BlackList {
-            fn target_dir(&self) -> Cow<Path> {
+            fn target_dir(&self) -> Cow<'_, Path> {
                 join!(temp_dir(), "maa-cli-test-cleanup").into()
             }
 
@@ -332,7 +530,7 @@ mod tests {
         struct WhiteList(Vec<&'static str>);
 
         impl PathProvider for WhiteList {
-            fn target_dir(&self) -> Cow<Path> {
+            fn target_dir(&self) -> Cow<'_, Path> {
                 join!(temp_dir(), "maa-cli-test-cleanup").into()
             }
 
@@ -389,6 +587,105 @@ mod tests {
         std::fs::remove_dir(&test_root).unwrap();
     }
 
+    #[test]
+    fn parse_age_and_size() {
+        assert_eq!(parse_age("30d"), Ok(Duration::from_secs(30 * 86400)));
+        assert_eq!(parse_age("30"), Ok(Duration::from_secs(30 * 86400)));
+        assert_eq!(parse_age("12h"), Ok(Duration::from_secs(12 * 3600)));
+        assert_eq!(parse_age("2w"), Ok(Duration::from_secs(14 * 86400)));
+        assert!(parse_age("d").is_err());
+        assert!(parse_age("30y").is_err());
+
+        assert_eq!(parse_size("1024"), Ok(1024));
+        assert_eq!(parse_size("2G"), Ok(2 << 30));
+        assert_eq!(parse_size("1.5M"), Ok(3 << 19));
+        assert_eq!(parse_size("512KiB"), Ok(512 << 10));
+        assert_eq!(parse_size("2gb"), Ok(2 << 30));
+        assert!(parse_size("G").is_err());
+        assert!(parse_size("2X").is_err());
+
+        assert_eq!(format_size(1000), "1000 B");
+        assert_eq!(format_size(3 << 19), "1.5 MiB");
+    }
+
+    #[test]
+    fn select_files_to_prune() {
+        let test_root = join!(temp_dir(), "maa-cli-test-select-prune");
+        if test_root.exists() {
+            std::fs::remove_dir_all(&test_root).unwrap();
+        }
+        join!(&test_root, "copilot").ensure().unwrap();
+
+        let now = SystemTime::now();
+        let days = |n: u64| now - Duration::from_secs(n * 86400);
+        for (name, size, age) in [
+            ("maa_cli-v0.4.6.tar.gz", 100, 60),
+            ("maa_cli-v0.4.7.tar.gz", 100, 40),
+            ("MAA-v5.0.0-linux-x86_64.tar.gz", 300, 20),
+            ("copilot/1.json", 10, 5),
+            ("MAA-v5.1.0-linux-x86_64.tar.gz", 300, 1),
+        ] {
+            let path = join!(&test_root, name);
+            std::fs::write(&path, vec![0; size]).unwrap();
+            std::fs::File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(days(age))
+                .unwrap();
+        }
+        let files = dirs::cache_files_in(&test_root).unwrap();
+        let select = |older_than: Option<u64>, max_size: Option<u64>| {
+            select_prune(
+                files.clone(),
+                now,
+                older_than.map(|d| Duration::from_secs(d * 86400)),
+                max_size,
+            )
+            .into_iter()
+            .map(|file| {
+                file.path
+                    .strip_prefix(&test_root)
+                    .unwrap()
+                    .to_string_lossy()
+                    .replace('\\', "/")
+            })
+            .collect::<Vec<_>>()
+        };
+
+        assert!(select(None, None).is_empty());
+        assert_eq!(
+            select(Some(30), None),
+            ["maa_cli-v0.4.6.tar.gz", "maa_cli-v0.4.7.tar.gz"]
+        );
+        // the oldest files are deleted until the total size is under the cap
+        assert_eq!(
+            select(None, Some(400)),
+            [
+                "maa_cli-v0.4.6.tar.gz",
+                "maa_cli-v0.4.7.tar.gz",
+                "MAA-v5.0.0-linux-x86_64.tar.gz",
+            ]
+        );
+        assert!(select(None, Some(810)).is_empty());
+        assert_eq!(select(None, Some(0)).len(), 5);
+        // both policies are applied
+        assert_eq!(select(Some(50), Some(710)), ["maa_cli-v0.4.6.tar.gz"]);
+        assert_eq!(
+            select(Some(50), Some(700)),
+            ["maa_cli-v0.4.6.tar.gz", "maa_cli-v0.4.7.tar.gz"]
+        );
+
+        // empty directories are removed after their files are deleted
+        let copilot = join!(&test_root, "copilot", "1.json");
+        std::fs::remove_file(&copilot).unwrap();
+        dirs::remove_empty_parents(&copilot, &test_root);
+        assert!(!join!(&test_root, "copilot").exists());
+        assert!(test_root.exists());
+
+        std::fs::remove_dir_all(&test_root).unwrap();
+    }
+
     #[test]
     fn test_del_item() {
         let test_root = join!(temp_dir(), "maa-cli-test-del-item");
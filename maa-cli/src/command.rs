@@ -19,6 +19,32 @@ pub(crate) struct CLI {
     /// and parameters will be set to default values.
     #[arg(long, global = true)]
     pub(crate) batch: bool,
+    /// Use given directory as the config directory
+    ///
+    /// All configurations, including task files, profiles and the cli configuration,
+    /// will be read from this directory instead of the default one.
+    /// This takes precedence over the environment variable `MAA_CONFIG_DIR`,
+    /// e.g. `maa --config-dir /path/to/project run task` runs a task of given project
+    /// without modifying the global configurations.
+    #[arg(long, global = true)]
+    pub(crate) config_dir: Option<PathBuf>,
+    /// Skip system-wide configs in `/etc/maa`
+    ///
+    /// Configs are merged from system-wide configs in `/etc/maa` (Unix only),
+    /// per-user configs in the config directory and per-project configs in `./.maa`,
+    /// where later ones take precedence. With this flag, system-wide configs are skipped.
+    #[arg(long, global = true)]
+    pub(crate) no_system_config: bool,
+    /// Use given profile, e.g. for another account
+    ///
+    /// Files in `profiles/<name>` of the config directory overlay the config directory,
+    /// i.e. they are used instead of files with the same path in the config directory,
+    /// and MaaCore of the profile keeps its caches and logs in `profiles/<name>`
+    /// of the state directory. Manage profiles by `maa profile`.
+    /// This option must be given before the subcommand, e.g. `maa --profile alt run daily`,
+    /// since `maa run --profile` selects the profile of MaaCore.
+    #[arg(long, value_name = "NAME")]
+    pub(crate) profile: Option<String>,
     #[command(flatten)]
     pub(crate) log: log::Args,
 }
@@ -47,6 +73,21 @@ pub(crate) enum Command {
         /// please use `maa-cli update` instead.
         #[arg(short, long)]
         force: bool,
+        /// Install the given version of MaaCore instead of the latest one of the channel
+        ///
+        /// The version can be given with or without the leading `v`, e.g. `5.0.0` or `v5.0.0`.
+        #[arg(long, value_parser = parse_version)]
+        version: Option<semver::Version>,
+        /// Build MaaCore from source instead of installing a prebuilt package
+        ///
+        /// This is useful on platforms without prebuilt packages.
+        /// The source of the release is downloaded and built by cmake,
+        /// which requires cmake, python and a C++ compiler.
+        #[arg(long)]
+        build_from_source: bool,
+        /// Number of parallel jobs to build MaaCore, default to the number of CPUs
+        #[arg(short, long, requires = "build_from_source")]
+        jobs: Option<usize>,
     },
     /// Update maa maa_core and resources
     ///
@@ -60,6 +101,47 @@ pub(crate) enum Command {
     Update {
         #[command(flatten)]
         common: config::cli::maa_core::CommonArgs,
+        /// Only update resources from the resource package, without reinstalling MaaCore
+        ///
+        /// The package is downloaded only if its version differs from the installed one.
+        /// This requires the version info to provide a resource package.
+        #[arg(long, conflicts_with = "no_resource")]
+        resource_only: bool,
+        #[command(flatten)]
+        hooks: config::cli::maa_core::HookArgs,
+    },
+    /// Uninstall MaaCore or its resources installed by maa-cli
+    ///
+    /// Only files recorded when installing are removed, so configuration files
+    /// and files added by you are kept. Files to be removed are always listed first,
+    /// and removed after confirmation.
+    #[cfg(feature = "core_installer")]
+    Uninstall {
+        /// Component to uninstall
+        component: crate::installer::uninstall::Component,
+        /// Also remove the cached package and staging files of the component,
+        /// and the manifest in the state directory if no other component is installed
+        #[arg(long)]
+        purge: bool,
+        /// Only list files to be removed
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Verify files of MaaCore and its resources installed by maa-cli
+    ///
+    /// Installed files are checked against the checksums recorded when installing,
+    /// and missing, modified and extraneous files are reported.
+    /// Exit with code 2 if any problem is found.
+    #[cfg(feature = "core_installer")]
+    Verify {
+        /// Component to verify, all installed components by default
+        component: Option<crate::installer::uninstall::Component>,
+        /// Repair damaged files by extracting them from the cached package again
+        ///
+        /// The package is downloaded again if it is not in the cache,
+        /// and extraneous files are removed.
+        #[arg(long)]
+        repair: bool,
     },
     /// Manage maa-cli self
     ///
@@ -75,19 +157,61 @@ pub(crate) enum Command {
     /// Note: the basic resource installed with maa-core will not be updated.
     ///
     /// The remote of can be configured in the config file of maa-cli.
-    HotUpdate,
+    HotUpdate {
+        /// Fetch from the fastest preset mirror
+        ///
+        /// The latency of each preset mirror is measured by a HEAD request,
+        /// and the fastest one is used regardless of `resource.mirror` in the config file.
+        #[arg(long)]
+        auto: bool,
+    },
     /// Print path of maa directories
     ///
     /// This command will print the path used by maa-cli.
     /// Some of these paths are used by maa-core and maa-run.
-    Dir { dir: Dir },
+    /// If no directory is given, a table of the config, data, cache, state and log directories
+    /// is printed, marking whether they exist. All overrides, e.g. `--config-dir`,
+    /// environment variables and `--profile`, are applied.
+    Dir {
+        dir: Option<Dir>,
+        /// Open the directory with the file manager instead of printing its path
+        #[arg(long, requires = "dir")]
+        open: bool,
+    },
+    /// Check the installation and configs for common problems
+    ///
+    /// The directories are checked to exist and be writable, MaaCore to be loadable,
+    /// the version of resources to be readable, all config and task files to parse
+    /// and the metadata url of MaaCore to be reachable. The result of each check is printed
+    /// with hints to fix the problems, and the command fails if any check fails.
+    Doctor {
+        /// Skip checks requiring network access
+        #[arg(long)]
+        offline: bool,
+    },
+    /// Move files left in legacy locations by older versions of maa-cli
+    ///
+    /// This command moves config files in the legacy config directory and records of maa-cli
+    /// in the cache directory into the current layout. The migration is also run once after
+    /// maa-cli is updated. Files existing in both locations are not overwritten, the newer one
+    /// is kept and the other one is backed up with a `.bak` suffix.
+    Migrate {
+        /// Only print what would be moved without moving anything
+        #[arg(long)]
+        dry_run: bool,
+    },
     /// Print version of given component
     ///
     /// This command will print the version of given component.
-    /// If no component is given, it will print the version of all components.
+    /// If no component is given, it will print the versions of maa-cli, MaaCore and resources,
+    /// the target triple and the installation paths, which are useful for bug reports.
+    /// Missing components are reported as not installed.
     Version {
         #[arg(default_value = "all")]
         component: Component,
+        /// Print the versions and paths of all components in JSON
+        #[arg(long)]
+        json: bool,
     },
     /// Run a custom task
     ///
@@ -99,13 +223,47 @@ pub(crate) enum Command {
     /// In the `tasks` directory, you can create a TOML or JSON file,
     /// to define a task. More information can be found in the README.
     /// You can also use `maa-cli list` to list all available tasks.
+    #[command(group(clap::ArgGroup::new("task_source").required(true)))]
     Run {
         /// Name of the task to run
         ///
         /// The task name is the name of the task file without the extension.
         /// The task file must be in the `tasks` directory of the config directory.
         /// The task file must be in the TOML, YAML or JSON format.
-        task: String,
+        #[arg(group = "task_source")]
+        task: Option<String>,
+        /// Path of the task file to run, instead of a task in the config directory
+        ///
+        /// The path can be absolute or relative to the current directory.
+        /// If the format can not be determined by the extension, it will be guessed from
+        /// the content, so you can use `--task-file /dev/stdin` to read the task from stdin.
+        #[arg(long, group = "task_source")]
+        task_file: Option<PathBuf>,
+        /// Task config in JSON format, instead of a task file
+        ///
+        /// This is useful when maa-cli is driven by other programs,
+        /// which construct task parameters dynamically, e.g.
+        /// `maa run --task-json '{"tasks": [{"type": "StartUp"}]}'`.
+        #[arg(long, group = "task_source")]
+        task_json: Option<String>,
+        /// Show the execution time of tasks in the last N runs profiled by `--profile-tasks`,
+        /// instead of running tasks
+        #[arg(long, value_name = "N", group = "task_source")]
+        show_profile_history: Option<usize>,
+        /// Run the task again when the config files are changed
+        ///
+        /// The config directory and the task file given by `--task-file` are watched,
+        /// the task is run again after they are saved. Errors of a run are reported
+        /// without exiting, so you can fix the config and save it again.
+        /// Press Ctrl+C to exit.
+        #[arg(long)]
+        watch: bool,
+        /// Time in milliseconds to wait for the files to settle before running again
+        ///
+        /// Some editors write a file in multiple steps, the task is only run again
+        /// after no change happened within this duration.
+        #[arg(long, default_value_t = 500, requires = "watch")]
+        watch_debounce: u64,
         #[command(flatten)]
         common: run::CommonArgs,
     },
@@ -193,12 +351,48 @@ pub(crate) enum Command {
         timezone: Option<i8>,
     },
     /// Clearing the caches of maa-cli and maa core
+    ///
+    /// With `--report`, `--older-than` or `--max-size`, the cache directory is
+    /// reported or pruned instead of cleaning up the targets.
     Cleanup {
         /// Specify the path for deletion
         targets: Vec<cleanup::CleanupTarget>,
+        /// Report the usage of each component of the cache directory
+        #[arg(long, conflicts_with = "targets")]
+        report: bool,
+        /// Delete cache files older than given age, e.g. `30d`, `12h` or `2w`
+        #[arg(long, value_name = "AGE", value_parser = cleanup::parse_age, conflicts_with = "targets")]
+        older_than: Option<std::time::Duration>,
+        /// Delete the oldest cache files until the cache is at most given size, e.g. `2G`
+        #[arg(long, value_name = "SIZE", value_parser = cleanup::parse_size, conflicts_with = "targets")]
+        max_size: Option<u64>,
     },
     /// List all available tasks
     List,
+    /// Print the path of the log of the latest run, or its last lines with `--tail`
+    ///
+    /// Commands running tasks write their log to `$(maa dir log)/runs`,
+    /// unless `--no-run-log` is given.
+    Log {
+        /// Print the last given number of lines of the log instead of its path
+        #[arg(
+            long,
+            value_name = "LINES",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "20"
+        )]
+        tail: Option<usize>,
+    },
+    /// Manage task files
+    #[command(subcommand)]
+    Task(TaskCommand),
+    /// Manage configuration files and community task configs
+    #[command(subcommand)]
+    Config(ConfigCommand),
+    /// Manage profiles used by `maa --profile <name>`
+    #[command(subcommand)]
+    Profile(ProfileCommand),
     /// Import configuration files
     Import {
         /// Path of the configuration file
@@ -242,7 +436,22 @@ pub(crate) enum Command {
         force: bool,
     },
     /// Generate completion script for given shell
-    Complete { shell: Shell },
+    ///
+    /// The completion script will be printed to stdout by default.
+    /// With `--install`, the script will be written to the location
+    /// where the shell loads completions from, and instructions
+    /// to load it will be printed if needed.
+    Complete {
+        /// Shell to generate completion script for
+        ///
+        /// Required unless `--install` is given,
+        /// in which case the shell is detected from the environment.
+        #[arg(required_unless_present = "install")]
+        shell: Option<Shell>,
+        /// Install the completion script instead of printing it
+        #[arg(long)]
+        install: bool,
+    },
     /// Generate man page
     Mangen {
         /// Path of the output file
@@ -259,9 +468,191 @@ pub(crate) enum SelfCommand {
     ///
     /// This command will download prebuilt binary of maa-cli,
     /// and install them to it current directory.
+    /// If maa-cli is installed by a package manager (e.g. Homebrew, AUR or Nix),
+    /// this command will refuse to update it, please update it with the package manager.
     Update {
         #[command(flatten)]
         common: config::cli::maa_cli::CommonArgs,
+        /// Force to update even if maa-cli is managed by a package manager
+        #[arg(long)]
+        force: bool,
+        /// Only check whether a newer version is available, do not update
+        #[arg(long)]
+        check: bool,
+        /// Verify the checksum of current binary instead of updating
+        ///
+        /// `OK` or `FAILED` is printed, and the exit code is 2 if the verification failed.
+        /// It can be combined with `--check` to also report whether an update is available.
+        #[arg(long)]
+        verify: bool,
+    },
+    /// Verify the integrity of maa-cli binary
+    ///
+    /// This command will compute the SHA-256 checksum of current maa-cli binary,
+    /// and compare it with the checksum of the released binary of current version.
+    /// If the release information of current version is not available,
+    /// the checksum recorded by last `maa self update` will be used.
+    Verify {
+        #[command(flatten)]
+        common: config::cli::maa_cli::CommonArgs,
+    },
+    /// Uninstall maa-cli after confirmation
+    ///
+    /// Files to be removed are always listed first. This is refused if maa-cli
+    /// is installed by a package manager, please uninstall it with the package manager.
+    /// Configuration files are never removed.
+    Uninstall {
+        /// Also remove cached packages and state files of maa-cli
+        #[arg(long)]
+        purge: bool,
+        /// Only list files to be removed
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Show information about the installation of maa-cli
+    ///
+    /// The information includes the version, the path of binary, the prebuilt target,
+    /// the update source after applying overrides, the cache directory and
+    /// the result of last update check. No network access is required.
+    Info {
+        #[command(flatten)]
+        common: config::cli::maa_cli::CommonArgs,
+        /// Output the information in JSON format
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub(crate) enum ProfileCommand {
+    /// List all profiles, the active one is marked by `*`
+    List,
+    /// Create an empty profile
+    ///
+    /// Files missing in the profile are taken from the config directory,
+    /// so only files differing from the config directory need to be added.
+    New {
+        /// Name of the profile
+        name: String,
+    },
+    /// Copy all files of a profile to a new profile
+    Copy {
+        /// Name of the profile to copy
+        from: String,
+        /// Name of the new profile
+        to: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub(crate) enum TaskCommand {
+    /// Validate all configuration files in the config directory
+    ///
+    /// All TOML, YAML and JSON files in the config directory (or given directory)
+    /// are loaded to check whether they are valid.
+    /// Task files in `tasks` directory, profiles in `profiles` directory and `cli.toml`
+    /// are checked against their schema, and other files are checked to be well-formed.
+    /// A summary of all files is printed, and the command fails if any file is invalid,
+    /// so it can be used in CI to prevent broken configurations from being committed.
+    ValidateAll {
+        /// Directory to validate, default to the config directory
+        #[arg(long)]
+        dir: Option<PathBuf>,
+        /// Format of the summary
+        #[arg(long, default_value = "text")]
+        output: config::validate::OutputFormat,
+    },
+    /// Download a task file from given url and install it
+    ///
+    /// The task file is validated and saved to the `tasks` directory of the config directory.
+    /// The file name is taken from the server response or the url,
+    /// and the format is detected from the extension or the content.
+    /// Hooks defined in the task file are reported, please review them before running the task.
+    #[cfg(feature = "__installer")]
+    Import {
+        /// URL of the task file, e.g. a raw url of a GitHub gist
+        url: String,
+        /// Overwrite existing task file with the same name without asking
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Export a task file in a given format to share it with others
+    ///
+    /// The exported file is pretty printed and records the version of maa-cli exporting it.
+    Export {
+        /// Name of the task file in the `tasks` directory, the extension can be omitted
+        name: String,
+        /// Output file, default to stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Format of the exported file
+        ///
+        /// Default to the extension of output file, or the format of the task file.
+        #[arg(short, long)]
+        format: Option<config::Filetype>,
+        /// Replace user inputs reading from environment variables by inputs with only description
+        #[arg(long)]
+        strip_secrets: bool,
+    },
+    /// Generate a task config of given task type with all known parameters
+    ///
+    /// Parameters are set to their default values, and required parameters without
+    /// a default value are user inputs, so they are queried when running the task
+    /// unless filled in. Edit the generated file to fit your needs.
+    New {
+        /// Type of the task, e.g. `Fight`, `Infrast` or `Copilot`
+        #[arg(long = "type", value_name = "TASK_TYPE")]
+        task_type: maa_sys::TaskType,
+        /// Output file, default to stdout
+        ///
+        /// The file must not exist, e.g. `$MAA_CONFIG_DIR/tasks/fight.toml`.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Format of the generated file
+        ///
+        /// Default to the extension of output file, or TOML.
+        #[arg(short, long)]
+        format: Option<config::Filetype>,
+    },
+}
+
+#[derive(Subcommand)]
+pub(crate) enum ConfigCommand {
+    /// Check the registry of community task configs for new versions
+    ///
+    /// Templates in the registry are listed with their descriptions, and installed task files
+    /// with the same name are compared with them by the `version` field in the root object.
+    /// The registry is fetched from `--registry`, `config_registry` in the cli config,
+    /// or the release assets of maa-cli.
+    #[cfg(feature = "__installer")]
+    CheckUpdates {
+        /// URL of the registry
+        #[arg(long)]
+        registry: Option<String>,
+    },
+    /// Install or update a task config to the latest version in the registry
+    ///
+    /// The downloaded file is checked against the checksum in the registry and validated,
+    /// then saved to the `tasks` directory of the config directory, replacing the installed one.
+    #[cfg(feature = "__installer")]
+    Update {
+        /// Name of the config in the registry
+        name: String,
+        /// URL of the registry
+        #[arg(long)]
+        registry: Option<String>,
+    },
+    /// Display a config file and refresh it every time the file changes
+    ///
+    /// The file is parsed and shown as a tree of values, user inputs are shown
+    /// by their prompts instead of being queried. Parsing errors are shown in place
+    /// of the tree, so you can fix the file and save it again. Press Ctrl+C to exit.
+    Watch {
+        /// Path of the config file in JSON, TOML or YAML
+        path: PathBuf,
+        /// Time in milliseconds to wait for the file to settle before refreshing
+        #[arg(long, default_value_t = 200)]
+        debounce: u64,
     },
 }
 
@@ -286,14 +677,99 @@ pub(crate) enum Dir {
     Config,
     /// Directory of maa-cli's cache
     Cache,
+    /// Directory of maa-cli's state, e.g. records of installations and update checks
+    State,
     /// Directory of MaaCore's resource
     Resource,
     /// Directory of MaaCore's hot update
     HotUpdate,
-    /// Directory of MaaCore's log
+    /// Directory of the logs of MaaCore and runs
     Log,
 }
 
+impl Command {
+    /// Name of the task run by this command, used to name its run log
+    pub(crate) fn run_name(&self) -> Option<String> {
+        let stem = |path: &std::path::Path| {
+            path.file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+        };
+        match self {
+            Command::Run {
+                task, task_file, ..
+            } => task
+                .as_deref()
+                .map(std::path::Path::new)
+                .or(task_file.as_deref())
+                .and_then(stem)
+                .or_else(|| Some("json".to_owned())),
+            Command::StartUp { .. } => Some("startup".to_owned()),
+            Command::CloseDown { .. } => Some("closedown".to_owned()),
+            Command::Fight { .. } => Some("fight".to_owned()),
+            Command::Copilot { .. } => Some("copilot".to_owned()),
+            Command::Roguelike { .. } => Some("roguelike".to_owned()),
+            _ => None,
+        }
+    }
+
+    /// Whether the command is given `--output json`, so the log file is written in JSON lines
+    pub(crate) fn json_output(&self) -> bool {
+        match self {
+            Command::Run { common, .. }
+            | Command::StartUp { common, .. }
+            | Command::CloseDown { common, .. }
+            | Command::Fight { common, .. }
+            | Command::Copilot { common, .. }
+            | Command::Roguelike { common, .. } => {
+                matches!(common.output, run::profile::OutputFormat::Json)
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether the command only reads files, so the directories of maa-cli are not created.
+    pub(crate) fn is_read_only(&self) -> bool {
+        match self {
+            Command::Version { .. }
+            | Command::Dir { .. }
+            | Command::Doctor { .. }
+            | Command::Complete { .. }
+            | Command::Mangen { .. }
+            | Command::List
+            | Command::Remainder { .. }
+            | Command::Log { .. }
+            | Command::Convert { .. }
+            | Command::Activity { .. }
+            | Command::Migrate { dry_run: true }
+            | Command::Profile(ProfileCommand::List)
+            | Command::Task(TaskCommand::ValidateAll { .. } | TaskCommand::Export { .. })
+            | Command::Config(ConfigCommand::Watch { .. }) => true,
+            #[cfg(feature = "cli_installer")]
+            Command::SelfC(SelfCommand::Info { .. }) => true,
+            _ => false,
+        }
+    }
+
+    /// Whether the command generates output for other programs, e.g. completion scripts,
+    /// man pages and JSON, which must not be mixed with prompts.
+    pub(crate) fn generates_output(&self) -> bool {
+        match self {
+            Command::Complete { .. }
+            | Command::Mangen { .. }
+            | Command::Version { json: true, .. } => true,
+            #[cfg(feature = "cli_installer")]
+            Command::SelfC(SelfCommand::Info { json: true, .. }) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Parse a version with an optional leading `v`
+#[cfg(feature = "core_installer")]
+fn parse_version(s: &str) -> Result<semver::Version, semver::Error> {
+    semver::Version::parse(s.strip_prefix('v').unwrap_or(s))
+}
+
 #[cfg(test)]
 pub(crate) fn parse_from<I, T>(args: I) -> CLI
 where
@@ -309,6 +785,8 @@ mod test {
 
     use crate::config::cli::Channel;
 
+    use std::path::Path;
+
     #[macro_export]
     macro_rules! assert_matches {
         ($value:expr, $pattern:pat $(if $guard:expr)? $(,)?) => {
@@ -322,6 +800,124 @@ mod test {
         assert!(parse_from(["maa", "list", "--batch"]).batch);
     }
 
+    #[test]
+    fn log() {
+        assert_matches!(
+            parse_from(["maa", "log"]).command,
+            Command::Log { tail: None }
+        );
+        assert_matches!(
+            parse_from(["maa", "log", "--tail"]).command,
+            Command::Log { tail: Some(20) }
+        );
+        assert_matches!(
+            parse_from(["maa", "log", "--tail=5"]).command,
+            Command::Log { tail: Some(5) }
+        );
+    }
+
+    #[test]
+    fn run_name() {
+        let run_name = |args: &[&str]| parse_from(args).command.run_name();
+        assert_eq!(run_name(&["maa", "run", "daily"]).as_deref(), Some("daily"));
+        assert_eq!(
+            run_name(&["maa", "run", "sub/daily.toml"]).as_deref(),
+            Some("daily")
+        );
+        assert_eq!(
+            run_name(&["maa", "run", "--task-file", "/path/to/weekly.json"]).as_deref(),
+            Some("weekly")
+        );
+        assert_eq!(
+            run_name(&["maa", "run", "--task-json", "{}"]).as_deref(),
+            Some("json")
+        );
+        assert_eq!(run_name(&["maa", "fight", "1-7"]).as_deref(), Some("fight"));
+        assert_eq!(run_name(&["maa", "startup"]).as_deref(), Some("startup"));
+        assert_eq!(run_name(&["maa", "list"]), None);
+        assert_eq!(run_name(&["maa", "log"]), None);
+    }
+
+    #[test]
+    fn json_output() {
+        let json_output = |args: &[&str]| parse_from(args).command.json_output();
+        assert!(json_output(&["maa", "run", "daily", "--output", "json"]));
+        assert!(json_output(&["maa", "fight", "1-7", "--output", "json"]));
+        assert!(!json_output(&["maa", "run", "daily"]));
+        assert!(!json_output(&[
+            "maa",
+            "task",
+            "validate-all",
+            "--output",
+            "json"
+        ]));
+    }
+
+    #[test]
+    fn is_read_only() {
+        let is_read_only = |args: &[&str]| parse_from(args).command.is_read_only();
+        assert!(is_read_only(&["maa", "version"]));
+        assert!(is_read_only(&["maa", "dir", "config"]));
+        assert!(is_read_only(&["maa", "list"]));
+        assert!(is_read_only(&["maa", "task", "validate-all"]));
+        assert!(is_read_only(&["maa", "migrate", "--dry-run"]));
+        assert!(!is_read_only(&["maa", "migrate"]));
+        assert!(!is_read_only(&["maa", "run", "daily"]));
+        assert!(!is_read_only(&["maa", "init"]));
+        assert!(!is_read_only(&["maa", "profile", "new", "alt"]));
+    }
+
+    #[test]
+    fn generates_output() {
+        let generates_output = |args: &[&str]| parse_from(args).command.generates_output();
+        assert!(generates_output(&["maa", "complete", "bash"]));
+        assert!(generates_output(&["maa", "mangen", "--path", "man"]));
+        assert!(generates_output(&["maa", "version", "--json"]));
+        assert!(!generates_output(&["maa", "version"]));
+        #[cfg(feature = "cli_installer")]
+        assert!(generates_output(&["maa", "self", "info", "--json"]));
+        assert!(!generates_output(&["maa", "run", "daily"]));
+    }
+
+    #[test]
+    fn profile() {
+        assert!(parse_from(["maa", "list"]).profile.is_none());
+        let cli = parse_from(["maa", "--profile", "alt", "run", "daily", "-p", "test"]);
+        assert_eq!(cli.profile.as_deref(), Some("alt"));
+        assert_matches!(
+            cli.command,
+            Command::Run { common, .. } if common.profile.as_deref() == Some("test")
+        );
+
+        assert_matches!(
+            parse_from(["maa", "profile", "list"]).command,
+            Command::Profile(ProfileCommand::List)
+        );
+        assert_matches!(
+            parse_from(["maa", "profile", "new", "alt"]).command,
+            Command::Profile(ProfileCommand::New { name }) if name == "alt"
+        );
+        assert_matches!(
+            parse_from(["maa", "profile", "copy", "alt", "alt2"]).command,
+            Command::Profile(ProfileCommand::Copy { from, to }) if from == "alt" && to == "alt2"
+        );
+    }
+
+    #[test]
+    fn config_dir() {
+        assert!(!parse_from(["maa", "list"]).no_system_config);
+        assert!(parse_from(["maa", "list", "--no-system-config"]).no_system_config);
+        assert!(parse_from(["maa", "list"]).config_dir.is_none());
+        assert_eq!(
+            parse_from(["maa", "--config-dir", "/path/to/project", "run", "daily"]).config_dir,
+            Some(PathBuf::from("/path/to/project"))
+        );
+        assert_eq!(
+            parse_from(["maa", "list", "--config-dir", "project"]).config_dir,
+            Some(PathBuf::from("project"))
+        );
+    }
+
     #[cfg(feature = "core_installer")]
     #[test]
     fn install() {
@@ -330,9 +926,46 @@ mod test {
             Command::Install {
                 common: config::cli::maa_core::CommonArgs { .. },
                 force: false,
+                version: None,
+                build_from_source: false,
+                jobs: None,
             }
         );
 
+        assert_matches!(
+            parse_from(["maa", "install", "--version", "v5.0.0"]).command,
+            Command::Install {
+                version: Some(version),
+                ..
+            } if version == semver::Version::new(5, 0, 0)
+        );
+        assert_matches!(
+            parse_from(["maa", "install", "--version", "5.0.0-beta.1"]).command,
+            Command::Install {
+                version: Some(version),
+                ..
+            } if version == semver::Version::parse("5.0.0-beta.1").unwrap()
+        );
+        assert!(CLI::try_parse_from(["maa", "install", "--version", "latest"]).is_err());
+
+        assert_matches!(
+            parse_from(["maa", "install", "--build-from-source"]).command,
+            Command::Install {
+                build_from_source: true,
+                jobs: None,
+                ..
+            }
+        );
+        assert_matches!(
+            parse_from(["maa", "install", "--build-from-source", "-j", "4"]).command,
+            Command::Install {
+                build_from_source: true,
+                jobs: Some(4),
+                ..
+            }
+        );
+        assert!(CLI::try_parse_from(["maa", "install", "--jobs", "4"]).is_err());
+
         assert_matches!(
             parse_from(["maa", "install", "beta"]).command,
             Command::Install {
@@ -394,6 +1027,50 @@ mod test {
         ));
     }
 
+    #[cfg(feature = "core_installer")]
+    #[test]
+    fn uninstall() {
+        assert_matches!(
+            parse_from(["maa", "uninstall", "core"]).command,
+            Command::Uninstall {
+                component: crate::installer::uninstall::Component::Core,
+                purge: false,
+                dry_run: false,
+            }
+        );
+
+        assert_matches!(
+            parse_from(["maa", "uninstall", "resource", "--purge", "--dry-run"]).command,
+            Command::Uninstall {
+                component: crate::installer::uninstall::Component::Resource,
+                purge: true,
+                dry_run: true,
+            }
+        );
+
+        assert!(CLI::try_parse_from(["maa", "uninstall"]).is_err());
+    }
+
+    #[cfg(feature = "core_installer")]
+    #[test]
+    fn verify() {
+        assert_matches!(
+            parse_from(["maa", "verify"]).command,
+            Command::Verify {
+                component: None,
+                repair: false,
+            }
+        );
+
+        assert_matches!(
+            parse_from(["maa", "verify", "resource", "--repair"]).command,
+            Command::Verify {
+                component: Some(crate::installer::uninstall::Component::Resource),
+                repair: true,
+            }
+        );
+    }
+
     #[cfg(feature = "core_installer")]
     #[test]
     fn update() {
@@ -401,8 +1078,39 @@ mod test {
             parse_from(["maa", "update"]).command,
             Command::Update {
                 common: config::cli::maa_core::CommonArgs { .. },
+                resource_only: false,
+                hooks: config::cli::maa_core::HookArgs {
+                    pre_update_hook: None,
+                    post_update_hook: None,
+                },
+            }
+        );
+
+        assert_matches!(
+            parse_from([
+                "maa",
+                "update",
+                "--pre-update-hook",
+                "systemctl stop maa",
+                "--post-update-hook=notify-send updated",
+            ])
+            .command,
+            Command::Update { hooks, .. }
+                if hooks.pre_update_hook.as_deref() == Some("systemctl stop maa")
+                    && hooks.post_update_hook.as_deref() == Some("notify-send updated")
+        );
+
+        assert_matches!(
+            parse_from(["maa", "update", "--resource-only"]).command,
+            Command::Update {
+                resource_only: true,
+                ..
             }
         );
+
+        assert!(
+            CLI::try_parse_from(["maa", "update", "--resource-only", "--no-resource"]).is_err()
+        );
     }
 
     #[cfg(feature = "cli_installer")]
@@ -420,6 +1128,9 @@ mod test {
                     channel: Some(Channel::Beta),
                     ..
                 },
+                force: false,
+                check: false,
+                verify: false,
             })
         );
 
@@ -430,48 +1141,179 @@ mod test {
                     common: config::cli::maa_cli::CommonArgs {
                         api_url: Some(url),
                         ..
-                    }
+                    },
+                    ..
                 }
             ) if url == "url"
         );
+
+        assert_matches!(
+            parse_from(["maa", "self", "update", "--force"]).command,
+            Command::SelfC(SelfCommand::Update { force: true, .. })
+        );
+
+        assert_matches!(
+            parse_from(["maa", "self", "update", "--check", "--verify"]).command,
+            Command::SelfC(SelfCommand::Update {
+                check: true,
+                verify: true,
+                ..
+            })
+        );
+
+        assert_matches!(
+            parse_from(["maa", "self", "verify"]).command,
+            Command::SelfC(SelfCommand::Verify { .. })
+        );
+
+        assert_matches!(
+            parse_from(["maa", "self", "uninstall"]).command,
+            Command::SelfC(SelfCommand::Uninstall {
+                purge: false,
+                dry_run: false,
+            })
+        );
+
+        assert_matches!(
+            parse_from(["maa", "self", "uninstall", "--purge", "--dry-run"]).command,
+            Command::SelfC(SelfCommand::Uninstall {
+                purge: true,
+                dry_run: true,
+            })
+        );
+
+        assert_matches!(
+            parse_from(["maa", "self", "info"]).command,
+            Command::SelfC(SelfCommand::Info { json: false, .. })
+        );
+        assert_matches!(
+            parse_from(["maa", "self", "info", "--json", "alpha"]).command,
+            Command::SelfC(SelfCommand::Info {
+                json: true,
+                common: config::cli::maa_cli::CommonArgs {
+                    channel: Some(Channel::Alpha),
+                    ..
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn hot_update() {
+        assert_matches!(
+            parse_from(["maa", "hot-update"]).command,
+            Command::HotUpdate { auto: false }
+        );
+        assert_matches!(
+            parse_from(["maa", "hot-update", "--auto"]).command,
+            Command::HotUpdate { auto: true }
+        );
+    }
+
+    #[test]
+    fn doctor() {
+        assert_matches!(
+            parse_from(["maa", "doctor"]).command,
+            Command::Doctor { offline: false }
+        );
+        assert_matches!(
+            parse_from(["maa", "doctor", "--offline"]).command,
+            Command::Doctor { offline: true }
+        );
+    }
+
+    #[test]
+    fn migrate() {
+        assert_matches!(
+            parse_from(["maa", "migrate"]).command,
+            Command::Migrate { dry_run: false }
+        );
+        assert_matches!(
+            parse_from(["maa", "migrate", "--dry-run"]).command,
+            Command::Migrate { dry_run: true }
+        );
     }
 
     #[test]
     fn dir() {
         assert_matches!(
             parse_from(["maa", "dir", "data"]).command,
-            Command::Dir { dir: Dir::Data }
+            Command::Dir {
+                dir: Some(Dir::Data),
+                open: false
+            }
         );
         assert_matches!(
             parse_from(["maa", "dir", "library"]).command,
-            Command::Dir { dir: Dir::Library }
+            Command::Dir {
+                dir: Some(Dir::Library),
+                open: false
+            }
         );
         assert_matches!(
             parse_from(["maa", "dir", "lib"]).command,
-            Command::Dir { dir: Dir::Library }
+            Command::Dir {
+                dir: Some(Dir::Library),
+                open: false
+            }
         );
         assert_matches!(
             parse_from(["maa", "dir", "config"]).command,
-            Command::Dir { dir: Dir::Config }
+            Command::Dir {
+                dir: Some(Dir::Config),
+                open: false
+            }
         );
         assert_matches!(
             parse_from(["maa", "dir", "cache"]).command,
-            Command::Dir { dir: Dir::Cache }
+            Command::Dir {
+                dir: Some(Dir::Cache),
+                open: false
+            }
         );
         assert_matches!(
             parse_from(["maa", "dir", "resource"]).command,
-            Command::Dir { dir: Dir::Resource }
+            Command::Dir {
+                dir: Some(Dir::Resource),
+                open: false
+            }
         );
         assert_matches!(
             parse_from(["maa", "dir", "hot-update"]).command,
             Command::Dir {
-                dir: Dir::HotUpdate
+                dir: Some(Dir::HotUpdate),
+                ..
             }
         );
         assert_matches!(
             parse_from(["maa", "dir", "log"]).command,
-            Command::Dir { dir: Dir::Log }
+            Command::Dir {
+                dir: Some(Dir::Log),
+                open: false
+            }
         );
+        assert_matches!(
+            parse_from(["maa", "dir", "state"]).command,
+            Command::Dir {
+                dir: Some(Dir::State),
+                open: false
+            }
+        );
+        assert_matches!(
+            parse_from(["maa", "dir"]).command,
+            Command::Dir {
+                dir: None,
+                open: false
+            }
+        );
+        assert_matches!(
+            parse_from(["maa", "dir", "config", "--open"]).command,
+            Command::Dir {
+                dir: Some(Dir::Config),
+                open: true
+            }
+        );
+        assert!(CLI::try_parse_from(["maa", "dir", "--open"]).is_err());
     }
 
     #[test]
@@ -479,37 +1321,50 @@ mod test {
         assert_matches!(
             parse_from(["maa", "version"]).command,
             Command::Version {
-                component: Component::All
+                component: Component::All,
+                json: false,
             }
         );
         assert_matches!(
             parse_from(["maa", "version", "all"]).command,
             Command::Version {
-                component: Component::All
+                component: Component::All,
+                json: false,
             }
         );
         assert_matches!(
             parse_from(["maa", "version", "maa-cli"]).command,
             Command::Version {
-                component: Component::MaaCLI
+                component: Component::MaaCLI,
+                json: false,
             }
         );
         assert_matches!(
             parse_from(["maa", "version", "cli"]).command,
             Command::Version {
-                component: Component::MaaCLI
+                component: Component::MaaCLI,
+                json: false,
             }
         );
         assert_matches!(
             parse_from(["maa", "version", "maa-core"]).command,
             Command::Version {
-                component: Component::MaaCore
+                component: Component::MaaCore,
+                json: false,
             }
         );
         assert_matches!(
             parse_from(["maa", "version", "core"]).command,
             Command::Version {
-                component: Component::MaaCore
+                component: Component::MaaCore,
+                json: false,
+            }
+        );
+        assert_matches!(
+            parse_from(["maa", "version", "--json"]).command,
+            Command::Version {
+                component: Component::All,
+                json: true,
             }
         );
     }
@@ -519,15 +1374,84 @@ mod test {
         assert_matches!(
             parse_from(["maa", "run", "task"]).command,
             Command::Run {
-                task,
-                common: run::CommonArgs { .. },
+                task: Some(task),
+                task_file: None,
+                task_json: None,
+                show_profile_history: None,
+                watch: false,
+                watch_debounce: 500,
+                common: run::CommonArgs {
+                    profile_tasks: false,
+                    output: run::profile::OutputFormat::Text,
+                    ..
+                },
             } if task == "task"
         );
 
+        assert_matches!(
+            parse_from(["maa", "run", "task", "--profile-tasks", "--output", "json"]).command,
+            Command::Run {
+                common: run::CommonArgs {
+                    profile_tasks: true,
+                    output: run::profile::OutputFormat::Json,
+                    ..
+                },
+                ..
+            }
+        );
+        assert_matches!(
+            parse_from(["maa", "run", "--show-profile-history", "5"]).command,
+            Command::Run {
+                task: None,
+                show_profile_history: Some(5),
+                ..
+            }
+        );
+        assert!(
+            CLI::try_parse_from(["maa", "run", "task", "--show-profile-history", "5"]).is_err()
+        );
+
+        assert_matches!(
+            CLI::parse_from(["maa", "run", "task", "--watch", "--watch-debounce", "100"]).command,
+            Command::Run {
+                watch: true,
+                watch_debounce: 100,
+                ..
+            }
+        );
+        assert!(CLI::try_parse_from(["maa", "run", "task", "--watch-debounce", "100"]).is_err());
+
+        assert_matches!(
+            parse_from(["maa", "run", "--task-json", "{}"]).command,
+            Command::Run {
+                task: None,
+                task_json: Some(json),
+                ..
+            } if json == "{}"
+        );
+        assert!(CLI::try_parse_from(["maa", "run", "task", "--task-json", "{}"]).is_err());
+        assert!(
+            CLI::try_parse_from(["maa", "run", "--task-file", "path", "--task-json", "{}"])
+                .is_err()
+        );
+
+        assert_matches!(
+            parse_from(["maa", "run", "--task-file", "/dev/stdin"]).command,
+            Command::Run {
+                task: None,
+                task_file: Some(path),
+                task_json: None,
+                ..
+            } if path == Path::new("/dev/stdin")
+        );
+
+        assert!(CLI::try_parse_from(["maa", "run"]).is_err());
+        assert!(CLI::try_parse_from(["maa", "run", "task", "--task-file", "path"]).is_err());
+
         assert!(matches!(
             parse_from(["maa", "run", "task", "-a", "addr"]).command,
             Command::Run {
-                task,
+                task: Some(task),
                 common: run::CommonArgs {
                     addr: Some(addr),
                     ..
@@ -538,7 +1462,7 @@ mod test {
         assert!(matches!(
             parse_from(["maa", "run", "task", "--addr", "addr"]).command,
             Command::Run {
-                task,
+                task: Some(task),
                 common: run::CommonArgs {
                     addr: Some(addr),
                     ..
@@ -550,7 +1474,7 @@ mod test {
         assert!(matches!(
             parse_from(["maa", "run", "task", "--user-resource"]).command,
             Command::Run {
-                task,
+                task: Some(task),
                 common: run::CommonArgs {
                     user_resource: true,
                     ..
@@ -558,6 +1482,18 @@ mod test {
                 ..
             } if task == "task"
         ));
+
+        assert!(matches!(
+            parse_from(["maa", "run", "task", "--timeout", "3600"]).command,
+            Command::Run {
+                common: run::CommonArgs {
+                    timeout: Some(3600),
+                    ..
+                },
+                ..
+            }
+        ));
+        assert!(CLI::try_parse_from(["maa", "run", "task", "--timeout", "1h"]).is_err());
     }
 
     #[test]
@@ -666,7 +1602,7 @@ mod test {
                 input,
                 output: None,
                 format: None,
-            } if input == PathBuf::from("input.toml")
+            } if input == Path::new("input.toml")
         );
 
         assert_matches!(
@@ -674,7 +1610,7 @@ mod test {
             Command::Convert {
                 output: Some(output),
                 ..
-            } if output == PathBuf::from("output.json")
+            } if output == Path::new("output.json")
         );
 
         assert_matches!(
@@ -691,7 +1627,7 @@ mod test {
                 output: Some(output),
                 format: Some(config::Filetype::Yaml),
                 ..
-            } if output == PathBuf::from("output.json")
+            } if output == Path::new("output.json")
         );
     }
 
@@ -741,13 +1677,28 @@ mod test {
 
         assert_matches!(
             parse_from(["maa", "cleanup", "log"]).command,
-            Command::Cleanup { targets } if targets == vec![Log]
+            Command::Cleanup { targets, .. } if targets == vec![Log]
         );
 
         assert_matches!(
             parse_from(["maa", "cleanup", "cli-cache", "log"]).command,
-            Command::Cleanup { targets } if targets == vec![CliCache, Log]
+            Command::Cleanup { targets, .. } if targets == vec![CliCache, Log]
+        );
+
+        assert_matches!(
+            parse_from(["maa", "cleanup", "--report"]).command,
+            Command::Cleanup { targets, report: true, older_than: None, max_size: None }
+                if targets.is_empty()
+        );
+
+        assert_matches!(
+            parse_from(["maa", "cleanup", "--older-than", "30d", "--max-size", "2G"]).command,
+            Command::Cleanup { report: false, older_than: Some(age), max_size: Some(size), .. }
+                if age == std::time::Duration::from_secs(30 * 86400) && size == 2 << 30
         );
+
+        assert!(CLI::try_parse_from(["maa", "cleanup", "log", "--report"]).is_err());
+        assert!(CLI::try_parse_from(["maa", "cleanup", "--older-than", "30y"]).is_err());
     }
 
     #[test]
@@ -755,6 +1706,137 @@ mod test {
         assert_matches!(parse_from(["maa", "list"]).command, Command::List);
     }
 
+    #[test]
+    fn task_command() {
+        assert_matches!(
+            parse_from(["maa", "task", "validate-all"]).command,
+            Command::Task(TaskCommand::ValidateAll {
+                dir: None,
+                output: config::validate::OutputFormat::Text,
+            })
+        );
+
+        assert_matches!(
+            parse_from(["maa", "task", "validate-all", "--dir", "path", "--output", "json"]).command,
+            Command::Task(TaskCommand::ValidateAll {
+                dir: Some(dir),
+                output: config::validate::OutputFormat::Json,
+            }) if dir == Path::new("path")
+        );
+
+        #[cfg(feature = "__installer")]
+        {
+            assert_matches!(
+                parse_from(["maa", "task", "import", "https://example.com/daily.toml"]).command,
+                Command::Task(TaskCommand::Import { url, force: false })
+                    if url == "https://example.com/daily.toml"
+            );
+
+            assert_matches!(
+                parse_from([
+                    "maa",
+                    "task",
+                    "import",
+                    "-f",
+                    "https://example.com/daily.toml"
+                ])
+                .command,
+                Command::Task(TaskCommand::Import { force: true, .. })
+            );
+        }
+
+        assert_matches!(
+            parse_from(["maa", "task", "export", "daily"]).command,
+            Command::Task(TaskCommand::Export {
+                name,
+                output: None,
+                format: None,
+                strip_secrets: false,
+            }) if name == "daily"
+        );
+
+        assert_matches!(
+            parse_from([
+                "maa",
+                "task",
+                "export",
+                "daily",
+                "-o",
+                "daily.yml",
+                "--format",
+                "yaml",
+                "--strip-secrets"
+            ])
+            .command,
+            Command::Task(TaskCommand::Export {
+                output: Some(output),
+                format: Some(config::Filetype::Yaml),
+                strip_secrets: true,
+                ..
+            }) if output == Path::new("daily.yml")
+        );
+
+        assert_matches!(
+            parse_from(["maa", "task", "new", "--type", "fight"]).command,
+            Command::Task(TaskCommand::New {
+                task_type: maa_sys::TaskType::Fight,
+                output: None,
+                format: None,
+            })
+        );
+        assert_matches!(
+            parse_from(["maa", "task", "new", "--type", "Copilot", "-o", "copilot.json"]).command,
+            Command::Task(TaskCommand::New {
+                task_type: maa_sys::TaskType::Copilot,
+                output: Some(output),
+                ..
+            }) if output == Path::new("copilot.json")
+        );
+        assert!(CLI::try_parse_from(["maa", "task", "new", "--type", "Unknown"]).is_err());
+        assert!(CLI::try_parse_from(["maa", "task", "new"]).is_err());
+    }
+
+    #[test]
+    fn config() {
+        assert_matches!(
+            parse_from(["maa", "config", "watch", "daily.toml"]).command,
+            Command::Config(ConfigCommand::Watch {
+                path,
+                debounce: 200,
+            }) if path == Path::new("daily.toml")
+        );
+        assert_matches!(
+            parse_from(["maa", "config", "watch", "daily.toml", "--debounce", "50"]).command,
+            Command::Config(ConfigCommand::Watch { debounce: 50, .. })
+        );
+        assert!(CLI::try_parse_from(["maa", "task", "watch", "daily.toml"]).is_err());
+        assert!(CLI::try_parse_from(["maa", "config", "export", "daily"]).is_err());
+
+        #[cfg(feature = "__installer")]
+        {
+            assert_matches!(
+                parse_from(["maa", "config", "check-updates"]).command,
+                Command::Config(ConfigCommand::CheckUpdates { registry: None })
+            );
+            assert_matches!(
+                parse_from([
+                    "maa",
+                    "config",
+                    "update",
+                    "daily",
+                    "--registry",
+                    "https://example.com/registry.json"
+                ])
+                .command,
+                Command::Config(ConfigCommand::Update {
+                    name,
+                    registry: Some(registry),
+                }) if name == "daily" && registry == "https://example.com/registry.json"
+            );
+            assert!(CLI::try_parse_from(["maa", "config", "update"]).is_err());
+        }
+    }
+
     #[test]
     fn import() {
         assert_matches!(
@@ -763,7 +1845,7 @@ mod test {
                 path,
                 force: false,
                 config_type,
-            } if path == PathBuf::from("path") && config_type == "task"
+            } if path == Path::new("path") && config_type == "task"
         );
 
         assert_matches!(
@@ -796,7 +1878,7 @@ mod test {
             Command::Init {
                 name: Some(name),
                 ..
-            } if name == PathBuf::from("name")
+            } if name == Path::new("name")
         );
 
         assert_matches!(
@@ -825,8 +1907,29 @@ mod test {
     fn complete() {
         assert_matches!(
             parse_from(["maa", "complete", "bash"]).command,
-            Command::Complete { shell: Shell::Bash }
+            Command::Complete {
+                shell: Some(Shell::Bash),
+                install: false,
+            }
+        );
+
+        assert_matches!(
+            parse_from(["maa", "complete", "--install"]).command,
+            Command::Complete {
+                shell: None,
+                install: true,
+            }
+        );
+
+        assert_matches!(
+            parse_from(["maa", "complete", "zsh", "--install"]).command,
+            Command::Complete {
+                shell: Some(Shell::Zsh),
+                install: true,
+            }
         );
+
+        assert!(CLI::try_parse_from(["maa", "complete"]).is_err());
     }
 
     #[test]
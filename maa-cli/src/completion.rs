@@ -0,0 +1,241 @@
+use crate::{
+    dirs::{self, Ensure},
+    value::userinput::{SelectD, UserInput, ValueWithDesc},
+};
+
+use std::{
+    ffi::OsString,
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use clap::{Command, ValueEnum};
+use clap_complete::Shell;
+use log::info;
+
+/// Generate completion script for given shell and write it to stdout.
+pub fn generate(shell: Shell, cmd: &mut Command) {
+    clap_complete::generate(shell, cmd, "maa", &mut std::io::stdout());
+}
+
+/// Generate completion script and install it to the location where the shell can find it.
+///
+/// If `shell` is not specified, detect it from the environment,
+/// and ask the user to select one if the detection failed.
+pub fn install(shell: Option<Shell>, cmd: &mut Command) -> Result<()> {
+    let shell = match shell.or_else(|| detect_shell(|key| std::env::var_os(key), cfg!(windows))) {
+        Some(shell) => shell,
+        None => select_shell()?,
+    };
+
+    let path = completion_path(shell, dirs::home(), |key| std::env::var_os(key));
+    if let Some(parent) = path.parent() {
+        parent.ensure()?;
+    }
+
+    let mut buf = Vec::new();
+    clap_complete::generate(shell, cmd, "maa", &mut buf);
+    File::create(&path)
+        .and_then(|mut file| file.write_all(&buf))
+        .with_context(|| format!("Failed to write completion script to {}", path.display()))?;
+
+    info!("Installed {} completion to {}", shell, path.display());
+    if let Some(hint) = source_hint(shell, &path) {
+        println!("{hint}");
+    }
+
+    Ok(())
+}
+
+/// Detect current shell from environment variables.
+///
+/// On Unix-like systems, the shell is detected from `$SHELL`.
+/// On Windows, PowerShell sets `PSModulePath` (the variable backing `$PSVersionTable`
+/// can not be read from a child process), while `COMSPEC` usually points to `cmd.exe`,
+/// which is not supported. Return `None` if the shell can not be determined.
+fn detect_shell(env: impl Fn(&str) -> Option<OsString>, windows: bool) -> Option<Shell> {
+    if let Some(shell) = env("SHELL") {
+        return Shell::from_shell_path(shell);
+    }
+
+    if windows {
+        if env("PSModulePath").is_some() {
+            return Some(Shell::PowerShell);
+        }
+        if let Some(comspec) = env("COMSPEC") {
+            return Shell::from_shell_path(comspec);
+        }
+    }
+
+    None
+}
+
+fn select_shell() -> Result<Shell> {
+    let shell = SelectD::<String>::new(
+        Shell::value_variants()
+            .iter()
+            .map(|shell| ValueWithDesc::new(shell.to_string(), None)),
+        None,
+        Some("your shell (failed to detect it automatically)"),
+        false,
+    )?
+    .value()?;
+
+    Shell::from_str(&shell, true).map_err(|err| anyhow::anyhow!(err))
+}
+
+/// Path where the completion script of given shell should be installed.
+fn completion_path(shell: Shell, home: &Path, env: impl Fn(&str) -> Option<OsString>) -> PathBuf {
+    let xdg_dir = |xdg_env: &str, default: &[&str]| {
+        env(xdg_env)
+            .map(PathBuf::from)
+            .filter(|path| path.is_absolute())
+            .unwrap_or_else(|| default.iter().fold(home.to_path_buf(), |p, s| p.join(s)))
+    };
+
+    match shell {
+        Shell::Bash => xdg_dir("XDG_DATA_HOME", &[".local", "share"])
+            .join("bash-completion")
+            .join("completions")
+            .join("maa"),
+        Shell::Zsh => home.join(".zfunc").join("_maa"),
+        Shell::Fish => xdg_dir("XDG_CONFIG_HOME", &[".config"])
+            .join("fish")
+            .join("completions")
+            .join("maa.fish"),
+        Shell::Elvish => xdg_dir("XDG_CONFIG_HOME", &[".config"])
+            .join("elvish")
+            .join("lib")
+            .join("maa.elv"),
+        _ => dirs::data().join("completions").join("maa.ps1"),
+    }
+}
+
+/// Instructions to load the installed completion script, if the shell does not load it itself.
+fn source_hint(shell: Shell, path: &Path) -> Option<String> {
+    match shell {
+        Shell::Bash => Some(format!(
+            "The completion is loaded automatically if bash-completion is installed, \
+             otherwise add `source {}` to ~/.bashrc",
+            path.display()
+        )),
+        Shell::Zsh => Some(format!(
+            "Add `fpath+={}` before `compinit` in ~/.zshrc",
+            path.parent().unwrap_or(path).display()
+        )),
+        Shell::Elvish => Some("Add `use maa` to ~/.config/elvish/rc.elv".to_owned()),
+        Shell::PowerShell => Some(format!(
+            "Add `. \"{}\"` to your PowerShell profile ($PROFILE)",
+            path.display()
+        )),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env_from<'a>(vars: &'a [(&str, &str)]) -> impl Fn(&str) -> Option<OsString> + 'a {
+        move |key| {
+            vars.iter()
+                .find(|(k, _)| *k == key)
+                .map(|(_, v)| OsString::from(v))
+        }
+    }
+
+    #[test]
+    fn detect() {
+        assert_eq!(
+            detect_shell(env_from(&[("SHELL", "/bin/bash")]), false),
+            Some(Shell::Bash)
+        );
+        assert_eq!(
+            detect_shell(env_from(&[("SHELL", "/usr/bin/zsh")]), false),
+            Some(Shell::Zsh)
+        );
+        assert_eq!(
+            detect_shell(env_from(&[("SHELL", "/opt/homebrew/bin/fish")]), false),
+            Some(Shell::Fish)
+        );
+        assert_eq!(
+            detect_shell(env_from(&[("SHELL", "/bin/tcsh")]), false),
+            None
+        );
+        assert_eq!(detect_shell(env_from(&[]), false), None);
+
+        // PowerShell on Windows
+        assert_eq!(
+            detect_shell(
+                env_from(&[
+                    ("PSModulePath", "C:\\Program Files\\PowerShell\\Modules"),
+                    ("COMSPEC", "C:\\Windows\\system32\\cmd.exe"),
+                ]),
+                true
+            ),
+            Some(Shell::PowerShell)
+        );
+        // cmd.exe is not supported, ask the user
+        assert_eq!(
+            detect_shell(
+                env_from(&[("COMSPEC", "C:\\Windows\\system32\\cmd.exe")]),
+                true
+            ),
+            None
+        );
+        // Bash on Windows (e.g. Git Bash) sets SHELL
+        assert_eq!(
+            detect_shell(
+                env_from(&[
+                    ("SHELL", "/usr/bin/bash"),
+                    ("COMSPEC", "C:\\Windows\\system32\\cmd.exe"),
+                ]),
+                true
+            ),
+            Some(Shell::Bash)
+        );
+    }
+
+    #[test]
+    fn path() {
+        let home = Path::new("/home/user");
+
+        assert_eq!(
+            completion_path(Shell::Bash, home, env_from(&[])),
+            home.join(".local/share/bash-completion/completions/maa")
+        );
+        assert_eq!(
+            completion_path(
+                Shell::Bash,
+                home,
+                env_from(&[("XDG_DATA_HOME", "/xdg/data")])
+            ),
+            Path::new("/xdg/data/bash-completion/completions/maa")
+        );
+        // relative XDG paths are invalid and ignored
+        assert_eq!(
+            completion_path(Shell::Fish, home, env_from(&[("XDG_CONFIG_HOME", "rel")])),
+            home.join(".config/fish/completions/maa.fish")
+        );
+        assert_eq!(
+            completion_path(Shell::Zsh, home, env_from(&[])),
+            home.join(".zfunc/_maa")
+        );
+        assert_eq!(
+            completion_path(Shell::Elvish, home, env_from(&[])),
+            home.join(".config/elvish/lib/maa.elv")
+        );
+    }
+
+    #[test]
+    fn hint() {
+        let path = Path::new("/home/user/.zfunc/_maa");
+        assert_eq!(
+            source_hint(Shell::Zsh, path).unwrap(),
+            "Add `fpath+=/home/user/.zfunc` before `compinit` in ~/.zshrc"
+        );
+        assert!(source_hint(Shell::Fish, path).is_none());
+    }
+}
@@ -92,7 +92,7 @@ impl ConnectionConfig {
         self
     }
 
-    pub fn connect_args(&self) -> (&str, Cow<str>, &str) {
+    pub fn connect_args(&self) -> (&str, Cow<'_, str>, &str) {
         let adb_path = self
             .adb_path
             .as_deref()
@@ -282,9 +282,9 @@ impl Default for ResourceConfig {
 fn default_resource_base_dirs() -> Vec<PathBuf> {
     let mut resource_dirs = Vec::new();
 
-    if let Some(resource_dir) = dirs::find_resource() {
-        debug!("Found resource directory: {}", resource_dir.display());
-        resource_dirs.push(resource_dir.into_owned());
+    if let Some(resource_dir) = crate::locate::find_resource() {
+        debug!("Found resource directory: {resource_dir}");
+        resource_dirs.push(resource_dir.into_path());
     } else {
         warn!("Resource directory not found!")
     }
@@ -1010,7 +1010,7 @@ mod tests {
                 ResourceConfig {
                     platform_diff_resource: Some(path),
                     ..
-                } if *path == PathBuf::from("iOS")
+                } if path.as_path() == std::path::Path::new("iOS")
             );
 
             assert_matches!(
@@ -1021,7 +1021,7 @@ mod tests {
                 ResourceConfig {
                     platform_diff_resource: Some(path),
                     ..
-                } if *path == PathBuf::from("iOS")
+                } if path.as_path() == std::path::Path::new("iOS")
             );
         }
 
@@ -1048,7 +1048,7 @@ mod tests {
 
             assert_eq!(
                 push_resource(&mut Vec::new(), resource_dir.clone()),
-                &[resource_dir.clone()]
+                std::slice::from_ref(&resource_dir)
             );
 
             assert_eq!(
@@ -1081,7 +1081,7 @@ mod tests {
                     ..Default::default()
                 }
                 .resource_dirs(),
-                [resource_dir.clone()]
+                std::slice::from_ref(&resource_dir)
             );
 
             assert_eq!(
@@ -1101,7 +1101,7 @@ mod tests {
                     ..Default::default()
                 }
                 .resource_dirs(),
-                [resource_dir.clone()]
+                std::slice::from_ref(&resource_dir)
             );
 
             assert_eq!(
@@ -1121,7 +1121,7 @@ mod tests {
                     ..Default::default()
                 }
                 .resource_dirs(),
-                [resource_dir.clone()]
+                std::slice::from_ref(&resource_dir)
             );
 
             fs::remove_dir_all(test_root).unwrap();
@@ -1,8 +1,11 @@
-use super::{normalize_url, return_true, Channel};
+use super::{deserialize_rate, normalize_url, parse_rate, return_true, Channel, IpFamily};
 
 use clap::Args;
 use serde::Deserialize;
 
+/// Environment variable to override the api url in the config file
+pub const API_URL_ENV: &str = "MAA_CLI_API";
+
 #[cfg_attr(test, derive(Debug, PartialEq))]
 #[derive(Deserialize, Clone)]
 pub struct Config {
@@ -14,6 +17,14 @@ pub struct Config {
     download_url: String,
     #[serde(default)]
     components: CLIComponents,
+    #[serde(default)]
+    update_check: UpdateCheck,
+    /// Maximum download rate in bytes per second
+    #[serde(default, deserialize_with = "deserialize_rate")]
+    limit_rate: Option<u64>,
+    /// Address family to connect to servers
+    #[serde(default)]
+    ip_family: IpFamily,
 }
 
 impl Default for Config {
@@ -23,6 +34,9 @@ impl Default for Config {
             api_url: default_api_url(),
             download_url: default_download_url(),
             components: Default::default(),
+            update_check: Default::default(),
+            limit_rate: None,
+            ip_family: IpFamily::Auto,
         }
     }
 }
@@ -41,6 +55,11 @@ impl Config {
         format!("{}{}.json", normalize_url(&self.api_url), self.channel())
     }
 
+    /// Whether the api url is the default one, i.e. not overridden by user.
+    pub fn is_default_api_url(&self) -> bool {
+        normalize_url(&self.api_url) == normalize_url(&default_api_url())
+    }
+
     pub fn set_api_url(&mut self, api_url: impl ToString) -> &mut Self {
         self.api_url = api_url.to_string();
         self
@@ -50,6 +69,11 @@ impl Config {
         format!("{}{}/{}", normalize_url(&self.download_url), tag, name)
     }
 
+    /// Base url of downloads, which is joined with tag and name of assets.
+    pub fn download_base(&self) -> String {
+        normalize_url(&self.download_url)
+    }
+
     pub fn set_download_url(&mut self, download_url: impl ToString) -> &mut Self {
         self.download_url = download_url.to_string();
         self
@@ -59,6 +83,28 @@ impl Config {
         &self.components
     }
 
+    pub fn update_check(&self) -> &UpdateCheck {
+        &self.update_check
+    }
+
+    pub fn limit_rate(&self) -> Option<u64> {
+        self.limit_rate
+    }
+
+    pub fn set_limit_rate(&mut self, limit_rate: u64) -> &mut Self {
+        self.limit_rate = Some(limit_rate);
+        self
+    }
+
+    pub fn ip_family(&self) -> IpFamily {
+        self.ip_family
+    }
+
+    pub fn set_ip_family(&mut self, ip_family: IpFamily) -> &mut Self {
+        self.ip_family = ip_family;
+        self
+    }
+
     pub fn with_args(mut self, args: &CommonArgs) -> Self {
         if let Some(channel) = args.channel {
             self.set_channel(channel);
@@ -69,6 +115,12 @@ impl Config {
         if let Some(download_url) = args.download_url.as_ref() {
             self.set_download_url(download_url);
         }
+        if let Some(limit_rate) = args.limit_rate {
+            self.set_limit_rate(limit_rate);
+        }
+        if let Some(ip_family) = IpFamily::from_flags(args.ipv4, args.ipv6) {
+            self.set_ip_family(ip_family);
+        }
         self
     }
 }
@@ -84,6 +136,7 @@ pub struct CommonArgs {
     /// Url of api to get version information
     ///
     /// This flag is used to set the URL of api to get version information.
+    /// It can also be changed by environment variable `MAA_CLI_API`.
     /// Default to <https://github.com/MaaAssistantArknights/maa-cli/raw/version/>.
     #[arg(long)]
     pub api_url: Option<String>,
@@ -93,6 +146,42 @@ pub struct CommonArgs {
     /// Default to <https://github.com/MaaAssistantArknights/maa-cli/releases/download/>.
     #[arg(long)]
     pub download_url: Option<String>,
+    /// Do not check available disk space before downloading
+    ///
+    /// Some filesystems (e.g. network mounts) may report wrong available space,
+    /// use this flag to skip the check.
+    #[arg(long)]
+    pub no_space_check: bool,
+    /// Fail if the checksum of downloaded binary is not available
+    ///
+    /// The checksum is read from the version information, or the `.sha256` file
+    /// published alongside the binary. By default, the download is not verified
+    /// with a warning if neither is available.
+    #[arg(long)]
+    pub require_checksum: bool,
+    /// Maximum download rate, e.g. `500k` or `2M` (bytes per second)
+    ///
+    /// It can also be configured by `cli.limit_rate` in the cli configure file.
+    #[arg(long, value_parser = parse_rate)]
+    pub limit_rate: Option<u64>,
+    /// Only connect to servers over IPv4
+    ///
+    /// This is useful when the IPv6 route to servers is broken, which makes
+    /// connections time out. It can also be configured by `cli.ip_family`
+    /// in the cli configure file.
+    #[arg(long, conflicts_with = "ipv6")]
+    pub ipv4: bool,
+    /// Only connect to servers over IPv6
+    ///
+    /// It can also be configured by `cli.ip_family` in the cli configure file.
+    #[arg(long)]
+    pub ipv6: bool,
+    /// Follow redirects from https to http
+    ///
+    /// Such redirects are refused by default, because the content may be tampered with
+    /// over plain http. Use this flag only if you trust the mirror.
+    #[arg(long)]
+    pub allow_insecure_redirect: bool,
 }
 
 fn default_api_url() -> String {
@@ -116,6 +205,31 @@ impl Default for CLIComponents {
     }
 }
 
+/// Check update of maa-cli in background when running other commands
+#[cfg_attr(test, derive(Debug, PartialEq))]
+#[derive(Deserialize, Clone)]
+pub struct UpdateCheck {
+    /// Whether to check update, disabled by default
+    #[serde(default)]
+    pub enable: bool,
+    /// Minimum interval between two checks in hours
+    #[serde(default = "default_check_interval")]
+    pub interval: u64,
+}
+
+impl Default for UpdateCheck {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            interval: default_check_interval(),
+        }
+    }
+}
+
+fn default_check_interval() -> u64 {
+    24
+}
+
 #[cfg(test)]
 pub mod tests {
     use super::*;
@@ -128,6 +242,9 @@ pub mod tests {
             download_url: "https://github.com/MaaAssistantArknights/maa-cli/releases/download/"
                 .to_string(),
             components: CLIComponents { binary: false },
+            update_check: UpdateCheck::default(),
+            limit_rate: None,
+            ip_family: IpFamily::Auto,
         }
     }
 
@@ -153,6 +270,31 @@ pub mod tests {
             );
         }
 
+        #[test]
+        fn deserialize_update_check() {
+            assert_de_tokens(
+                &UpdateCheck {
+                    enable: false,
+                    interval: 24,
+                },
+                &[Token::Map { len: Some(0) }, Token::MapEnd],
+            );
+            assert_de_tokens(
+                &UpdateCheck {
+                    enable: true,
+                    interval: 12,
+                },
+                &[
+                    Token::Map { len: Some(2) },
+                    Token::Str("enable"),
+                    Token::Bool(true),
+                    Token::Str("interval"),
+                    Token::U64(12),
+                    Token::MapEnd,
+                ],
+            );
+        }
+
         #[test]
         fn deserialize_config() {
             assert_de_tokens(
@@ -161,6 +303,9 @@ pub mod tests {
                     api_url: "https://foo.bar/api/".to_owned(),
                     download_url: "https://foo.bar/download/".to_owned(),
                     components: CLIComponents { binary: false },
+                    update_check: UpdateCheck::default(),
+                    limit_rate: None,
+                    ip_family: IpFamily::Auto,
                 },
                 &[
                     Token::Map { len: Some(4) },
@@ -223,6 +368,17 @@ pub mod tests {
             );
         }
 
+        #[test]
+        fn is_default_api_url() {
+            assert!(Config::default().is_default_api_url());
+            assert!(Config::default()
+                .set_api_url("https://github.com/MaaAssistantArknights/maa-cli/raw/version")
+                .is_default_api_url());
+            assert!(!Config::default()
+                .set_api_url("https://foo.bar/cli/")
+                .is_default_api_url());
+        }
+
         #[test]
         fn download_url() {
             assert_eq!(
@@ -236,6 +392,13 @@ pub mod tests {
                     .download_url("v0.3.12", "maa_cli.zip"),
                 "https://foo.bar/download/v0.3.12/maa_cli.zip",
             );
+
+            assert_eq!(
+                Config::default()
+                    .set_download_url("https://foo.bar/download")
+                    .download_base(),
+                "https://foo.bar/download/",
+            );
         }
 
         #[test]
@@ -262,6 +425,12 @@ pub mod tests {
                     channel: None,
                     api_url: None,
                     download_url: None,
+                    no_space_check: false,
+                    require_checksum: false,
+                    limit_rate: None,
+                    ipv4: false,
+                    ipv6: false,
+                    allow_insecure_redirect: false,
                 }),
                 Config::default(),
             );
@@ -271,6 +440,12 @@ pub mod tests {
                     channel: Some(Channel::Alpha),
                     api_url: Some("https://foo.bar/api/".to_string()),
                     download_url: Some("https://foo.bar/download/".to_string()),
+                    no_space_check: false,
+                    require_checksum: false,
+                    limit_rate: None,
+                    ipv4: false,
+                    ipv6: false,
+                    allow_insecure_redirect: false,
                 }),
                 Config {
                     channel: Channel::Alpha,
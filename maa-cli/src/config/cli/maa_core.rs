@@ -1,4 +1,6 @@
-use super::{normalize_url, return_true, Channel};
+use super::{
+    deserialize_rate, normalize_url, parse_rate, paths::CorePaths, return_true, Channel, IpFamily,
+};
 
 use clap::Args;
 use serde::Deserialize;
@@ -14,6 +16,31 @@ pub struct Config {
     api_url: String,
     #[serde(default)]
     components: Components,
+    /// Maximum download rate in bytes per second
+    #[serde(default, deserialize_with = "deserialize_rate")]
+    limit_rate: Option<u64>,
+    /// Address family to connect to servers
+    #[serde(default)]
+    ip_family: IpFamily,
+    /// Update resources from the resource package before running tasks,
+    /// deprecated in favor of `auto_update = "resource"`
+    #[serde(default)]
+    auto_update_resource: bool,
+    /// Components to update before running tasks
+    #[serde(default)]
+    auto_update: Option<AutoUpdate>,
+    /// Do not run tasks if the automatic update failed
+    #[serde(default)]
+    require_fresh: bool,
+    /// Shell command run before downloading an update
+    #[serde(default)]
+    pre_update_hook: Option<String>,
+    /// Shell command run after an update is applied
+    #[serde(default)]
+    post_update_hook: Option<String>,
+    /// Paths of MaaCore not managed by the installer
+    #[serde(flatten)]
+    paths: CorePaths,
 }
 
 impl Default for Config {
@@ -23,6 +50,14 @@ impl Default for Config {
             test_time: default_test_time(),
             api_url: default_api_url(),
             components: Default::default(),
+            limit_rate: None,
+            ip_family: IpFamily::Auto,
+            auto_update_resource: false,
+            auto_update: None,
+            require_fresh: false,
+            pre_update_hook: None,
+            post_update_hook: None,
+            paths: CorePaths::default(),
         }
     }
 }
@@ -64,6 +99,61 @@ impl Config {
         self
     }
 
+    pub fn limit_rate(&self) -> Option<u64> {
+        self.limit_rate
+    }
+
+    pub fn set_limit_rate(&mut self, limit_rate: u64) -> &Self {
+        self.limit_rate = Some(limit_rate);
+        self
+    }
+
+    pub fn ip_family(&self) -> IpFamily {
+        self.ip_family
+    }
+
+    pub fn set_ip_family(&mut self, ip_family: IpFamily) -> &Self {
+        self.ip_family = ip_family;
+        self
+    }
+
+    /// Components to update before running tasks.
+    ///
+    /// If `auto_update` is not set, the deprecated `auto_update_resource` is respected.
+    pub fn auto_update(&self) -> AutoUpdate {
+        self.auto_update.unwrap_or(if self.auto_update_resource {
+            AutoUpdate::Resource
+        } else {
+            AutoUpdate::Never
+        })
+    }
+
+    pub fn require_fresh(&self) -> bool {
+        self.require_fresh
+    }
+
+    pub fn paths(&self) -> &CorePaths {
+        &self.paths
+    }
+
+    pub fn pre_update_hook(&self) -> Option<&str> {
+        self.pre_update_hook.as_deref()
+    }
+
+    pub fn post_update_hook(&self) -> Option<&str> {
+        self.post_update_hook.as_deref()
+    }
+
+    pub fn apply_hooks(mut self, hooks: &HookArgs) -> Self {
+        if let Some(hook) = &hooks.pre_update_hook {
+            self.pre_update_hook = Some(hook.clone());
+        }
+        if let Some(hook) = &hooks.post_update_hook {
+            self.post_update_hook = Some(hook.clone());
+        }
+        self
+    }
+
     pub fn apply_args(mut self, args: &CommonArgs) -> Self {
         if let Some(channel) = args.channel {
             self.set_channel(channel);
@@ -77,6 +167,12 @@ impl Config {
         if args.no_resource {
             self.set_components(|components| components.resource = false);
         }
+        if let Some(limit_rate) = args.limit_rate {
+            self.set_limit_rate(limit_rate);
+        }
+        if let Some(ip_family) = IpFamily::from_flags(args.ipv4, args.ipv6) {
+            self.set_ip_family(ip_family);
+        }
         self
     }
 }
@@ -89,6 +185,38 @@ fn default_api_url() -> String {
     String::from("https://ota.maa.plus/MaaAssistantArknights/api/version/")
 }
 
+/// Components updated automatically before running tasks
+#[cfg_attr(test, derive(Debug))]
+#[derive(Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum AutoUpdate {
+    #[default]
+    Never,
+    /// Resources from the resource package
+    Resource,
+    /// MaaCore and resources shipped with it
+    Core,
+    /// MaaCore and then resources from the resource package
+    All,
+}
+
+impl AutoUpdate {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Never => "never",
+            Self::Resource => "resource",
+            Self::Core => "core",
+            Self::All => "all",
+        }
+    }
+}
+
+impl std::fmt::Display for AutoUpdate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 #[cfg_attr(test, derive(Debug, PartialEq))]
 #[derive(Deserialize, Clone)]
 pub struct Components {
@@ -153,6 +281,57 @@ pub struct CommonArgs {
     /// It can also be changed by environment variable `MAA_API_URL`.
     #[arg(long)]
     pub api_url: Option<String>,
+    /// Do not check available disk space before downloading
+    ///
+    /// By default, we check whether there is enough space to download and extract
+    /// the package before downloading. Some filesystems (e.g. network mounts)
+    /// may report wrong available space, use this flag to skip the check.
+    #[arg(long)]
+    pub no_space_check: bool,
+    /// Maximum download rate, e.g. `500k` or `2M` (bytes per second)
+    ///
+    /// It can also be configured by `core.limit_rate` in the cli configure file.
+    #[arg(long, value_parser = parse_rate)]
+    pub limit_rate: Option<u64>,
+    /// Only connect to servers over IPv4
+    ///
+    /// This is useful when the IPv6 route to servers is broken, which makes
+    /// connections time out. It can also be configured by `core.ip_family`
+    /// in the cli configure file.
+    #[arg(long, conflicts_with = "ipv6")]
+    pub ipv4: bool,
+    /// Only connect to servers over IPv6
+    ///
+    /// It can also be configured by `core.ip_family` in the cli configure file.
+    #[arg(long)]
+    pub ipv6: bool,
+    /// Follow redirects from https to http
+    ///
+    /// Such redirects are refused by default, because the content may be tampered with
+    /// over plain http. Use this flag only if you trust the mirror.
+    #[arg(long)]
+    pub allow_insecure_redirect: bool,
+}
+
+/// Hooks run when updating MaaCore or resources
+#[derive(Args, Default)]
+pub struct HookArgs {
+    /// Shell command to run before downloading an update
+    ///
+    /// The command is only run if an update is going to be applied, e.g. to stop
+    /// a running daemon, and the update is aborted if the command fails.
+    /// The versions are passed by `MAA_OLD_VERSION` and `MAA_NEW_VERSION`.
+    /// It can also be configured by `core.pre_update_hook` in the cli configure file.
+    #[arg(long, value_name = "COMMAND")]
+    pub pre_update_hook: Option<String>,
+    /// Shell command to run after an update is applied
+    ///
+    /// The command is not run if everything is up to date, e.g. to reload a daemon
+    /// or send a notification. The versions are passed by `MAA_OLD_VERSION` and
+    /// `MAA_NEW_VERSION`. Failures of the command are logged without failing the update.
+    /// It can also be configured by `core.post_update_hook` in the cli configure file.
+    #[arg(long, value_name = "COMMAND")]
+    pub post_update_hook: Option<String>,
 }
 
 #[cfg(test)]
@@ -170,6 +349,14 @@ pub mod tests {
                 library: true,
                 resource: true,
             },
+            limit_rate: None,
+            ip_family: IpFamily::Auto,
+            auto_update_resource: false,
+            auto_update: None,
+            require_fresh: false,
+            pre_update_hook: None,
+            post_update_hook: None,
+            paths: Default::default(),
         }
     }
 
@@ -219,6 +406,14 @@ pub mod tests {
                         library: true,
                         resource: true,
                     },
+                    limit_rate: None,
+                    ip_family: IpFamily::Auto,
+                    auto_update_resource: false,
+                    auto_update: None,
+                    require_fresh: false,
+                    pre_update_hook: None,
+                    post_update_hook: None,
+                    paths: Default::default(),
                 },
                 &[Token::Map { len: Some(0) }, Token::MapEnd],
             );
@@ -237,9 +432,17 @@ pub mod tests {
                         library: false,
                         resource: false,
                     },
+                    limit_rate: None,
+                    ip_family: IpFamily::Auto,
+                    auto_update_resource: true,
+                    auto_update: None,
+                    require_fresh: false,
+                    pre_update_hook: None,
+                    post_update_hook: None,
+                    paths: Default::default(),
                 },
                 &[
-                    Token::Map { len: Some(4) },
+                    Token::Map { len: Some(5) },
                     Token::Str("channel"),
                     Channel::Beta.to_token(),
                     Token::Str("test_time"),
@@ -253,6 +456,46 @@ pub mod tests {
                     Token::Str("resource"),
                     Token::Bool(false),
                     Token::MapEnd,
+                    Token::Str("auto_update_resource"),
+                    Token::Bool(true),
+                    Token::MapEnd,
+                ],
+            );
+
+            assert_de_tokens(
+                &Config {
+                    auto_update: Some(AutoUpdate::All),
+                    require_fresh: true,
+                    ..Default::default()
+                },
+                &[
+                    Token::Map { len: Some(2) },
+                    Token::Str("auto_update"),
+                    Token::Some,
+                    Token::UnitVariant {
+                        name: "AutoUpdate",
+                        variant: "all",
+                    },
+                    Token::Str("require_fresh"),
+                    Token::Bool(true),
+                    Token::MapEnd,
+                ],
+            );
+
+            assert_de_tokens(
+                &Config {
+                    pre_update_hook: Some("systemctl stop maa".to_owned()),
+                    post_update_hook: Some("systemctl start maa".to_owned()),
+                    ..Default::default()
+                },
+                &[
+                    Token::Map { len: Some(2) },
+                    Token::Str("pre_update_hook"),
+                    Token::Some,
+                    Token::Str("systemctl stop maa"),
+                    Token::Str("post_update_hook"),
+                    Token::Some,
+                    Token::Str("systemctl start maa"),
                     Token::MapEnd,
                 ],
             );
@@ -262,6 +505,48 @@ pub mod tests {
     mod methods {
         use super::*;
 
+        #[test]
+        fn auto_update() {
+            let config = |auto_update, auto_update_resource| Config {
+                auto_update,
+                auto_update_resource,
+                ..Default::default()
+            };
+            assert_eq!(config(None, false).auto_update(), AutoUpdate::Never);
+            assert_eq!(config(None, true).auto_update(), AutoUpdate::Resource);
+            assert_eq!(
+                config(Some(AutoUpdate::Core), true).auto_update(),
+                AutoUpdate::Core
+            );
+            assert_eq!(
+                config(Some(AutoUpdate::Never), true).auto_update(),
+                AutoUpdate::Never
+            );
+            assert!(!default_config().require_fresh());
+        }
+
+        #[test]
+        fn update_hooks() {
+            let config = Config {
+                pre_update_hook: Some("pre".to_owned()),
+                post_update_hook: Some("post".to_owned()),
+                ..Default::default()
+            };
+            assert_eq!(default_config().pre_update_hook(), None);
+            assert_eq!(default_config().post_update_hook(), None);
+
+            let config = config.apply_hooks(&HookArgs::default());
+            assert_eq!(config.pre_update_hook(), Some("pre"));
+            assert_eq!(config.post_update_hook(), Some("post"));
+
+            let config = config.apply_hooks(&HookArgs {
+                pre_update_hook: None,
+                post_update_hook: Some("notify".to_owned()),
+            });
+            assert_eq!(config.pre_update_hook(), Some("pre"));
+            assert_eq!(config.post_update_hook(), Some("notify"));
+        }
+
         #[test]
         fn channel() {
             assert_eq!(default_config().channel(), Channel::Stable);
@@ -364,12 +649,25 @@ pub mod tests {
                 })
             );
 
+            assert_eq!(
+                &apply_to_default(&CommonArgs {
+                    ipv6: true,
+                    ..Default::default()
+                }),
+                default_config().set_ip_family(IpFamily::Ipv6)
+            );
+
             assert_eq!(
                 apply_to_default(&CommonArgs {
                     channel: Some(Channel::Beta),
                     test_time: Some(5),
                     api_url: Some("https://foo.bar/maa_core/".to_string()),
                     no_resource: true,
+                    no_space_check: false,
+                    limit_rate: Some(1000),
+                    ipv4: true,
+                    ipv6: false,
+                    allow_insecure_redirect: false,
                 }),
                 Config {
                     channel: Channel::Beta,
@@ -379,6 +677,14 @@ pub mod tests {
                         resource: false,
                         ..Default::default()
                     },
+                    limit_rate: Some(1000),
+                    ip_family: IpFamily::Ipv4,
+                    auto_update_resource: false,
+                    auto_update: None,
+                    require_fresh: false,
+                    pre_update_hook: None,
+                    post_update_hook: None,
+                    paths: Default::default(),
                 }
             );
         }
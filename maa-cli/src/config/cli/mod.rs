@@ -3,10 +3,9 @@ pub mod maa_cli;
 #[cfg(feature = "core_installer")]
 pub mod maa_core;
 
+pub mod paths;
 pub mod resource;
 
-use super::FindFileOrDefault;
-
 use crate::dirs;
 
 use std::sync::OnceLock;
@@ -22,11 +21,23 @@ pub struct CLIConfig {
     #[cfg(feature = "core_installer")]
     #[serde(default)]
     core: maa_core::Config,
+    /// Paths of MaaCore, the only part of MaaCore configuration used without the installer
+    #[cfg(not(feature = "core_installer"))]
+    #[serde(default)]
+    core: paths::CorePaths,
     #[cfg(feature = "cli_installer")]
     #[serde(default)]
     cli: maa_cli::Config,
     #[serde(default)]
     resource: resource::Config,
+    /// User agent of the requests sent by the installers, overrides the default one
+    #[cfg(feature = "__installer")]
+    #[serde(default)]
+    user_agent: Option<String>,
+    /// URL of the registry of task config templates used by `maa config check-updates`
+    #[cfg(feature = "__installer")]
+    #[serde(default)]
+    config_registry: Option<String>,
 }
 
 impl CLIConfig {
@@ -35,14 +46,48 @@ impl CLIConfig {
         self.core.clone()
     }
 
+    pub fn core_paths(&self) -> &paths::CorePaths {
+        #[cfg(feature = "core_installer")]
+        return self.core.paths();
+        #[cfg(not(feature = "core_installer"))]
+        return &self.core;
+    }
+
     #[cfg(feature = "cli_installer")]
     pub fn cli_config(&self) -> maa_cli::Config {
-        self.cli.clone()
+        let mut config = self.cli.clone();
+        if let Some(api_url) = std::env::var(maa_cli::API_URL_ENV)
+            .ok()
+            .filter(|url| !url.is_empty())
+        {
+            config.set_api_url(api_url);
+        }
+        config
     }
 
     pub fn resource_config(&self) -> resource::Config {
         self.resource.clone()
     }
+
+    /// Address family of requests not made by an installer, e.g. downloading task configs,
+    /// which is `cli.ip_family` if the maa-cli installer is enabled, or `core.ip_family`.
+    #[cfg(feature = "__installer")]
+    pub fn ip_family(&self) -> IpFamily {
+        #[cfg(feature = "cli_installer")]
+        return self.cli.ip_family();
+        #[cfg(not(feature = "cli_installer"))]
+        return self.core.ip_family();
+    }
+
+    #[cfg(feature = "__installer")]
+    pub fn user_agent(&self) -> Option<&str> {
+        self.user_agent.as_deref()
+    }
+
+    #[cfg(feature = "__installer")]
+    pub fn config_registry(&self) -> Option<&str> {
+        self.config_registry.as_deref()
+    }
 }
 
 impl super::FromFile for CLIConfig {}
@@ -50,13 +95,20 @@ impl super::FromFile for CLIConfig {}
 pub fn cli_config() -> &'static CLIConfig {
     static INSTALLER_CONFIG: OnceLock<CLIConfig> = OnceLock::new();
     INSTALLER_CONFIG.get_or_init(|| {
-        CLIConfig::find_file_or_default(dirs::config().join("cli"))
-            .expect("Failed to load installer config")
+        let layered = super::layered::find::<CLIConfig>(&dirs::config_layers(), "cli")
+            .expect("Failed to load installer config");
+        match layered {
+            Some(layered) => {
+                layered.log_sources("cli config");
+                layered.config
+            }
+            None => CLIConfig::default(),
+        }
     })
 }
 
-#[cfg_attr(test, derive(Debug, PartialEq))]
-#[derive(ValueEnum, Clone, Copy, Default, Deserialize)]
+#[cfg_attr(test, derive(Debug))]
+#[derive(ValueEnum, Clone, Copy, Default, Deserialize, PartialEq)]
 pub enum Channel {
     #[default]
     #[serde(alias = "stable")]
@@ -77,10 +129,109 @@ impl std::fmt::Display for Channel {
     }
 }
 
+/// Address family used by the installers to connect to servers
+#[cfg(feature = "__installer")]
+#[cfg_attr(test, derive(Debug))]
+#[derive(Clone, Copy, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum IpFamily {
+    /// Use both IPv4 and IPv6, which one is preferred is decided by the system
+    #[default]
+    Auto,
+    /// Only connect over IPv4
+    Ipv4,
+    /// Only connect over IPv6
+    Ipv6,
+}
+
+#[cfg(feature = "__installer")]
+impl IpFamily {
+    /// Get the address family from the `--ipv4` and `--ipv6` flags, `None` if neither is given
+    pub fn from_flags(ipv4: bool, ipv6: bool) -> Option<Self> {
+        match (ipv4, ipv6) {
+            (true, _) => Some(Self::Ipv4),
+            (_, true) => Some(Self::Ipv6),
+            _ => None,
+        }
+    }
+
+    /// Local address to bind, connections are restricted to the family of the address
+    pub fn local_address(self) -> Option<std::net::IpAddr> {
+        use std::net::{Ipv4Addr, Ipv6Addr};
+        match self {
+            Self::Auto => None,
+            Self::Ipv4 => Some(Ipv4Addr::UNSPECIFIED.into()),
+            Self::Ipv6 => Some(Ipv6Addr::UNSPECIFIED.into()),
+        }
+    }
+}
+
+#[cfg(feature = "__installer")]
+impl std::fmt::Display for IpFamily {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IpFamily::Auto => write!(f, "IPv4 and IPv6"),
+            IpFamily::Ipv4 => write!(f, "IPv4 only"),
+            IpFamily::Ipv6 => write!(f, "IPv6 only"),
+        }
+    }
+}
+
 fn return_true() -> bool {
     true
 }
 
+/// Parse a download rate limit like `500k` or `2M` into bytes per second.
+///
+/// Suffixes `k`, `M` and `G` are decimal (case insensitive) and an optional trailing `B`
+/// is allowed, a bare number is in bytes. Fractions like `1.5M` are accepted.
+#[cfg(feature = "__installer")]
+pub fn parse_rate(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let s = s.strip_suffix(['B', 'b']).unwrap_or(s);
+    let (number, unit) = match s.char_indices().last() {
+        Some((i, c)) if c.is_ascii_alphabetic() => (&s[..i], c.to_ascii_lowercase()),
+        _ => (s, ' '),
+    };
+    let multiplier = match unit {
+        ' ' => 1,
+        'k' => 1_000,
+        'm' => 1_000_000,
+        'g' => 1_000_000_000,
+        _ => return Err(format!("unknown unit in rate `{s}`, expected k, M or G")),
+    };
+    let number: f64 = number
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid rate `{s}`, expected a value like `500k` or `2M`"))?;
+    let rate = (number * multiplier as f64).round();
+    if !rate.is_finite() || rate < 1.0 {
+        return Err(format!("rate `{s}` should be at least 1 byte per second"));
+    }
+    Ok(rate as u64)
+}
+
+#[cfg(feature = "__installer")]
+fn deserialize_rate<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Rate {
+        Bytes(u64),
+        Text(String),
+    }
+
+    match Option::<Rate>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(Rate::Bytes(bytes)) => Ok(Some(bytes)),
+        Some(Rate::Text(text)) => parse_rate(&text)
+            .map(Some)
+            .map_err(serde::de::Error::custom),
+    }
+}
+
 fn normalize_url(url: &str) -> String {
     if url.ends_with('/') {
         url.to_owned()
@@ -91,7 +242,7 @@ fn normalize_url(url: &str) -> String {
 
 #[cfg(test)]
 mod tests {
-    use super::{resource::GitBackend, *};
+    use super::*;
 
     use serde_json;
     use serde_test::{assert_de_tokens, Token};
@@ -113,6 +264,53 @@ mod tests {
         }
     }
 
+    #[cfg(feature = "__installer")]
+    #[test]
+    fn rate() {
+        assert_eq!(parse_rate("1024"), Ok(1024));
+        assert_eq!(parse_rate("500k"), Ok(500_000));
+        assert_eq!(parse_rate("500K"), Ok(500_000));
+        assert_eq!(parse_rate("500kB"), Ok(500_000));
+        assert_eq!(parse_rate("2M"), Ok(2_000_000));
+        assert_eq!(parse_rate("1.5m"), Ok(1_500_000));
+        assert_eq!(parse_rate("1G"), Ok(1_000_000_000));
+        assert_eq!(parse_rate(" 100 "), Ok(100));
+        assert_eq!(parse_rate("100B"), Ok(100));
+
+        assert!(parse_rate("").is_err());
+        assert!(parse_rate("0").is_err());
+        assert!(parse_rate("0.1").is_err());
+        assert!(parse_rate("-1k").is_err());
+        assert!(parse_rate("2T").is_err());
+        assert!(parse_rate("fast").is_err());
+
+        #[derive(Deserialize)]
+        struct Test {
+            #[serde(default, deserialize_with = "deserialize_rate")]
+            rate: Option<u64>,
+        }
+        let rate = |s: &str| toml::from_str::<Test>(s).map(|t| t.rate);
+        assert_eq!(rate("").unwrap(), None);
+        assert_eq!(rate("rate = 1000").unwrap(), Some(1000));
+        assert_eq!(rate("rate = \"2M\"").unwrap(), Some(2_000_000));
+        assert!(rate("rate = \"2X\"").is_err());
+    }
+
+    #[cfg(feature = "__installer")]
+    #[test]
+    fn ip_family() {
+        let families: [IpFamily; 3] = serde_json::from_str(r#"["auto", "ipv4", "ipv6"]"#).unwrap();
+        assert_eq!(families, [IpFamily::Auto, IpFamily::Ipv4, IpFamily::Ipv6]);
+
+        assert_eq!(IpFamily::from_flags(false, false), None);
+        assert_eq!(IpFamily::from_flags(true, false), Some(IpFamily::Ipv4));
+        assert_eq!(IpFamily::from_flags(false, true), Some(IpFamily::Ipv6));
+
+        assert_eq!(IpFamily::Auto.local_address(), None);
+        assert!(IpFamily::Ipv4.local_address().unwrap().is_ipv4());
+        assert!(IpFamily::Ipv6.local_address().unwrap().is_ipv6());
+    }
+
     #[test]
     fn deserialize_channel() {
         let channels: [Channel; 3] =
@@ -197,7 +395,7 @@ mod tests {
                 Token::Str("auto_update"),
                 Token::Bool(true),
                 Token::Str("backend"),
-                GitBackend::Libgit2.to_token(),
+                resource::tests::example_config().backend().to_token(),
                 Token::Str("remote"),
                 Token::Map { len: Some(3) },
                 Token::Str("branch"),
@@ -214,6 +412,8 @@ mod tests {
         )
     }
 
+    // The example uses the libgit2 backend
+    #[cfg(feature = "git2")]
     #[test]
     fn deserialize_example() {
         let config: CLIConfig =
@@ -223,9 +423,15 @@ mod tests {
         let expect = CLIConfig {
             #[cfg(feature = "core_installer")]
             core: maa_core::tests::example_config(),
+            #[cfg(not(feature = "core_installer"))]
+            core: paths::CorePaths::default(),
             #[cfg(feature = "cli_installer")]
             cli: maa_cli::tests::example_config(),
             resource: resource::tests::example_config(),
+            #[cfg(feature = "__installer")]
+            user_agent: None,
+            #[cfg(feature = "__installer")]
+            config_registry: None,
         };
 
         assert_eq!(config, expect);
@@ -287,6 +493,26 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "__installer")]
+    #[test]
+    fn user_agent() {
+        assert_eq!(CLIConfig::default().user_agent(), None);
+        let config: CLIConfig = toml::from_str(r#"user_agent = "maa-cli""#).unwrap();
+        assert_eq!(config.user_agent(), Some("maa-cli"));
+    }
+
+    #[cfg(feature = "__installer")]
+    #[test]
+    fn config_registry() {
+        assert_eq!(CLIConfig::default().config_registry(), None);
+        let config: CLIConfig =
+            toml::from_str(r#"config_registry = "https://example.com/registry.json""#).unwrap();
+        assert_eq!(
+            config.config_registry(),
+            Some("https://example.com/registry.json")
+        );
+    }
+
     #[test]
     fn normalize_url_test() {
         assert_eq!(normalize_url("https://foo.bar"), "https://foo.bar/");
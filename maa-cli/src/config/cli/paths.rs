@@ -0,0 +1,69 @@
+use crate::dirs;
+
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+/// Paths of MaaCore given by the user, e.g. a copy installed by the distro or built by the user
+///
+/// Relative paths are relative to the config directory.
+#[cfg_attr(test, derive(Debug, PartialEq))]
+#[derive(Deserialize, Default, Clone)]
+pub struct CorePaths {
+    /// Directory containing the MaaCore library
+    #[serde(default)]
+    library_path: Option<PathBuf>,
+    /// Resource directory of MaaCore
+    #[serde(default)]
+    resource_path: Option<PathBuf>,
+}
+
+impl CorePaths {
+    pub fn library_path(&self) -> Option<PathBuf> {
+        self.library_path.as_deref().map(abs_path)
+    }
+
+    pub fn resource_path(&self) -> Option<PathBuf> {
+        self.resource_path.as_deref().map(abs_path)
+    }
+}
+
+fn abs_path(path: &Path) -> PathBuf {
+    dirs::abs_config(path, None::<&str>).unwrap_or_else(|| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_test::{assert_de_tokens, Token};
+
+    #[test]
+    fn deserialize() {
+        assert_de_tokens(
+            &CorePaths::default(),
+            &[Token::Map { len: Some(0) }, Token::MapEnd],
+        );
+
+        let paths = CorePaths {
+            library_path: Some("/usr/lib/maa".into()),
+            resource_path: Some("resource".into()),
+        };
+        assert_de_tokens(
+            &paths,
+            &[
+                Token::Map { len: Some(2) },
+                Token::Str("library_path"),
+                Token::Some,
+                Token::Str("/usr/lib/maa"),
+                Token::Str("resource_path"),
+                Token::Some,
+                Token::Str("resource"),
+                Token::MapEnd,
+            ],
+        );
+
+        assert_eq!(paths.library_path(), Some(PathBuf::from("/usr/lib/maa")));
+        assert_eq!(paths.resource_path(), Some(dirs::config().join("resource")));
+    }
+}
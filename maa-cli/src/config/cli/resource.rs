@@ -12,6 +12,9 @@ pub struct Config {
     backend: GitBackend,
     #[serde(default)]
     remote: Remote,
+    /// Mirror to fetch resource repository from, overrides the url of remote
+    #[serde(default)]
+    mirror: Option<Mirror>,
 }
 
 impl Config {
@@ -26,6 +29,72 @@ impl Config {
     pub fn remote(&self) -> &Remote {
         &self.remote
     }
+
+    pub fn mirror(&self) -> Option<&Mirror> {
+        self.mirror.as_ref()
+    }
+}
+
+/// URL of the resource repository on GitHub
+const GITHUB_URL: &str = "https://github.com/MaaAssistantArknights/MaaResource.git";
+
+/// URL of the resource repository on the mirror operated by MAA for users in China
+const CN_URL: &str = "https://git.maa-org.net/MAA/MaaResource.git";
+
+/// Mirror of the resource repository
+#[cfg_attr(test, derive(Debug))]
+#[derive(Clone, PartialEq)]
+pub enum Mirror {
+    /// The repository on GitHub
+    Github,
+    /// The mirror for users in China
+    Cn,
+    /// The fastest one of the presets, measured by latency
+    Auto,
+    /// A repository at given URL
+    Url(String),
+}
+
+impl Mirror {
+    /// Preset mirrors which are probed by [`Mirror::Auto`]
+    pub const PRESETS: [Self; 2] = [Self::Github, Self::Cn];
+
+    /// URL of the repository, `None` for [`Mirror::Auto`] which is only known after probing
+    pub fn url(&self) -> Option<&str> {
+        match self {
+            Self::Github => Some(GITHUB_URL),
+            Self::Cn => Some(CN_URL),
+            Self::Auto => None,
+            Self::Url(url) => Some(url),
+        }
+    }
+}
+
+impl std::str::FromStr for Mirror {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "github" => Ok(Self::Github),
+            "cn" => Ok(Self::Cn),
+            "auto" => Ok(Self::Auto),
+            url if url.contains("://") || url.starts_with("git@") => Ok(Self::Url(url.to_owned())),
+            _ => Err(format!(
+                "unknown mirror `{s}`, expected \"github\", \"cn\", \"auto\" or a URL"
+            )),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Mirror {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
 }
 
 #[cfg_attr(test, derive(Debug, PartialEq))]
@@ -67,9 +136,9 @@ impl Default for Remote {
 
 fn default_url() -> String {
     if get_locale().is_some_and(check_zh_cn) {
-        String::from("https://git.maa-org.net/MAA/MaaResource.git")
+        String::from(CN_URL)
     } else {
-        String::from("https://github.com/MaaAssistantArknights/MaaResource.git")
+        String::from(GITHUB_URL)
     }
 }
 
@@ -99,12 +168,16 @@ pub mod tests {
     pub fn example_config() -> Config {
         Config {
             auto_update: true,
+            #[cfg(feature = "git2")]
             backend: GitBackend::Libgit2,
+            #[cfg(not(feature = "git2"))]
+            backend: GitBackend::Git,
             remote: Remote {
                 url: String::from("https://github.com/MaaAssistantArknights/MaaResource.git"),
                 branch: Some(String::from("main")),
                 ssh_key: None,
             },
+            mirror: None,
         }
     }
 
@@ -120,7 +193,8 @@ pub mod tests {
                     url: default_url(),
                     branch: None,
                     ssh_key: None,
-                }
+                },
+                mirror: None,
             }
         );
     }
@@ -194,9 +268,10 @@ pub mod tests {
                         branch: Some(String::from("main")),
                         ssh_key: Some(PathBuf::from("~/.ssh/id_ed25519")),
                     },
+                    mirror: Some(Mirror::Cn),
                 },
                 &[
-                    Token::Map { len: Some(3) },
+                    Token::Map { len: Some(4) },
                     Token::Str("auto_update"),
                     Token::Bool(true),
                     Token::Str("backend"),
@@ -212,12 +287,38 @@ pub mod tests {
                     Token::Some,
                     Token::Str("~/.ssh/id_ed25519"),
                     Token::MapEnd,
+                    Token::Str("mirror"),
+                    Token::Some,
+                    Token::Str("cn"),
                     Token::MapEnd,
                 ],
             );
         }
     }
 
+    #[test]
+    fn mirror() {
+        assert_eq!("github".parse::<Mirror>().unwrap().url(), Some(GITHUB_URL));
+        assert_eq!("cn".parse::<Mirror>().unwrap().url(), Some(CN_URL));
+        assert_eq!("auto".parse::<Mirror>().unwrap(), Mirror::Auto);
+        assert_eq!(Mirror::Auto.url(), None);
+        assert_eq!(
+            "https://gitee.com/MaaMirror/Resource.git"
+                .parse::<Mirror>()
+                .unwrap()
+                .url(),
+            Some("https://gitee.com/MaaMirror/Resource.git")
+        );
+        assert_eq!(
+            "git@github.com:MaaAssistantArknights/MaaResource.git"
+                .parse::<Mirror>()
+                .unwrap()
+                .url(),
+            Some("git@github.com:MaaAssistantArknights/MaaResource.git")
+        );
+        assert!("gitee".parse::<Mirror>().is_err());
+    }
+
     #[test]
     fn url() {
         assert_eq!(Remote::default().url(), default_url());
@@ -0,0 +1,223 @@
+use super::{schema_url, task::TaskConfig, Filetype, FromFile, SUPPORTED_EXTENSION};
+
+use crate::{
+    dirs::{self, Ensure},
+    value::SCHEMA_KEY,
+};
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use serde_json::Value as JsonValue;
+
+/// Export a task config in the `tasks` directory, so it can be shared with others.
+///
+/// The format defaults to the extension of `output`, or the format of the task file.
+/// The result is written to `output` if given, otherwise to stdout.
+pub fn export_task(
+    name: &str,
+    output: Option<&Path>,
+    format: Option<Filetype>,
+    strip_secrets: bool,
+) -> Result<()> {
+    let path = find_task(&dirs::config().join("tasks"), name)?;
+    let format = format
+        .or_else(|| output.and_then(Filetype::parse_filetype))
+        .or_else(|| Filetype::parse_filetype(&path))
+        .unwrap_or(Filetype::Toml);
+
+    let content = export(&path, format, strip_secrets)?;
+
+    match output {
+        Some(output) => {
+            if let Some(dir) = output.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+                dir.ensure()?;
+            }
+            std::fs::write(output, content)
+                .with_context(|| format!("Failed to write to {}", output.display()))?;
+        }
+        None => print!("{content}"),
+    }
+
+    Ok(())
+}
+
+/// Find a task file by name, the extension can be omitted.
+pub(super) fn find_task(dir: &Path, name: &str) -> Result<PathBuf> {
+    let path = dir.join(name);
+    if Filetype::is_valid_file(&path) && path.is_file() {
+        return Ok(path);
+    }
+    SUPPORTED_EXTENSION
+        .iter()
+        .map(|ext| path.with_extension(ext))
+        .find(|path| path.is_file())
+        .with_context(|| format!("Task `{name}` not found in {}", dir.display()))
+}
+
+fn export(path: &Path, format: Filetype, strip_secrets: bool) -> Result<String> {
+    let mut value = JsonValue::from_file(path)
+        .with_context(|| format!("Failed to read task file {}", path.display()))?;
+    serde_json::from_value::<TaskConfig>(value.clone())
+        .with_context(|| format!("{} is not a valid task config", path.display()))?;
+
+    if strip_secrets {
+        strip(&mut value);
+    }
+
+    render(value, format)
+}
+
+/// Replace user inputs which read their value from an environment variable
+/// by inputs with only the description, so the value is queried from the user.
+///
+/// An input without a description is described by the name of the environment variable,
+/// otherwise it would be an empty object instead of an input.
+fn strip(value: &mut JsonValue) {
+    match value {
+        JsonValue::Object(map) if map.contains_key("env") && is_input(map) => {
+            let description = map.remove("description").unwrap_or_else(|| {
+                match map.get("env").and_then(JsonValue::as_str) {
+                    Some(env) => format!("value of {env}"),
+                    None => "value from environment variable".to_owned(),
+                }
+                .into()
+            });
+            map.clear();
+            map.insert("description".to_owned(), description);
+        }
+        JsonValue::Object(map) => map.values_mut().for_each(strip),
+        JsonValue::Array(array) => array.iter_mut().for_each(strip),
+        _ => {}
+    }
+}
+
+fn is_input(map: &serde_json::Map<String, JsonValue>) -> bool {
+    const INPUT_KEYS: [&str; 6] = [
+        "default",
+        "description",
+        "alternatives",
+        "default_index",
+        "allow_custom",
+        "env",
+    ];
+    map.keys().all(|key| INPUT_KEYS.contains(&key.as_str()))
+}
+
+/// Pretty print the value in given format, with a comment of the version of maa-cli
+/// and the URL of the schema of this version in the `$schema` field.
+///
+/// JSON has no comments, so the version is stored in the `$comment` field,
+/// which is ignored when loading the task.
+fn render(mut value: JsonValue, format: Filetype) -> Result<String> {
+    let comment = concat!("Exported by maa-cli ", env!("MAA_VERSION"));
+
+    let JsonValue::Object(map) = &mut value else {
+        bail!("A task config should be an object");
+    };
+    map.insert(SCHEMA_KEY.to_owned(), schema_url().into());
+
+    let mut buf = Vec::new();
+    match format {
+        Filetype::Json => {
+            map.insert("$comment".to_owned(), comment.into());
+        }
+        Filetype::Toml | Filetype::Yaml => buf.extend(format!("# {comment}\n").as_bytes()),
+    }
+    format.write(&mut buf, &value)?;
+    if !buf.ends_with(b"\n") {
+        buf.push(b'\n');
+    }
+
+    Ok(String::from_utf8(buf)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{env::temp_dir, fs};
+
+    use serde_json::json;
+
+    #[test]
+    fn strip_secrets() {
+        let mut value = json!({
+            "tasks": [{
+                "type": "Fight",
+                "params": {
+                    "stage": { "default": "1-7", "description": "stage to fight" },
+                    "password": {
+                        "env": "MAA_PASSWORD",
+                        "default": "secret",
+                        "description": "password of account",
+                    },
+                    "token": { "env": "MAA_TOKEN" },
+                    "env": "not an input",
+                },
+            }],
+        });
+        strip(&mut value);
+        assert_eq!(
+            value,
+            json!({
+                "tasks": [{
+                    "type": "Fight",
+                    "params": {
+                        "stage": { "default": "1-7", "description": "stage to fight" },
+                        "password": { "description": "password of account" },
+                        "token": { "description": "value of MAA_TOKEN" },
+                        "env": "not an input",
+                    },
+                }],
+            })
+        );
+    }
+
+    #[test]
+    fn export_task_file() {
+        let dir = temp_dir().join("maa-test-export-task");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("daily.json"),
+            r#"{"tasks": [{"type": "StartUp", "params": {"client_type": "Official"}}]}"#,
+        )
+        .unwrap();
+        fs::write(dir.join("invalid.json"), r#"{"foo": 1}"#).unwrap();
+
+        let path = find_task(&dir, "daily").unwrap();
+        assert_eq!(path, dir.join("daily.json"));
+        assert_eq!(find_task(&dir, "daily.json").unwrap(), path);
+        assert!(find_task(&dir, "not-exist").is_err());
+
+        let version = env!("MAA_VERSION");
+        let is_task =
+            |format: Filetype, content: &str| format.parse_str::<TaskConfig>(content).is_ok();
+
+        let toml = export(&path, Filetype::Toml, false).unwrap();
+        assert!(toml.starts_with(&format!("# Exported by maa-cli {version}\n")));
+        assert!(is_task(Filetype::Toml, &toml));
+
+        let yaml = export(&path, Filetype::Yaml, false).unwrap();
+        assert!(yaml.starts_with("# Exported by maa-cli"));
+        assert!(is_task(Filetype::Yaml, &yaml));
+        assert!(yaml.contains(&format!("$schema: {}\n", schema_url())));
+
+        let json = export(&path, Filetype::Json, false).unwrap();
+        let value: JsonValue = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            value["$comment"],
+            format!("Exported by maa-cli {version}").as_str()
+        );
+        assert_eq!(value["$schema"], schema_url().as_str());
+        assert!(
+            json.contains("\n  \"tasks\""),
+            "json should be pretty printed"
+        );
+        assert!(is_task(Filetype::Json, &json));
+
+        assert!(export(&dir.join("invalid.json"), Filetype::Toml, false).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,249 @@
+//! Configs merged from the config directories of all layers, see [`dirs::config_layers`].
+//!
+//! A config file is looked up in each layer, e.g. `cli.toml` in `/etc/maa`, the config
+//! directory and `./.maa`, and the found files are merged in order by [`MAAValue::merge_mut`],
+//! so objects are merged recursively and other values of later layers take precedence.
+//!
+//! [`dirs::config_layers`]: crate::dirs::config_layers
+
+use super::{FromFile, Result, SUPPORTED_EXTENSION};
+
+use crate::value::MAAValue;
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use log::info;
+use serde::de::DeserializeOwned;
+use serde_json::Value as JsonValue;
+
+/// A config merged from the files of all layers
+pub struct Layered<T> {
+    pub config: T,
+    /// Files contributing to the config, from the lowest precedence to the highest
+    pub sources: Vec<PathBuf>,
+    /// Files defining each top level key, the last one wins if the values are not objects
+    pub provenance: BTreeMap<String, Vec<PathBuf>>,
+}
+
+impl<T> Layered<T> {
+    /// The file whose value of given top level key is used, i.e. the last file defining it
+    pub fn winner(&self, key: &str) -> Option<&Path> {
+        self.provenance
+            .get(key)
+            .and_then(|files| files.last())
+            .map(PathBuf::as_path)
+    }
+
+    /// Log the files contributing to the config and the winners of conflicting keys
+    pub fn log_sources(&self, name: &str) {
+        let sources: Vec<_> = self
+            .sources
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect();
+        info!("Loaded {name} from {}", sources.join(", "));
+        for (key, files) in self.provenance.iter().filter(|(_, files)| files.len() > 1) {
+            if let Some(winner) = self.winner(key) {
+                info!(
+                    "`{key}` of {name} is defined in {} files, using the one in {}",
+                    files.len(),
+                    winner.display()
+                );
+            }
+        }
+    }
+}
+
+/// Find the file with a supported extension of given path without extension
+fn find_path(path: &Path) -> Option<PathBuf> {
+    SUPPORTED_EXTENSION
+        .iter()
+        .map(|ext| path.with_extension(ext))
+        .find(|path| path.is_file())
+}
+
+/// Find config file `path` (without extension) in each layer and merge the found files in order.
+///
+/// Return `Ok(None)` if the file is not found in any layer.
+pub fn find<T: DeserializeOwned>(
+    layers: &[PathBuf],
+    path: impl AsRef<Path>,
+) -> Result<Option<Layered<T>>> {
+    find_with(layers, path, |file| {
+        Ok(MAAValue::from_json(JsonValue::from_file(file)?))
+    })
+}
+
+/// Like [`find`], but each found file is loaded by `load`, e.g. to resolve includes of task files.
+pub fn find_with<T, E>(
+    layers: &[PathBuf],
+    path: impl AsRef<Path>,
+    load: impl Fn(&Path) -> Result<MAAValue, E>,
+) -> Result<Option<Layered<T>>, E>
+where
+    T: DeserializeOwned,
+    E: From<serde_json::Error>,
+{
+    let path = path.as_ref();
+
+    let mut merged: Option<MAAValue> = None;
+    let mut sources = Vec::new();
+    let mut provenance: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+    for file in layers
+        .iter()
+        .filter_map(|layer| find_path(&layer.join(path)))
+    {
+        let value = load(&file)?;
+        if let Some(map) = value.as_object() {
+            for key in map.keys() {
+                provenance
+                    .entry(key.clone())
+                    .or_default()
+                    .push(file.clone());
+            }
+        }
+        match &mut merged {
+            Some(merged) => merged.merge_mut(&value),
+            None => merged = Some(value),
+        }
+        sources.push(file);
+    }
+
+    let Some(merged) = merged else {
+        return Ok(None);
+    };
+    Ok(Some(Layered {
+        config: merged.into_typed()?,
+        sources,
+        provenance,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::config::task::TaskConfig;
+
+    use std::{env::temp_dir, fs};
+
+    use serde::Deserialize;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Config {
+        a: i32,
+        b: Option<String>,
+        nested: BTreeMap<String, i32>,
+    }
+
+    /// Create three layers, system, user and project, in a temp directory
+    fn layers(name: &str) -> (PathBuf, [PathBuf; 3]) {
+        let root = temp_dir().join(name);
+        if root.exists() {
+            fs::remove_dir_all(&root).unwrap();
+        }
+        let layers = ["etc", "user", "project"].map(|layer| root.join(layer));
+        for layer in &layers {
+            fs::create_dir_all(layer.join("tasks")).unwrap();
+        }
+        (root, layers)
+    }
+
+    #[test]
+    fn merge_in_order() {
+        let (root, layers) = layers("maa-test-layered-merge");
+        let [system, user, project] = &layers;
+        fs::write(
+            system.join("cli.toml"),
+            "a = 1\nb = \"system\"\n[nested]\nx = 1\ny = 1\n",
+        )
+        .unwrap();
+        fs::write(user.join("cli.json"), r#"{"a": 2, "nested": {"y": 2}}"#).unwrap();
+        fs::write(project.join("cli.yaml"), "nested:\n  z: 3\n").unwrap();
+
+        let layered = find::<Config>(&layers, "cli").unwrap().unwrap();
+        assert_eq!(
+            layered.config,
+            Config {
+                a: 2,
+                b: Some("system".to_owned()),
+                nested: BTreeMap::from([("x".into(), 1), ("y".into(), 2), ("z".into(), 3)]),
+            }
+        );
+        assert_eq!(
+            layered.sources,
+            [
+                system.join("cli.toml"),
+                user.join("cli.json"),
+                project.join("cli.yaml"),
+            ]
+        );
+        assert_eq!(layered.winner("a"), Some(user.join("cli.json").as_path()));
+        assert_eq!(layered.winner("b"), Some(system.join("cli.toml").as_path()));
+        assert_eq!(layered.provenance["nested"].len(), 3);
+        assert_eq!(layered.winner("c"), None);
+
+        // layers are merged in given order
+        let reversed = [project.clone(), user.clone(), system.clone()];
+        let layered = find::<Config>(&reversed, "cli").unwrap().unwrap();
+        assert_eq!(layered.config.a, 1);
+
+        // missing in some layers
+        fs::remove_file(user.join("cli.json")).unwrap();
+        let layered = find::<Config>(&layers, "cli").unwrap().unwrap();
+        assert_eq!(layered.config.a, 1);
+        assert_eq!(layered.sources.len(), 2);
+
+        assert!(find::<Config>(&layers, "not-exist").unwrap().is_none());
+        assert!(find::<Config>(&[], "cli").unwrap().is_none());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn conflicting_tasks() {
+        let (root, layers) = layers("maa-test-layered-tasks");
+        let [system, user, project] = &layers;
+        fs::write(
+            system.join("tasks").join("daily.toml"),
+            "startup = true\n[[tasks]]\ntype = \"StartUp\"\n",
+        )
+        .unwrap();
+        fs::write(
+            user.join("tasks").join("daily.json"),
+            r#"{"tasks": [{"type": "Fight", "params": {"stage": {}}}]}"#,
+        )
+        .unwrap();
+        fs::write(
+            project.join("tasks").join("daily.yaml"),
+            "closedown: true\n",
+        )
+        .unwrap();
+
+        let layered = find::<TaskConfig>(&layers, "tasks/daily").unwrap().unwrap();
+        // the task list is replaced as a whole by the later layer
+        assert_eq!(
+            layered.winner("tasks"),
+            Some(user.join("tasks").join("daily.json").as_path())
+        );
+        assert_eq!(layered.sources.len(), 3);
+        assert_eq!(
+            layered.winner("closedown"),
+            Some(project.join("tasks").join("daily.yaml").as_path())
+        );
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn invalid_merged_config() {
+        let (root, layers) = layers("maa-test-layered-invalid");
+        fs::write(layers[0].join("cli.toml"), "a = 1\n[nested]\n").unwrap();
+        fs::write(layers[2].join("cli.json"), r#"{"a": "2"}"#).unwrap();
+        assert!(find::<Config>(&layers, "cli").is_err());
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -79,6 +79,28 @@ fn file_not_found(path: impl AsRef<Path>) -> Error {
 
 const SUPPORTED_EXTENSION: [&str; 4] = ["json", "yaml", "yml", "toml"];
 
+/// URL of the JSON schema of task files published with each release of maa-cli, `{TAG}` is
+/// replaced by the tag of the release, see [`schema_url`]
+pub const SCHEMA_URL_TEMPLATE: &str =
+    "https://github.com/MaaAssistantArknights/maa-cli/releases/download/{TAG}/task.schema.json";
+
+/// URL of the JSON schema for task files written by this version of maa-cli
+///
+/// It is stored in the `$schema` key of generated files, so editors can validate them.
+pub fn schema_url() -> String {
+    schema_url_of(env!("MAA_VERSION"))
+}
+
+/// Alpha versions are released with the `nightly` tag, and others with `v<version>`
+fn schema_url_of(version: &str) -> String {
+    let tag = if version.contains("alpha") {
+        "nightly".to_owned()
+    } else {
+        format!("v{version}")
+    };
+    SCHEMA_URL_TEMPLATE.replace("{TAG}", &tag)
+}
+
 #[derive(Clone, Copy, ValueEnum)]
 pub enum Filetype {
     #[clap(alias = "j")]
@@ -122,6 +144,18 @@ impl Filetype {
         })
     }
 
+    fn parse_str<T>(&self, content: &str) -> Result<T>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        use Filetype::*;
+        Ok(match self {
+            Json => serde_json::from_str(content)?,
+            Yaml => serde_yaml::from_str(content)?,
+            Toml => toml::from_str(content)?,
+        })
+    }
+
     fn write<T>(&self, mut writer: impl std::io::Write, value: &T) -> Result<()>
     where
         T: serde::Serialize,
@@ -156,8 +190,30 @@ pub trait FromFile: Sized + serde::de::DeserializeOwned {
             Err(file_not_found(path))
         }
     }
+
+    /// Similar to `from_file`, but guess the format from the content
+    /// if it can not be determined by the extension, e.g. `/dev/stdin`.
+    ///
+    /// The content will be parsed as JSON, TOML and YAML in order,
+    /// and the error of the last attempt will be returned if all of them failed.
+    fn from_any_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if Filetype::is_valid_file(path) {
+            return Self::from_file(path);
+        }
+
+        let content = fs::read_to_string(path).map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => file_not_found(path),
+            _ => e.into(),
+        })?;
+        Filetype::Json
+            .parse_str(&content)
+            .or_else(|_| Filetype::Toml.parse_str(&content))
+            .or_else(|_| Filetype::Yaml.parse_str(&content))
+    }
 }
 
+#[cfg(test)]
 pub trait FindFile: FromFile {
     /// Find file with supported extension and deserialize it.
     ///
@@ -173,28 +229,15 @@ pub trait FindFile: FromFile {
         }
         Ok(None)
     }
-    /// Find file with supported extension and deserialize it.
-    ///
-    /// The file should not have extension. If it has extension, it will be ignored.
-    /// Return error if file not found.
-    fn find_file(path: impl AsRef<Path>) -> Result<Self> {
-        let path = path.as_ref();
-        Self::find_file_or_none(path)?.ok_or_else(|| file_not_found(path))
-    }
-}
-
-pub trait FindFileOrDefault: FromFile + Default {
-    fn find_file_or_default(path: impl AsRef<Path>) -> Result<Self> {
-        Self::find_file_or_none(path).map(|opt| opt.unwrap_or_default())
-    }
 }
 
+#[cfg(test)]
 impl<T> FindFile for T where T: FromFile {}
 
-impl<T> FindFileOrDefault for T where T: FromFile + Default {}
-
 impl FromFile for JsonValue {}
 
+impl FromFile for crate::value::MAAValue {}
+
 pub fn convert(file: &Path, out: Option<&Path>, ft: Option<Filetype>) -> Result<()> {
     let ft = ft.or_else(|| {
         out.and_then(|path| path.extension())
@@ -239,7 +282,7 @@ pub fn import(src: &Path, force: bool, config_type: &str) -> std::io::Result<()>
         // check if the file name is cli with supported extension for cli configuration
         if file
             .file_stem()
-            .is_some_and(|stem| stem.to_str().map_or(false, |stem| stem == "cli"))
+            .is_some_and(|stem| stem.to_str() == Some("cli"))
             && Filetype::is_valid_file(file)
         {
             let cli_path = dirs::config().join("cli");
@@ -350,6 +393,24 @@ pub mod task;
 
 pub mod init;
 
+pub mod validate;
+
+#[cfg(feature = "__installer")]
+pub mod remote;
+
+#[cfg(feature = "__installer")]
+pub mod registry;
+
+pub mod export;
+
+pub mod task_schemas;
+
+pub mod layered;
+
+pub mod template;
+
+pub mod watch;
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -360,6 +421,22 @@ mod tests {
     use serde::Deserialize;
     use serde_json::{json, Value as JsonValue};
 
+    #[test]
+    fn schema_url_tag() {
+        assert_eq!(
+            schema_url_of("0.4.8"),
+            "https://github.com/MaaAssistantArknights/maa-cli/releases/download/v0.4.8/task.schema.json"
+        );
+        assert_eq!(
+            schema_url_of("0.4.9-beta.1"),
+            "https://github.com/MaaAssistantArknights/maa-cli/releases/download/v0.4.9-beta.1/task.schema.json"
+        );
+        assert_eq!(
+            schema_url_of("0.4.9-alpha.1+sha.abcdef"),
+            "https://github.com/MaaAssistantArknights/maa-cli/releases/download/nightly/task.schema.json"
+        );
+    }
+
     #[test]
     fn filetype() {
         use Filetype::*;
@@ -402,9 +479,46 @@ mod tests {
         std::fs::remove_dir_all(&test_root).unwrap();
     }
 
+    #[test]
+    fn from_any_file() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct TestConfig {
+            a: i32,
+        }
+
+        impl FromFile for TestConfig {}
+
+        let test_root = temp_dir().join("maa-test-from-any-file");
+        std::fs::create_dir_all(&test_root).unwrap();
+
+        let expected = TestConfig { a: 1 };
+        for (name, content) in [
+            ("json", r#"{ "a": 1 }"#),
+            ("toml", "a = 1"),
+            ("yaml", "a: 1"),
+            // misleading extension is respected
+            ("test.json", r#"{ "a": 1 }"#),
+        ] {
+            let path = test_root.join(name);
+            std::fs::write(&path, content).unwrap();
+            assert_eq!(TestConfig::from_any_file(&path).unwrap(), expected);
+        }
+
+        let path = test_root.join("invalid");
+        std::fs::write(&path, "a = ").unwrap();
+        assert!(TestConfig::from_any_file(&path).is_err());
+
+        assert_matches!(
+            TestConfig::from_any_file(test_root.join("not_exist")).unwrap_err(),
+            Error::Io(e) if e.kind() == std::io::ErrorKind::NotFound
+        );
+
+        std::fs::remove_dir_all(&test_root).unwrap();
+    }
+
     #[test]
     fn find_file() {
-        #[derive(Deserialize, PartialEq, Debug, Default)]
+        #[derive(Deserialize, PartialEq, Debug)]
         struct TestConfig {
             a: i32,
             b: String,
@@ -438,32 +552,6 @@ mod tests {
             }
         );
 
-        assert_eq!(
-            TestConfig::find_file(&test_file).unwrap(),
-            TestConfig {
-                a: 1,
-                b: "test".to_string()
-            }
-        );
-
-        assert_matches!(
-            TestConfig::find_file(&non_exist_file).unwrap_err(),
-            Error::Io(e) if e.kind() == std::io::ErrorKind::NotFound
-        );
-
-        assert_eq!(
-            TestConfig::find_file_or_default(&test_file).unwrap(),
-            TestConfig {
-                a: 1,
-                b: "test".to_string()
-            }
-        );
-
-        assert_eq!(
-            TestConfig::find_file_or_default(&non_exist_file).unwrap(),
-            TestConfig::default()
-        );
-
         std::fs::remove_dir_all(&test_root).unwrap();
     }
 
@@ -0,0 +1,339 @@
+//! Registry of community task config templates.
+//!
+//! The registry is a JSON file listing templates with their versions and checksums.
+//! Installed task configs record their version by a `version` field in the root object,
+//! which is compared with the version in the registry to find configs to update.
+
+use super::{
+    cli::cli_config,
+    export::find_task,
+    remote::{filename_from_url, parse_content, warn_hooks},
+    task::TaskConfig,
+};
+
+use crate::{
+    dirs::{self, Ensure},
+    installer::http,
+};
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use log::info;
+use prettytable::{format, row, Table};
+use reqwest::blocking::Client;
+use semver::Version;
+use serde::Deserialize;
+use serde_json::Value as JsonValue;
+use sha2::{Digest, Sha256};
+
+/// URL of the registry used if not configured
+pub const DEFAULT_REGISTRY_URL: &str =
+    "https://github.com/MaaAssistantArknights/maa-cli/releases/latest/download/config-registry.json";
+
+#[cfg_attr(test, derive(Debug, PartialEq))]
+#[derive(Deserialize)]
+struct Registry {
+    configs: Vec<Entry>,
+}
+
+/// A template in the registry
+#[cfg_attr(test, derive(Debug, PartialEq))]
+#[derive(Deserialize)]
+struct Entry {
+    /// Name of the template, which is also the name of the installed task file
+    name: String,
+    #[serde(default)]
+    description: String,
+    version: Version,
+    /// URL to download the template
+    url: String,
+    /// Checksum of the template
+    sha256: String,
+}
+
+/// Status of a template in the registry compared with the installed config
+#[cfg_attr(test, derive(Debug, PartialEq))]
+enum Status {
+    NotInstalled,
+    UpToDate,
+    /// A newer version is available, the installed version is `None` if it is unknown
+    Outdated(Option<Version>),
+}
+
+impl std::fmt::Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Status::NotInstalled => write!(f, "not installed"),
+            Status::UpToDate => write!(f, "up to date"),
+            Status::Outdated(Some(version)) => write!(f, "{version} -> newer available"),
+            Status::Outdated(None) => write!(f, "unknown version -> newer available"),
+        }
+    }
+}
+
+/// URL of the registry, given by `--registry` or `config_registry` in cli config
+fn registry_url(registry: Option<&str>) -> &str {
+    registry
+        .or_else(|| cli_config().config_registry())
+        .unwrap_or(DEFAULT_REGISTRY_URL)
+}
+
+/// Print templates in the registry, and whether the installed configs are outdated.
+pub fn check_updates(registry: Option<&str>) -> Result<()> {
+    let ip_family = cli_config().ip_family();
+    let registry = fetch_registry(&http::blocking_client(ip_family)?, registry_url(registry))
+        .map_err(|err| http::with_family_hint(err, ip_family))?;
+    let dir = dirs::config().join("tasks");
+
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
+    table.set_titles(row!["NAME", "VERSION", "STATUS", "DESCRIPTION"]);
+    let mut outdated = 0;
+    for entry in &registry.configs {
+        let status = status(entry, &dir);
+        if matches!(status, Status::Outdated(_)) {
+            outdated += 1;
+        }
+        table.add_row(row![entry.name, entry.version, status, entry.description]);
+    }
+    table.printstd();
+
+    if outdated > 0 {
+        info!("{outdated} config(s) can be updated by `maa config update <name>`");
+    }
+
+    Ok(())
+}
+
+/// Install or update a config to the latest version in the registry.
+pub fn update(name: &str, registry: Option<&str>) -> Result<()> {
+    let ip_family = cli_config().ip_family();
+    let client = http::blocking_client(ip_family)?;
+    let registry = fetch_registry(&client, registry_url(registry))
+        .map_err(|err| http::with_family_hint(err, ip_family))?;
+    let entry = registry
+        .configs
+        .iter()
+        .find(|entry| entry.name == name)
+        .with_context(|| format!("Config `{name}` not found in the registry"))?;
+
+    let dir = dirs::config().join("tasks");
+    if matches!(status(entry, &dir), Status::UpToDate) {
+        info!("Config `{name}` is up to date ({})", entry.version);
+        return Ok(());
+    }
+
+    let path =
+        update_in(&client, entry, &dir).map_err(|err| http::with_family_hint(err, ip_family))?;
+    info!(
+        "Updated config `{name}` to {} at {}",
+        entry.version,
+        path.display()
+    );
+    Ok(())
+}
+
+fn fetch_registry(client: &Client, url: &str) -> Result<Registry> {
+    client
+        .get(url)
+        .send()
+        .and_then(|resp| resp.error_for_status())
+        .and_then(|resp| resp.json())
+        .with_context(|| format!("Failed to fetch config registry from {url}"))
+}
+
+/// Version of an installed config, `None` if the field is missing or not a valid version
+fn installed_version(path: &Path) -> Option<Version> {
+    use super::FromFile;
+
+    JsonValue::from_file(path)
+        .ok()?
+        .get("version")?
+        .as_str()?
+        .parse()
+        .ok()
+}
+
+fn status(entry: &Entry, dir: &Path) -> Status {
+    match find_task(dir, &entry.name) {
+        Err(_) => Status::NotInstalled,
+        Ok(path) => match installed_version(&path) {
+            Some(version) if version >= entry.version => Status::UpToDate,
+            version => Status::Outdated(version),
+        },
+    }
+}
+
+/// Download a template, verify it and write it to given directory, return the written path.
+///
+/// The installed file with the same name is replaced, even if its format is different.
+fn update_in(client: &Client, entry: &Entry, dir: &Path) -> Result<PathBuf> {
+    // the name is used as file name, so it must not point to other directories
+    if Path::new(&entry.name).file_name() != Some(entry.name.as_ref()) {
+        bail!("Invalid config name `{}` in the registry", entry.name);
+    }
+
+    let url = &entry.url;
+    let content = client
+        .get(url)
+        .send()
+        .and_then(|resp| resp.error_for_status())
+        .and_then(|resp| resp.bytes())
+        .with_context(|| format!("Failed to download config from {url}"))?;
+
+    let checksum = format!("{:x}", Sha256::digest(&content));
+    if !checksum.eq_ignore_ascii_case(&entry.sha256) {
+        bail!(
+            "Checksum mismatch of config `{}`, expected {}, got {checksum}",
+            entry.name,
+            entry.sha256
+        );
+    }
+
+    let content = String::from_utf8(content.to_vec())
+        .with_context(|| format!("Config `{}` is not a valid UTF-8 file", entry.name))?;
+    let name = reqwest::Url::parse(url)
+        .ok()
+        .and_then(|url| filename_from_url(&url));
+    let (filetype, value) = parse_content(name.as_deref(), &content)?;
+    serde_json::from_value::<TaskConfig>(value.clone())
+        .with_context(|| format!("Config `{}` is not a valid task config", entry.name))?;
+    warn_hooks(&value);
+
+    let old = find_task(dir, &entry.name).ok();
+    let path = dir
+        .ensure()?
+        .join(&entry.name)
+        .with_extension(filetype.to_str());
+    std::fs::write(&path, content)
+        .with_context(|| format!("Failed to write config to {}", path.display()))?;
+    if let Some(old) = old.filter(|old| old != &path) {
+        std::fs::remove_file(&old)
+            .with_context(|| format!("Failed to remove old config {}", old.display()))?;
+    }
+
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::installer::test_server::{Response, Server};
+
+    fn entry(version: &str, url: String, content: &str) -> Entry {
+        Entry {
+            name: "daily".to_owned(),
+            description: String::new(),
+            version: version.parse().unwrap(),
+            url,
+            sha256: format!("{:x}", Sha256::digest(content)),
+        }
+    }
+
+    #[test]
+    fn fetch() {
+        let client = Client::builder().no_proxy().build().unwrap();
+        let server = Server::new([Response::ok(
+            r#"{"configs": [{
+                "name": "daily",
+                "description": "Daily routine",
+                "version": "1.2.0",
+                "url": "https://example.com/daily.toml",
+                "sha256": "abc"
+            }]}"#,
+        )]);
+        assert_eq!(
+            fetch_registry(&client, &server.url("config-registry.json")).unwrap(),
+            Registry {
+                configs: vec![Entry {
+                    name: "daily".to_owned(),
+                    description: "Daily routine".to_owned(),
+                    version: Version::new(1, 2, 0),
+                    url: "https://example.com/daily.toml".to_owned(),
+                    sha256: "abc".to_owned(),
+                }]
+            }
+        );
+
+        let server = Server::new([Response::status(404)]);
+        assert!(fetch_registry(&client, &server.url("config-registry.json")).is_err());
+    }
+
+    #[test]
+    fn check_status() {
+        let dir = std::env::temp_dir().join("maa-test-registry-status");
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir).unwrap();
+        }
+        std::fs::create_dir_all(&dir).unwrap();
+        let entry = entry("1.2.0", String::new(), "");
+
+        assert_eq!(status(&entry, &dir), Status::NotInstalled);
+
+        let path = dir.join("daily.toml");
+        std::fs::write(&path, "[[tasks]]\ntype = \"StartUp\"\n").unwrap();
+        assert_eq!(status(&entry, &dir), Status::Outdated(None));
+
+        std::fs::write(
+            &path,
+            "version = \"1.1.0\"\n[[tasks]]\ntype = \"StartUp\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            status(&entry, &dir),
+            Status::Outdated(Some(Version::new(1, 1, 0)))
+        );
+
+        std::fs::write(
+            &path,
+            "version = \"1.2.0\"\n[[tasks]]\ntype = \"StartUp\"\n",
+        )
+        .unwrap();
+        assert_eq!(status(&entry, &dir), Status::UpToDate);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn update_config() {
+        let dir = std::env::temp_dir().join("maa-test-registry-update");
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir).unwrap();
+        }
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("daily.json"), r#"{"tasks": []}"#).unwrap();
+        let client = Client::builder().no_proxy().build().unwrap();
+
+        let content = "version = \"1.2.0\"\n[[tasks]]\ntype = \"Fight\"\n";
+        let server = Server::new([Response::ok(content)]);
+        let entry = entry("1.2.0", server.url("daily.toml"), content);
+        let path = update_in(&client, &entry, &dir).unwrap();
+        assert_eq!(path, dir.join("daily.toml"));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), content);
+        // the old config in another format is replaced
+        assert!(!dir.join("daily.json").exists());
+        assert_eq!(status(&entry, &dir), Status::UpToDate);
+
+        // checksum mismatch
+        let server = Server::new([Response::ok("[[tasks]]\ntype = \"StartUp\"\n")]);
+        let entry = self::entry("1.3.0", server.url("daily.toml"), content);
+        let err = update_in(&client, &entry, &dir).unwrap_err();
+        assert!(err.to_string().starts_with("Checksum mismatch"));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), content);
+
+        // invalid task config
+        let invalid = r#"{"foo": 1}"#;
+        let server = Server::new([Response::ok(invalid)]);
+        let entry = self::entry("1.3.0", server.url("daily.json"), invalid);
+        assert!(update_in(&client, &entry, &dir).is_err());
+        assert!(!dir.join("daily.json").exists());
+
+        let mut entry = self::entry("1.3.0", server.url("daily.json"), invalid);
+        entry.name = "../daily".to_owned();
+        assert!(update_in(&client, &entry, &dir).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
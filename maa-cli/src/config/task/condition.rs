@@ -2,12 +2,18 @@ use super::client_type::ClientType;
 
 use crate::activity::has_side_story_open;
 
-use chrono::{DateTime, Datelike, NaiveDateTime, NaiveTime, TimeZone, Utc, Weekday};
-use serde::Deserialize;
-
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDateTime, NaiveTime, TimeZone, Utc, Weekday};
+use serde::{Deserialize, Deserializer};
+use serde_json::Value as JsonValue;
+
+/// A condition of a task variant
+///
+/// Conditions are tagged by the `type` key, e.g. `{ type = "Weekday", weekdays = ["Sat"] }`.
+/// The boolean combinators can also be written by a single key, i.e. `{ all = [...] }`,
+/// `{ any = [...] }` and `{ not = ... }`, see [`Condition::deserialize`].
 #[cfg_attr(test, derive(PartialEq, Debug))]
 #[derive(Deserialize)]
-#[serde(tag = "type")]
+#[serde(tag = "type", remote = "Self")]
 #[derive(Default)]
 pub enum Condition {
     /// The task is always active
@@ -18,7 +24,11 @@ pub enum Condition {
     /// By default, use the weekday in user local time zone.
     /// If client is specified, use the weekday in the server time zone and start of the day will be
     /// 04:00:00 instead of 00:00:00 in server time zone, and the end of the day will be 03:59:59.
+    ///
+    /// Weekdays can be given by names, e.g. `Mon` or `Monday`,
+    /// or numbers from 0 for Monday to 6 for Sunday.
     Weekday {
+        #[serde(deserialize_with = "deserialize_weekdays")]
         weekdays: Vec<Weekday>,
         #[serde(default, alias = "client")]
         timezone: TimeOffset,
@@ -49,13 +59,16 @@ pub enum Condition {
     },
     /// The task is active on the specified datetime range
     ///
+    /// The range is inclusive, both `start` and `end` are included.
     /// If `start` is `None`, the task is active before `end`.
     /// If `end` is `None`, the task is active after `start`.
+    /// Bounds with an offset, e.g. `2024-05-01T16:00:00+08:00`, are absolute,
+    /// and bounds without an offset are in the time zone given by `timezone`.
     DateTime {
         #[serde(default)]
-        start: Option<NaiveDateTime>,
+        start: Option<DateTimeBound>,
         #[serde(default)]
-        end: Option<NaiveDateTime>,
+        end: Option<DateTimeBound>,
         #[serde(default)]
         timezone: TimeOffset,
     },
@@ -64,14 +77,64 @@ pub enum Condition {
         client: ClientType,
     },
     /// The task is active if all the sub-conditions are met
-    #[serde(alias = "Combined")]
+    #[serde(alias = "Combined", alias = "All")]
     And { conditions: Vec<Condition> },
     /// The task is active if any of the sub-conditions is met
+    #[serde(alias = "Any")]
     Or { conditions: Vec<Condition> },
     /// The task is active if the inner condition is not met
     Not { condition: Box<Condition> },
 }
 
+fn deserialize_weekdays<'de, D>(deserializer: D) -> Result<Vec<Weekday>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum WeekdayRepr {
+        Name(Weekday),
+        Number(u8),
+    }
+
+    Vec::<WeekdayRepr>::deserialize(deserializer)?
+        .into_iter()
+        .map(|weekday| match weekday {
+            WeekdayRepr::Name(weekday) => Ok(weekday),
+            WeekdayRepr::Number(n) => Weekday::try_from(n).map_err(|_| {
+                serde::de::Error::custom(format!(
+                    "invalid weekday {n}, expected 0 (Monday) to 6 (Sunday)"
+                ))
+            }),
+        })
+        .collect()
+}
+
+/// A bound of `DateTime` condition, with or without an offset
+#[cfg_attr(test, derive(PartialEq, Debug))]
+#[derive(Clone, Copy, Deserialize)]
+#[serde(untagged)]
+pub enum DateTimeBound {
+    Offset(DateTime<FixedOffset>),
+    Naive(NaiveDateTime),
+}
+
+impl DateTimeBound {
+    /// Get the naive date time of the bound in given time zone
+    fn naive(self, timezone: TimeOffset) -> NaiveDateTime {
+        match self {
+            Self::Offset(datetime) => timezone.date_time(datetime),
+            Self::Naive(datetime) => datetime,
+        }
+    }
+}
+
+impl From<NaiveDateTime> for DateTimeBound {
+    fn from(datetime: NaiveDateTime) -> Self {
+        Self::Naive(datetime)
+    }
+}
+
 #[cfg_attr(test, derive(PartialEq, Debug))]
 #[derive(Clone, Copy, Default, Deserialize)]
 #[serde(untagged)]
@@ -106,25 +169,30 @@ fn tz_to_offset(tz: i8) -> chrono::FixedOffset {
 }
 
 impl Condition {
-    pub fn is_active(&self) -> bool {
+    /// Whether the condition is met at given time
+    ///
+    /// Time based conditions are evaluated at `now` converted to their time zones,
+    /// so that the result is deterministic for a given `now`.
+    /// `OnSideStory` always uses the current time, because it depends on remote data.
+    pub fn is_active_at(&self, now: DateTime<Utc>) -> bool {
         use Condition::*;
         match *self {
             Always => true,
             Weekday {
                 ref weekdays,
                 timezone,
-            } => weekdays.contains(&timezone.naive_now().weekday()),
+            } => weekdays.contains(&timezone.date_time(now).weekday()),
             DayMod {
                 divisor,
                 remainder,
                 timezone,
-            } => remainder_of_day_mod(timezone, divisor) == remainder,
+            } => timezone.date_time(now).num_days_from_ce() as u32 % divisor == remainder,
             Time {
                 start,
                 end,
                 timezone,
             } => {
-                let now_time = timezone.naive_now().time();
+                let now_time = timezone.date_time(now).time();
 
                 match (start, end) {
                     (Some(s), Some(e)) => time_in_range(now_time, s, e),
@@ -138,18 +206,20 @@ impl Condition {
                 end,
                 timezone,
             } => {
-                let now = timezone.naive_now();
+                let now = timezone.date_time(now);
+                let start = start.map(|s| s.naive(timezone));
+                let end = end.map(|e| e.naive(timezone));
                 match (start, end) {
-                    (Some(s), Some(e)) => now >= s && now < e,
+                    (Some(s), Some(e)) => now >= s && now <= e,
                     (Some(s), None) => now >= s,
-                    (None, Some(e)) => now < e,
+                    (None, Some(e)) => now <= e,
                     (None, None) => true,
                 }
             }
             OnSideStory { client } => has_side_story_open(client),
             And { ref conditions } => {
                 for condition in conditions {
-                    if !condition.is_active() {
+                    if !condition.is_active_at(now) {
                         return false;
                     }
                 }
@@ -157,13 +227,95 @@ impl Condition {
             }
             Or { ref conditions } => {
                 for condition in conditions {
-                    if condition.is_active() {
+                    if condition.is_active_at(now) {
                         return true;
                     }
                 }
                 false
             }
-            Not { ref condition } => !condition.is_active(),
+            Not { ref condition } => !condition.is_active_at(now),
+        }
+    }
+
+    /// Whether the condition will never be met after given time
+    ///
+    /// Only `DateTime` conditions with an `end` can expire, which are used for events.
+    /// Expired conditions are reported, so that stale entries in configs are visible.
+    pub fn is_expired_at(&self, now: DateTime<Utc>) -> bool {
+        use Condition::*;
+        match self {
+            DateTime {
+                end: Some(end),
+                timezone,
+                ..
+            } => timezone.date_time(now) > end.naive(*timezone),
+            And { conditions } => conditions.iter().any(|c| c.is_expired_at(now)),
+            Or { conditions } => {
+                !conditions.is_empty() && conditions.iter().all(|c| c.is_expired_at(now))
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Deserialize a condition tagged by `type`, or a combinator written by a single key.
+///
+/// Only the combinators have the key-based form, where the key names what to do with
+/// the sub-conditions. Other conditions keep the `type` key, so all their fields are
+/// written in one place.
+impl<'de> Deserialize<'de> for Condition {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = JsonValue::deserialize(deserializer)?;
+        let combinator = value
+            .as_object()
+            .filter(|map| map.len() == 1)
+            .and_then(|map| map.iter().next())
+            .filter(|(key, _)| matches!(key.as_str(), "all" | "any" | "not"));
+        let condition = match combinator {
+            Some((key, inner)) => match key.as_str() {
+                "all" => Vec::deserialize(inner).map(|conditions| Condition::And { conditions }),
+                "any" => Vec::deserialize(inner).map(|conditions| Condition::Or { conditions }),
+                _ => Box::deserialize(inner).map(|condition| Condition::Not { condition }),
+            },
+            None => Condition::deserialize(&value),
+        };
+        condition.map_err(serde::de::Error::custom)
+    }
+}
+
+/// Maximum depth of nested conditions
+const MAX_DEPTH: usize = 16;
+
+impl Condition {
+    /// Check whether the boolean combinators are well-formed
+    ///
+    /// Conditions can be nested at most [`MAX_DEPTH`] levels. The error names the path of
+    /// the malformed condition relative to the variant, e.g. `condition.conditions[1]`.
+    /// Empty `And` and `Or` are allowed, which are always and never met respectively.
+    pub fn check(&self) -> Result<(), String> {
+        self.check_at("condition", 1)
+    }
+
+    fn check_at(&self, path: &str, depth: usize) -> Result<(), String> {
+        use Condition::*;
+
+        if depth > MAX_DEPTH {
+            return Err(format!(
+                "condition at `{path}` is nested too deeply, at most {MAX_DEPTH} levels are allowed"
+            ));
+        }
+        match self {
+            And { conditions } | Or { conditions } => {
+                for (index, condition) in conditions.iter().enumerate() {
+                    condition.check_at(&format!("{path}.conditions[{index}]"), depth + 1)?;
+                }
+                Ok(())
+            }
+            Not { condition } => condition.check_at(&format!("{path}.condition"), depth + 1),
+            _ => Ok(()),
         }
     }
 }
@@ -198,7 +350,7 @@ mod tests {
 
         #[test]
         fn always() {
-            assert!(Condition::Always.is_active());
+            assert!(Condition::Always.is_active_at(Utc::now()));
         }
 
         #[test]
@@ -216,19 +368,19 @@ mod tests {
                 weekdays: vec![weekday],
                 timezone: Local
             }
-            .is_active());
+            .is_active_at(Utc::now()));
             assert!(!Condition::Weekday {
                 weekdays: vec![weekday.pred(), weekday.succ()],
                 timezone: Local,
             }
-            .is_active());
+            .is_active_at(Utc::now()));
 
             assert_eq!(
                 Condition::Weekday {
                     weekdays: vec![weekday_in_cn],
                     timezone: Client(ClientType::Official),
                 }
-                .is_active(),
+                .is_active_at(Utc::now()),
                 !should_be_prev_day
             );
 
@@ -237,11 +389,58 @@ mod tests {
                     weekdays: vec![weekday_in_cn.pred(), weekday_in_cn.succ()],
                     timezone: Client(ClientType::Official),
                 }
-                .is_active(),
+                .is_active_at(Utc::now()),
                 should_be_prev_day
             );
         }
 
+        #[test]
+        fn weekday_at() {
+            fn cn_time(d: u32, h: u32, mi: u32) -> DateTime<Utc> {
+                FixedOffset::east_opt(8 * 3600)
+                    .unwrap()
+                    .with_ymd_and_hms(2024, 2, d, h, mi, 0)
+                    .unwrap()
+                    .with_timezone(&Utc)
+            }
+
+            // 2024-02-14 is Wednesday
+            let wednesday = |timezone| Condition::Weekday {
+                weekdays: vec![Weekday::Wed],
+                timezone,
+            };
+
+            // the day of the official server starts at 04:00 in UTC+8
+            let official = wednesday(TimeOffset::Client(ClientType::Official));
+            assert!(!official.is_active_at(cn_time(14, 3, 59)));
+            assert!(official.is_active_at(cn_time(14, 4, 0)));
+            assert!(official.is_active_at(cn_time(15, 3, 59)));
+            assert!(!official.is_active_at(cn_time(15, 4, 0)));
+
+            let utc8 = wednesday(TimeOffset::TimeZone(8));
+            assert!(!utc8.is_active_at(cn_time(13, 23, 59)));
+            assert!(utc8.is_active_at(cn_time(14, 0, 0)));
+            assert!(utc8.is_active_at(cn_time(14, 23, 59)));
+            assert!(!utc8.is_active_at(cn_time(15, 0, 0)));
+
+            // both weekday and time must hold
+            let evening = Condition::And {
+                conditions: vec![
+                    wednesday(TimeOffset::Client(ClientType::Official)),
+                    Condition::Time {
+                        start: NaiveTime::from_hms_opt(18, 0, 0),
+                        end: NaiveTime::from_hms_opt(4, 0, 0),
+                        timezone: TimeOffset::TimeZone(8),
+                    },
+                ],
+            };
+            assert!(!evening.is_active_at(cn_time(14, 12, 0)));
+            assert!(evening.is_active_at(cn_time(14, 18, 0)));
+            assert!(evening.is_active_at(cn_time(15, 3, 59)));
+            assert!(!evening.is_active_at(cn_time(15, 4, 0)));
+            assert!(!evening.is_active_at(cn_time(13, 20, 0)));
+        }
+
         #[test]
         fn time_offset() {
             fn datetime(tz: i32, y: i32, m: u32, d: u32, h: u32, mi: u32) -> DateTime<FixedOffset> {
@@ -362,7 +561,7 @@ mod tests {
                 remainder: 0,
                 timezone: TimeOffset::Local,
             }
-            .is_active());
+            .is_active_at(Utc::now()));
 
             assert_eq!(
                 Condition::DayMod {
@@ -370,8 +569,8 @@ mod tests {
                     remainder: 0,
                     timezone: TimeOffset::Local
                 }
-                .is_active(),
-                num_days % 2 == 0
+                .is_active_at(Utc::now()),
+                num_days.is_multiple_of(2)
             );
 
             assert_eq!(
@@ -380,7 +579,7 @@ mod tests {
                     remainder: 1,
                     timezone: TimeOffset::Local
                 }
-                .is_active(),
+                .is_active_at(Utc::now()),
                 num_days % 2 == 1
             );
         }
@@ -399,43 +598,82 @@ mod tests {
                 end: Some(now_time + seconds(10)),
                 timezone: TimeOffset::Local,
             }
-            .is_active());
+            .is_active_at(Utc::now()));
             assert!(Condition::Time {
                 start: Some(now_time + seconds(-10)),
                 end: None,
                 timezone: TimeOffset::Local
             }
-            .is_active());
+            .is_active_at(Utc::now()));
             assert!(Condition::Time {
                 start: None,
                 end: Some(now_time + seconds(10)),
                 timezone: TimeOffset::Local
             }
-            .is_active());
+            .is_active_at(Utc::now()));
             assert!(Condition::Time {
                 start: None,
                 end: None,
                 timezone: TimeOffset::Local
             }
-            .is_active());
+            .is_active_at(Utc::now()));
             assert!(!Condition::Time {
                 start: Some(now_time + seconds(10)),
                 end: Some(now_time + seconds(20)),
                 timezone: TimeOffset::Local
             }
-            .is_active());
+            .is_active_at(Utc::now()));
             assert!(!Condition::Time {
                 start: Some(now_time + seconds(10)),
                 end: None,
                 timezone: TimeOffset::Local
             }
-            .is_active());
+            .is_active_at(Utc::now()));
             assert!(!Condition::Time {
                 start: None,
                 end: Some(now_time + seconds(-10)),
                 timezone: TimeOffset::Local
             }
-            .is_active());
+            .is_active_at(Utc::now()));
+        }
+
+        #[test]
+        fn time_at() {
+            fn utc(h: u32, mi: u32, s: u32) -> DateTime<Utc> {
+                // 10:00 UTC is 18:00 in UTC+8
+                Utc.with_ymd_and_hms(2024, 2, 14, h, mi, s).unwrap()
+            }
+            fn time(h: u32, m: u32) -> Option<NaiveTime> {
+                NaiveTime::from_hms_opt(h, m, 0)
+            }
+
+            let evening = Condition::Time {
+                start: time(18, 0),
+                end: time(4, 0),
+                timezone: TimeOffset::TimeZone(8),
+            };
+            assert!(!evening.is_active_at(utc(9, 59, 59)));
+            assert!(evening.is_active_at(utc(10, 0, 0)));
+            assert!(evening.is_active_at(utc(15, 59, 59)));
+            // crossing midnight
+            assert!(evening.is_active_at(utc(16, 0, 0)));
+            assert!(evening.is_active_at(utc(19, 59, 59)));
+            assert!(!evening.is_active_at(utc(20, 0, 0)));
+
+            let day = Condition::Time {
+                start: time(4, 0),
+                end: time(18, 0),
+                timezone: TimeOffset::TimeZone(8),
+            };
+            assert!(!day.is_active_at(utc(19, 59, 59)));
+            assert!(day.is_active_at(utc(20, 0, 0)));
+            assert!(day.is_active_at(utc(9, 59, 59)));
+            assert!(!day.is_active_at(utc(10, 0, 0)));
+
+            assert!(Condition::Not {
+                condition: Box::new(evening)
+            }
+            .is_active_at(utc(0, 0, 0)));
         }
 
         #[test]
@@ -469,47 +707,139 @@ mod tests {
             let now_datetime = now.naive_local();
 
             assert!(Condition::DateTime {
-                start: Some(now_datetime + seconds(-10)),
-                end: Some(now_datetime + seconds(10)),
+                start: Some((now_datetime + seconds(-10)).into()),
+                end: Some((now_datetime + seconds(10)).into()),
                 timezone: TimeOffset::Local,
             }
-            .is_active());
+            .is_active_at(Utc::now()));
             assert!(Condition::DateTime {
-                start: Some(now_datetime + seconds(-10)),
+                start: Some((now_datetime + seconds(-10)).into()),
                 end: None,
                 timezone: TimeOffset::Local
             }
-            .is_active());
+            .is_active_at(Utc::now()));
             assert!(Condition::DateTime {
                 start: None,
-                end: Some(now_datetime + seconds(10)),
+                end: Some((now_datetime + seconds(10)).into()),
                 timezone: TimeOffset::Local
             }
-            .is_active());
+            .is_active_at(Utc::now()));
             assert!(Condition::DateTime {
                 start: None,
                 end: None,
                 timezone: TimeOffset::Local
             }
-            .is_active());
+            .is_active_at(Utc::now()));
             assert!(!Condition::DateTime {
-                start: Some(now_datetime + seconds(10)),
-                end: Some(now_datetime + seconds(20)),
+                start: Some((now_datetime + seconds(10)).into()),
+                end: Some((now_datetime + seconds(20)).into()),
                 timezone: TimeOffset::Local
             }
-            .is_active());
+            .is_active_at(Utc::now()));
             assert!(!Condition::DateTime {
-                start: Some(now_datetime + seconds(10)),
+                start: Some((now_datetime + seconds(10)).into()),
                 end: None,
                 timezone: TimeOffset::Local
             }
-            .is_active());
+            .is_active_at(Utc::now()));
             assert!(!Condition::DateTime {
                 start: None,
-                end: Some(now_datetime + seconds(-10)),
+                end: Some((now_datetime + seconds(-10)).into()),
                 timezone: TimeOffset::Local
             }
-            .is_active());
+            .is_active_at(Utc::now()));
+        }
+
+        #[test]
+        fn datetime_at() {
+            fn utc(d: u32, h: u32, mi: u32, s: u32) -> DateTime<Utc> {
+                Utc.with_ymd_and_hms(2024, 5, d, h, mi, s).unwrap()
+            }
+            fn naive(d: u32, h: u32, mi: u32, s: u32) -> Option<DateTimeBound> {
+                NaiveDate::from_ymd_opt(2024, 5, d)
+                    .and_then(|date| date.and_hms_opt(h, mi, s))
+                    .map(Into::into)
+            }
+            fn cn(d: u32, h: u32, mi: u32, s: u32) -> Option<DateTimeBound> {
+                FixedOffset::east_opt(8 * 3600)
+                    .unwrap()
+                    .with_ymd_and_hms(2024, 5, d, h, mi, s)
+                    .single()
+                    .map(DateTimeBound::Offset)
+            }
+
+            // 2024-05-01T16:00:00+08:00 is 2024-05-01T08:00:00Z
+            for (start, end, timezone) in [
+                (
+                    naive(1, 16, 0, 0),
+                    naive(15, 4, 0, 0),
+                    TimeOffset::TimeZone(8),
+                ),
+                (
+                    naive(1, 8, 0, 0),
+                    naive(14, 20, 0, 0),
+                    TimeOffset::TimeZone(0),
+                ),
+                // bounds with offsets do not depend on the time zone
+                (cn(1, 16, 0, 0), cn(15, 4, 0, 0), TimeOffset::TimeZone(-7)),
+                (
+                    cn(1, 16, 0, 0),
+                    cn(15, 4, 0, 0),
+                    TimeOffset::Client(ClientType::YoStarJP),
+                ),
+            ] {
+                let event = Condition::DateTime {
+                    start,
+                    end,
+                    timezone,
+                };
+                // both the start and the end are included
+                assert!(!event.is_active_at(utc(1, 7, 59, 59)));
+                assert!(event.is_active_at(utc(1, 8, 0, 0)));
+                assert!(event.is_active_at(utc(14, 19, 59, 59)));
+                assert!(event.is_active_at(utc(14, 20, 0, 0)));
+                assert!(!event.is_active_at(utc(14, 20, 0, 1)));
+
+                assert!(!event.is_expired_at(utc(1, 7, 59, 59)));
+                assert!(!event.is_expired_at(utc(14, 20, 0, 0)));
+                assert!(event.is_expired_at(utc(14, 20, 0, 1)));
+            }
+        }
+
+        #[test]
+        fn expired() {
+            let now = Utc.with_ymd_and_hms(2024, 5, 15, 0, 0, 0).unwrap();
+            let ended = || Condition::DateTime {
+                start: None,
+                end: Some(naive_local_datetime(2024, 5, 1, 0, 0, 0).into()),
+                timezone: TimeOffset::TimeZone(0),
+            };
+            let not_ended = || Condition::DateTime {
+                start: Some(naive_local_datetime(2024, 5, 1, 0, 0, 0).into()),
+                end: None,
+                timezone: TimeOffset::TimeZone(0),
+            };
+
+            assert!(ended().is_expired_at(now));
+            assert!(!not_ended().is_expired_at(now));
+            assert!(!Condition::Always.is_expired_at(now));
+            assert!(Condition::And {
+                conditions: vec![Condition::Always, ended()]
+            }
+            .is_expired_at(now));
+            assert!(!Condition::Or {
+                conditions: vec![Condition::Always, ended()]
+            }
+            .is_expired_at(now));
+            assert!(Condition::Or {
+                conditions: vec![ended(), ended()]
+            }
+            .is_expired_at(now));
+            assert!(!Condition::Or { conditions: vec![] }.is_expired_at(now));
+            assert!(!Condition::Not {
+                condition: Box::new(ended())
+            }
+            .is_expired_at(now));
         }
 
         // It's hart to test OnSideStory, because it depends on real world data
@@ -521,7 +851,7 @@ mod tests {
             assert!(Condition::And {
                 conditions: vec![Condition::Always, Condition::Always]
             }
-            .is_active());
+            .is_active_at(Utc::now()));
             assert!(!Condition::And {
                 conditions: vec![
                     Condition::Always,
@@ -530,7 +860,7 @@ mod tests {
                     },
                 ]
             }
-            .is_active());
+            .is_active_at(Utc::now()));
 
             assert!(Condition::Or {
                 conditions: vec![
@@ -540,7 +870,7 @@ mod tests {
                     }
                 ]
             }
-            .is_active());
+            .is_active_at(Utc::now()));
 
             assert!(!Condition::Or {
                 conditions: vec![
@@ -552,18 +882,18 @@ mod tests {
                     }
                 ]
             }
-            .is_active());
+            .is_active_at(Utc::now()));
 
             assert!(!Condition::Not {
                 condition: Box::new(Condition::Always)
             }
-            .is_active());
+            .is_active_at(Utc::now()));
         }
     }
 
     mod serde {
         use super::*;
-        use serde_test::{assert_de_tokens, Token};
+        use serde_test::{assert_de_tokens, assert_de_tokens_error, Token};
 
         #[test]
         fn weekday() {
@@ -627,6 +957,39 @@ mod tests {
                     Token::MapEnd,
                 ],
             );
+
+            assert_de_tokens(
+                &Condition::Weekday {
+                    weekdays: vec![Weekday::Mon, Weekday::Tue, Weekday::Sun],
+                    timezone: TimeOffset::Local,
+                },
+                &[
+                    Token::Map { len: Some(2) },
+                    Token::Str("type"),
+                    Token::Str("Weekday"),
+                    Token::Str("weekdays"),
+                    Token::Seq { len: Some(3) },
+                    Token::U8(0),
+                    Token::Str("Tue"),
+                    Token::U8(6),
+                    Token::SeqEnd,
+                    Token::MapEnd,
+                ],
+            );
+
+            assert_de_tokens_error::<Condition>(
+                &[
+                    Token::Map { len: Some(2) },
+                    Token::Str("type"),
+                    Token::Str("Weekday"),
+                    Token::Str("weekdays"),
+                    Token::Seq { len: Some(1) },
+                    Token::U8(7),
+                    Token::SeqEnd,
+                    Token::MapEnd,
+                ],
+                "invalid weekday 7, expected 0 (Monday) to 6 (Sunday)",
+            );
         }
 
         #[test]
@@ -707,8 +1070,8 @@ mod tests {
         fn datetime() {
             assert_de_tokens(
                 &Condition::DateTime {
-                    start: Some(naive_local_datetime(2021, 8, 1, 16, 0, 0)),
-                    end: Some(naive_local_datetime(2021, 8, 21, 4, 0, 0)),
+                    start: Some(naive_local_datetime(2021, 8, 1, 16, 0, 0).into()),
+                    end: Some(naive_local_datetime(2021, 8, 21, 4, 0, 0).into()),
                     timezone: TimeOffset::Local,
                 },
                 &[
@@ -726,7 +1089,7 @@ mod tests {
             assert_de_tokens(
                 &Condition::DateTime {
                     start: None,
-                    end: Some(naive_local_datetime(2021, 8, 21, 4, 0, 0)),
+                    end: Some(naive_local_datetime(2021, 8, 21, 4, 0, 0).into()),
                     timezone: TimeOffset::TimeZone(8),
                 },
                 &[
@@ -740,6 +1103,27 @@ mod tests {
                     Token::MapEnd,
                 ],
             );
+
+            assert_de_tokens(
+                &Condition::DateTime {
+                    start: Some(DateTimeBound::Offset(
+                        FixedOffset::east_opt(8 * 3600)
+                            .unwrap()
+                            .with_ymd_and_hms(2024, 5, 1, 16, 0, 0)
+                            .unwrap(),
+                    )),
+                    end: None,
+                    timezone: TimeOffset::Local,
+                },
+                &[
+                    Token::Map { len: Some(2) },
+                    Token::Str("type"),
+                    Token::Str("DateTime"),
+                    Token::Str("start"),
+                    Token::Str("2024-05-01T16:00:00+08:00"),
+                    Token::MapEnd,
+                ],
+            );
         }
 
         #[test]
@@ -858,6 +1242,113 @@ mod tests {
                     Token::MapEnd,
                 ],
             );
+
+            for (alias, expected) in [
+                (
+                    "All",
+                    Condition::And {
+                        conditions: vec![Condition::Always],
+                    },
+                ),
+                (
+                    "Any",
+                    Condition::Or {
+                        conditions: vec![Condition::Always],
+                    },
+                ),
+            ] {
+                assert_de_tokens(
+                    &expected,
+                    &[
+                        Token::Map { len: Some(2) },
+                        Token::Str("type"),
+                        Token::Str(alias),
+                        Token::Str("conditions"),
+                        Token::Seq { len: Some(1) },
+                        Token::Map { len: Some(1) },
+                        Token::Str("type"),
+                        Token::Str("Always"),
+                        Token::MapEnd,
+                        Token::SeqEnd,
+                        Token::MapEnd,
+                    ],
+                );
+            }
+        }
+
+        #[test]
+        fn combinator_keys() {
+            use serde_json::{from_value, json};
+
+            let weekend_json = || json!({ "type": "Weekday", "weekdays": ["Sat", "Sun"] });
+            let weekend = || Condition::Weekday {
+                weekdays: vec![Weekday::Sat, Weekday::Sun],
+                timezone: TimeOffset::Local,
+            };
+            let condition: Condition = from_value(json!({
+                "any": [
+                    { "all": [weekend_json(), { "type": "Always" }] },
+                    { "not": weekend_json() },
+                ]
+            }))
+            .unwrap();
+            assert_eq!(
+                condition,
+                Condition::Or {
+                    conditions: vec![
+                        Condition::And {
+                            conditions: vec![weekend(), Condition::Always],
+                        },
+                        Condition::Not {
+                            condition: Box::new(weekend()),
+                        },
+                    ],
+                }
+            );
+
+            // keys are only combinators if they are the only key
+            assert!(from_value::<Condition>(json!({ "type": "Always", "not": {} })).is_ok());
+            assert!(from_value::<Condition>(json!({ "all": [], "any": [] })).is_err());
+            let err = from_value::<Condition>(json!({ "any": { "type": "Always" } })).unwrap_err();
+            assert!(err
+                .to_string()
+                .contains("invalid type: map, expected a sequence"));
+        }
+    }
+
+    #[test]
+    fn check() {
+        fn not(condition: Condition) -> Condition {
+            Condition::Not {
+                condition: Box::new(condition),
+            }
+        }
+
+        let nested = Condition::Or {
+            conditions: vec![
+                Condition::Always,
+                Condition::And {
+                    conditions: vec![not(Condition::Always), Condition::Always],
+                },
+            ],
+        };
+        assert_eq!(nested.check(), Ok(()));
+
+        // empty combinators are allowed
+        let empty = Condition::Or {
+            conditions: vec![Condition::And { conditions: vec![] }],
+        };
+        assert_eq!(empty.check(), Ok(()));
+        assert!(Condition::And { conditions: vec![] }.is_active_at(Utc::now()));
+        assert!(!Condition::Or { conditions: vec![] }.is_active_at(Utc::now()));
+
+        let mut deep = Condition::Always;
+        for _ in 1..MAX_DEPTH {
+            deep = not(deep);
         }
+        assert_eq!(deep.check(), Ok(()));
+        let err = not(deep).check().unwrap_err();
+        assert!(err.starts_with("condition at `condition.condition.condition"));
+        assert!(err.ends_with("is nested too deeply, at most 16 levels are allowed"));
     }
 }
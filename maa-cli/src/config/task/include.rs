@@ -0,0 +1,284 @@
+//! Include other task files by the top level `__include__` key.
+//!
+//! Included paths are relative to the including file. The included files are merged in order
+//! by [`MAAValue::merge_mut`] and then the including file is merged, so its values win.
+//! Included files may include other files, up to [`MAX_DEPTH`] levels.
+
+use super::TaskConfig;
+
+use crate::{config::FromFile, value::MAAValue};
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use log::info;
+use serde_json::Value as JsonValue;
+
+/// Key of the files to include in a task file, a path or an array of paths
+pub const INCLUDE_KEY: &str = "__include__";
+
+/// Maximum depth of nested includes
+pub const MAX_DEPTH: usize = 8;
+
+/// Load a task file and resolve its includes.
+pub fn load(path: &Path) -> Result<TaskConfig> {
+    resolve(path)?
+        .into_typed()
+        .with_context(|| format!("Failed to load task file {}", path.display()))
+}
+
+/// Load a task file as a value and merge the files included by it recursively.
+///
+/// The content of the file is guessed if it has no valid extension, e.g. `/dev/stdin`.
+pub fn resolve(path: &Path) -> Result<MAAValue> {
+    let value = JsonValue::from_any_file(path)
+        .with_context(|| format!("Failed to load task file {}", path.display()))?;
+    resolve_value(path, value, &mut Vec::new())
+}
+
+/// Paths of the files directly included by a task file, empty if it can not be loaded.
+pub fn included_paths(path: &Path) -> Vec<PathBuf> {
+    let dir = path.parent().unwrap_or(Path::new(""));
+    JsonValue::from_file(path)
+        .ok()
+        .and_then(|mut value| take_includes(path, &mut value).ok().flatten())
+        .unwrap_or_default()
+        .into_iter()
+        .map(|include| dir.join(include))
+        .collect()
+}
+
+/// Remove the include key from `value` loaded from `path`, and return the included paths.
+fn take_includes(path: &Path, value: &mut JsonValue) -> Result<Option<Vec<String>>> {
+    Ok(Some(
+        match value
+            .as_object_mut()
+            .and_then(|map| map.remove(INCLUDE_KEY))
+        {
+            None => return Ok(None),
+            Some(JsonValue::String(include)) => vec![include],
+            Some(JsonValue::Array(includes)) => includes
+                .into_iter()
+                .map(|include| match include {
+                    JsonValue::String(include) => Ok(include),
+                    _ => bail!(
+                        "`{INCLUDE_KEY}` of {} must be a path or an array of paths",
+                        path.display()
+                    ),
+                })
+                .collect::<Result<_>>()?,
+            Some(_) => bail!(
+                "`{INCLUDE_KEY}` of {} must be a path or an array of paths",
+                path.display()
+            ),
+        },
+    ))
+}
+
+/// Merge the files included by `value` loaded from `path`, `stack` is the chain of including
+/// files which is used to detect cycles.
+fn resolve_value(path: &Path, mut value: JsonValue, stack: &mut Vec<PathBuf>) -> Result<MAAValue> {
+    let Some(includes) = take_includes(path, &mut value)? else {
+        return Ok(MAAValue::from_json(value));
+    };
+
+    if stack.len() >= MAX_DEPTH {
+        bail!(
+            "Too deep includes in {}, at most {MAX_DEPTH} levels are allowed",
+            path.display()
+        );
+    }
+    stack.push(path.canonicalize().unwrap_or_else(|_| path.to_owned()));
+
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let mut merged = MAAValue::new();
+    for include in includes {
+        let included = dir.join(&include);
+        let canonical = included.canonicalize().unwrap_or_else(|_| included.clone());
+        if let Some(start) = stack.iter().position(|path| path == &canonical) {
+            let chain: Vec<_> = stack[start..]
+                .iter()
+                .chain(std::iter::once(&canonical))
+                .map(|path| path.display().to_string())
+                .collect();
+            bail!("Include cycle detected: {}", chain.join(" -> "));
+        }
+
+        info!("{} includes {}", path.display(), included.display());
+        let value = JsonValue::from_file(&included).with_context(|| {
+            format!(
+                "Failed to load {} included by {}",
+                included.display(),
+                path.display()
+            )
+        })?;
+        merged.merge_mut(&resolve_value(&included, value, stack)?);
+    }
+
+    stack.pop();
+    merged.merge_mut(&MAAValue::from_json(value));
+    Ok(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::object;
+
+    use std::{env::temp_dir, fs};
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = temp_dir().join(name);
+        if dir.exists() {
+            fs::remove_dir_all(&dir).unwrap();
+        }
+        fs::create_dir_all(dir.join("common")).unwrap();
+        dir
+    }
+
+    #[test]
+    fn include_files() {
+        let dir = test_dir("maa-test-include");
+        fs::write(
+            dir.join("common").join("base.toml"),
+            r#"
+            __include__ = "fight.json"
+            startup = true
+            closedown = true
+
+            [[tasks]]
+            type = "StartUp"
+            "#,
+        )
+        .unwrap();
+        fs::write(
+            dir.join("common").join("fight.json"),
+            r#"{
+                "client_type": "Official",
+                "defaults": { "stage": "1-7", "medicine": { "default": 0 } }
+            }"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.join("common").join("extra.yaml"),
+            "closedown: false\ndefaults:\n  medicine: 1\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("daily.toml"),
+            r#"
+            __include__ = ["common/base.toml", "common/extra.yaml"]
+            startup = false
+
+            [[tasks]]
+            type = "Fight"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            resolve(&dir.join("daily.toml")).unwrap(),
+            object!(
+                "client_type" => "Official",
+                "defaults" => object!("stage" => "1-7", "medicine" => 1),
+                "startup" => false,
+                "closedown" => false,
+                "tasks" => [object!("type" => "Fight")],
+            )
+        );
+
+        let config = load(&dir.join("daily.toml")).unwrap();
+        assert_eq!(config.startup, Some(false));
+        assert_eq!(config.closedown, Some(false));
+        assert_eq!(config.tasks.len(), 1);
+
+        // files without includes are loaded as is, and inputs are kept
+        assert_eq!(
+            resolve(&dir.join("common").join("fight.json")).unwrap(),
+            object!(
+                "client_type" => "Official",
+                "defaults" => object!("stage" => "1-7", "medicine" => object!("default" => 0)),
+            )
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn include_errors() {
+        let dir = test_dir("maa-test-include-errors");
+        let path = dir.join("daily.json");
+        let err = |content: &str| {
+            fs::write(&path, content).unwrap();
+            format!("{:#}", resolve(&path).unwrap_err())
+        };
+
+        // missing file
+        let msg = err(r#"{"__include__": "missing.toml", "tasks": []}"#);
+        assert!(msg.contains(&dir.join("missing.toml").display().to_string()));
+        assert!(msg.contains(&path.display().to_string()));
+
+        // unparseable file
+        fs::write(dir.join("common").join("broken.toml"), "tasks = [").unwrap();
+        let msg = err(r#"{"__include__": ["common/broken.toml"], "tasks": []}"#);
+        assert!(msg.contains("broken.toml"));
+        assert!(msg.contains(&path.display().to_string()));
+        assert!(msg.contains("TOML deserialize error"));
+
+        // invalid include
+        let msg = err(r#"{"__include__": 1, "tasks": []}"#);
+        assert!(msg.contains("must be a path or an array of paths"));
+        let msg = err(r#"{"__include__": [1], "tasks": []}"#);
+        assert!(msg.contains("must be a path or an array of paths"));
+
+        // cycles
+        let msg = err(r#"{"__include__": "daily.json", "tasks": []}"#);
+        assert!(msg.starts_with("Include cycle detected"));
+        fs::write(
+            dir.join("common").join("a.toml"),
+            "__include__ = \"../daily.json\"",
+        )
+        .unwrap();
+        let msg = err(r#"{"__include__": "common/a.toml", "tasks": []}"#);
+        assert!(msg.starts_with("Include cycle detected"));
+        assert!(msg.contains("a.toml -> "));
+
+        // the same file can be included by different files
+        fs::write(dir.join("common").join("a.toml"), "startup = true").unwrap();
+        fs::write(
+            dir.join("common").join("b.toml"),
+            "__include__ = \"a.toml\"",
+        )
+        .unwrap();
+        fs::write(
+            &path,
+            r#"{"__include__": ["common/a.toml", "common/b.toml"], "tasks": []}"#,
+        )
+        .unwrap();
+        assert!(resolve(&path).is_ok());
+
+        // too deep includes
+        for i in 0..=MAX_DEPTH {
+            fs::write(
+                dir.join(format!("{i}.toml")),
+                format!("__include__ = \"{}.toml\"", i + 1),
+            )
+            .unwrap();
+        }
+        fs::write(dir.join(format!("{}.toml", MAX_DEPTH + 1)), "tasks = []").unwrap();
+        assert!(format!("{:#}", resolve(&dir.join("0.toml")).unwrap_err())
+            .starts_with("Too deep includes"));
+        assert!(resolve(&dir.join("1.toml")).is_ok());
+
+        assert_eq!(included_paths(&dir.join("1.toml")), [dir.join("2.toml")]);
+        assert_eq!(
+            included_paths(&path),
+            [dir.join("common/a.toml"), dir.join("common/b.toml")]
+        );
+        assert!(included_paths(&dir.join(format!("{}.toml", MAX_DEPTH + 1))).is_empty());
+        assert!(included_paths(&dir.join("missing.toml")).is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -3,26 +3,54 @@ pub use client_type::ClientType;
 
 mod condition;
 use condition::Condition;
+
 pub use condition::{remainder_of_day_mod, TimeOffset};
 
+mod overrides;
+pub use overrides::ParamOverride;
+
+pub mod include;
+
 use crate::{dirs, object, value::MAAValue};
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use anyhow::Context;
+use chrono::{DateTime, Utc};
+use log::{debug, info};
 use maa_sys::TaskType;
 use serde::Deserialize;
 
 #[cfg_attr(test, derive(PartialEq, Debug))]
 #[derive(Deserialize, Default)]
-#[serde(deny_unknown_fields)]
+#[serde(try_from = "RawTaskVariant")]
 pub struct TaskVariant {
+    condition: Condition,
+    params: MAAValue,
+}
+
+/// A task variant before its condition is checked
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawTaskVariant {
     #[serde(default)]
     condition: Condition,
     #[serde(default)]
     params: MAAValue,
 }
 
+impl TryFrom<RawTaskVariant> for TaskVariant {
+    type Error = String;
+
+    fn try_from(raw: RawTaskVariant) -> Result<Self, Self::Error> {
+        raw.condition.check()?;
+        Ok(Self {
+            condition: raw.condition,
+            params: raw.params,
+        })
+    }
+}
+
 impl TaskVariant {
     // This constructor seems to be useless,
     // because predefined task always active and ask params from user.
@@ -31,8 +59,8 @@ impl TaskVariant {
     //     Self { condition, params }
     // }
 
-    pub fn is_active(&self) -> bool {
-        self.condition.is_active()
+    pub fn is_active_at(&self, now: DateTime<Utc>) -> bool {
+        self.condition.is_active_at(now)
     }
 
     pub fn params(&self) -> &MAAValue {
@@ -111,9 +139,10 @@ impl Task {
         )
     }
 
-    pub fn is_active(&self) -> bool {
+    /// Whether any variant of the task is active at given time
+    pub fn is_active_at(&self, now: DateTime<Utc>) -> bool {
         for variant in self.variants.iter() {
-            if variant.is_active() {
+            if variant.is_active_at(now) {
                 return true;
             }
         }
@@ -124,13 +153,28 @@ impl Task {
         self.task_type
     }
 
+    /// Indices of variants whose conditions are expired at given time
+    pub fn expired_variants(&self, now: DateTime<Utc>) -> impl Iterator<Item = usize> + '_ {
+        self.variants
+            .iter()
+            .enumerate()
+            .filter(move |(_, variant)| variant.condition.is_expired_at(now))
+            .map(|(index, _)| index)
+    }
+
+    #[cfg(test)]
     pub fn params(&self) -> MAAValue {
+        self.params_at(Utc::now())
+    }
+
+    /// Get the params of the task with variants active at given time merged
+    pub fn params_at(&self, now: DateTime<Utc>) -> MAAValue {
         let mut params = self.params.clone();
         match self.strategy {
             // Merge params from the first active variant
             Strategy::First => {
                 for variant in &self.variants {
-                    if variant.is_active() {
+                    if variant.is_active_at(now) {
                         params.merge_mut(variant.params());
                         break;
                     }
@@ -139,12 +183,14 @@ impl Task {
             // Merge params from all active variants
             Strategy::Merge => {
                 for variant in &self.variants {
-                    if variant.is_active() {
+                    if variant.is_active_at(now) {
                         params.merge_mut(variant.params());
                     }
                 }
             }
         }
+        // A null value in variants means the param should be removed
+        params.strip_nulls_mut();
         params
     }
 }
@@ -154,6 +200,15 @@ pub struct TaskConfig {
     client_type: Option<ClientType>,
     startup: Option<bool>,
     closedown: Option<bool>,
+    /// Shell command to run before running tasks, consumed by maa-cli
+    #[serde(default)]
+    pre_hook: Option<String>,
+    /// Shell command to run after running tasks, consumed by maa-cli
+    ///
+    /// The status of tasks is passed by the environment variable `MAA_TASK_STATUS`,
+    /// which is `success` or `failure`.
+    #[serde(default)]
+    post_hook: Option<String>,
     tasks: Vec<Task>,
 }
 
@@ -163,6 +218,8 @@ impl TaskConfig {
             client_type: None,
             startup: None,
             closedown: None,
+            pre_hook: None,
+            post_hook: None,
             tasks: Vec::new(),
         }
     }
@@ -171,7 +228,32 @@ impl TaskConfig {
         self.tasks.push(task);
     }
 
-    pub fn init(&self) -> anyhow::Result<InitializedTaskConfig> {
+    /// Initialize the task config with params overridden by `--set`
+    ///
+    /// Overrides are applied to the params of active tasks after variants are merged,
+    /// and before inputs are initialized, so overridden inputs are not prompted.
+    /// All overrides are checked before any input is prompted.
+    pub fn init_with(&self, overrides: &[ParamOverride]) -> anyhow::Result<InitializedTaskConfig> {
+        let now = Utc::now();
+        for task in &self.tasks {
+            for index in task.expired_variants(now) {
+                info!(
+                    "Variant {index} of task {} skipped: condition expired",
+                    task.name.as_deref().unwrap_or(task.task_type.as_ref())
+                );
+            }
+        }
+
+        let mut used = vec![false; overrides.len()];
+        let mut active = Vec::new();
+        for task in self.tasks.iter().filter(|task| task.is_active_at(now)) {
+            let mut params = task.params_at(now);
+            let selected = overrides::select(overrides, &mut used, task, &params);
+            overrides::apply(&mut params, &selected)?;
+            active.push((task, params));
+        }
+        overrides::check_used(overrides, &used)?;
+
         let mut startup = self.startup;
         let mut closedown = self.closedown;
         let mut client_type = self.client_type;
@@ -181,78 +263,80 @@ impl TaskConfig {
 
         let mut tasks: Vec<InitializedTask> = Vec::new();
 
-        for task in self.tasks.iter() {
-            if task.is_active() {
-                let task_type = task.task_type();
-                let mut params = task.params().init()?;
-
-                use TaskType::*;
-                match task_type {
-                    StartUp => {
-                        let start_game = params.get_or("enable", true)
-                            && params.get_or("start_game_enabled", false);
-
-                        match (start_game, startup) {
-                            (true, None) => {
-                                startup = Some(true);
-                            }
-                            (false, Some(true)) => {
-                                params.insert("enable", true);
-                                params.insert("start_game_enabled", true);
-                            }
-                            _ => {}
-                        }
+        for (task, params) in active {
+            let task_type = task.task_type();
+            let mut params = params.init()?;
 
-                        match (params.get("client_type"), client_type) {
-                            // If client_type in task is set, set client type in config automatically
-                            (Some(t), None) => {
-                                client_type = Some(
-                                    t.as_str()
-                                        .context("client_type must be a string")?
-                                        .parse()?,
-                                );
-                            }
-                            // If client type in config is set, set client_type in task automatically
-                            (None, Some(t)) => {
-                                params.insert("client_type", t.to_string());
-                            }
-                            _ => {}
-                        }
+            use TaskType::*;
+            match task_type {
+                StartUp => {
+                    let start_game =
+                        params.get_or("enable", true) && params.get_or("start_game_enabled", false);
 
-                        prepend_startup = false;
+                    match (start_game, startup) {
+                        (true, None) => {
+                            startup = Some(true);
+                        }
+                        (false, Some(true)) => {
+                            params.insert("enable", true);
+                            params.insert("start_game_enabled", true);
+                        }
+                        _ => {}
                     }
-                    CloseDown => {
-                        match (params.get_or("enable", true), closedown) {
-                            // If closedown task is enabled, enable closedown automatically
-                            (true, None) => {
-                                closedown = Some(true);
-                            }
-                            // If closedown is enabled manually, enable closedown task automatically
-                            (false, Some(true)) => {
-                                params.insert("enable", true);
-                            }
-                            _ => {}
+
+                    match (params.get("client_type"), client_type) {
+                        // If client_type in task is set, set client type in config automatically
+                        (Some(t), None) => {
+                            client_type = Some(
+                                t.as_str()
+                                    .context("client_type must be a string")?
+                                    .parse()?,
+                            );
+                        }
+                        // If client type in config is set, set client_type in task automatically
+                        (None, Some(t)) => {
+                            params.insert("client_type", t.to_string());
                         }
+                        _ => {}
+                    }
 
-                        append_closedown = false;
+                    prepend_startup = false;
+                }
+                CloseDown => {
+                    match (params.get_or("enable", true), closedown) {
+                        // If closedown task is enabled, enable closedown automatically
+                        (true, None) => {
+                            closedown = Some(true);
+                        }
+                        // If closedown is enabled manually, enable closedown task automatically
+                        (false, Some(true)) => {
+                            params.insert("enable", true);
+                        }
+                        _ => {}
                     }
-                    _ => {
-                        // For any task that has a filename parameter
-                        // and the filename parameter is not an absolute path,
-                        // it will be treated as a relative path to the config directory
-                        // and will be converted to an absolute path.
-                        if let Some(v) = params.get("filename") {
-                            let file: PathBuf =
-                                v.as_str().context("filename must be a string")?.into();
-                            let sub_dir = task_type.as_ref().to_lowercase();
-                            if let Some(path) = dirs::abs_config(file, Some(sub_dir)) {
-                                params.insert("filename", path.to_str().context("Invilid UTF-8")?)
-                            }
+
+                    append_closedown = false;
+                }
+                _ => {
+                    // For any task that has a filename parameter
+                    // and the filename parameter is not an absolute path,
+                    // it will be treated as a relative path to the config directory
+                    // and will be converted to an absolute path.
+                    if let Some(v) = params.get("filename") {
+                        let file: PathBuf = v.as_str().context("filename must be a string")?.into();
+                        let sub_dir = task_type.as_ref().to_lowercase();
+                        if let Some(path) = dirs::abs_config(file, Some(sub_dir)) {
+                            params.insert("filename", path.to_str().context("Invilid UTF-8")?)
                         }
                     }
                 }
-                tasks.push(InitializedTask::new(task.name.clone(), task_type, params));
             }
+            debug!(
+                "Params of task {} take about {} bytes",
+                task_type.as_ref(),
+                params.size_bytes()
+            );
+            tasks.push(InitializedTask::new(task.name.clone(), task_type, params));
         }
 
         if prepend_startup {
@@ -276,6 +360,8 @@ impl TaskConfig {
             client_type,
             start_app: startup.unwrap_or(false),
             close_app: closedown.unwrap_or(false),
+            pre_hook: self.pre_hook.clone(),
+            post_hook: self.post_hook.clone(),
             tasks,
         })
     }
@@ -283,11 +369,30 @@ impl TaskConfig {
 
 impl super::FromFile for TaskConfig {}
 
+/// Load task config from a file at given path, instead of the config directory.
+///
+/// The format is determined by the extension of the file,
+/// or guessed from the content if there is no valid extension (e.g. `/dev/stdin`).
+/// Files included by `__include__` are merged, see [`include`].
+pub fn load_task_from_path(path: &Path) -> anyhow::Result<TaskConfig> {
+    include::load(path)
+}
+
+/// Parse task config from a JSON string, e.g. passed by `--task-json`.
+pub fn parse_task_json(json: &str) -> anyhow::Result<TaskConfig> {
+    serde_json::from_str(json).context("Failed to parse task config from JSON")
+}
+
 #[cfg_attr(test, derive(PartialEq, Debug))]
 pub struct InitializedTaskConfig {
     pub client_type: Option<ClientType>,
+    // Only used to launch PlayCover on macOS
+    #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
     pub start_app: bool,
+    #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
     pub close_app: bool,
+    pub pre_hook: Option<String>,
+    pub post_hook: Option<String>,
     pub tasks: Vec<InitializedTask>,
 }
 
@@ -350,7 +455,7 @@ mod tests {
                         Strategy::default(),
                         variants
                     )
-                    .is_active(),
+                    .is_active_at(Utc::now()),
                     expected
                 );
             }
@@ -378,6 +483,36 @@ mod tests {
             test_with_veriants(vec![never_active(), never_active()], false);
         }
 
+        #[test]
+        fn expired_variants() {
+            use chrono::TimeZone;
+
+            let ended = TaskVariant {
+                condition: Condition::DateTime {
+                    start: None,
+                    end: chrono::NaiveDate::from_ymd_opt(2024, 5, 15)
+                        .and_then(|date| date.and_hms_opt(4, 0, 0))
+                        .map(Into::into),
+                    timezone: TimeOffset::TimeZone(8),
+                },
+                params: object!("stage" => "SL-8"),
+            };
+            let task = Task::new(
+                None,
+                TaskType::Fight,
+                object!(),
+                Strategy::First,
+                vec![ended, TaskVariant::default()],
+            );
+
+            let during = Utc.with_ymd_and_hms(2024, 5, 14, 0, 0, 0).unwrap();
+            let after = Utc.with_ymd_and_hms(2024, 5, 14, 20, 0, 1).unwrap();
+            assert_eq!(task.expired_variants(during).count(), 0);
+            assert_eq!(task.expired_variants(after).collect::<Vec<_>>(), [0]);
+            assert_eq!(task.params_at(during), object!("stage" => "SL-8"));
+            assert_eq!(task.params_at(after), object!());
+        }
+
         #[test]
         fn get_type() {
             assert_eq!(
@@ -465,6 +600,13 @@ mod tests {
                 object!("a" => 2, "b" => 4),
             );
 
+            test_with_variants(
+                object!("a" => 1, "b" => 2),
+                Strategy::Merge,
+                vec![object!("a" => MAAValue::Null), object!("c" => 3)],
+                object!("b" => 2, "c" => 3),
+            );
+
             assert_eq!(
                 Task::new(
                     None,
@@ -488,6 +630,154 @@ mod tests {
                 object!("a" => 3, "b" => 4, "c" => 5),
             );
         }
+
+        #[test]
+        fn nested_conditions() {
+            use chrono::TimeZone;
+
+            let task: Task = toml::from_str(
+                r#"
+                type = "Fight"
+                params = { stage = "1-7" }
+
+                [[variants]]
+                params = { stage = "CE-6" }
+                [variants.condition]
+                type = "Any"
+                conditions = [
+                  { type = "Weekday", weekdays = ["Sat", "Sun"], timezone = 0 },
+                  { type = "All", conditions = [
+                    { type = "Weekday", weekdays = ["Mon", "Tue", "Wed", "Thu", "Fri"], timezone = 0 },
+                    { type = "Time", start = "20:00:00", timezone = 0 },
+                  ] },
+                ]
+
+                [[variants]]
+                "#,
+            )
+            .unwrap();
+
+            let stage = |task: &Task, d: u32, h: u32| {
+                let now = Utc.with_ymd_and_hms(2024, 5, d, h, 0, 0).unwrap();
+                task.params_at(now)
+                    .get("stage")
+                    .unwrap()
+                    .as_str()
+                    .unwrap()
+                    .to_owned()
+            };
+            // 2024-05-15 is Wednesday and 2024-05-18 is Saturday
+            assert_eq!(stage(&task, 15, 12), "1-7");
+            assert_eq!(stage(&task, 15, 20), "CE-6");
+            assert_eq!(stage(&task, 18, 12), "CE-6");
+
+            // the same condition with combinators written by keys
+            let keyed: Task = toml::from_str(
+                r#"
+                type = "Fight"
+                params = { stage = "1-7" }
+
+                [[variants]]
+                params = { stage = "CE-6" }
+                [variants.condition]
+                any = [
+                  { type = "Weekday", weekdays = ["Sat", "Sun"], timezone = 0 },
+                  { all = [
+                    { type = "Weekday", weekdays = ["Mon", "Tue", "Wed", "Thu", "Fri"], timezone = 0 },
+                    { not = { type = "Time", end = "20:00:00", timezone = 0 } },
+                  ] },
+                ]
+
+                [[variants]]
+                "#,
+            )
+            .unwrap();
+            assert_eq!(stage(&keyed, 15, 12), "1-7");
+            assert_eq!(stage(&keyed, 15, 20), "CE-6");
+            assert_eq!(stage(&keyed, 18, 12), "CE-6");
+
+            // empty combinators are allowed, an empty `Any` is never met
+            let empty: Task = toml::from_str(
+                r#"
+                type = "Fight"
+                params = { stage = "1-7" }
+                [[variants]]
+                condition = { type = "Any", conditions = [] }
+                params = { stage = "CE-6" }
+                [[variants]]
+                condition = { not = { all = [] } }
+                params = { stage = "CE-5" }
+                [[variants]]
+                "#,
+            )
+            .unwrap();
+            assert_eq!(stage(&empty, 15, 12), "1-7");
+        }
+
+        #[test]
+        fn get_params_at() {
+            use chrono::{NaiveTime, TimeZone};
+
+            fn utc(h: u32) -> DateTime<Utc> {
+                Utc.with_ymd_and_hms(2024, 2, 14, h, 0, 0).unwrap()
+            }
+
+            let evening = || Condition::Time {
+                start: NaiveTime::from_hms_opt(18, 0, 0),
+                end: NaiveTime::from_hms_opt(4, 0, 0),
+                timezone: TimeOffset::TimeZone(0),
+            };
+
+            let task = Task::new(
+                None,
+                TaskType::Fight,
+                object!("stage" => "1-7"),
+                Strategy::Merge,
+                vec![
+                    TaskVariant {
+                        condition: evening(),
+                        params: object!("stage" => "CE-6"),
+                    },
+                    TaskVariant {
+                        condition: Condition::Always,
+                        params: object!("times" => 1),
+                    },
+                    TaskVariant {
+                        condition: evening(),
+                        params: object!("times" => 2),
+                    },
+                ],
+            );
+            assert_eq!(
+                task.params_at(utc(12)),
+                object!("stage" => "1-7", "times" => 1)
+            );
+            assert_eq!(
+                task.params_at(utc(18)),
+                object!("stage" => "CE-6", "times" => 2)
+            );
+            assert_eq!(
+                task.params_at(utc(3)),
+                object!("stage" => "CE-6", "times" => 2)
+            );
+            assert_eq!(
+                task.params_at(utc(4)),
+                object!("stage" => "1-7", "times" => 1)
+            );
+
+            let task = Task::new(
+                None,
+                TaskType::Fight,
+                object!(),
+                Strategy::First,
+                vec![TaskVariant {
+                    condition: evening(),
+                    params: object!("stage" => "CE-6"),
+                }],
+            );
+            assert!(task.is_active_at(utc(20)));
+            assert!(!task.is_active_at(utc(12)));
+        }
     }
 
     mod task_config {
@@ -575,8 +865,8 @@ mod tests {
                         },
                         TaskVariant {
                             condition: Condition::DateTime {
-                                start: Some(naive_local_datetime(2023, 8, 1, 16, 0, 0)),
-                                end: Some(naive_local_datetime(2023, 8, 21, 3, 59, 59)),
+                                start: Some(naive_local_datetime(2023, 8, 1, 16, 0, 0).into()),
+                                end: Some(naive_local_datetime(2023, 8, 21, 3, 59, 59).into()),
                                 timezone: TimeOffset::TimeZone(8),
                             },
                             params: object!(
@@ -653,12 +943,29 @@ mod tests {
                 .unwrap();
                 assert_eq!(task_config.tasks, example_task_config().tasks)
             }
+
+            #[test]
+            fn from_path_and_json() {
+                let task_config =
+                    load_task_from_path(Path::new("./config_examples/tasks/daily.toml")).unwrap();
+                assert_eq!(task_config.tasks, example_task_config().tasks);
+
+                let json = std::fs::read_to_string("./config_examples/tasks/daily.json").unwrap();
+                assert_eq!(
+                    parse_task_json(&json).unwrap().tasks,
+                    example_task_config().tasks
+                );
+                assert!(parse_task_json("{").is_err());
+                assert!(load_task_from_path(Path::new("./not_exist.json")).is_err());
+            }
         }
 
         #[test]
         fn init() {
             assert_eq!(
                 TaskConfig {
+                    pre_hook: None,
+                    post_hook: None,
                     client_type: None,
                     startup: None,
                     closedown: None,
@@ -674,9 +981,11 @@ mod tests {
                         Task::new_with_default(CloseDown, object!()),
                     ],
                 }
-                .init()
+                .init_with(&[])
                 .unwrap(),
                 InitializedTaskConfig {
+                    pre_hook: None,
+                    post_hook: None,
                     client_type: Some(ClientType::Official),
                     start_app: true,
                     close_app: true,
@@ -696,6 +1005,8 @@ mod tests {
 
             assert_eq!(
                 TaskConfig {
+                    pre_hook: None,
+                    post_hook: None,
                     client_type: Some(ClientType::Official),
                     startup: Some(true),
                     closedown: Some(true),
@@ -705,9 +1016,11 @@ mod tests {
                         Task::new_with_default(CloseDown, object!("enable" => false)),
                     ],
                 }
-                .init()
+                .init_with(&[])
                 .unwrap(),
                 InitializedTaskConfig {
+                    pre_hook: None,
+                    post_hook: None,
                     client_type: Some(ClientType::Official),
                     start_app: true,
                     close_app: true,
@@ -728,14 +1041,18 @@ mod tests {
 
             assert_eq!(
                 TaskConfig {
+                    pre_hook: None,
+                    post_hook: None,
                     client_type: None,
                     startup: Some(true),
                     closedown: Some(true),
                     tasks: vec![Task::new_with_default(Fight, object!("stage" => "1-7"))],
                 }
-                .init()
+                .init_with(&[])
                 .unwrap(),
                 InitializedTaskConfig {
+                    pre_hook: None,
+                    post_hook: None,
                     client_type: None,
                     start_app: true,
                     close_app: true,
@@ -755,14 +1072,18 @@ mod tests {
 
             assert_eq!(
                 TaskConfig {
+                    pre_hook: None,
+                    post_hook: None,
                     client_type: Some(ClientType::YoStarEN),
                     startup: Some(true),
                     closedown: Some(true),
                     tasks: vec![Task::new_with_default(Fight, object!("stage" => "1-7"))],
                 }
-                .init()
+                .init_with(&[])
                 .unwrap(),
                 InitializedTaskConfig {
+                    pre_hook: None,
+                    post_hook: None,
                     client_type: Some(ClientType::YoStarEN),
                     start_app: true,
                     close_app: true,
@@ -781,6 +1102,104 @@ mod tests {
             )
         }
 
+        #[test]
+        fn init_with_overrides() {
+            use crate::value::userinput::Input;
+
+            let task_config = TaskConfig {
+                pre_hook: None,
+                post_hook: None,
+                client_type: None,
+                startup: None,
+                closedown: None,
+                tasks: vec![
+                    Task::new(
+                        Some("Fight Daily".to_string()),
+                        Fight,
+                        object!(
+                            "stage" => Input::new(Some("1-7".to_string()), None),
+                            "medicine" => [0, 0],
+                        ),
+                        Strategy::default(),
+                        default_variants(),
+                    ),
+                    Task::new_with_default(Infrast, object!("mode" => 0)),
+                ],
+            };
+            let overrides = |flags: &[&str]| -> Vec<ParamOverride> {
+                flags.iter().map(|flag| flag.parse().unwrap()).collect()
+            };
+
+            let initialized = task_config
+                .init_with(&overrides(&[
+                    "stage=CE-6",
+                    "medicine.1=3",
+                    "Fight Daily:times=2",
+                    "infrast:drones=Money",
+                ]))
+                .unwrap();
+            assert_eq!(
+                initialized.tasks,
+                vec![
+                    InitializedTask::new(
+                        Some("Fight Daily".to_string()),
+                        Fight,
+                        object!(
+                            "stage" => "CE-6",
+                            "medicine" => [0, 3],
+                            "times" => 2,
+                        ),
+                    ),
+                    InitializedTask::new_noname(Infrast, object!("mode" => 0, "drones" => "Money"),),
+                ]
+            );
+
+            assert!(task_config.init_with(&overrides(&["times=2"])).is_err());
+            assert!(task_config.init_with(&overrides(&["Mall:mode=1"])).is_err());
+            assert!(task_config
+                .init_with(&overrides(&["medicine.3=1"]))
+                .is_err());
+            assert!(task_config.init_with(&overrides(&["mode=x"])).is_err());
+        }
+
+        #[test]
+        fn hooks() {
+            let task_config = parse_task_json(
+                r#"{
+                    "pre_hook": "echo start",
+                    "post_hook": "echo $MAA_TASK_STATUS",
+                    "tasks": []
+                }"#,
+            )
+            .unwrap();
+            let initialized = task_config.init_with(&[]).unwrap();
+            assert_eq!(initialized.pre_hook.as_deref(), Some("echo start"));
+            assert_eq!(
+                initialized.post_hook.as_deref(),
+                Some("echo $MAA_TASK_STATUS")
+            );
+
+            let task_config: TaskConfig = toml::from_str(
+                r#"
+                post_hook = "notify-send done"
+                tasks = []
+                "#,
+            )
+            .unwrap();
+            let initialized = task_config.init_with(&[]).unwrap();
+            assert_eq!(initialized.pre_hook, None);
+            assert_eq!(initialized.post_hook.as_deref(), Some("notify-send done"));
+
+            let initialized = parse_task_json(r#"{"tasks": []}"#)
+                .unwrap()
+                .init_with(&[])
+                .unwrap();
+            assert_eq!(initialized.pre_hook, None);
+            assert_eq!(initialized.post_hook, None);
+
+            assert!(parse_task_json(r#"{"pre_hook": 1, "tasks": []}"#).is_err());
+        }
+
         #[test]
         fn initialized_task() {
             let task = InitializedTask::new(
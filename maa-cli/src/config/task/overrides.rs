@@ -0,0 +1,244 @@
+use super::Task;
+
+use crate::value::{MAAValue, Map, ValueType};
+
+/// An override of task params given by `--set [TASK:]KEY=VALUE`
+///
+/// The key is a dot-notation path in the params, e.g. `stage` or `medicine.0`,
+/// see [`MAAValue::into_flat_map`]. An optional name or type of task before the key selects
+/// the tasks to override, otherwise the tasks whose params contain the first segment
+/// of the key are overridden.
+#[cfg_attr(test, derive(Debug, PartialEq))]
+#[derive(Clone)]
+pub struct ParamOverride {
+    flag: String,
+    task: Option<String>,
+    key: String,
+    value: String,
+}
+
+impl std::str::FromStr for ParamOverride {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (target, value) = s
+            .split_once('=')
+            .ok_or_else(|| format!("invalid override `{s}`, expected `[TASK:]KEY=VALUE`"))?;
+        let (task, key) = match target.split_once(':') {
+            Some((task, key)) => (Some(task.to_owned()), key),
+            None => (None, target),
+        };
+        if key.is_empty() || task.as_ref().is_some_and(String::is_empty) {
+            return Err(format!(
+                "invalid override `{s}`, the task and key must not be empty"
+            ));
+        }
+        Ok(Self {
+            flag: s.to_owned(),
+            task,
+            key: key.to_owned(),
+            value: value.to_owned(),
+        })
+    }
+}
+
+impl ParamOverride {
+    /// Whether the override applies to given task with its params
+    fn matches(&self, task: &Task, params: &MAAValue) -> bool {
+        match &self.task {
+            Some(selector) => {
+                task.name.as_deref() == Some(selector.as_str())
+                    || task.task_type.as_ref().eq_ignore_ascii_case(selector)
+            }
+            None => {
+                let first = self.key.split('.').next().unwrap_or_default();
+                params.get(first).is_some()
+            }
+        }
+    }
+
+    /// Parse the value as the type of the existing value, or infer the type if there is none
+    ///
+    /// The type can not be known from inputs, so they are replaced by inferred values,
+    /// e.g. `3` is an int, `true` is a bool and `CE-6` is a string.
+    fn typed_value(&self, existing: Option<&MAAValue>) -> Result<MAAValue, String> {
+        let raw = self.value.as_str();
+        let expected = existing.map(MAAValue::value_type);
+        let mismatch = |expected: ValueType| format!("`{raw}` is not a valid {expected}");
+        match expected {
+            Some(ValueType::Bool) => raw
+                .parse::<bool>()
+                .map(MAAValue::from)
+                .map_err(|_| mismatch(ValueType::Bool)),
+            Some(ValueType::Int) => raw
+                .parse::<i32>()
+                .map(MAAValue::from)
+                .map_err(|_| mismatch(ValueType::Int)),
+            Some(ValueType::Float) => raw
+                .parse::<f32>()
+                .map(MAAValue::from)
+                .map_err(|_| mismatch(ValueType::Float)),
+            Some(ValueType::String) => Ok(raw.into()),
+            Some(expected @ (ValueType::Array | ValueType::Object)) => serde_json::from_str(raw)
+                .map(MAAValue::from_json)
+                .ok()
+                .filter(|value| value.value_type() == expected)
+                .ok_or_else(|| mismatch(expected)),
+            _ => Ok(serde_json::from_str(raw)
+                .map(MAAValue::from_json)
+                .unwrap_or_else(|_| raw.into())),
+        }
+    }
+}
+
+/// Apply overrides to the params of a task, the first error is returned with the flag.
+pub(super) fn apply(params: &mut MAAValue, overrides: &[&ParamOverride]) -> anyhow::Result<()> {
+    if overrides.is_empty() {
+        return Ok(());
+    }
+
+    let mut values = Map::new();
+    for o in overrides {
+        let value = params
+            .get_flat(&o.key)
+            .and_then(|existing| o.typed_value(existing))
+            .map_err(|err| anyhow::anyhow!("Invalid `--set {}`: {err}", o.flag))?;
+        values.insert(o.key.clone(), value);
+    }
+    let patch = params.flat_patch(values);
+    params.merge_mut(&patch);
+    Ok(())
+}
+
+/// Select the overrides applying to given task, and mark them as used
+pub(super) fn select<'a>(
+    overrides: &'a [ParamOverride],
+    used: &mut [bool],
+    task: &Task,
+    params: &MAAValue,
+) -> Vec<&'a ParamOverride> {
+    overrides
+        .iter()
+        .zip(used.iter_mut())
+        .filter(|(o, _)| o.matches(task, params))
+        .map(|(o, used)| {
+            *used = true;
+            o
+        })
+        .collect()
+}
+
+/// Return an error for the first override which applies to no task
+pub(super) fn check_used(overrides: &[ParamOverride], used: &[bool]) -> anyhow::Result<()> {
+    if let Some((o, _)) = overrides.iter().zip(used).find(|(_, used)| !**used) {
+        match &o.task {
+            Some(task) => anyhow::bail!("Invalid `--set {}`: no task named `{task}`", o.flag),
+            None => anyhow::bail!(
+                "Invalid `--set {}`: no task has param `{}`, \
+                 prefix it with the name or type of a task, e.g. `Fight:{}`",
+                o.flag,
+                o.key.split('.').next().unwrap_or_default(),
+                o.flag
+            ),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::object;
+
+    fn parse(s: &str) -> ParamOverride {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parse_override() {
+        assert_eq!(
+            parse("stage=CE-6"),
+            ParamOverride {
+                flag: "stage=CE-6".to_owned(),
+                task: None,
+                key: "stage".to_owned(),
+                value: "CE-6".to_owned(),
+            }
+        );
+        assert_eq!(
+            parse("Fight:medicine.0=1=2"),
+            ParamOverride {
+                flag: "Fight:medicine.0=1=2".to_owned(),
+                task: Some("Fight".to_owned()),
+                key: "medicine.0".to_owned(),
+                value: "1=2".to_owned(),
+            }
+        );
+        assert_eq!(parse("stage=").value, "");
+
+        assert!("stage".parse::<ParamOverride>().is_err());
+        assert!("=1".parse::<ParamOverride>().is_err());
+        assert!(":stage=1".parse::<ParamOverride>().is_err());
+    }
+
+    #[test]
+    fn typed_value() {
+        let o = parse("key=3");
+        assert_eq!(o.typed_value(None), Ok(MAAValue::from(3)));
+        assert_eq!(o.typed_value(Some(&"1-7".into())), Ok(MAAValue::from("3")));
+        assert_eq!(o.typed_value(Some(&1.0.into())), Ok(MAAValue::from(3.0)));
+        assert_eq!(
+            o.typed_value(Some(&true.into())),
+            Err("`3` is not a valid bool".to_owned())
+        );
+
+        assert_eq!(
+            parse("key=CE-6").typed_value(None),
+            Ok(MAAValue::from("CE-6"))
+        );
+        assert_eq!(
+            parse("key=[1, 2]").typed_value(Some(&MAAValue::from([0]))),
+            Ok(MAAValue::from([1, 2]))
+        );
+        assert_eq!(
+            parse("key=1").typed_value(Some(&MAAValue::from([0]))),
+            Err("`1` is not a valid array".to_owned())
+        );
+        assert_eq!(
+            parse("key=x").typed_value(Some(&1.into())),
+            Err("`x` is not a valid int".to_owned())
+        );
+    }
+
+    #[test]
+    fn apply_overrides() {
+        let mut params = object!("stage" => "1-7", "medicine" => [1, 2]);
+        let overrides = [
+            parse("stage=CE-6"),
+            parse("medicine.1=5"),
+            parse("a.b=true"),
+        ];
+        apply(&mut params, &overrides.iter().collect::<Vec<_>>()).unwrap();
+        assert_eq!(
+            params,
+            object!(
+                "stage" => "CE-6",
+                "medicine" => [1, 5],
+                "a" => object!("b" => true),
+            )
+        );
+
+        let err = apply(&mut params, &[&parse("medicine.3=1")]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid `--set medicine.3=1`: \
+             index 3 is out of bounds of the array at `medicine` with 2 elements"
+        );
+        let err = apply(&mut params, &[&parse("medicine=x")]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid `--set medicine=x`: `x` is not a valid array"
+        );
+    }
+}
@@ -0,0 +1,213 @@
+//! Known parameters of MAA tasks with their default values, used to generate task templates.
+//!
+//! Parameters are taken from the integration document of MaaCore. Required parameters
+//! without a sensible default are user inputs with only a description, so they are queried
+//! when the task is run unless they are filled in. Empty objects are avoided, since they
+//! are parsed as user inputs in task files.
+
+use std::{collections::BTreeMap, sync::OnceLock};
+
+use maa_sys::TaskType;
+use serde_json::{json, Value as JsonValue};
+
+/// Parameters of each task type, keyed by the name of the task type
+static SCHEMAS: OnceLock<BTreeMap<&'static str, JsonValue>> = OnceLock::new();
+
+fn schemas() -> &'static BTreeMap<&'static str, JsonValue> {
+    SCHEMAS.get_or_init(|| {
+        BTreeMap::from([
+            (
+                "StartUp",
+                json!({
+                    "client_type": "Official",
+                    "start_game_enabled": true,
+                    "account_name": "",
+                }),
+            ),
+            (
+                "CloseDown",
+                json!({
+                    "client_type": "Official",
+                }),
+            ),
+            (
+                "Fight",
+                json!({
+                    "stage": "",
+                    "medicine": 0,
+                    "expiring_medicine": 0,
+                    "stone": 0,
+                    "times": 2147483647,
+                    "series": 1,
+                    "report_to_penguin": false,
+                    "penguin_id": "",
+                    "server": "CN",
+                    "client_type": "Official",
+                    "DrGrandet": false,
+                }),
+            ),
+            (
+                "Recruit",
+                json!({
+                    "refresh": false,
+                    "select": [4],
+                    "confirm": [3, 4],
+                    "first_tags": [],
+                    "extra_tags_mode": 0,
+                    "times": 0,
+                    "set_time": true,
+                    "expedite": false,
+                    "skip_robot": true,
+                    "recruitment_time": { "3": 540, "4": 540 },
+                    "report_to_penguin": false,
+                    "penguin_id": "",
+                    "report_to_yituliu": false,
+                    "yituliu_id": "",
+                    "server": "CN",
+                }),
+            ),
+            (
+                "Infrast",
+                json!({
+                    "mode": 0,
+                    "facility": ["Mfg", "Trade", "Power", "Control", "Reception", "Office", "Dorm"],
+                    "drones": "_NotUse",
+                    "threshold": 0.3,
+                    "replenish": false,
+                    "dorm_notstationed_enabled": false,
+                    "dorm_trust_enabled": false,
+                }),
+            ),
+            (
+                "Mall",
+                json!({
+                    "shopping": true,
+                    "buy_first": [],
+                    "blacklist": [],
+                    "force_shopping_if_credit_full": false,
+                    "only_buy_discount": false,
+                    "reserve_max_credit": false,
+                }),
+            ),
+            (
+                "Award",
+                json!({
+                    "award": true,
+                    "mail": false,
+                    "recruit": false,
+                    "orundum": false,
+                    "mining": false,
+                    "specialaccess": false,
+                }),
+            ),
+            (
+                "Roguelike",
+                json!({
+                    "theme": "Phantom",
+                    "mode": 0,
+                    "squad": "",
+                    "roles": "",
+                    "core_char": "",
+                    "use_support": false,
+                    "use_nonfriend_support": false,
+                    "starts_count": 2147483647,
+                    "investment_enabled": true,
+                    "investments_count": 2147483647,
+                    "stop_when_investment_full": false,
+                }),
+            ),
+            (
+                "Copilot",
+                json!({
+                    "filename": { "description": "path or URI of the copilot file" },
+                    "formation": false,
+                }),
+            ),
+            (
+                "SSSCopilot",
+                json!({
+                    "filename": { "description": "path or URI of the copilot file" },
+                    "loop_times": 1,
+                }),
+            ),
+            ("Depot", json!({})),
+            ("OperBox", json!({})),
+            (
+                "ReclamationAlgorithm",
+                json!({
+                    "theme": 1,
+                    "mode": 0,
+                }),
+            ),
+            (
+                "Custom",
+                json!({
+                    "task_names": [],
+                }),
+            ),
+            (
+                "SingleStep",
+                json!({
+                    "type": "copilot",
+                    "subtype": "stage",
+                }),
+            ),
+            (
+                "VideoRecognition",
+                json!({
+                    "filename": { "description": "path of the video file" },
+                }),
+            ),
+        ])
+    })
+}
+
+/// Parameters of given task type with their default values
+pub fn params(task_type: TaskType) -> Option<&'static JsonValue> {
+    schemas().get(task_type.as_ref())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::value::MAAValue;
+
+    const TASK_TYPES: [&str; 16] = [
+        "StartUp",
+        "CloseDown",
+        "Fight",
+        "Recruit",
+        "Infrast",
+        "Mall",
+        "Award",
+        "Roguelike",
+        "Copilot",
+        "SSSCopilot",
+        "Depot",
+        "OperBox",
+        "ReclamationAlgorithm",
+        "Custom",
+        "SingleStep",
+        "VideoRecognition",
+    ];
+
+    #[test]
+    fn all_task_types() {
+        assert_eq!(schemas().len(), TASK_TYPES.len());
+        for name in TASK_TYPES {
+            let task_type: TaskType = name.parse().unwrap();
+            let params = params(task_type).unwrap_or_else(|| panic!("no schema of {name}"));
+            assert!(params.is_object(), "params of {name} is not an object");
+            // all parameters are valid values of task files
+            let value: MAAValue = serde_json::from_value(params.clone()).unwrap();
+            assert!(
+                params.as_object().unwrap().is_empty() || value.as_object().is_some(),
+                "params of {name} are not parsed as an object"
+            );
+            // params of all task types can be converted between values and JSON
+            let converted = MAAValue::from_json(params.clone());
+            assert_eq!(MAAValue::from_json(converted.to_json().unwrap()), converted);
+        }
+    }
+}
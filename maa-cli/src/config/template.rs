@@ -0,0 +1,136 @@
+use super::{schema_url, task::TaskConfig, task_schemas, Filetype};
+
+use crate::{dirs::Ensure, value::SCHEMA_KEY};
+
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use maa_sys::TaskType;
+use serde_json::{json, Value as JsonValue};
+
+/// Generate a task config of given task type, with all known parameters set to their defaults.
+///
+/// The format defaults to the extension of `output`, or TOML. The result is written to
+/// `output` if given, which must not exist, otherwise to stdout.
+pub fn new_task(
+    task_type: TaskType,
+    output: Option<&Path>,
+    format: Option<Filetype>,
+) -> Result<()> {
+    let format = format
+        .or_else(|| output.and_then(Filetype::parse_filetype))
+        .unwrap_or(Filetype::Toml);
+
+    let content = generate(task_type, format)?;
+
+    match output {
+        Some(output) => {
+            if output.exists() {
+                bail!("{} already exists", output.display());
+            }
+            if let Some(dir) = output.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+                dir.ensure()?;
+            }
+            std::fs::write(output, content)
+                .with_context(|| format!("Failed to write to {}", output.display()))?;
+            println!(
+                "Task template of {task_type} is written to {}",
+                output.display()
+            );
+        }
+        None => print!("{content}"),
+    }
+
+    Ok(())
+}
+
+fn template(task_type: TaskType) -> Result<JsonValue> {
+    let params = task_schemas::params(task_type)
+        .with_context(|| format!("No template of task type {task_type}"))?;
+
+    let mut task = json!({ "type": task_type.as_ref() });
+    // Empty params are omitted, since an empty object is parsed as a user input
+    if params.as_object().is_some_and(|params| !params.is_empty()) {
+        task["params"] = params.clone();
+    }
+    Ok(json!({ SCHEMA_KEY: schema_url(), "tasks": [task] }))
+}
+
+fn generate(task_type: TaskType, format: Filetype) -> Result<String> {
+    let value = template(task_type)?;
+    // Make sure the template can be loaded as a task config
+    serde_json::from_value::<TaskConfig>(value.clone())
+        .with_context(|| format!("Invalid template of task type {task_type}"))?;
+
+    let mut buf = Vec::new();
+    format.write(&mut buf, &value)?;
+    if !buf.ends_with(b"\n") {
+        buf.push(b'\n');
+    }
+    Ok(String::from_utf8(buf)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        config::FromFile,
+        value::{MAAInput, MAAValue},
+    };
+
+    use std::{env::temp_dir, fs};
+
+    #[test]
+    fn generate_templates() {
+        let fight = generate(TaskType::Fight, Filetype::Toml).unwrap();
+        assert!(fight.starts_with(&format!("\"$schema\" = \"{}\"\n", schema_url())));
+        assert!(fight.contains("[[tasks]]\ntype = \"Fight\"\n"));
+        assert!(fight.contains("medicine = 0\n"));
+
+        let depot = generate(TaskType::Depot, Filetype::Json).unwrap();
+        let value: JsonValue = serde_json::from_str(&depot).unwrap();
+        assert_eq!(
+            value,
+            json!({ "$schema": schema_url(), "tasks": [{ "type": "Depot" }] })
+        );
+
+        let copilot = generate(TaskType::Copilot, Filetype::Yaml).unwrap();
+        assert!(copilot.contains("type: Copilot"));
+    }
+
+    #[test]
+    fn write_template() {
+        let dir = temp_dir().join("maa-test-task-template");
+        if dir.exists() {
+            fs::remove_dir_all(&dir).unwrap();
+        }
+
+        for (task_type, name) in [
+            (TaskType::Fight, "fight.toml"),
+            (TaskType::Copilot, "copilot.json"),
+            (TaskType::Infrast, "infrast.yaml"),
+        ] {
+            let path = dir.join(name);
+            new_task(task_type, Some(&path), None).unwrap();
+
+            TaskConfig::from_file(&path).unwrap();
+            let params = JsonValue::from_file(&path).unwrap()["tasks"][0]["params"].clone();
+            let params: MAAValue = serde_json::from_value(params).unwrap();
+            match task_type {
+                // required parameters are user inputs
+                TaskType::Copilot => assert!(matches!(
+                    params.get("filename"),
+                    Some(MAAValue::Input(MAAInput::InputString(_)))
+                )),
+                TaskType::Fight => assert_eq!(params.get("stage"), Some(&MAAValue::from(""))),
+                _ => assert_eq!(params.get("threshold"), Some(&MAAValue::from(0.3))),
+            }
+        }
+
+        // existing files are not overwritten
+        assert!(new_task(TaskType::Mall, Some(&dir.join("fight.toml")), None).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,334 @@
+use super::{asst::AsstConfig, cli::CLIConfig, task::include, Filetype, FromFile};
+
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+use clap::ValueEnum;
+use prettytable::{format, row, Table};
+use rayon::prelude::*;
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+
+/// Kind of a configuration file, determined by its location in the config directory.
+#[cfg_attr(test, derive(Debug, PartialEq))]
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfigKind {
+    /// Task files in `tasks` directory
+    Task,
+    /// Files in `tasks` directory included by other task files, which are not complete task
+    /// files, so they are only checked to be well-formed with resolvable includes
+    Fragment,
+    /// MaaCore configurations in `profiles` directory
+    Profile,
+    /// CLI configuration `cli.toml` (or other extensions) in the root
+    Cli,
+    /// Other files, e.g. infrast plans, which are only checked to be well-formed
+    Other,
+}
+
+impl ConfigKind {
+    pub fn from_path(root: &Path, path: &Path) -> Self {
+        let mut relative = path.strip_prefix(root).unwrap_or(path);
+        // Files in a profile directory `profiles/<name>` are classified as in the config directory
+        let mut components = relative.components();
+        if let (Some(first), Some(_)) = (components.next(), components.next()) {
+            if first.as_os_str() == crate::dirs::PROFILES_DIR && components.clone().next().is_some()
+            {
+                relative = components.as_path();
+            }
+        }
+        let mut components = relative.components();
+        match (components.next(), components.next()) {
+            (Some(first), Some(_)) if first.as_os_str() == "tasks" => ConfigKind::Task,
+            (Some(first), Some(_)) if first.as_os_str() == "profiles" => ConfigKind::Profile,
+            (Some(_), None) if relative.file_stem().is_some_and(|s| s == "cli") => ConfigKind::Cli,
+            _ => ConfigKind::Other,
+        }
+    }
+}
+
+impl std::fmt::Display for ConfigKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigKind::Task => write!(f, "task"),
+            ConfigKind::Fragment => write!(f, "fragment"),
+            ConfigKind::Profile => write!(f, "profile"),
+            ConfigKind::Cli => write!(f, "cli"),
+            ConfigKind::Other => write!(f, "other"),
+        }
+    }
+}
+
+/// Check whether a configuration file can be loaded as given kind.
+///
+/// Files included by task files are merged before checking, see [`include`].
+pub fn validate_config(path: &Path, kind: ConfigKind) -> anyhow::Result<()> {
+    match kind {
+        ConfigKind::Task => include::load(path).map(|_| ()),
+        ConfigKind::Fragment => include::resolve(path).map(|_| ()),
+        ConfigKind::Profile => Ok(AsstConfig::from_file(path).map(|_| ())?),
+        ConfigKind::Cli => Ok(CLIConfig::from_file(path).map(|_| ())?),
+        ConfigKind::Other => Ok(JsonValue::from_file(path).map(|_| ())?),
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Default)]
+pub enum OutputFormat {
+    /// Human readable table
+    #[default]
+    Text,
+    /// JSON array of reports, one for each file
+    Json,
+}
+
+#[cfg_attr(test, derive(Debug))]
+#[derive(Serialize)]
+pub struct Report {
+    path: PathBuf,
+    kind: ConfigKind,
+    valid: bool,
+    error: Option<String>,
+}
+
+/// Validate all configuration files in given directory, the config directory by default.
+///
+/// A summary is printed in given format, and an error is returned if any file is invalid.
+pub fn validate_all(dir: Option<&Path>, output: OutputFormat) -> anyhow::Result<()> {
+    let root = dir.unwrap_or_else(|| crate::dirs::config());
+    let reports = validate_dir(root)?;
+
+    match output {
+        OutputFormat::Text => {
+            if reports.is_empty() {
+                println!("No configuration files found in {}", root.display());
+            } else {
+                report_table(root, &reports).printstd();
+            }
+        }
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&reports)?),
+    }
+
+    let failed = reports.iter().filter(|r| !r.valid).count();
+    if failed > 0 {
+        anyhow::bail!(
+            "{failed} of {} configuration files are invalid",
+            reports.len()
+        );
+    }
+
+    Ok(())
+}
+
+impl Report {
+    pub fn kind(&self) -> ConfigKind {
+        self.kind
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+}
+
+/// Validate all configuration files in given directory without printing anything.
+pub fn validate_dir(root: &Path) -> std::io::Result<Vec<Report>> {
+    let mut files = Vec::new();
+    collect_files(root, &mut files)?;
+
+    // Task files included by other task files are fragments
+    let fragments: HashSet<PathBuf> = files
+        .par_iter()
+        .filter(|path| matches!(ConfigKind::from_path(root, path), ConfigKind::Task))
+        .flat_map_iter(|path| include::included_paths(path))
+        .filter_map(|path| path.canonicalize().ok())
+        .collect();
+
+    // Loading files is mostly IO bound, so load them in parallel
+    let mut reports: Vec<Report> = files
+        .par_iter()
+        .map(|path| {
+            let kind = match ConfigKind::from_path(root, path) {
+                ConfigKind::Task
+                    if path
+                        .canonicalize()
+                        .is_ok_and(|path| fragments.contains(&path)) =>
+                {
+                    ConfigKind::Fragment
+                }
+                kind => kind,
+            };
+            let result = validate_config(path, kind);
+            Report {
+                path: path.clone(),
+                kind,
+                valid: result.is_ok(),
+                error: result.err().map(|e| format!("{e:#}")),
+            }
+        })
+        .collect();
+
+    // Sort reports by path, so errors are reported in a stable order
+    reports.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Ok(reports)
+}
+
+/// Collect all files with supported extensions in given directory recursively.
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in dir.read_dir()? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else if Filetype::is_valid_file(&path) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn report_table(root: &Path, reports: &[Report]) -> Table {
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
+    table.set_titles(row!["FILE", "TYPE", "STATUS", "ERROR"]);
+    for report in reports {
+        table.add_row(row![
+            report
+                .path
+                .strip_prefix(root)
+                .unwrap_or(&report.path)
+                .display(),
+            report.kind,
+            if report.valid { "PASS" } else { "FAIL" },
+            report.error.as_deref().unwrap_or("")
+        ]);
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::config::task::TaskConfig;
+
+    use std::{env::temp_dir, fs};
+
+    #[test]
+    fn loaded_configs_are_send_and_sync() {
+        // Files are loaded in parallel, so loaded configs must be shareable between threads
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<crate::value::MAAValue>();
+        assert_send_sync::<TaskConfig>();
+        assert_send_sync::<AsstConfig>();
+        assert_send_sync::<CLIConfig>();
+        assert_send_sync::<Report>();
+    }
+
+    #[test]
+    fn kind_from_path() {
+        let root = Path::new("/config");
+        let kind = |path: &str| ConfigKind::from_path(root, &root.join(path));
+
+        assert_eq!(kind("tasks/daily.toml"), ConfigKind::Task);
+        assert_eq!(kind("tasks/sub/daily.toml"), ConfigKind::Task);
+        assert_eq!(kind("profiles/default.json"), ConfigKind::Profile);
+        assert_eq!(kind("profiles/alt/tasks/daily.toml"), ConfigKind::Task);
+        assert_eq!(
+            kind("profiles/alt/profiles/default.json"),
+            ConfigKind::Profile
+        );
+        assert_eq!(kind("profiles/alt/cli.toml"), ConfigKind::Cli);
+        assert_eq!(kind("profiles/alt/infrast/plan.json"), ConfigKind::Other);
+        assert_eq!(kind("cli.toml"), ConfigKind::Cli);
+        assert_eq!(kind("tasks.toml"), ConfigKind::Other);
+        assert_eq!(kind("infrast/plan.json"), ConfigKind::Other);
+    }
+
+    #[test]
+    fn validate() {
+        let root = temp_dir().join("maa-test-validate-all");
+        if root.exists() {
+            fs::remove_dir_all(&root).unwrap();
+        }
+        fs::create_dir_all(root.join("tasks")).unwrap();
+        fs::create_dir_all(root.join("profiles")).unwrap();
+        fs::create_dir_all(root.join("infrast")).unwrap();
+
+        fs::write(
+            root.join("tasks").join("valid.toml"),
+            "[[tasks]]\ntype = \"StartUp\"\n",
+        )
+        .unwrap();
+        fs::write(root.join("tasks").join("invalid.json"), r#"{"foo": 1}"#).unwrap();
+        fs::write(root.join("tasks").join("README.md"), "not a config").unwrap();
+        fs::write(root.join("profiles").join("default.json"), "{}").unwrap();
+        fs::write(root.join("cli.toml"), "[core]\ntest_time = 0\n").unwrap();
+        fs::write(root.join("infrast").join("plan.json"), "{").unwrap();
+        fs::create_dir_all(root.join("tasks").join("common")).unwrap();
+        fs::write(
+            root.join("tasks").join("common").join("base.toml"),
+            "startup = true\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("tasks").join("daily.toml"),
+            "__include__ = [\"common/base.toml\"]\n[[tasks]]\ntype = \"StartUp\"\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("tasks").join("broken.toml"),
+            "__include__ = \"missing.toml\"\n[[tasks]]\ntype = \"StartUp\"\n",
+        )
+        .unwrap();
+
+        let reports = validate_dir(&root).unwrap();
+        let summary: Vec<_> = reports
+            .iter()
+            .map(|r| {
+                (
+                    r.path.strip_prefix(&root).unwrap().to_path_buf(),
+                    r.kind,
+                    r.valid,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (PathBuf::from("cli.toml"), ConfigKind::Cli, true),
+                (PathBuf::from("infrast/plan.json"), ConfigKind::Other, false),
+                (
+                    PathBuf::from("profiles/default.json"),
+                    ConfigKind::Profile,
+                    true
+                ),
+                (PathBuf::from("tasks/broken.toml"), ConfigKind::Task, false),
+                (
+                    PathBuf::from("tasks/common/base.toml"),
+                    ConfigKind::Fragment,
+                    true
+                ),
+                (PathBuf::from("tasks/daily.toml"), ConfigKind::Task, true),
+                (PathBuf::from("tasks/invalid.json"), ConfigKind::Task, false),
+                (PathBuf::from("tasks/valid.toml"), ConfigKind::Task, true),
+            ]
+        );
+        assert!(reports[3].error.as_ref().unwrap().contains("missing.toml"));
+        assert!(reports[6].error.as_ref().unwrap().contains("tasks"));
+
+        assert!(validate_all(Some(&root), OutputFormat::Json).is_err());
+
+        fs::remove_file(root.join("tasks").join("invalid.json")).unwrap();
+        fs::remove_file(root.join("tasks").join("broken.toml")).unwrap();
+        fs::remove_file(root.join("infrast").join("plan.json")).unwrap();
+        validate_all(Some(&root), OutputFormat::Text).unwrap();
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,81 @@
+//! Display a config file and refresh it when it changes, used by `maa config watch`.
+
+use super::FromFile;
+
+use crate::value::MAAValue;
+
+use std::{
+    io::IsTerminal,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::Result;
+
+/// Display the config file, and display it again every time it changes until Ctrl+C.
+pub fn watch(path: &Path, debounce: Duration) -> Result<()> {
+    let paths: Vec<PathBuf> = vec![path.to_owned()];
+    crate::run::watch::watch(
+        || {
+            display(path);
+            Ok(())
+        },
+        paths,
+        debounce,
+    )
+}
+
+fn display(path: &Path) {
+    // Clear the screen, so the display stays at the top of the terminal
+    if std::io::stdout().is_terminal() {
+        print!("\x1b[2J\x1b[H");
+    }
+    println!(
+        "{} (updated at {})",
+        path.display(),
+        chrono::Local::now().format("%H:%M:%S")
+    );
+    println!();
+    print!("{}", render(path));
+}
+
+/// Render the config file as a tree, or the error if it is invalid.
+fn render(path: &Path) -> String {
+    match MAAValue::from_file(path) {
+        Ok(value) => value.to_tree(),
+        Err(err) => format!("Error: {err:#}\n"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_file() {
+        let dir = std::env::temp_dir().join("maa-test-config-watch");
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let path = dir.join("task.toml");
+        std::fs::write(
+            &path,
+            "[[tasks]]\ntype = \"Fight\"\n[tasks.params]\nstage = \"1-7\"\nmedicine = { default = 2, description = \"medicine to use\" }\n",
+        )
+        .unwrap();
+        assert_eq!(
+            render(&path),
+            "tasks:\n  \
+               -\n    \
+                 params:\n      \
+                   medicine: <Please input medicine to use [default: 2]>\n      \
+                   stage: \"1-7\"\n    \
+                 type: \"Fight\"\n"
+        );
+
+        std::fs::write(&path, "[[tasks]\n").unwrap();
+        assert!(render(&path).starts_with("Error: "));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(render(&path).starts_with("Error: "));
+    }
+}
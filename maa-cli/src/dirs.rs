@@ -4,9 +4,14 @@ use std::{
     ffi::OsStr,
     fs::{create_dir, create_dir_all, remove_dir_all},
     path::{Path, PathBuf},
-    sync::OnceLock,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        OnceLock,
+    },
+    time::SystemTime,
 };
 
+use anyhow::Context;
 use directories::ProjectDirs;
 use dunce::canonicalize;
 
@@ -54,42 +59,251 @@ fn dir_from_env(maa_env: impl AsRef<OsStr>, xdg_env: impl AsRef<OsStr>) -> Optio
         .or_else(|| var_os(xdg_env).map(|xdg| join!(xdg, "maa")))
 }
 
+/// Where directories are located if they are not given by environment variables.
+#[derive(Clone, Copy)]
+pub enum Layout<'a> {
+    /// Native directories of current platform, e.g. `~/Library/Application Support` on macOS
+    Native(Option<&'a ProjectDirs>),
+    /// XDG Base Directory layout in given home directory, e.g. `~/.config/maa`
+    Xdg(&'a Path),
+}
+
+impl Layout<'_> {
+    fn data_dir(self) -> Option<PathBuf> {
+        match self {
+            Self::Native(proj) => proj.map(|dirs| dirs.data_dir().into()),
+            Self::Xdg(home) => Some(join!(home, ".local", "share", "maa")),
+        }
+    }
+
+    fn state_dir(self) -> Option<PathBuf> {
+        match self {
+            Self::Native(proj) => {
+                proj.map(|dirs| dirs.state_dir().unwrap_or_else(|| dirs.data_dir()).into())
+            }
+            Self::Xdg(home) => Some(join!(home, ".local", "state", "maa")),
+        }
+    }
+
+    fn cache_dir(self) -> Option<PathBuf> {
+        match self {
+            Self::Native(proj) => proj.map(|dirs| dirs.cache_dir().into()),
+            Self::Xdg(home) => Some(join!(home, ".cache", "maa")),
+        }
+    }
+
+    fn config_dir(self) -> Option<PathBuf> {
+        match self {
+            Self::Native(proj) => proj.map(|dirs| {
+                if cfg!(target_os = "macos") {
+                    dirs.config_dir().join("config")
+                } else {
+                    dirs.config_dir().into()
+                }
+            }),
+            Self::Xdg(home) => Some(join!(home, ".config", "maa")),
+        }
+    }
+}
+
+/// Whether the value of `MAA_XDG` opts in the XDG layout, e.g. `1` or `true`.
+fn xdg_opted_in(value: Option<&OsStr>) -> bool {
+    value.and_then(OsStr::to_str).is_some_and(|value| {
+        ["1", "true", "yes", "on"]
+            .iter()
+            .any(|opt_in| value.eq_ignore_ascii_case(opt_in))
+    })
+}
+
+/// Whether the XDG layout is used instead of the native one.
+///
+/// The XDG layout is the native one on Linux, and it can be opted in on macOS by `MAA_XDG=1`,
+/// which is useful to share dotfiles between Linux and macOS.
+pub fn use_xdg() -> bool {
+    cfg!(target_os = "macos") && xdg_opted_in(var_os("MAA_XDG").as_deref())
+}
+
 /// Get the data directory.
-fn get_data_dir(proj: Option<&ProjectDirs>) -> PathBuf {
+fn get_data_dir(layout: Layout) -> PathBuf {
     dir_from_env("MAA_DATA_DIR", "XDG_DATA_HOME")
-        .or_else(|| proj.map(|dirs| dirs.data_dir().into()))
+        .or_else(|| layout.data_dir())
         .expect("Failed to get data directory!")
 }
 
 /// Get the state directory.
-fn get_state_dir(proj: Option<&ProjectDirs>) -> PathBuf {
+fn get_state_dir(layout: Layout) -> PathBuf {
     dir_from_env("MAA_STATE_DIR", "XDG_STATE_HOME")
-        .or_else(|| proj.map(|dirs| dirs.state_dir().unwrap_or_else(|| dirs.data_dir()).into()))
+        .or_else(|| layout.state_dir())
         .expect("Failed to get state directory!")
 }
 
 /// Get the cache directory.
-fn get_cache_dir(proj: Option<&ProjectDirs>) -> PathBuf {
+fn get_cache_dir(layout: Layout) -> PathBuf {
     dir_from_env("MAA_CACHE_DIR", "XDG_CACHE_HOME")
-        .or_else(|| proj.map(|dirs| dirs.cache_dir().into()))
+        .or_else(|| layout.cache_dir())
         .expect("Failed to get cache directory!")
 }
 
+/// Config directory given by `--config-dir`, which takes precedence over `MAA_CONFIG_DIR`.
+static CONFIG_DIR_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Override the config directory for the entire process.
+///
+/// This must be called before any directory is accessed, otherwise it has no effect
+/// and an error is returned. A relative path is resolved against current directory,
+/// and a leading `~` is expanded to the home directory.
+pub fn set_config_dir(path: PathBuf) -> std::io::Result<()> {
+    let path = resolve_dir(&path, &std::env::current_dir()?);
+
+    if DIRS.get().is_some() || CONFIG_DIR_OVERRIDE.set(path).is_err() {
+        return Err(std::io::Error::other(
+            "Config directory can only be set before accessing any directory",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Resolve a directory given by user, expanding `~` and resolving relative paths against `cwd`.
+fn resolve_dir(path: &Path, cwd: &Path) -> PathBuf {
+    let path = expand_tilde(path);
+    if path.is_absolute() {
+        path.into_owned()
+    } else {
+        cwd.join(path)
+    }
+}
+
+/// Get the config directory given by user, `None` to use the default one.
+///
+/// The precedence is `--config-dir` (`flag`), `MAA_CONFIG_DIR` (`env`),
+/// then `XDG_CONFIG_HOME/maa` (`xdg`). The flag has been resolved by [`set_config_dir`],
+/// and the environment variable is resolved against `cwd` in the same way.
+fn config_dir_from(
+    flag: Option<&Path>,
+    env: Option<&OsStr>,
+    xdg: Option<&OsStr>,
+    cwd: &Path,
+) -> Option<PathBuf> {
+    flag.map(Path::to_path_buf)
+        .or_else(|| env.map(|env| resolve_dir(Path::new(env), cwd)))
+        .or_else(|| xdg.map(|xdg| join!(xdg, "maa")))
+}
+
+/// Directory of system-wide configs, e.g. set up by administrators, only used on Unix.
+const SYSTEM_CONFIG_DIR: &str = "/etc/maa";
+
+/// Directory of per-project configs, relative to current directory.
+pub const PROJECT_CONFIG_DIR: &str = ".maa";
+
+/// Whether system-wide configs are skipped, set by `--no-system-config`.
+static NO_SYSTEM_CONFIG: AtomicBool = AtomicBool::new(false);
+
+/// Skip system-wide configs in [`config_layers`] for the entire process.
+pub fn disable_system_config() {
+    NO_SYSTEM_CONFIG.store(true, Ordering::Relaxed);
+}
+
+/// Get existing config directories of all layers, from the lowest precedence to the highest.
+///
+/// The layers are system-wide configs in `system`, per-user configs in `user` (the config
+/// directory), configs of the active profile in `profile` and per-project configs in `.maa`
+/// of `cwd`. The same directory is only listed once, at its lowest precedence.
+fn config_layers_from(
+    system: Option<&Path>,
+    user: &Path,
+    profile: Option<&Path>,
+    cwd: Option<&Path>,
+) -> Vec<PathBuf> {
+    let project = cwd.map(|cwd| cwd.join(PROJECT_CONFIG_DIR));
+    let mut layers: Vec<PathBuf> = Vec::with_capacity(4);
+    let mut seen = Vec::with_capacity(4);
+    for dir in [system, Some(user), profile, project.as_deref()]
+        .into_iter()
+        .flatten()
+    {
+        if !dir.is_dir() {
+            continue;
+        }
+        let canonical = canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf());
+        if !seen.contains(&canonical) {
+            seen.push(canonical);
+            layers.push(dir.to_path_buf());
+        }
+    }
+    layers
+}
+
+/// Directory of profiles in the config and state directories.
+pub const PROFILES_DIR: &str = "profiles";
+
+/// Profile given by `--profile`, whose directories overlay the base directories.
+static PROFILE: OnceLock<String> = OnceLock::new();
+
+/// Check that a profile name can be used as a directory name.
+pub fn check_profile_name(name: &str) -> std::io::Result<()> {
+    if name.is_empty()
+        || name == "."
+        || name == ".."
+        || name.contains(std::path::is_separator)
+        || name.contains('/')
+    {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("Invalid profile name `{name}`, it must be a non-empty directory name"),
+        ));
+    }
+    Ok(())
+}
+
+/// Activate a profile for the entire process.
+///
+/// This must be called before any directory is accessed, otherwise it has no effect
+/// and an error is returned.
+pub fn set_profile(name: String) -> std::io::Result<()> {
+    check_profile_name(&name)?;
+    if DIRS.get().is_some() || PROFILE.set(name).is_err() {
+        return Err(std::io::Error::other(
+            "Profile can only be set before accessing any directory",
+        ));
+    }
+    Ok(())
+}
+
+/// Resolve `relative` in `base`, overlaid by `layer` if the path exists in it.
+fn overlay(base: &Path, layer: Option<&Path>, relative: &Path) -> PathBuf {
+    layer
+        .map(|layer| layer.join(relative))
+        .filter(|path| path.exists())
+        .unwrap_or_else(|| base.join(relative))
+}
+
+/// Get the config directory of an older layout, `None` if there is no such layout.
+///
+/// The `directories` crate used `~/Library/Preferences` as the config directory on macOS
+/// before version 3, so configs of old maa-cli may be left there.
+fn get_legacy_config_dir(proj: Option<&ProjectDirs>) -> Option<PathBuf> {
+    if cfg!(target_os = "macos") {
+        proj.map(|dirs| dirs.preference_dir().to_path_buf())
+    } else {
+        None
+    }
+}
+
 /// Get the config directory.
-fn get_config_dir(proj: Option<&ProjectDirs>) -> PathBuf {
-    dir_from_env("MAA_CONFIG_DIR", "XDG_CONFIG_HOME")
-        .or_else(|| {
-            proj.map(|dirs| {
-                if cfg!(target_os = "macos") {
-                    dirs.config_dir().join("config")
-                } else {
-                    dirs.config_dir().into()
-                }
-            })
-        })
-        .expect("Failed to get config directory!")
+fn get_config_dir(layout: Layout) -> PathBuf {
+    let cwd = std::env::current_dir().unwrap_or_default();
+    config_dir_from(
+        CONFIG_DIR_OVERRIDE.get().map(PathBuf::as_path),
+        var_os("MAA_CONFIG_DIR").as_deref(),
+        var_os("XDG_CONFIG_HOME").as_deref(),
+        &cwd,
+    )
+    .or_else(|| layout.config_dir())
+    .expect("Failed to get config directory!")
 }
 
+#[derive(Clone)]
 pub struct Dirs {
     data: PathBuf,
     library: PathBuf,
@@ -99,26 +313,43 @@ pub struct Dirs {
     resource: PathBuf,
     hot_update: PathBuf,
     state: PathBuf,
+    profile_state: PathBuf,
     log: PathBuf,
+    profile: Option<String>,
 }
 
 impl Dirs {
     pub fn new(proj: Option<ProjectDirs>) -> Self {
-        let proj = proj.as_ref();
-        let data_dir = get_data_dir(proj);
-        let state_dir = get_state_dir(proj);
-        let cache_dir = get_cache_dir(proj);
+        if use_xdg() {
+            Self::with_layout(Layout::Xdg(home()))
+        } else {
+            Self::with_layout(Layout::Native(proj.as_ref()))
+        }
+    }
+
+    /// Resolve directories in given layout, environment variables and overrides still apply.
+    pub fn with_layout(layout: Layout) -> Self {
+        let data_dir = get_data_dir(layout);
+        let state_dir = get_state_dir(layout);
+        let cache_dir = get_cache_dir(layout);
+        let profile = PROFILE.get().cloned();
+        let profile_state = match &profile {
+            Some(name) => join!(&state_dir, PROFILES_DIR, name),
+            None => state_dir.clone(),
+        };
 
         Self {
             copilot: cache_dir.join("copilot"),
             cache: cache_dir,
-            config: get_config_dir(proj),
+            config: get_config_dir(layout),
             library: data_dir.join("lib"),
             resource: data_dir.join("resource"),
             hot_update: data_dir.join("MaaResource"),
             data: data_dir,
-            log: state_dir.join("debug"),
+            log: profile_state.join("debug"),
+            profile_state,
             state: state_dir,
+            profile,
         }
     }
 
@@ -141,6 +372,7 @@ impl Dirs {
     /// Then, assume the executable file is in the `bin` directory,
     /// try to find the MaaCore in the `lib` directory in the parent directory of the executable file.
     /// If the executable is a symbolic link, will try to find the MaaCore both in the symbolic link and the link target.
+    #[cfg(feature = "core_installer")]
     pub fn find_library<'a>(&'a self, exe_path: &'a Path) -> Option<Cow<'a, Path>> {
         let lib_name = maa_lib_name();
         if self.library().join(lib_name).exists() {
@@ -175,6 +407,9 @@ impl Dirs {
     /// The `sub_dir` is the sub directory of the config directory.
     /// If `sub_dir` is `None`, the path is relative to the config directory.
     /// Otherwise, the path is relative to the `sub_dir` directory.
+    ///
+    /// If a profile is active, the config directory of the profile overlays the config
+    /// directory, i.e. the path in the profile is returned if it exists.
     pub fn abs_config<P: AsRef<Path>, D: AsRef<Path>>(
         &self,
         path: P,
@@ -184,13 +419,69 @@ impl Dirs {
         if path.is_absolute() {
             None
         } else {
-            let mut result = self.config.to_path_buf();
-            if let Some(sub_dir) = sub_dir {
-                result.push(sub_dir);
-            }
-            result.push(path);
-            Some(result)
+            let relative = match sub_dir {
+                Some(sub_dir) => sub_dir.as_ref().join(path),
+                None => path.to_path_buf(),
+            };
+            Some(overlay(
+                &self.config,
+                self.profile_config().as_deref(),
+                &relative,
+            ))
+        }
+    }
+
+    /// Get existing config directories of all layers, from the lowest precedence to the highest.
+    ///
+    /// The layers are system-wide configs in `/etc/maa` (Unix only, skipped by
+    /// `--no-system-config`), per-user configs in the config directory, configs of the
+    /// active profile in `profiles/<name>` of the config directory, and per-project configs
+    /// in `.maa` of current directory.
+    pub fn config_layers(&self) -> Vec<PathBuf> {
+        let system = (cfg!(unix) && !NO_SYSTEM_CONFIG.load(Ordering::Relaxed))
+            .then(|| Path::new(SYSTEM_CONFIG_DIR));
+        let cwd = std::env::current_dir().ok();
+        config_layers_from(
+            system,
+            self.config(),
+            self.profile_config().as_deref(),
+            cwd.as_deref(),
+        )
+    }
+
+    /// Get the name of the active profile given by `--profile`, if any.
+    pub fn profile(&self) -> Option<&str> {
+        self.profile.as_deref()
+    }
+
+    /// Get the config directory of the active profile, if any.
+    pub fn profile_config(&self) -> Option<PathBuf> {
+        self.profile()
+            .map(|name| join!(&self.config, PROFILES_DIR, name))
+    }
+
+    /// Get the state directory of the active profile, or the state directory if no profile.
+    ///
+    /// This is the user directory of MaaCore, so each profile has its own caches and logs.
+    pub fn profile_state(&self) -> &Path {
+        &self.profile_state
+    }
+
+    /// Directories used by maa-cli, with all overrides, e.g. `--config-dir` and profiles, applied.
+    ///
+    /// The config directory of the active profile is listed after the config directory.
+    pub fn resolved(&self) -> Vec<ResolvedDir> {
+        let mut dirs = vec![ResolvedDir::new("config", self.config())];
+        if let Some(profile) = self.profile_config() {
+            dirs.push(ResolvedDir::new("profile", &profile));
         }
+        dirs.extend([
+            ResolvedDir::new("data", self.data()),
+            ResolvedDir::new("cache", self.cache()),
+            ResolvedDir::new("state", self.state()),
+            ResolvedDir::new("log", self.log()),
+        ]);
+        dirs
     }
 
     /// Get cache directory.
@@ -262,10 +553,202 @@ impl Dirs {
     pub fn log(&self) -> &Path {
         &self.log
     }
+
+    /// Directories required by maa-cli with their names, permissions to create them
+    /// and whether they must be writable.
+    ///
+    /// User authored configs, state records and logs are private, others are shared.
+    /// The config directory is only read, so it may be read-only, e.g. a shared system config.
+    fn required(&self) -> [(&'static str, &Path, u32, bool); 5] {
+        [
+            ("cache", self.cache(), SHARED_DIR_MODE, true),
+            ("config", self.config(), PRIVATE_DIR_MODE, false),
+            ("data", self.data(), SHARED_DIR_MODE, true),
+            ("state", self.state(), PRIVATE_DIR_MODE, true),
+            ("log", self.log(), PRIVATE_DIR_MODE, true),
+        ]
+    }
+
+    /// Create all required directories if missing, return the first error encountered.
+    ///
+    /// Existing directories are checked to be writable directories,
+    /// except the config directory which is only checked to be readable.
+    /// If `create_config` is false, a missing config directory is an error instead of
+    /// being created, so a mistyped config directory given by user is reported.
+    pub fn ensure_all(&self, create_config: bool) -> anyhow::Result<()> {
+        for (name, dir, mode, writable) in self.required() {
+            if !writable && !create_config && !dir.exists() {
+                anyhow::bail!("The {name} directory {} does not exist", dir.display());
+            }
+            let ret = if writable || !dir.exists() {
+                dir.ensure_with_mode(mode).map(|_| ())
+            } else {
+                check_is_dir(dir).and_then(|_| check_readable(dir))
+            };
+            ret.with_context(|| format!("The {name} directory is not usable"))?;
+        }
+        Ok(())
+    }
+
+    /// Check all required directories exist and are usable without creating them.
+    pub fn check_all(&self) -> anyhow::Result<()> {
+        for (name, dir, _, writable) in self.required() {
+            if !dir.is_dir() {
+                anyhow::bail!("The {name} directory {} does not exist", dir.display());
+            }
+            let ret = if writable {
+                check_writable(dir)
+            } else {
+                check_readable(dir)
+            };
+            ret.with_context(|| format!("The {name} directory is not usable"))?;
+        }
+        Ok(())
+    }
+
+    /// All files in the cache directory, empty if the cache directory does not exist.
+    ///
+    /// Symbolic links are listed as files without being followed,
+    /// so nothing outside the cache directory is reported.
+    pub fn cache_files(&self) -> std::io::Result<Vec<CacheFile>> {
+        cache_files_in(self.cache())
+    }
+
+    /// Usage of each component of the cache directory, sorted by component.
+    pub fn cache_usage(&self) -> std::io::Result<Vec<CacheUsage>> {
+        Ok(summarize_cache(&self.cache_files()?))
+    }
+}
+
+/// Components of the cache directory, determined by the top level entry of files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CacheComponent {
+    /// Archives and staging files of maa-cli
+    Cli,
+    /// Archives, staging files and source builds of MaaCore
+    Core,
+    /// Downloaded copilot files
+    Copilot,
+    /// Downloaded resources
+    Resource,
+    /// Anything else
+    Other,
+}
+
+impl CacheComponent {
+    /// Classify a file by the name of its top level entry in the cache directory.
+    pub fn classify(name: &OsStr) -> Self {
+        let name = name.to_string_lossy();
+        if name.starts_with("maa_cli-") || name.starts_with("maa-cli-") {
+            Self::Cli
+        } else if name.starts_with("MAA-")
+            || name.starts_with("MaaAssistantArknights-")
+            || name.starts_with("maa-core-")
+        {
+            Self::Core
+        } else if name == "copilot" {
+            Self::Copilot
+        } else if name.to_ascii_lowercase().contains("resource") {
+            Self::Resource
+        } else {
+            Self::Other
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Cli => "cli",
+            Self::Core => "core",
+            Self::Copilot => "copilot",
+            Self::Resource => "resource",
+            Self::Other => "other",
+        }
+    }
+}
+
+impl std::fmt::Display for CacheComponent {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A file in the cache directory, see [`Dirs::cache_files`]
+#[derive(Debug, Clone)]
+pub struct CacheFile {
+    pub path: PathBuf,
+    pub component: CacheComponent,
+    pub size: u64,
+    pub modified: SystemTime,
+}
+
+/// Usage of a component of the cache directory, see [`Dirs::cache_usage`]
+#[derive(Debug, PartialEq)]
+pub struct CacheUsage {
+    pub component: CacheComponent,
+    pub count: usize,
+    pub bytes: u64,
+    pub oldest: SystemTime,
+    pub newest: SystemTime,
+}
+
+/// All files in given cache directory, see [`Dirs::cache_files`]
+pub fn cache_files_in(root: &Path) -> std::io::Result<Vec<CacheFile>> {
+    fn walk(
+        dir: &Path,
+        component: Option<CacheComponent>,
+        files: &mut Vec<CacheFile>,
+    ) -> std::io::Result<()> {
+        for entry in std::fs::read_dir(dir).map_err(|e| path_error(e, "read", dir))? {
+            let entry = entry?;
+            let path = entry.path();
+            let component =
+                component.unwrap_or_else(|| CacheComponent::classify(&entry.file_name()));
+            // Metadata of symbolic links themselves, so they are never followed
+            let metadata = path
+                .symlink_metadata()
+                .map_err(|e| path_error(e, "read", &path))?;
+            if metadata.is_dir() {
+                walk(&path, Some(component), files)?;
+            } else {
+                files.push(CacheFile {
+                    path,
+                    component,
+                    size: metadata.len(),
+                    modified: metadata.modified()?,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    let mut files = Vec::new();
+    if root.is_dir() {
+        walk(root, None, &mut files)?;
+    }
+    Ok(files)
 }
 
+fn summarize_cache(files: &[CacheFile]) -> Vec<CacheUsage> {
+    let mut usages = std::collections::BTreeMap::<CacheComponent, CacheUsage>::new();
+    for file in files {
+        let usage = usages.entry(file.component).or_insert(CacheUsage {
+            component: file.component,
+            count: 0,
+            bytes: 0,
+            oldest: file.modified,
+            newest: file.modified,
+        });
+        usage.count += 1;
+        usage.bytes += file.size;
+        usage.oldest = usage.oldest.min(file.modified);
+        usage.newest = usage.newest.max(file.modified);
+    }
+    usages.into_values().collect()
+}
+
+static DIRS: OnceLock<Dirs> = OnceLock::new();
+
 fn dirs() -> &'static Dirs {
-    static DIRS: OnceLock<Dirs> = OnceLock::new();
     DIRS.get_or_init(|| Dirs::new(ProjectDirs::from("com", "loong", "maa")))
 }
 
@@ -282,6 +765,7 @@ pub fn library() -> &'static Path {
     dirs().library()
 }
 
+#[cfg(feature = "core_installer")]
 pub fn find_library() -> Option<Cow<'static, Path>> {
     dirs().find_library(exe()?)
 }
@@ -290,6 +774,18 @@ pub fn config() -> &'static Path {
     dirs().config()
 }
 
+pub fn config_layers() -> Vec<PathBuf> {
+    dirs().config_layers()
+}
+
+pub fn profile() -> Option<&'static str> {
+    dirs().profile()
+}
+
+pub fn profile_state() -> &'static Path {
+    dirs().profile_state()
+}
+
 pub fn abs_config<P: AsRef<Path>, D: AsRef<Path>>(path: P, sub_dir: Option<D>) -> Option<PathBuf> {
     dirs().abs_config(path, sub_dir)
 }
@@ -322,7 +818,113 @@ pub fn log() -> &'static Path {
     dirs().log()
 }
 
-fn home() -> &'static Path {
+/// Create all required directories, see [`Dirs::ensure_all`].
+///
+/// The config directory given by `--config-dir` or `MAA_CONFIG_DIR` must exist,
+/// unless `create_config` is true, e.g. for `maa init`.
+pub fn ensure_all(create_config: bool) -> anyhow::Result<()> {
+    let explicit = CONFIG_DIR_OVERRIDE.get().is_some() || var_os("MAA_CONFIG_DIR").is_some();
+    dirs().ensure_all(create_config || !explicit)
+}
+
+pub fn check_all() -> anyhow::Result<()> {
+    dirs().check_all()
+}
+
+pub fn resolved() -> Vec<ResolvedDir> {
+    dirs().resolved()
+}
+
+/// A directory used by maa-cli and whether it exists, see [`Dirs::resolved`]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+#[derive(serde::Serialize)]
+pub struct ResolvedDir {
+    pub name: &'static str,
+    pub path: PathBuf,
+    pub exists: bool,
+}
+
+impl ResolvedDir {
+    fn new(name: &'static str, path: &Path) -> Self {
+        Self {
+            name,
+            path: path.to_path_buf(),
+            exists: path.is_dir(),
+        }
+    }
+}
+
+/// A table of directories with their existence, used by `maa dir` and `maa self info`
+pub fn resolved_table(dirs: &[ResolvedDir]) -> prettytable::Table {
+    use prettytable::{format, row, Table};
+
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
+    table.set_titles(row!["Directory", "Path", "Exists"]);
+    for dir in dirs {
+        table.add_row(row![
+            dir.name,
+            dir.path.display(),
+            if dir.exists { "yes" } else { "no" }
+        ]);
+    }
+    table
+}
+
+/// Open given directory with the file manager of current platform
+pub fn open(path: &Path) -> anyhow::Result<()> {
+    if !path.is_dir() {
+        anyhow::bail!("Directory {} does not exist", path.display());
+    }
+    let opener = if cfg!(target_os = "macos") {
+        "open"
+    } else if cfg!(windows) {
+        "explorer"
+    } else {
+        "xdg-open"
+    };
+    // explorer exits with 1 even if the directory is opened, so only spawning errors are reported
+    std::process::Command::new(opener)
+        .arg(path)
+        .spawn()
+        .with_context(|| format!("Failed to open {} by `{opener}`", path.display()))?;
+    Ok(())
+}
+
+pub fn cache_files() -> std::io::Result<Vec<CacheFile>> {
+    dirs().cache_files()
+}
+
+pub fn cache_usage() -> std::io::Result<Vec<CacheUsage>> {
+    dirs().cache_usage()
+}
+
+/// The config directory of an older layout, if the default config directory is in use.
+///
+/// Configs in it are moved into the config directory by [`crate::migrate`].
+pub fn legacy_config_dir() -> Option<PathBuf> {
+    if CONFIG_DIR_OVERRIDE.get().is_some()
+        || var_os("MAA_CONFIG_DIR").is_some()
+        || var_os("XDG_CONFIG_HOME").is_some()
+    {
+        return None;
+    }
+    get_legacy_config_dir(ProjectDirs::from("com", "loong", "maa").as_ref())
+        .filter(|legacy| legacy != config())
+}
+
+/// Directories in the native layout, if the XDG layout is opted in by [`use_xdg`].
+///
+/// Files in them are moved into the XDG layout by [`crate::migrate`].
+pub fn native_dirs() -> Option<Dirs> {
+    use_xdg().then(|| {
+        Dirs::with_layout(Layout::Native(
+            ProjectDirs::from("com", "loong", "maa").as_ref(),
+        ))
+    })
+}
+
+pub fn home() -> &'static Path {
     static HOME: OnceLock<PathBuf> = OnceLock::new();
     HOME.get_or_init(|| {
         directories::BaseDirs::new()
@@ -332,7 +934,7 @@ fn home() -> &'static Path {
     })
 }
 
-pub fn expand_tilde(path: &Path) -> Cow<Path> {
+pub fn expand_tilde(path: &Path) -> Cow<'_, Path> {
     if let Ok(path) = path.strip_prefix("~") {
         home().join(path).into()
     } else {
@@ -341,7 +943,7 @@ pub fn expand_tilde(path: &Path) -> Cow<Path> {
 }
 
 /// Similar to `finder(exe_path.parent()?)`, but try to canonicalize the path first.
-fn _find_from<F>(exe_path: &Path, finder: F) -> Option<Cow<Path>>
+fn _find_from<F>(exe_path: &Path, finder: F) -> Option<Cow<'_, Path>>
 where
     F: Fn(Cow<Path>) -> Option<Cow<Path>>,
 {
@@ -361,7 +963,7 @@ pub trait Ensure: Sized {
     /// Ensure the path exists, create it if not.
     ///
     /// Return the path itself if it exists or created successfully.
-    /// Otherwise, return an error.
+    /// Otherwise, return an error, which is also returned if the path is not a directory.
     fn ensure(self) -> Result<Self, Self::Error>;
 
     /// Ensure the dir is empty, create it if not.
@@ -369,25 +971,129 @@ pub trait Ensure: Sized {
     /// Return the path itself if it exists or created successfully.
     /// If the dir is not empty, remove all files in it.
     fn ensure_clean(self) -> Result<Self, Self::Error>;
+
+    /// Ensure the dir exists and is writable, create it with given permissions if not.
+    ///
+    /// The `mode` is only applied to the dir created, e.g. [`PRIVATE_DIR_MODE`] for dirs
+    /// holding user data and [`SHARED_DIR_MODE`] for others, parent dirs and existing dirs are
+    /// left untouched. On platforms other than Unix, the mode is ignored.
+    fn ensure_with_mode(self, mode: u32) -> Result<Self, Self::Error>;
+}
+
+/// Permissions of dirs only accessible by current user, e.g. the config and state directories
+pub const PRIVATE_DIR_MODE: u32 = 0o700;
+
+/// Permissions of dirs readable by all users, e.g. the cache directory
+pub const SHARED_DIR_MODE: u32 = 0o755;
+
+/// Attach the operation and the path to an io error, keeping its kind.
+fn path_error(err: std::io::Error, operation: &str, path: &Path) -> std::io::Error {
+    std::io::Error::new(
+        err.kind(),
+        format!("Failed to {operation} {}: {err}", path.display()),
+    )
+}
+
+/// Return an error if the existing path is not a directory.
+fn check_is_dir(path: &Path) -> std::io::Result<()> {
+    if path.is_dir() {
+        Ok(())
+    } else {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::AlreadyExists,
+            format!(
+                "{} is not a directory, please move or remove the file in the way",
+                path.display()
+            ),
+        ))
+    }
+}
+
+/// Check whether a directory is writable by creating and removing a probe file,
+/// since permission bits do not tell the whole story, e.g. on read-only mounts.
+///
+/// The probe file is named by the process id and the current time, so processes checking
+/// the same directory at the same time do not remove the probe file of each other.
+pub fn check_writable(dir: &Path) -> std::io::Result<()> {
+    static COUNTER: std::sync::atomic::AtomicU32 = std::sync::atomic::AtomicU32::new(0);
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |time| time.subsec_nanos());
+    let count = COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    let probe = dir.join(format!(
+        ".maa-write-check-{}-{nanos}-{count}",
+        std::process::id()
+    ));
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&probe)
+        .and_then(|_| std::fs::remove_file(&probe))
+        .map_err(|err| {
+            std::io::Error::new(
+                err.kind(),
+                format!("{} is not writable: {err}", dir.display()),
+            )
+        })
+}
+
+/// Remove parent directories of a removed file if they become empty, up to but excluding `root`.
+pub fn remove_empty_parents(path: &Path, root: &Path) {
+    for dir in path.ancestors().skip(1) {
+        if dir == root || !dir.starts_with(root) || std::fs::remove_dir(dir).is_err() {
+            break;
+        }
+        log::debug!("Removed empty directory {}", dir.display());
+    }
+}
+
+/// Check whether a directory is readable by listing it.
+pub fn check_readable(dir: &Path) -> std::io::Result<()> {
+    std::fs::read_dir(dir).map(|_| ()).map_err(|err| {
+        std::io::Error::new(
+            err.kind(),
+            format!("{} is not readable: {err}", dir.display()),
+        )
+    })
 }
 
 impl Ensure for &Path {
     type Error = std::io::Error;
 
     fn ensure(self) -> Result<Self, Self::Error> {
-        if !self.exists() {
-            create_dir_all(self)?;
+        if self.exists() {
+            check_is_dir(self)?;
+        } else {
+            create_dir_all(self).map_err(|err| path_error(err, "create directory", self))?;
         }
         Ok(self)
     }
 
     fn ensure_clean(self) -> Result<Self, Self::Error> {
         if self.exists() {
-            remove_dir_all(self)?;
+            remove_dir_all(self).map_err(|err| path_error(err, "remove directory", self))?;
         } else if let Some(parent) = self.parent() {
             parent.ensure()?;
         }
-        create_dir(self)?;
+        create_dir(self).map_err(|err| path_error(err, "create directory", self))?;
+        Ok(self)
+    }
+
+    fn ensure_with_mode(self, mode: u32) -> Result<Self, Self::Error> {
+        if self.exists() {
+            check_is_dir(self)?;
+            check_writable(self)?;
+            return Ok(self);
+        }
+        create_dir_all(self).map_err(|err| path_error(err, "create directory", self))?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(self, std::fs::Permissions::from_mode(mode))
+                .map_err(|err| path_error(err, "set permissions of", self))?;
+        }
+        #[cfg(not(unix))]
+        let _ = mode;
         Ok(self)
     }
 }
@@ -468,6 +1174,11 @@ mod tests {
         fn state_relative() {
             env::remove_var("XDG_STATE_HOME");
             let project = ProjectDirs::from("com", "loong", "maa");
+            let xdg = Layout::Xdg(Path::new("/home"));
+            assert_eq!(
+                Dirs::with_layout(xdg).state(),
+                Path::new("/home/.local/state/maa")
+            );
             if cfg!(target_os = "macos") {
                 assert_eq!(
                     test_dirs().state(),
@@ -487,12 +1198,14 @@ mod tests {
             env::set_var("XDG_STATE_HOME", "/xdg");
             let dirs = Dirs::new(project.clone());
             assert_eq!(dirs.state(), PathBuf::from("/xdg/maa"));
+            assert_eq!(Dirs::with_layout(xdg).state(), dirs.state());
             assert_eq!(dirs.log(), PathBuf::from("/xdg/maa/debug"));
             env::remove_var("XDG_STATE_HOME");
 
             env::set_var("MAA_STATE_DIR", "/maa");
             let dirs = Dirs::new(project.clone());
             assert_eq!(dirs.state(), PathBuf::from("/maa"));
+            assert_eq!(Dirs::with_layout(xdg).state(), dirs.state());
             assert_eq!(dirs.log(), PathBuf::from("/maa/debug"));
             env::remove_var("MAA_STATE_DIR");
         }
@@ -501,6 +1214,11 @@ mod tests {
         fn data_relative() {
             env::remove_var("XDG_DATA_HOME");
             let project = ProjectDirs::from("com", "loong", "maa");
+            let xdg = Layout::Xdg(Path::new("/home"));
+            assert_eq!(
+                Dirs::with_layout(xdg).data(),
+                Path::new("/home/.local/share/maa")
+            );
             if cfg!(target_os = "macos") {
                 assert_eq!(
                     test_dirs().data(),
@@ -530,6 +1248,7 @@ mod tests {
             if env::var_os("MAA_CORE_INSTALLED").is_some() {
                 // This is not used in this test, but needed.
                 let extra_dir = Path::new("/usr/local/share/maa");
+                #[cfg(feature = "core_installer")]
                 assert_eq!(
                     test_dirs().find_library(extra_dir).unwrap(),
                     test_dirs().library()
@@ -538,6 +1257,7 @@ mod tests {
                     test_dirs().find_resource(extra_dir).unwrap(),
                     test_dirs().resource()
                 );
+                #[cfg(feature = "core_installer")]
                 assert_eq!(find_library().unwrap(), library());
                 assert_eq!(find_resource().unwrap(), resource());
             }
@@ -545,6 +1265,7 @@ mod tests {
             env::set_var("XDG_DATA_HOME", "/xdg");
             let dirs = Dirs::new(project.clone());
             assert_eq!(dirs.data(), PathBuf::from("/xdg/maa"));
+            assert_eq!(Dirs::with_layout(xdg).data(), dirs.data());
             assert_eq!(dirs.library(), PathBuf::from("/xdg/maa/lib"));
             assert_eq!(dirs.resource(), PathBuf::from("/xdg/maa/resource"));
             env::remove_var("XDG_DATA_HOME");
@@ -552,6 +1273,7 @@ mod tests {
             env::set_var("MAA_DATA_DIR", "/maa");
             let dirs = Dirs::new(project.clone());
             assert_eq!(dirs.data(), PathBuf::from("/maa"));
+            assert_eq!(Dirs::with_layout(xdg).data(), dirs.data());
             assert_eq!(dirs.library(), PathBuf::from("/maa/lib"));
             assert_eq!(dirs.resource(), PathBuf::from("/maa/resource"));
             env::remove_var("MAA_DATA_DIR");
@@ -574,6 +1296,7 @@ mod tests {
             let bin_exe = bin_dir.join("maa");
             File::create(&bin_exe).unwrap();
             File::create(library_dir.join(maa_lib_name())).unwrap();
+            #[cfg(feature = "core_installer")]
             assert_eq!(dirs.find_library(&bin_exe).unwrap(), library_dir);
             assert_eq!(dirs.find_resource(&bin_exe).unwrap(), resource_dir);
 
@@ -589,6 +1312,7 @@ mod tests {
             let bin_exe = bin_dir.join("maa");
             File::create(bin_dir.join("maa")).unwrap();
             File::create(library_dir.join(maa_lib_name())).unwrap();
+            #[cfg(feature = "core_installer")]
             assert_eq!(dirs.find_library(&bin_exe).unwrap(), library_dir);
             assert_eq!(dirs.find_resource(&bin_exe).unwrap(), resource_dir);
 
@@ -624,6 +1348,7 @@ mod tests {
                 File::create(&bin_exe).unwrap();
                 File::create(library_dir.join(maa_lib_name())).unwrap();
                 symlink(&bin_exe, &linked_exe).unwrap();
+                #[cfg(feature = "core_installer")]
                 assert_eq!(dirs.find_library(&linked_exe).unwrap(), library_dir);
                 assert_eq!(dirs.find_resource(&linked_exe).unwrap(), resource_dir);
                 // Test the situation that maa -> path/cellar/bin, core -> path/lib, resource -> path/share/maa,
@@ -639,6 +1364,7 @@ mod tests {
                 std::fs::create_dir_all(&library_dir).unwrap();
                 std::fs::create_dir_all(&resource_dir).unwrap();
                 File::create(library_dir.join(maa_lib_name())).unwrap();
+                #[cfg(feature = "core_installer")]
                 assert_eq!(dirs.find_library(&linked_exe).unwrap(), library_dir);
                 assert_eq!(dirs.find_resource(&linked_exe).unwrap(), resource_dir);
             }
@@ -650,6 +1376,11 @@ mod tests {
         fn config_relative() {
             env::remove_var("XDG_CONFIG_HOME");
             let project = ProjectDirs::from("com", "loong", "maa");
+            let xdg = Layout::Xdg(Path::new("/home"));
+            assert_eq!(
+                Dirs::with_layout(xdg).config(),
+                Path::new("/home/.config/maa")
+            );
             if cfg!(target_os = "macos") {
                 assert_eq!(
                     test_dirs().config(),
@@ -682,16 +1413,23 @@ mod tests {
             env::set_var("XDG_CONFIG_HOME", "/xdg");
             let dirs = Dirs::new(project.clone());
             assert_eq!(dirs.config(), PathBuf::from("/xdg/maa"));
+            assert_eq!(Dirs::with_layout(xdg).config(), dirs.config());
 
             env::set_var("MAA_CONFIG_DIR", "/maa");
             let dirs = Dirs::new(project.clone());
             assert_eq!(dirs.config(), PathBuf::from("/maa"));
+            assert_eq!(Dirs::with_layout(xdg).config(), dirs.config());
         }
 
         #[test]
         fn cache_relative() {
             env::remove_var("XDG_CACHE_HOME");
             let project = ProjectDirs::from("com", "loong", "maa");
+            let xdg = Layout::Xdg(Path::new("/home"));
+            assert_eq!(
+                Dirs::with_layout(xdg).cache(),
+                Path::new("/home/.cache/maa")
+            );
             if cfg!(target_os = "macos") {
                 assert_eq!(
                     test_dirs().cache(),
@@ -711,15 +1449,97 @@ mod tests {
             env::set_var("XDG_CACHE_HOME", "/xdg");
             let dirs = Dirs::new(project.clone());
             assert_eq!(dirs.cache(), PathBuf::from("/xdg/maa"));
+            assert_eq!(Dirs::with_layout(xdg).cache(), dirs.cache());
             assert_eq!(dirs.copilot(), PathBuf::from("/xdg/maa/copilot"));
 
             env::set_var("MAA_CACHE_DIR", "/maa");
             let dirs = Dirs::new(project.clone());
             assert_eq!(dirs.cache(), PathBuf::from("/maa"));
+            assert_eq!(Dirs::with_layout(xdg).cache(), dirs.cache());
             assert_eq!(dirs.copilot(), PathBuf::from("/maa/copilot"));
         }
     }
 
+    #[test]
+    fn xdg_opt_in() {
+        assert!(!xdg_opted_in(None));
+        assert!(!xdg_opted_in(Some(OsStr::new(""))));
+        assert!(!xdg_opted_in(Some(OsStr::new("0"))));
+        assert!(!xdg_opted_in(Some(OsStr::new("false"))));
+        assert!(xdg_opted_in(Some(OsStr::new("1"))));
+        assert!(xdg_opted_in(Some(OsStr::new("true"))));
+        assert!(xdg_opted_in(Some(OsStr::new("YES"))));
+
+        // The XDG layout is the native one on Linux, so the opt-in only matters on macOS
+        if !cfg!(target_os = "macos") {
+            env::set_var("MAA_XDG", "1");
+            assert!(!use_xdg());
+            env::remove_var("MAA_XDG");
+        }
+    }
+
+    #[test]
+    fn ensure_and_check_all() {
+        let root = temp_dir().join("maa-test-dirs-ensure-all");
+        if root.exists() {
+            remove_dir_all(&root).unwrap();
+        }
+        let dirs = Dirs {
+            data: root.join("data"),
+            library: root.join("data/lib"),
+            config: root.join("config"),
+            cache: root.join("cache"),
+            copilot: root.join("cache/copilot"),
+            resource: root.join("data/resource"),
+            hot_update: root.join("data/MaaResource"),
+            state: root.join("state"),
+            profile_state: root.join("state"),
+            log: root.join("state/debug"),
+            profile: None,
+        };
+
+        let err = dirs.check_all().unwrap_err();
+        assert!(err.to_string().starts_with("The cache directory"), "{err}");
+        assert!(!root.join("cache").exists());
+
+        // a missing config directory given by user is not created
+        let err = dirs.ensure_all(false).unwrap_err();
+        assert!(err.to_string().starts_with("The config directory"), "{err}");
+        assert!(!root.join("config").exists());
+
+        dirs.ensure_all(true).unwrap();
+        for (_, dir, _, _) in dirs.required() {
+            assert!(dir.is_dir());
+        }
+        dirs.ensure_all(false).unwrap();
+        dirs.check_all().unwrap();
+        // the probe file is removed
+        assert_eq!(std::fs::read_dir(root.join("cache")).unwrap().count(), 0);
+
+        // a read-only config directory is usable
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let set_mode = |mode| {
+                std::fs::set_permissions(root.join("config"), std::fs::Permissions::from_mode(mode))
+                    .unwrap()
+            };
+            set_mode(0o500);
+            dirs.ensure_all(false).unwrap();
+            dirs.check_all().unwrap();
+            set_mode(0o700);
+        }
+
+        // a file in place of a directory
+        remove_dir_all(root.join("state")).unwrap();
+        std::fs::write(root.join("state"), "").unwrap();
+        let err = dirs.ensure_all(true).unwrap_err();
+        assert!(err.to_string().starts_with("The state directory"), "{err}");
+        assert!(dirs.check_all().is_err());
+
+        remove_dir_all(&root).unwrap();
+    }
+
     #[test]
     fn test_expand_tilde() {
         assert_eq!(expand_tilde(Path::new("~")), home());
@@ -730,6 +1550,178 @@ mod tests {
         assert_eq!(expand_tilde(Path::new("/foo")), Path::new("/foo"));
     }
 
+    #[test]
+    fn config_dir_precedence() {
+        let root = temp_dir().join("maa-test-config-dir");
+        let flag = root.join("flag");
+        let env = root.join("env");
+        let xdg = root.join("xdg");
+        let cwd = root.join("cwd");
+
+        let resolve = |flag: Option<&Path>, env: Option<&Path>, xdg: Option<&Path>| {
+            config_dir_from(
+                flag,
+                env.map(Path::as_os_str),
+                xdg.map(Path::as_os_str),
+                &cwd,
+            )
+        };
+
+        assert_eq!(resolve(Some(&flag), Some(&env), Some(&xdg)), Some(flag));
+        assert_eq!(resolve(None, Some(&env), Some(&xdg)), Some(env));
+        assert_eq!(resolve(None, None, Some(&xdg)), Some(xdg.join("maa")));
+        assert_eq!(resolve(None, None, None), None);
+
+        // a relative path in the environment variable is resolved against current directory
+        assert_eq!(
+            resolve(None, Some(Path::new("account2")), None),
+            Some(cwd.join("account2"))
+        );
+        assert_eq!(
+            resolve(None, Some(Path::new("~/maa")), None),
+            Some(home().join("maa"))
+        );
+
+        assert_eq!(
+            resolve_dir(Path::new("account1"), &cwd),
+            cwd.join("account1")
+        );
+        assert_eq!(resolve_dir(Path::new("~"), &cwd), home());
+        assert_eq!(
+            resolve_dir(Path::new("~/account1"), &cwd),
+            home().join("account1")
+        );
+        assert_eq!(resolve_dir(&root, &cwd), root);
+    }
+
+    #[test]
+    fn ensure_with_mode() {
+        let test_root = temp_dir().join("maa-test-ensure-with-mode");
+        if test_root.exists() {
+            remove_dir_all(&test_root).unwrap();
+        }
+        let state = test_root.join("state");
+        let cache = test_root.join("cache");
+        assert_eq!(
+            state.as_path().ensure_with_mode(PRIVATE_DIR_MODE).unwrap(),
+            state
+        );
+        assert_eq!(
+            cache.as_path().ensure_with_mode(SHARED_DIR_MODE).unwrap(),
+            cache
+        );
+        assert!(state.is_dir() && cache.is_dir());
+        // existing directories are checked to be writable without leaving the probe file
+        state.as_path().ensure_with_mode(PRIVATE_DIR_MODE).unwrap();
+        assert_eq!(std::fs::read_dir(&state).unwrap().count(), 0);
+
+        // a file in the way is reported with its path
+        let file = test_root.join("file");
+        std::fs::write(&file, "").unwrap();
+        for err in [
+            file.as_path().ensure().unwrap_err(),
+            file.as_path()
+                .ensure_with_mode(SHARED_DIR_MODE)
+                .unwrap_err(),
+        ] {
+            assert_eq!(err.kind(), std::io::ErrorKind::AlreadyExists);
+            assert!(err.to_string().contains(&*file.to_string_lossy()), "{err}");
+            assert!(err.to_string().contains("is not a directory"), "{err}");
+        }
+        let err = file.join("sub").as_path().ensure().unwrap_err();
+        assert!(
+            err.to_string().starts_with("Failed to create directory"),
+            "{err}"
+        );
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = |path: &Path| path.metadata().unwrap().permissions().mode() & 0o777;
+            assert_eq!(mode(&state), PRIVATE_DIR_MODE);
+            assert_eq!(mode(&cache), SHARED_DIR_MODE);
+
+            // existing directories are left untouched
+            let shared = test_root.join("shared");
+            create_dir(&shared).unwrap();
+            std::fs::set_permissions(&shared, std::fs::Permissions::from_mode(0o750)).unwrap();
+            shared.as_path().ensure_with_mode(PRIVATE_DIR_MODE).unwrap();
+            assert_eq!(mode(&shared), 0o750);
+
+            // a read-only parent, which is not enforced for privileged users, e.g. root
+            let parent = test_root.join("readonly");
+            create_dir(&parent).unwrap();
+            std::fs::set_permissions(&parent, std::fs::Permissions::from_mode(0o555)).unwrap();
+            if check_writable(&parent).is_err() {
+                let child = parent.join("child");
+                let err = child
+                    .as_path()
+                    .ensure_with_mode(SHARED_DIR_MODE)
+                    .unwrap_err();
+                assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+                assert!(err.to_string().contains(&*child.to_string_lossy()), "{err}");
+
+                let err = parent
+                    .as_path()
+                    .ensure_with_mode(SHARED_DIR_MODE)
+                    .unwrap_err();
+                assert!(err.to_string().contains("is not writable"), "{err}");
+            }
+            std::fs::set_permissions(&parent, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+
+        remove_dir_all(&test_root).unwrap();
+    }
+
+    /// Resolve the state directory in a child process with overridden `HOME` and XDG variables,
+    /// since changing `HOME` affects other tests running in parallel.
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    fn state_with_overridden_home() {
+        let home = temp_dir().join("maa-test-state-home");
+        let run = |xdg: Option<&Path>| {
+            let mut command = std::process::Command::new(env::current_exe().unwrap());
+            command
+                .args(["--exact", "dirs::tests::state_in_child", "--ignored"])
+                .env("HOME", &home)
+                .env("MAA_TEST_HOME", &home)
+                .env_remove("MAA_STATE_DIR")
+                .env_remove("XDG_STATE_HOME");
+            if let Some(xdg) = xdg {
+                command.env("XDG_STATE_HOME", xdg);
+            }
+            let output = command.output().unwrap();
+            assert!(
+                output.status.success(),
+                "{}",
+                String::from_utf8_lossy(&output.stdout)
+            );
+        };
+        run(None);
+        run(Some(&home.join("xdg")));
+    }
+
+    #[test]
+    #[ignore = "run by state_with_overridden_home"]
+    fn state_in_child() {
+        let Some(home) = var_os("MAA_TEST_HOME").map(PathBuf::from) else {
+            return;
+        };
+        let dirs = Dirs::new(ProjectDirs::from("com", "loong", "maa"));
+        let expected = match var_os("XDG_STATE_HOME") {
+            Some(xdg) => join!(xdg, "maa"),
+            None if cfg!(target_os = "macos") => {
+                home.join("Library/Application Support/com.loong.maa")
+            }
+            None => home.join(".local/state/maa"),
+        };
+        assert_eq!(dirs.state(), expected);
+        assert_eq!(dirs.log(), expected.join("debug"));
+        // state is never kept in the cache directory, which is safe to delete
+        assert!(!dirs.state().starts_with(dirs.cache()));
+        assert!(!dirs.cache().starts_with(dirs.state()));
+    }
+
     #[test]
     fn ensure() {
         let test_root = temp_dir().join("maa-test-ensure");
@@ -787,6 +1779,260 @@ mod tests {
         remove_dir_all(&test_root).unwrap();
     }
 
+    #[test]
+    fn config_layers() {
+        let test_root = temp_dir().join("maa-test-config-layers");
+        if test_root.exists() {
+            remove_dir_all(&test_root).unwrap();
+        }
+        let system = test_root.join("system");
+        let user = test_root.join("user");
+        let cwd = test_root.join("project");
+        let project = cwd.join(PROJECT_CONFIG_DIR);
+
+        // missing layers are skipped
+        assert!(config_layers_from(Some(&system), &user, None, Some(&cwd)).is_empty());
+
+        for dir in [&system, &user, &project] {
+            create_dir_all(dir).unwrap();
+        }
+        assert_eq!(
+            config_layers_from(Some(&system), &user, None, Some(&cwd)),
+            [system.clone(), user.clone(), project.clone()]
+        );
+        assert_eq!(
+            config_layers_from(None, &user, None, Some(&cwd)),
+            [user.clone(), project.clone()]
+        );
+        // the project layer is the user layer, e.g. `--config-dir .maa`
+        assert_eq!(
+            config_layers_from(Some(&system), &project, None, Some(&cwd)),
+            [system.clone(), project.clone()]
+        );
+
+        remove_dir_all(&test_root).unwrap();
+    }
+
+    #[test]
+    fn resolved() {
+        let root = temp_dir().join("maa-test-dirs-resolved");
+        if root.exists() {
+            remove_dir_all(&root).unwrap();
+        }
+        // e.g. given by `--config-dir` and `MAA_STATE_DIR`
+        let mut dirs = Dirs {
+            data: root.join("data"),
+            library: root.join("data/lib"),
+            config: root.join("project"),
+            cache: root.join("cache"),
+            copilot: root.join("cache/copilot"),
+            resource: root.join("data/resource"),
+            hot_update: root.join("data/MaaResource"),
+            state: root.join("state"),
+            profile_state: root.join("state"),
+            log: root.join("state/debug"),
+            profile: None,
+        };
+        create_dir_all(root.join("project")).unwrap();
+        create_dir_all(root.join("state")).unwrap();
+
+        let resolved = dirs.resolved();
+        assert_eq!(
+            resolved.iter().map(|dir| dir.name).collect::<Vec<_>>(),
+            ["config", "data", "cache", "state", "log"]
+        );
+        assert_eq!(
+            resolved[0],
+            ResolvedDir {
+                name: "config",
+                path: root.join("project"),
+                exists: true,
+            }
+        );
+        assert!(!resolved[1].exists);
+        assert!(resolved[3].exists);
+
+        // a profile adds its config directory and moves the log directory
+        dirs.profile = Some("alt".to_owned());
+        dirs.profile_state = join!(&root, "state", "profiles", "alt");
+        dirs.log = dirs.profile_state.join("debug");
+        let resolved = dirs.resolved();
+        assert_eq!(
+            resolved.iter().map(|dir| dir.name).collect::<Vec<_>>(),
+            ["config", "profile", "data", "cache", "state", "log"]
+        );
+        assert_eq!(resolved[1].path, join!(&root, "project", "profiles", "alt"));
+        assert!(!resolved[1].exists);
+        assert_eq!(
+            resolved[5].path,
+            join!(&root, "state", "profiles", "alt", "debug")
+        );
+
+        let table = resolved_table(&resolved).to_string();
+        assert!(table.contains("profile"), "{table}");
+        assert!(super::open(&root.join("missing")).is_err());
+
+        remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn profile_overlay() {
+        let test_root = temp_dir().join("maa-test-profile-overlay");
+        if test_root.exists() {
+            remove_dir_all(&test_root).unwrap();
+        }
+        let config = test_root.join("config");
+        let state = test_root.join("state");
+
+        let mut test_dirs = dirs().clone();
+        test_dirs.config = config.clone();
+        test_dirs.state = state.clone();
+        test_dirs.profile_state = state.clone();
+        assert_eq!(test_dirs.profile(), None);
+        assert_eq!(test_dirs.profile_config(), None);
+        assert_eq!(
+            test_dirs.abs_config("plan.json", Some("infrast")),
+            Some(join!(&config, "infrast", "plan.json"))
+        );
+
+        test_dirs.profile = Some("alt".to_owned());
+        test_dirs.profile_state = join!(&state, "profiles", "alt");
+        let profile = join!(&config, "profiles", "alt");
+        assert_eq!(test_dirs.profile_config(), Some(profile.clone()));
+        create_dir_all(join!(&config, "infrast")).unwrap();
+        create_dir_all(join!(&profile, "infrast")).unwrap();
+        for path in ["infrast/base.json", "infrast/both.json"] {
+            std::fs::write(config.join(path), "{}").unwrap();
+        }
+        for path in ["infrast/profile.json", "infrast/both.json"] {
+            std::fs::write(profile.join(path), "{}").unwrap();
+        }
+
+        let abs = |path: &str| test_dirs.abs_config(path, Some("infrast")).unwrap();
+        // files only in the base are used
+        assert_eq!(abs("base.json"), join!(&config, "infrast", "base.json"));
+        // files in the profile take precedence
+        assert_eq!(abs("both.json"), join!(&profile, "infrast", "both.json"));
+        assert_eq!(
+            abs("profile.json"),
+            join!(&profile, "infrast", "profile.json")
+        );
+        // missing files are resolved in the base
+        assert_eq!(
+            abs("missing.json"),
+            join!(&config, "infrast", "missing.json")
+        );
+        assert_eq!(test_dirs.abs_config("/abs.json", None::<&str>), None);
+
+        // the profile is a config layer between the user and project layers
+        let cwd = test_root.join("project");
+        create_dir_all(cwd.join(PROJECT_CONFIG_DIR)).unwrap();
+        assert_eq!(
+            config_layers_from(None, &config, Some(&profile), Some(&cwd)),
+            [
+                config.clone(),
+                profile.clone(),
+                cwd.join(PROJECT_CONFIG_DIR)
+            ]
+        );
+        // missing profile directories are skipped
+        assert_eq!(
+            config_layers_from(None, &config, Some(&config.join("profiles/x")), None),
+            std::slice::from_ref(&config)
+        );
+
+        remove_dir_all(&test_root).unwrap();
+    }
+
+    #[test]
+    fn profile_name() {
+        assert!(check_profile_name("alt").is_ok());
+        assert!(check_profile_name("account-2").is_ok());
+        for name in ["", ".", "..", "a/b"] {
+            assert!(check_profile_name(name).is_err(), "{name}");
+        }
+        #[cfg(windows)]
+        assert!(check_profile_name("a\\b").is_err());
+    }
+
+    #[test]
+    fn cache_usage() {
+        use std::time::Duration;
+
+        let test_root = temp_dir().join("maa-test-cache-usage");
+        if test_root.exists() {
+            remove_dir_all(&test_root).unwrap();
+        }
+        let cache = test_root.join("cache");
+        let outside = test_root.join("outside");
+        create_dir_all(cache.join("copilot")).unwrap();
+        create_dir_all(cache.join("maa-core-staging").join("lib")).unwrap();
+        create_dir_all(&outside).unwrap();
+        std::fs::write(outside.join("large"), [0; 1000]).unwrap();
+
+        let now = SystemTime::now();
+        let days = |n: u64| now - Duration::from_secs(n * 86400);
+        let write = |path: &str, size: usize, modified: SystemTime| {
+            let path = cache.join(path);
+            std::fs::write(&path, vec![0; size]).unwrap();
+            std::fs::File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(modified)
+                .unwrap();
+        };
+        write(
+            "maa_cli-v0.4.7-x86_64-unknown-linux-gnu.tar.gz",
+            10,
+            days(40),
+        );
+        write(
+            "maa_cli-v0.4.8-x86_64-unknown-linux-gnu.tar.gz",
+            20,
+            days(1),
+        );
+        write("MAA-v5.0.0-linux-x86_64.tar.gz", 30, days(10));
+        write("maa-core-staging/lib/libMaaCore.so", 5, days(20));
+        write("copilot/12345.json", 1, days(2));
+        write("unknown", 2, days(3));
+        // symbolic links are not followed out of the cache directory
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&outside, cache.join("resource")).unwrap();
+
+        assert!(cache_files_in(&test_root.join("not-exist"))
+            .unwrap()
+            .is_empty());
+        let files = cache_files_in(&cache).unwrap();
+        assert!(files.iter().all(|file| file.path.starts_with(&cache)));
+
+        let usages = summarize_cache(&files);
+        let usage = |component| usages.iter().find(|u| u.component == component).unwrap();
+        assert_eq!(
+            usages[0],
+            CacheUsage {
+                component: CacheComponent::Cli,
+                count: 2,
+                bytes: 30,
+                oldest: days(40),
+                newest: days(1),
+            }
+        );
+        assert_eq!(usage(CacheComponent::Core).count, 2);
+        assert_eq!(usage(CacheComponent::Core).bytes, 35);
+        assert_eq!(usage(CacheComponent::Core).oldest, days(20));
+        assert_eq!(usage(CacheComponent::Copilot).bytes, 1);
+        assert_eq!(usage(CacheComponent::Other).bytes, 2);
+        #[cfg(unix)]
+        {
+            let link = usage(CacheComponent::Resource);
+            assert_eq!(link.count, 1);
+            assert!(link.bytes < 1000);
+        }
+
+        remove_dir_all(&test_root).unwrap();
+    }
+
     #[test]
     fn ensure_name_ok() {
         assert_eq!(ensure_name("foo"), "foo");
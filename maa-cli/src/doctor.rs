@@ -0,0 +1,426 @@
+//! Checks of the installation and configs of maa-cli, used by `maa doctor`.
+//!
+//! Each check is a function returning one or more [`Check`]s, so they can be tested
+//! against broken layouts without touching the real installation.
+
+use crate::{
+    config::validate::{validate_dir, ConfigKind, Report},
+    dirs::{self, ResolvedDir},
+    locate, run,
+};
+
+use std::path::Path;
+
+use anyhow::{bail, Result};
+use prettytable::{format, row, Table};
+
+/// Result of a check
+#[cfg_attr(test, derive(Debug, PartialEq))]
+#[derive(Clone, Copy)]
+pub enum Status {
+    Pass,
+    Warn,
+    Fail,
+    /// The check is skipped, e.g. network checks with `--offline`
+    Skip,
+}
+
+impl std::fmt::Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Status::Pass => write!(f, "PASS"),
+            Status::Warn => write!(f, "WARN"),
+            Status::Fail => write!(f, "FAIL"),
+            Status::Skip => write!(f, "SKIP"),
+        }
+    }
+}
+
+/// A check with its result and a hint to fix the problem found
+#[cfg_attr(test, derive(Debug))]
+pub struct Check {
+    name: String,
+    status: Status,
+    message: String,
+    hint: Option<String>,
+}
+
+impl Check {
+    fn new(name: impl Into<String>, status: Status, message: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            status,
+            message: message.into(),
+            hint: None,
+        }
+    }
+
+    fn pass(name: impl Into<String>, message: impl Into<String>) -> Self {
+        Self::new(name, Status::Pass, message)
+    }
+
+    fn warn(name: impl Into<String>, message: impl Into<String>) -> Self {
+        Self::new(name, Status::Warn, message)
+    }
+
+    fn fail(name: impl Into<String>, message: impl Into<String>) -> Self {
+        Self::new(name, Status::Fail, message)
+    }
+
+    fn with_hint(mut self, hint: impl Into<String>) -> Self {
+        self.hint = Some(hint.into());
+        self
+    }
+}
+
+/// Check that directories exist and are writable
+///
+/// A missing profile directory is only a warning, since a profile may only have state files.
+/// The config directory is only checked to be readable, since it may be read-only.
+pub fn check_dirs(dirs: &[ResolvedDir]) -> Vec<Check> {
+    dirs.iter()
+        .map(|dir| {
+            let name = format!("{} directory", dir.name);
+            let path = dir.path.display();
+            if !dir.path.exists() {
+                let check = if dir.name == "profile" {
+                    Check::warn(name, format!("{path} does not exist"))
+                } else {
+                    Check::fail(name, format!("{path} does not exist"))
+                };
+                check.with_hint("Run any other maa command to create it")
+            } else if !dir.path.is_dir() {
+                Check::fail(name, format!("{path} is not a directory"))
+                    .with_hint("Move or remove the file in the way")
+            } else if let Err(err) = if dir.name == "config" {
+                dirs::check_readable(&dir.path)
+            } else {
+                dirs::check_writable(&dir.path)
+            } {
+                Check::fail(name, err.to_string())
+                    .with_hint(format!("Fix the permissions of {path}"))
+            } else {
+                Check::pass(name, path.to_string())
+            }
+        })
+        .collect()
+}
+
+/// Check that MaaCore can be loaded, given the version of loaded MaaCore
+pub fn check_library(version: Result<&str>) -> Check {
+    match version {
+        Ok(version) => Check::pass("MaaCore", format!("v{version} is loadable")),
+        Err(err) => Check::fail("MaaCore", format!("{err:#}")).with_hint(
+            "Run `maa install` to install MaaCore, or `maa install --force` to replace a broken one",
+        ),
+    }
+}
+
+/// Check that the version of resources in given directory is readable
+pub fn check_resource(dir: Option<&Path>) -> Check {
+    const HINT: &str = "Run `maa update` to reinstall resources";
+    let Some(dir) = dir else {
+        return Check::fail("Resource", "resources not found").with_hint(HINT);
+    };
+
+    let file = dir.join("version.json");
+    let content = match std::fs::read_to_string(&file) {
+        Ok(content) => content,
+        Err(err) => {
+            return Check::fail(
+                "Resource",
+                format!("failed to read {}: {err}", file.display()),
+            )
+            .with_hint(HINT)
+        }
+    };
+    match serde_json::from_str::<serde_json::Value>(&content) {
+        Ok(version) => match version.get("last_updated").and_then(|v| v.as_str()) {
+            Some(last_updated) => Check::pass(
+                "Resource",
+                format!("{} (last updated: {last_updated})", dir.display()),
+            ),
+            None => Check::warn(
+                "Resource",
+                format!("no `last_updated` in {}", file.display()),
+            )
+            .with_hint("Run `maa hot-update` to fetch the latest resources"),
+        },
+        Err(err) => Check::fail(
+            "Resource",
+            format!("failed to parse {}: {err}", file.display()),
+        )
+        .with_hint(HINT),
+    }
+}
+
+/// Check that all config files in given directory parse, task files are checked separately
+pub fn check_configs(root: &Path) -> Vec<Check> {
+    let reports = match validate_dir(root) {
+        Ok(reports) => reports,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(err) => {
+            return vec![Check::fail(
+                "Config files",
+                format!("failed to read {}: {err}", root.display()),
+            )]
+        }
+    };
+    let (tasks, configs): (Vec<_>, Vec<_>) = reports
+        .into_iter()
+        .partition(|report| matches!(report.kind(), ConfigKind::Task | ConfigKind::Fragment));
+
+    vec![
+        summarize("Config files", root, &configs),
+        summarize("Task files", root, &tasks),
+    ]
+}
+
+fn summarize(name: &str, root: &Path, reports: &[Report]) -> Check {
+    let invalid: Vec<_> = reports
+        .iter()
+        .filter_map(|report| {
+            report.error().map(|error| {
+                let path = report.path().strip_prefix(root).unwrap_or(report.path());
+                format!("{}: {error}", path.display())
+            })
+        })
+        .collect();
+    match invalid.first() {
+        None => Check::pass(name, format!("{} files parsed", reports.len())),
+        Some(first) => Check::fail(
+            name,
+            format!(
+                "{} of {} files invalid, {first}",
+                invalid.len(),
+                reports.len()
+            ),
+        )
+        .with_hint("Run `maa task validate-all` to list all invalid files"),
+    }
+}
+
+/// Check that the metadata url of MaaCore is reachable
+#[cfg(feature = "core_installer")]
+pub fn check_network(url: &str, ip_family: crate::config::cli::IpFamily) -> Check {
+    match crate::installer::check_reachable(url, ip_family, std::time::Duration::from_secs(10)) {
+        Ok(()) => Check::pass("Network", format!("{url} is reachable")),
+        Err(err) => Check::fail("Network", format!("{err:#}")).with_hint(
+            "Check your network and proxy, or set `core.api_url` in cli.toml to a mirror",
+        ),
+    }
+}
+
+fn checks(offline: bool) -> Vec<Check> {
+    let mut checks = check_dirs(&dirs::resolved());
+    checks.push(check_library(run::core_version()));
+    checks.push(check_resource(
+        locate::find_resource().as_ref().map(locate::Located::path),
+    ));
+    checks.extend(check_configs(dirs::config()));
+
+    if offline {
+        checks.push(Check::new("Network", Status::Skip, "skipped by --offline"));
+    } else {
+        #[cfg(feature = "core_installer")]
+        {
+            let config = crate::config::cli::cli_config().core_config();
+            checks.push(check_network(&config.api_url(), config.ip_family()));
+        }
+        #[cfg(not(feature = "core_installer"))]
+        checks.push(Check::new(
+            "Network",
+            Status::Skip,
+            "maa-cli is built without the installer",
+        ));
+    }
+
+    checks
+}
+
+fn table(checks: &[Check]) -> Table {
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
+    table.set_titles(row!["CHECK", "STATUS", "MESSAGE"]);
+    for check in checks {
+        table.add_row(row![check.name, check.status, check.message]);
+    }
+    table
+}
+
+/// Run all checks and print the results, an error is returned if any check fails.
+pub fn doctor(offline: bool) -> Result<()> {
+    let checks = checks(offline);
+    table(&checks).printstd();
+
+    let hints: Vec<_> = checks
+        .iter()
+        .filter_map(|check| check.hint.as_ref().map(|hint| (check, hint)))
+        .filter(|(check, _)| matches!(check.status, Status::Warn | Status::Fail))
+        .collect();
+    if !hints.is_empty() {
+        println!("\nHints:");
+        for (check, hint) in hints {
+            println!("  {}: {hint}", check.name);
+        }
+    }
+
+    let failed = checks
+        .iter()
+        .filter(|check| matches!(check.status, Status::Fail))
+        .count();
+    if failed > 0 {
+        bail!("{failed} of {} checks failed", checks.len());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::dirs::Ensure;
+
+    use std::{env::temp_dir, fs, path::PathBuf};
+
+    fn status(checks: &[Check]) -> Vec<Status> {
+        checks.iter().map(|check| check.status).collect()
+    }
+
+    fn resolved(name: &'static str, path: PathBuf) -> ResolvedDir {
+        ResolvedDir {
+            name,
+            exists: path.is_dir(),
+            path,
+        }
+    }
+
+    #[test]
+    fn dirs() {
+        let root = temp_dir().join("maa-test-doctor-dirs");
+        root.as_path().ensure_clean().unwrap();
+        let file = root.join("file");
+        fs::write(&file, "").unwrap();
+
+        let checks = check_dirs(&[
+            resolved("config", root.clone()),
+            resolved("data", root.join("missing")),
+            resolved("profile", root.join("missing")),
+            resolved("cache", file),
+        ]);
+        assert_eq!(
+            status(&checks),
+            [Status::Pass, Status::Fail, Status::Warn, Status::Fail]
+        );
+        assert_eq!(checks[1].name, "data directory");
+        assert!(checks[3].message.ends_with("is not a directory"));
+        assert!(checks[3].hint.is_some());
+        // the probe file is removed
+        assert_eq!(fs::read_dir(&root).unwrap().count(), 1);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn library() {
+        assert_eq!(check_library(Ok("5.0.0")).status, Status::Pass);
+        let check = check_library(Err(anyhow::anyhow!("MaaCore not found")));
+        assert_eq!(check.status, Status::Fail);
+        assert_eq!(check.message, "MaaCore not found");
+        assert!(check.hint.unwrap().contains("maa install"));
+    }
+
+    #[test]
+    fn resource() {
+        let root = temp_dir().join("maa-test-doctor-resource");
+        root.as_path().ensure_clean().unwrap();
+
+        assert_eq!(check_resource(None).status, Status::Fail);
+        // empty resource directory
+        assert_eq!(check_resource(Some(&root)).status, Status::Fail);
+
+        fs::write(root.join("version.json"), "{").unwrap();
+        let check = check_resource(Some(&root));
+        assert_eq!(check.status, Status::Fail);
+        assert!(check.message.starts_with("failed to parse"));
+
+        fs::write(root.join("version.json"), "{}").unwrap();
+        assert_eq!(check_resource(Some(&root)).status, Status::Warn);
+
+        fs::write(
+            root.join("version.json"),
+            r#"{"last_updated": "2024-05-25 10:00:00.000"}"#,
+        )
+        .unwrap();
+        let check = check_resource(Some(&root));
+        assert_eq!(check.status, Status::Pass);
+        assert!(check
+            .message
+            .ends_with("(last updated: 2024-05-25 10:00:00.000)"));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn configs() {
+        let root = temp_dir().join("maa-test-doctor-configs");
+        root.as_path().ensure_clean().unwrap();
+
+        // a missing config directory has nothing to check
+        assert_eq!(
+            status(&check_configs(&root.join("missing"))),
+            [Status::Pass, Status::Pass]
+        );
+
+        fs::write(root.join("cli.toml"), "[core]\nchannel = \"beta\"\n").unwrap();
+        let tasks = root.join("tasks");
+        tasks.as_path().ensure().unwrap();
+        fs::write(tasks.join("daily.toml"), "[[tasks]]\ntype = \"StartUp\"\n").unwrap();
+        let checks = check_configs(&root);
+        assert_eq!(status(&checks), [Status::Pass, Status::Pass]);
+        assert_eq!(checks[0].message, "1 files parsed");
+
+        // a task file which can not be deserialized
+        fs::write(tasks.join("broken.json"), r#"{"foo": 1}"#).unwrap();
+        fs::write(root.join("infrast.json"), "{").unwrap();
+        let checks = check_configs(&root);
+        assert_eq!(status(&checks), [Status::Fail, Status::Fail]);
+        assert!(checks[0]
+            .message
+            .starts_with("1 of 2 files invalid, infrast.json"));
+        assert!(checks[1].message.starts_with("1 of 2 files invalid, tasks"));
+        assert!(checks[1].hint.is_some());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "core_installer")]
+    fn network() {
+        use crate::{
+            config::cli::IpFamily,
+            installer::test_server::{Response, Server},
+        };
+
+        let server = Server::new([Response::ok("{}")]);
+        let url = server.url("/stable.json");
+        assert_eq!(check_network(&url, IpFamily::Auto).status, Status::Pass);
+
+        let server = Server::new([Response::status(404)]);
+        let url = server.url("/stable.json");
+        let check = check_network(&url, IpFamily::Auto);
+        assert_eq!(check.status, Status::Fail);
+        assert!(check.hint.is_some());
+    }
+
+    #[test]
+    fn report() {
+        let checks = [
+            Check::pass("a", "ok"),
+            Check::new("b", Status::Skip, "skipped"),
+        ];
+        let table = table(&checks).to_string();
+        assert!(table.contains("PASS"), "{table}");
+        assert!(table.contains("SKIP"), "{table}");
+    }
+}
@@ -0,0 +1,275 @@
+//! Check available disk space before downloading and extracting assets.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Result};
+use log::debug;
+
+/// Estimated ratio of extracted size to archive size.
+///
+/// Most files in the release archives are compressed by about 2-3 times,
+/// so we take a conservative estimation.
+const EXTRACT_RATIO: u64 = 3;
+
+/// Estimated size of files extracted from an archive of given size.
+pub fn extracted_size(archive_size: u64) -> u64 {
+    archive_size.saturating_mul(EXTRACT_RATIO)
+}
+
+/// Space requirement of a path, which may not exist yet.
+pub struct Requirement<'a> {
+    path: &'a Path,
+    bytes: u64,
+}
+
+impl<'a> Requirement<'a> {
+    pub fn new(path: &'a Path, bytes: u64) -> Self {
+        Self { path, bytes }
+    }
+}
+
+/// Information of the filesystem containing a path.
+#[cfg_attr(test, derive(Clone, Copy))]
+pub struct Space {
+    /// Identifier of the filesystem, `None` if unknown
+    ///
+    /// Requirements on the same filesystem are summed up.
+    id: Option<u64>,
+    /// Available bytes for current user
+    available: u64,
+}
+
+/// Check whether there is enough space for all requirements.
+pub fn check(requirements: &[Requirement]) -> Result<()> {
+    check_with(requirements, probe)
+}
+
+fn check_with(
+    requirements: &[Requirement],
+    probe: impl Fn(&Path) -> io::Result<Space>,
+) -> Result<()> {
+    // (filesystem id, available bytes, required bytes, paths)
+    let mut filesystems: Vec<(Option<u64>, u64, u64, Vec<&Path>)> = Vec::new();
+
+    for requirement in requirements {
+        let path = requirement.path;
+        let space = probe(path)
+            .with_context(|| format!("Failed to get available space of {}", path.display()))?;
+        debug!(
+            "Available space of {}: {} bytes, required: {} bytes",
+            path.display(),
+            space.available,
+            requirement.bytes
+        );
+
+        match filesystems
+            .iter_mut()
+            .find(|(id, ..)| id.is_some() && *id == space.id)
+        {
+            Some((_, _, required, paths)) => {
+                *required = required.saturating_add(requirement.bytes);
+                paths.push(path);
+            }
+            None => filesystems.push((space.id, space.available, requirement.bytes, vec![path])),
+        }
+    }
+
+    for (_, available, required, paths) in filesystems {
+        if required > available {
+            let paths = paths
+                .iter()
+                .map(|p| p.display().to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            bail!(
+                "Not enough disk space for {paths}: {required} bytes required, \
+                 but only {available} bytes available \
+                 (use `--no-space-check` to skip this check)"
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// The nearest existing ancestor of a path, which may be the path itself.
+fn existing_ancestor(path: &Path) -> PathBuf {
+    path.ancestors()
+        .find(|p| p.exists())
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+#[cfg(unix)]
+fn probe(path: &Path) -> io::Result<Space> {
+    use std::{ffi::CString, mem::MaybeUninit, os::unix::prelude::*};
+
+    let path = existing_ancestor(path);
+    let c_path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+    let mut stat = MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: c_path is a valid C string and stat is a valid pointer to write to
+    if unsafe { libc::statvfs(c_path.as_ptr(), stat.as_mut_ptr()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: statvfs succeeded so stat is initialized
+    let stat = unsafe { stat.assume_init() };
+
+    #[allow(clippy::unnecessary_cast)] // the types of fields vary between platforms
+    let available = (stat.f_bavail as u64).saturating_mul(stat.f_frsize as u64);
+
+    Ok(Space {
+        id: Some(path.metadata()?.dev()),
+        available,
+    })
+}
+
+#[cfg(windows)]
+fn probe(path: &Path) -> io::Result<Space> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows::{core::PCWSTR, Win32::Storage::FileSystem::GetDiskFreeSpaceExW};
+
+    let path = existing_ancestor(path);
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+
+    let mut available = 0u64;
+    // SAFETY: wide is a null-terminated wide string and available is a valid pointer
+    unsafe {
+        GetDiskFreeSpaceExW(
+            PCWSTR(wide.as_ptr()),
+            Some(&mut available as *mut u64),
+            None,
+            None,
+        )
+    }
+    .map_err(io::Error::other)?;
+
+    Ok(Space {
+        id: None,
+        available,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stub(spaces: &'static [(&'static str, Space)]) -> impl Fn(&Path) -> io::Result<Space> {
+        move |path| {
+            spaces
+                .iter()
+                .find(|(prefix, _)| path.starts_with(prefix))
+                .map(|(_, space)| *space)
+                .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))
+        }
+    }
+
+    const SPACES: &[(&str, Space)] = &[
+        (
+            "/cache",
+            Space {
+                id: Some(1),
+                available: 100,
+            },
+        ),
+        (
+            "/data",
+            Space {
+                id: Some(1),
+                available: 100,
+            },
+        ),
+        (
+            "/other",
+            Space {
+                id: Some(2),
+                available: 300,
+            },
+        ),
+        (
+            "/unknown",
+            Space {
+                id: None,
+                available: 100,
+            },
+        ),
+    ];
+
+    #[test]
+    fn enough_space() {
+        check_with(
+            &[Requirement::new(Path::new("/cache/a"), 100)],
+            stub(SPACES),
+        )
+        .unwrap();
+        check_with(
+            &[
+                Requirement::new(Path::new("/cache/a"), 50),
+                Requirement::new(Path::new("/data/b"), 50),
+                Requirement::new(Path::new("/other/c"), 300),
+            ],
+            stub(SPACES),
+        )
+        .unwrap();
+        // filesystems with unknown id are not summed up
+        check_with(
+            &[
+                Requirement::new(Path::new("/unknown/a"), 100),
+                Requirement::new(Path::new("/unknown/b"), 100),
+            ],
+            stub(SPACES),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn not_enough_space() {
+        let err = check_with(
+            &[Requirement::new(Path::new("/cache/a"), 101)],
+            stub(SPACES),
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains("/cache/a"));
+        assert!(err.contains("101 bytes required"));
+        assert!(err.contains("100 bytes available"));
+
+        // requirements on the same filesystem are summed up
+        let err = check_with(
+            &[
+                Requirement::new(Path::new("/cache/a"), 60),
+                Requirement::new(Path::new("/data/b"), 60),
+                Requirement::new(Path::new("/other/c"), 60),
+            ],
+            stub(SPACES),
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains("/cache/a, /data/b"));
+        assert!(err.contains("120 bytes required"));
+    }
+
+    #[test]
+    fn probe_failed() {
+        assert!(check_with(&[Requirement::new(Path::new("/foo"), 1)], stub(SPACES)).is_err());
+    }
+
+    #[test]
+    fn probe_temp_dir() {
+        let dir = std::env::temp_dir()
+            .join("maa-disk-space-probe")
+            .join("not-exist");
+        probe(&dir).unwrap();
+        assert_eq!(existing_ancestor(&dir), std::env::temp_dir());
+    }
+
+    #[test]
+    fn extracted() {
+        assert_eq!(extracted_size(100), 300);
+        assert_eq!(extracted_size(u64::MAX), u64::MAX);
+    }
+}
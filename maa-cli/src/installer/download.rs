@@ -1,9 +1,10 @@
-use log::debug;
+use log::{debug, warn};
 
 use std::cmp::min;
 use std::fs::{remove_file, File};
 use std::io::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 use std::time::{Duration, Instant};
 
 use digest::Digest;
@@ -11,12 +12,21 @@ use futures_util::StreamExt;
 use indicatif::{ProgressBar, ProgressStyle};
 use reqwest::Client;
 use sha2::Sha256;
+#[cfg(feature = "cli_installer")]
+use sha2::Sha512;
 
 #[derive(Debug)]
 pub enum Error {
     Reqwest(reqwest::Error),
     Io(std::io::Error),
-    Verify,
+    /// The checksum of given algorithm is mismatched
+    Verify(&'static str),
+    /// The size reported by the server or downloaded from the final url is not the expected one
+    Size {
+        expected: u64,
+        actual: u64,
+        url: String,
+    },
 }
 
 impl From<reqwest::Error> for Error {
@@ -36,121 +46,539 @@ impl std::fmt::Display for Error {
         match self {
             Error::Reqwest(e) => e.fmt(f),
             Error::Io(e) => e.fmt(f),
-            Error::Verify => write!(f, "Checksum verification failed"),
+            Error::Verify(algorithm) => write!(
+                f,
+                "Checksum verification failed ({algorithm} mismatched), \
+                 the corrupted file has been removed, please try again"
+            ),
+            Error::Size {
+                expected,
+                actual,
+                url,
+            } => write!(
+                f,
+                "Size mismatched, expected {expected} bytes but got {actual} bytes from {url}, \
+                 the mirror may be stale or the file is truncated"
+            ),
         }
     }
 }
 
 impl std::error::Error for Error {}
 
+#[cfg(feature = "core_installer")]
+impl Error {
+    /// Whether the error is transient, so the download may succeed if retried.
+    ///
+    /// Timeouts, connection failures and resets, server errors (5xx) and rate limiting (429)
+    /// are recoverable, while other errors like checksum or size mismatches are not.
+    pub fn is_recoverable(&self) -> bool {
+        match self {
+            Error::Reqwest(e) => {
+                e.is_timeout()
+                    || e.is_connect()
+                    || e.is_body()
+                    || e.status().is_some_and(|s| {
+                        s.is_server_error() || s == reqwest::StatusCode::TOO_MANY_REQUESTS
+                    })
+            }
+            _ => false,
+        }
+    }
+}
+
 type Result<T> = std::result::Result<T, Error>;
 
 /// Checksum checker.
 ///
-/// Currently only support sha256.
-/// Used to verify the integrity of downloaded files.
+/// Currently support sha256 and sha512, the latter is only used by the cli installer.
+/// Used to verify the integrity of downloaded files, multiple checkers can be used at the
+/// same time, e.g. checksums from both the version json and a sidecar file.
 pub enum Checker<'a> {
     Sha256(&'a str),
+    #[cfg(feature = "cli_installer")]
+    Sha512(&'a str),
 }
 
 impl<'a> Checker<'a> {
     fn hasher(&self) -> Hasher {
         match self {
             Self::Sha256(_) => Hasher::Sha256(Sha256::new()),
+            #[cfg(feature = "cli_installer")]
+            Self::Sha512(_) => Hasher::Sha512(Sha512::new()),
         }
     }
 
     fn checksum(&self) -> &str {
         match self {
             Self::Sha256(checksum) => checksum,
+            #[cfg(feature = "cli_installer")]
+            Self::Sha512(checksum) => checksum,
+        }
+    }
+
+    /// Name of the algorithm, used in error messages.
+    pub fn algorithm(&self) -> &'static str {
+        match self {
+            Self::Sha256(_) => "sha256",
+            #[cfg(feature = "cli_installer")]
+            Self::Sha512(_) => "sha512",
         }
     }
 }
 
 enum Hasher {
     Sha256(Sha256),
+    #[cfg(feature = "cli_installer")]
+    Sha512(Sha512),
 }
 
 impl Hasher {
     pub fn update(&mut self, data: &[u8]) {
         match self {
             Self::Sha256(hasher) => hasher.update(data),
+            #[cfg(feature = "cli_installer")]
+            Self::Sha512(hasher) => hasher.update(data),
         }
     }
 
     pub fn verify(self, checksum: &str) -> bool {
-        match self {
-            Self::Sha256(hasher) => {
-                let digest = format!("{:x}", hasher.finalize());
-                digest == *checksum
+        let digest = match self {
+            Self::Sha256(hasher) => format!("{:x}", hasher.finalize()),
+            #[cfg(feature = "cli_installer")]
+            Self::Sha512(hasher) => format!("{:x}", hasher.finalize()),
+        };
+        digest.eq_ignore_ascii_case(checksum)
+    }
+}
+
+/// Hashers of multiple checkers, which are updated at the same time.
+struct Hashers<'c, 'a> {
+    checkers: &'c [Checker<'a>],
+    hashers: Vec<Hasher>,
+}
+
+impl<'c, 'a> Hashers<'c, 'a> {
+    fn new(checkers: &'c [Checker<'a>]) -> Self {
+        Self {
+            checkers,
+            hashers: checkers.iter().map(Checker::hasher).collect(),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        self.hashers.iter_mut().for_each(|h| h.update(data));
+    }
+
+    /// Return the first checker whose checksum is mismatched, `None` if all matched.
+    fn mismatched(self) -> Option<&'c Checker<'a>> {
+        self.hashers
+            .into_iter()
+            .zip(self.checkers)
+            .find_map(|(hasher, checker)| (!hasher.verify(checker.checksum())).then_some(checker))
+    }
+}
+
+/// Verify an existing file with all given checkers, and return the first mismatched one.
+#[cfg(feature = "cli_installer")]
+fn verify_file_all<'c, 'a>(
+    checkers: &'c [Checker<'a>],
+    path: &Path,
+) -> std::io::Result<Option<&'c Checker<'a>>> {
+    let mut hashers = Hashers::new(checkers);
+    let mut file = File::open(path)?;
+    let mut buf = [0; 8192];
+    loop {
+        let n = std::io::Read::read(&mut file, &mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hashers.update(&buf[..n]);
+    }
+    Ok(hashers.mismatched())
+}
+
+/// A token bucket to limit the download rate.
+///
+/// The bucket can be shared by concurrent downloads, so the total rate is limited.
+/// Bursts up to one second of the rate are allowed.
+pub struct Throttle {
+    /// Maximum rate in bytes per second
+    rate: u64,
+    bucket: Mutex<Bucket>,
+}
+
+struct Bucket {
+    /// Available bytes, negative if more bytes have been consumed than allowed
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl Throttle {
+    pub fn new(rate: u64) -> Self {
+        Self::new_at(rate, Instant::now())
+    }
+
+    fn new_at(rate: u64, now: Instant) -> Self {
+        Self {
+            rate: rate.max(1),
+            bucket: Mutex::new(Bucket {
+                tokens: rate as f64,
+                last_refill: now,
+            }),
+        }
+    }
+
+    /// Consume given bytes from the bucket, and return how long to wait before continuing.
+    fn delay_at(&self, now: Instant, bytes: u64) -> Duration {
+        let rate = self.rate as f64;
+        let mut bucket = self.bucket.lock().unwrap_or_else(|e| e.into_inner());
+
+        let elapsed = now.saturating_duration_since(bucket.last_refill);
+        bucket.tokens = (bucket.tokens + elapsed.as_secs_f64() * rate).min(rate);
+        bucket.last_refill = now;
+        bucket.tokens -= bytes as f64;
+
+        if bucket.tokens < 0.0 {
+            Duration::from_secs_f64(-bucket.tokens / rate)
+        } else {
+            Duration::ZERO
+        }
+    }
+
+    /// Wait until given bytes are allowed to be consumed.
+    async fn consume(&self, bytes: u64) {
+        let delay = self.delay_at(Instant::now(), bytes);
+        if !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+/// Events of download progress, see [`Progress`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProgressEvent {
+    /// The download is started, with the total size in bytes
+    Begin { total: u64 },
+    /// Some bytes are downloaded, sent at most every [`PROGRESS_INTERVAL`]
+    /// and when the last byte is downloaded
+    Update {
+        downloaded: u64,
+        total: u64,
+        bytes_per_sec: f64,
+    },
+    /// The download is finished, with the bytes downloaded
+    End { downloaded: u64, total: u64 },
+}
+
+/// Minimum interval between two [`ProgressEvent::Update`] events
+pub const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A sink of download progress events.
+///
+/// The terminal progress bar [`ProgressBarSink`] is used if no sink is given to [`download`],
+/// a closure can be used as a sink, and `&mut |_| {}` discards all events.
+pub trait Progress: Send {
+    fn on_event(&mut self, event: ProgressEvent);
+}
+
+impl<F: FnMut(ProgressEvent) + Send> Progress for F {
+    fn on_event(&mut self, event: ProgressEvent) {
+        self(event)
+    }
+}
+
+/// Step of download percentages logged by [`ProgressBarSink`]
+const PROGRESS_LOG_STEP: u64 = 25;
+
+/// The default progress sink, which draws a progress bar in the terminal.
+///
+/// The progress bar is not logged, only milestones of every [`PROGRESS_LOG_STEP`] percent are,
+/// so log files are not flooded by progress updates.
+#[derive(Default)]
+pub struct ProgressBarSink {
+    bar: Option<ProgressBar>,
+    logged: u64,
+}
+
+impl ProgressBarSink {
+    /// The milestone percentage reached by `downloaded` bytes, if it is not logged yet
+    fn milestone(&mut self, downloaded: u64, total: u64) -> Option<u64> {
+        if total == 0 {
+            return None;
+        }
+        let percent = (downloaded.min(total) * 100 / total) / PROGRESS_LOG_STEP * PROGRESS_LOG_STEP;
+        if percent > self.logged {
+            self.logged = percent;
+            Some(percent)
+        } else {
+            None
+        }
+    }
+
+    fn log_milestone(&mut self, downloaded: u64, total: u64) {
+        if let Some(percent) = self.milestone(downloaded, total) {
+            debug!("Downloaded {percent}% ({downloaded}/{total} bytes)");
+        }
+    }
+}
+
+impl Progress for ProgressBarSink {
+    fn on_event(&mut self, event: ProgressEvent) {
+        match event {
+            ProgressEvent::Begin { total } => {
+                let bar = ProgressBar::new(total);
+                bar.set_style(
+                    ProgressStyle::default_bar()
+                        .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})")
+                        .unwrap()
+                        .progress_chars("=>-"),
+                );
+                bar.set_message("Downloading...");
+                self.bar = Some(bar);
+                self.logged = 0;
+            }
+            ProgressEvent::Update {
+                downloaded, total, ..
+            } => {
+                if let Some(bar) = &self.bar {
+                    bar.set_position(downloaded);
+                }
+                self.log_milestone(downloaded, total);
+            }
+            ProgressEvent::End { downloaded, total } => {
+                self.log_milestone(downloaded, total);
+                if let Some(bar) = self.bar.take() {
+                    bar.set_position(downloaded);
+                    bar.finish_with_message("Downloaded.");
+                }
             }
         }
     }
 }
 
 // download a file with given url and size to a given path,
-// with optional checksum verification.
+// verified by all given checksum checkers.
+//
+// If any checksum verification failed, the downloaded file will be removed,
+// and the download will be retried once. If no checker is given,
+// the file is not verified with a warning.
 //
 // # Arguments
 // * `client` - A reqwest client.
 // * `url` - The url to download from.
 // * `path` - The path to save the downloaded file.
 // * `size` - The size of the file.
-// * `checker` - The optional checksum checker.
-pub async fn download<'a>(
+// * `checkers` - The checksum checkers, all of them must pass.
+// * `throttle` - The optional throttle to limit the download rate.
+// * `progress` - The sink of progress events, a terminal progress bar if `None`.
+pub async fn download(
     client: &Client,
     url: &str,
     path: &Path,
     size: u64,
-    checker: Option<Checker<'a>>,
+    checkers: &[Checker<'_>],
+    throttle: Option<&Throttle>,
+    progress: Option<&mut dyn Progress>,
 ) -> Result<()> {
-    let resp = client.get(url).send().await?;
+    if checkers.is_empty() {
+        warn!(
+            "No checksum to verify {}, it will NOT be verified",
+            path.display()
+        );
+    }
+    let mut default_sink = ProgressBarSink::default();
+    let progress: &mut dyn Progress = match progress {
+        Some(progress) => progress,
+        None => &mut default_sink,
+    };
+    match download_once(client, url, path, size, checkers, throttle, &mut *progress).await {
+        Err(Error::Verify(algorithm)) => {
+            warn!("Checksum verification failed ({algorithm} mismatched), removed corrupted file and retrying...");
+            download_once(client, url, path, size, checkers, throttle, progress).await
+        }
+        result => result,
+    }
+}
 
-    let progress_bar = ProgressBar::new(size);
-    progress_bar.set_style(
-        ProgressStyle::default_bar()
-            .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})")
-            .unwrap()
-            .progress_chars("=>-"),
-    );
-    progress_bar.set_message("Downloading...");
+/// Reuse the file at given path if its size matches and it passes the checksum verification,
+/// otherwise remove it and download a new one.
+#[cfg(feature = "cli_installer")]
+pub async fn fetch(
+    client: &Client,
+    url: &str,
+    path: &Path,
+    size: u64,
+    checkers: &[Checker<'_>],
+    throttle: Option<&Throttle>,
+    progress: Option<&mut dyn Progress>,
+) -> Result<()> {
+    if check_file_exists(path, size) {
+        match verify_file_all(checkers, path)? {
+            Some(checker) => {
+                warn!(
+                    "Existing file {} is corrupted ({} mismatched), removing it",
+                    path.display(),
+                    checker.algorithm()
+                );
+                remove_file(path)?;
+            }
+            None => {
+                println!("Found existing file: {}", path.display());
+                return Ok(());
+            }
+        }
+    }
+
+    download(client, url, path, size, checkers, throttle, progress).await
+}
+
+async fn download_once(
+    client: &Client,
+    url: &str,
+    path: &Path,
+    size: u64,
+    checkers: &[Checker<'_>],
+    throttle: Option<&Throttle>,
+    progress: &mut dyn Progress,
+) -> Result<()> {
+    let mut source = Source::open(client, url, size).await?;
 
-    let mut stream = resp.bytes_stream();
     let mut file = File::create(path)?;
+    let mut hashers = Hashers::new(checkers);
+    let mut downloaded: u64 = 0;
 
-    if let Some(checker) = checker {
-        let mut downloaded: u64 = 0;
-        let mut hasher = checker.hasher();
+    progress.on_event(ProgressEvent::Begin { total: size });
+    let start = Instant::now();
+    let mut last_update: Option<Instant> = None;
+    while let Some(chunk) = source.next().await? {
+        if let Some(throttle) = throttle {
+            throttle.consume(chunk.len() as u64).await;
+        }
+        file.write_all(&chunk)?;
+        hashers.update(&chunk);
+        downloaded += chunk.len() as u64;
 
-        while let Some(chunk) = stream.next().await {
-            let chunk = chunk?;
-            file.write_all(&chunk)?;
-            hasher.update(&chunk);
-            downloaded = min(downloaded + chunk.len() as u64, size);
-            progress_bar.set_position(downloaded);
+        let now = Instant::now();
+        if downloaded == size || last_update.is_none_or(|t| now - t >= PROGRESS_INTERVAL) {
+            last_update = Some(now);
+            let elapsed = (now - start).as_secs_f64();
+            progress.on_event(ProgressEvent::Update {
+                downloaded: min(downloaded, size),
+                total: size,
+                bytes_per_sec: if elapsed > 0.0 {
+                    downloaded as f64 / elapsed
+                } else {
+                    0.0
+                },
+            });
         }
+    }
+    progress.on_event(ProgressEvent::End {
+        downloaded: min(downloaded, size),
+        total: size,
+    });
 
-        progress_bar.finish_with_message("Downloaded, verifying checksum...");
+    if downloaded != size {
+        remove_file(path)?;
+        return Err(Error::Size {
+            expected: size,
+            actual: downloaded,
+            url: source.final_url(url),
+        });
+    }
 
-        if hasher.verify(checker.checksum()) {
-            println!("Checksum verified");
-        } else {
+    if !checkers.is_empty() {
+        println!("Verifying checksum...");
+        if let Some(checker) = hashers.mismatched() {
             remove_file(path)?;
-            return Err(Error::Verify);
+            return Err(Error::Verify(checker.algorithm()));
+        }
+        println!("Checksum verified");
+    }
+
+    Ok(())
+}
+
+/// Source of a download, a response of HTTP request or a local file for `file://` urls.
+enum Source {
+    Http(reqwest::Response),
+    File(File),
+}
+
+impl Source {
+    /// Open the source of given url, the size of a local file is checked before copying.
+    async fn open(client: &Client, url: &str, size: u64) -> Result<Self> {
+        match local_path(url) {
+            Some(path) => {
+                debug!("Copying from local file {}", path.display());
+                let file = File::open(&path)?;
+                let actual = file.metadata()?.len();
+                if actual != size {
+                    return Err(Error::Io(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!(
+                            "Size of {} is {actual} bytes, expected {size} bytes",
+                            path.display()
+                        ),
+                    )));
+                }
+                Ok(Self::File(file))
+            }
+            None => {
+                let resp = client.get(url).send().await?.error_for_status()?;
+                if resp.url().as_str() != url {
+                    debug!("Downloading from {} (redirected from {url})", resp.url());
+                }
+                // Abort before downloading the whole file from a stale mirror
+                match resp.content_length() {
+                    Some(actual) if actual != size => {
+                        return Err(Error::Size {
+                            expected: size,
+                            actual,
+                            url: resp.url().to_string(),
+                        })
+                    }
+                    Some(_) => {}
+                    None => debug!("No Content-Length in response of {url}, size is not checked"),
+                }
+                Ok(Self::Http(resp))
+            }
         }
-    } else {
-        let mut downloaded: u64 = 0;
-        while let Some(chunk) = stream.next().await {
-            let chunk = chunk?;
-            file.write_all(&chunk)?;
-            downloaded = min(downloaded + chunk.len() as u64, size);
-            progress_bar.set_position(downloaded);
+    }
+
+    /// The url the content is actually from, which differs from `url` if redirected.
+    fn final_url(&self, url: &str) -> String {
+        match self {
+            Self::Http(resp) => resp.url().to_string(),
+            Self::File(_) => url.to_owned(),
         }
+    }
 
-        progress_bar.finish_with_message("Downloaded.");
+    /// Read next chunk of the source, `None` if the end is reached.
+    async fn next(&mut self) -> Result<Option<Vec<u8>>> {
+        match self {
+            Self::Http(resp) => Ok(resp.chunk().await?.map(Vec::from)),
+            Self::File(file) => {
+                let mut buf = vec![0; 64 * 1024];
+                let n = std::io::Read::read(file, &mut buf)?;
+                buf.truncate(n);
+                Ok((n > 0).then_some(buf))
+            }
+        }
     }
+}
 
-    Ok(())
+/// Get the local path of a `file://` url, `None` if the url is not a file url.
+///
+/// Percent-encoded characters are decoded, and drive letters are supported on Windows,
+/// e.g. `file:///C:/mirror/maa.zip` is `C:\mirror\maa.zip`.
+pub fn local_path(url: &str) -> Option<PathBuf> {
+    let url = reqwest::Url::parse(url).ok()?;
+    if url.scheme() != "file" {
+        return None;
+    }
+    url.to_file_path().ok()
 }
 
 /// Try to download a file with given url and timeout.
@@ -163,6 +591,11 @@ pub async fn download<'a>(
 /// # Returns
 /// The number of bytes downloaded.
 async fn try_download(client: &Client, url: &str, timeout: Duration) -> Result<u64> {
+    // A local file is always the fastest
+    if let Some(path) = local_path(url) {
+        return Ok(path.metadata()?.len());
+    }
+
     let resp = client.get(url).send().await?;
 
     let mut stream = resp.bytes_stream();
@@ -188,14 +621,16 @@ async fn try_download(client: &Client, url: &str, timeout: Duration) -> Result<u
 /// * `path` - The path to save the downloaded file.
 /// * `size` - The size of the file.
 /// * `t` - The test duration for each mirror, in seconds, 0 to skip the test.
-/// * `checker` - The optional checksum checker.
-pub async fn download_mirrors<'a>(
+/// * `checkers` - The checksum checkers, all of them must pass.
+/// * `throttle` - The optional throttle to limit the download rate, not applied to speed test.
+pub async fn download_mirrors(
     client: &Client,
     mirrors: Vec<String>,
     path: &Path,
     size: u64,
     t: u64,
-    checker: Option<Checker<'a>>,
+    checkers: &[Checker<'_>],
+    throttle: Option<&Throttle>,
 ) -> Result<()> {
     // The first mirror is the default download link.
     let mut download_link = &mirrors[0];
@@ -203,7 +638,7 @@ pub async fn download_mirrors<'a>(
     if t == 0 {
         println!("Skip speed test, downloading from first link...");
         debug!("First link: {}", download_link);
-        download(client, download_link, path, size, checker).await?;
+        download(client, download_link, path, size, checkers, throttle, None).await?;
         return Ok(());
     }
 
@@ -227,11 +662,510 @@ pub async fn download_mirrors<'a>(
 
     println!("Downloading from fastest mirror...");
     debug!("Fastest link: {}", download_link);
-    download(client, download_link, path, size, checker).await?;
+    download(client, download_link, path, size, checkers, throttle, None).await?;
 
     Ok(())
 }
 
+/// Compute the sha256 checksum of a file, in lowercase hex.
+pub fn sha256sum(path: &Path) -> std::io::Result<String> {
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Find the checksum of given file in the content of a checksum file.
+///
+/// The content is in the format of `sha256sum` output, i.e. `<hex>  <filename>` per line,
+/// where the filename may be prefixed with `*` (binary mode) or contain directories.
+/// A checksum file with a single bare checksum is also accepted.
+/// Lines are matched by the file name, so a list of checksums of multiple files is supported.
+#[cfg(feature = "cli_installer")]
+pub fn parse_checksum_file<'a>(content: &'a str, filename: &str) -> Option<&'a str> {
+    let is_hex = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_hexdigit());
+
+    let lines: Vec<&str> = content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect();
+
+    if let [line] = lines[..] {
+        if is_hex(line) {
+            return Some(line);
+        }
+    }
+
+    lines.into_iter().find_map(|line| {
+        let (checksum, name) = line.split_once(char::is_whitespace)?;
+        let name = name.trim_start().trim_start_matches('*');
+        let matched = Path::new(name)
+            .file_name()
+            .is_some_and(|name| name == filename);
+        (matched && is_hex(checksum)).then_some(checksum)
+    })
+}
+
 pub fn check_file_exists(path: &Path, size: u64) -> bool {
     path.exists() && path.is_file() && path.metadata().is_ok_and(|metadata| metadata.len() == size)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::installer::test_server::{client, Response, Server};
+
+    use std::{env::temp_dir, fs};
+
+    use tokio::runtime::Runtime;
+
+    const CONTENT: &[u8] = b"correct content";
+    const CORRUPTED: &[u8] = b"corrupt content";
+    // sha256 of CONTENT
+    const CHECKSUM: &str = "55d731f2fe4bc2dc72f0288f5bc9a594dc3069d1949735fa3f50fde6580012f9";
+
+    #[cfg(feature = "cli_installer")]
+    fn sha512sum(content: &[u8]) -> String {
+        format!("{:x}", Sha512::digest(content))
+    }
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        Runtime::new().unwrap().block_on(future)
+    }
+
+    #[test]
+    fn progress_milestones() {
+        let mut sink = ProgressBarSink::default();
+        let milestones: Vec<_> = [0, 10, 25, 30, 49, 50, 99, 100, 100]
+            .into_iter()
+            .filter_map(|downloaded| sink.milestone(downloaded, 100))
+            .collect();
+        assert_eq!(milestones, [25, 50, 75, 100]);
+
+        // unknown total size
+        assert_eq!(ProgressBarSink::default().milestone(100, 0), None);
+        // a retried download is logged again
+        sink.on_event(ProgressEvent::Begin { total: 200 });
+        assert_eq!(sink.milestone(60, 200), Some(25));
+    }
+
+    #[cfg(feature = "cli_installer")]
+    #[test]
+    fn parse_checksum() {
+        let name = "maa_cli-v0.4.8-x86_64-unknown-linux-gnu.tar.gz";
+
+        assert_eq!(
+            parse_checksum_file(&format!("{CHECKSUM}  {name}\n"), name),
+            Some(CHECKSUM)
+        );
+        // CRLF line endings and binary mode marker
+        assert_eq!(
+            parse_checksum_file(&format!("{CHECKSUM} *{name}\r\n"), name),
+            Some(CHECKSUM)
+        );
+        // file name with directories
+        assert_eq!(
+            parse_checksum_file(&format!("{CHECKSUM}  ./dist/{name}\n"), name),
+            Some(CHECKSUM)
+        );
+        // a list of checksums of multiple files
+        let list = format!(
+            "{}  maa_cli-v0.4.8-aarch64-apple-darwin.zip\r\n\r\n{CHECKSUM}  {name}\r\n",
+            "0".repeat(64)
+        );
+        assert_eq!(parse_checksum_file(&list, name), Some(CHECKSUM));
+        assert_eq!(parse_checksum_file(&list, "other.zip"), None);
+        // a bare checksum
+        assert_eq!(
+            parse_checksum_file(&format!("{CHECKSUM}\r\n"), name),
+            Some(CHECKSUM)
+        );
+        // invalid content
+        assert_eq!(parse_checksum_file("<html></html>", name), None);
+        assert_eq!(parse_checksum_file(&format!("not-hex  {name}"), name), None);
+        assert_eq!(parse_checksum_file("", name), None);
+    }
+
+    #[cfg(feature = "cli_installer")]
+    #[test]
+    fn checker() {
+        let path = temp_dir().join("maa-test-checker");
+        let sha512 = sha512sum(CONTENT);
+        let checkers = [Checker::Sha256(CHECKSUM), Checker::Sha512(&sha512)];
+        fs::write(&path, CONTENT).unwrap();
+        assert!(verify_file_all(&checkers, &path).unwrap().is_none());
+        assert!(verify_file_all(&[], &path).unwrap().is_none());
+        fs::write(&path, CORRUPTED).unwrap();
+        let mismatched = verify_file_all(&checkers, &path).unwrap().unwrap();
+        assert_eq!(mismatched.algorithm(), "sha256");
+        assert_eq!(sha256sum(&path).unwrap().len(), 64);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn retry_on_checksum_mismatch() {
+        let server = Server::new([Response::ok(CORRUPTED), Response::ok(CONTENT)]);
+        let path = temp_dir().join("maa-test-retry-download");
+
+        block_on(download(
+            &client(),
+            &server.url("file"),
+            &path,
+            CONTENT.len() as u64,
+            &[Checker::Sha256(CHECKSUM)],
+            None,
+            None,
+        ))
+        .unwrap();
+
+        assert_eq!(server.requests().len(), 2);
+        assert_eq!(fs::read(&path).unwrap(), CONTENT);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn remove_after_retry_failed() {
+        let server = Server::new([Response::ok(CORRUPTED)]);
+        let path = temp_dir().join("maa-test-retry-failed");
+
+        let err = block_on(download(
+            &client(),
+            &server.url("file"),
+            &path,
+            CONTENT.len() as u64,
+            &[Checker::Sha256(CHECKSUM)],
+            None,
+            None,
+        ))
+        .unwrap_err();
+
+        assert!(matches!(err, Error::Verify("sha256")));
+        assert!(err.to_string().contains("removed"));
+        assert_eq!(server.requests().len(), 2);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn size_mismatch() {
+        let size = CONTENT.len() as u64;
+        let path = temp_dir().join("maa-test-download-size-mismatch");
+        let _ = fs::remove_file(&path);
+
+        // a wrong Content-Length aborts before anything is written
+        let server = Server::new([Response::ok(CONTENT).header("Content-Length", size + 10)]);
+        let err = block_on(download(
+            &client(),
+            &server.url("file"),
+            &path,
+            size,
+            &[],
+            None,
+            None,
+        ))
+        .unwrap_err();
+        assert!(
+            matches!(err, Error::Size { expected, actual, .. } if expected == size && actual == size + 10)
+        );
+        let message = err.to_string();
+        assert!(message.contains(&size.to_string()), "{message}");
+        assert!(message.contains("stale"), "{message}");
+        assert!(!path.exists());
+
+        // without Content-Length, the downloaded size is checked at the end
+        let chunked = format!(
+            "{:x}\r\n{}\r\n0\r\n\r\n",
+            CONTENT.len(),
+            std::str::from_utf8(CONTENT).unwrap()
+        );
+        let server = Server::new([Response::ok(chunked).header("Transfer-Encoding", "chunked")]);
+        let err = block_on(download(
+            &client(),
+            &server.url("file"),
+            &path,
+            size + 1,
+            &[Checker::Sha256(CHECKSUM)],
+            None,
+            None,
+        ))
+        .unwrap_err();
+        assert!(
+            matches!(err, Error::Size { expected, actual, .. } if expected == size + 1 && actual == size)
+        );
+        assert!(!path.exists());
+
+        // and the download succeeds if it matches
+        block_on(download(
+            &client(),
+            &server.url("file"),
+            &path,
+            size,
+            &[Checker::Sha256(CHECKSUM)],
+            None,
+            None,
+        ))
+        .unwrap();
+        assert_eq!(fs::read(&path).unwrap(), CONTENT);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn progress_events() {
+        let content = vec![b'x'; 256 * 1024];
+        let server = Server::new([Response::ok(content.clone())]);
+        let path = temp_dir().join("maa-test-download-progress");
+
+        let mut events = Vec::new();
+        block_on(download(
+            &client(),
+            &server.url("file"),
+            &path,
+            content.len() as u64,
+            &[],
+            None,
+            Some(&mut |event| events.push(event)),
+        ))
+        .unwrap();
+
+        let total = content.len() as u64;
+        assert_eq!(events.first(), Some(&ProgressEvent::Begin { total }));
+        assert_eq!(
+            events.last(),
+            Some(&ProgressEvent::End {
+                downloaded: total,
+                total
+            })
+        );
+        let updates: Vec<u64> = events
+            .iter()
+            .filter_map(|event| match event {
+                ProgressEvent::Update { downloaded, .. } => Some(*downloaded),
+                _ => None,
+            })
+            .collect();
+        assert!(updates.windows(2).all(|w| w[0] <= w[1]));
+        assert_eq!(updates.last(), Some(&total));
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn download_within_runtime() {
+        let server = Server::new([Response::ok(CONTENT)]);
+        let path = temp_dir().join("maa-test-download-within-runtime");
+
+        // Calling the blocking download path from an async context (like `#[tokio::test]`)
+        // must not panic with "Cannot start a runtime from within a runtime"
+        block_on(async {
+            crate::installer::block_on(download(
+                &client(),
+                &server.url("file"),
+                &path,
+                CONTENT.len() as u64,
+                &[Checker::Sha256(CHECKSUM)],
+                None,
+                None,
+            ))
+        })
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), CONTENT);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn throttle_delay() {
+        let start = Instant::now();
+        let secs = |s: f64| Duration::from_secs_f64(s);
+        let throttle = Throttle::new_at(1000, start);
+
+        // a burst of one second is allowed
+        assert_eq!(throttle.delay_at(start, 1000), Duration::ZERO);
+        // then the rate is limited
+        assert_eq!(throttle.delay_at(start, 500), secs(0.5));
+        // the debt is paid after waiting
+        assert_eq!(throttle.delay_at(start + secs(0.5), 250), secs(0.25));
+        assert_eq!(throttle.delay_at(start + secs(0.75), 0), Duration::ZERO);
+        // tokens are refilled over time but never exceed the rate
+        assert_eq!(throttle.delay_at(start + secs(10.0), 1000), Duration::ZERO);
+        assert_eq!(throttle.delay_at(start + secs(10.0), 100), secs(0.1));
+    }
+
+    #[test]
+    fn throttled_download() {
+        let content = vec![b'x'; 3000];
+        let server = Server::new([Response::ok(content.clone())]);
+        let path = temp_dir().join("maa-test-throttled-download");
+
+        let start = Instant::now();
+        block_on(download(
+            &client(),
+            &server.url("file"),
+            &path,
+            content.len() as u64,
+            &[],
+            Some(&Throttle::new(2000)),
+            None,
+        ))
+        .unwrap();
+        // 2000 bytes are allowed as a burst, the rest 1000 bytes take 0.5s
+        assert!(start.elapsed() >= Duration::from_millis(450));
+        assert_eq!(fs::read(&path).unwrap(), content);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(feature = "cli_installer")]
+    #[test]
+    fn fetch_corrupted_cache() {
+        let server = Server::new([Response::ok(CORRUPTED), Response::ok(CONTENT)]);
+        let path = temp_dir().join("maa-test-fetch-corrupted");
+        // A corrupted file with the same size as the expected one
+        fs::write(&path, CORRUPTED).unwrap();
+
+        block_on(fetch(
+            &client(),
+            &server.url("file"),
+            &path,
+            CONTENT.len() as u64,
+            &[Checker::Sha256(CHECKSUM)],
+            None,
+            None,
+        ))
+        .unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), CONTENT);
+
+        // The valid file is reused without any request
+        let requests = server.requests().len();
+        block_on(fetch(
+            &client(),
+            &server.url("file"),
+            &path,
+            CONTENT.len() as u64,
+            &[Checker::Sha256(CHECKSUM)],
+            None,
+            None,
+        ))
+        .unwrap();
+        assert_eq!(server.requests().len(), requests);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn file_url() {
+        assert_eq!(local_path("https://foo.bar/maa.zip"), None);
+        assert_eq!(local_path("/not/a/url"), None);
+
+        #[cfg(unix)]
+        assert_eq!(
+            local_path("file:///mnt/mirror/maa%20cli.zip"),
+            Some(PathBuf::from("/mnt/mirror/maa cli.zip"))
+        );
+        #[cfg(windows)]
+        assert_eq!(
+            local_path("file:///C:/mirror/maa%20cli.zip"),
+            Some(PathBuf::from(r"C:\mirror\maa cli.zip"))
+        );
+    }
+
+    #[test]
+    fn download_local_file() {
+        let mirror = temp_dir().join("maa-test-local-mirror");
+        fs::create_dir_all(&mirror).unwrap();
+        let src = mirror.join("maa.zip");
+        fs::write(&src, CONTENT).unwrap();
+        let url = reqwest::Url::from_file_path(&src).unwrap().to_string();
+        let path = temp_dir().join("maa-test-download-local-file");
+        let size = CONTENT.len() as u64;
+
+        // the local file is chosen by the speed test without any request
+        block_on(download_mirrors(
+            &client(),
+            vec!["http://127.0.0.1:1/maa.zip".to_owned(), url.clone()],
+            &path,
+            size,
+            1,
+            &[Checker::Sha256(CHECKSUM)],
+            None,
+        ))
+        .unwrap();
+        assert_eq!(fs::read(&path).unwrap(), CONTENT);
+
+        // size mismatch
+        let err =
+            block_on(download(&client(), &url, &path, size + 1, &[], None, None)).unwrap_err();
+        assert!(err.to_string().contains("expected"), "{err}");
+
+        // checksum mismatch
+        fs::write(&src, CORRUPTED).unwrap();
+        let err = block_on(download(
+            &client(),
+            &url,
+            &path,
+            size,
+            &[Checker::Sha256(CHECKSUM)],
+            None,
+            None,
+        ))
+        .unwrap_err();
+        assert!(matches!(err, Error::Verify("sha256")));
+        assert!(!path.exists());
+
+        fs::remove_dir_all(&mirror).unwrap();
+    }
+
+    #[cfg(feature = "cli_installer")]
+    #[test]
+    fn multiple_checkers() {
+        let server = Server::new((0..4).map(|_| Response::ok(CONTENT)));
+        let path = temp_dir().join("maa-test-multiple-checkers");
+        let size = CONTENT.len() as u64;
+        let sha512 = sha512sum(CONTENT);
+
+        // all checksums agree
+        block_on(download(
+            &client(),
+            &server.url("file"),
+            &path,
+            size,
+            &[Checker::Sha256(CHECKSUM), Checker::Sha512(&sha512)],
+            None,
+            None,
+        ))
+        .unwrap();
+        assert_eq!(fs::read(&path).unwrap(), CONTENT);
+
+        // one of several checksums mismatched, which is retried and reported
+        let wrong = sha512sum(CORRUPTED);
+        let err = block_on(download(
+            &client(),
+            &server.url("file"),
+            &path,
+            size,
+            &[Checker::Sha256(CHECKSUM), Checker::Sha512(&wrong)],
+            None,
+            None,
+        ))
+        .unwrap_err();
+        assert!(matches!(err, Error::Verify("sha512")));
+        assert!(err.to_string().contains("sha512 mismatched"));
+        assert!(!path.exists());
+
+        // no checker, the file is not verified
+        block_on(download(
+            &client(),
+            &server.url("file"),
+            &path,
+            size,
+            &[],
+            None,
+            None,
+        ))
+        .unwrap();
+        assert_eq!(fs::read(&path).unwrap(), CONTENT);
+        assert_eq!(server.requests().len(), 4);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -3,11 +3,14 @@ use crate::dirs::Ensure;
 use std::{
     borrow::Cow,
     fs::File,
-    io::copy,
-    path::{Path, PathBuf},
+    io::{copy, Read},
+    path::{Component, Path, PathBuf},
 };
 
 use anyhow::{anyhow, bail, Context, Result};
+#[cfg(feature = "core_installer")]
+use glob::{MatchOptions, Pattern};
+use log::warn;
 
 /// Supported archive types.
 ///
@@ -60,117 +63,933 @@ impl<'f> Archive<'f> {
         }
     }
 
-    /// Extract the archive file with a mapper function.
+    /// Extract files matching glob patterns into the paired destination directories.
+    ///
+    /// The relative structure of matched files is preserved, see [`relative_path`],
+    /// e.g. `(**/resource/**, dir)` extracts the whole `resource` subtree into `dir`.
+    /// If a file matches multiple patterns, the first one wins,
+    /// and files matching no pattern are skipped.
+    ///
+    /// Return the list of `(source, destination)` pairs. If `dry_run` is true,
+    /// nothing is written and only the list is returned.
+    #[cfg(feature = "core_installer")]
+    pub fn extract_matching(
+        &self,
+        patterns: &[(Pattern, PathBuf)],
+        dry_run: bool,
+    ) -> Result<Vec<(PathBuf, PathBuf)>> {
+        let mapper = |path: &Path| {
+            patterns.iter().find_map(|(pattern, dest)| {
+                relative_path(pattern, path).map(|relative| dest.join(relative))
+            })
+        };
+        if !dry_run {
+            println!("Extracting archive file...");
+        }
+        self.walk(mapper, dry_run)
+    }
+
+    /// Extract the archive into a staging directory, then move the files into place.
     ///
     /// The mapper function is used to map the file path in the archive to the output path.
     /// If the mapper function returns `None`, the file will be skipped.
-    /// This is useful when you want to extract only some files from the archive.
-    /// If the output path does not exist, it will be created.
-    /// If the output path exists, the file will be skipped if the file size matches.
-    /// Otherwise, the file will be overwritten.
     /// The file permissions will be preserved.
-    pub fn extract(&self, mapper: impl Fn(&Path) -> Option<PathBuf>) -> Result<()> {
+    ///
+    /// All mapped files are written into `staging_dir` first, and moved to their destinations
+    /// only after every file is written, so a failure during extraction leaves the destinations
+    /// untouched. The staging directory is removed afterwards, whether it succeeded or not.
+    pub fn extract_staged(
+        &self,
+        mapper: impl Fn(&Path) -> Option<PathBuf>,
+        staging_dir: &Path,
+    ) -> Result<Vec<(PathBuf, PathBuf)>> {
+        self.stage(mapper, staging_dir)?.commit()
+    }
+
+    /// Extract the archive into a staging directory, see [`Archive::extract_staged`].
+    ///
+    /// The files are moved into place by [`Staged::commit`], so callers can prepare the
+    /// destinations, e.g. clean old files, after the extraction is known to succeed.
+    pub fn stage(
+        &self,
+        mapper: impl Fn(&Path) -> Option<PathBuf>,
+        staging_dir: &Path,
+    ) -> Result<Staged> {
         println!("Extracting archive file...");
+        let planned = self.walk(&mapper, true)?;
+
+        let mut staged = Staged {
+            staging_dir: staging_dir.ensure_clean()?.to_path_buf(),
+            files: Vec::new(),
+        };
+        // Name staged files by their indices, so paths in the archive are never trusted
+        let index = std::cell::Cell::new(0);
+        let staging_mapper = |path: &Path| {
+            mapper(path).map(|_| {
+                index.set(index.get() + 1);
+                staging_dir.join(index.get().to_string())
+            })
+        };
+        let extracted = match self.archive_type {
+            ArchiveType::Zip => extract_zip(&self.file, staging_mapper, false),
+            ArchiveType::TarGz => extract_tar_gz(&self.file, staging_mapper, false),
+        }?;
+        if extracted.len() != planned.len() {
+            bail!("Archive changed during extraction");
+        }
+
+        staged.files = extracted
+            .into_iter()
+            .zip(planned)
+            .map(|((_, staged), (src, dest))| (src, staged, dest))
+            .collect();
+        Ok(staged)
+    }
+
+    /// Map all files in the archive and extract them unless `dry_run`.
+    ///
+    /// The archive is validated before anything is written: all entries are read to the end,
+    /// and all destinations are checked to be writable. So a truncated archive or a conflict
+    /// in destinations leaves the destinations untouched. Each file is written to a temporary
+    /// file and then renamed to the destination, so no file is left half written.
+    fn walk(
+        &self,
+        mapper: impl Fn(&Path) -> Option<PathBuf>,
+        dry_run: bool,
+    ) -> Result<Vec<(PathBuf, PathBuf)>> {
+        let entries = match self.archive_type {
+            ArchiveType::Zip => extract_zip(&self.file, &mapper, true),
+            ArchiveType::TarGz => extract_tar_gz(&self.file, &mapper, true),
+        }
+        .context("Failed to read archive, it may be corrupted or truncated")?;
+        check_destinations(&entries)?;
+
+        if dry_run {
+            return Ok(entries);
+        }
+
         match self.archive_type {
-            ArchiveType::Zip => extract_zip(&self.file, mapper),
-            ArchiveType::TarGz => extract_tar_gz(&self.file, mapper),
+            ArchiveType::Zip => extract_zip(&self.file, mapper, false),
+            ArchiveType::TarGz => extract_tar_gz(&self.file, mapper, false),
+        }
+    }
+}
+
+/// Options to match paths in archives, `*` and `?` never match the separator `/`
+#[cfg(feature = "core_installer")]
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+/// Get the path of a file relative to the destination, `None` if it does not match the pattern.
+///
+/// The relative path starts at the components matched by the last `**`,
+/// so `**/resource/**` maps `MAA/resource/tasks/a.json` to `tasks/a.json`.
+/// If there is no `**`, the relative path is the file name.
+#[cfg(feature = "core_installer")]
+pub fn relative_path(pattern: &Pattern, path: &Path) -> Option<PathBuf> {
+    if !pattern.matches_path_with(path, MATCH_OPTIONS) {
+        return None;
+    }
+
+    let pattern = pattern.as_str();
+    let relative = match pattern.rfind("**") {
+        None => Path::new(path.file_name()?),
+        Some(index) => match pattern[..index].trim_end_matches('/') {
+            "" => path,
+            parent => {
+                let parent = Pattern::new(parent).ok()?;
+                let mut ancestors: Vec<_> = path.ancestors().skip(1).collect();
+                ancestors.reverse();
+                let base = ancestors
+                    .into_iter()
+                    .find(|dir| parent.matches_path_with(dir, MATCH_OPTIONS))?;
+                path.strip_prefix(base).ok()?
+            }
+        },
+    };
+    // A `**` at the end may match nothing, which is not a file in the subtree
+    (!relative.as_os_str().is_empty()).then(|| relative.to_path_buf())
+}
+
+/// Check that all destinations can be written before extracting anything.
+///
+/// A destination must not be a directory, and its nearest existing ancestor must be
+/// a writable directory, otherwise the extraction would fail halfway.
+fn check_destinations(entries: &[(PathBuf, PathBuf)]) -> Result<()> {
+    for (_, dest) in entries {
+        if dest.symlink_metadata().is_ok_and(|m| m.is_dir()) {
+            bail!("Failed to extract to {}, it is a directory", dest.display());
+        }
+
+        let mut ancestor = dest.parent();
+        while let Some(dir) = ancestor {
+            match dir.metadata() {
+                Ok(metadata) if !metadata.is_dir() => bail!(
+                    "Failed to extract to {}, {} is not a directory",
+                    dest.display(),
+                    dir.display()
+                ),
+                Ok(metadata) => {
+                    // Read-only attribute of directories is ignored on Windows
+                    if cfg!(unix) && metadata.permissions().readonly() {
+                        bail!(
+                            "Failed to extract to {}, {} is not writable",
+                            dest.display(),
+                            dir.display()
+                        );
+                    }
+                    break;
+                }
+                Err(_) => ancestor = dir.parent(),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Write a file by `write` to a temporary path and then rename it to the destination.
+fn write_atomically(dest: &Path, write: impl FnOnce(&Path) -> Result<()>) -> Result<()> {
+    #[cfg(test)]
+    tests::inject_failure()?;
+
+    if let Some(p) = dest.parent() {
+        p.ensure()?;
+    }
+
+    let mut name = std::ffi::OsString::from(".");
+    name.push(dest.file_name().unwrap_or_default());
+    name.push(".tmp");
+    let tmp = dest.with_file_name(name);
+    if tmp.symlink_metadata().is_ok() {
+        std::fs::remove_file(&tmp)
+            .with_context(|| format!("Failed to remove temporary file: {}", tmp.display()))?;
+    }
+
+    if let Err(err) = write(&tmp) {
+        let _ = std::fs::remove_file(&tmp);
+        return Err(err);
+    }
+    std::fs::rename(&tmp, dest)
+        .with_context(|| format!("Failed to extract file: {}", dest.display()))
+}
+
+/// Files extracted into a staging directory, waiting to be moved into place.
+///
+/// The staging directory is removed when dropped, so files are discarded if not committed.
+pub struct Staged {
+    staging_dir: PathBuf,
+    /// `(source, staged, destination)` of each file
+    files: Vec<(PathBuf, PathBuf, PathBuf)>,
+}
+
+impl Staged {
+    /// Move all staged files to their destinations.
+    ///
+    /// Return the list of `(source, destination)` pairs.
+    pub fn commit(mut self) -> Result<Vec<(PathBuf, PathBuf)>> {
+        let files = std::mem::take(&mut self.files);
+        let mut moved = Vec::with_capacity(files.len());
+        for (src, staged, dest) in files {
+            move_file(&staged, &dest)?;
+            moved.push((src, dest));
+        }
+        Ok(moved)
+    }
+}
+
+impl Drop for Staged {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.staging_dir);
+    }
+}
+
+/// Move a file to the destination, replacing the existing one.
+///
+/// If the file can not be renamed, e.g. across filesystems, it is copied to a temporary file
+/// next to the destination and then renamed, so the destination is replaced atomically.
+pub(super) fn move_file(src: &Path, dest: &Path) -> Result<()> {
+    if let Some(p) = dest.parent() {
+        p.ensure()?;
+    }
+    if std::fs::rename(src, dest).is_ok() {
+        return Ok(());
+    }
+
+    write_atomically(dest, |tmp| {
+        #[cfg(unix)]
+        if src.symlink_metadata()?.is_symlink() {
+            std::os::unix::fs::symlink(std::fs::read_link(src)?, tmp)?;
+            return Ok(());
+        }
+        std::fs::copy(src, tmp)
+            .with_context(|| format!("Failed to copy file to {}", dest.display()))?;
+        Ok(())
+    })
+}
+
+/// Check whether an entry is safe to extract, return the reason if it is not.
+///
+/// Archives are downloaded from configurable mirrors, so their contents are not trusted.
+/// An entry is rejected if its path is absolute or goes above the archive root by `..`,
+/// or if it is a symlink whose target is absolute or contains `..`. Since the target of a
+/// symlink can only point to its own directory or below, it never escapes the destination,
+/// wherever the entry is mapped to.
+fn check_entry(path: &Path, link_target: Option<&Path>) -> Result<(), &'static str> {
+    let mut depth = 0usize;
+    for component in path.components() {
+        match component {
+            Component::Normal(_) => depth += 1,
+            Component::CurDir => {}
+            Component::ParentDir if depth > 0 => depth -= 1,
+            _ => return Err("path escapes the archive"),
+        }
+    }
+
+    let escapes = |c: Component| !matches!(c, Component::Normal(_) | Component::CurDir);
+    if link_target.is_some_and(|target| target.components().any(escapes)) {
+        return Err("symlink points outside its directory");
+    }
+
+    Ok(())
+}
+
+/// Map an entry to its destination, `None` if it is rejected or skipped by the mapper.
+///
+/// Rejected entries are logged only if `validate`, so they are logged once per extraction.
+fn map_entry(
+    path: &Path,
+    link_target: Option<&Path>,
+    mapper: impl Fn(&Path) -> Option<PathBuf>,
+    validate: bool,
+) -> Option<PathBuf> {
+    match check_entry(path, link_target) {
+        Ok(()) => mapper(path),
+        Err(reason) => {
+            if validate {
+                warn!("Skipped {} in archive: {reason}", path.display());
+            }
+            None
         }
     }
 }
 
-fn extract_zip(file: &Path, mapper: impl Fn(&Path) -> Option<PathBuf>) -> Result<()> {
+/// Mode of extracted files, setuid, setgid and sticky bits are stripped.
+#[cfg(unix)]
+fn file_mode(mode: u32) -> u32 {
+    mode & 0o777
+}
+
+/// Extract the zip file, or only read all entries to the end if `validate`.
+fn extract_zip(
+    file: &Path,
+    mapper: impl Fn(&Path) -> Option<PathBuf>,
+    validate: bool,
+) -> Result<Vec<(PathBuf, PathBuf)>> {
+    const S_IFMT: u32 = 0o170000;
+    const S_IFLNK: u32 = 0o120000;
+
     let mut archive = zip::ZipArchive::new(File::open(file)?)?;
+    let mut extracted = Vec::new();
 
     for i in 0..archive.len() {
-        let mut file = archive.by_index(i).unwrap();
-
-        let outpath = match file.enclosed_name() {
-            Some(path) => match mapper(path) {
-                Some(path) => path,
-                None => continue,
-            },
-            None => continue,
-        };
+        let mut file = archive.by_index(i)?;
 
         if file.is_dir() {
             continue;
+        }
+
+        // The target of a symlink is stored as the content of the entry
+        let link_target = if file.unix_mode().is_some_and(|m| m & S_IFMT == S_IFLNK) {
+            let mut contents = Vec::new();
+            file.read_to_end(&mut contents)?;
+            Some(PathBuf::from(
+                String::from_utf8_lossy(&contents).into_owned(),
+            ))
         } else {
-            if let Some(p) = outpath.parent() {
-                p.ensure()?;
+            if validate {
+                // Read to the end to verify the data and its checksum
+                copy(&mut file, &mut std::io::sink())?;
             }
+            None
+        };
 
-            #[cfg(unix)]
-            {
-                use std::{
-                    fs::remove_file,
-                    io::Read,
-                    os::unix::{ffi::OsStringExt, fs::symlink},
-                };
+        // Names with a root or `..` are rejected by `enclosed_name`
+        let name = file.name().to_owned();
+        let src = match file.enclosed_name() {
+            Some(path) => path.to_path_buf(),
+            None => {
+                if validate {
+                    warn!("Skipped {name} in archive: path escapes the archive");
+                }
+                continue;
+            }
+        };
+        let Some(outpath) = map_entry(&src, link_target.as_deref(), &mapper, validate) else {
+            continue;
+        };
 
-                const S_IFLNK: u32 = 0o120000;
+        extracted.push((src, outpath.clone()));
+        if validate {
+            continue;
+        }
 
-                if let Some(mode) = file.unix_mode() {
-                    if mode & S_IFLNK == S_IFLNK {
-                        let mut contents = Vec::new();
-                        file.read_to_end(&mut contents)?;
-                        let link_target = std::ffi::OsString::from_vec(contents);
-                        if outpath.exists() {
-                            remove_file(&outpath).with_context(|| {
-                                format!("Failed to remove existing file: {}", outpath.display())
-                            })?;
-                        }
-                        symlink(link_target, &outpath).with_context(|| {
-                            format!("Failed to extract file: {}", outpath.display())
-                        })?;
-                        continue;
-                    }
-                }
+        write_atomically(&outpath, |tmp| {
+            if let Some(link_target) = &link_target {
+                #[cfg(unix)]
+                std::os::unix::fs::symlink(link_target, tmp)
+                    .with_context(|| format!("Failed to extract file: {}", outpath.display()))?;
+                #[cfg(not(unix))]
+                std::fs::write(tmp, link_target.to_string_lossy().as_bytes())
+                    .with_context(|| format!("Failed to extract file: {}", outpath.display()))?;
+                return Ok(());
             }
 
-            let mut outfile = File::create(&outpath)
-                .with_context(|| format!("Failed to create file: {}", outpath.display()))?;
+            let mut outfile = File::create(tmp)
+                .with_context(|| format!("Failed to create file: {}", tmp.display()))?;
             copy(&mut file, &mut outfile)
                 .with_context(|| format!("Failed to extract file: {}", outpath.display()))?;
-        }
 
-        #[cfg(unix)]
-        {
-            use std::fs::{set_permissions, Permissions};
-            use std::os::unix::fs::PermissionsExt;
+            #[cfg(unix)]
+            {
+                use std::fs::{set_permissions, Permissions};
+                use std::os::unix::fs::PermissionsExt;
 
-            if let Some(mode) = file.unix_mode() {
-                set_permissions(&outpath, Permissions::from_mode(mode))
-                    .with_context(|| format!("Failed to set permissions: {}", outpath.display()))?;
+                if let Some(mode) = file.unix_mode() {
+                    set_permissions(tmp, Permissions::from_mode(file_mode(mode))).with_context(
+                        || format!("Failed to set permissions: {}", outpath.display()),
+                    )?;
+                }
             }
-        }
+
+            Ok(())
+        })?;
     }
 
-    Ok(())
+    Ok(extracted)
 }
 
-fn extract_tar_gz(file: &Path, mapper: impl Fn(&Path) -> Option<PathBuf>) -> Result<()> {
+/// Extract the tar.gz file, or only read all entries to the end if `validate`.
+///
+/// Only regular files and symlinks are extracted, hard links and special files are skipped.
+/// The setuid, setgid and sticky bits are stripped by [`tar::Entry::unpack`].
+fn extract_tar_gz(
+    file: &Path,
+    mapper: impl Fn(&Path) -> Option<PathBuf>,
+    validate: bool,
+) -> Result<Vec<(PathBuf, PathBuf)>> {
     let gz_decoder = flate2::read::GzDecoder::new(File::open(file)?);
     let mut archive = tar::Archive::new(gz_decoder);
+    let mut extracted = Vec::new();
 
     for entry in archive.entries()? {
         let mut file = entry?;
 
-        let outpath = match &file.path() {
-            Ok(path) => match mapper(path) {
-                Some(path) => path,
-                None => continue,
-            },
-            Err(e) => return Err(anyhow!("Error while reading tar entry: {}", e)),
+        let entry_type = file.header().entry_type();
+        if entry_type.is_dir() {
+            continue;
+        }
+
+        if validate {
+            copy(&mut file, &mut std::io::sink())?;
+        }
+
+        let src = file
+            .path()
+            .map_err(|e| anyhow!("Error while reading tar entry: {}", e))?
+            .into_owned();
+        let link_target = match file.link_name()? {
+            Some(target) if entry_type.is_symlink() => Some(target.into_owned()),
+            _ => None,
         };
 
-        if let Some(p) = outpath.parent() {
-            p.ensure()?;
+        if !entry_type.is_file() && !entry_type.is_symlink() {
+            if validate {
+                warn!(
+                    "Skipped {} in archive: unsupported entry type {entry_type:?}",
+                    src.display()
+                );
+            }
+            continue;
         }
+        let Some(outpath) = map_entry(&src, link_target.as_deref(), &mapper, validate) else {
+            continue;
+        };
 
-        file.unpack(&outpath)?;
+        extracted.push((src, outpath.clone()));
+        if validate {
+            continue;
+        }
+
+        write_atomically(&outpath, |tmp| {
+            file.unpack(tmp)
+                .with_context(|| format!("Failed to extract file: {}", outpath.display()))?;
+            Ok(())
+        })?;
     }
 
-    println!("Done!");
+    if !validate {
+        println!("Done!");
+    }
 
-    Ok(())
+    Ok(extracted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{cell::Cell, env::temp_dir, fs, io::Write};
+
+    thread_local! {
+        /// Number of files to write before an injected failure
+        static FAIL_AFTER: Cell<Option<usize>> = const { Cell::new(None) };
+    }
+
+    pub(super) fn inject_failure() -> Result<()> {
+        FAIL_AFTER.with(|fail_after| match fail_after.get() {
+            Some(0) => bail!("Injected failure"),
+            Some(n) => {
+                fail_after.set(Some(n - 1));
+                Ok(())
+            }
+            None => Ok(()),
+        })
+    }
+
+    const FILES: [(&str, &str); 4] = [
+        ("MAA-v5/resource/config.json", "config"),
+        ("MAA-v5/resource/tasks/a.json", "task a"),
+        ("MAA-v5/libMaaCore.so", "library"),
+        ("MAA-v5/README.md", "readme"),
+    ];
+
+    #[cfg(feature = "core_installer")]
+    fn create_zip(path: &Path) {
+        let mut zip = zip::ZipWriter::new(File::create(path).unwrap());
+        let options = zip::write::FileOptions::default();
+        zip.add_directory("MAA-v5/resource/tasks/", options)
+            .unwrap();
+        for (name, content) in FILES {
+            zip.start_file(name, options).unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    #[cfg(feature = "core_installer")]
+    fn create_tar_gz(path: &Path) {
+        let encoder =
+            flate2::write::GzEncoder::new(File::create(path).unwrap(), Default::default());
+        let mut tar = tar::Builder::new(encoder);
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Directory);
+        header.set_mode(0o755);
+        header.set_size(0);
+        tar.append_data(&mut header, "MAA-v5/resource/tasks/", std::io::empty())
+            .unwrap();
+        for (name, content) in FILES {
+            let mut header = tar::Header::new_gnu();
+            header.set_mode(0o644);
+            header.set_size(content.len() as u64);
+            tar.append_data(&mut header, name, content.as_bytes())
+                .unwrap();
+        }
+        tar.into_inner().unwrap().finish().unwrap();
+    }
+
+    #[cfg(feature = "core_installer")]
+    fn patterns(dir: &Path) -> Vec<(Pattern, PathBuf)> {
+        vec![
+            ("**/resource/**".parse().unwrap(), dir.join("resource")),
+            ("*/*.json".parse().unwrap(), dir.join("unreachable")),
+            ("**/*.json".parse().unwrap(), dir.join("json")),
+            ("*/lib*.so".parse().unwrap(), dir.join("lib")),
+        ]
+    }
+
+    #[cfg(feature = "core_installer")]
+    fn check_extract_matching(name: &str, create: fn(&Path)) {
+        let dir = temp_dir().join(format!("maa-test-extract-matching-{name}"));
+        if dir.exists() {
+            fs::remove_dir_all(&dir).unwrap();
+        }
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join(name);
+        create(&file);
+
+        let archive = Archive::new(file.as_path().into()).unwrap();
+        let out = dir.join("out");
+        let expected = vec![
+            (
+                PathBuf::from("MAA-v5/resource/config.json"),
+                out.join("resource/config.json"),
+            ),
+            (
+                PathBuf::from("MAA-v5/resource/tasks/a.json"),
+                out.join("resource/tasks/a.json"),
+            ),
+            (
+                PathBuf::from("MAA-v5/libMaaCore.so"),
+                out.join("lib/libMaaCore.so"),
+            ),
+        ];
+
+        // dry run returns the plan without writing anything
+        let planned = archive.extract_matching(&patterns(&out), true).unwrap();
+        assert_eq!(planned, expected);
+        assert!(!out.exists());
+
+        let extracted = archive.extract_matching(&patterns(&out), false).unwrap();
+        assert_eq!(extracted, expected);
+        assert_eq!(
+            fs::read_to_string(out.join("resource/tasks/a.json")).unwrap(),
+            "task a"
+        );
+        assert_eq!(
+            fs::read_to_string(out.join("lib/libMaaCore.so")).unwrap(),
+            "library"
+        );
+        // first match wins and non-matching entries are skipped
+        assert!(!out.join("json").exists());
+        assert!(!out.join("unreachable").exists());
+        assert!(!out.join("README.md").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "core_installer")]
+    fn check_truncated(name: &str, create: fn(&Path)) {
+        let dir = temp_dir().join(format!("maa-test-extract-truncated-{name}"));
+        if dir.exists() {
+            fs::remove_dir_all(&dir).unwrap();
+        }
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join(name);
+        create(&file);
+        let content = fs::read(&file).unwrap();
+        fs::write(&file, &content[..content.len() * 2 / 3]).unwrap();
+
+        let out = dir.join("out");
+        fs::create_dir_all(out.join("lib")).unwrap();
+        fs::write(out.join("lib/libMaaCore.so"), "old library").unwrap();
+
+        let archive = Archive::new(file.as_path().into()).unwrap();
+        assert!(archive.extract_matching(&patterns(&out), false).is_err());
+        // nothing is touched
+        assert_eq!(
+            fs::read_to_string(out.join("lib/libMaaCore.so")).unwrap(),
+            "old library"
+        );
+        assert!(!out.join("resource").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "core_installer")]
+    fn check_conflict(name: &str, create: fn(&Path)) {
+        let dir = temp_dir().join(format!("maa-test-extract-conflict-{name}"));
+        if dir.exists() {
+            fs::remove_dir_all(&dir).unwrap();
+        }
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join(name);
+        create(&file);
+        let archive = Archive::new(file.as_path().into()).unwrap();
+        let out = dir.join("out");
+
+        // a directory at the destination of a file
+        fs::create_dir_all(out.join("lib/libMaaCore.so")).unwrap();
+        assert!(archive.extract_matching(&patterns(&out), false).is_err());
+        assert!(!out.join("resource").exists());
+        fs::remove_dir_all(&out).unwrap();
+
+        // a file at the parent of a destination
+        fs::create_dir_all(&out).unwrap();
+        fs::write(out.join("resource"), "").unwrap();
+        assert!(archive.extract_matching(&patterns(&out), false).is_err());
+        assert!(!out.join("lib").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "core_installer")]
+    #[test]
+    fn validate_zip() {
+        check_truncated("fixture.zip", create_zip);
+        check_conflict("fixture.zip", create_zip);
+    }
+
+    #[cfg(feature = "core_installer")]
+    #[test]
+    fn validate_tar_gz() {
+        check_truncated("fixture.tar.gz", create_tar_gz);
+        check_conflict("fixture.tar.gz", create_tar_gz);
+    }
+
+    #[cfg(feature = "core_installer")]
+    fn check_staged(name: &str, create: fn(&Path)) {
+        let dir = temp_dir().join(format!("maa-test-extract-staged-{name}"));
+        if dir.exists() {
+            fs::remove_dir_all(&dir).unwrap();
+        }
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join(name);
+        create(&file);
+        let archive = Archive::new(file.as_path().into()).unwrap();
+
+        let out = dir.join("out");
+        let staging = dir.join("staging");
+        fs::create_dir_all(out.join("lib")).unwrap();
+        fs::write(out.join("lib/libMaaCore.so"), "old library").unwrap();
+        let patterns = patterns(&out);
+        let mapper = |path: &Path| {
+            patterns
+                .iter()
+                .find_map(|(pattern, dest)| relative_path(pattern, path).map(|r| dest.join(r)))
+        };
+
+        // fail after the first file is written into the staging directory
+        FAIL_AFTER.with(|f| f.set(Some(1)));
+        assert!(archive.extract_staged(mapper, &staging).is_err());
+        FAIL_AFTER.with(|f| f.set(None));
+        assert_eq!(
+            fs::read_to_string(out.join("lib/libMaaCore.so")).unwrap(),
+            "old library"
+        );
+        assert!(!out.join("resource").exists());
+        assert!(!staging.exists());
+
+        let extracted = archive.extract_staged(mapper, &staging).unwrap();
+        assert_eq!(extracted.len(), 3);
+        assert_eq!(
+            fs::read_to_string(out.join("lib/libMaaCore.so")).unwrap(),
+            "library"
+        );
+        assert_eq!(
+            fs::read_to_string(out.join("resource/tasks/a.json")).unwrap(),
+            "task a"
+        );
+        assert!(!staging.exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "core_installer")]
+    #[test]
+    fn staged_zip() {
+        check_staged("fixture.zip", create_zip);
+    }
+
+    #[cfg(feature = "core_installer")]
+    #[test]
+    fn staged_tar_gz() {
+        check_staged("fixture.tar.gz", create_tar_gz);
+    }
+
+    #[test]
+    fn move_across() {
+        let dir = temp_dir().join("maa-test-extract-move");
+        if dir.exists() {
+            fs::remove_dir_all(&dir).unwrap();
+        }
+        fs::create_dir_all(&dir).unwrap();
+
+        // a missing source can not be renamed, so the copy fallback is used and fails
+        assert!(move_file(&dir.join("missing"), &dir.join("dest")).is_err());
+        assert!(!dir.join("dest").exists());
+        assert!(!dir.join(".dest.tmp").exists());
+
+        fs::write(dir.join("src"), "new").unwrap();
+        fs::write(dir.join("dest"), "old").unwrap();
+        move_file(&dir.join("src"), &dir.join("sub/dest")).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("sub/dest")).unwrap(), "new");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Entries escaping the archive, `(name, content)` of files and `(name, target)` of symlinks
+    const HOSTILE_FILES: [(&str, &str); 2] = [
+        ("../maa-test-escape.txt", "escaped"),
+        ("/tmp/maa-test-absolute.txt", "absolute"),
+    ];
+    const HOSTILE_LINKS: [(&str, &str); 3] = [
+        ("MAA-v5/resource/escape", "../../../outside"),
+        ("MAA-v5/resource/absolute", "/etc/passwd"),
+        ("MAA-v5/resource/link.json", "config.json"),
+    ];
+
+    fn create_hostile_zip(path: &Path) {
+        let mut zip = zip::ZipWriter::new(File::create(path).unwrap());
+        let options = zip::write::FileOptions::default();
+        for (name, content) in FILES.into_iter().chain(HOSTILE_FILES) {
+            zip.start_file(name, options).unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        }
+        for (name, target) in HOSTILE_LINKS {
+            zip.add_symlink(name, target, options).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    fn create_hostile_tar_gz(path: &Path) {
+        // Set names without the sanitization of `Header::set_path`
+        fn raw_header(name: &str, entry_type: tar::EntryType, size: usize) -> tar::Header {
+            let mut header = tar::Header::new_old();
+            header.as_old_mut().name[..name.len()].copy_from_slice(name.as_bytes());
+            header.set_entry_type(entry_type);
+            header.set_mode(0o644);
+            header.set_size(size as u64);
+            header
+        }
+
+        let encoder =
+            flate2::write::GzEncoder::new(File::create(path).unwrap(), Default::default());
+        let mut tar = tar::Builder::new(encoder);
+        for (name, content) in FILES.into_iter().chain(HOSTILE_FILES) {
+            let mut header = raw_header(name, tar::EntryType::Regular, content.len());
+            if name.ends_with(".so") {
+                header.set_mode(0o4755);
+            }
+            header.set_cksum();
+            tar.append(&header, content.as_bytes()).unwrap();
+        }
+        for (name, target) in HOSTILE_LINKS {
+            let mut header = raw_header(name, tar::EntryType::Symlink, 0);
+            header.set_link_name_literal(target).unwrap();
+            header.set_cksum();
+            tar.append(&header, std::io::empty()).unwrap();
+        }
+        let mut header = raw_header("MAA-v5/resource/hard", tar::EntryType::Link, 0);
+        header.set_link_name_literal("/etc/passwd").unwrap();
+        header.set_cksum();
+        tar.append(&header, std::io::empty()).unwrap();
+        tar.into_inner().unwrap().finish().unwrap();
+    }
+
+    fn check_hostile(name: &str, create: fn(&Path)) {
+        let dir = temp_dir().join(format!("maa-test-extract-hostile-{name}"));
+        if dir.exists() {
+            fs::remove_dir_all(&dir).unwrap();
+        }
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join(name);
+        create(&file);
+        let archive = Archive::new(file.as_path().into()).unwrap();
+
+        // Map every entry into the output directory, so only the checks stop the escapes
+        let out = dir.join("out");
+        let extracted = archive
+            .extract_staged(|path| Some(out.join(path)), &dir.join("staging"))
+            .unwrap();
+        let sources: Vec<_> = extracted.iter().map(|(src, _)| src.as_path()).collect();
+        let mut expected: Vec<_> = FILES.iter().map(|(name, _)| Path::new(*name)).collect();
+        expected.push(Path::new("MAA-v5/resource/link.json"));
+        assert_eq!(sources, expected);
+
+        assert!(!dir.join("maa-test-escape.txt").exists());
+        assert!(!temp_dir().join("maa-test-escape.txt").exists());
+        assert!(!Path::new("/tmp/maa-test-absolute.txt").exists());
+        assert!(!out.join("MAA-v5/resource/escape").exists());
+        assert!(!out.join("MAA-v5/resource/absolute").exists());
+        assert!(!out.join("MAA-v5/resource/hard").exists());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            let link = out.join("MAA-v5/resource/link.json");
+            assert_eq!(fs::read_link(&link).unwrap(), Path::new("config.json"));
+            assert_eq!(fs::read_to_string(&link).unwrap(), "config");
+
+            let mode = fs::metadata(out.join("MAA-v5/libMaaCore.so"))
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o7000, 0);
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn hostile_zip() {
+        check_hostile("fixture.zip", create_hostile_zip);
+    }
+
+    #[test]
+    fn hostile_tar_gz() {
+        check_hostile("fixture.tar.gz", create_hostile_tar_gz);
+    }
+
+    #[test]
+    fn entry_check() {
+        let check =
+            |path: &str, target: Option<&str>| check_entry(Path::new(path), target.map(Path::new));
+
+        assert!(check("MAA/a.json", None).is_ok());
+        assert!(check("./MAA/../a.json", None).is_ok());
+        assert!(check("../a.json", None).is_err());
+        assert!(check("MAA/../../a.json", None).is_err());
+        assert!(check("/a.json", None).is_err());
+
+        assert!(check("MAA/link", Some("a.json")).is_ok());
+        assert!(check("MAA/link", Some("./sub/a.json")).is_ok());
+        assert!(check("MAA/link", Some("../a.json")).is_err());
+        assert!(check("MAA/link", Some("/etc/passwd")).is_err());
+
+        #[cfg(unix)]
+        assert_eq!(file_mode(0o104755), 0o755);
+    }
+
+    #[cfg(feature = "core_installer")]
+    #[test]
+    fn relative_paths() {
+        let relative = |pattern: &str, path: &str| {
+            relative_path(&Pattern::new(pattern).unwrap(), Path::new(path))
+        };
+
+        assert_eq!(
+            relative("**/resource/**", "MAA-v5/resource/tasks/a.json"),
+            Some("tasks/a.json".into())
+        );
+        assert_eq!(
+            relative("**/resource/**", "resource/a.json"),
+            Some("a.json".into())
+        );
+        assert_eq!(relative("**/resource/**", "MAA-v5/libMaaCore.so"), None);
+        assert_eq!(relative("**/resource/**", "MAA-v5/resource"), None);
+
+        assert_eq!(
+            relative("*/lib*.so*", "MAA-v5/libMaaCore.so.1"),
+            Some("libMaaCore.so.1".into())
+        );
+        assert_eq!(relative("*/lib*.so*", "MAA-v5/sub/libMaaCore.so"), None);
+        assert_eq!(relative("maa?", "maa1"), Some("maa1".into()));
+        assert_eq!(relative("maa?", "maa"), None);
+        assert_eq!(relative("**", "a/b"), Some("a/b".into()));
+        assert_eq!(
+            relative("a/**/*.json", "a/b/c.json"),
+            Some("b/c.json".into())
+        );
+        assert_eq!(relative("a/**/*.json", "a/c.json"), Some("c.json".into()));
+
+        // many wildcards are matched without backtracking exponentially
+        let path = format!("{}b", "a".repeat(64));
+        assert_eq!(relative(&"*a".repeat(32), &path), None);
+    }
+
+    #[cfg(feature = "core_installer")]
+    #[test]
+    fn extract_matching_zip() {
+        check_extract_matching("fixture.zip", create_zip);
+    }
+
+    #[cfg(feature = "core_installer")]
+    #[test]
+    fn extract_matching_tar_gz() {
+        check_extract_matching("fixture.tar.gz", create_tar_gz);
+    }
 }
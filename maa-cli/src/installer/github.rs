@@ -0,0 +1,238 @@
+//! Fetch release information from the GitHub releases API.
+//!
+//! This is used as a fallback when the version json is not reachable,
+//! e.g. the raw content of GitHub is blocked in some regions.
+
+use super::{http, version_json::fetch_json_cached};
+
+#[cfg(feature = "cli_installer")]
+use crate::config::cli::Channel;
+use crate::config::cli::IpFamily;
+use crate::dirs;
+
+use anyhow::Result;
+#[cfg(feature = "cli_installer")]
+use anyhow::{bail, Context};
+use reqwest::blocking::Client;
+#[cfg(feature = "cli_installer")]
+use semver::Version;
+use serde::Deserialize;
+
+/// GitHub releases API of maa-cli
+#[cfg(feature = "cli_installer")]
+pub const MAA_CLI_RELEASES_API: &str =
+    "https://api.github.com/repos/MaaAssistantArknights/maa-cli/releases";
+
+/// GitHub releases API of MaaCore, used to install a pinned version
+#[cfg(feature = "core_installer")]
+pub const MAA_CORE_RELEASES_API: &str =
+    "https://api.github.com/repos/MaaAssistantArknights/MaaAssistantArknights/releases";
+
+/// Tag of the nightly release, which is used by the alpha channel
+#[cfg(feature = "cli_installer")]
+const NIGHTLY_TAG: &str = "nightly";
+
+#[derive(Deserialize)]
+pub struct Release {
+    #[cfg(feature = "cli_installer")]
+    tag_name: String,
+    #[cfg(feature = "cli_installer")]
+    name: Option<String>,
+    #[cfg(feature = "cli_installer")]
+    #[serde(default)]
+    draft: bool,
+    assets: Vec<ReleaseAsset>,
+}
+
+impl Release {
+    #[cfg(feature = "cli_installer")]
+    pub fn tag(&self) -> &str {
+        &self.tag_name
+    }
+
+    /// Version of the release.
+    ///
+    /// The tag of nightly release is always `nightly`,
+    /// so the version is parsed from the name of release (e.g. `v0.4.8-alpha.1+sha.abcdef`),
+    /// and falls back to the tag if the name is not a valid version.
+    #[cfg(feature = "cli_installer")]
+    pub fn version(&self) -> Result<Version> {
+        let parse = |s: &str| Version::parse(s.strip_prefix('v').unwrap_or(s)).ok();
+        self.name
+            .as_deref()
+            .and_then(parse)
+            .or_else(|| parse(&self.tag_name))
+            .with_context(|| format!("Failed to parse version of release {}", self.tag_name))
+    }
+
+    pub fn assets(&self) -> &[ReleaseAsset] {
+        &self.assets
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ReleaseAsset {
+    name: String,
+    size: u64,
+    browser_download_url: String,
+    /// Digest of the asset computed by GitHub, e.g. `sha256:<hex>`
+    #[cfg(feature = "core_installer")]
+    #[serde(default)]
+    digest: Option<String>,
+}
+
+impl ReleaseAsset {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn download_url(&self) -> &str {
+        &self.browser_download_url
+    }
+
+    /// SHA-256 checksum from the digest, `None` if not available or of another algorithm.
+    #[cfg(feature = "core_installer")]
+    pub fn sha256sum(&self) -> Option<&str> {
+        self.digest.as_deref()?.strip_prefix("sha256:")
+    }
+}
+
+/// Client to access GitHub API, which requires a user agent.
+pub fn client(ip_family: IpFamily) -> Result<Client> {
+    http::blocking_client(ip_family)
+}
+
+/// Fetch the latest release of given channel.
+///
+/// - stable: the latest release, which excludes pre-releases;
+/// - beta: the latest release except the nightly one, which may be a pre-release;
+/// - alpha: the nightly release.
+#[cfg(feature = "cli_installer")]
+pub fn fetch_release(client: &Client, api: &str, channel: Channel) -> Result<Release> {
+    let api = api.trim_end_matches('/');
+    match channel {
+        Channel::Stable => fetch_json_cached(client, &format!("{api}/latest"), dirs::cache()),
+        Channel::Beta => {
+            let releases: Vec<Release> =
+                fetch_json_cached(client, &format!("{api}?per_page=10"), dirs::cache())?;
+            match releases
+                .into_iter()
+                .find(|r| !r.draft && r.tag_name != NIGHTLY_TAG)
+            {
+                Some(release) => Ok(release),
+                None => bail!("No beta release found"),
+            }
+        }
+        Channel::Alpha => fetch_release_by_tag(client, api, NIGHTLY_TAG),
+    }
+}
+
+/// Fetch the release of given tag, e.g. `v5.0.0`.
+pub fn fetch_release_by_tag(client: &Client, api: &str, tag: &str) -> Result<Release> {
+    let url = format!("{}/tags/{tag}", api.trim_end_matches('/'));
+    fetch_json_cached(client, &url, dirs::cache())
+}
+
+/// Fetch a text file, e.g. a checksum file attached to a release.
+#[cfg(feature = "cli_installer")]
+pub fn fetch_text(client: &Client, url: &str) -> Result<String> {
+    client
+        .get(url)
+        .send()
+        .and_then(|resp| resp.error_for_status())
+        .and_then(|resp| resp.text())
+        .with_context(|| format!("Failed to fetch {url}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "cli_installer")]
+    use crate::installer::test_server::{Response, Server};
+
+    #[cfg(feature = "cli_installer")]
+    fn client() -> Client {
+        Client::builder().no_proxy().build().unwrap()
+    }
+
+    #[cfg(feature = "cli_installer")]
+    fn release(tag: &str, name: &str, draft: bool) -> String {
+        format!(r#"{{"tag_name": "{tag}", "name": "{name}", "draft": {draft}, "assets": []}}"#)
+    }
+
+    #[cfg(feature = "cli_installer")]
+    #[test]
+    fn version() {
+        let parse = |tag: &str, name: &str| {
+            serde_json::from_str::<Release>(&release(tag, name, false))
+                .unwrap()
+                .version()
+                .ok()
+        };
+
+        assert_eq!(parse("v0.4.8", "v0.4.8"), Some(Version::new(0, 4, 8)));
+        assert_eq!(
+            parse("nightly", "v0.4.9-alpha.1+sha.abcdef"),
+            Some(Version::parse("0.4.9-alpha.1+sha.abcdef").unwrap())
+        );
+        assert_eq!(
+            parse("v0.4.8", "Release 0.4.8"),
+            Some(Version::new(0, 4, 8))
+        );
+        assert_eq!(parse("nightly", "Nightly"), None);
+    }
+
+    #[cfg(feature = "cli_installer")]
+    #[test]
+    fn fetch_by_channel() {
+        let server = Server::new([Response::ok(release("v0.4.8", "v0.4.8", false))]);
+        let api = server.url("releases");
+        let latest = fetch_release(&client(), &api, Channel::Stable).unwrap();
+        assert_eq!(latest.tag(), "v0.4.8");
+        assert!(server.requests()[0].starts_with("GET /releases/latest "));
+
+        let server = Server::new([Response::ok(format!(
+            "[{}, {}, {}]",
+            release("nightly", "v0.4.9-alpha.1+sha.abcdef", false),
+            release("v0.4.9-beta.2", "v0.4.9-beta.2", true),
+            release("v0.4.9-beta.1", "v0.4.9-beta.1", false),
+        ))]);
+        let api = server.url("releases");
+        let latest = fetch_release(&client(), &api, Channel::Beta).unwrap();
+        assert_eq!(latest.tag(), "v0.4.9-beta.1");
+        assert!(server.requests()[0].starts_with("GET /releases?per_page=10 "));
+
+        let server = Server::new([Response::ok(release(
+            "nightly",
+            "v0.4.9-alpha.1+sha.abcdef",
+            false,
+        ))]);
+        let api = server.url("releases");
+        let latest = fetch_release(&client(), &api, Channel::Alpha).unwrap();
+        assert_eq!(latest.tag(), "nightly");
+        assert!(server.requests()[0].starts_with("GET /releases/tags/nightly "));
+    }
+
+    #[cfg(feature = "core_installer")]
+    #[test]
+    fn asset_digest() {
+        let asset = |digest: &str| {
+            serde_json::from_str::<ReleaseAsset>(&format!(
+                r#"{{"name": "a.zip", "size": 1, "browser_download_url": "url"{digest}}}"#
+            ))
+            .unwrap()
+        };
+        assert_eq!(
+            asset(r#", "digest": "sha256:abc""#).sha256sum(),
+            Some("abc")
+        );
+        assert_eq!(asset(r#", "digest": "sha512:abc""#).sha256sum(), None);
+        assert_eq!(asset(r#", "digest": null"#).sha256sum(), None);
+        assert_eq!(asset("").sha256sum(), None);
+    }
+}
@@ -0,0 +1,263 @@
+//! HTTP clients shared by the installers.
+//!
+//! All clients of the installers are built here, so the address family preference
+//! is applied to both the version info fetching and the asset downloading.
+
+use super::download;
+
+use crate::config::cli::{cli_config, IpFamily};
+
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use log::debug;
+use reqwest::{redirect::Policy, Url};
+
+/// Maximum number of redirects to follow in a request
+const MAX_REDIRECTS: usize = 10;
+
+/// Whether redirects from https to http are followed, see [`allow_insecure_redirect`]
+static ALLOW_INSECURE_REDIRECT: AtomicBool = AtomicBool::new(false);
+
+/// Allow or refuse redirects from https to http for all clients built afterwards.
+///
+/// Such redirects are refused by default, since the content may be tampered with
+/// in the plain http part of the chain.
+pub fn allow_insecure_redirect(allow: bool) {
+    ALLOW_INSECURE_REDIRECT.store(allow, Ordering::Relaxed);
+}
+
+/// Check whether to follow a redirect to `next`, where `previous` are the urls visited.
+fn check_redirect(previous: &[Url], next: &Url, allow_insecure: bool) -> Result<(), String> {
+    if previous.len() > MAX_REDIRECTS {
+        return Err(format!(
+            "Too many redirects (more than {MAX_REDIRECTS}), last redirected to {next}"
+        ));
+    }
+    if !allow_insecure
+        && next.scheme() == "http"
+        && previous.iter().any(|url| url.scheme() == "https")
+    {
+        return Err(format!(
+            "Refused to redirect from https to http ({next}), \
+             use `--allow-insecure-redirect` if you trust the mirror"
+        ));
+    }
+    Ok(())
+}
+
+/// User agent of all clients, `user_agent` in cli config if set, or [`default_user_agent`].
+pub fn user_agent() -> String {
+    cli_config()
+        .user_agent()
+        .map_or_else(default_user_agent, str::to_owned)
+}
+
+/// Default user agent in the form of `maa-cli/<version> (<target>)`.
+///
+/// Packagers can append a comment to identify their builds by setting
+/// `MAA_USER_AGENT_SUFFIX` at build time, e.g. `maa-cli/0.4.8 (aarch64-apple-darwin; brew)`.
+pub fn default_user_agent() -> String {
+    format_user_agent(
+        env!("MAA_VERSION"),
+        env!("MAA_TARGET"),
+        option_env!("MAA_USER_AGENT_SUFFIX"),
+    )
+}
+
+fn format_user_agent(version: &str, target: &str, suffix: Option<&str>) -> String {
+    match suffix.map(str::trim).filter(|s| !s.is_empty()) {
+        Some(suffix) => format!("maa-cli/{version} ({target}; {suffix})"),
+        None => format!("maa-cli/{version} ({target})"),
+    }
+}
+
+/// Redirect policy of all clients, each redirect is logged at debug level.
+fn redirect_policy() -> Policy {
+    let allow_insecure = ALLOW_INSECURE_REDIRECT.load(Ordering::Relaxed);
+    Policy::custom(move |attempt| {
+        match check_redirect(attempt.previous(), attempt.url(), allow_insecure) {
+            Ok(()) => {
+                debug!("Redirected to {}", attempt.url());
+                attempt.follow()
+            }
+            Err(message) => attempt.error(message),
+        }
+    })
+}
+
+/// Builder of the async client used to download assets.
+pub fn builder(ip_family: IpFamily) -> reqwest::ClientBuilder {
+    reqwest::Client::builder()
+        .user_agent(user_agent())
+        .local_address(ip_family.local_address())
+        .redirect(redirect_policy())
+}
+
+/// Builder of the blocking client used to fetch version info and checksums.
+pub fn blocking_builder(ip_family: IpFamily) -> reqwest::blocking::ClientBuilder {
+    reqwest::blocking::Client::builder()
+        .user_agent(user_agent())
+        .local_address(ip_family.local_address())
+        .redirect(redirect_policy())
+}
+
+/// Async client with given connect timeout.
+pub fn client(ip_family: IpFamily, connect_timeout: Duration) -> Result<reqwest::Client> {
+    builder(ip_family)
+        .connect_timeout(connect_timeout)
+        .build()
+        .context("Failed to create reqwest client")
+}
+
+/// Blocking client with default settings.
+pub fn blocking_client(ip_family: IpFamily) -> Result<reqwest::blocking::Client> {
+    blocking_builder(ip_family)
+        .build()
+        .context("Failed to create reqwest client")
+}
+
+/// Add the address family tried to the error if it is caused by a timeout or connect failure.
+///
+/// Broken IPv6 routes are a common cause of such errors, which can not be told from the
+/// error message of reqwest, so the hint helps users to diagnose the problem by themselves.
+pub fn with_family_hint(err: anyhow::Error, ip_family: IpFamily) -> anyhow::Error {
+    let is_connection_error = err.chain().any(|e| {
+        let reqwest_err = e.downcast_ref::<reqwest::Error>().or_else(|| {
+            match e.downcast_ref::<download::Error>() {
+                Some(download::Error::Reqwest(e)) => Some(e),
+                _ => None,
+            }
+        });
+        reqwest_err.is_some_and(|e| e.is_timeout() || e.is_connect())
+    });
+    if !is_connection_error {
+        return err;
+    }
+
+    match ip_family {
+        IpFamily::Auto => err.context(format!(
+            "Connection failed over {ip_family}, \
+             if your network has broken IPv6 routes, try `--ipv4`"
+        )),
+        _ => err.context(format!("Connection failed over {ip_family}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::installer::test_server::{Response, Server};
+
+    fn get(ip_family: IpFamily, url: &str) -> reqwest::Result<String> {
+        blocking_builder(ip_family)
+            .no_proxy()
+            .timeout(Duration::from_secs(5))
+            .build()
+            .unwrap()
+            .get(url)
+            .send()?
+            .text()
+    }
+
+    #[test]
+    fn restrict_family() {
+        // The test server only listens on 127.0.0.1
+        let server = Server::new([Response::ok("auto"), Response::ok("ipv4")]);
+        let url = server.url("file");
+
+        assert_eq!(get(IpFamily::Auto, &url).unwrap(), "auto");
+        assert_eq!(get(IpFamily::Ipv4, &url).unwrap(), "ipv4");
+
+        let err = get(IpFamily::Ipv6, &url).unwrap_err();
+        assert!(err.is_connect(), "{err:?}");
+
+        let message = format!("{:#}", with_family_hint(err.into(), IpFamily::Ipv6));
+        assert!(message.starts_with("Connection failed over IPv6 only"));
+    }
+
+    #[test]
+    fn family_hint() {
+        // Bind and drop a listener to get a port which is not listening
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let err = get(IpFamily::Auto, &format!("http://127.0.0.1:{port}/file")).unwrap_err();
+        let message = format!("{:#}", with_family_hint(err.into(), IpFamily::Auto));
+        assert!(message.starts_with("Connection failed over IPv4 and IPv6"));
+        assert!(message.contains("--ipv4"));
+
+        let err = download::Error::Reqwest(get(IpFamily::Ipv6, "http://127.0.0.1:1/").unwrap_err());
+        let message = format!("{:#}", with_family_hint(err.into(), IpFamily::Ipv6));
+        assert!(message.starts_with("Connection failed over IPv6 only"));
+
+        // Other errors are not changed
+        let err = with_family_hint(anyhow::anyhow!("other error"), IpFamily::Ipv4);
+        assert_eq!(format!("{err:#}"), "other error");
+    }
+
+    #[test]
+    fn user_agent_header() {
+        assert_eq!(
+            format_user_agent("0.4.8", "x86_64-unknown-linux-gnu", None),
+            "maa-cli/0.4.8 (x86_64-unknown-linux-gnu)"
+        );
+        assert_eq!(
+            format_user_agent("0.4.8", "aarch64-apple-darwin", Some("brew")),
+            "maa-cli/0.4.8 (aarch64-apple-darwin; brew)"
+        );
+        assert_eq!(
+            format_user_agent("0.4.8", "aarch64-apple-darwin", Some(" ")),
+            "maa-cli/0.4.8 (aarch64-apple-darwin)"
+        );
+
+        let server = Server::new([Response::ok("")]);
+        get(IpFamily::Auto, &server.url("file")).unwrap();
+        let request = server.requests()[0].to_ascii_lowercase();
+        let expected = format!("user-agent: {}\r\n", user_agent()).to_ascii_lowercase();
+        assert!(request.contains(&expected), "{request}");
+        assert!(user_agent().starts_with("maa-cli"));
+    }
+
+    #[test]
+    fn redirect_check() {
+        let url = |s: &str| Url::parse(s).unwrap();
+        let https = url("https://example.com/maa.zip");
+        let http = url("http://mirror.example.com/maa.zip");
+
+        assert!(check_redirect(std::slice::from_ref(&https), &https, false).is_ok());
+        assert!(check_redirect(std::slice::from_ref(&http), &http, false).is_ok());
+        assert!(check_redirect(std::slice::from_ref(&http), &https, false).is_ok());
+
+        // https downgraded to http in the chain
+        let err = check_redirect(&[https.clone(), http.clone()], &http, false).unwrap_err();
+        assert!(err.contains("--allow-insecure-redirect"), "{err}");
+        assert!(err.contains("http://mirror.example.com/maa.zip"), "{err}");
+        assert!(check_redirect(std::slice::from_ref(&https), &http, true).is_ok());
+
+        let chain = vec![http.clone(); MAX_REDIRECTS + 1];
+        assert!(check_redirect(&chain, &http, true)
+            .unwrap_err()
+            .starts_with("Too many redirects"));
+    }
+
+    #[test]
+    fn redirect_chain() {
+        let target = Server::new([Response::ok("content")]);
+        let hop = Server::new([Response::status(302).header("Location", target.url("file"))]);
+        let first = Server::new([Response::status(301).header("Location", hop.url("file"))]);
+        assert_eq!(get(IpFamily::Auto, &first.url("file")).unwrap(), "content");
+
+        // a redirect loop is stopped at the hop limit
+        let lp = Server::new([Response::status(302).header("Location", "/loop")]);
+        let err = get(IpFamily::Auto, &lp.url("loop")).unwrap_err();
+        assert!(err.is_redirect(), "{err:?}");
+        assert_eq!(lp.requests().len(), MAX_REDIRECTS + 1);
+    }
+}
@@ -0,0 +1,489 @@
+//! Verify the files of components installed by maa-cli.
+//!
+//! The installers record the sha256 checksum of each installed file in the manifest.
+//! Files are checked against the manifest, and damaged files can be repaired by
+//! extracting them again from the cached package, which is downloaded again if removed.
+
+use super::{
+    block_on,
+    download::{download, local_path, sha256sum, Throttle},
+    extract::Archive,
+    http,
+    maa_core::{extract_mapper, STAGING_DIR},
+    manifest::{self, Entry, Manifest},
+    uninstall::Component,
+};
+
+use crate::{
+    config::cli::{cli_config, maa_core::Components},
+    dirs::{self, Ensure},
+};
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{bail, Context, Result};
+use log::{debug, warn};
+
+/// Exit code of `maa verify` when problems are found, which is distinct from other errors.
+pub const PROBLEMS_FOUND_EXIT_CODE: i32 = 2;
+
+/// Compute the sha256 checksums of installed files, to be recorded in the manifest.
+pub fn hash_files(files: Vec<PathBuf>) -> Result<BTreeMap<PathBuf, String>> {
+    files
+        .into_iter()
+        .map(|file| {
+            let hash = sha256sum(&file)
+                .with_context(|| format!("Failed to compute checksum of {}", file.display()))?;
+            Ok((file, hash))
+        })
+        .collect()
+}
+
+/// Problems found in the files of an installed component.
+#[cfg_attr(test, derive(Debug, PartialEq))]
+#[derive(Default)]
+pub struct Report {
+    /// Recorded files which do not exist
+    missing: Vec<PathBuf>,
+    /// Recorded files whose checksums mismatch
+    modified: Vec<PathBuf>,
+    /// Files in the installation directory which are not recorded
+    extraneous: Vec<PathBuf>,
+}
+
+impl Report {
+    pub fn is_ok(&self) -> bool {
+        self.missing.is_empty() && self.modified.is_empty() && self.extraneous.is_empty()
+    }
+
+    /// Files to be extracted again from the package
+    fn damaged(&self) -> impl Iterator<Item = &PathBuf> {
+        self.missing.iter().chain(&self.modified)
+    }
+
+    fn print(&self, name: &str) {
+        if self.is_ok() {
+            println!("{name}: OK");
+            return;
+        }
+        println!("{name}: FAILED");
+        for (kind, files) in [
+            ("missing", &self.missing),
+            ("modified", &self.modified),
+            ("extraneous", &self.extraneous),
+        ] {
+            for file in files {
+                println!("  {kind:<10} {}", file.display());
+            }
+        }
+    }
+}
+
+/// Check the files of an installed component in `root` against its manifest entry.
+///
+/// Files in `exclude`, e.g. the directory of another component inside `root`,
+/// are not reported as extraneous. Files without recorded checksums, which are installed
+/// by older versions of maa-cli, are only checked for existence.
+fn check(entry: &Entry, root: &Path, exclude: &[&Path]) -> Result<Report> {
+    let mut report = Report::default();
+    for file in entry.files() {
+        if !file.exists() {
+            report.missing.push(file.clone());
+        } else if let Some(expected) = entry.hash(file) {
+            let actual = sha256sum(file)
+                .with_context(|| format!("Failed to compute checksum of {}", file.display()))?;
+            if actual != expected {
+                report.modified.push(file.clone());
+            }
+        }
+    }
+
+    if root.exists() {
+        let recorded: BTreeSet<_> = entry.files().iter().collect();
+        let mut files = Vec::new();
+        walk(root, exclude, &mut files)
+            .with_context(|| format!("Failed to read {}", root.display()))?;
+        report.extraneous = files
+            .into_iter()
+            .filter(|file| !recorded.contains(file))
+            .collect();
+        report.extraneous.sort();
+    }
+
+    Ok(report)
+}
+
+fn walk(dir: &Path, exclude: &[&Path], files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in dir.read_dir()? {
+        let path = entry?.path();
+        if exclude.contains(&path.as_path()) {
+            continue;
+        }
+        if path.is_dir() {
+            walk(&path, exclude, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Directories of the installation and the cache, where packages and staging files are.
+struct Layout<'a> {
+    library: &'a Path,
+    resource: &'a Path,
+    cache: &'a Path,
+}
+
+impl Layout<'_> {
+    fn root(&self, component: Component) -> &Path {
+        match component {
+            Component::Core => self.library,
+            Component::Resource => self.resource,
+        }
+    }
+
+    /// Directories of other components which may be inside the root of `component`
+    fn exclude(&self, component: Component) -> [&Path; 1] {
+        match component {
+            Component::Core => [self.resource],
+            Component::Resource => [self.library],
+        }
+    }
+}
+
+/// Extract damaged files again from the package, and remove extraneous files.
+///
+/// The package is looked up in the cache directory by its name,
+/// and downloaded from the recorded source with `fetch` if it has been removed.
+fn repair(
+    entry: &Entry,
+    report: &Report,
+    layout: &Layout,
+    fetch: impl FnOnce(&str, &Path) -> Result<()>,
+) -> Result<()> {
+    let damaged: BTreeSet<_> = report.damaged().collect();
+    if !damaged.is_empty() {
+        let Some(name) = entry.package_name() else {
+            bail!("Unknown package of the installation, please reinstall it");
+        };
+        let package = layout.cache.ensure()?.join(name);
+        if !package.exists() {
+            println!("Package {name} is not in the cache, downloading it again...");
+            fetch(entry.source(), &package)?;
+        }
+
+        let components = Components {
+            library: true,
+            resource: true,
+        };
+        let staged = Archive::new(package.into())?.stage(
+            |path| {
+                extract_mapper(path, layout.library, layout.resource, &components)
+                    .filter(|dest| damaged.contains(dest))
+            },
+            &layout.cache.join(STAGING_DIR),
+        )?;
+        for (_, dest) in staged.commit()? {
+            debug!("Restored {}", dest.display());
+        }
+    }
+
+    for file in &report.extraneous {
+        debug!("Removing {}", file.display());
+        std::fs::remove_file(file)
+            .with_context(|| format!("Failed to remove {}", file.display()))?;
+    }
+
+    Ok(())
+}
+
+/// Download the package from `url` into `path`.
+///
+/// The size of the package is not recorded, so it is queried before downloading.
+/// No checksum of the package is known, but the extracted files are verified against
+/// the checksums in the manifest after repairing.
+fn fetch_package(url: &str, path: &Path) -> Result<()> {
+    let config = cli_config().core_config();
+    let client = http::client(config.ip_family(), Duration::from_secs(3))?;
+    let throttle = config.limit_rate().map(Throttle::new);
+    block_on(async {
+        let size = match local_path(url) {
+            Some(local) => std::fs::metadata(local)?.len(),
+            None => client
+                .head(url)
+                .send()
+                .await?
+                .error_for_status()?
+                .content_length()
+                .context("Unknown size of the package")?,
+        };
+        download(&client, url, path, size, &[], throttle.as_ref(), None).await?;
+        anyhow::Ok(())
+    })?
+    .map_err(|err| http::with_family_hint(err, config.ip_family()))
+    .context("Failed to download package")
+}
+
+/// Verify the installed components, all components installed by maa-cli if `None`.
+///
+/// Return whether no problem is left, i.e. all files are intact or repaired.
+pub fn verify(component: Option<Component>, repair: bool) -> Result<bool> {
+    let layout = Layout {
+        library: dirs::library(),
+        resource: dirs::resource(),
+        cache: dirs::cache(),
+    };
+    verify_in(&manifest::path(), component, repair, &layout, fetch_package)
+}
+
+fn verify_in(
+    manifest_path: &Path,
+    component: Option<Component>,
+    repair: bool,
+    layout: &Layout,
+    fetch: impl Fn(&str, &Path) -> Result<()>,
+) -> Result<bool> {
+    let manifest = Manifest::load(manifest_path);
+    let components = match component {
+        Some(component) => {
+            if manifest.get(component.name()).is_none() {
+                bail!(
+                    "{} is not installed by maa-cli, nothing to verify",
+                    component.name()
+                );
+            }
+            vec![component]
+        }
+        None => [Component::Core, Component::Resource]
+            .into_iter()
+            .filter(|component| manifest.get(component.name()).is_some())
+            .collect(),
+    };
+    if components.is_empty() {
+        bail!("Nothing is installed by maa-cli, nothing to verify");
+    }
+
+    let mut ok = true;
+    for component in components {
+        let name = component.name();
+        let entry = manifest.get(name).expect("filtered above");
+        if entry.files().iter().any(|file| entry.hash(file).is_none()) {
+            warn!(
+                "Some files of {name} are installed without checksums, only their existence is checked"
+            );
+        }
+
+        let root = layout.root(component);
+        let exclude = layout.exclude(component);
+        let report = check(entry, root, &exclude)?;
+        report.print(name);
+        if report.is_ok() {
+            continue;
+        }
+
+        if repair && entry.built_from_source() {
+            // There is no package to extract damaged files from, building again is required
+            warn!(
+                "{name} is built from source and can not be repaired, \
+                 reinstall it with `maa install --build-from-source --force`"
+            );
+            ok = false;
+        } else if repair {
+            println!("Repairing {name}...");
+            self::repair(entry, &report, layout, &fetch)?;
+            let report = check(entry, root, &exclude)?;
+            report.print(name);
+            ok &= report.is_ok();
+        } else {
+            ok = false;
+        }
+    }
+
+    Ok(ok)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use manifest::{MAA_CORE, RESOURCE};
+    use std::env::consts::{DLL_PREFIX, DLL_SUFFIX};
+
+    fn create_tar_gz(path: &Path, files: &[(&str, &str)]) {
+        let encoder =
+            flate2::write::GzEncoder::new(std::fs::File::create(path).unwrap(), Default::default());
+        let mut tar = tar::Builder::new(encoder);
+        for (name, content) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_mode(0o644);
+            header.set_size(content.len() as u64);
+            tar.append_data(&mut header, name, content.as_bytes())
+                .unwrap();
+        }
+        tar.into_inner().unwrap().finish().unwrap();
+    }
+
+    #[test]
+    fn verify_and_repair() {
+        let dir = std::env::temp_dir().join("maa-test-integrity");
+        dir.as_path().ensure_clean().unwrap();
+
+        let library = dir.join("lib");
+        let resource = dir.join("lib/resource");
+        let cache = dir.join("cache");
+        let manifest_path = dir.join("state/manifest.json");
+        let layout = Layout {
+            library: &library,
+            resource: &resource,
+            cache: &cache,
+        };
+
+        let lib_name = format!("{DLL_PREFIX}MaaCore{DLL_SUFFIX}");
+        let package_files = [
+            (format!("MAA-v5/{lib_name}"), "library"),
+            ("MAA-v5/resource/config.json".to_owned(), "config"),
+            ("MAA-v5/resource/tasks/a.json".to_owned(), "task a"),
+        ];
+        let package_files: Vec<_> = package_files
+            .iter()
+            .map(|(name, content)| (name.as_str(), *content))
+            .collect();
+        let package_name = "MAA-v5.0.0-linux-x86_64.tar.gz";
+        let mirror = dir.join("mirror").ensure().unwrap().join(package_name);
+        create_tar_gz(&mirror, &package_files);
+
+        // fabricate an installation
+        let lib_file = library.join(&lib_name);
+        let resource_files = [resource.join("config.json"), resource.join("tasks/a.json")];
+        for (file, (_, content)) in std::iter::once(&lib_file)
+            .chain(&resource_files)
+            .zip(&package_files)
+        {
+            file.parent().unwrap().ensure().unwrap();
+            std::fs::write(file, content).unwrap();
+        }
+
+        let source = format!("https://example.com/v5.0.0/{package_name}");
+        let mut manifest = Manifest::default();
+        let lib_files = hash_files(vec![lib_file.clone()]).unwrap();
+        manifest.insert_hashed(MAA_CORE, "5.0.0", &source, lib_files);
+        let resource_files_hashed = hash_files(resource_files.to_vec()).unwrap();
+        manifest.insert_hashed(RESOURCE, "5.0.0", &source, resource_files_hashed);
+        manifest.save(&manifest_path).unwrap();
+
+        let no_fetch = |_: &str, _: &Path| -> Result<()> { panic!("should not fetch") };
+        let fetch_mirror = |url: &str, path: &Path| {
+            assert_eq!(url, source);
+            std::fs::copy(&mirror, path)?;
+            Ok(())
+        };
+
+        // an intact installation passes
+        assert!(verify_in(&manifest_path, None, false, &layout, no_fetch).unwrap());
+        assert!(verify_in(
+            &manifest_path,
+            Some(Component::Core),
+            false,
+            &layout,
+            no_fetch
+        )
+        .unwrap());
+
+        // corrupt the installation
+        std::fs::write(&resource_files[0], "corrupted").unwrap();
+        std::fs::remove_file(&resource_files[1]).unwrap();
+        std::fs::write(resource.join("extra.json"), "extra").unwrap();
+
+        let manifest = Manifest::load(&manifest_path);
+        let report = check(manifest.get(RESOURCE).unwrap(), &resource, &[&library]).unwrap();
+        assert_eq!(
+            report,
+            Report {
+                missing: vec![resource_files[1].clone()],
+                modified: vec![resource_files[0].clone()],
+                extraneous: vec![resource.join("extra.json")],
+            }
+        );
+        // the resource directory inside the library directory is not extraneous
+        let report = check(manifest.get(MAA_CORE).unwrap(), &library, &[&resource]).unwrap();
+        assert!(report.is_ok());
+
+        assert!(!verify_in(&manifest_path, None, false, &layout, no_fetch).unwrap());
+        assert!(verify_in(
+            &manifest_path,
+            Some(Component::Core),
+            false,
+            &layout,
+            no_fetch
+        )
+        .unwrap());
+
+        // repair with the package downloaded again, since it is not in the cache
+        let library_modified = std::fs::metadata(&lib_file).unwrap().modified().unwrap();
+        assert!(verify_in(&manifest_path, None, true, &layout, fetch_mirror).unwrap());
+        assert_eq!(
+            std::fs::read_to_string(&resource_files[0]).unwrap(),
+            "config"
+        );
+        assert_eq!(
+            std::fs::read_to_string(&resource_files[1]).unwrap(),
+            "task a"
+        );
+        assert!(!resource.join("extra.json").exists());
+        assert!(cache.join(package_name).exists());
+        // intact files are not touched
+        assert_eq!(
+            std::fs::metadata(&lib_file).unwrap().modified().unwrap(),
+            library_modified
+        );
+
+        // repair with the cached package
+        std::fs::write(&lib_file, "corrupted").unwrap();
+        assert!(verify_in(
+            &manifest_path,
+            Some(Component::Core),
+            true,
+            &layout,
+            no_fetch
+        )
+        .unwrap());
+        assert_eq!(std::fs::read_to_string(&lib_file).unwrap(), "library");
+        assert!(verify_in(&manifest_path, None, false, &layout, no_fetch).unwrap());
+
+        // components built from source are verified but not repaired
+        let mut manifest = Manifest::load(&manifest_path);
+        let source_files = hash_files(vec![lib_file.clone()]).unwrap();
+        manifest
+            .insert_hashed(
+                MAA_CORE,
+                "5.0.0",
+                "https://example.com/v5.0.0.tar.gz",
+                source_files,
+            )
+            .set_built_from_source(true);
+        manifest.save(&manifest_path).unwrap();
+        assert!(verify_in(&manifest_path, None, true, &layout, no_fetch).unwrap());
+        std::fs::write(&lib_file, "corrupted").unwrap();
+        assert!(!verify_in(&manifest_path, None, true, &layout, no_fetch).unwrap());
+        assert_eq!(std::fs::read_to_string(&lib_file).unwrap(), "corrupted");
+
+        // components not installed can not be verified
+        let empty_manifest = dir.join("state/empty.json");
+        assert!(verify_in(&empty_manifest, None, false, &layout, no_fetch).is_err());
+        assert!(verify_in(
+            &empty_manifest,
+            Some(Component::Resource),
+            false,
+            &layout,
+            no_fetch
+        )
+        .is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
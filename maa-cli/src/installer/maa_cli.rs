@@ -1,78 +1,680 @@
 use super::{
-    download::{download, Checker},
+    block_on,
+    disk_space::{self, extracted_size, Requirement},
+    download::{
+        check_file_exists, fetch, local_path, parse_checksum_file, sha256sum, Checker, Throttle,
+    },
     extract::Archive,
-    version_json::VersionJSON,
+    github, http,
+    package_manager::PackageManager,
+    uninstall::{self, Plan},
+    update_check,
+    version_json::{self, VersionJSON},
 };
 
 use crate::{
-    config::cli::{cli_config, maa_cli::CommonArgs},
+    config::cli::{
+        cli_config,
+        maa_cli::{CommonArgs, Config},
+        Channel,
+    },
     dirs::{self, Ensure},
+    locate::{self, Located},
 };
 
 use std::{
+    collections::HashMap,
     env::{consts, current_exe},
-    time::Duration,
+    io::Read,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    time::{Duration, Instant},
 };
 
-use anyhow::{anyhow, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use dunce::canonicalize;
+use log::{debug, info, warn};
 use semver::Version;
-use serde::Deserialize;
-use tokio::runtime::Runtime;
+use serde::{Deserialize, Serialize};
+
+pub fn update(args: &CommonArgs, force: bool) -> Result<()> {
+    let bin_path = canonicalize(current_exe()?)?;
+    if let Some(manager) = PackageManager::detect(&bin_path) {
+        if force {
+            warn!("maa-cli seems to be managed by {manager}, updating it anyway");
+        } else {
+            warn!(
+                "maa-cli seems to be installed by {manager}, please update it with {}, \
+                 or use `--force` to update it anyway",
+                manager.upgrade_command()
+            );
+            return Ok(());
+        }
+    }
 
-pub fn update(args: &CommonArgs) -> Result<()> {
     let config = cli_config().cli_config().with_args(args);
+    http::allow_insecure_redirect(args.allow_insecure_redirect);
 
-    println!("Fetching maa-cli version info...");
-    let version_json: VersionJSON<Details> = reqwest::blocking::get(config.api_url())
-        .context("Failed to fetch version info")?
-        .json()
-        .context("Failed to parse version info")?;
+    let version_json = fetch_version_json(&config)?;
     let current_version: Version = env!("MAA_VERSION").parse()?;
     if !version_json.can_update("maa-cli", &current_version)? {
         return Ok(());
     }
 
+    if !args.no_space_check {
+        let asset = version_json.details().asset()?;
+        let cache_path = dirs::cache().join(asset.name());
+        let mut requirements = Vec::new();
+        if !check_file_exists(&cache_path, asset.size()) {
+            requirements.push(Requirement::new(&cache_path, asset.size()));
+        }
+        if config.components().binary {
+            requirements.push(Requirement::new(&bin_path, extracted_size(asset.size())));
+        }
+        disk_space::check(&requirements)?;
+    }
+
+    let cache_path = fetch_asset(&config, version_json.details(), args.require_checksum)?;
+
+    if config.components().binary {
+        // Extract the new binary next to the old one and check it works before replacing,
+        // so that a broken release will not leave the user without a working maa-cli
+        let staging_path = staging_path(&bin_path);
+        extract_binary(&cache_path, &staging_path)?;
+        let mut cmd = Command::new(&staging_path);
+        cmd.arg("--version");
+        if let Err(err) = smoke_test(cmd, version_json.version(), SMOKE_TEST_TIMEOUT) {
+            let _ = std::fs::remove_file(&staging_path);
+            return Err(err.context("The new maa-cli binary is broken, keep the current one"));
+        }
+        replace_binary(&staging_path, &bin_path)?;
+        // Record the checksum of installed binary, used by `maa self verify`
+        // when the version json is not available for installed version
+        write_local_checksum(&sha256sum(&bin_path)?, version_json.version())?;
+    }
+
+    Ok(())
+}
+
+/// Uninstall maa-cli itself after confirmation, with its cache and state files if `purge`.
+///
+/// Installations managed by a package manager are refused.
+pub fn uninstall(purge: bool, dry_run: bool) -> Result<()> {
+    let bin_path = canonicalize(current_exe()?)?;
+    if let Some(manager) = PackageManager::detect(&bin_path) {
+        bail!("maa-cli seems to be installed by {manager}, please uninstall it with {manager}");
+    }
+
+    let purge = if purge {
+        purge_targets(dirs::cache())
+    } else {
+        Vec::new()
+    };
+    let plan = Plan::new(vec![bin_path], None, purge);
+    if plan.confirm_and_execute("maa-cli", dry_run, uninstall::ask)? {
+        println!("Uninstalled maa-cli");
+    }
+    Ok(())
+}
+
+/// Cached packages and state files of maa-cli.
+fn purge_targets(cache_dir: &Path) -> Vec<PathBuf> {
+    let mut targets: Vec<PathBuf> = cache_dir
+        .read_dir()
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with("maa_cli-"))
+        .map(|entry| entry.path())
+        .collect();
+    targets.sort();
+    targets.push(local_checksum_path());
+    targets.push(update_check::state_path());
+    targets
+}
+
+/// Verify the integrity of current maa-cli binary.
+///
+/// The expected checksum is computed from the release archive of current version
+/// if the version json is for current version, otherwise the checksum recorded
+/// when maa-cli was updated is used.
+pub fn verify(args: &CommonArgs) -> Result<()> {
+    let config = cli_config().cli_config().with_args(args);
+    http::allow_insecure_redirect(args.allow_insecure_redirect);
+
+    let version_json = fetch_version_json(&config);
+    if !verify_binary(&config, &version_json, args.require_checksum)? {
+        bail!("Checksum of maa-cli v{} mismatch", env!("MAA_VERSION"))
+    }
+    Ok(())
+}
+
+/// Exit code of `maa self update --verify` when the checksum of current binary mismatches,
+/// which is distinct from other errors.
+pub const VERIFY_FAILED_EXIT_CODE: i32 = 2;
+
+/// Verify current binary and check whether an update is available without updating,
+/// used by `maa self update --verify` and `maa self update --check`.
+///
+/// The version json is fetched once and shared by both. Return `false` if the verification
+/// is requested and failed.
+pub fn check_installation(args: &CommonArgs, check: bool, verify: bool) -> Result<bool> {
+    let config = cli_config().cli_config().with_args(args);
+    http::allow_insecure_redirect(args.allow_insecure_redirect);
+
+    let version_json = fetch_version_json(&config);
+    let verified = !verify || verify_binary(&config, &version_json, args.require_checksum)?;
+    if check {
+        let current_version: Version = env!("MAA_VERSION").parse()?;
+        version_json?.can_update("maa-cli", &current_version)?;
+    }
+    Ok(verified)
+}
+
+/// Compare the checksum of current binary with the expected one, print `OK` or `FAILED`.
+fn verify_binary(
+    config: &Config,
+    version_json: &Result<VersionJSON<Details>>,
+    require_checksum: bool,
+) -> Result<bool> {
     let bin_path = canonicalize(current_exe()?)?;
-    let details = version_json.details();
+    let current_version: Version = env!("MAA_VERSION").parse()?;
+
+    let expected = match version_json {
+        Ok(version_json) if version_json.version() == &current_version => {
+            let cache_path = fetch_asset(config, version_json.details(), require_checksum)?;
+            let extracted = dirs::cache().join("maa-cli-verify").join(cli_exe());
+            extract_binary(&cache_path, &extracted)?;
+            let checksum = sha256sum(&extracted)?;
+            std::fs::remove_file(&extracted)?;
+            Some(checksum)
+        }
+        Ok(version_json) => {
+            info!(
+                "No release info for maa-cli v{} (latest: v{}), use local checksum",
+                current_version,
+                version_json.version()
+            );
+            None
+        }
+        Err(err) => {
+            warn!("{err:#}, use local checksum");
+            None
+        }
+    };
+
+    let expected = match expected {
+        Some(checksum) => checksum,
+        None => read_local_checksum(&current_version)?.with_context(|| {
+            format!("No checksum available to verify maa-cli v{current_version}")
+        })?,
+    };
+
+    let actual = sha256sum(&bin_path)?;
+    if actual == expected {
+        println!("{}: OK", bin_path.display());
+        Ok(true)
+    } else {
+        println!("{}: FAILED", bin_path.display());
+        debug!("Expected checksum: {expected}, actual checksum: {actual}");
+        Ok(false)
+    }
+}
+
+/// Information about the installation of maa-cli, used to help troubleshooting.
+#[derive(Serialize)]
+struct Info {
+    version: &'static str,
+    path: Option<PathBuf>,
+    /// Package manager which installed maa-cli, if any
+    package_manager: Option<String>,
+    target: Option<&'static str>,
+    channel: String,
+    api_url: String,
+    download_url: String,
+    user_agent: String,
+    cache_dir: PathBuf,
+    cache_size: u64,
+    /// Config directory in use, which may be given by `--config-dir` or `MAA_CONFIG_DIR`
+    config_dir: PathBuf,
+    /// Profile given by `--profile`, if any
+    profile: Option<&'static str>,
+    /// Directories in use and whether they exist, the same as `maa dir`
+    dirs: Vec<dirs::ResolvedDir>,
+    last_update_check: Option<update_check::State>,
+    /// Directory of the MaaCore library which will be loaded, and where it is found
+    core_library: Option<Located>,
+    /// Resource directory of MaaCore, and where it is found
+    core_resource: Option<Located>,
+}
+
+impl Info {
+    fn new(config: &Config) -> Self {
+        let path = current_exe().and_then(canonicalize).ok();
+        let cache_dir = dirs::cache().to_path_buf();
+        Self {
+            version: env!("MAA_VERSION"),
+            package_manager: path
+                .as_deref()
+                .and_then(PackageManager::detect)
+                .map(|manager| manager.to_string()),
+            path,
+            target: target().ok(),
+            channel: config.channel().to_string(),
+            api_url: config.api_url(),
+            download_url: config.download_base(),
+            user_agent: http::user_agent(),
+            cache_size: dir_size(&cache_dir),
+            cache_dir,
+            config_dir: dirs::config().to_path_buf(),
+            profile: dirs::profile(),
+            dirs: dirs::resolved(),
+            last_update_check: update_check::last_check(),
+            core_library: locate::find_library(),
+            core_resource: locate::find_resource(),
+        }
+    }
+
+    fn print(&self) {
+        fn or_unknown(value: Option<impl std::fmt::Display>) -> String {
+            value.map_or_else(|| "unknown".to_owned(), |v| v.to_string())
+        }
+
+        println!("Version: v{}", self.version);
+        println!(
+            "Path: {}",
+            or_unknown(self.path.as_deref().map(Path::display))
+        );
+        if let Some(manager) = &self.package_manager {
+            println!("Installed by: {manager}");
+        }
+        println!("Target: {}", or_unknown(self.target));
+        println!("Channel: {}", self.channel);
+        println!("API URL: {}", self.api_url);
+        println!("Download URL: {}", self.download_url);
+        println!("User agent: {}", self.user_agent);
+        println!(
+            "Cache: {} ({} bytes)",
+            self.cache_dir.display(),
+            self.cache_size
+        );
+        println!("Profile: {}", self.profile.unwrap_or("none"));
+        println!("Directories:");
+        dirs::resolved_table(&self.dirs).printstd();
+        match &self.last_update_check {
+            Some(state) => println!(
+                "Last update check: {} (latest: {})",
+                or_unknown(
+                    chrono::DateTime::from_timestamp(state.last_check() as i64, 0)
+                        .map(|time| time.with_timezone(&chrono::Local).to_rfc3339())
+                ),
+                or_unknown(state.latest().map(|v| format!("v{v}")))
+            ),
+            None => println!("Last update check: never"),
+        }
+        let located = |located: &Option<Located>| {
+            located.as_ref().map_or_else(
+                || "not found".to_owned(),
+                |located| format!("{} (from {})", located.path().display(), located.source()),
+            )
+        };
+        println!("MaaCore library: {}", located(&self.core_library));
+        println!("MaaCore resource: {}", located(&self.core_resource));
+    }
+}
+
+/// Print information about the installation of maa-cli, without network access.
+pub fn info(args: &CommonArgs, json: bool) -> Result<()> {
+    let config = cli_config().cli_config().with_args(args);
+    let info = Info::new(&config);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&info)?);
+    } else {
+        info.print();
+    }
+    Ok(())
+}
+
+/// Total size of all files in a directory, inaccessible files are ignored.
+fn dir_size(dir: &Path) -> u64 {
+    let Ok(entries) = dir.read_dir() else {
+        return 0;
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => dir_size(&entry.path()),
+            Ok(_) => entry.metadata().map_or(0, |m| m.len()),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+fn fetch_version_json(config: &Config) -> Result<VersionJSON<Details>> {
+    println!("Fetching maa-cli version info...");
+    match version_json::fetch(&config.api_url(), config.ip_family()) {
+        // The fallback is only used for the default source,
+        // a custom api url is usually a mirror which works when GitHub is blocked
+        Err(err) if config.is_default_api_url() => {
+            warn!("{err:#}");
+            warn!("Falling back to GitHub releases API");
+            fetch_from_releases(
+                &github::client(config.ip_family())?,
+                github::MAA_CLI_RELEASES_API,
+                config.channel(),
+            )
+        }
+        ret => ret,
+    }
+}
+
+/// Construct version json from the latest release of given channel by GitHub releases API.
+///
+/// Only the asset of current platform is included. The checksum is read from the
+/// checksum file attached to the release, if it is not available, the downloaded
+/// file will not be verified.
+fn fetch_from_releases(
+    client: &reqwest::blocking::Client,
+    api: &str,
+    channel: Channel,
+) -> Result<VersionJSON<Details>> {
+    let release = github::fetch_release(client, api, channel)?;
+    let version = release.version()?;
+    let target = target()?;
+
+    let assets = release.assets();
+    let archive = assets
+        .iter()
+        .find(|a| {
+            a.name().contains(target)
+                && !a.name().ends_with(".sha256")
+                && !a.name().ends_with(".sha512")
+        })
+        .with_context(|| {
+            format!(
+                "No prebuilt binary for {target} in release {}",
+                release.tag()
+            )
+        })?;
+
+    // The checksum files are the output of `sha256sum` and `sha512sum`
+    let release_checksum = |ext: &str| {
+        let checksum_name = format!("{}.{ext}", archive.name());
+        let checksum_file = assets.iter().find(|a| a.name() == checksum_name)?;
+        match github::fetch_text(client, checksum_file.download_url()) {
+            Ok(content) => parse_checksum_file(&content, archive.name()).map(str::to_owned),
+            Err(err) => {
+                warn!("{err:#}");
+                None
+            }
+        }
+    };
+
+    let asset = Asset {
+        name: archive.name().to_owned(),
+        size: archive.size(),
+        sha256sum: release_checksum("sha256"),
+        sha512sum: release_checksum("sha512"),
+    };
+
+    Ok(VersionJSON::new(
+        version,
+        Details {
+            tag: release.tag().to_owned(),
+            assets: Assets(HashMap::from([(target.to_owned(), asset)])),
+        },
+    ))
+}
+
+/// Download the release archive of given details to cache directory if not exists or corrupted.
+///
+/// The archive is verified by all available checksums, the sha512 and sha256 given by the
+/// version json, and the `.sha512` and `.sha256` files published alongside the archive. If none is available,
+/// the download is not verified, or an error is returned if `require_checksum` is true.
+fn fetch_asset(config: &Config, details: &Details, require_checksum: bool) -> Result<PathBuf> {
     let asset = details.asset()?;
     let asset_name = asset.name();
     let asset_size = asset.size();
-    let asset_checksum = asset.checksum();
+    let url = config.download_url(details.tag(), asset_name);
+
+    let client = http::blocking_client(config.ip_family())?;
+    let sidecar_sha256 = fetch_sidecar_checksum(&client, &url, "sha256");
+    let sidecar_sha512 = fetch_sidecar_checksum(&client, &url, "sha512");
+
+    // SHA-512 is preferred, so it is checked and reported first
+    let checkers: Vec<_> = [
+        merge_checksum(
+            asset_name,
+            "sha512",
+            asset.sha512_checksum(),
+            sidecar_sha512.as_deref(),
+        )?
+        .map(Checker::Sha512),
+        merge_checksum(
+            asset_name,
+            "sha256",
+            asset.checksum(),
+            sidecar_sha256.as_deref(),
+        )?
+        .map(Checker::Sha256),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+
+    if checkers.is_empty() {
+        if require_checksum {
+            bail!(
+                "No checksum available for {asset_name}, refuse to download it without verification"
+            );
+        }
+        warn!("No checksum available for {asset_name}, use `--require-checksum` to abort in this case");
+    }
     let cache_path = dirs::cache().ensure()?.join(asset_name);
 
-    if cache_path.exists() && cache_path.metadata()?.len() == asset_size {
-        println!("Found existing file: {}", cache_path.display());
-    } else {
-        let url = config.download_url(details.tag(), asset_name);
-        let client = reqwest::Client::builder()
-            .connect_timeout(Duration::from_secs(10))
-            .build()
-            .context("Failed to create reqwest client")?;
-        Runtime::new()
-            .context("Failed to create tokio runtime")?
-            .block_on(download(
-                &client,
-                &url,
-                &cache_path,
-                asset_size,
-                Some(Checker::Sha256(asset_checksum)),
-            ))
-            .context("Failed to download maa-cli")?;
+    let client = http::client(config.ip_family(), Duration::from_secs(10))?;
+    let throttle = config.limit_rate().map(Throttle::new);
+    block_on(fetch(
+        &client,
+        &url,
+        &cache_path,
+        asset_size,
+        &checkers,
+        throttle.as_ref(),
+        None,
+    ))?
+    .map_err(|err| http::with_family_hint(err.into(), config.ip_family()))
+    .context("Failed to download maa-cli")?;
+
+    Ok(cache_path)
+}
+
+/// Merge the checksum given by the version json and the one from the checksum file,
+/// an error is returned if both are given but different.
+fn merge_checksum<'a>(
+    asset_name: &str,
+    algorithm: &str,
+    version_json: Option<&'a str>,
+    sidecar: Option<&'a str>,
+) -> Result<Option<&'a str>> {
+    match (version_json, sidecar) {
+        (Some(checksum), Some(sidecar)) if !checksum.eq_ignore_ascii_case(sidecar) => bail!(
+            "The {algorithm} checksum of {asset_name} in version info ({checksum}) is different \
+             from the one in the checksum file ({sidecar}), refuse to download it"
+        ),
+        (checksum, sidecar) => Ok(checksum.or(sidecar)),
+    }
+}
+
+/// Fetch the checksum from the checksum file published alongside the asset at given url,
+/// `ext` is the extension of the checksum file, e.g. `sha256`.
+fn fetch_sidecar_checksum(
+    client: &reqwest::blocking::Client,
+    url: &str,
+    ext: &str,
+) -> Option<String> {
+    let sidecar_url = format!("{url}.{ext}");
+    debug!("Fetching checksum from {sidecar_url}");
+    let content = match local_path(&sidecar_url) {
+        Some(path) => std::fs::read_to_string(path)
+            .inspect_err(|err| debug!("Failed to read checksum file: {err}"))
+            .ok()?,
+        None => client
+            .get(&sidecar_url)
+            .send()
+            .and_then(|resp| resp.error_for_status())
+            .and_then(|resp| resp.text())
+            .inspect_err(|err| debug!("Failed to fetch checksum file: {err}"))
+            .ok()?,
     };
 
-    let cli_exe = format!("maa{}", consts::EXE_SUFFIX);
-    Archive::new(cache_path.into())?.extract(|path| {
-        if config.components().binary && path.ends_with(&cli_exe) {
-            Some(bin_path.clone())
-        } else {
-            None
+    let filename = url.rsplit('/').next().unwrap_or(url);
+    let checksum = parse_checksum_file(&content, filename);
+    if checksum.is_none() {
+        debug!("No checksum of {filename} found in {sidecar_url}");
+    }
+    checksum.map(str::to_owned)
+}
+
+fn cli_exe() -> String {
+    format!("maa{}", consts::EXE_SUFFIX)
+}
+
+fn extract_binary(archive: &Path, dest: &Path) -> Result<()> {
+    let cli_exe = cli_exe();
+    Archive::new(archive.into())?.extract_staged(
+        |path| {
+            if path.ends_with(&cli_exe) {
+                Some(dest.to_path_buf())
+            } else {
+                None
+            }
+        },
+        &dirs::cache().join("maa-cli-staging"),
+    )?;
+    Ok(())
+}
+
+/// Path to extract the new binary to, which is in the same directory as the current binary,
+/// so that it can be moved to the final path atomically.
+fn staging_path(bin_path: &Path) -> PathBuf {
+    let name = bin_path
+        .file_name()
+        .map_or_else(cli_exe, |name| name.to_string_lossy().into_owned());
+    bin_path.with_file_name(format!(".{name}.new"))
+}
+
+/// Replace the current binary with the new one.
+///
+/// On Windows, a running executable can not be overwritten but can be renamed,
+/// so the current binary is moved aside first.
+fn replace_binary(new: &Path, bin_path: &Path) -> Result<()> {
+    if cfg!(windows) {
+        let old = bin_path.with_extension("old");
+        let _ = std::fs::remove_file(&old);
+        std::fs::rename(bin_path, &old)
+            .with_context(|| format!("Failed to move {} aside", bin_path.display()))?;
+    }
+    std::fs::rename(new, bin_path)
+        .with_context(|| format!("Failed to install new binary to {}", bin_path.display()))
+}
+
+const SMOKE_TEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Run the new binary with `--version` and check it reports the expected version.
+///
+/// The command is killed if it does not exit in given timeout.
+fn smoke_test(mut cmd: Command, expected: &Version, timeout: Duration) -> Result<()> {
+    let mut child = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to run the new binary")?;
+
+    // Read outputs in background threads, so that a full pipe will not block the child
+    let read_to_string = |mut pipe: Box<dyn Read + Send>| {
+        std::thread::spawn(move || {
+            let mut output = String::new();
+            let _ = pipe.read_to_string(&mut output);
+            output
+        })
+    };
+    let stdout = read_to_string(Box::new(child.stdout.take().expect("stdout is piped")));
+    let stderr = read_to_string(Box::new(child.stderr.take().expect("stderr is piped")));
+
+    let start = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if start.elapsed() > timeout {
+            let _ = child.kill();
+            let _ = child.wait();
+            bail!(
+                "The new binary did not exit in {} seconds",
+                timeout.as_secs_f32()
+            );
         }
-    })?;
+        std::thread::sleep(Duration::from_millis(50));
+    };
+
+    let stdout = stdout.join().unwrap_or_default();
+    let stderr = stderr.join().unwrap_or_default();
+    if !status.success() {
+        bail!(
+            "The new binary exited with {status}, stderr: {}",
+            stderr.trim()
+        );
+    }
+
+    let expected = expected.to_string();
+    if !stdout.split_whitespace().any(|word| word == expected) {
+        bail!(
+            "The new binary reported version `{}`, expected {expected}",
+            stdout.trim()
+        );
+    }
 
     Ok(())
 }
 
+fn local_checksum_path() -> PathBuf {
+    dirs::state().join("maa-cli.sha256")
+}
+
+fn write_local_checksum(checksum: &str, version: &Version) -> Result<()> {
+    dirs::state().ensure_with_mode(dirs::PRIVATE_DIR_MODE)?;
+    std::fs::write(local_checksum_path(), format_checksum(checksum, version))
+        .context("Failed to record checksum of maa-cli")
+}
+
+fn read_local_checksum(version: &Version) -> Result<Option<String>> {
+    let path = local_checksum_path();
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    Ok(parse_checksum(&std::fs::read_to_string(path)?, version))
+}
+
+/// Format checksum in the format of `sha256sum` output, with version as file name.
+fn format_checksum(checksum: &str, version: &Version) -> String {
+    format!("{checksum}  maa-cli-v{version}\n")
+}
+
+/// Parse checksum file written by `format_checksum`.
+///
+/// Return `None` if the recorded checksum is not for given version.
+fn parse_checksum(content: &str, version: &Version) -> Option<String> {
+    let (checksum, name) = content.trim().split_once("  ")?;
+    if name.strip_prefix("maa-cli-v")? == version.to_string() {
+        Some(checksum.to_owned())
+    } else {
+        None
+    }
+}
+
 #[derive(Deserialize)]
 struct Details {
     tag: String,
@@ -89,45 +691,65 @@ impl Details {
     }
 }
 
+/// Assets of a release, keyed by target triple
+///
+/// Platforms without a published build are simply missing from the map.
 #[derive(Deserialize)]
-struct Assets {
-    #[serde(rename = "x86_64-apple-darwin")]
-    x86_64_apple_darwin: Asset,
-    #[serde(rename = "aarch64-apple-darwin")]
-    aarch64_apple_darwin: Asset,
-    #[serde(rename = "x86_64-unknown-linux-gnu")]
-    x86_64_unknown_linux_gnu: Asset,
-    #[serde(rename = "aarch64-unknown-linux-gnu")]
-    aarch64_unknown_linux_gnu: Asset,
-    #[serde(rename = "x86_64-pc-windows-msvc")]
-    x86_64_pc_windows_msvc: Asset,
-}
+struct Assets(HashMap<String, Asset>);
 
 impl Assets {
     fn asset(&self) -> Result<&Asset> {
-        use consts::{ARCH, OS};
-        match OS {
-            "macos" => match ARCH {
-                "x86_64" => Ok(&self.x86_64_apple_darwin),
-                "aarch64" => Ok(&self.aarch64_apple_darwin),
-                _ => Err(anyhow!("Unsupported architecture: {ARCH}")),
-            },
-            "linux" => match consts::ARCH {
-                "x86_64" => Ok(&self.x86_64_unknown_linux_gnu),
-                "aarch64" => Ok(&self.aarch64_unknown_linux_gnu),
-                _ => Err(anyhow!("Unsupported architecture: {ARCH}")),
-            },
-            "windows" if consts::ARCH == "x86_64" => Ok(&self.x86_64_pc_windows_msvc),
-            _ => Err(anyhow!("Unsupported platform: {OS} {ARCH}")),
-        }
+        let target = target()?;
+        self.0.get(target).ok_or_else(|| {
+            anyhow!(
+                "No prebuilt binary for {target}, available platforms: {}",
+                self.platforms().join(", ")
+            )
+        })
+    }
+
+    /// Target triples of all published builds, in sorted order
+    fn platforms(&self) -> Vec<&str> {
+        let mut platforms: Vec<&str> = self.0.keys().map(String::as_str).collect();
+        platforms.sort_unstable();
+        platforms
+    }
+}
+
+#[cfg(test)]
+impl VersionJSON<Details> {
+    /// Target triples of all platforms with a published build, e.g. `x86_64-unknown-linux-gnu`
+    pub fn available_platforms(&self) -> Vec<&str> {
+        self.details().assets.platforms()
+    }
+}
+
+/// Target triple of prebuilt binary for current platform
+fn target() -> Result<&'static str> {
+    use consts::{ARCH, OS};
+    match OS {
+        "macos" => match ARCH {
+            "x86_64" => Ok("x86_64-apple-darwin"),
+            "aarch64" => Ok("aarch64-apple-darwin"),
+            _ => Err(anyhow!("Unsupported architecture: {ARCH}")),
+        },
+        "linux" => match ARCH {
+            "x86_64" => Ok("x86_64-unknown-linux-gnu"),
+            "aarch64" => Ok("aarch64-unknown-linux-gnu"),
+            _ => Err(anyhow!("Unsupported architecture: {ARCH}")),
+        },
+        "windows" if ARCH == "x86_64" => Ok("x86_64-pc-windows-msvc"),
+        _ => Err(anyhow!("Unsupported platform: {OS} {ARCH}")),
     }
 }
 
+#[cfg_attr(test, derive(Debug))]
 #[derive(Deserialize)]
 struct Asset {
     name: String,
     size: u64,
-    sha256sum: String,
+    sha256sum: Option<String>,
+    sha512sum: Option<String>,
 }
 
 impl Asset {
@@ -139,8 +761,12 @@ impl Asset {
         self.size
     }
 
-    pub fn checksum(&self) -> &str {
-        &self.sha256sum
+    pub fn checksum(&self) -> Option<&str> {
+        self.sha256sum.as_deref()
+    }
+
+    pub fn sha512_checksum(&self) -> Option<&str> {
+        self.sha512sum.as_deref()
     }
 }
 
@@ -195,7 +821,421 @@ mod tests {
         assert_eq!(asset.size(), 123456);
         assert_eq!(
             asset.checksum(),
-            "1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef"
+            Some("1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef")
+        );
+        assert_eq!(
+            version_json.available_platforms(),
+            [
+                "aarch64-apple-darwin",
+                "aarch64-unknown-linux-gnu",
+                "x86_64-apple-darwin",
+                "x86_64-pc-windows-msvc",
+                "x86_64-unknown-linux-gnu",
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_platforms() {
+        let json = r#"
+{
+    "version": "0.1.0",
+    "details": {
+        "tag": "v0.1.0",
+        "assets": {
+            "riscv64gc-unknown-linux-gnu": { "name": "maa-cli.tar.gz", "size": 1 }
+        }
+    }
+}
+        "#;
+
+        let version_json: VersionJSON<Details> = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            version_json.available_platforms(),
+            ["riscv64gc-unknown-linux-gnu"]
+        );
+        let err = version_json.details().asset().unwrap_err();
+        assert!(err
+            .to_string()
+            .ends_with("available platforms: riscv64gc-unknown-linux-gnu"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn smoke_test_new_binary() {
+        fn fake_binary(script: &str) -> Command {
+            let mut cmd = Command::new("sh");
+            cmd.arg("-c").arg(script);
+            cmd
+        }
+
+        let version = Version::parse("0.4.9").unwrap();
+        let timeout = Duration::from_secs(5);
+
+        smoke_test(fake_binary("echo maa 0.4.9"), &version, timeout).unwrap();
+
+        let err = smoke_test(fake_binary("echo maa 0.4.8"), &version, timeout).unwrap_err();
+        assert!(err.to_string().contains("`maa 0.4.8`"));
+
+        let err = smoke_test(
+            fake_binary("echo 'Segmentation fault' >&2; exit 139"),
+            &version,
+            timeout,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("Segmentation fault"));
+
+        let start = Instant::now();
+        let err = smoke_test(
+            fake_binary("sleep 10"),
+            &version,
+            Duration::from_millis(200),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("did not exit"));
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        let err =
+            smoke_test(Command::new("/path/to/nonexistent/maa"), &version, timeout).unwrap_err();
+        assert!(err.to_string().contains("Failed to run"));
+    }
+
+    #[test]
+    fn staging() {
+        let bin_path = Path::new("/usr/local/bin/maa");
+        assert_eq!(staging_path(bin_path), Path::new("/usr/local/bin/.maa.new"));
+
+        let dir = std::env::temp_dir().join("maa-test-replace-binary");
+        std::fs::create_dir_all(&dir).unwrap();
+        let bin_path = dir.join("maa");
+        let staging = staging_path(&bin_path);
+        std::fs::write(&bin_path, "old").unwrap();
+        std::fs::write(&staging, "new").unwrap();
+        replace_binary(&staging, &bin_path).unwrap();
+        assert_eq!(std::fs::read_to_string(&bin_path).unwrap(), "new");
+        assert!(!staging.exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn info_json() {
+        let config = Config::default();
+        let info = serde_json::to_value(Info::new(&config)).unwrap();
+        let object = info.as_object().unwrap();
+
+        let mut keys: Vec<_> = object.keys().map(String::as_str).collect();
+        keys.sort_unstable();
+        assert_eq!(
+            keys,
+            [
+                "api_url",
+                "cache_dir",
+                "cache_size",
+                "channel",
+                "config_dir",
+                "core_library",
+                "core_resource",
+                "dirs",
+                "download_url",
+                "last_update_check",
+                "package_manager",
+                "path",
+                "profile",
+                "target",
+                "user_agent",
+                "version",
+            ]
+        );
+        assert_eq!(info["version"], env!("MAA_VERSION"));
+        assert_eq!(info["channel"], "stable");
+        assert_eq!(info["api_url"], config.api_url());
+        assert_eq!(info["download_url"], config.download_base());
+        assert_eq!(info["user_agent"], http::user_agent());
+        assert!(info["path"].is_string());
+        assert!(info["cache_size"].is_u64());
+        assert_eq!(info["config_dir"], dirs::config().to_str().unwrap());
+        assert!(info["profile"].is_null());
+        assert_eq!(info["dirs"][0]["name"], "config");
+        assert_eq!(info["dirs"][0]["path"], dirs::config().to_str().unwrap());
+    }
+
+    #[test]
+    fn size_of_dir() {
+        let dir = std::env::temp_dir().join("maa-test-dir-size");
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir).unwrap();
+        }
+        assert_eq!(dir_size(&dir), 0);
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("a"), [0; 10]).unwrap();
+        std::fs::write(dir.join("sub").join("b"), [0; 5]).unwrap();
+        assert_eq!(dir_size(&dir), 15);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn sha512_checksum() {
+        let asset: Asset = serde_json::from_str(
+            r#"{"name": "maa-cli.zip", "size": 123456, "sha256sum": "abc", "sha512sum": "def"}"#,
+        )
+        .unwrap();
+        assert_eq!(asset.checksum(), Some("abc"));
+        assert_eq!(asset.sha512_checksum(), Some("def"));
+
+        let asset: Asset =
+            serde_json::from_str(r#"{"name": "maa-cli.zip", "size": 123456}"#).unwrap();
+        assert_eq!(asset.sha512_checksum(), None);
+    }
+
+    #[test]
+    fn merge_checksums() {
+        assert_eq!(merge_checksum("a", "sha512", None, None).unwrap(), None);
+        assert_eq!(
+            merge_checksum("a", "sha512", Some("abc"), None).unwrap(),
+            Some("abc")
+        );
+        assert_eq!(
+            merge_checksum("a", "sha512", None, Some("abc")).unwrap(),
+            Some("abc")
+        );
+        assert_eq!(
+            merge_checksum("a", "sha512", Some("abc"), Some("ABC")).unwrap(),
+            Some("abc")
+        );
+        let err = merge_checksum("a", "sha512", Some("abc"), Some("def")).unwrap_err();
+        assert!(err.to_string().starts_with("The sha512 checksum of a"));
+    }
+
+    #[test]
+    fn sidecar_checksum() {
+        use crate::installer::test_server::{Response, Server};
+
+        const CHECKSUM: &str = "55d731f2fe4bc2dc72f0288f5bc9a594dc3069d1949735fa3f50fde6580012f9";
+        let client = reqwest::blocking::Client::builder()
+            .no_proxy()
+            .build()
+            .unwrap();
+
+        let server = Server::new([Response::ok(format!("{CHECKSUM}  maa_cli.tar.gz\r\n"))]);
+        let url = server.url("v0.4.8/maa_cli.tar.gz");
+        assert_eq!(
+            fetch_sidecar_checksum(&client, &url, "sha256").as_deref(),
+            Some(CHECKSUM)
+        );
+        assert!(server.requests()[0].starts_with("GET /v0.4.8/maa_cli.tar.gz.sha256 "));
+
+        // checksum of another file
+        let server = Server::new([Response::ok(format!("{CHECKSUM}  other.tar.gz\n"))]);
+        let url = server.url("v0.4.8/maa_cli.tar.gz");
+        assert_eq!(fetch_sidecar_checksum(&client, &url, "sha256"), None);
+
+        // sidecar file not found
+        let server = Server::new([Response::status(404)]);
+        let url = server.url("v0.4.8/maa_cli.tar.gz");
+        assert_eq!(fetch_sidecar_checksum(&client, &url, "sha256"), None);
+    }
+
+    mod releases_api {
+        use super::*;
+
+        use crate::installer::test_server::{Response, Server};
+
+        // A stripped response of `GET /repos/MaaAssistantArknights/maa-cli/releases/latest`,
+        // `{server}` is replaced with the url of test server
+        const LATEST_RELEASE: &str = r#"
+{
+  "url": "https://api.github.com/repos/MaaAssistantArknights/maa-cli/releases/150000000",
+  "tag_name": "v0.4.8",
+  "target_commitish": "main",
+  "name": "v0.4.8",
+  "draft": false,
+  "prerelease": false,
+  "created_at": "2024-05-25T14:12:57Z",
+  "published_at": "2024-05-25T14:29:39Z",
+  "assets": [
+    {
+      "name": "maa_cli-v0.4.8-aarch64-apple-darwin.zip",
+      "content_type": "application/zip",
+      "size": 4130000,
+      "browser_download_url": "{server}/v0.4.8/maa_cli-v0.4.8-aarch64-apple-darwin.zip"
+    },
+    {
+      "name": "maa_cli-v0.4.8-aarch64-apple-darwin.zip.sha256",
+      "content_type": "application/octet-stream",
+      "size": 106,
+      "browser_download_url": "{server}/v0.4.8/maa_cli-v0.4.8-aarch64-apple-darwin.zip.sha256"
+    },
+    {
+      "name": "maa_cli-v0.4.8-aarch64-unknown-linux-gnu.tar.gz",
+      "content_type": "application/gzip",
+      "size": 4250000,
+      "browser_download_url": "{server}/v0.4.8/maa_cli-v0.4.8-aarch64-unknown-linux-gnu.tar.gz"
+    },
+    {
+      "name": "maa_cli-v0.4.8-aarch64-unknown-linux-gnu.tar.gz.sha256",
+      "content_type": "application/octet-stream",
+      "size": 113,
+      "browser_download_url": "{server}/v0.4.8/maa_cli-v0.4.8-aarch64-unknown-linux-gnu.tar.gz.sha256"
+    },
+    {
+      "name": "maa_cli-v0.4.8-x86_64-apple-darwin.zip",
+      "content_type": "application/zip",
+      "size": 4300000,
+      "browser_download_url": "{server}/v0.4.8/maa_cli-v0.4.8-x86_64-apple-darwin.zip"
+    },
+    {
+      "name": "maa_cli-v0.4.8-x86_64-apple-darwin.zip.sha256",
+      "content_type": "application/octet-stream",
+      "size": 105,
+      "browser_download_url": "{server}/v0.4.8/maa_cli-v0.4.8-x86_64-apple-darwin.zip.sha256"
+    },
+    {
+      "name": "maa_cli-v0.4.8-x86_64-pc-windows-msvc.zip",
+      "content_type": "application/zip",
+      "size": 4080000,
+      "browser_download_url": "{server}/v0.4.8/maa_cli-v0.4.8-x86_64-pc-windows-msvc.zip"
+    },
+    {
+      "name": "maa_cli-v0.4.8-x86_64-pc-windows-msvc.zip.sha256",
+      "content_type": "application/octet-stream",
+      "size": 108,
+      "browser_download_url": "{server}/v0.4.8/maa_cli-v0.4.8-x86_64-pc-windows-msvc.zip.sha256"
+    },
+    {
+      "name": "maa_cli-v0.4.8-x86_64-unknown-linux-gnu.tar.gz",
+      "content_type": "application/gzip",
+      "size": 4400000,
+      "browser_download_url": "{server}/v0.4.8/maa_cli-v0.4.8-x86_64-unknown-linux-gnu.tar.gz"
+    },
+    {
+      "name": "maa_cli-v0.4.8-x86_64-unknown-linux-gnu.tar.gz.sha256",
+      "content_type": "application/octet-stream",
+      "size": 112,
+      "browser_download_url": "{server}/v0.4.8/maa_cli-v0.4.8-x86_64-unknown-linux-gnu.tar.gz.sha256"
+    }
+  ]
+}
+"#;
+
+        const CHECKSUM: &str = "55d731f2fe4bc2dc72f0288f5bc9a594dc3069d1949735fa3f50fde6580012f9";
+
+        fn client() -> reqwest::blocking::Client {
+            reqwest::blocking::Client::builder()
+                .no_proxy()
+                .build()
+                .unwrap()
+        }
+
+        fn latest_release(server: &Server, with_checksum: bool) -> String {
+            let base = server.url("");
+            let release = LATEST_RELEASE.replace("{server}", base.trim_end_matches('/'));
+            if with_checksum {
+                release
+            } else {
+                let mut value: serde_json::Value = serde_json::from_str(&release).unwrap();
+                value["assets"]
+                    .as_array_mut()
+                    .unwrap()
+                    .retain(|a| !a["name"].as_str().unwrap().ends_with(".sha256"));
+                value.to_string()
+            }
+        }
+
+        #[test]
+        fn with_checksum() {
+            let Ok(target) = target() else { return };
+
+            // The server can not know its url before created, so start a server to get the
+            // url of checksum file and serve the release json by another one
+            let checksum_server = Server::new([Response::ok(format!(
+                "{CHECKSUM}  maa_cli-v0.4.8-{target}.tar.gz\n"
+            ))]);
+            let api_server = Server::new([Response::ok(latest_release(&checksum_server, true))]);
+
+            let version_json =
+                fetch_from_releases(&client(), &api_server.url("releases"), Channel::Stable)
+                    .unwrap();
+            assert_eq!(version_json.version(), &Version::new(0, 4, 8));
+
+            let details = version_json.details();
+            assert_eq!(details.tag(), "v0.4.8");
+            let asset = details.asset().unwrap();
+            assert!(asset
+                .name()
+                .starts_with(&format!("maa_cli-v0.4.8-{target}")));
+            assert_ne!(asset.size(), 0);
+            assert_eq!(asset.checksum(), Some(CHECKSUM));
+
+            assert!(checksum_server.requests()[0]
+                .starts_with(&format!("GET /v0.4.8/{}.sha256 ", asset.name())));
+        }
+
+        #[test]
+        fn without_checksum() {
+            let Ok(target) = target() else { return };
+
+            let unused = Server::new([Response::status(404)]);
+            let api_server = Server::new([Response::ok(latest_release(&unused, false))]);
+
+            let version_json =
+                fetch_from_releases(&client(), &api_server.url("releases"), Channel::Stable)
+                    .unwrap();
+            let asset = version_json.details().asset().unwrap();
+            assert!(asset.name().contains(target));
+            assert_eq!(asset.checksum(), None);
+            assert!(unused.requests().is_empty());
+        }
+
+        #[test]
+        fn checksum_unavailable() {
+            if target().is_err() {
+                return;
+            }
+
+            let checksum_server = Server::new([Response::status(404)]);
+            let api_server = Server::new([Response::ok(latest_release(&checksum_server, true))]);
+
+            let version_json =
+                fetch_from_releases(&client(), &api_server.url("releases"), Channel::Stable)
+                    .unwrap();
+            assert_eq!(version_json.details().asset().unwrap().checksum(), None);
+        }
+    }
+
+    #[test]
+    fn purge_cache_and_state() {
+        let dir = std::env::temp_dir().join("maa-test-cli-purge");
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in [
+            "maa_cli-v0.4.8-x86_64-unknown-linux-gnu.tar.gz",
+            "MAA-v5.0.0.zip",
+        ] {
+            std::fs::write(dir.join(name), "").unwrap();
+        }
+
+        let targets = purge_targets(&dir);
+        assert_eq!(
+            targets,
+            [
+                dir.join("maa_cli-v0.4.8-x86_64-unknown-linux-gnu.tar.gz"),
+                local_checksum_path(),
+                update_check::state_path(),
+            ]
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn local_checksum() {
+        let version = Version::parse("0.4.8").unwrap();
+        let content = format_checksum("abcdef", &version);
+        assert_eq!(content, "abcdef  maa-cli-v0.4.8\n");
+        assert_eq!(parse_checksum(&content, &version).unwrap(), "abcdef");
+        assert_eq!(
+            parse_checksum(&content, &Version::parse("0.4.9").unwrap()),
+            None
         );
+        assert_eq!(parse_checksum("abcdef", &version), None);
     }
 }
@@ -1,34 +1,38 @@
 // This file is used to download and extract prebuilt packages of maa-core.
 
 use super::{
-    download::{check_file_exists, download_mirrors},
-    extract::Archive,
-    version_json::VersionJSON,
+    block_on,
+    disk_space::{self, extracted_size, Requirement},
+    download::{self, check_file_exists, download_mirrors, Checker, Throttle},
+    extract::{relative_path, Archive},
+    github, http, integrity, manifest, source_build,
+    version_json::{self, VersionJSON},
 };
 
 use crate::{
     config::cli::{
         cli_config,
-        maa_core::{CommonArgs, Components, Config},
+        maa_core::{AutoUpdate, CommonArgs, Components, Config, HookArgs},
+        Channel,
     },
     dirs::{self, Ensure},
     run,
+    value::userinput::{BoolInput, UserInput},
 };
 
 use std::{
-    borrow::Cow,
     env::consts::{ARCH, DLL_PREFIX, DLL_SUFFIX, OS},
     path::{self, Path, PathBuf},
     time::Duration,
 };
 
 use anyhow::{anyhow, bail, Context, Result};
-use log::debug;
+use glob::Pattern;
+use log::{debug, warn};
 use semver::Version;
 use serde::Deserialize;
-use tokio::runtime::Runtime;
 
-fn extract_mapper(
+pub(super) fn extract_mapper(
     src: &Path,
     lib_dir: &Path,
     resource_dir: &Path,
@@ -77,52 +81,152 @@ pub fn version() -> Result<Version> {
         .context("Failed to get version")
 }
 
-pub fn install(force: bool, args: &CommonArgs) -> Result<()> {
+/// Install MaaCore and resources, the latest version of the channel unless `pinned`.
+///
+/// If MaaCore is already installed, nothing is done unless `force`, and if the installed
+/// version is the pinned one, it is reported as up to date.
+///
+/// If `build` is given, MaaCore is built from source with given number of jobs,
+/// the number of CPUs by default, instead of installed from a prebuilt package.
+pub fn install(
+    force: bool,
+    pinned: Option<&Version>,
+    build: Option<Option<usize>>,
+    args: &CommonArgs,
+) -> Result<()> {
     let config = cli_config().core_config().apply_args(args);
+    http::allow_insecure_redirect(args.allow_insecure_redirect);
 
     let lib_dir = dirs::library();
     let lib_name = format!("{}MaaCore{}", DLL_PREFIX, DLL_SUFFIX);
 
     if lib_dir.join(lib_name).exists() && !force {
+        if let Some(pinned) = pinned {
+            if version().is_ok_and(|installed| &installed == pinned) {
+                println!("Up to date: MaaCore v{pinned}.");
+                return Ok(());
+            }
+        }
         bail!("MaaCore already exists, use `maa update` to update it or `maa install --force` to force reinstall")
     }
 
-    println!(
-        "Fetching MaaCore version info (channel: {})...",
-        config.channel()
-    );
-    let version_json = get_version_json(&config)?;
-    let asset_version = version_json.version();
-    let asset_name = name(asset_version)?;
-    let asset = version_json.details().asset(&asset_name)?;
+    let channel = pinned.is_none().then(|| config.channel());
+    if let Some(jobs) = build {
+        let version = match pinned {
+            Some(pinned) => pinned.clone(),
+            None => {
+                println!(
+                    "Fetching MaaCore version info (channel: {})...",
+                    config.channel()
+                );
+                get_version_json(&config)?.version().clone()
+            }
+        };
+        let jobs = jobs.unwrap_or_else(source_build::default_jobs);
+        return source_build::install(&config, &version, jobs, channel);
+    }
 
-    println!("Downloading MaaCore {}...", asset_version);
-    let cache_dir = dirs::cache().ensure()?;
-    let archive = download(
-        cache_dir.join(asset_name).into(),
-        asset.size(),
-        asset.download_links(),
-        &config,
-    )?;
+    let version_json = match pinned {
+        Some(pinned) => {
+            println!("Fetching MaaCore version info (version: v{pinned})...");
+            fetch_pinned(
+                get_version_json(&config),
+                &github::client(config.ip_family())?,
+                github::MAA_CORE_RELEASES_API,
+                pinned,
+            )?
+        }
+        None => {
+            println!(
+                "Fetching MaaCore version info (channel: {})...",
+                config.channel()
+            );
+            get_version_json(&config)?
+        }
+    };
 
-    println!("Installing MaaCore...");
-    let components = config.components();
-    if components.library {
-        debug!("Cleaning library directory");
-        lib_dir.ensure_clean()?;
+    install_package(&config, &version_json, channel, args.no_space_check)
+}
+
+/// Config of MaaCore installer for updating.
+///
+/// The channel MaaCore is installed from is kept unless another one is given by `--channel`.
+fn update_config(args: &CommonArgs) -> Config {
+    let mut config = cli_config().core_config().apply_args(args);
+    if args.channel.is_none() {
+        if let Some(channel) = manifest::installed(manifest::MAA_CORE).and_then(|e| e.channel()) {
+            config.set_channel(channel);
+        }
     }
-    let resource_dir = dirs::resource();
-    if components.resource {
-        debug!("Cleaning resource directory");
-        resource_dir.ensure_clean()?;
+    config
+}
+
+/// What to do when updating MaaCore
+#[cfg_attr(test, derive(Debug, PartialEq))]
+enum Decision {
+    UpToDate,
+    Update,
+    /// The remote version is older, which happens when switching to a more stable channel
+    Downgrade,
+}
+
+/// Decide whether to update MaaCore from `current` version to `remote` version of `channel`.
+///
+/// An older remote version is only offered as a downgrade if the channel is changed,
+/// i.e. differs from the `installed` one, or MaaCore of unknown channel is a prerelease
+/// while updating from the stable channel.
+fn decide(
+    remote: &Version,
+    current: &Version,
+    installed: Option<Channel>,
+    channel: Channel,
+) -> Decision {
+    if remote > current {
+        return Decision::Update;
+    }
+    if remote == current {
+        return Decision::UpToDate;
+    }
+    let switched = match installed {
+        Some(installed) => installed != channel,
+        None => !current.pre.is_empty() && matches!(channel, Channel::Stable),
+    };
+    if switched {
+        Decision::Downgrade
+    } else {
+        Decision::UpToDate
     }
-    archive.extract(|path: &Path| extract_mapper(path, lib_dir, resource_dir, components))?;
+}
 
-    Ok(())
+/// Run an update hook, the versions are passed by `MAA_OLD_VERSION` and `MAA_NEW_VERSION`.
+fn run_update_hook(hook: Option<&str>, old: &str, new: &str) -> Result<()> {
+    match hook {
+        Some(hook) => {
+            run::hook::run_with_env(hook, &[("MAA_OLD_VERSION", old), ("MAA_NEW_VERSION", new)])
+        }
+        None => Ok(()),
+    }
 }
 
-pub fn update(args: &CommonArgs) -> Result<()> {
-    let config = cli_config().core_config().apply_args(args);
+/// Run the pre-update hook, the update is aborted if it fails
+fn pre_update(config: &Config, old: &str, new: &str) -> Result<()> {
+    run_update_hook(config.pre_update_hook(), old, new)
+        .context("Pre-update hook failed, the update is aborted")
+}
+
+/// Run the post-update hook, failures are only logged as the update is already applied
+fn post_update(config: &Config, old: &str, new: &str) {
+    if let Err(err) = run_update_hook(config.post_update_hook(), old, new) {
+        warn!("Post-update hook failed: {err:#}");
+    }
+}
+
+/// Update MaaCore, return whether it is updated.
+///
+/// The update hooks are only run if an update is applied.
+pub fn update(args: &CommonArgs, hooks: &HookArgs) -> Result<bool> {
+    let config = update_config(args).apply_hooks(hooks);
+    http::allow_insecure_redirect(args.allow_insecure_redirect);
 
     let components = config.components();
     // Check if any component is specified
@@ -146,31 +250,288 @@ pub fn update(args: &CommonArgs) -> Result<()> {
         ),
         _ => {}
     }
+    // Get the installed version before any network access, so a broken installation
+    // is reported without waiting for the version info
+    let current_version = version()?;
 
     println!(
         "Fetching MaaCore version info (channel: {})...",
         config.channel()
     );
     let version_json = get_version_json(&config)?;
-    let asset_version = version_json.version();
-    let current_version = version()?;
-    if !version_json.can_update("MaaCore", &current_version)? {
-        return Ok(());
+    let remote_version = version_json.version();
+    let installed_channel = manifest::installed(manifest::MAA_CORE).and_then(|e| e.channel());
+    match decide(
+        remote_version,
+        &current_version,
+        installed_channel,
+        config.channel(),
+    ) {
+        Decision::UpToDate => {
+            println!("Up to date: MaaCore v{current_version}.");
+            return Ok(false);
+        }
+        Decision::Update => {
+            println!("Found newer MaaCore version: v{remote_version} (current: v{current_version})")
+        }
+        Decision::Downgrade => {
+            let prompt = format!(
+                "downgrade MaaCore from v{current_version} to v{remote_version} of {} channel",
+                config.channel()
+            );
+            if !BoolInput::new(Some(false), Some(&prompt)).value()? {
+                println!("Keep MaaCore v{current_version}.");
+                return Ok(false);
+            }
+        }
     }
+
+    let (old, new) = (current_version.to_string(), remote_version.to_string());
+    pre_update(&config, &old, &new)?;
+    install_package(
+        &config,
+        &version_json,
+        Some(config.channel()),
+        args.no_space_check,
+    )?;
+    post_update(&config, &old, &new);
+
+    Ok(true)
+}
+
+/// Update resources only, from the resource package in the version json.
+///
+/// The version of installed resources is recorded after each update,
+/// and the package is downloaded only if the remote version is different.
+/// Return whether the resources are updated, the update hooks are only run if they are.
+pub fn update_resource(args: &CommonArgs, hooks: &HookArgs) -> Result<bool> {
+    let config = update_config(args).apply_hooks(hooks);
+    http::allow_insecure_redirect(args.allow_insecure_redirect);
+
+    let resource_dir = dirs::resource();
+    match dirs::find_resource() {
+        Some(dir) if dir != resource_dir => bail!(
+            "MaaCore resource found at {} but not installed by maa, aborting",
+            dir.display()
+        ),
+        Some(_) => {}
+        None => bail!("MaaCore resource not found, use `maa install` to install it first"),
+    }
+
+    println!(
+        "Fetching MaaCore version info (channel: {})...",
+        config.channel()
+    );
+    let version_json = get_version_json(&config)?;
+    let resource = version_json.details().resource().context(
+        "No resource package in the version info, use `maa update` to update MaaCore and resources",
+    )?;
+
+    let current_version = manifest::installed(manifest::RESOURCE);
+    let current_version = current_version.as_ref().map(manifest::Entry::version);
+    if current_version == Some(resource.version()) {
+        println!("Up to date: MaaCore resource {}.", resource.version());
+        return Ok(false);
+    }
+    println!(
+        "Found newer MaaCore resource version: {} (current: {})",
+        resource.version(),
+        current_version.unwrap_or("unknown")
+    );
+    let old = current_version.unwrap_or("unknown").to_owned();
+    pre_update(&config, &old, resource.version())?;
+
+    let asset = resource.asset();
+    let cache_dir = dirs::cache().ensure()?;
+    if !args.no_space_check {
+        disk_space::check(&[
+            Requirement::new(&cache_dir.join(asset.name()), asset.size()),
+            Requirement::new(resource_dir, extracted_size(asset.size())),
+        ])?;
+    }
+
+    println!("Downloading MaaCore resource {}...", resource.version());
+    let archive = asset.download(cache_dir, &config)?;
+
+    println!("Installing MaaCore resource...");
+    let files = install_resource(&archive, resource_dir, &staging_dir())?;
+    manifest::record(
+        manifest::RESOURCE,
+        resource.version(),
+        &asset.browser_download_url,
+        integrity::hash_files(files)?,
+        Some(config.channel()),
+    )?;
+    post_update(&config, &old, resource.version());
+
+    Ok(true)
+}
+
+/// Result of the automatic update before running tasks
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub enum AutoUpdated {
+    Updated,
+    UpToDate,
+    /// The update server is not reachable within [`AUTO_UPDATE_BUDGET`]
+    Offline,
+    Failed,
+}
+
+impl std::fmt::Display for AutoUpdated {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Updated => "updated",
+            Self::UpToDate => "up to date",
+            Self::Offline => "skipped, update server not reachable",
+            Self::Failed => "failed",
+        })
+    }
+}
+
+/// Time to wait for the update server before skipping the automatic update,
+/// so tasks on an offline machine are not blocked
+const AUTO_UPDATE_BUDGET: Duration = Duration::from_secs(5);
+
+/// Update components set by `core.auto_update` before running tasks.
+///
+/// Return `None` if automatic update is disabled. Failures are reported as warnings,
+/// so tasks still run with current MaaCore and resources, unless `core.require_fresh`
+/// is enabled, in which case an error is returned.
+pub fn auto_update() -> Result<Option<AutoUpdated>> {
+    let config = cli_config().core_config();
+    let mode = config.auto_update();
+    if mode == AutoUpdate::Never {
+        return Ok(None);
+    }
+
+    let args = CommonArgs::default();
+    let updated = auto_update_with(
+        mode,
+        config.require_fresh(),
+        || reachable(&update_config(&args), AUTO_UPDATE_BUDGET),
+        || {
+            let updated = update(&args, &HookArgs::default())?;
+            // MaaCore may be loaded to get its version, the updated one is loaded later
+            if updated && maa_sys::binding::loaded() {
+                maa_sys::binding::unload();
+            }
+            Ok(updated)
+        },
+        || update_resource(&args, &HookArgs::default()),
+    )?;
+    Ok(Some(updated))
+}
+
+fn auto_update_with(
+    mode: AutoUpdate,
+    require_fresh: bool,
+    reachable: impl FnOnce() -> bool,
+    update_core: impl FnOnce() -> Result<bool>,
+    update_resource: impl FnOnce() -> Result<bool>,
+) -> Result<AutoUpdated> {
+    if !reachable() {
+        if require_fresh {
+            bail!("Update server is not reachable, and `core.require_fresh` is enabled");
+        }
+        warn!("Update server is not reachable, skip updating {mode}");
+        return Ok(AutoUpdated::Offline);
+    }
+
+    let updated = match mode {
+        AutoUpdate::Never => Ok(false),
+        AutoUpdate::Resource => update_resource(),
+        AutoUpdate::Core => update_core(),
+        AutoUpdate::All => update_core().and_then(|core| Ok(update_resource()? || core)),
+    };
+    match updated {
+        Ok(true) => Ok(AutoUpdated::Updated),
+        Ok(false) => Ok(AutoUpdated::UpToDate),
+        Err(err) if require_fresh => Err(err.context(format!("Failed to update {mode}"))),
+        Err(err) => {
+            warn!("Failed to update {mode}: {err:#}");
+            Ok(AutoUpdated::Failed)
+        }
+    }
+}
+
+/// Check whether the version info can be fetched within `budget`.
+fn reachable(config: &Config, budget: Duration) -> bool {
+    let url = config.api_url();
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        // local mirrors are always reachable, errors are reported when reading them
+        return true;
+    }
+    let result = http::blocking_builder(config.ip_family())
+        .timeout(budget)
+        .build()
+        .and_then(|client| client.get(&url).send())
+        .and_then(|resp| resp.error_for_status());
+    if let Err(err) = &result {
+        debug!("Failed to fetch {url}: {err}");
+    }
+    result.is_ok()
+}
+
+/// Replace the resource directory with the `resource` tree in the archive.
+///
+/// The tree is staged before cleaning the old resources, so a failed extraction
+/// never leaves resources of different versions mixed. Return the installed files.
+fn install_resource(
+    archive: &Archive,
+    resource_dir: &Path,
+    staging_dir: &Path,
+) -> Result<Vec<PathBuf>> {
+    let patterns = [(Pattern::new(RESOURCE_PATTERN)?, resource_dir.to_path_buf())];
+    if archive.extract_matching(&patterns, true)?.is_empty() {
+        bail!("No resource found in the package");
+    }
+
+    let [(pattern, _)] = &patterns;
+    let staged = archive.stage(
+        |path| relative_path(pattern, path).map(|path| resource_dir.join(path)),
+        staging_dir,
+    )?;
+    debug!("Cleaning resource directory");
+    resource_dir.ensure_clean()?;
+    let files = staged.commit()?.into_iter().map(|(_, dest)| dest).collect();
+
+    Ok(files)
+}
+
+/// Pattern of files in the resource tree of a package
+const RESOURCE_PATTERN: &str = "**/resource/**";
+
+/// Download the package of current platform in the version json and install it.
+///
+/// The package is extracted into the staging directory before cleaning the old files,
+/// so a failed extraction will not leave a broken installation.
+fn install_package(
+    config: &Config,
+    version_json: &VersionJSON<Details>,
+    channel: Option<Channel>,
+    no_space_check: bool,
+) -> Result<()> {
+    let components = config.components();
+    let asset_version = version_json.version();
     let asset_name = name(asset_version)?;
     let asset = version_json.details().asset(&asset_name)?;
 
-    println!("Downloading MaaCore {}...", asset_version);
     let cache_dir = dirs::cache().ensure()?;
     let asset_path = cache_dir.join(asset_name);
-    let archive = download(
-        asset_path.into(),
-        asset.size(),
-        asset.download_links(),
-        &config,
-    )?;
+    if !no_space_check {
+        check_space(&asset_path, asset.size(), components)?;
+    }
+
+    println!("Downloading MaaCore {}...", asset_version);
+    let archive = asset.download(cache_dir, config)?;
 
     println!("Installing MaaCore...");
+    let lib_dir = dirs::library();
+    let resource_dir = dirs::resource();
+    let staged = archive.stage(
+        |path| extract_mapper(path, lib_dir, resource_dir, components),
+        &staging_dir(),
+    )?;
     if components.library {
         debug!("Cleaning library directory");
         lib_dir.ensure_clean()?;
@@ -179,19 +540,79 @@ pub fn update(args: &CommonArgs) -> Result<()> {
         debug!("Cleaning resource directory");
         resource_dir.ensure_clean()?;
     }
-    archive.extract(|path| extract_mapper(path, lib_dir, resource_dir, components))?;
+    let (resource_files, lib_files) = staged
+        .commit()?
+        .into_iter()
+        .map(|(_, dest)| dest)
+        .partition(|dest| dest.starts_with(resource_dir));
+
+    let version = asset_version.to_string();
+    let source = &asset.browser_download_url;
+    if components.library {
+        let files = integrity::hash_files(lib_files)?;
+        manifest::record(manifest::MAA_CORE, &version, source, files, channel)?;
+    }
+    if components.resource {
+        let files = integrity::hash_files(resource_files)?;
+        manifest::record(manifest::RESOURCE, &version, source, files, channel)?;
+    }
 
     Ok(())
 }
 
-fn get_version_json(config: &Config) -> Result<VersionJSON<Details>> {
-    let url = config.api_url();
-    let version_json = reqwest::blocking::get(&url)
-        .with_context(|| format!("Failed to fetch version info from {}", url))?
-        .json()
-        .with_context(|| "Failed to parse version info")?;
+/// Get the version info of a pinned version.
+///
+/// The version json of the channel is used if it is the pinned version, since it includes
+/// the mirrors. Otherwise, the release of the version is fetched from GitHub releases API.
+fn fetch_pinned(
+    channel_json: Result<VersionJSON<Details>>,
+    client: &reqwest::blocking::Client,
+    releases_api: &str,
+    pinned: &Version,
+) -> Result<VersionJSON<Details>> {
+    match channel_json {
+        Ok(version_json) if version_json.version() == pinned => return Ok(version_json),
+        Ok(version_json) => debug!(
+            "The version of the channel is v{}, fetching v{pinned} from GitHub releases",
+            version_json.version()
+        ),
+        Err(err) => debug!("{err:#}, fetching v{pinned} from GitHub releases"),
+    }
+
+    let release = github::fetch_release_by_tag(client, releases_api, &format!("v{pinned}"))
+        .with_context(|| format!("Failed to find MaaCore v{pinned}"))?;
+    Ok(VersionJSON::new(
+        pinned.clone(),
+        Details::from_release(&release),
+    ))
+}
+
+/// Name of the directory in cache to extract the package into before moving files into place.
+pub(super) const STAGING_DIR: &str = "maa-core-staging";
+
+fn staging_dir() -> PathBuf {
+    dirs::cache().join(STAGING_DIR)
+}
 
-    Ok(version_json)
+/// Check whether there is enough space to download and extract the package.
+///
+/// Most of the extracted size is taken by resources, so the whole estimated size
+/// is required at the resource directory if resources are installed.
+fn check_space(asset_path: &Path, size: u64, components: &Components) -> Result<()> {
+    let mut requirements = Vec::new();
+    if !check_file_exists(asset_path, size) {
+        requirements.push(Requirement::new(asset_path, size));
+    }
+    if components.resource {
+        requirements.push(Requirement::new(dirs::resource(), extracted_size(size)));
+    } else if components.library {
+        requirements.push(Requirement::new(dirs::library(), extracted_size(size)));
+    }
+    disk_space::check(&requirements)
+}
+
+fn get_version_json(config: &Config) -> Result<VersionJSON<Details>> {
+    version_json::fetch(&config.api_url(), config.ip_family())
 }
 
 /// Get the name of the asset for the current platform
@@ -212,17 +633,52 @@ pub fn name(version: &Version) -> Result<String> {
     }
 }
 
+#[cfg_attr(test, derive(Debug))]
 #[derive(Deserialize)]
 pub struct Details {
     assets: Vec<Asset>,
+    /// Package of resources only, which may be updated without MaaCore
+    #[serde(default)]
+    resource: Option<ResourceAsset>,
 }
 
 impl Details {
+    /// Details of a release from GitHub releases API, which has no mirrors.
+    fn from_release(release: &github::Release) -> Self {
+        Self {
+            assets: release.assets().iter().map(Asset::from).collect(),
+            resource: None,
+        }
+    }
+
     pub fn asset(&self, name: &str) -> Result<&Asset> {
         self.assets
             .iter()
             .find(|asset| name == asset.name())
-            .ok_or_else(|| anyhow!("Asset not found"))
+            .ok_or_else(|| anyhow!("Asset {name} not found"))
+    }
+
+    pub fn resource(&self) -> Option<&ResourceAsset> {
+        self.resource.as_ref()
+    }
+}
+
+#[cfg_attr(test, derive(Debug, PartialEq))]
+#[derive(Deserialize)]
+pub struct ResourceAsset {
+    /// Version of resources, e.g. a date or a commit of the resource repository
+    version: String,
+    #[serde(flatten)]
+    asset: Asset,
+}
+
+impl ResourceAsset {
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    pub fn asset(&self) -> &Asset {
+        &self.asset
     }
 }
 
@@ -232,7 +688,23 @@ pub struct Asset {
     name: String,
     size: u64,
     browser_download_url: String,
+    #[serde(default)]
     mirrors: Vec<String>,
+    /// SHA-256 checksum of the asset, the download is not verified if not given
+    #[serde(default)]
+    sha256sum: Option<String>,
+}
+
+impl From<&github::ReleaseAsset> for Asset {
+    fn from(asset: &github::ReleaseAsset) -> Self {
+        Self {
+            name: asset.name().to_owned(),
+            size: asset.size(),
+            browser_download_url: asset.download_url().to_owned(),
+            mirrors: Vec::new(),
+            sha256sum: asset.sha256sum().map(str::to_owned),
+        }
+    }
 }
 
 impl Asset {
@@ -249,44 +721,248 @@ impl Asset {
         links.insert(0, self.browser_download_url.clone());
         links
     }
-}
 
-pub fn download<'p>(
-    path: Cow<'p, Path>,
-    size: u64,
-    links: Vec<String>,
-    config: &Config,
-) -> Result<Archive<'p>> {
-    if check_file_exists(&path, size) {
-        println!("Already downloaded, skip downloading");
-        return Archive::new(path);
+    /// Download the asset into given directory, retrying on transient errors.
+    ///
+    /// See [`Asset::download_with_retry`], with [`DEFAULT_RETRIES`] and [`DEFAULT_RETRY_DELAY`].
+    pub fn download(&self, dir: &Path, config: &Config) -> Result<Archive<'static>> {
+        self.download_with_retry(dir, config, DEFAULT_RETRIES, DEFAULT_RETRY_DELAY)
     }
 
-    let client = reqwest::Client::builder()
-        .connect_timeout(Duration::from_secs(3))
-        .build()
-        .context("Failed to build reqwest client")?;
-    Runtime::new()
-        .context("Failed to create tokio runtime")?
-        .block_on(download_mirrors(
-            &client,
-            links,
-            &path,
-            size,
-            config.test_time(),
-            None,
-        ))
+    /// Download the asset into given directory, the file is reused if it is already downloaded.
+    ///
+    /// Recoverable errors, see [`download::Error::is_recoverable`], are retried at most
+    /// `max_retries` times, the delay starts from `retry_delay` and doubles for each retry.
+    pub fn download_with_retry(
+        &self,
+        dir: &Path,
+        config: &Config,
+        max_retries: u32,
+        retry_delay: Duration,
+    ) -> Result<Archive<'static>> {
+        let path = dir.join(self.name());
+        if check_file_exists(&path, self.size) {
+            println!("Already downloaded, skip downloading");
+            return Archive::new(path.into());
+        }
+
+        let client = http::client(config.ip_family(), Duration::from_secs(3))?;
+        let throttle = config.limit_rate().map(Throttle::new);
+        let checkers: Vec<_> = self
+            .sha256sum
+            .as_deref()
+            .map(Checker::Sha256)
+            .into_iter()
+            .collect();
+        retry(max_retries, retry_delay, || {
+            block_on(download_mirrors(
+                &client,
+                self.download_links(),
+                &path,
+                self.size,
+                config.test_time(),
+                &checkers,
+                throttle.as_ref(),
+            ))?
+        })
+        .map_err(|err| http::with_family_hint(err.into(), config.ip_family()))
         .context("Failed to download asset")?;
 
-    Archive::new(path)
+        Archive::new(path.into())
+    }
+}
+
+/// Default number of retries of downloading an asset
+pub const DEFAULT_RETRIES: u32 = 3;
+
+/// Default delay before the first retry, doubled for each retry
+pub const DEFAULT_RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// Call `f` until it succeeds, fails with an unrecoverable error, or `max_retries` is reached.
+fn retry<T>(
+    max_retries: u32,
+    retry_delay: Duration,
+    mut f: impl FnMut() -> std::result::Result<T, download::Error>,
+) -> std::result::Result<T, download::Error> {
+    let mut delay = retry_delay;
+    for attempt in 1.. {
+        match f() {
+            Err(err) if attempt <= max_retries && err.is_recoverable() => {
+                println!(
+                    "Retry {attempt}/{max_retries} in {}s: {err}",
+                    delay.as_secs_f64()
+                );
+                std::thread::sleep(delay);
+                delay = delay.saturating_mul(2);
+            }
+            result => return result,
+        }
+    }
+    unreachable!("the loop only ends by returning")
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    use crate::installer::test_server::{client, Response, Server};
+
     use serde_json;
 
+    #[test]
+    #[cfg(unix)]
+    fn update_hooks() {
+        let hooks = |pre: &str, post: &str| HookArgs {
+            pre_update_hook: Some(pre.to_owned()),
+            post_update_hook: Some(post.to_owned()),
+        };
+
+        let config = Config::default();
+        pre_update(&config, "5.0.0", "5.1.0").unwrap();
+        post_update(&config, "5.0.0", "5.1.0");
+
+        let config = Config::default().apply_hooks(&hooks(
+            r#"test "$MAA_OLD_VERSION" = 5.0.0 && test "$MAA_NEW_VERSION" = 5.1.0"#,
+            "exit 1",
+        ));
+        pre_update(&config, "5.0.0", "5.1.0").unwrap();
+        assert!(pre_update(&config, "5.0.0", "5.2.0").is_err());
+        // failures of the post-update hook are only logged
+        post_update(&config, "5.0.0", "5.1.0");
+    }
+
+    #[test]
+    fn auto_update_decision() {
+        use std::cell::RefCell;
+        use AutoUpdated::*;
+
+        // Run with stubbed updaters, return the result and the updaters called
+        fn run(
+            mode: AutoUpdate,
+            require_fresh: bool,
+            reachable: bool,
+            core: fn() -> Result<bool>,
+            resource: fn() -> Result<bool>,
+        ) -> (Result<AutoUpdated>, Vec<&'static str>) {
+            let called = RefCell::new(Vec::new());
+            let result = auto_update_with(
+                mode,
+                require_fresh,
+                || reachable,
+                || {
+                    called.borrow_mut().push("core");
+                    core()
+                },
+                || {
+                    called.borrow_mut().push("resource");
+                    resource()
+                },
+            );
+            (result, called.into_inner())
+        }
+        let updated = || Ok(true);
+        let up_to_date = || Ok(false);
+        let failed = || bail!("failed");
+
+        for require_fresh in [false, true] {
+            let (result, called) = run(AutoUpdate::Resource, require_fresh, true, failed, updated);
+            assert_eq!(result.unwrap(), Updated);
+            assert_eq!(called, ["resource"]);
+
+            let (result, called) = run(AutoUpdate::Core, require_fresh, true, up_to_date, failed);
+            assert_eq!(result.unwrap(), UpToDate);
+            assert_eq!(called, ["core"]);
+
+            let (result, called) = run(AutoUpdate::All, require_fresh, true, up_to_date, updated);
+            assert_eq!(result.unwrap(), Updated);
+            assert_eq!(called, ["core", "resource"]);
+
+            let (result, called) = run(AutoUpdate::All, require_fresh, true, updated, up_to_date);
+            assert_eq!(result.unwrap(), Updated);
+            assert_eq!(called, ["core", "resource"]);
+
+            let (result, _) = run(AutoUpdate::All, require_fresh, true, up_to_date, up_to_date);
+            assert_eq!(result.unwrap(), UpToDate);
+        }
+
+        // an offline machine is not blocked unless fresh components are required
+        let (result, called) = run(AutoUpdate::All, false, false, updated, updated);
+        assert_eq!(result.unwrap(), Offline);
+        assert!(called.is_empty());
+        let (result, called) = run(AutoUpdate::All, true, false, updated, updated);
+        assert!(result.is_err());
+        assert!(called.is_empty());
+
+        // failures are reported as warnings unless fresh components are required
+        let (result, called) = run(AutoUpdate::Resource, false, true, updated, failed);
+        assert_eq!(result.unwrap(), Failed);
+        assert_eq!(called, ["resource"]);
+        let (result, _) = run(AutoUpdate::Resource, true, true, updated, failed);
+        assert!(result.is_err());
+
+        // resources are not updated if updating MaaCore failed
+        let (result, called) = run(AutoUpdate::All, false, true, failed, updated);
+        assert_eq!(result.unwrap(), Failed);
+        assert_eq!(called, ["core"]);
+        let (result, _) = run(AutoUpdate::All, true, true, failed, updated);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn update_decision() {
+        use Channel::*;
+        use Decision::*;
+
+        fn decide_on(
+            remote: &str,
+            current: &str,
+            installed: Option<Channel>,
+            channel: Channel,
+            expected: Decision,
+        ) {
+            assert_eq!(
+                decide(
+                    &Version::parse(remote).unwrap(),
+                    &Version::parse(current).unwrap(),
+                    installed,
+                    channel,
+                ),
+                expected,
+                "remote: {remote}, current: {current}, installed: {installed:?}, channel: {channel:?}"
+            );
+        }
+
+        // stay on the same channel
+        decide_on("5.0.1", "5.0.0", Some(Stable), Stable, Update);
+        decide_on("5.0.0", "5.0.0", Some(Stable), Stable, UpToDate);
+        decide_on("5.0.0", "5.0.1", Some(Stable), Stable, UpToDate);
+        decide_on("5.1.0-beta.2", "5.1.0-beta.1", Some(Beta), Beta, Update);
+        decide_on("5.1.0", "5.1.0-beta.2", Some(Beta), Beta, Update);
+        decide_on("5.1.0-beta.1", "5.1.0-beta.2", Some(Beta), Beta, UpToDate);
+        decide_on("5.1.0-alpha.3", "5.1.0-alpha.2", Some(Alpha), Alpha, Update);
+
+        // switch to a less stable channel
+        decide_on("5.1.0-beta.1", "5.0.0", Some(Stable), Beta, Update);
+        decide_on("5.0.0", "5.0.0", Some(Stable), Beta, UpToDate);
+
+        // switch back to stable when the installed prerelease is newer
+        decide_on("5.0.0", "5.1.0-beta.1", Some(Beta), Stable, Downgrade);
+        decide_on("5.1.0", "5.1.0-beta.1", Some(Beta), Stable, Update);
+        decide_on(
+            "5.0.0-beta.3",
+            "5.1.0-alpha.1",
+            Some(Alpha),
+            Beta,
+            Downgrade,
+        );
+
+        // installed channel unknown, e.g. pinned version or recorded by older versions
+        decide_on("5.0.0", "5.1.0-beta.1", None, Stable, Downgrade);
+        decide_on("5.0.0", "5.1.0-beta.1", None, Beta, UpToDate);
+        decide_on("5.0.0", "5.0.1", None, Stable, UpToDate);
+        decide_on("5.0.1", "5.0.0", None, Stable, Update);
+    }
+
     #[test]
     fn deserialize_version_json() {
         // This is a stripped version of the real json
@@ -361,15 +1037,18 @@ mod tests {
         let version_json: VersionJSON<Details> =
             serde_json::from_str(json_str).expect("Failed to parse json");
 
-        assert!(version_json
-            .can_update("MaaCore", &Version::parse("4.26.0").unwrap())
-            .unwrap());
-        assert!(version_json
-            .can_update("MaaCore", &Version::parse("4.26.1-beta.1").unwrap())
-            .unwrap());
-        assert!(!version_json
-            .can_update("MaaCore", &Version::parse("4.27.0").unwrap())
-            .unwrap());
+        #[cfg(feature = "cli_installer")]
+        {
+            assert!(version_json
+                .can_update("MaaCore", &Version::parse("4.26.0").unwrap())
+                .unwrap());
+            assert!(version_json
+                .can_update("MaaCore", &Version::parse("4.26.1-beta.1").unwrap())
+                .unwrap());
+            assert!(!version_json
+                .can_update("MaaCore", &Version::parse("4.27.0").unwrap())
+                .unwrap());
+        }
 
         assert_eq!(
             version_json.version(),
@@ -417,6 +1096,211 @@ mod tests {
         }
     }
 
+    /// Version json of given version with the asset of current platform, which has a mirror
+    fn channel_json(version: &str) -> VersionJSON<Details> {
+        let version = Version::parse(version).unwrap();
+        let name = name(&version).unwrap();
+        serde_json::from_value(serde_json::json!({
+            "version": format!("v{version}"),
+            "details": {
+                "assets": [{
+                    "name": name,
+                    "size": 100,
+                    "browser_download_url": format!("https://github.com/{name}"),
+                    "mirrors": [format!("https://mirror.example.com/{name}")],
+                }]
+            }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn pinned_version() {
+        let Ok(name) = name(&Version::new(4, 26, 0)) else {
+            return;
+        };
+        let pinned = Version::new(4, 26, 0);
+
+        // the channel is at the pinned version, no need to query GitHub
+        let unused = Server::new([Response::status(404)]);
+        let version_json = fetch_pinned(
+            Ok(channel_json("4.26.0")),
+            &client_blocking(),
+            &unused.url("releases"),
+            &pinned,
+        )
+        .unwrap();
+        assert_eq!(version_json.version(), &pinned);
+        let asset = version_json.details().asset(&name).unwrap();
+        assert_eq!(asset.download_links().len(), 2);
+        assert!(unused.requests().is_empty());
+
+        // the channel is at another version or unreachable, fetch the release of the tag
+        let release = serde_json::json!({
+            "tag_name": "v4.26.0",
+            "name": "v4.26.0",
+            "assets": [
+                {
+                    "name": name,
+                    "size": 123,
+                    "browser_download_url": "https://github.com/release/asset",
+                    "digest": "sha256:abcdef",
+                },
+                {
+                    "name": "MAA-v4.26.0-unknown.zip",
+                    "size": 1,
+                    "browser_download_url": "https://github.com/release/other",
+                },
+            ],
+        })
+        .to_string();
+        for channel in [Ok(channel_json("4.26.1")), Err(anyhow!("unreachable"))] {
+            let server = Server::new([Response::ok(release.clone())]);
+            let version_json = fetch_pinned(
+                channel,
+                &client_blocking(),
+                &server.url("releases"),
+                &pinned,
+            )
+            .unwrap();
+            assert!(server.requests()[0].starts_with("GET /releases/tags/v4.26.0 "));
+            assert_eq!(version_json.version(), &pinned);
+
+            let asset = version_json.details().asset(&name).unwrap();
+            assert_eq!(asset.size(), 123);
+            assert_eq!(asset.download_links(), ["https://github.com/release/asset"]);
+            assert_eq!(asset.sha256sum.as_deref(), Some("abcdef"));
+            assert!(version_json.details().asset("missing.zip").is_err());
+        }
+
+        // no such release
+        let server = Server::new([Response::status(404)]);
+        let err = fetch_pinned(
+            Ok(channel_json("4.26.1")),
+            &client_blocking(),
+            &server.url("releases"),
+            &pinned,
+        )
+        .unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("Failed to find MaaCore v4.26.0"));
+    }
+
+    #[test]
+    fn resource_asset() {
+        let version_json = channel_json("4.26.0");
+        assert!(version_json.details().resource().is_none());
+
+        let version_json: VersionJSON<Details> = serde_json::from_value(serde_json::json!({
+            "version": "v4.26.0",
+            "details": {
+                "assets": [],
+                "resource": {
+                    "version": "2024-05-25",
+                    "name": "MAA-resource.tar.gz",
+                    "size": 100,
+                    "browser_download_url": "https://github.com/MAA-resource.tar.gz",
+                    "sha256sum": "abcdef",
+                },
+            }
+        }))
+        .unwrap();
+        let resource = version_json.details().resource().unwrap();
+        assert_eq!(resource.version(), "2024-05-25");
+        assert_eq!(resource.asset().name(), "MAA-resource.tar.gz");
+        assert_eq!(resource.asset().size(), 100);
+        assert_eq!(resource.asset().sha256sum.as_deref(), Some("abcdef"));
+    }
+
+    #[test]
+    fn resource_only() {
+        let dir = std::env::temp_dir().join("maa-test-resource-only");
+        let resource_dir = dir.join("resource");
+        let staging_dir = dir.join("staging");
+        dir.as_path().ensure_clean().unwrap();
+
+        let create_tar_gz = |name: &str, files: &[(&str, &str)]| {
+            let path = dir.join(name);
+            let encoder = flate2::write::GzEncoder::new(
+                std::fs::File::create(&path).unwrap(),
+                Default::default(),
+            );
+            let mut tar = tar::Builder::new(encoder);
+            for (name, content) in files {
+                let mut header = tar::Header::new_gnu();
+                header.set_mode(0o644);
+                header.set_size(content.len() as u64);
+                tar.append_data(&mut header, name, content.as_bytes())
+                    .unwrap();
+            }
+            tar.into_inner().unwrap().finish().unwrap();
+            Archive::new(path.into()).unwrap()
+        };
+
+        resource_dir.ensure().unwrap();
+        std::fs::write(resource_dir.join("old.json"), "old").unwrap();
+
+        // only the resource tree is installed, and old resources are removed
+        let archive = create_tar_gz(
+            "resource.tar.gz",
+            &[
+                ("MAA-v5/resource/tasks/a.json", "new"),
+                ("MAA-v5/resource/config.json", "{}"),
+                ("MAA-v5/libMaaCore.so", "lib"),
+            ],
+        );
+        let files = install_resource(&archive, &resource_dir, &staging_dir).unwrap();
+        assert_eq!(files.len(), 2);
+        assert!(files.contains(&resource_dir.join("tasks/a.json")));
+        assert_eq!(
+            std::fs::read_to_string(resource_dir.join("tasks/a.json")).unwrap(),
+            "new"
+        );
+        assert!(resource_dir.join("config.json").exists());
+        assert!(!resource_dir.join("old.json").exists());
+        assert!(!dir.join("libMaaCore.so").exists());
+        assert!(!staging_dir.exists());
+
+        // a package without resources or a broken package leaves resources untouched
+        let archive = create_tar_gz("empty.tar.gz", &[("MAA-v5/libMaaCore.so", "lib")]);
+        assert!(install_resource(&archive, &resource_dir, &staging_dir).is_err());
+        let broken = dir.join("broken.tar.gz");
+        let bytes = std::fs::read(dir.join("resource.tar.gz")).unwrap();
+        std::fs::write(&broken, &bytes[..bytes.len() / 2]).unwrap();
+        let archive = Archive::new(broken.into()).unwrap();
+        assert!(install_resource(&archive, &resource_dir, &staging_dir).is_err());
+        assert_eq!(
+            std::fs::read_to_string(resource_dir.join("tasks/a.json")).unwrap(),
+            "new"
+        );
+        assert!(resource_dir.join("config.json").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "cli_installer")]
+    #[test]
+    fn up_to_date() {
+        let version_json = channel_json("4.26.0");
+        let v = |s: &str| Version::parse(s).unwrap();
+        assert!(version_json.can_update("MaaCore", &v("4.25.9")).unwrap());
+        assert!(version_json
+            .can_update("MaaCore", &v("4.26.0-beta.3"))
+            .unwrap());
+        assert!(!version_json.can_update("MaaCore", &v("4.26.0")).unwrap());
+        assert!(!version_json
+            .can_update("MaaCore", &v("4.27.0-alpha.1"))
+            .unwrap());
+    }
+
+    fn client_blocking() -> reqwest::blocking::Client {
+        reqwest::blocking::Client::builder()
+            .no_proxy()
+            .build()
+            .unwrap()
+    }
+
     #[test]
     fn test_extract_mapper() {
         let config = Components::default();
@@ -445,4 +1329,54 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn retry_download() {
+        let server = Server::new([
+            Response::status(503),
+            Response::status(429),
+            Response::ok("content"),
+            Response::status(404),
+        ]);
+        let path = std::env::temp_dir().join("maa-test-retry-download-asset");
+        let download = || {
+            crate::installer::block_on(download::download(
+                &client(),
+                &server.url("asset"),
+                &path,
+                7,
+                &[],
+                None,
+                None,
+            ))?
+        };
+
+        retry(3, Duration::from_millis(1), download).unwrap();
+        assert_eq!(server.requests().len(), 3);
+        assert_eq!(std::fs::read(&path).unwrap(), b"content");
+
+        // not recoverable
+        let err = retry(3, Duration::from_millis(1), download).unwrap_err();
+        assert!(!err.is_recoverable());
+        assert_eq!(server.requests().len(), 4);
+
+        // give up after max retries
+        let server = Server::new([Response::status(500)]);
+        let err = retry(2, Duration::from_millis(1), || {
+            crate::installer::block_on(download::download(
+                &client(),
+                &server.url("asset"),
+                &path,
+                7,
+                &[],
+                None,
+                None,
+            ))?
+        })
+        .unwrap_err();
+        assert!(err.is_recoverable());
+        assert_eq!(server.requests().len(), 3);
+
+        std::fs::remove_file(&path).unwrap();
+    }
 }
@@ -0,0 +1,300 @@
+//! Manifest of components installed by maa-cli.
+//!
+//! The installers record the version, the source and the files of each installed component,
+//! so versions can be reported without loading the library or accessing the network,
+//! and components can be uninstalled without touching other files.
+
+use crate::{
+    config::cli::Channel,
+    dirs::{self, Ensure},
+};
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Component name of the MaaCore library
+pub const MAA_CORE: &str = "MaaCore";
+
+/// Component name of the MaaCore resources
+pub const RESOURCE: &str = "resource";
+
+#[cfg_attr(test, derive(Debug, PartialEq))]
+#[derive(Deserialize, Serialize, Default)]
+#[serde(transparent)]
+pub struct Manifest(std::collections::BTreeMap<String, Entry>);
+
+#[cfg_attr(test, derive(Debug, PartialEq))]
+#[derive(Deserialize, Serialize, Clone)]
+pub struct Entry {
+    version: String,
+    /// URL of the package the component is installed from
+    source: String,
+    installed_at: DateTime<Utc>,
+    /// Files installed by the installer
+    #[serde(default)]
+    files: Vec<PathBuf>,
+    /// Sha256 checksums of installed files, used to verify the installation
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    hashes: BTreeMap<PathBuf, String>,
+    /// Channel the component is installed from, `None` if a pinned version is installed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    channel: Option<String>,
+    /// Whether the component is built from source instead of installed from a package
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    built_from_source: bool,
+}
+
+impl Entry {
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    pub fn installed_at(&self) -> DateTime<Utc> {
+        self.installed_at
+    }
+
+    #[cfg_attr(not(feature = "core_installer"), allow(dead_code))]
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    #[cfg_attr(not(feature = "core_installer"), allow(dead_code))]
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    /// Sha256 checksum of an installed file, `None` if it is not recorded
+    #[cfg_attr(not(feature = "core_installer"), allow(dead_code))]
+    pub fn hash(&self, file: &Path) -> Option<&str> {
+        self.hashes.get(file).map(String::as_str)
+    }
+
+    /// File name of the package the component is installed from, i.e. the last segment of
+    /// the source URL, which is also the name of the package in the cache directory.
+    #[cfg_attr(not(feature = "core_installer"), allow(dead_code))]
+    pub fn package_name(&self) -> Option<&str> {
+        self.source
+            .rsplit('/')
+            .next()
+            .filter(|name| !name.is_empty())
+    }
+
+    /// Channel the component is installed from, unknown channels are ignored
+    #[cfg_attr(not(feature = "core_installer"), allow(dead_code))]
+    pub fn channel(&self) -> Option<Channel> {
+        use clap::ValueEnum;
+        Channel::from_str(self.channel.as_deref()?, true).ok()
+    }
+
+    /// Whether the component is built from source, so there is no package to repair it from
+    #[cfg_attr(not(feature = "core_installer"), allow(dead_code))]
+    pub fn built_from_source(&self) -> bool {
+        self.built_from_source
+    }
+
+    #[cfg_attr(not(feature = "core_installer"), allow(dead_code))]
+    pub fn set_built_from_source(&mut self, built_from_source: bool) -> &mut Self {
+        self.built_from_source = built_from_source;
+        self
+    }
+}
+
+impl Manifest {
+    /// Load the manifest, an empty one is returned if it does not exist or is invalid.
+    pub fn load(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    #[cfg_attr(not(feature = "core_installer"), allow(dead_code))]
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            dir.ensure_with_mode(dirs::PRIVATE_DIR_MODE)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    pub fn get(&self, component: &str) -> Option<&Entry> {
+        self.0.get(component)
+    }
+
+    #[cfg_attr(not(feature = "core_installer"), allow(dead_code))]
+    pub fn insert(
+        &mut self,
+        component: &str,
+        version: &str,
+        source: &str,
+        files: Vec<PathBuf>,
+    ) -> &mut Entry {
+        let entry = Entry {
+            version: version.to_owned(),
+            source: source.to_owned(),
+            installed_at: Utc::now(),
+            files,
+            hashes: BTreeMap::new(),
+            channel: None,
+            built_from_source: false,
+        };
+        self.0.insert(component.to_owned(), entry);
+        self.0.get_mut(component).expect("just inserted")
+    }
+
+    /// Insert an entry with the installed files and their sha256 checksums.
+    #[cfg_attr(not(feature = "core_installer"), allow(dead_code))]
+    pub fn insert_hashed(
+        &mut self,
+        component: &str,
+        version: &str,
+        source: &str,
+        files: BTreeMap<PathBuf, String>,
+    ) -> &mut Entry {
+        let entry = self.insert(component, version, source, files.keys().cloned().collect());
+        entry.hashes = files;
+        entry
+    }
+
+    #[cfg_attr(not(feature = "core_installer"), allow(dead_code))]
+    pub fn remove(&mut self, component: &str) -> Option<Entry> {
+        self.0.remove(component)
+    }
+
+    #[cfg(feature = "core_installer")]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Path of the manifest in the state directory.
+pub fn path() -> PathBuf {
+    dirs::state().join("manifest.json")
+}
+
+/// Get the entry of an installed component from the manifest.
+#[cfg_attr(not(feature = "core_installer"), allow(dead_code))]
+pub fn installed(component: &str) -> Option<Entry> {
+    Manifest::load(&path()).get(component).cloned()
+}
+
+/// Record an installed component in the manifest, with the channel it is installed from.
+///
+/// The installed files are given with their sha256 checksums.
+#[cfg_attr(not(feature = "core_installer"), allow(dead_code))]
+pub fn record(
+    component: &str,
+    version: &str,
+    source: &str,
+    files: BTreeMap<PathBuf, String>,
+    channel: Option<Channel>,
+) -> Result<()> {
+    modify(|manifest| {
+        manifest
+            .insert_hashed(component, version, source, files)
+            .channel = channel.map(|channel| channel.to_string());
+    })
+}
+
+/// Record a component built from source in the manifest, `source` is the URL of the source.
+#[cfg_attr(not(feature = "core_installer"), allow(dead_code))]
+pub fn record_source_build(
+    component: &str,
+    version: &str,
+    source: &str,
+    files: BTreeMap<PathBuf, String>,
+    channel: Option<Channel>,
+) -> Result<()> {
+    modify(|manifest| {
+        let entry = manifest.insert_hashed(component, version, source, files);
+        entry.channel = channel.map(|channel| channel.to_string());
+        entry.set_built_from_source(true);
+    })
+}
+
+fn modify(f: impl FnOnce(&mut Manifest)) -> Result<()> {
+    let path = path();
+    let mut manifest = Manifest::load(&path);
+    f(&mut manifest);
+    manifest.save(&path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load_and_save() {
+        let path = std::env::temp_dir()
+            .join("maa-test-manifest")
+            .join("manifest.json");
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(Manifest::load(&path), Manifest::default());
+
+        let mut manifest = Manifest::default();
+        manifest.insert_hashed(
+            MAA_CORE,
+            "5.0.0",
+            "https://example.com/MAA-v5.0.0.tar.gz",
+            BTreeMap::from([("/maa/lib/libMaaCore.so".into(), "abcdef".to_owned())]),
+        );
+        manifest
+            .insert(
+                RESOURCE,
+                "2024-05-25",
+                "https://example.com/resource.tar.gz",
+                Vec::new(),
+            )
+            .channel = Some("beta".to_owned());
+        manifest
+            .insert(
+                "built",
+                "5.0.0",
+                "https://example.com/v5.0.0.tar.gz",
+                Vec::new(),
+            )
+            .set_built_from_source(true);
+        manifest.save(&path).unwrap();
+
+        let loaded = Manifest::load(&path);
+        assert_eq!(loaded, manifest);
+        let entry = loaded.get(MAA_CORE).unwrap();
+        assert_eq!(entry.version(), "5.0.0");
+        assert_eq!(entry.source(), "https://example.com/MAA-v5.0.0.tar.gz");
+        assert!(entry.installed_at() <= Utc::now());
+        assert_eq!(entry.files(), [PathBuf::from("/maa/lib/libMaaCore.so")]);
+        assert_eq!(entry.channel(), None);
+        assert_eq!(
+            entry.hash(Path::new("/maa/lib/libMaaCore.so")),
+            Some("abcdef")
+        );
+        assert_eq!(entry.hash(Path::new("/maa/lib/other.so")), None);
+        assert_eq!(entry.package_name(), Some("MAA-v5.0.0.tar.gz"));
+        assert_eq!(loaded.get(RESOURCE).unwrap().version(), "2024-05-25");
+        assert_eq!(loaded.get(RESOURCE).unwrap().channel(), Some(Channel::Beta));
+        assert!(!entry.built_from_source());
+        assert!(loaded.get("built").unwrap().built_from_source());
+        assert!(loaded.get("missing").is_none());
+
+        // files are optional, e.g. in manifests written by older versions
+        let entry: Entry = serde_json::from_str(
+            r#"{"version": "5.0.0", "source": "", "installed_at": "2024-05-25T08:00:00Z"}"#,
+        )
+        .unwrap();
+        assert!(entry.files().is_empty());
+        assert_eq!(entry.channel(), None);
+        assert_eq!(entry.package_name(), None);
+
+        // an invalid manifest is treated as empty
+        std::fs::write(&path, "not json").unwrap();
+        assert_eq!(Manifest::load(&path), Manifest::default());
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
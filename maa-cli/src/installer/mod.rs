@@ -1,13 +1,84 @@
 #[cfg(feature = "__installer")]
+mod disk_space;
+#[cfg(feature = "__installer")]
 mod download;
 #[cfg(feature = "__installer")]
 mod extract;
 #[cfg(feature = "__installer")]
+mod github;
+#[cfg(feature = "__installer")]
+pub(crate) mod http;
+#[cfg(feature = "__installer")]
 mod version_json;
 
+#[cfg(all(test, feature = "__installer"))]
+pub(crate) mod test_server;
+
+#[cfg(feature = "core_installer")]
+pub mod integrity;
 #[cfg(feature = "cli_installer")]
 pub mod maa_cli;
 #[cfg(feature = "core_installer")]
 pub mod maa_core;
+#[cfg(feature = "cli_installer")]
+mod package_manager;
+#[cfg(feature = "core_installer")]
+mod source_build;
+#[cfg(feature = "__installer")]
+pub mod uninstall;
+#[cfg(feature = "cli_installer")]
+pub mod update_check;
 
+pub mod manifest;
 pub mod resource;
+
+/// Check whether given url is reachable within `timeout`, used by `maa doctor`.
+#[cfg(feature = "core_installer")]
+pub fn check_reachable(
+    url: &str,
+    ip_family: crate::config::cli::IpFamily,
+    timeout: std::time::Duration,
+) -> anyhow::Result<()> {
+    use anyhow::Context;
+
+    let client = http::blocking_builder(ip_family)
+        .timeout(timeout)
+        .build()
+        .context("Failed to create reqwest client")?;
+    client
+        .get(url)
+        .send()
+        .and_then(|response| response.error_for_status())
+        .map_err(|err| http::with_family_hint(err.into(), ip_family))?;
+    Ok(())
+}
+
+/// Run a future to completion on the tokio runtime shared by all installer operations.
+///
+/// The runtime is created lazily on first use, an error is returned if it can not be created.
+/// If called from within a tokio runtime, where blocking on another runtime will panic,
+/// the future is run on a separate thread instead.
+#[cfg(feature = "__installer")]
+fn block_on<F>(future: F) -> std::io::Result<F::Output>
+where
+    F: std::future::Future + Send,
+    F::Output: Send,
+{
+    use std::sync::OnceLock;
+    use tokio::runtime::{Handle, Runtime};
+
+    static RUNTIME: OnceLock<std::io::Result<Runtime>> = OnceLock::new();
+    let runtime = RUNTIME.get_or_init(Runtime::new).as_ref().map_err(|err| {
+        std::io::Error::new(err.kind(), format!("Failed to create tokio runtime: {err}"))
+    })?;
+
+    Ok(if Handle::try_current().is_ok() {
+        std::thread::scope(|s| {
+            s.spawn(|| runtime.block_on(future))
+                .join()
+                .unwrap_or_else(|e| std::panic::resume_unwind(e))
+        })
+    } else {
+        runtime.block_on(future)
+    })
+}
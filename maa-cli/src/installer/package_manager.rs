@@ -0,0 +1,187 @@
+use std::{fmt, path::Path};
+
+/// Package manager which may own the installed maa-cli binary.
+///
+/// If maa-cli is installed by a package manager, updating it by itself
+/// will overwrite files owned by the package manager,
+/// so it should be updated by the package manager instead.
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub enum PackageManager {
+    Homebrew,
+    /// Arch Linux packages, maa-cli is usually installed from AUR
+    Pacman,
+    Nix,
+    Scoop,
+    /// Package manager specified at build time by `MAA_PACKAGE_MANAGED`
+    Other(&'static str),
+}
+
+impl PackageManager {
+    /// Detect the package manager which owns the binary at given path.
+    pub fn detect(bin_path: &Path) -> Option<Self> {
+        if let Some(name) = option_env!("MAA_PACKAGE_MANAGED").filter(|s| !s.is_empty()) {
+            return Some(Self::from_name(name));
+        }
+
+        Self::from_path(bin_path, owned_by_pacman)
+    }
+
+    fn from_name(name: &'static str) -> Self {
+        match name.to_ascii_lowercase().as_str() {
+            "homebrew" | "brew" => Self::Homebrew,
+            "pacman" | "aur" => Self::Pacman,
+            "nix" => Self::Nix,
+            "scoop" => Self::Scoop,
+            _ => Self::Other(name),
+        }
+    }
+
+    /// Heuristically detect package manager from the path of binary.
+    ///
+    /// `pacman_owned` is used to check whether a path under `/usr` is owned by pacman,
+    /// which can not be determined by the path only.
+    fn from_path(bin_path: &Path, pacman_owned: impl Fn(&Path) -> bool) -> Option<Self> {
+        if bin_path.starts_with("/nix/store") {
+            return Some(Self::Nix);
+        }
+
+        if [
+            "/opt/homebrew",
+            "/usr/local/Cellar",
+            "/home/linuxbrew/.linuxbrew",
+        ]
+        .iter()
+        .any(|prefix| bin_path.starts_with(prefix))
+        {
+            return Some(Self::Homebrew);
+        }
+
+        if bin_path
+            .components()
+            .zip(bin_path.components().skip(1))
+            .any(|(a, b)| a.as_os_str().eq_ignore_ascii_case("scoop") && b.as_os_str() == "apps")
+        {
+            return Some(Self::Scoop);
+        }
+
+        if bin_path.starts_with("/usr")
+            && !bin_path.starts_with("/usr/local")
+            && pacman_owned(bin_path)
+        {
+            return Some(Self::Pacman);
+        }
+
+        None
+    }
+
+    /// Command to upgrade maa-cli with this package manager.
+    pub fn upgrade_command(&self) -> &'static str {
+        match self {
+            Self::Homebrew => "brew upgrade maa-cli",
+            Self::Pacman => "your AUR helper, e.g. `paru -Syu maa-cli`",
+            Self::Nix => "nix profile upgrade or rebuild your nix configuration",
+            Self::Scoop => "scoop update maa-cli",
+            Self::Other(_) => "the package manager used to install maa-cli",
+        }
+    }
+}
+
+impl fmt::Display for PackageManager {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Homebrew => write!(f, "Homebrew"),
+            Self::Pacman => write!(f, "pacman"),
+            Self::Nix => write!(f, "Nix"),
+            Self::Scoop => write!(f, "Scoop"),
+            Self::Other(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+/// Check whether the binary is installed by pacman.
+///
+/// A precise check requires `pacman -Qo`, which is slow,
+/// so we only check whether there is a maa-cli package in the local database.
+fn owned_by_pacman(_: &Path) -> bool {
+    Path::new("/var/lib/pacman/local")
+        .read_dir()
+        .map(|entries| {
+            entries.filter_map(|e| e.ok()).any(|e| {
+                e.file_name()
+                    .to_str()
+                    .is_some_and(|name| name.starts_with("maa-cli"))
+            })
+        })
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_path() {
+        fn detect(path: &str) -> Option<PackageManager> {
+            PackageManager::from_path(Path::new(path), |_| false)
+        }
+
+        use PackageManager::*;
+
+        assert_eq!(
+            detect("/opt/homebrew/Cellar/maa-cli/0.4.8/bin/maa"),
+            Some(Homebrew)
+        );
+        assert_eq!(
+            detect("/usr/local/Cellar/maa-cli/0.4.8/bin/maa"),
+            Some(Homebrew)
+        );
+        assert_eq!(
+            detect("/home/linuxbrew/.linuxbrew/Cellar/maa-cli/0.4.8/bin/maa"),
+            Some(Homebrew)
+        );
+        assert_eq!(detect("/nix/store/hash-maa-cli-0.4.8/bin/maa"), Some(Nix));
+        assert_eq!(
+            detect("C:/Users/user/scoop/apps/maa-cli/current/maa.exe"),
+            Some(Scoop)
+        );
+        assert_eq!(detect("/usr/bin/maa"), None);
+        assert_eq!(detect("/usr/local/bin/maa"), None);
+        assert_eq!(detect("/home/user/.local/bin/maa"), None);
+
+        assert_eq!(
+            PackageManager::from_path(Path::new("/usr/bin/maa"), |_| true),
+            Some(Pacman)
+        );
+        assert_eq!(
+            PackageManager::from_path(Path::new("/usr/local/bin/maa"), |_| true),
+            None
+        );
+        assert_eq!(
+            PackageManager::from_path(Path::new("/home/user/.local/bin/maa"), |_| true),
+            None
+        );
+    }
+
+    #[test]
+    fn from_name() {
+        use PackageManager::*;
+
+        assert_eq!(PackageManager::from_name("brew"), Homebrew);
+        assert_eq!(PackageManager::from_name("AUR"), Pacman);
+        assert_eq!(PackageManager::from_name("nix"), Nix);
+        assert_eq!(PackageManager::from_name("winget"), Other("winget"));
+    }
+
+    #[test]
+    fn upgrade_command() {
+        assert_eq!(
+            PackageManager::Homebrew.upgrade_command(),
+            "brew upgrade maa-cli"
+        );
+        assert_eq!(
+            PackageManager::Scoop.upgrade_command(),
+            "scoop update maa-cli"
+        );
+        assert_eq!(PackageManager::Other("winget").to_string(), "winget");
+    }
+}
@@ -1,10 +1,15 @@
 use crate::{
-    config::cli::{cli_config, resource::GitBackend},
+    config::cli::{
+        cli_config,
+        resource::{GitBackend, Mirror},
+    },
     dirs,
 };
 
-use anyhow::{bail, Result};
-use log::{debug, warn};
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Result};
+use log::{debug, info, warn};
 
 trait StatusExt {
     /// If error, return the error, otherwise return an error if the status is not successful
@@ -15,10 +20,7 @@ impl StatusExt for std::io::Result<std::process::ExitStatus> {
     fn check(self) -> std::io::Result<()> {
         self.and_then(|status| {
             if !status.success() {
-                Err(std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    "Command failed",
-                ))
+                Err(std::io::Error::other("Command failed"))
             } else {
                 Ok(())
             }
@@ -26,7 +28,10 @@ impl StatusExt for std::io::Result<std::process::ExitStatus> {
     }
 }
 
-pub fn update(is_auto: bool) -> Result<()> {
+/// Update the hot update resource repository.
+///
+/// If `auto_mirror` is true, the fastest preset mirror is used regardless of the configured one.
+pub fn update(is_auto: bool, auto_mirror: bool) -> Result<()> {
     let config = cli_config().resource_config();
 
     // Skip auto update if auto update is disabled
@@ -35,7 +40,22 @@ pub fn update(is_auto: bool) -> Result<()> {
     }
 
     let backend = config.backend();
-    let url = config.remote().url();
+    let mirror = if auto_mirror {
+        Some(Mirror::Auto)
+    } else {
+        config.mirror().cloned()
+    };
+    // The remote of the repository is only used when no mirror is configured
+    let mirror_url = match mirror {
+        Some(Mirror::Auto) => Some(fastest_mirror(probe)?),
+        Some(mirror) => mirror.url().map(str::to_owned),
+        None => None,
+    };
+    if let Some(url) = &mirror_url {
+        info!("Using resource mirror: {url}");
+    }
+    let url = mirror_url.as_deref().unwrap_or(config.remote().url());
+    let remote = mirror_url.as_deref().unwrap_or("origin");
     let branch = config.remote().branch();
     let ssh_key = config.remote().ssh_key().map(dirs::expand_tilde);
     let dest = dirs::hot_update();
@@ -72,9 +92,9 @@ pub fn update(is_auto: bool) -> Result<()> {
     if dest.exists() {
         debug!("Fetching resource repository...");
         match backend {
-            GitBackend::Git => git::pull(dest, branch, ssh_key.as_deref())?,
+            GitBackend::Git => git::pull(dest, remote, branch, ssh_key.as_deref())?,
             #[cfg(feature = "git2")]
-            GitBackend::Libgit2 => git2::pull(dest, branch, ssh_key.as_deref())?,
+            GitBackend::Libgit2 => git2::pull(dest, remote, branch, ssh_key.as_deref())?,
         }
     } else {
         debug!("Cloning resource repository...");
@@ -88,6 +108,49 @@ pub fn update(is_auto: bool) -> Result<()> {
     Ok(())
 }
 
+/// Timeout of a probe request to a mirror
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Latency of a HEAD request to the url, `None` if the mirror is unreachable.
+///
+/// Any response counts, since the status of a HEAD request to a repository varies by host.
+fn probe(url: &str) -> Option<Duration> {
+    let client = reqwest::blocking::Client::builder()
+        .timeout(PROBE_TIMEOUT)
+        .build()
+        .ok()?;
+    let start = Instant::now();
+    match client.head(url).send() {
+        Ok(_) => Some(start.elapsed()),
+        Err(err) => {
+            debug!("Failed to probe mirror {url}: {err}");
+            None
+        }
+    }
+}
+
+/// Preset mirrors which are reachable sorted by latency measured by `probe`.
+fn rank_mirrors(probe: impl Fn(&str) -> Option<Duration>) -> Vec<(&'static str, Duration)> {
+    let mut latencies: Vec<_> = Mirror::PRESETS
+        .iter()
+        .filter_map(|mirror| mirror.url())
+        .filter_map(|url| {
+            let latency = probe(url);
+            debug!("Latency of mirror {url}: {latency:?}");
+            Some((url, latency?))
+        })
+        .collect();
+    latencies.sort_by_key(|(_, latency)| *latency);
+    latencies
+}
+
+fn fastest_mirror(probe: impl Fn(&str) -> Option<Duration>) -> Result<String> {
+    rank_mirrors(probe)
+        .first()
+        .map(|(url, _)| (*url).to_owned())
+        .context("None of the resource mirrors is reachable")
+}
+
 mod git {
     use super::StatusExt;
 
@@ -128,10 +191,15 @@ mod git {
         Ok(())
     }
 
-    pub fn pull(repo: &Path, branch: Option<&str>, ssh_key: Option<&Path>) -> Result<()> {
+    pub fn pull(
+        repo: &Path,
+        remote: &str,
+        branch: Option<&str>,
+        ssh_key: Option<&Path>,
+    ) -> Result<()> {
         let mut cmd = std::process::Command::new("git");
 
-        cmd.args(["pull", "origin"]);
+        cmd.args(["pull", remote]);
 
         if let Some(branch) = branch {
             cmd.arg(branch);
@@ -194,7 +262,12 @@ mod git2 {
         Ok(())
     }
 
-    pub fn pull(repo: &Path, branch: Option<&str>, ssh_key: Option<&Path>) -> Result<()> {
+    pub fn pull(
+        repo: &Path,
+        remote: &str,
+        branch: Option<&str>,
+        ssh_key: Option<&Path>,
+    ) -> Result<()> {
         let repo = Repository::open(repo).context("Failed to open resource repository")?;
 
         let branch = branch.unwrap_or("main");
@@ -211,8 +284,10 @@ mod git2 {
             fetch_options
         });
 
-        repo.find_remote("origin")
-            .context("Failed to find remote 'origin'")?
+        // The remote is either the name of a remote or the url of a mirror
+        repo.find_remote(remote)
+            .or_else(|_| repo.remote_anonymous(remote))
+            .with_context(|| format!("Failed to find remote '{remote}'"))?
             .fetch(&[branch], fetch_options.as_mut(), None)?;
 
         let fetch_head = repo
@@ -251,3 +326,49 @@ mod git2 {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn latency(ms: u64) -> Option<Duration> {
+        Some(Duration::from_millis(ms))
+    }
+
+    #[test]
+    fn rank() {
+        let github = Mirror::Github.url().unwrap();
+        let cn = Mirror::Cn.url().unwrap();
+
+        let stub = |url: &str| {
+            if url == github {
+                latency(300)
+            } else {
+                latency(50)
+            }
+        };
+        assert_eq!(
+            rank_mirrors(stub),
+            [
+                (cn, Duration::from_millis(50)),
+                (github, Duration::from_millis(300))
+            ]
+        );
+        assert_eq!(fastest_mirror(stub).unwrap(), cn);
+
+        let stub = |url: &str| {
+            if url == github {
+                latency(20)
+            } else {
+                latency(50)
+            }
+        };
+        assert_eq!(fastest_mirror(stub).unwrap(), github);
+
+        // unreachable mirrors are skipped
+        let stub = |url: &str| if url == github { None } else { latency(500) };
+        assert_eq!(rank_mirrors(stub), [(cn, Duration::from_millis(500))]);
+
+        assert!(fastest_mirror(|_| None).is_err());
+    }
+}
@@ -0,0 +1,539 @@
+//! Build MaaCore from source for platforms without prebuilt packages.
+//!
+//! The source tarball of the release tag is downloaded from GitHub and built by the
+//! documented cmake workflow: prebuilt dependencies are downloaded by
+//! `tools/maadeps-download.py`, then MaaCore is configured, built and installed into a
+//! prefix in the cache directory. The installed files are moved into the same layout as
+//! prebuilt packages, and recorded in the manifest as built from source.
+
+use super::{
+    extract::{move_file, Archive},
+    http, integrity,
+    maa_core::extract_mapper,
+    manifest,
+};
+
+use crate::{
+    config::cli::{maa_core::Config, Channel},
+    dirs::{self, Ensure},
+};
+
+use std::{
+    ffi::{OsStr, OsString},
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{bail, Context, Result};
+use log::debug;
+use semver::Version;
+
+/// Name of the directory in cache to build MaaCore in
+const BUILD_DIR: &str = "maa-core-build";
+
+/// URL of the source tarball of given version
+fn source_url(version: &Version) -> String {
+    format!(
+        "https://github.com/MaaAssistantArknights/MaaAssistantArknights/archive/refs/tags/v{version}.tar.gz"
+    )
+}
+
+/// Default number of parallel build jobs, the number of available CPUs
+pub fn default_jobs() -> usize {
+    std::thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// Tools required to build MaaCore
+#[cfg_attr(test, derive(Debug, PartialEq))]
+struct Toolchain {
+    cmake: PathBuf,
+    python: PathBuf,
+}
+
+/// A tool required to build MaaCore, any of `names` is accepted
+struct Requirement {
+    names: &'static [&'static str],
+    hint: &'static str,
+}
+
+const CMAKE: Requirement = Requirement {
+    names: &["cmake"],
+    hint: "cmake is required to configure and build MaaCore, \
+           install it by your package manager, e.g. `apt install cmake`",
+};
+
+const PYTHON: Requirement = Requirement {
+    names: &["python3", "python"],
+    hint: "python is required to download prebuilt dependencies of MaaCore, \
+           install it by your package manager, e.g. `apt install python3`",
+};
+
+const COMPILER: Requirement = Requirement {
+    names: if cfg!(windows) {
+        &["cl", "clang-cl", "clang++", "g++"]
+    } else {
+        &["c++", "g++", "clang++"]
+    },
+    hint: "a C++ compiler with C++20 support is required to build MaaCore, \
+           install it by your package manager, e.g. `apt install g++`, \
+           or set `CXX` to the path of the compiler",
+};
+
+/// Check that all tools required to build MaaCore are available before downloading anything.
+///
+/// `find` looks up an executable by name. A C++ compiler given by `cxx`, i.e. the `CXX`
+/// environment variable which is also respected by cmake, is accepted if it exists.
+/// All missing tools are reported at once.
+fn preflight(find: impl Fn(&str) -> Option<PathBuf>, cxx: Option<&OsStr>) -> Result<Toolchain> {
+    let find_any = |requirement: &Requirement| requirement.names.iter().find_map(|n| find(n));
+
+    let cmake = find_any(&CMAKE);
+    let python = find_any(&PYTHON);
+    let compiler = match cxx {
+        Some(cxx) => find(&cxx.to_string_lossy()).or_else(|| {
+            let path = PathBuf::from(cxx);
+            path.is_file().then_some(path)
+        }),
+        None => find_any(&COMPILER),
+    };
+
+    let missing: Vec<_> = [
+        (cmake.is_none(), &CMAKE),
+        (python.is_none(), &PYTHON),
+        (compiler.is_none(), &COMPILER),
+    ]
+    .into_iter()
+    .filter_map(|(missing, requirement)| missing.then_some(requirement.hint))
+    .collect();
+    if !missing.is_empty() {
+        bail!(
+            "Missing tools to build MaaCore from source:\n{}",
+            missing
+                .iter()
+                .map(|hint| format!("  - {hint}"))
+                .collect::<Vec<_>>()
+                .join("\n")
+        );
+    }
+
+    Ok(Toolchain {
+        cmake: cmake.expect("checked above"),
+        python: python.expect("checked above"),
+    })
+}
+
+/// Find an executable by name in the directories of `paths`, like `which`.
+fn which_in(name: &str, paths: &OsStr) -> Option<PathBuf> {
+    let names: Vec<OsString> = if cfg!(windows) && Path::new(name).extension().is_none() {
+        vec![format!("{name}.exe").into(), format!("{name}.cmd").into()]
+    } else {
+        vec![name.into()]
+    };
+    std::env::split_paths(paths)
+        .flat_map(|dir| names.iter().map(move |name| dir.join(name)))
+        .find(|path| is_executable(path))
+}
+
+fn which(name: &str) -> Option<PathBuf> {
+    which_in(name, &std::env::var_os("PATH")?)
+}
+
+fn is_executable(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        path.metadata()
+            .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+    }
+    #[cfg(not(unix))]
+    path.is_file()
+}
+
+/// Commands to build MaaCore in `source` and install it into `prefix`, in order.
+fn build_commands(
+    toolchain: &Toolchain,
+    source: &Path,
+    prefix: &Path,
+    version: &Version,
+    jobs: usize,
+) -> Vec<Command> {
+    let build = source.join("build");
+
+    let mut deps = Command::new(&toolchain.python);
+    deps.arg(source.join("tools").join("maadeps-download.py"));
+
+    let mut configure = Command::new(&toolchain.cmake);
+    configure
+        .arg("-S")
+        .arg(source)
+        .arg("-B")
+        .arg(&build)
+        .args([
+            "-DCMAKE_BUILD_TYPE=Release",
+            "-DINSTALL_THIRD_LIBS=ON",
+            "-DINSTALL_RESOURCE=ON",
+            "-DINSTALL_PYTHON=OFF",
+        ])
+        .arg(format!("-DMAA_VERSION=v{version}"));
+
+    let mut compile = Command::new(&toolchain.cmake);
+    compile
+        .arg("--build")
+        .arg(&build)
+        .args(["--config", "Release", "--parallel"])
+        .arg(jobs.to_string());
+
+    let mut install = Command::new(&toolchain.cmake);
+    install
+        .arg("--install")
+        .arg(&build)
+        .args(["--config", "Release", "--prefix"])
+        .arg(prefix);
+
+    let mut commands = vec![deps, configure, compile, install];
+    for command in &mut commands {
+        command.current_dir(source);
+    }
+    commands
+}
+
+fn run(mut command: Command) -> Result<()> {
+    debug!("Running {command:?}");
+    let status = command
+        .status()
+        .with_context(|| format!("Failed to run {command:?}"))?;
+    if !status.success() {
+        bail!("{command:?} exited with {status}");
+    }
+    Ok(())
+}
+
+/// Move MaaCore installed in `prefix` into the library and resource directories.
+///
+/// Files are mapped in the same way as prebuilt packages, so the layout of the prefix
+/// does not matter. Return the moved files, i.e. `(library files, resource files)`.
+fn install_built(
+    prefix: &Path,
+    lib_dir: &Path,
+    resource_dir: &Path,
+    config: &Config,
+) -> Result<(Vec<PathBuf>, Vec<PathBuf>)> {
+    let components = config.components();
+    let mut files = Vec::new();
+    collect_files(prefix, &mut files)?;
+    let planned: Vec<_> = files
+        .into_iter()
+        .filter_map(|src| {
+            let relative = src.strip_prefix(prefix).ok()?;
+            let dest = extract_mapper(relative, lib_dir, resource_dir, components)?;
+            Some((src, dest))
+        })
+        .collect();
+    if !planned.iter().any(|(_, dest)| dest.starts_with(lib_dir)) && components.library {
+        bail!("No MaaCore library found in {}", prefix.display());
+    }
+
+    if components.library {
+        lib_dir.ensure_clean()?;
+    }
+    if components.resource {
+        resource_dir.ensure_clean()?;
+    }
+    let mut moved = Vec::with_capacity(planned.len());
+    for (src, dest) in planned {
+        move_file(&src, &dest)?;
+        moved.push(dest);
+    }
+    Ok(moved
+        .into_iter()
+        .partition(|dest| !dest.starts_with(resource_dir)))
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in dir
+        .read_dir()
+        .with_context(|| format!("Failed to read {}", dir.display()))?
+    {
+        let path = entry?.path();
+        if path.is_dir() && !path.is_symlink() {
+            collect_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Download the source tarball of given version, reuse it if it is already downloaded.
+///
+/// Tarballs of tags are generated by GitHub on demand, so their size and checksum are unknown.
+fn download_source(url: &str, path: &Path, config: &Config) -> Result<()> {
+    if path.exists() {
+        println!("Already downloaded, skip downloading");
+        return Ok(());
+    }
+    let client = http::blocking_client(config.ip_family())?;
+    let mut resp = client
+        .get(url)
+        .send()
+        .and_then(|resp| resp.error_for_status())
+        .with_context(|| format!("Failed to download {url}"))?;
+    let tmp = path.with_extension("part");
+    let mut file = std::fs::File::create(&tmp)?;
+    resp.copy_to(&mut file)
+        .with_context(|| format!("Failed to download {url}"))?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// Build MaaCore of given version from source and install it.
+pub fn install(
+    config: &Config,
+    version: &Version,
+    jobs: usize,
+    channel: Option<Channel>,
+) -> Result<()> {
+    let toolchain = preflight(which, std::env::var_os("CXX").as_deref())?;
+
+    let cache_dir = dirs::cache().ensure()?;
+    let url = source_url(version);
+    let tarball = cache_dir.join(format!("MaaAssistantArknights-v{version}.tar.gz"));
+    println!("Downloading MaaCore v{version} source...");
+    download_source(&url, &tarball, config)?;
+
+    let build_dir = cache_dir.join(BUILD_DIR);
+    build_dir.as_path().ensure_clean()?;
+    let source = build_dir.join("source");
+    let prefix = build_dir.join("install");
+    // Strip the top level directory `MaaAssistantArknights-<version>` of the tarball
+    Archive::new(tarball.into())?.extract_staged(
+        |path| {
+            let mut components = path.components();
+            components.next();
+            let relative = components.as_path();
+            (!relative.as_os_str().is_empty()).then(|| source.join(relative))
+        },
+        &build_dir.join("staging"),
+    )?;
+
+    println!("Building MaaCore v{version} with {jobs} jobs...");
+    for command in build_commands(&toolchain, &source, &prefix, version, jobs) {
+        run(command)?;
+    }
+
+    println!("Installing MaaCore...");
+    let (lib_files, resource_files) =
+        install_built(&prefix, dirs::library(), dirs::resource(), config)?;
+
+    let version = version.to_string();
+    if config.components().library {
+        let files = integrity::hash_files(lib_files)?;
+        manifest::record_source_build(manifest::MAA_CORE, &version, &url, files, channel)?;
+    }
+    if config.components().resource {
+        let files = integrity::hash_files(resource_files)?;
+        manifest::record_source_build(manifest::RESOURCE, &version, &url, files, channel)?;
+    }
+
+    std::fs::remove_dir_all(&build_dir)
+        .with_context(|| format!("Failed to remove {}", build_dir.display()))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env::consts::{DLL_PREFIX, DLL_SUFFIX};
+
+    #[test]
+    fn preflight_checks() {
+        let available = |tools: &'static [&'static str]| {
+            move |name: &str| {
+                tools
+                    .contains(&name)
+                    .then(|| PathBuf::from("/usr/bin").join(name))
+            }
+        };
+
+        let toolchain = preflight(available(&["cmake", "python3", "c++", "cl"]), None).unwrap();
+        assert_eq!(
+            toolchain,
+            Toolchain {
+                cmake: "/usr/bin/cmake".into(),
+                python: "/usr/bin/python3".into(),
+            }
+        );
+        assert!(preflight(available(&["cmake", "python", "g++", "clang++"]), None).is_ok());
+
+        // all missing tools are reported with hints
+        let err = preflight(available(&["python3"]), None)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("cmake is required"));
+        assert!(err.contains("C++ compiler"));
+        assert!(!err.contains("python is required"));
+
+        let err = preflight(available(&[]), None).unwrap_err().to_string();
+        assert_eq!(err.lines().count(), 4);
+
+        // the compiler given by `CXX` is used instead of the default ones
+        let tools = available(&["cmake", "python3", "c++", "cl", "my-c++"]);
+        assert!(preflight(tools, Some(OsStr::new("my-c++"))).is_ok());
+        let err = preflight(tools, Some(OsStr::new("missing-c++"))).unwrap_err();
+        assert!(err.to_string().contains("C++ compiler"));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn find_executable() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join("maa-test-which");
+        dir.as_path().ensure_clean().unwrap();
+        let first = dir.join("first");
+        let second = dir.join("second");
+        first.as_path().ensure().unwrap();
+        second.as_path().ensure().unwrap();
+
+        let create = |path: &Path, mode: u32| {
+            std::fs::write(path, "").unwrap();
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode)).unwrap();
+        };
+        create(&first.join("cmake"), 0o644);
+        create(&second.join("cmake"), 0o755);
+        create(&first.join("python3"), 0o755);
+        create(&second.join("python3"), 0o755);
+
+        let paths = std::env::join_paths([&first, &second]).unwrap();
+        // files which are not executable are skipped
+        assert_eq!(which_in("cmake", &paths), Some(second.join("cmake")));
+        // the first one in paths wins
+        assert_eq!(which_in("python3", &paths), Some(first.join("python3")));
+        assert_eq!(which_in("c++", &paths), None);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn commands() {
+        let toolchain = Toolchain {
+            cmake: "cmake".into(),
+            python: "python3".into(),
+        };
+        let source = Path::new("/cache/build/source");
+        let prefix = Path::new("/cache/build/install");
+        let commands = build_commands(&toolchain, source, prefix, &Version::new(5, 0, 0), 4);
+
+        let args = |command: &Command| {
+            std::iter::once(command.get_program())
+                .chain(command.get_args())
+                .map(|arg| arg.to_string_lossy().into_owned())
+                .collect::<Vec<_>>()
+        };
+        let build = source.join("build").to_string_lossy().into_owned();
+        let source_str = source.to_string_lossy().into_owned();
+        assert_eq!(commands.len(), 4);
+        assert_eq!(
+            args(&commands[0]),
+            [
+                "python3".to_owned(),
+                source
+                    .join("tools")
+                    .join("maadeps-download.py")
+                    .to_string_lossy()
+                    .into_owned()
+            ]
+        );
+        assert_eq!(
+            args(&commands[1]),
+            [
+                "cmake",
+                "-S",
+                &source_str,
+                "-B",
+                &build,
+                "-DCMAKE_BUILD_TYPE=Release",
+                "-DINSTALL_THIRD_LIBS=ON",
+                "-DINSTALL_RESOURCE=ON",
+                "-DINSTALL_PYTHON=OFF",
+                "-DMAA_VERSION=v5.0.0",
+            ]
+        );
+        assert_eq!(
+            args(&commands[2]),
+            [
+                "cmake",
+                "--build",
+                &build,
+                "--config",
+                "Release",
+                "--parallel",
+                "4"
+            ]
+        );
+        assert_eq!(
+            args(&commands[3]),
+            [
+                "cmake",
+                "--install",
+                &build,
+                "--config",
+                "Release",
+                "--prefix",
+                &prefix.to_string_lossy()
+            ]
+        );
+        assert!(commands
+            .iter()
+            .all(|command| command.get_current_dir() == Some(source)));
+    }
+
+    #[test]
+    fn install_built_files() {
+        let dir = std::env::temp_dir().join("maa-test-source-build");
+        dir.as_path().ensure_clean().unwrap();
+        let prefix = dir.join("install");
+        let lib_dir = dir.join("lib");
+        let resource_dir = dir.join("resource");
+
+        let lib_name = format!("{DLL_PREFIX}MaaCore{DLL_SUFFIX}");
+        let deps_name = format!("{DLL_PREFIX}onnxruntime{DLL_SUFFIX}");
+        for file in [
+            prefix.join(&lib_name),
+            prefix.join("lib").join(&deps_name),
+            prefix.join("resource/config.json"),
+            prefix.join("resource/tasks/a.json"),
+            prefix.join("include/AsstCaller.h"),
+        ] {
+            file.parent().unwrap().ensure().unwrap();
+            std::fs::write(file, "").unwrap();
+        }
+        lib_dir.as_path().ensure().unwrap();
+        std::fs::write(lib_dir.join("old.so"), "").unwrap();
+
+        let (mut lib_files, mut resource_files) =
+            install_built(&prefix, &lib_dir, &resource_dir, &Config::default()).unwrap();
+        lib_files.sort();
+        resource_files.sort();
+        let mut expected = vec![lib_dir.join(&lib_name), lib_dir.join(&deps_name)];
+        expected.sort();
+        assert_eq!(lib_files, expected);
+        assert_eq!(
+            resource_files,
+            [
+                resource_dir.join("config.json"),
+                resource_dir.join("tasks/a.json")
+            ]
+        );
+        // old files are removed, and files not in the layout are not installed
+        assert!(!lib_dir.join("old.so").exists());
+        assert!(!lib_dir.join("AsstCaller.h").exists());
+
+        // a build without the library is rejected
+        assert!(install_built(&prefix, &lib_dir, &resource_dir, &Config::default()).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,341 @@
+//! Uninstall components installed by the installers.
+//!
+//! Only files recorded by the installers are removed, so user configs and files
+//! added by users are never touched.
+
+#[cfg(feature = "core_installer")]
+use super::manifest::{self, Manifest};
+
+use crate::dirs;
+use crate::value::userinput::{BoolInput, UserInput};
+
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "core_installer")]
+use anyhow::bail;
+use anyhow::{Context, Result};
+#[cfg(feature = "core_installer")]
+use dunce::canonicalize;
+
+#[cfg(feature = "core_installer")]
+#[derive(clap::ValueEnum, Clone, Copy)]
+pub enum Component {
+    /// MaaCore library
+    Core,
+    /// Resources of MaaCore
+    Resource,
+}
+
+#[cfg(feature = "core_installer")]
+impl Component {
+    pub(super) fn name(self) -> &'static str {
+        match self {
+            Self::Core => manifest::MAA_CORE,
+            Self::Resource => manifest::RESOURCE,
+        }
+    }
+
+    pub(super) fn root(self) -> &'static Path {
+        match self {
+            Self::Core => dirs::library(),
+            Self::Resource => dirs::resource(),
+        }
+    }
+}
+
+/// Files to be removed by an uninstallation.
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct Plan {
+    /// Installed files, empty directories left are removed up to `root`
+    files: Vec<PathBuf>,
+    /// Directory containing the installed files, which is kept
+    root: Option<PathBuf>,
+    /// Cache and state files removed by `--purge`, directories are removed recursively
+    purge: Vec<PathBuf>,
+}
+
+impl Plan {
+    pub fn new(files: Vec<PathBuf>, root: Option<PathBuf>, purge: Vec<PathBuf>) -> Self {
+        Self {
+            files: files.into_iter().filter(|path| path.exists()).collect(),
+            root,
+            purge: purge.into_iter().filter(|path| path.exists()).collect(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.files.is_empty() && self.purge.is_empty()
+    }
+
+    fn print(&self) {
+        for path in self.files.iter().chain(&self.purge) {
+            println!("  {}", path.display());
+        }
+    }
+
+    /// Print files to be removed, and remove them if confirmed unless `dry_run`.
+    ///
+    /// Return whether the files are removed.
+    pub fn confirm_and_execute(
+        &self,
+        what: &str,
+        dry_run: bool,
+        confirm: impl FnOnce(&str) -> Result<bool>,
+    ) -> Result<bool> {
+        if self.is_empty() {
+            println!("No files of {what} found");
+        } else {
+            println!("The following files of {what} will be removed:");
+            self.print();
+        }
+        if dry_run {
+            println!("Dry run, nothing is removed");
+            return Ok(false);
+        }
+        if !self.is_empty() && !confirm(what)? {
+            println!("Aborted");
+            return Ok(false);
+        }
+        self.execute()?;
+        Ok(true)
+    }
+
+    fn execute(&self) -> Result<()> {
+        for file in &self.files {
+            remove(file)?;
+            if let Some(root) = &self.root {
+                dirs::remove_empty_parents(file, root);
+            }
+        }
+        for path in &self.purge {
+            remove(path)?;
+        }
+        Ok(())
+    }
+}
+
+/// Ask the user to confirm uninstalling, default to no.
+pub fn ask(what: &str) -> Result<bool> {
+    Ok(BoolInput::new(Some(false), Some(&format!("uninstall {what}"))).value()?)
+}
+
+fn remove(path: &Path) -> Result<()> {
+    let result = if path.is_dir() {
+        std::fs::remove_dir_all(path)
+    } else {
+        std::fs::remove_file(path)
+    };
+    match result {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+            Err(err).with_context(|| format!("Failed to remove {}", path.display()))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Uninstall a component of MaaCore, with its cached package, staging files and state if `purge`.
+#[cfg(feature = "core_installer")]
+pub fn uninstall(component: Component, purge: bool, dry_run: bool) -> Result<()> {
+    let manifest_path = manifest::path();
+    let purge_dir = purge.then(dirs::cache);
+    uninstall_in(
+        &manifest_path,
+        component.name(),
+        component.root(),
+        purge_dir,
+        dry_run,
+        ask,
+    )
+}
+
+#[cfg(feature = "core_installer")]
+fn uninstall_in(
+    manifest_path: &Path,
+    name: &str,
+    root: &Path,
+    cache_dir: Option<&Path>,
+    dry_run: bool,
+    confirm: impl FnOnce(&str) -> Result<bool>,
+) -> Result<()> {
+    let mut manifest = Manifest::load(manifest_path);
+    let Some(entry) = manifest.remove(name) else {
+        bail!("{name} is not installed by maa-cli, nothing to uninstall");
+    };
+
+    let root = canonicalize(root).unwrap_or_else(|_| root.to_owned());
+    let mut files = Vec::new();
+    for file in entry.files() {
+        files.extend(installed_path(file, &root)?);
+    }
+
+    let mut purge = cache_dir.map_or_else(Vec::new, |cache_dir| {
+        let package = entry.package_name().map(|name| cache_dir.join(name));
+        package
+            .into_iter()
+            .chain([cache_dir.join(super::maa_core::STAGING_DIR)])
+            .collect()
+    });
+    // The manifest is the only state of components, removed with the last one
+    let purge_state = cache_dir.is_some() && manifest.is_empty();
+    if purge_state {
+        purge.push(manifest_path.to_owned());
+    }
+    let plan = Plan::new(files, Some(root), purge);
+
+    if plan.confirm_and_execute(name, dry_run, confirm)? {
+        if !purge_state {
+            manifest.save(manifest_path)?;
+        }
+        println!("Uninstalled {name}");
+    }
+    Ok(())
+}
+
+/// Resolve the location of a file recorded in the manifest, `None` if it does not exist.
+///
+/// The manifest may be corrupted or tampered, so a file which is not under `root` is refused.
+/// Only the parent directory is resolved, so a symlink is removed itself instead of its target.
+#[cfg(feature = "core_installer")]
+fn installed_path(file: &Path, root: &Path) -> Result<Option<PathBuf>> {
+    let refuse = || {
+        anyhow::anyhow!(
+            "{} recorded in the manifest is not under {}, refusing to remove it",
+            file.display(),
+            root.display()
+        )
+    };
+    let (Some(parent), Some(name)) = (file.parent(), file.file_name()) else {
+        return Err(refuse());
+    };
+    let Ok(parent) = canonicalize(parent) else {
+        return Ok(None);
+    };
+    let path = parent.join(name);
+    if path == root || !path.starts_with(root) {
+        return Err(refuse());
+    }
+    Ok(path.symlink_metadata().is_ok().then_some(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::dirs::Ensure;
+
+    #[cfg(feature = "core_installer")]
+    #[test]
+    fn uninstall_recorded_files() {
+        use manifest::{MAA_CORE, RESOURCE};
+
+        let dir = std::env::temp_dir().join("maa-test-uninstall");
+        dir.as_path().ensure_clean().unwrap();
+
+        let lib = dir.join("lib");
+        let resource = dir.join("resource");
+        let cache = dir.join("cache");
+        let manifest_path = dir.join("state/manifest.json");
+        let write = |path: &Path| {
+            path.parent().unwrap().ensure().unwrap();
+            std::fs::write(path, "").unwrap();
+        };
+
+        let lib_files = vec![lib.join("libMaaCore.so"), lib.join("libonnxruntime.so")];
+        let resource_files = vec![
+            resource.join("config.json"),
+            resource.join("tasks/a.json"),
+            resource.join("tasks/b.json"),
+        ];
+        for file in lib_files.iter().chain(&resource_files) {
+            write(file);
+        }
+        // files not recorded are kept
+        write(&resource.join("user.json"));
+        write(&cache.join("MAA-v5.0.0-linux-x86_64.tar.gz"));
+        write(&cache.join("maa-core-staging/1"));
+        write(&cache.join("other.zip"));
+
+        let mut manifest = Manifest::default();
+        let source = "https://example.com/v5.0.0/MAA-v5.0.0-linux-x86_64.tar.gz";
+        manifest.insert(MAA_CORE, "5.0.0", source, lib_files.clone());
+        manifest.insert(RESOURCE, "5.0.0", source, resource_files.clone());
+        manifest.save(&manifest_path).unwrap();
+
+        let yes = |_: &str| Ok(true);
+        let no = |_: &str| Ok(false);
+
+        // dry run removes nothing
+        uninstall_in(&manifest_path, RESOURCE, &resource, Some(&cache), true, yes).unwrap();
+        assert!(resource_files.iter().all(|file| file.exists()));
+        assert!(Manifest::load(&manifest_path).get(RESOURCE).is_some());
+
+        // nothing is removed if not confirmed
+        uninstall_in(&manifest_path, RESOURCE, &resource, None, false, no).unwrap();
+        assert!(resource_files.iter().all(|file| file.exists()));
+
+        // recorded files are removed, the root and other files are kept
+        uninstall_in(&manifest_path, RESOURCE, &resource, None, false, yes).unwrap();
+        assert!(resource_files.iter().all(|file| !file.exists()));
+        assert!(!resource.join("tasks").exists());
+        assert!(resource.join("user.json").exists());
+        assert!(cache.join("MAA-v5.0.0-linux-x86_64.tar.gz").exists());
+        let manifest = Manifest::load(&manifest_path);
+        assert!(manifest.get(RESOURCE).is_none());
+        assert!(manifest.get(MAA_CORE).is_some());
+
+        // not installed
+        assert!(uninstall_in(&manifest_path, RESOURCE, &resource, None, false, yes).is_err());
+
+        // files not under the root are refused
+        let outside = dir.join("outside");
+        write(&outside);
+        let mut manifest = Manifest::load(&manifest_path);
+        let tampered = vec![lib.join("libMaaCore.so"), lib.join("../outside")];
+        manifest.insert(RESOURCE, "5.0.0", source, tampered);
+        manifest.save(&manifest_path).unwrap();
+        assert!(uninstall_in(&manifest_path, RESOURCE, &lib, None, false, yes).is_err());
+        manifest.insert(RESOURCE, "5.0.0", source, vec![lib.clone()]);
+        manifest.save(&manifest_path).unwrap();
+        assert!(uninstall_in(&manifest_path, RESOURCE, &lib, None, false, yes).is_err());
+        assert!(outside.exists());
+        assert!(lib_files.iter().all(|file| file.exists()));
+        manifest.remove(RESOURCE);
+        manifest.save(&manifest_path).unwrap();
+
+        // purge the cached package, staging files and the manifest with the last component
+        uninstall_in(&manifest_path, MAA_CORE, &lib, Some(&cache), false, yes).unwrap();
+        assert!(lib_files.iter().all(|file| !file.exists()));
+        assert!(lib.exists());
+        assert!(!cache.join("MAA-v5.0.0-linux-x86_64.tar.gz").exists());
+        assert!(!cache.join("maa-core-staging").exists());
+        assert!(cache.join("other.zip").exists());
+        assert!(!manifest_path.exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn plan() {
+        let dir = std::env::temp_dir().join("maa-test-uninstall-plan");
+        dir.as_path().ensure_clean().unwrap();
+        std::fs::write(dir.join("a"), "").unwrap();
+
+        let plan = Plan::new(
+            vec![dir.join("a"), dir.join("missing")],
+            None,
+            vec![dir.join("missing")],
+        );
+        assert_eq!(
+            plan,
+            Plan {
+                files: vec![dir.join("a")],
+                root: None,
+                purge: Vec::new(),
+            }
+        );
+        assert!(!plan.is_empty());
+        assert!(Plan::new(Vec::new(), None, Vec::new()).is_empty());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,279 @@
+//! Check update of maa-cli in background when running other commands.
+//!
+//! The check is disabled by default, and can be enabled by `cli.update_check.enable`.
+//! The version json is fetched at most once per interval, and the result is cached
+//! in the state directory, so the notice is shown even if the check is skipped.
+
+use super::{
+    http,
+    version_json::{fetch_json_cached, VersionJSON},
+};
+
+use crate::{
+    config::cli::{cli_config, maa_cli::Config, IpFamily},
+    dirs::{self, Ensure},
+};
+
+use std::{
+    io::IsTerminal,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use log::debug;
+use semver::Version;
+use serde::{de::IgnoredAny, Deserialize, Serialize};
+
+/// Timeout of the request, the check is given up if it takes longer.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Maximum time to wait for the check to finish at the end of a command.
+const FINISH_BUDGET: Duration = Duration::from_millis(500);
+
+/// Result of the last update check, persisted in the state directory.
+#[cfg_attr(test, derive(Debug, PartialEq))]
+#[derive(Deserialize, Serialize, Default)]
+pub struct State {
+    /// Unix timestamp of the last check in seconds
+    last_check: u64,
+    /// Latest version found by the last successful check
+    latest: Option<Version>,
+}
+
+impl State {
+    fn load(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            dir.ensure_with_mode(dirs::PRIVATE_DIR_MODE)?;
+        }
+        std::fs::write(path, serde_json::to_string(self)?)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    fn is_due(&self, now: u64, interval_hours: u64) -> bool {
+        now.saturating_sub(self.last_check) >= interval_hours.saturating_mul(3600)
+    }
+
+    /// Unix timestamp of the last check in seconds
+    pub fn last_check(&self) -> u64 {
+        self.last_check
+    }
+
+    /// Latest version found by the last successful check
+    pub fn latest(&self) -> Option<&Version> {
+        self.latest.as_ref()
+    }
+}
+
+/// The state of the last update check, `None` if never checked.
+pub fn last_check() -> Option<State> {
+    let state = State::load(&state_path());
+    (state.last_check > 0).then_some(state)
+}
+
+/// A running update check, the notice is printed by [`UpdateCheck::finish`].
+pub struct UpdateCheck {
+    /// Receiver of the result of the check running in background, `None` if not due
+    receiver: Option<Receiver<Version>>,
+    /// Latest version found by previous checks
+    cached: Option<Version>,
+}
+
+/// Start to check update in background if it is enabled.
+///
+/// Nothing is done in batch mode or if stderr is not a terminal,
+/// where the notice may be read by scripts instead of users.
+pub fn start(batch: bool) -> Option<UpdateCheck> {
+    let config = cli_config().cli_config();
+    if !config.update_check().enable || batch || !std::io::stderr().is_terminal() {
+        return None;
+    }
+    Some(start_with(&config, state_path(), now()))
+}
+
+fn start_with(config: &Config, state_path: PathBuf, now: u64) -> UpdateCheck {
+    let state = State::load(&state_path);
+    let receiver = state.is_due(now, config.update_check().interval).then(|| {
+        let url = config.api_url();
+        let ip_family = config.ip_family();
+        let (sender, receiver) = mpsc::channel();
+        // The thread is detached, it will be killed when the main thread exits
+        std::thread::spawn(move || match check(&url, ip_family, &state_path, now) {
+            Ok(version) => {
+                let _ = sender.send(version);
+            }
+            Err(err) => debug!("Failed to check update: {err:#}"),
+        });
+        receiver
+    });
+
+    UpdateCheck {
+        receiver,
+        cached: state.latest,
+    }
+}
+
+impl UpdateCheck {
+    /// Print a notice if a newer version is available.
+    ///
+    /// The result of the running check is waited for a short time,
+    /// if it is not finished, the cached result is used.
+    pub fn finish(self) {
+        let Some(current) = current() else {
+            return;
+        };
+        if let Some(notice) = self.latest().and_then(|latest| notice(&latest, &current)) {
+            eprintln!("{notice}");
+        }
+    }
+
+    fn latest(self) -> Option<Version> {
+        self.receiver
+            .and_then(|receiver| receiver.recv_timeout(FINISH_BUDGET).ok())
+            .or(self.cached)
+    }
+}
+
+/// Fetch the latest version and record it in the state file.
+///
+/// The time of check is recorded even if the fetch failed,
+/// so an unreachable server will not slow down every command.
+fn check(url: &str, ip_family: IpFamily, state_path: &Path, now: u64) -> Result<Version> {
+    let mut state = State::load(state_path);
+    state.last_check = now;
+    state.save(state_path)?;
+
+    let client = http::blocking_builder(ip_family)
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .context("Failed to create reqwest client")?;
+    check_with(&client, url, state_path, state)
+}
+
+fn check_with(
+    client: &reqwest::blocking::Client,
+    url: &str,
+    state_path: &Path,
+    mut state: State,
+) -> Result<Version> {
+    let version_json: VersionJSON<IgnoredAny> = fetch_json_cached(client, url, dirs::cache())?;
+    let version = version_json.version().clone();
+    state.latest = Some(version.clone());
+    state.save(state_path)?;
+    Ok(version)
+}
+
+fn notice(latest: &Version, current: &Version) -> Option<String> {
+    (latest > current).then(|| format!("maa-cli v{latest} is available, run `maa self update`"))
+}
+
+pub(super) fn state_path() -> PathBuf {
+    dirs::state().join("maa-cli-update-check.json")
+}
+
+/// Version of running maa-cli, `None` if it is not a valid version, e.g. of a custom build
+fn current() -> Option<Version> {
+    env!("MAA_VERSION")
+        .parse()
+        .map_err(|err| debug!("Failed to parse the version of maa-cli: {err}"))
+        .ok()
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::installer::test_server::{Response, Server};
+
+    use std::{env::temp_dir, fs};
+
+    fn version_json(version: &str) -> String {
+        format!(r#"{{"version": "{version}", "details": {{}}}}"#)
+    }
+
+    #[test]
+    fn due() {
+        let state = State {
+            last_check: 100_000,
+            latest: None,
+        };
+        assert!(!state.is_due(100_000, 24));
+        assert!(!state.is_due(100_000 + 24 * 3600 - 1, 24));
+        assert!(state.is_due(100_000 + 24 * 3600, 24));
+        assert!(state.is_due(100_000, 0));
+        assert!(State::default().is_due(now(), 24));
+    }
+
+    #[test]
+    fn notice_newer() {
+        let v = |s: &str| Version::parse(s).unwrap();
+        assert_eq!(
+            notice(&v("0.4.9"), &v("0.4.8")).as_deref(),
+            Some("maa-cli v0.4.9 is available, run `maa self update`")
+        );
+        assert_eq!(notice(&v("0.4.8"), &v("0.4.8")), None);
+        assert_eq!(notice(&v("0.4.7"), &v("0.4.8")), None);
+        assert!(notice(&v("0.4.9-beta.1"), &v("0.4.8")).is_some());
+    }
+
+    #[test]
+    fn check_and_cache() {
+        let dir = temp_dir().join("maa-test-update-check");
+        if dir.exists() {
+            fs::remove_dir_all(&dir).unwrap();
+        }
+        let state_path = dir.join("state.json");
+
+        // a missing or broken state file is treated as never checked
+        assert_eq!(State::load(&state_path), State::default());
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&state_path, "not json").unwrap();
+        assert_eq!(State::load(&state_path), State::default());
+
+        let server = Server::new([Response::ok(version_json("0.4.9")), Response::status(404)]);
+        let client = reqwest::blocking::Client::builder()
+            .no_proxy()
+            .build()
+            .unwrap();
+        let state = State {
+            last_check: 42,
+            latest: None,
+        };
+        let version = check_with(&client, &server.url("stable.json"), &state_path, state).unwrap();
+        assert_eq!(version, Version::new(0, 4, 9));
+        assert_eq!(
+            State::load(&state_path),
+            State {
+                last_check: 42,
+                latest: Some(Version::new(0, 4, 9)),
+            }
+        );
+
+        // failed check keeps the cached version
+        let state = State::load(&state_path);
+        assert!(check_with(&client, &server.url("stable.json"), &state_path, state).is_err());
+        assert_eq!(State::load(&state_path).latest, Some(Version::new(0, 4, 9)));
+
+        // not due, so only the cached version is used without network access
+        let config = Config::default().set_api_url(server.url("")).clone();
+        let check = start_with(&config, state_path.clone(), 42 + 3600);
+        assert!(check.receiver.is_none());
+        assert_eq!(check.latest(), Some(Version::new(0, 4, 9)));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,5 +1,21 @@
+use super::{download::local_path, http};
+
+use crate::{config::cli::IpFamily, dirs};
+
+use std::{
+    error::Error as _,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use log::debug;
+use reqwest::{
+    header::{ETAG, IF_NONE_MATCH},
+    StatusCode,
+};
 use semver::Version;
-use serde::Deserialize;
+use serde::{de::DeserializeOwned, Deserialize};
+use sha2::{Digest, Sha256};
 
 #[cfg_attr(test, derive(Debug, PartialEq))]
 pub struct VersionJSON<D> {
@@ -34,10 +50,15 @@ impl<'de, A: Deserialize<'de>> Deserialize<'de> for VersionJSON<A> {
 }
 
 impl<D> VersionJSON<D> {
+    pub fn new(version: Version, details: D) -> Self {
+        Self { version, details }
+    }
+
     pub fn version(&self) -> &Version {
         &self.version
     }
 
+    #[cfg(feature = "cli_installer")]
     pub fn can_update(&self, name: &str, current_version: &Version) -> Result<bool, semver::Error> {
         let version = self.version();
         if version > current_version {
@@ -57,10 +78,454 @@ impl<D> VersionJSON<D> {
     }
 }
 
+/// Fetch and parse version json from given url.
+///
+/// Network errors are classified and reported with a hint to help troubleshooting,
+/// the original error is kept as the source of returned error.
+///
+/// The url can also be a `file://` url or a plain filesystem path, e.g. a mirror on a network share.
+pub fn fetch<D: DeserializeOwned>(
+    url: &str,
+    ip_family: IpFamily,
+) -> anyhow::Result<VersionJSON<D>> {
+    if let Some(path) = local_path(url).or_else(|| (!url.contains("://")).then(|| url.into())) {
+        return read_json(&path);
+    }
+    fetch_json_cached(&http::blocking_client(ip_family)?, url, dirs::cache())
+        .map_err(|err| http::with_family_hint(err, ip_family))
+}
+
+/// Fetch and parse json from given url with given client, errors are classified as `fetch`.
+#[cfg(test)]
+fn fetch_json<T: DeserializeOwned>(
+    client: &reqwest::blocking::Client,
+    url: &str,
+) -> anyhow::Result<T> {
+    client
+        .get(url)
+        .send()
+        .and_then(|resp| resp.error_for_status())
+        .and_then(|resp| resp.json())
+        .map_err(|err| fetch_error(err, url))
+}
+
+/// Files caching the body and ETag of the version json fetched from `url` in `cache_dir`.
+///
+/// The file names are keyed by the hash of the url, so version jsons of different urls,
+/// e.g. of maa-cli and MaaCore, never overwrite each other.
+fn cached_paths(cache_dir: &Path, url: &str) -> (PathBuf, PathBuf) {
+    let key = format!("{:x}", Sha256::digest(url.as_bytes()));
+    let name = format!("version-{}.json", &key[..16]);
+    let etag = format!("{name}.etag");
+    (cache_dir.join(name), cache_dir.join(etag))
+}
+
+/// Fetch and parse json from given url with given client, errors are classified as `fetch`.
+///
+/// The version json cached in `cache_dir` is revalidated by ETag.
+///
+/// The ETag of the cached version json is sent in `If-None-Match`, and the cached version
+/// json is used if the server responds with `304 Not Modified`. Otherwise, the response is
+/// cached with its ETag if it has one. Failures of caching are ignored.
+pub fn fetch_json_cached<T: DeserializeOwned>(
+    client: &reqwest::blocking::Client,
+    url: &str,
+    cache_dir: &Path,
+) -> anyhow::Result<T> {
+    fetch_json_cached_with(client, url, cache_dir, true)
+}
+
+/// Fetch the version json, the cached one is revalidated only if `revalidate` is true.
+fn fetch_json_cached_with<T: DeserializeOwned>(
+    client: &reqwest::blocking::Client,
+    url: &str,
+    cache_dir: &Path,
+    revalidate: bool,
+) -> anyhow::Result<T> {
+    let (json_path, etag_path) = cached_paths(cache_dir, url);
+
+    let etag = (revalidate && json_path.is_file())
+        .then(|| std::fs::read_to_string(&etag_path).ok())
+        .flatten()
+        .map(|etag| etag.trim().to_owned())
+        .filter(|etag| !etag.is_empty());
+
+    let mut request = client.get(url);
+    if let Some(etag) = &etag {
+        request = request.header(IF_NONE_MATCH, etag);
+    }
+    let response = request
+        .send()
+        .and_then(|resp| resp.error_for_status())
+        .map_err(|err| fetch_error(err, url))?;
+
+    if response.status() == StatusCode::NOT_MODIFIED {
+        match read_json(&json_path) {
+            Ok(value) => {
+                debug!("Version info from {url} is not modified, using the cached one");
+                return Ok(value);
+            }
+            Err(err) if revalidate => {
+                debug!("Failed to use the cached version info: {err:#}, fetching again");
+                // Remove the ETag, and fetch again without it for the full content
+                let _ = std::fs::remove_file(&etag_path);
+                return fetch_json_cached_with(client, url, cache_dir, false);
+            }
+            Err(err) => {
+                return Err(err.context(format!(
+                    "Failed to fetch version info from {url}: not modified without a cached one"
+                )))
+            }
+        }
+    }
+
+    let new_etag = response
+        .headers()
+        .get(ETAG)
+        .and_then(|etag| etag.to_str().ok())
+        .map(str::to_owned);
+    let body = response.bytes().map_err(|err| fetch_error(err, url))?;
+    let value = serde_json::from_slice(&body).map_err(|err| {
+        anyhow::Error::new(err).context(format!(
+            "Failed to fetch version info from {url}: {}",
+            FetchError::Parse
+        ))
+    })?;
+
+    if let Some(new_etag) = new_etag {
+        // Remove the old ETag first, so it never pairs with another version json
+        let _ = std::fs::remove_file(&etag_path);
+        let cached = std::fs::create_dir_all(cache_dir)
+            .and_then(|_| std::fs::write(&json_path, &body))
+            .and_then(|_| std::fs::write(&etag_path, new_etag));
+        if let Err(err) = cached {
+            debug!("Failed to cache version info from {url}: {err}");
+        }
+    }
+
+    Ok(value)
+}
+
+fn fetch_error(err: reqwest::Error, url: &str) -> anyhow::Error {
+    // The url of error is the final url after redirects
+    let final_url = err.url().map_or(url, |u| u.as_str()).to_owned();
+    let kind = FetchError::classify(&err);
+    anyhow::Error::new(err).context(format!(
+        "Failed to fetch version info from {final_url}: {kind}"
+    ))
+}
+
+fn read_json<T: DeserializeOwned>(path: &std::path::Path) -> anyhow::Result<T> {
+    use anyhow::Context;
+
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read version info from {}", path.display()))?;
+    serde_json::from_str(&content)
+        .with_context(|| format!("Failed to parse version info from {}", path.display()))
+}
+
+/// Classified error of fetching version json.
+#[cfg_attr(test, derive(Debug, PartialEq))]
+enum FetchError {
+    Dns,
+    Timeout,
+    Connect,
+    Tls,
+    Status(u16),
+    Parse,
+    Other,
+}
+
+impl FetchError {
+    fn classify(err: &reqwest::Error) -> Self {
+        if let Some(status) = err.status() {
+            return Self::Status(status.as_u16());
+        }
+        if err.is_decode() {
+            return Self::Parse;
+        }
+        if err.is_timeout() {
+            return Self::Timeout;
+        }
+
+        // Errors from hyper are opaque, so we have to check the messages of the source chain
+        let mut messages = Vec::new();
+        let mut source = err.source();
+        while let Some(e) = source {
+            messages.push(e.to_string().to_lowercase());
+            source = e.source();
+        }
+        let mentions = |keywords: &[&str]| {
+            messages
+                .iter()
+                .any(|m| keywords.iter().any(|k| m.contains(k)))
+        };
+
+        if mentions(&[
+            "dns error",
+            "failed to lookup address",
+            "name or service not known",
+        ]) {
+            Self::Dns
+        } else if mentions(&["certificate", "tls", "ssl", "handshake"]) {
+            Self::Tls
+        } else if err.is_connect() {
+            Self::Connect
+        } else if mentions(&["timed out"]) {
+            Self::Timeout
+        } else {
+            Self::Other
+        }
+    }
+}
+
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Dns => write!(
+                f,
+                "failed to resolve the host name, please check your network and DNS settings"
+            ),
+            Self::Timeout => write!(
+                f,
+                "request timed out, please check your network and proxy settings"
+            ),
+            Self::Connect => write!(
+                f,
+                "failed to connect to the server, please check your network and proxy settings \
+                 (HTTP_PROXY, HTTPS_PROXY)"
+            ),
+            Self::Tls => write!(
+                f,
+                "TLS handshake failed, please check your system certificates and proxy settings"
+            ),
+            Self::Status(code) => write!(
+                f,
+                "server returned HTTP {code}, please check the api url \
+                 (`api_url` in cli config, `--api-url` or the `MAA_CLI_API` override)"
+            ),
+            Self::Parse => write!(
+                f,
+                "failed to parse version info, the api url may point to a stale mirror \
+                 or a wrong file"
+            ),
+            Self::Other => write!(f, "please check your network and proxy settings"),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    use crate::installer::test_server::{Response, Server};
+
+    use std::{fs, time::Duration};
+
+    fn client() -> reqwest::blocking::Client {
+        reqwest::blocking::Client::builder()
+            .no_proxy()
+            .timeout(Duration::from_millis(500))
+            .build()
+            .unwrap()
+    }
+
+    fn fetch_error(url: &str) -> (FetchError, String) {
+        let err = fetch_json::<VersionJSON<()>>(&client(), url).unwrap_err();
+        let kind = FetchError::classify(err.downcast_ref::<reqwest::Error>().unwrap());
+        (kind, err.to_string())
+    }
+
+    #[test]
+    fn fetch_ok() {
+        let server = Server::new([Response::ok(r#"{"version": "v1.0.0", "details": null}"#)]);
+        let version_json =
+            fetch_json::<VersionJSON<()>>(&client(), &server.url("stable.json")).unwrap();
+        assert_eq!(version_json.version(), &Version::new(1, 0, 0));
+    }
+
+    #[test]
+    fn fetch_cached_by_etag() {
+        let dir = std::env::temp_dir().join("maa-test-version-json-etag");
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir).unwrap();
+        }
+        let v1 = r#"{"version": "v1.0.0", "details": null}"#;
+        let v2 = r#"{"version": "v2.0.0", "details": null}"#;
+        let server = Server::new([
+            Response::ok(v1).header("ETag", "\"v1\""),
+            Response::status(304),
+            Response::ok(v2).header("ETag", "\"v2\""),
+            Response::ok(v2),
+        ]);
+        let url = server.url("stable.json");
+        let fetch = || fetch_json_cached::<VersionJSON<()>>(&client(), &url, &dir).unwrap();
+        let (cached_json, cached_etag) = cached_paths(&dir, &url);
+
+        // the first response is cached with its ETag
+        assert_eq!(fetch().version(), &Version::new(1, 0, 0));
+        assert_eq!(fs::read_to_string(&cached_etag).unwrap(), "\"v1\"");
+        assert_eq!(fs::read_to_string(&cached_json).unwrap(), v1);
+        // not modified, the cached version json is used
+        assert_eq!(fetch().version(), &Version::new(1, 0, 0));
+        // modified, the cache is replaced
+        assert_eq!(fetch().version(), &Version::new(2, 0, 0));
+        assert_eq!(fs::read_to_string(&cached_etag).unwrap(), "\"v2\"");
+        // the cache is removed, no ETag is sent
+        fs::remove_file(&cached_json).unwrap();
+        assert_eq!(fetch().version(), &Version::new(2, 0, 0));
+
+        let requests = server.requests();
+        let if_none_match = |i: usize| {
+            requests[i].lines().find_map(|line| {
+                line.to_lowercase()
+                    .strip_prefix("if-none-match: ")
+                    .map(str::to_owned)
+            })
+        };
+        assert_eq!(if_none_match(0), None);
+        assert_eq!(if_none_match(1).as_deref(), Some("\"v1\""));
+        assert_eq!(if_none_match(2).as_deref(), Some("\"v1\""));
+        assert_eq!(if_none_match(3), None);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn fetch_cached_by_url() {
+        let dir = std::env::temp_dir().join("maa-test-version-json-urls");
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir).unwrap();
+        }
+        let cli = r#"{"version": "v1.0.0", "details": null}"#;
+        let core = r#"{"version": "v5.0.0", "details": null}"#;
+        let server = Server::new([
+            Response::ok(cli).header("ETag", "\"cli\""),
+            Response::ok(core).header("ETag", "\"core\""),
+            Response::status(304),
+            Response::status(304),
+        ]);
+        let fetch = |name: &str| {
+            fetch_json_cached::<VersionJSON<()>>(&client(), &server.url(name), &dir)
+                .unwrap()
+                .version()
+                .clone()
+        };
+
+        assert_eq!(fetch("cli.json"), Version::new(1, 0, 0));
+        assert_eq!(fetch("core.json"), Version::new(5, 0, 0));
+        // each url revalidates its own cache
+        assert_eq!(fetch("cli.json"), Version::new(1, 0, 0));
+        assert_eq!(fetch("core.json"), Version::new(5, 0, 0));
+
+        let requests = server.requests();
+        assert!(requests[2]
+            .to_lowercase()
+            .contains("if-none-match: \"cli\""));
+        assert!(requests[3]
+            .to_lowercase()
+            .contains("if-none-match: \"core\""));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn fetch_cached_missing_body() {
+        let dir = std::env::temp_dir().join("maa-test-version-json-etag-missing");
+        fs::create_dir_all(&dir).unwrap();
+        // a corrupted cache, the server says not modified, but the body can not be parsed
+        let server = Server::new([
+            Response::status(304),
+            Response::ok(r#"{"version": "v1.0.0", "details": null}"#),
+        ]);
+        let url = server.url("stable.json");
+        let (cached_json, cached_etag) = cached_paths(&dir, &url);
+        fs::write(cached_json, "not json").unwrap();
+        fs::write(cached_etag, "\"v1\"").unwrap();
+        let version_json = fetch_json_cached::<VersionJSON<()>>(&client(), &url, &dir).unwrap();
+        assert_eq!(version_json.version(), &Version::new(1, 0, 0));
+        assert_eq!(server.requests().len(), 2);
+        assert!(!server.requests()[1]
+            .to_lowercase()
+            .contains("if-none-match"));
+
+        // a misbehaving server always says not modified, retry only once
+        let server = Server::new([
+            Response::status(304),
+            Response::status(304),
+            Response::status(304),
+        ]);
+        let url = server.url("stable.json");
+        let (cached_json, cached_etag) = cached_paths(&dir, &url);
+        fs::write(cached_json, "not json").unwrap();
+        fs::write(cached_etag, "\"v1\"").unwrap();
+        assert!(fetch_json_cached::<VersionJSON<()>>(&client(), &url, &dir).is_err());
+        assert_eq!(server.requests().len(), 2);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn fetch_local() {
+        let dir = std::env::temp_dir().join("maa-test-version-json-local");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("stable.json");
+        std::fs::write(&path, r#"{"version": "v1.0.0", "details": null}"#).unwrap();
+
+        let url = reqwest::Url::from_file_path(&path).unwrap();
+        let version_json: VersionJSON<()> = fetch(url.as_str(), IpFamily::Auto).unwrap();
+        assert_eq!(version_json.version(), &Version::new(1, 0, 0));
+
+        let version_json: VersionJSON<()> = fetch(path.to_str().unwrap(), IpFamily::Auto).unwrap();
+        assert_eq!(version_json.version(), &Version::new(1, 0, 0));
+
+        let err = fetch::<()>(dir.join("beta.json").to_str().unwrap(), IpFamily::Auto).unwrap_err();
+        assert!(err.to_string().contains("beta.json"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn classify_status() {
+        let server = Server::new([Response::status(404)]);
+        let url = server.url("stable.json");
+        let (kind, message) = fetch_error(&url);
+        assert_eq!(kind, FetchError::Status(404));
+        assert!(message.contains(&url));
+        assert!(message.contains("--api-url"));
+        assert!(message.contains("MAA_CLI_API"));
+    }
+
+    #[test]
+    fn classify_parse() {
+        let server = Server::new([Response::ok("<html></html>")]);
+        let (kind, message) = fetch_error(&server.url("stable.json"));
+        assert_eq!(kind, FetchError::Parse);
+        assert!(message.contains("stale mirror"));
+    }
+
+    #[test]
+    fn classify_connect() {
+        // Bind and drop a listener to get a port which is not listening
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let (kind, _) = fetch_error(&format!("http://127.0.0.1:{port}/stable.json"));
+        assert_eq!(kind, FetchError::Connect);
+    }
+
+    #[test]
+    fn classify_timeout() {
+        // A server which accepts connections but never responds
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/stable.json", listener.local_addr().unwrap());
+        let (kind, _) = fetch_error(&url);
+        assert_eq!(kind, FetchError::Timeout);
+    }
+
+    #[cfg(feature = "cli_installer")]
     #[test]
     fn test_can_update() {
         fn can_update(remote: &str, current: &str, expected: bool) {
@@ -0,0 +1,544 @@
+//! Locate the MaaCore library and resources.
+//!
+//! The library is searched in the following order, the first one which exists,
+//! matches the architecture and can be loaded wins:
+//!
+//! 1. `core.library_path` in `cli.toml`;
+//! 2. the `MAA_CORE_DIR` environment variable;
+//! 3. the library directory managed by the installer;
+//! 4. directories relative to the executable, see [`crate::dirs::Dirs::find_library`];
+//! 5. the default search paths of the system library loader.
+//!
+//! Resources are searched in the same order, with `core.resource_path` in `cli.toml`
+//! and the `resource` directory in `MAA_CORE_DIR`.
+
+use crate::{
+    config::cli::cli_config,
+    dirs::{self, maa_lib_name},
+};
+
+use std::{
+    borrow::Cow,
+    env::{current_exe, var_os},
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use dunce::canonicalize;
+use log::debug;
+use serde::Serialize;
+
+/// Environment variable of the directory containing MaaCore
+pub const CORE_DIR_ENV: &str = "MAA_CORE_DIR";
+
+/// Where a path of MaaCore comes from
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Source {
+    Config,
+    Env,
+    Installer,
+    Executable,
+    System,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Config => write!(f, "cli.toml"),
+            Self::Env => write!(f, "{CORE_DIR_ENV}"),
+            Self::Installer => write!(f, "installer"),
+            Self::Executable => write!(f, "next to executable"),
+            Self::System => write!(f, "system"),
+        }
+    }
+}
+
+/// A located path of MaaCore with its source
+#[cfg_attr(test, derive(Debug, PartialEq))]
+#[derive(Serialize, Clone)]
+pub struct Located {
+    source: Source,
+    path: PathBuf,
+}
+
+impl Located {
+    #[cfg_attr(not(feature = "cli_installer"), allow(dead_code))]
+    pub fn source(&self) -> Source {
+        self.source
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn into_path(self) -> PathBuf {
+        self.path
+    }
+}
+
+impl fmt::Display for Located {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ({})", self.path.display(), self.source)
+    }
+}
+
+/// Why a candidate of the library is rejected
+#[cfg_attr(test, derive(PartialEq))]
+#[derive(Debug)]
+pub enum Reason {
+    Missing,
+    WrongArch(String),
+    LoadError(String),
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Missing => write!(f, "file not found"),
+            Self::WrongArch(arch) => write!(
+                f,
+                "wrong architecture, built for {arch} but expected {}",
+                std::env::consts::ARCH
+            ),
+            Self::LoadError(err) => write!(f, "failed to load: {err}"),
+        }
+    }
+}
+
+/// Error when no candidate of the library can be loaded, with the reason of each candidate
+#[derive(Debug)]
+pub struct LoadError(Vec<(Source, PathBuf, Reason)>);
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "MaaCore not found, tried:")?;
+        for (source, path, reason) in &self.0 {
+            write!(f, "\n  {} ({source}): {reason}", path.display())?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+/// Candidate directories of the library in order of priority.
+fn library_candidates() -> Vec<(Source, PathBuf)> {
+    candidates(
+        cli_config().core_paths().library_path(),
+        var_os(CORE_DIR_ENV).map(PathBuf::from),
+        dirs::library(),
+        exe_library_dirs(),
+    )
+}
+
+fn candidates(
+    config: Option<PathBuf>,
+    env: Option<PathBuf>,
+    installer: &Path,
+    exe_dirs: Vec<PathBuf>,
+) -> Vec<(Source, PathBuf)> {
+    let mut candidates = Vec::new();
+    candidates.extend(config.map(|dir| (Source::Config, dir)));
+    candidates.extend(env.map(|dir| (Source::Env, dir)));
+    candidates.push((Source::Installer, installer.to_path_buf()));
+    for dir in exe_dirs {
+        if candidates.iter().all(|(_, d)| d != &dir) {
+            candidates.push((Source::Executable, dir));
+        }
+    }
+    candidates
+}
+
+/// Directories relative to the executable where the library may be found.
+///
+/// The directory of the executable and the `lib` directory in its parent directory,
+/// for both the executable and the link target if it is a symbolic link.
+fn exe_library_dirs() -> Vec<PathBuf> {
+    let Ok(exe) = current_exe() else {
+        return Vec::new();
+    };
+    let mut exe_dirs: Vec<PathBuf> = Vec::new();
+    for exe in [canonicalize(&exe).ok(), Some(exe)].into_iter().flatten() {
+        if let Some(dir) = exe.parent() {
+            if !exe_dirs.iter().any(|d| d == dir) {
+                exe_dirs.push(dir.to_path_buf());
+            }
+        }
+    }
+
+    exe_dirs
+        .into_iter()
+        .flat_map(|dir| {
+            let lib_dir = dir.parent().map(|parent| parent.join("lib"));
+            std::iter::once(dir).chain(lib_dir)
+        })
+        .collect()
+}
+
+/// Check whether the library in the directory exists and matches current architecture.
+///
+/// Return the path of the library file.
+fn check(dir: &Path) -> Result<PathBuf, Reason> {
+    let file = dir.join(maa_lib_name());
+    if !file.exists() {
+        return Err(Reason::Missing);
+    }
+    if let Some(arch) = foreign_arch(&file) {
+        return Err(Reason::WrongArch(arch));
+    }
+    Ok(file)
+}
+
+/// Find the library directory without loading it.
+pub fn find_library() -> Option<Located> {
+    library_candidates()
+        .into_iter()
+        .find(|(_, dir)| check(dir).is_ok())
+        .map(|(source, path)| Located { source, path })
+}
+
+/// Load the library by `load` from the first candidate which succeeds.
+///
+/// The `load` function is called with the directory and the path of the library,
+/// for the system library loader, the directory is `None` and the path is the library name.
+/// Return the directory of the library, or the library name if loaded by the system loader.
+pub fn load_library<F>(load: F) -> Result<Located, LoadError>
+where
+    F: FnMut(Option<&Path>, &Path) -> Result<(), String>,
+{
+    load_from(library_candidates(), load)
+}
+
+fn load_from<F>(candidates: Vec<(Source, PathBuf)>, mut load: F) -> Result<Located, LoadError>
+where
+    F: FnMut(Option<&Path>, &Path) -> Result<(), String>,
+{
+    let mut tried = Vec::new();
+    for (source, dir) in candidates {
+        match check(&dir).and_then(|file| load(Some(&dir), &file).map_err(Reason::LoadError)) {
+            Ok(()) => return Ok(Located { source, path: dir }),
+            Err(reason) => {
+                debug!("Skip MaaCore in {} ({source}): {reason}", dir.display());
+                tried.push((source, dir, reason));
+            }
+        }
+    }
+
+    let name = Path::new(maa_lib_name());
+    match load(None, name) {
+        Ok(()) => Ok(Located {
+            source: Source::System,
+            path: name.to_path_buf(),
+        }),
+        Err(err) => {
+            tried.push((Source::System, name.to_path_buf(), Reason::LoadError(err)));
+            Err(LoadError(tried))
+        }
+    }
+}
+
+/// Find the resource directory.
+pub fn find_resource() -> Option<Located> {
+    resource_from(
+        cli_config().core_paths().resource_path(),
+        var_os(CORE_DIR_ENV).map(|dir| PathBuf::from(dir).join("resource")),
+        dirs::find_resource(),
+    )
+}
+
+fn resource_from(
+    config: Option<PathBuf>,
+    env: Option<PathBuf>,
+    found: Option<Cow<Path>>,
+) -> Option<Located> {
+    let located = |source, path| Some(Located { source, path });
+    if let Some(path) = config.filter(|path| path.exists()) {
+        return located(Source::Config, path);
+    }
+    if let Some(path) = env.filter(|path| path.exists()) {
+        return located(Source::Env, path);
+    }
+    let path = found?.into_owned();
+    if path == dirs::resource() {
+        located(Source::Installer, path)
+    } else {
+        located(Source::Executable, path)
+    }
+}
+
+/// Architecture of the library if it is built for a different one than current,
+/// `None` if it matches or can not be determined.
+fn foreign_arch(file: &Path) -> Option<String> {
+    use std::io::Read;
+
+    let mut header = Vec::with_capacity(4096);
+    std::fs::File::open(file)
+        .ok()?
+        .take(4096)
+        .read_to_end(&mut header)
+        .ok()?;
+    let (format, machine) = binary_machine(&header)?;
+    let expected = expected_machine(format, std::env::consts::ARCH)?;
+    (machine != expected).then(|| {
+        ["x86_64", "aarch64", "x86"]
+            .into_iter()
+            .find(|arch| expected_machine(format, arch) == Some(machine))
+            .map_or_else(|| format!("unknown ({machine:#x})"), str::to_owned)
+    })
+}
+
+#[derive(Clone, Copy)]
+enum Format {
+    Elf,
+    MachO,
+    Pe,
+}
+
+/// Format and machine type in the header of an executable or shared library
+fn binary_machine(header: &[u8]) -> Option<(Format, u32)> {
+    let u16_at = |offset: usize, big_endian: bool| {
+        let bytes = header.get(offset..offset + 2)?.try_into().ok()?;
+        Some(if big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        })
+    };
+    let u32_le_at = |offset: usize| {
+        let bytes = header.get(offset..offset + 4)?.try_into().ok()?;
+        Some(u32::from_le_bytes(bytes))
+    };
+
+    match header.get(..4)? {
+        [0x7f, b'E', b'L', b'F'] => {
+            let big_endian = *header.get(5)? == 2;
+            Some((Format::Elf, u16_at(18, big_endian)?.into()))
+        }
+        // 64 bit Mach-O in little endian, universal binaries are not checked
+        [0xcf, 0xfa, 0xed, 0xfe] => Some((Format::MachO, u32_le_at(4)?)),
+        [b'M', b'Z', ..] => {
+            let offset = u32_le_at(0x3c)? as usize;
+            if header.get(offset..offset + 4)? != b"PE\0\0" {
+                return None;
+            }
+            Some((Format::Pe, u16_at(offset + 4, false)?.into()))
+        }
+        _ => None,
+    }
+}
+
+fn expected_machine(format: Format, arch: &str) -> Option<u32> {
+    match (format, arch) {
+        (Format::Elf, "x86_64") => Some(62),
+        (Format::Elf, "aarch64") => Some(183),
+        (Format::Elf, "x86") => Some(3),
+        (Format::MachO, "x86_64") => Some(0x0100_0007),
+        (Format::MachO, "aarch64") => Some(0x0100_000c),
+        (Format::MachO, "x86") => Some(7),
+        (Format::Pe, "x86_64") => Some(0x8664),
+        (Format::Pe, "aarch64") => Some(0xaa64),
+        (Format::Pe, "x86") => Some(0x14c),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::dirs::Ensure;
+
+    /// A minimal ELF header of given machine type
+    fn elf(machine: u16) -> Vec<u8> {
+        let mut header = vec![0; 64];
+        header[..4].copy_from_slice(b"\x7fELF");
+        header[4] = 2;
+        header[5] = 1;
+        header[18..20].copy_from_slice(&machine.to_le_bytes());
+        header
+    }
+
+    fn current_machine() -> u16 {
+        expected_machine(Format::Elf, std::env::consts::ARCH).map_or(62, |m| m as u16)
+    }
+
+    fn foreign_machine() -> u16 {
+        if current_machine() == 183 {
+            62
+        } else {
+            183
+        }
+    }
+
+    #[test]
+    fn candidates_order() {
+        let exe_dirs = vec![PathBuf::from("/opt/maa/bin"), PathBuf::from("/data/lib")];
+        let dirs = candidates(
+            Some("/config".into()),
+            Some("/env".into()),
+            Path::new("/data/lib"),
+            exe_dirs,
+        );
+        assert_eq!(
+            dirs,
+            [
+                (Source::Config, PathBuf::from("/config")),
+                (Source::Env, PathBuf::from("/env")),
+                (Source::Installer, PathBuf::from("/data/lib")),
+                (Source::Executable, PathBuf::from("/opt/maa/bin")),
+            ]
+        );
+
+        let dirs = candidates(None, None, Path::new("/data/lib"), Vec::new());
+        assert_eq!(dirs, [(Source::Installer, PathBuf::from("/data/lib"))]);
+    }
+
+    #[test]
+    fn load_in_order() {
+        let dir = std::env::temp_dir().join("maa-test-locate");
+        dir.as_path().ensure_clean().unwrap();
+
+        let lib_name = maa_lib_name();
+        let config = dir.join("config");
+        let env = dir.join("env");
+        let installer = dir.join("installer");
+        let exe = dir.join("exe");
+        for (dir, machine) in [
+            (&env, foreign_machine()),
+            (&installer, current_machine()),
+            (&exe, current_machine()),
+        ] {
+            dir.as_path().ensure().unwrap();
+            std::fs::write(dir.join(lib_name), elf(machine)).unwrap();
+        }
+        let candidates = || {
+            candidates(
+                Some(config.clone()),
+                Some(env.clone()),
+                &installer,
+                vec![exe.clone()],
+            )
+        };
+
+        // the first valid candidate is loaded
+        let mut loaded = Vec::new();
+        let located = load_from(candidates(), |dir, path| {
+            loaded.push(path.to_path_buf());
+            assert_eq!(dir.unwrap().join(lib_name), path);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(located.source(), Source::Installer);
+        assert_eq!(located.path(), installer);
+        assert_eq!(loaded, [installer.join(lib_name)]);
+
+        // fallback to the next candidate if failed to load
+        let located = load_from(candidates(), |dir, _| {
+            if dir == Some(installer.as_path()) {
+                Err("broken".to_owned())
+            } else {
+                Ok(())
+            }
+        })
+        .unwrap();
+        assert_eq!(located.source(), Source::Executable);
+
+        // fallback to the system loader
+        let located = load_from(candidates(), |dir, _| match dir {
+            Some(_) => Err("broken".to_owned()),
+            None => Ok(()),
+        })
+        .unwrap();
+        assert_eq!(located.source(), Source::System);
+        assert_eq!(located.path(), Path::new(lib_name));
+
+        // all candidates are reported with reasons
+        let err = load_from(candidates(), |_, _| Err("broken".to_owned())).unwrap_err();
+        let reasons: Vec<_> = err
+            .0
+            .iter()
+            .map(|(source, _, reason)| (*source, reason))
+            .collect();
+        assert_eq!(reasons[0], (Source::Config, &Reason::Missing));
+        assert!(matches!(reasons[1], (Source::Env, Reason::WrongArch(_))));
+        assert_eq!(
+            reasons[2],
+            (Source::Installer, &Reason::LoadError("broken".to_owned()))
+        );
+        assert_eq!(reasons[3].0, Source::Executable);
+        assert_eq!(reasons[4].0, Source::System);
+        let message = err.to_string();
+        assert!(message.contains(&config.display().to_string()));
+        assert!(message.contains("file not found"));
+        assert!(message.contains("wrong architecture"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn resource_order() {
+        let dir = std::env::temp_dir().join("maa-test-locate-resource");
+        dir.as_path().ensure_clean().unwrap();
+        let config = dir.join("config");
+        let env = dir.join("env");
+        let exe = dir.join("exe");
+        for dir in [&env, &exe] {
+            dir.as_path().ensure().unwrap();
+        }
+
+        let found = || Some(Cow::Borrowed(exe.as_path()));
+        let located = resource_from(Some(config.clone()), Some(env.clone()), found()).unwrap();
+        assert_eq!(located.source(), Source::Env);
+        assert_eq!(located.path(), env);
+
+        config.as_path().ensure().unwrap();
+        let located = resource_from(Some(config.clone()), Some(env.clone()), found()).unwrap();
+        assert_eq!(located.source(), Source::Config);
+
+        let located = resource_from(None, None, found()).unwrap();
+        assert_eq!(located.source(), Source::Executable);
+        assert_eq!(located.path(), exe);
+
+        let located = resource_from(None, None, Some(dirs::resource().into())).unwrap();
+        assert_eq!(located.source(), Source::Installer);
+
+        assert!(resource_from(None, None, None).is_none());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64", target_arch = "x86"))]
+    fn arch() {
+        let dir = std::env::temp_dir().join("maa-test-locate-arch");
+        dir.as_path().ensure_clean().unwrap();
+
+        let file = dir.join("lib");
+        std::fs::write(&file, elf(current_machine())).unwrap();
+        assert_eq!(foreign_arch(&file), None);
+        std::fs::write(&file, elf(foreign_machine())).unwrap();
+        let foreign = if foreign_machine() == 62 {
+            "x86_64"
+        } else {
+            "aarch64"
+        };
+        assert_eq!(foreign_arch(&file).as_deref(), Some(foreign));
+        std::fs::write(&file, elf(0xffff)).unwrap();
+        assert_eq!(foreign_arch(&file).as_deref(), Some("unknown (0xffff)"));
+
+        // unknown formats are not checked
+        std::fs::write(&file, "not a library").unwrap();
+        assert_eq!(foreign_arch(&file), None);
+
+        // PE header
+        let mut pe = vec![0; 0x100];
+        pe[..2].copy_from_slice(b"MZ");
+        pe[0x3c..0x40].copy_from_slice(&0x80u32.to_le_bytes());
+        pe[0x80..0x84].copy_from_slice(b"PE\0\0");
+        pe[0x84..0x86].copy_from_slice(&0xaa64u16.to_le_bytes());
+        assert!(matches!(binary_machine(&pe), Some((Format::Pe, 0xaa64))));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,4 +1,13 @@
-use std::{io::Write, path::PathBuf};
+use std::{
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{Mutex, PoisonError},
+};
+
+use anyhow::{bail, Context};
+use log::LevelFilter;
+use tracing_appender::non_blocking::{NonBlockingBuilder, WorkerGuard};
 
 #[derive(clap::Args)]
 pub struct Args {
@@ -24,6 +33,19 @@ pub struct Args {
     /// `$(maa dir log)/YYYY/MM/DD/HH:MM:SS.log`.
     #[arg(long, global = true, require_equals = true, value_name = "PATH")]
     log_file: Option<Option<PathBuf>>,
+    /// Rotate the log file when it grows larger than 10 MiB
+    ///
+    /// The old log files are renamed to `<PATH>.1`, `<PATH>.2` and so on,
+    /// where `<PATH>.1` is the newest one, and at most 5 old log files are kept.
+    #[arg(long, global = true, requires = "log_file")]
+    log_rotate: bool,
+    /// Do not write the log of commands running tasks to a file
+    ///
+    /// By default, commands running tasks, e.g. `maa run` and `maa fight`, also write
+    /// their log to `$(maa dir log)/runs/<TIME>_<TASK>.log` at the level `MAA_RUN_LOG`
+    /// (debug by default), which can be found by `maa log` after the run.
+    #[arg(long, global = true)]
+    no_run_log: bool,
 }
 
 impl Args {
@@ -55,30 +77,259 @@ impl Args {
         log_path(self.log_file)
     }
 
-    pub fn init_logger(self) -> anyhow::Result<()> {
+    /// Initialize the logger, `run` is the name of the task run by the command, if any.
+    ///
+    /// The log file given by `--log-file` is written in background, call [`finish`] to flush it
+    /// before exiting. If `json` is true, i.e. `--output json` is given, each record is written
+    /// to the log file as a JSON object in one line. The log of a run is also written to a new
+    /// file in the run log directory, see [`run_log_dir`], and old run logs are removed by
+    /// [`RunLogRetention`].
+    pub fn init_logger(self, run: Option<&str>, json: bool) -> anyhow::Result<()> {
         let mut builder = env_logger::Builder::new();
 
         builder.filter_level(self.to_filter());
-        builder.format(LogPrefix::from_env().format(self.log_file.is_some()));
+        if json && self.log_file.is_some() {
+            builder.format(json_format);
+        } else {
+            builder.format(LogPrefix::from_env().format(self.log_file.is_some()));
+        }
 
         if let Some(path) = log_path(self.log_file) {
             if let Some(dir) = path.parent() {
                 use crate::dirs::Ensure;
                 dir.ensure()?;
             }
-            let file = std::fs::OpenOptions::new()
-                .create(true)
-                .append(true)
-                .open(path)?;
-            builder.target(env_logger::Target::Pipe(Box::new(file)));
+            let target: Box<dyn Write + Send> = if self.log_rotate {
+                Box::new(RotatingFile::open(path, ROTATE_SIZE, ROTATE_BACKUPS)?)
+            } else {
+                Box::new(open_append(&path)?)
+            };
+            // Do not drop records when the writer falls behind, the log file should be complete
+            let (writer, guard) = NonBlockingBuilder::default().lossy(false).finish(target);
+            *FILE_WRITER.lock().unwrap_or_else(PoisonError::into_inner) = Some(guard);
+            builder.target(env_logger::Target::Pipe(Box::new(writer)));
         }
 
-        builder.init();
+        let console = builder.build();
+        let run_level = run_log_level();
+        let run = run.filter(|_| !self.no_run_log && run_level != LevelFilter::Off);
+        let Some(run) = run else {
+            log::set_max_level(console.filter());
+            log::set_boxed_logger(Box::new(console))?;
+            return Ok(());
+        };
+
+        use crate::dirs::Ensure;
+        let dir = run_log_dir();
+        dir.as_path().ensure()?;
+        let path = dir.join(run_log_name(chrono::Local::now(), run));
+        let file = open_append(&path)
+            .with_context(|| format!("Failed to open run log {}", path.display()))?;
+        let removed = RunLogRetention::from_env().prune(&dir)?;
+
+        log::set_max_level(console.filter().max(run_level));
+        log::set_boxed_logger(Box::new(TeeLogger {
+            console,
+            file: Mutex::new(file),
+            level: run_level,
+        }))?;
+
+        log::debug!("Run log: {}", path.display());
+        for path in removed {
+            log::debug!("Removed old run log {}", path.display());
+        }
 
         Ok(())
     }
 }
 
+/// Guard of the thread writing the log file given by `--log-file`, see [`finish`]
+static FILE_WRITER: Mutex<Option<WorkerGuard>> = Mutex::new(None);
+
+/// Flush the log file given by `--log-file` and stop the thread writing it
+///
+/// Records logged after this are not written to the log file.
+pub fn finish() {
+    drop(
+        FILE_WRITER
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take(),
+    );
+}
+
+/// Directory of the log files of runs, i.e. `runs` in the log directory
+pub fn run_log_dir() -> PathBuf {
+    crate::dirs::log().join(RUN_LOG_DIR)
+}
+
+const RUN_LOG_DIR: &str = "runs";
+
+/// Level of the run log, given by `MAA_RUN_LOG`, e.g. `info` or `off`, debug by default
+fn run_log_level() -> LevelFilter {
+    std::env::var_os("MAA_RUN_LOG")
+        .and_then(|s| s.to_str().and_then(|s| s.parse().ok()))
+        .unwrap_or(LevelFilter::Debug)
+}
+
+/// File name of the log of a run, e.g. `2024-01-01_04-00-00_daily.log`
+fn run_log_name(time: chrono::DateTime<chrono::Local>, run: &str) -> String {
+    let run: String = run
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{}_{run}.log", time.format("%Y-%m-%d_%H-%M-%S"))
+}
+
+/// Log files in given directory from the newest to the oldest.
+///
+/// Files are named by their start time, so they are sorted by name.
+fn run_logs_in(dir: &Path) -> std::io::Result<Vec<(PathBuf, u64)>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut logs = Vec::new();
+    for entry in dir.read_dir()? {
+        let entry = entry?;
+        let path = entry.path();
+        let metadata = entry.metadata()?;
+        if metadata.is_file() && path.extension().is_some_and(|ext| ext == "log") {
+            logs.push((path, metadata.len()));
+        }
+    }
+    logs.sort_unstable_by(|(a, _), (b, _)| b.cmp(a));
+    Ok(logs)
+}
+
+/// Default number of run log files to keep
+const RUN_LOG_KEEP: usize = 30;
+
+/// Default total size of run log files to keep
+const RUN_LOG_MAX_SIZE: u64 = 100 * 1024 * 1024;
+
+/// Number and total size of run log files to keep
+///
+/// They can be changed by `MAA_RUN_LOG_KEEP` and `MAA_RUN_LOG_MAX_SIZE`, e.g. `10` and `50M`.
+#[cfg_attr(test, derive(Debug, PartialEq))]
+struct RunLogRetention {
+    keep: usize,
+    max_size: u64,
+}
+
+impl RunLogRetention {
+    fn from_env() -> Self {
+        let env = |name: &str| std::env::var(name).ok();
+        Self {
+            keep: env("MAA_RUN_LOG_KEEP")
+                .and_then(|s| s.trim().parse().ok())
+                .unwrap_or(RUN_LOG_KEEP),
+            max_size: env("MAA_RUN_LOG_MAX_SIZE")
+                .and_then(|s| crate::cleanup::parse_size(&s).ok())
+                .unwrap_or(RUN_LOG_MAX_SIZE),
+        }
+    }
+
+    /// Remove the oldest log files in given directory, until at most `keep` files are left
+    /// and their total size is at most `max_size`, return the removed files.
+    ///
+    /// The newest file, i.e. the log of the current run, is never removed.
+    fn prune(&self, dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+        let mut total = 0;
+        let mut removed = Vec::new();
+        for (index, (path, size)) in run_logs_in(dir)?.into_iter().enumerate() {
+            total += size;
+            if index > 0 && (index >= self.keep || total > self.max_size) {
+                std::fs::remove_file(&path)?;
+                removed.push(path);
+            }
+        }
+        Ok(removed)
+    }
+}
+
+/// The log of the latest run, if any
+pub fn latest_run_log() -> std::io::Result<Option<PathBuf>> {
+    Ok(run_logs_in(&run_log_dir())?
+        .into_iter()
+        .next()
+        .map(|(path, _)| path))
+}
+
+/// Print the path of the latest run log, or its last `tail` lines
+pub fn show_latest(tail: Option<usize>) -> anyhow::Result<()> {
+    let Some(path) = latest_run_log()? else {
+        bail!("No run log found in {}", run_log_dir().display());
+    };
+    match tail {
+        Some(lines) => {
+            let content = std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            let all: Vec<&str> = content.lines().collect();
+            for line in &all[all.len().saturating_sub(lines)..] {
+                println!("{line}");
+            }
+        }
+        None => println!("{}", path.display()),
+    }
+    Ok(())
+}
+
+/// A logger writing records to the console logger and records of maa-cli at `level` to a file.
+///
+/// Records of dependencies are written to the file only if they are warnings or errors,
+/// so the file is not flooded by debug logs of e.g. the HTTP client.
+struct TeeLogger {
+    console: env_logger::Logger,
+    file: Mutex<File>,
+    level: LevelFilter,
+}
+
+impl TeeLogger {
+    fn file_enabled(&self, metadata: &log::Metadata) -> bool {
+        let level = if metadata.target().starts_with("maa") {
+            self.level
+        } else {
+            self.level.min(LevelFilter::Warn)
+        };
+        metadata.level() <= level
+    }
+}
+
+impl log::Log for TeeLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        self.console.enabled(metadata) || self.file_enabled(metadata)
+    }
+
+    fn log(&self, record: &log::Record) {
+        self.console.log(record);
+        if self.file_enabled(record.metadata()) {
+            if let Ok(mut file) = self.file.lock() {
+                // Failing to write the run log should not break the run
+                let _ = writeln!(
+                    file,
+                    "[{} {:<5}] {}",
+                    chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
+                    record.level(),
+                    record.args()
+                );
+            }
+        }
+    }
+
+    fn flush(&self) {
+        self.console.flush();
+        if let Ok(mut file) = self.file.lock() {
+            let _ = file.flush();
+        }
+    }
+}
+
 fn log_path(path: Option<Option<PathBuf>>) -> Option<PathBuf> {
     path.map(|path| {
         path.unwrap_or_else(|| {
@@ -93,6 +344,96 @@ fn log_path(path: Option<Option<PathBuf>>) -> Option<PathBuf> {
     })
 }
 
+fn open_append(path: &Path) -> std::io::Result<File> {
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+}
+
+/// Size of log file to rotate by `--log-rotate`
+const ROTATE_SIZE: u64 = 10 * 1024 * 1024;
+
+/// Number of old log files kept by `--log-rotate`
+const ROTATE_BACKUPS: usize = 5;
+
+/// A log file which is rotated when it grows larger than `max_size`.
+///
+/// Old log files are named `<path>.1` to `<path>.<backups>` from the newest to the oldest,
+/// and the oldest one is removed when rotating.
+///
+/// The rolling appender of `tracing-appender` only rotates files by time, so the file is rotated
+/// by size here and written in background by the non-blocking writer of `tracing-appender`.
+struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_size: u64,
+    backups: usize,
+}
+
+impl RotatingFile {
+    fn open(path: PathBuf, max_size: u64, backups: usize) -> std::io::Result<Self> {
+        let file = open_append(&path)?;
+        let size = file.metadata()?.len();
+        let mut file = Self {
+            path,
+            file,
+            size,
+            max_size,
+            backups,
+        };
+        if file.size >= max_size {
+            file.rotate()?;
+        }
+        Ok(file)
+    }
+
+    fn backup(&self, index: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{index}"));
+        path.into()
+    }
+
+    fn rotate(&mut self) -> std::io::Result<()> {
+        self.file.flush()?;
+        let oldest = self.backup(self.backups);
+        if oldest.exists() {
+            std::fs::remove_file(oldest)?;
+        }
+        if self.backups == 0 {
+            std::fs::remove_file(&self.path)?;
+        } else {
+            for index in (1..self.backups).rev() {
+                let backup = self.backup(index);
+                if backup.exists() {
+                    std::fs::rename(backup, self.backup(index + 1))?;
+                }
+            }
+            std::fs::rename(&self.path, self.backup(1))?;
+        }
+        self.file = open_append(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        // Records are written at once, so they are never split into two files
+        if self.size > 0 && self.size + buf.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+}
+
 /// Whether or not to print log prefix [YYYY-MM-DD HH:MM:SS LEVEL]
 #[cfg_attr(test, derive(Debug, PartialEq))]
 #[derive(Clone, Copy, Default)]
@@ -159,6 +500,21 @@ fn plain_format(buf: &mut env_logger::fmt::Formatter, record: &log::Record) -> s
     writeln!(buf, "{}", record.args())
 }
 
+fn json_format(buf: &mut env_logger::fmt::Formatter, record: &log::Record) -> std::io::Result<()> {
+    writeln!(buf, "{}", json_line(chrono::Local::now(), record))
+}
+
+/// A record as a JSON object in one line, newlines in the message are escaped
+fn json_line(time: chrono::DateTime<chrono::Local>, record: &log::Record) -> String {
+    serde_json::json!({
+        "timestamp": time.to_rfc3339_opts(chrono::SecondsFormat::Secs, false),
+        "level": record.level().as_str(),
+        "target": record.target(),
+        "message": record.args().to_string(),
+    })
+    .to_string()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -236,6 +592,223 @@ mod tests {
                 .log_file()
                 .is_some_and(|x| x == Path::new("path")));
         }
+
+        #[test]
+        fn log_rotate() {
+            use crate::command::CLI;
+            use clap::Parser;
+
+            assert!(!parse_from(["maa", "list"]).log.log_rotate);
+            assert!(
+                parse_from(["maa", "list", "--log-file=path", "--log-rotate"])
+                    .log
+                    .log_rotate
+            );
+            assert!(CLI::try_parse_from(["maa", "list", "--log-rotate"]).is_err());
+        }
+
+        #[test]
+        fn no_run_log() {
+            assert!(!parse_from(["maa", "run", "daily"]).log.no_run_log);
+            assert!(
+                parse_from(["maa", "run", "daily", "--no-run-log"])
+                    .log
+                    .no_run_log
+            );
+        }
+    }
+
+    #[test]
+    fn rotating_file() {
+        use crate::dirs::Ensure;
+
+        let dir = std::env::temp_dir().join("maa-test-log-rotate");
+        dir.as_path().ensure_clean().unwrap();
+        let path = dir.join("maa.log");
+        let read = |path: &Path| std::fs::read_to_string(path).unwrap();
+
+        let mut file = RotatingFile::open(path.clone(), 10, 2).unwrap();
+        file.write_all(b"first\n").unwrap();
+        file.write_all(b"second\n").unwrap();
+        assert_eq!(read(&path), "second\n");
+        assert_eq!(read(&file.backup(1)), "first\n");
+
+        file.write_all(b"third\n").unwrap();
+        file.write_all(b"fourth\n").unwrap();
+        assert_eq!(read(&path), "fourth\n");
+        assert_eq!(read(&file.backup(1)), "third\n");
+        assert_eq!(read(&file.backup(2)), "second\n");
+        assert!(!file.backup(3).exists());
+
+        // a record larger than the limit is written to a new file as a whole
+        file.write_all(b"a long long record\n").unwrap();
+        assert_eq!(read(&path), "a long long record\n");
+        drop(file);
+
+        // an existing file is appended, and rotated at first if it is too large
+        let file = RotatingFile::open(path.clone(), 100, 2).unwrap();
+        assert_eq!(file.size, 19);
+        drop(file);
+        let file = RotatingFile::open(path.clone(), 10, 2).unwrap();
+        assert_eq!(file.size, 0);
+        assert_eq!(read(&file.backup(1)), "a long long record\n");
+        assert_eq!(read(&file.backup(2)), "fourth\n");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn json_lines() {
+        use chrono::TimeZone;
+
+        let time = chrono::Local.with_ymd_and_hms(2024, 1, 2, 3, 4, 5).unwrap();
+        let line = |level, message: std::fmt::Arguments| {
+            json_line(
+                time,
+                &log::Record::builder()
+                    .level(level)
+                    .target("maa::run")
+                    .args(message)
+                    .build(),
+            )
+        };
+        let content = [
+            line(log::Level::Info, format_args!("Task started")),
+            line(log::Level::Warn, format_args!("multi\nline \"quoted\"")),
+        ]
+        .join("\n");
+
+        let records: Vec<serde_json::Value> = content
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(
+            records[0]["timestamp"],
+            time.to_rfc3339_opts(chrono::SecondsFormat::Secs, false)
+        );
+        assert_eq!(records[0]["level"], "INFO");
+        assert_eq!(records[0]["target"], "maa::run");
+        assert_eq!(records[0]["message"], "Task started");
+        assert_eq!(records[1]["level"], "WARN");
+        assert_eq!(records[1]["message"], "multi\nline \"quoted\"");
+    }
+
+    #[test]
+    fn run_log_name() {
+        use chrono::TimeZone;
+
+        let time = chrono::Local.with_ymd_and_hms(2024, 1, 2, 3, 4, 5).unwrap();
+        assert_eq!(
+            super::run_log_name(time, "daily"),
+            "2024-01-02_03-04-05_daily.log"
+        );
+        assert_eq!(
+            super::run_log_name(time, "sub/daily task"),
+            "2024-01-02_03-04-05_sub_daily_task.log"
+        );
+    }
+
+    #[test]
+    fn prune_run_logs() {
+        use crate::dirs::Ensure;
+
+        let dir = std::env::temp_dir().join("maa-test-prune-run-logs");
+        dir.as_path().ensure_clean().unwrap();
+        let names = [
+            "2024-01-01_00-00-00_a.log",
+            "2024-01-02_00-00-00_b.log",
+            "2024-01-03_00-00-00_c.log",
+            "2024-01-04_00-00-00_d.log",
+            "2024-01-05_00-00-00_e.log",
+        ];
+        for name in names {
+            std::fs::write(dir.join(name), "0123456789").unwrap();
+        }
+        std::fs::write(dir.join("other.txt"), "").unwrap();
+
+        let logs: Vec<_> = run_logs_in(&dir)
+            .unwrap()
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect();
+        assert_eq!(
+            logs,
+            names
+                .iter()
+                .rev()
+                .map(|name| dir.join(name))
+                .collect::<Vec<_>>()
+        );
+
+        // keep the newest N files
+        let retention = RunLogRetention {
+            keep: 4,
+            max_size: 100,
+        };
+        assert_eq!(retention.prune(&dir).unwrap(), [dir.join(names[0])]);
+        assert!(!dir.join(names[0]).exists());
+        assert!(retention.prune(&dir).unwrap().is_empty());
+
+        // keep at most M bytes
+        let retention = RunLogRetention {
+            keep: 10,
+            max_size: 25,
+        };
+        assert_eq!(
+            retention.prune(&dir).unwrap(),
+            [dir.join(names[2]), dir.join(names[1])]
+        );
+        assert_eq!(run_logs_in(&dir).unwrap().len(), 2);
+
+        // the newest file is never removed
+        let retention = RunLogRetention {
+            keep: 0,
+            max_size: 0,
+        };
+        assert_eq!(retention.prune(&dir).unwrap(), [dir.join(names[3])]);
+        assert!(dir.join(names[4]).exists());
+        assert!(dir.join("other.txt").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(run_logs_in(&dir).unwrap().is_empty());
+    }
+
+    #[test]
+    fn run_log_retention() {
+        std::env::remove_var("MAA_RUN_LOG_KEEP");
+        std::env::remove_var("MAA_RUN_LOG_MAX_SIZE");
+        assert_eq!(
+            RunLogRetention::from_env(),
+            RunLogRetention {
+                keep: RUN_LOG_KEEP,
+                max_size: RUN_LOG_MAX_SIZE,
+            }
+        );
+
+        std::env::set_var("MAA_RUN_LOG_KEEP", "10");
+        std::env::set_var("MAA_RUN_LOG_MAX_SIZE", "50M");
+        assert_eq!(
+            RunLogRetention::from_env(),
+            RunLogRetention {
+                keep: 10,
+                max_size: 50 * 1024 * 1024,
+            }
+        );
+
+        std::env::remove_var("MAA_RUN_LOG_KEEP");
+        std::env::remove_var("MAA_RUN_LOG_MAX_SIZE");
+    }
+
+    #[test]
+    fn run_log_level() {
+        std::env::remove_var("MAA_RUN_LOG");
+        assert_eq!(super::run_log_level(), LevelFilter::Debug);
+        std::env::set_var("MAA_RUN_LOG", "info");
+        assert_eq!(super::run_log_level(), LevelFilter::Info);
+        std::env::set_var("MAA_RUN_LOG", "off");
+        assert_eq!(super::run_log_level(), LevelFilter::Off);
+        std::env::remove_var("MAA_RUN_LOG");
     }
 
     mod log_prefix {
@@ -277,14 +850,14 @@ mod tests {
             let plf = plain_format
                 as fn(&mut env_logger::fmt::Formatter, &log::Record) -> std::io::Result<()>;
 
-            assert_eq!(LogPrefix::Always.format(true), pff);
-            assert_eq!(LogPrefix::Always.format(false), pff);
+            assert!(std::ptr::fn_addr_eq(LogPrefix::Always.format(true), pff));
+            assert!(std::ptr::fn_addr_eq(LogPrefix::Always.format(false), pff));
 
-            assert_eq!(LogPrefix::Never.format(true), plf);
-            assert_eq!(LogPrefix::Never.format(false), plf);
+            assert!(std::ptr::fn_addr_eq(LogPrefix::Never.format(true), plf));
+            assert!(std::ptr::fn_addr_eq(LogPrefix::Never.format(false), plf));
 
-            assert_eq!(LogPrefix::Auto.format(true), pff);
-            assert_eq!(LogPrefix::Auto.format(false), plf);
+            assert!(std::ptr::fn_addr_eq(LogPrefix::Auto.format(true), pff));
+            assert!(std::ptr::fn_addr_eq(LogPrefix::Auto.format(false), plf));
         }
     }
 }
@@ -6,75 +6,246 @@ mod log;
 mod activity;
 mod cleanup;
 mod command;
+mod completion;
 mod config;
+mod doctor;
 mod installer;
+mod locate;
+mod migrate;
+mod profile;
 mod run;
 mod value;
+mod version;
 
-use crate::command::{Command, Component, Dir, CLI};
+use crate::command::{Command, Dir, CLI};
 
 use anyhow::{Context, Result};
 use clap::{CommandFactory, Parser};
 
 fn main() -> Result<()> {
+    let ret = try_main();
+
+    if let Some(Exit(code)) = ret.as_ref().err().and_then(|err| err.downcast_ref()) {
+        log::finish();
+        std::process::exit(*code);
+    }
+
+    // Record the error in the run log, it is printed to stderr when returned from main
+    if let Err(err) = &ret {
+        ::log::debug!("Error: {err:#}");
+    }
+
+    log::finish();
+
+    // A run aborted by `--timeout` exits with a distinct code, like the `timeout` command
+    if let Err(err) = &ret {
+        if err.is::<run::TimedOut>() {
+            eprintln!("Error: {err:?}");
+            std::process::exit(run::TIMEOUT_EXIT_CODE);
+        }
+    }
+
+    ret
+}
+
+fn try_main() -> Result<()> {
     let cli = command::CLI::parse();
 
-    cli.log.init_logger()?;
+    if let Some(config_dir) = cli.config_dir {
+        dirs::set_config_dir(config_dir)?;
+    }
+    if cli.no_system_config {
+        dirs::disable_system_config();
+    }
+    if let Some(profile) = cli.profile {
+        dirs::set_profile(profile)?;
+    }
+
+    // Create missing directories at first for commands which write files, so permission errors
+    // are reported clearly instead of failing in the middle of a command.
+    // A missing config directory given by user is only created by `maa init`.
+    let read_only = cli.command.is_read_only();
+    if !read_only {
+        dirs::ensure_all(matches!(cli.command, Command::Init { .. }))?;
+    }
+
+    cli.log
+        .init_logger(cli.command.run_name().as_deref(), cli.command.json_output())?;
+
+    // Read-only commands work without the directories, so problems are only logged
+    if read_only {
+        if let Err(err) = dirs::check_all() {
+            ::log::info!("{err:#}");
+        }
+    }
+
+    ::log::info!("Config directory: {}", dirs::config().display());
+    if let Some(profile) = dirs::profile() {
+        ::log::info!("Profile: {profile}");
+    }
+    for layer in dirs::config_layers() {
+        ::log::debug!("Config layer: {}", layer.display());
+    }
 
     if cli.batch {
         value::userinput::enable_batch_mode()
     }
 
-    match cli.command {
+    // Commands which generate output for other programs are not interrupted by the prompt
+    if !matches!(cli.command, Command::Migrate { .. }) && !cli.command.generates_output() {
+        if let Err(err) = migrate::run_once() {
+            ::log::warn!("Failed to migrate legacy files: {err:#}");
+        }
+    }
+
+    // Commands which update maa-cli or generate files are not interrupted by the notice
+    #[cfg(feature = "cli_installer")]
+    let update_check = match cli.command {
+        Command::SelfC(_) | Command::Complete { .. } | Command::Mangen { .. } => None,
+        _ => installer::update_check::start(cli.batch),
+    };
+
+    // The notice is printed even if the command failed
+    let ret = execute(cli.command);
+
+    #[cfg(feature = "cli_installer")]
+    if let Some(update_check) = update_check {
+        update_check.finish();
+    }
+
+    ret
+}
+
+/// Exit with the code without printing an error, returned by commands which report
+/// problems by exit code, so the work after the command is still done.
+#[derive(Debug)]
+struct Exit(i32);
+
+impl std::fmt::Display for Exit {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Exit with code {}", self.0)
+    }
+}
+
+impl std::error::Error for Exit {}
+
+fn execute(command: Command) -> Result<()> {
+    match command {
+        #[cfg(feature = "core_installer")]
+        Command::Install {
+            force,
+            version,
+            common,
+            build_from_source,
+            jobs,
+        } => {
+            let build = build_from_source.then_some(jobs);
+            installer::maa_core::install(force, version.as_ref(), build, &common)?;
+            installer::resource::update(false, false)?;
+        }
+        #[cfg(feature = "core_installer")]
+        Command::Update {
+            common,
+            resource_only: true,
+            hooks,
+        } => {
+            installer::maa_core::update_resource(&common, &hooks)?;
+        }
         #[cfg(feature = "core_installer")]
-        Command::Install { force, common } => {
-            installer::maa_core::install(force, &common)?;
-            installer::resource::update(false)?;
+        Command::Update { common, hooks, .. } => {
+            installer::maa_core::update(&common, &hooks)?;
+            installer::resource::update(false, false)?;
         }
         #[cfg(feature = "core_installer")]
-        Command::Update { common } => {
-            installer::maa_core::update(&common)?;
-            installer::resource::update(false)?;
+        Command::Uninstall {
+            component,
+            purge,
+            dry_run,
+        } => installer::uninstall::uninstall(component, purge, dry_run)?,
+        #[cfg(feature = "core_installer")]
+        Command::Verify { component, repair } => {
+            if !installer::integrity::verify(component, repair)? {
+                return Err(Exit(installer::integrity::PROBLEMS_FOUND_EXIT_CODE).into());
+            }
         }
         #[cfg(feature = "cli_installer")]
         Command::SelfC(self_c) => match self_c {
-            command::SelfCommand::Update { common } => installer::maa_cli::update(&common)?,
-        },
-        Command::HotUpdate => installer::resource::update(false)?,
-        Command::Dir { dir } => match dir {
-            Dir::Data => println!("{}", dirs::data().display()),
-            Dir::Library => {
-                println!(
-                    "{}",
-                    dirs::find_library().context("Library not found")?.display()
-                )
+            command::SelfCommand::Update {
+                common,
+                check,
+                verify,
+                ..
+            } if check || verify => {
+                if !installer::maa_cli::check_installation(&common, check, verify)? {
+                    return Err(Exit(installer::maa_cli::VERIFY_FAILED_EXIT_CODE).into());
+                }
             }
-            Dir::Resource => {
-                println!(
-                    "{}",
-                    dirs::find_resource()
-                        .context("Resource not found")?
-                        .display()
-                )
+            command::SelfCommand::Update { common, force, .. } => {
+                installer::maa_cli::update(&common, force)?
             }
-            Dir::HotUpdate => println!("{}", dirs::hot_update().display()),
-            Dir::Config => println!("{}", dirs::config().display()),
-            Dir::Cache => println!("{}", dirs::cache().display()),
-            Dir::Log => println!("{}", dirs::log().display()),
-        },
-        Command::Version { component } => match component {
-            Component::All => {
-                println!("maa-cli v{}", env!("MAA_VERSION"));
-                println!("MaaCore {}", run::core_version()?);
+            command::SelfCommand::Verify { common } => installer::maa_cli::verify(&common)?,
+            command::SelfCommand::Uninstall { purge, dry_run } => {
+                installer::maa_cli::uninstall(purge, dry_run)?
             }
-            Component::MaaCLI => {
-                println!("maa-cli v{}", env!("MAA_VERSION"));
+            command::SelfCommand::Info { common, json } => installer::maa_cli::info(&common, json)?,
+        },
+        Command::HotUpdate { auto } => installer::resource::update(false, auto)?,
+        Command::Doctor { offline } => doctor::doctor(offline)?,
+        Command::Migrate { dry_run } => migrate::migrate(dry_run)?,
+        Command::Dir { dir: None, .. } => dirs::resolved_table(&dirs::resolved()).printstd(),
+        Command::Dir {
+            dir: Some(dir),
+            open,
+        } => {
+            let path = match dir {
+                Dir::Data => dirs::data().to_path_buf(),
+                Dir::Library => locate::find_library()
+                    .context("Library not found")?
+                    .path()
+                    .to_path_buf(),
+                Dir::Resource => locate::find_resource()
+                    .context("Resource not found")?
+                    .path()
+                    .to_path_buf(),
+                Dir::HotUpdate => dirs::hot_update().to_path_buf(),
+                Dir::Config => dirs::config().to_path_buf(),
+                Dir::Cache => dirs::cache().to_path_buf(),
+                Dir::State => dirs::state().to_path_buf(),
+                Dir::Log => dirs::log().to_path_buf(),
+            };
+            if open {
+                dirs::open(&path)?;
+            } else {
+                println!("{}", path.display());
             }
-            Component::MaaCore => {
-                println!("MaaCore {}", run::core_version()?);
+        }
+        Command::Version { component, json } => version::print(component, json)?,
+        Command::Run {
+            task,
+            task_file,
+            task_json,
+            show_profile_history,
+            watch,
+            watch_debounce,
+            common,
+        } => {
+            let run = || match (&task, &task_file, &task_json) {
+                (_, _, Some(json)) => run::run_task_json(json, common.clone()),
+                (_, Some(path), None) => run::run_task_file(path, common.clone()),
+                (Some(task), None, None) => run::run_custom(task, common.clone()),
+                (None, None, None) => unreachable!("one of task sources is required"),
+            };
+            if let Some(n) = show_profile_history {
+                run::profile::show_history(n, common.output)?
+            } else if watch {
+                let paths = std::iter::once(dirs::config().to_path_buf())
+                    .chain(task_file.clone())
+                    .collect();
+                run::watch::watch(run, paths, std::time::Duration::from_millis(watch_debounce))?
+            } else {
+                run()?
             }
-        },
-        Command::Run { task, common } => run::run_custom(task, common)?,
+        }
         Command::StartUp {
             client,
             account,
@@ -109,7 +280,22 @@ fn main() -> Result<()> {
                 )
             );
         }
-        Command::Cleanup { targets } => cleanup::cleanup(&targets)?,
+        Command::Log { tail } => log::show_latest(tail)?,
+        Command::Cleanup {
+            targets,
+            report,
+            older_than,
+            max_size,
+        } => {
+            if older_than.is_some() || max_size.is_some() {
+                cleanup::prune(older_than, max_size)?;
+            }
+            if report {
+                cleanup::report()?;
+            } else if older_than.is_none() && max_size.is_none() {
+                cleanup::cleanup(&targets)?;
+            }
+        }
         Command::List => {
             let task_dir = dirs::config().join("tasks");
             if !task_dir.exists() {
@@ -124,13 +310,55 @@ fn main() -> Result<()> {
                 }
             }
         }
+        Command::Profile(profile_c) => match profile_c {
+            command::ProfileCommand::List => profile::list()?,
+            command::ProfileCommand::New { name } => profile::new(&name)?,
+            command::ProfileCommand::Copy { from, to } => profile::copy(&from, &to)?,
+        },
+        Command::Task(task_c) => match task_c {
+            command::TaskCommand::ValidateAll { dir, output } => {
+                config::validate::validate_all(dir.as_deref(), output)?
+            }
+            #[cfg(feature = "__installer")]
+            command::TaskCommand::Import { url, force } => {
+                config::remote::import_task(&url, force)?
+            }
+            command::TaskCommand::Export {
+                name,
+                output,
+                format,
+                strip_secrets,
+            } => config::export::export_task(&name, output.as_deref(), format, strip_secrets)?,
+            command::TaskCommand::New {
+                task_type,
+                output,
+                format,
+            } => config::template::new_task(task_type, output.as_deref(), format)?,
+        },
+        Command::Config(config_c) => match config_c {
+            #[cfg(feature = "__installer")]
+            command::ConfigCommand::CheckUpdates { registry } => {
+                config::registry::check_updates(registry.as_deref())?
+            }
+            #[cfg(feature = "__installer")]
+            command::ConfigCommand::Update { name, registry } => {
+                config::registry::update(&name, registry.as_deref())?
+            }
+            command::ConfigCommand::Watch { path, debounce } => {
+                config::watch::watch(&path, std::time::Duration::from_millis(debounce))?
+            }
+        },
         Command::Import {
             path,
             force,
             config_type,
         } => config::import(&path, force, &config_type)?,
-        Command::Complete { shell } => {
-            clap_complete::generate(shell, &mut CLI::command(), "maa", &mut std::io::stdout());
+        Command::Complete { shell, install } => {
+            if install {
+                completion::install(shell, &mut CLI::command())?;
+            } else if let Some(shell) = shell {
+                completion::generate(shell, &mut CLI::command());
+            }
         }
         Command::Init {
             name,
@@ -0,0 +1,598 @@
+//! Move files left in legacy locations by older versions of maa-cli into the current layout.
+//!
+//! Known legacy locations are:
+//!
+//! - the config directory of the old layout on macOS, `~/Library/Preferences/com.loong.maa`,
+//!   see [`dirs::legacy_config_dir`];
+//! - records of maa-cli kept in the cache directory before the state directory existed,
+//!   e.g. the install manifest, which are moved into the state directory;
+//! - the native directories on macOS, e.g. `~/Library/Application Support/com.loong.maa`,
+//!   which are moved into the XDG layout when it is opted in by `MAA_XDG=1`,
+//!   see [`dirs::use_xdg`].
+//!
+//! The migration is run once per version of maa-cli and layout at startup after the user
+//! confirms it, which is recorded in the state directory, and can be run manually by
+//! `maa migrate`. Files existing in both locations are
+//! never overwritten silently, the newer one is kept and the other one is backed up next to it.
+//! A `MIGRATED` marker is left in each legacy location files are moved from.
+
+use crate::{
+    dirs::{self, Ensure},
+    value::userinput::{self, BoolInput, UserInput},
+};
+
+use std::{
+    ffi::OsString,
+    fs,
+    io::IsTerminal,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use log::warn;
+
+/// Name of the marker left in legacy locations after migration
+const MARKER: &str = "MIGRATED";
+
+/// File in the state directory recording the version of maa-cli and the layout of the migration
+const RECORD: &str = "migrated-version";
+
+/// Records of maa-cli kept in the cache directory by old versions
+const LEGACY_STATE_FILES: &[&str] = &[
+    "manifest.json",
+    "maa-cli-update-check.json",
+    "maa-cli.sha256",
+];
+
+/// Data of MaaCore kept in the data directory
+const DATA_FILES: &[&str] = &["lib", "resource", "MaaResource"];
+
+/// A legacy location and where its files go
+#[cfg_attr(test, derive(Debug))]
+struct Legacy {
+    from: PathBuf,
+    to: PathBuf,
+    /// Files or directories to move, relative to `from`, or all files if `None`
+    files: Option<&'static [&'static str]>,
+}
+
+/// Known legacy locations of given directories
+fn legacy_locations(
+    legacy_config: Option<&Path>,
+    config: &Path,
+    cache: &Path,
+    state: &Path,
+) -> Vec<Legacy> {
+    let mut locations = Vec::new();
+    if let Some(legacy_config) = legacy_config {
+        locations.push(Legacy {
+            from: legacy_config.to_path_buf(),
+            to: config.to_path_buf(),
+            files: None,
+        });
+    }
+    if cache != state {
+        locations.push(Legacy {
+            from: cache.to_path_buf(),
+            to: state.to_path_buf(),
+            files: Some(LEGACY_STATE_FILES),
+        });
+    }
+    locations
+}
+
+/// Locations of the config, data and state directories in the native layout,
+/// whose files go to the ones in the XDG layout.
+///
+/// The config directory may be in the data directory in the native layout, e.g. on macOS,
+/// so only known files are moved from the data and state directories.
+fn layout_locations(native: [&Path; 3], xdg: [&Path; 3]) -> Vec<Legacy> {
+    let files = [None, Some(DATA_FILES), Some(LEGACY_STATE_FILES)];
+    native
+        .into_iter()
+        .zip(xdg)
+        .zip(files)
+        .filter(|((from, to), _)| from != to)
+        .map(|((from, to), files)| Legacy {
+            from: from.to_path_buf(),
+            to: to.to_path_buf(),
+            files,
+        })
+        .collect()
+}
+
+/// What to do with a legacy file
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub enum Action {
+    /// Move the legacy file into the new location
+    Move,
+    /// The legacy file is newer, back up the existing file and then move the legacy file
+    Replace { backup: PathBuf },
+    /// The existing file is newer, keep it and move the legacy file to a backup
+    Keep { backup: PathBuf },
+}
+
+/// A step of the migration of a legacy file
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct Step {
+    pub from: PathBuf,
+    pub to: PathBuf,
+    pub action: Action,
+}
+
+impl std::fmt::Display for Step {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (from, to) = (self.from.display(), self.to.display());
+        match &self.action {
+            Action::Move => write!(f, "move {from} to {to}"),
+            Action::Replace { backup } => write!(
+                f,
+                "move {from} to {to}, the older existing file is backed up to {}",
+                backup.display()
+            ),
+            Action::Keep { backup } => write!(
+                f,
+                "keep the newer {to}, {from} is backed up to {}",
+                backup.display()
+            ),
+        }
+    }
+}
+
+/// A path next to given one which does not exist, e.g. `cli.toml.bak` or `cli.toml.bak.1`
+fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".bak");
+    let backup = path.with_file_name(&name);
+    if backup.symlink_metadata().is_err() {
+        return backup;
+    }
+    (1..)
+        .map(|index| {
+            let mut name = name.clone();
+            name.push(format!(".{index}"));
+            path.with_file_name(name)
+        })
+        .find(|backup| backup.symlink_metadata().is_err())
+        .expect("there is always a free backup name")
+}
+
+fn walk(dir: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            walk(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Plan the migration of given legacy locations, missing locations are skipped
+fn plan(locations: &[Legacy]) -> Result<Vec<Step>> {
+    let mut steps = Vec::new();
+    for legacy in locations {
+        if !legacy.from.is_dir() {
+            continue;
+        }
+        let mut files = match legacy.files {
+            Some(files) => {
+                let mut found = Vec::new();
+                for path in files.iter().map(|file| legacy.from.join(file)) {
+                    if path.is_dir() {
+                        walk(&path, &mut found)
+                            .with_context(|| format!("Failed to read {}", path.display()))?;
+                    } else if path.is_file() {
+                        found.push(path);
+                    }
+                }
+                found
+            }
+            None => {
+                let mut files = Vec::new();
+                walk(&legacy.from, &mut files)
+                    .with_context(|| format!("Failed to read {}", legacy.from.display()))?;
+                files.retain(|path| path != &legacy.from.join(MARKER));
+                files
+            }
+        };
+        files.sort();
+
+        for from in files {
+            let relative = from
+                .strip_prefix(&legacy.from)
+                .expect("files are in the legacy location");
+            let to = legacy.to.join(relative);
+            let action = if to.symlink_metadata().is_err() {
+                Action::Move
+            } else if modified(&from)? > modified(&to)? {
+                Action::Replace {
+                    backup: backup_path(&to),
+                }
+            } else {
+                Action::Keep {
+                    backup: backup_path(&to),
+                }
+            };
+            steps.push(Step { from, to, action });
+        }
+    }
+    Ok(steps)
+}
+
+fn modified(path: &Path) -> Result<std::time::SystemTime> {
+    fs::symlink_metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .with_context(|| format!("Failed to read the modification time of {}", path.display()))
+}
+
+/// Move a file, by copying and removing it if it can not be renamed, e.g. across filesystems.
+///
+/// The file is copied to a temporary file next to the destination and renamed into place
+/// before it is removed, so an interrupted move never leaves a partial file or loses the file.
+fn move_file(from: &Path, to: &Path) -> Result<()> {
+    if let Some(parent) = to.parent() {
+        parent.ensure()?;
+    }
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+
+    let mut tmp_name = OsString::from(".");
+    tmp_name.push(to.file_name().unwrap_or_default());
+    tmp_name.push(".migrating");
+    let tmp = to.with_file_name(tmp_name);
+    fs::copy(from, &tmp)
+        .and_then(|_| fs::rename(&tmp, to))
+        .inspect_err(|_| {
+            let _ = fs::remove_file(&tmp);
+        })
+        .with_context(|| format!("Failed to copy {} to {}", from.display(), to.display()))?;
+    fs::remove_file(from).with_context(|| format!("Failed to remove {}", from.display()))
+}
+
+/// Apply the steps of a migration, and leave a marker in the legacy locations
+fn apply(locations: &[Legacy], steps: &[Step]) -> Result<()> {
+    for step in steps {
+        match &step.action {
+            Action::Move => move_file(&step.from, &step.to)?,
+            Action::Replace { backup } => {
+                move_file(&step.to, backup)?;
+                move_file(&step.from, &step.to)?;
+            }
+            Action::Keep { backup } => move_file(&step.from, backup)?,
+        }
+    }
+
+    for legacy in locations {
+        if steps.iter().any(|step| step.from.starts_with(&legacy.from)) {
+            let marker = legacy.from.join(MARKER);
+            fs::write(
+                &marker,
+                format!(
+                    "Files in this directory were moved to {} by maa-cli v{}.\n",
+                    legacy.to.display(),
+                    env!("MAA_VERSION")
+                ),
+            )
+            .with_context(|| format!("Failed to write {}", marker.display()))?;
+        }
+    }
+    Ok(())
+}
+
+fn locations() -> Vec<Legacy> {
+    let mut locations = legacy_locations(
+        dirs::legacy_config_dir().as_deref(),
+        dirs::config(),
+        dirs::cache(),
+        dirs::state(),
+    );
+    if let Some(native) = dirs::native_dirs() {
+        locations.extend(layout_locations(
+            [native.config(), native.data(), native.state()],
+            [dirs::config(), dirs::data(), dirs::state()],
+        ));
+    }
+    locations
+}
+
+/// Key of the record, the migration is run again if the layout is changed
+fn record_key() -> String {
+    let version = env!("MAA_VERSION");
+    if dirs::use_xdg() {
+        format!("{version} xdg")
+    } else {
+        version.to_owned()
+    }
+}
+
+/// Migrate files in legacy locations, or only print the plan if `dry_run`
+pub fn migrate(dry_run: bool) -> Result<()> {
+    let locations = locations();
+    let steps = plan(&locations)?;
+    if steps.is_empty() {
+        println!("Nothing to migrate");
+        return Ok(());
+    }
+    for step in &steps {
+        println!("{}{step}", if dry_run { "Would " } else { "" });
+    }
+    if !dry_run {
+        apply(&locations, &steps)?;
+        println!("Migrated {} files", steps.len());
+    }
+    Ok(())
+}
+
+fn is_recorded(record: &Path, key: &str) -> bool {
+    fs::read_to_string(record).is_ok_and(|recorded| recorded.trim() == key)
+}
+
+/// Run the migration after asking the user if it is not run by current version or in current
+/// layout, the plan and the prompt are printed to stderr.
+///
+/// In batch mode or if stdin is not a terminal, no one can confirm the migration,
+/// so a warning is printed instead and the migration is left to the next interactive run.
+pub fn run_once() -> Result<()> {
+    let (locations, record, key) = (locations(), dirs::state().join(RECORD), record_key());
+    if userinput::is_batch_mode() || !std::io::stdin().is_terminal() {
+        if !is_recorded(&record, &key) && !plan(&locations)?.is_empty() {
+            warn!("Found files left by older versions of maa-cli, run `maa migrate` to move them");
+        }
+        return Ok(());
+    }
+    run_once_in(&locations, &record, &key, ask)
+}
+
+/// Ask the user to confirm the migration, default to yes.
+///
+/// The prompt is written to stderr, so the output of the command is not mixed with it.
+fn ask() -> Result<bool> {
+    Ok(BoolInput::new(Some(true), Some("migrate these files"))
+        .ask(&mut std::io::stderr(), &mut std::io::stdin().lock())?)
+}
+
+/// Run the migration if it is confirmed, a declined migration is recorded as well,
+/// so the user is not asked again by the same version.
+fn run_once_in(
+    locations: &[Legacy],
+    record: &Path,
+    key: &str,
+    confirm: impl FnOnce() -> Result<bool>,
+) -> Result<()> {
+    if is_recorded(record, key) {
+        return Ok(());
+    }
+
+    let steps = plan(locations)?;
+    if !steps.is_empty() {
+        eprintln!("Found files left by older versions of maa-cli:");
+        for step in &steps {
+            eprintln!("  {step}");
+        }
+        if confirm()? {
+            apply(locations, &steps)?;
+        } else {
+            eprintln!("Skipped, run `maa migrate` to move them later");
+        }
+    }
+
+    if let Some(parent) = record.parent() {
+        parent.ensure_with_mode(dirs::PRIVATE_DIR_MODE)?;
+    }
+    fs::write(record, key).with_context(|| format!("Failed to write {}", record.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{
+        env::temp_dir,
+        time::{Duration, SystemTime},
+    };
+
+    fn write(path: &Path, content: &str, age: u64) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+        fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(age))
+            .unwrap();
+    }
+
+    fn read(path: &Path) -> String {
+        fs::read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn backup_path() {
+        let dir = temp_dir().join("maa-test-migrate-backup-path");
+        dir.as_path().ensure_clean().unwrap();
+        let path = dir.join("cli.toml");
+        assert_eq!(super::backup_path(&path), dir.join("cli.toml.bak"));
+        fs::write(dir.join("cli.toml.bak"), "").unwrap();
+        assert_eq!(super::backup_path(&path), dir.join("cli.toml.bak.1"));
+        fs::write(dir.join("cli.toml.bak.1"), "").unwrap();
+        assert_eq!(super::backup_path(&path), dir.join("cli.toml.bak.2"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn migrate_legacy_layout() {
+        let root = temp_dir().join("maa-test-migrate-legacy");
+        if root.exists() {
+            fs::remove_dir_all(&root).unwrap();
+        }
+        let legacy = root.join("Preferences");
+        let (config, cache, state) = (root.join("config"), root.join("cache"), root.join("state"));
+        let locations = legacy_locations(Some(&legacy), &config, &cache, &state);
+
+        write(&legacy.join("tasks/daily.toml"), "daily", 0);
+        // the legacy file is newer
+        write(&legacy.join("cli.toml"), "legacy cli", 10);
+        write(&config.join("cli.toml"), "current cli", 100);
+        // the existing file is newer
+        write(&legacy.join("profiles/default.toml"), "legacy profile", 100);
+        write(&config.join("profiles/default.toml"), "current profile", 10);
+        write(&cache.join("manifest.json"), "{}", 0);
+        write(&cache.join("maa_cli-v1.tar.gz"), "package", 0);
+
+        let steps = plan(&locations).unwrap();
+        assert_eq!(
+            steps,
+            [
+                Step {
+                    from: legacy.join("cli.toml"),
+                    to: config.join("cli.toml"),
+                    action: Action::Replace {
+                        backup: config.join("cli.toml.bak")
+                    },
+                },
+                Step {
+                    from: legacy.join("profiles/default.toml"),
+                    to: config.join("profiles/default.toml"),
+                    action: Action::Keep {
+                        backup: config.join("profiles/default.toml.bak")
+                    },
+                },
+                Step {
+                    from: legacy.join("tasks/daily.toml"),
+                    to: config.join("tasks/daily.toml"),
+                    action: Action::Move,
+                },
+                Step {
+                    from: cache.join("manifest.json"),
+                    to: state.join("manifest.json"),
+                    action: Action::Move,
+                },
+            ]
+        );
+
+        // planning does not change anything
+        assert!(!config.join("tasks/daily.toml").exists());
+
+        let record = state.join(RECORD);
+        // a declined migration changes nothing but is recorded
+        run_once_in(&locations, &record, "0.9.0", || Ok(false)).unwrap();
+        assert_eq!(read(&record), "0.9.0");
+        assert_eq!(plan(&locations).unwrap(), steps);
+
+        run_once_in(&locations, &record, "1.0.0", || Ok(true)).unwrap();
+        assert_eq!(read(&record), "1.0.0");
+
+        assert_eq!(read(&config.join("tasks/daily.toml")), "daily");
+        assert_eq!(read(&config.join("cli.toml")), "legacy cli");
+        assert_eq!(read(&config.join("cli.toml.bak")), "current cli");
+        assert_eq!(
+            read(&config.join("profiles/default.toml")),
+            "current profile"
+        );
+        assert_eq!(
+            read(&config.join("profiles/default.toml.bak")),
+            "legacy profile"
+        );
+        assert_eq!(read(&state.join("manifest.json")), "{}");
+        // other files in the cache are left untouched
+        assert!(cache.join("maa_cli-v1.tar.gz").exists());
+
+        // only markers are left in legacy locations
+        let mut left = Vec::new();
+        walk(&legacy, &mut left).unwrap();
+        assert_eq!(left, [legacy.join(MARKER)]);
+        assert!(cache.join(MARKER).exists());
+        assert!(!config.join(MARKER).exists());
+
+        // the migration is run once per version
+        write(&legacy.join("tasks/weekly.toml"), "weekly", 0);
+        run_once_in(&locations, &record, "1.0.0", || Ok(true)).unwrap();
+        assert!(!config.join("tasks/weekly.toml").exists());
+        // and run again if the layout is changed
+        run_once_in(&locations, &record, "1.0.0 xdg", || Ok(true)).unwrap();
+        assert_eq!(read(&config.join("tasks/weekly.toml")), "weekly");
+
+        // nothing is left to migrate
+        assert!(plan(&locations).unwrap().is_empty());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn migrate_native_layout() {
+        let root = temp_dir().join("maa-test-migrate-native");
+        if root.exists() {
+            fs::remove_dir_all(&root).unwrap();
+        }
+        // the config directory is in the data directory, which is also the state directory
+        let native = root.join("Application Support/com.loong.maa");
+        let native_config = native.join("config");
+        let home = root.join("home");
+        let (config, data, state) = (
+            home.join(".config/maa"),
+            home.join(".local/share/maa"),
+            home.join(".local/state/maa"),
+        );
+        let locations =
+            layout_locations([&native_config, &native, &native], [&config, &data, &state]);
+
+        write(&native_config.join("cli.toml"), "cli", 0);
+        write(&native.join("lib/libMaaCore.dylib"), "lib", 0);
+        write(&native.join("resource/config.json"), "{}", 0);
+        write(&native.join("manifest.json"), "{}", 0);
+        write(&native.join("debug/asst.log"), "log", 0);
+
+        let moved: Vec<_> = plan(&locations)
+            .unwrap()
+            .into_iter()
+            .map(|step| {
+                assert_eq!(step.action, Action::Move);
+                step.to
+            })
+            .collect();
+        assert_eq!(
+            moved,
+            [
+                config.join("cli.toml"),
+                data.join("lib/libMaaCore.dylib"),
+                data.join("resource/config.json"),
+                state.join("manifest.json"),
+            ]
+        );
+
+        run_once_in(&locations, &state.join(RECORD), "1.0.0 xdg", || Ok(true)).unwrap();
+        assert_eq!(read(&config.join("cli.toml")), "cli");
+        assert_eq!(read(&data.join("lib/libMaaCore.dylib")), "lib");
+        assert_eq!(read(&state.join("manifest.json")), "{}");
+        assert!(native_config.join(MARKER).exists());
+        assert!(native.join(MARKER).exists());
+        // logs are not moved
+        assert!(native.join("debug/asst.log").exists());
+
+        // unchanged directories are skipped
+        assert!(layout_locations([&config, &data, &state], [&config, &data, &state]).is_empty());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn legacy_locations_of_dirs() {
+        let root = Path::new("/maa");
+        let locations = legacy_locations(None, &root.join("config"), root, root);
+        assert!(locations.is_empty());
+        assert!(plan(&locations).unwrap().is_empty());
+
+        let locations = legacy_locations(
+            Some(&root.join("missing")),
+            &root.join("config"),
+            &root.join("cache"),
+            &root.join("state"),
+        );
+        assert_eq!(locations.len(), 2);
+        // missing locations are skipped
+        assert!(plan(&locations).unwrap().is_empty());
+    }
+}
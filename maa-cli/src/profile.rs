@@ -0,0 +1,150 @@
+//! Named profiles, e.g. for different accounts.
+//!
+//! A profile is a directory `profiles/<name>` in the config directory, which overlays the
+//! config directory, see [`dirs::Dirs::abs_config`] and [`dirs::Dirs::config_layers`].
+//! Profiles are activated by `maa --profile <name>`.
+
+use crate::dirs::{self, Ensure, PROFILES_DIR};
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+
+/// Names of all profiles in given config directory, sorted by name
+fn profiles_in(config: &Path) -> Result<Vec<String>> {
+    let dir = config.join(PROFILES_DIR);
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut names = Vec::new();
+    for entry in dir
+        .read_dir()
+        .with_context(|| format!("Failed to read {}", dir.display()))?
+    {
+        let entry = entry?;
+        // Files in the directory are profiles of MaaCore, e.g. `profiles/default.toml`
+        if entry.file_type()?.is_dir() {
+            names.push(entry.file_name().to_string_lossy().into_owned());
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+fn profile_dir(config: &Path, name: &str) -> Result<PathBuf> {
+    dirs::check_profile_name(name)?;
+    Ok(join!(config, PROFILES_DIR, name))
+}
+
+fn new_in(config: &Path, name: &str) -> Result<PathBuf> {
+    let dir = profile_dir(config, name)?;
+    if dir.exists() {
+        bail!("Profile `{name}` already exists in {}", dir.display());
+    }
+    dir.as_path().ensure_with_mode(dirs::PRIVATE_DIR_MODE)?;
+    Ok(dir)
+}
+
+fn copy_in(config: &Path, from: &str, to: &str) -> Result<PathBuf> {
+    fn copy_dir(src: &Path, dest: &Path) -> Result<()> {
+        dest.ensure()?;
+        for entry in src.read_dir()? {
+            let entry = entry?;
+            let dest = dest.join(entry.file_name());
+            if entry.file_type()?.is_dir() {
+                copy_dir(&entry.path(), &dest)?;
+            } else {
+                std::fs::copy(entry.path(), &dest).with_context(|| {
+                    format!(
+                        "Failed to copy {} to {}",
+                        entry.path().display(),
+                        dest.display()
+                    )
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    let src = profile_dir(config, from)?;
+    if !src.is_dir() {
+        bail!("Profile `{from}` not found in {}", src.display());
+    }
+    let dest = new_in(config, to)?;
+    copy_dir(&src, &dest)?;
+    Ok(dest)
+}
+
+/// List all profiles, the active one is marked by `*`
+pub fn list() -> Result<()> {
+    let names = profiles_in(dirs::config())?;
+    if names.is_empty() {
+        println!("No profiles found, create one by `maa profile new <name>`");
+    }
+    for name in names {
+        let mark = if dirs::profile() == Some(name.as_str()) {
+            "*"
+        } else {
+            " "
+        };
+        println!("{mark} {name}");
+    }
+    Ok(())
+}
+
+/// Create an empty profile, files missing in the profile are taken from the config directory
+pub fn new(name: &str) -> Result<()> {
+    let dir = new_in(dirs::config(), name)?;
+    println!("Profile `{name}` is created in {}", dir.display());
+    Ok(())
+}
+
+/// Copy all files of a profile to a new profile
+pub fn copy(from: &str, to: &str) -> Result<()> {
+    let dir = copy_in(dirs::config(), from, to)?;
+    println!("Profile `{from}` is copied to {}", dir.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{env::temp_dir, fs};
+
+    #[test]
+    fn manage_profiles() {
+        let config = temp_dir().join("maa-test-manage-profiles");
+        if config.exists() {
+            fs::remove_dir_all(&config).unwrap();
+        }
+
+        assert!(profiles_in(&config).unwrap().is_empty());
+
+        let alt = new_in(&config, "alt").unwrap();
+        assert_eq!(alt, join!(&config, "profiles", "alt"));
+        assert!(new_in(&config, "alt").is_err());
+        assert!(new_in(&config, "../alt").is_err());
+
+        // profiles of MaaCore are not listed
+        fs::write(join!(&config, "profiles", "default.toml"), "").unwrap();
+        fs::create_dir_all(alt.join("tasks")).unwrap();
+        fs::write(join!(&alt, "tasks", "daily.toml"), "tasks = []\n").unwrap();
+        fs::write(join!(&alt, "cli.toml"), "").unwrap();
+
+        let copied = copy_in(&config, "alt", "alt2").unwrap();
+        assert_eq!(
+            fs::read_to_string(join!(&copied, "tasks", "daily.toml")).unwrap(),
+            "tasks = []\n"
+        );
+        assert!(copied.join("cli.toml").exists());
+        assert!(copy_in(&config, "alt", "alt2").is_err());
+        assert!(copy_in(&config, "missing", "alt3").is_err());
+        assert!(!join!(&config, "profiles", "alt3").exists());
+
+        assert_eq!(profiles_in(&config).unwrap(), ["alt", "alt2"]);
+
+        fs::remove_dir_all(&config).unwrap();
+    }
+}
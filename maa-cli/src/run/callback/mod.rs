@@ -1,6 +1,8 @@
 pub mod summary;
 use summary::{edit_current_task_detail, end_current_task, start_task};
 
+use super::profile;
+
 use std::{fmt::Write, sync::atomic::AtomicBool};
 
 use log::{debug, error, info, trace, warn};
@@ -185,23 +187,29 @@ fn process_taskchain(code: AsstMsg, message: &Map<String, Value>) -> Option<()>
 
     use AsstMsg::*;
 
+    let taskid = message.get("taskid").and_then(Value::as_i64);
+
     match code {
         TaskChainStart => {
             info!("{} {}", taskchain, "Start");
-            start_task(message.get("taskid")?.as_i64()? as AsstTaskId);
+            start_task(taskid? as AsstTaskId);
+            profile::start_task(taskid? as AsstTaskId);
         }
         TaskChainCompleted => {
             info!("{} {}", taskchain, "Completed");
             end_current_task(summary::Reason::Completed);
+            profile::end_task(taskid? as AsstTaskId, profile::Status::Success);
         }
         TaskChainStopped => {
             warn!("{} {}", taskchain, "Stopped");
             end_current_task(summary::Reason::Stopped);
+            profile::end_task(taskid? as AsstTaskId, profile::Status::Failure);
         }
         TaskChainError => {
             error!("{} {}", taskchain, "Error");
             end_current_task(summary::Reason::Error);
             MAA_CORE_ERRORED.store(true, std::sync::atomic::Ordering::Relaxed);
+            profile::end_task(taskid? as AsstTaskId, profile::Status::Failure);
         }
         TaskChainExtraInfo => {}
 
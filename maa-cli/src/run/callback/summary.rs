@@ -44,6 +44,8 @@ pub(super) fn edit_current_task_detail(f: impl FnOnce(&mut Detail)) -> Option<()
 pub struct Summary {
     task_summarys: Map<AsstTaskId, TaskSummary>,
     current_task: Option<AsstTaskId>,
+    /// Notes about the run displayed before the summary of tasks
+    notes: Vec<String>,
 }
 
 impl Summary {
@@ -51,9 +53,14 @@ impl Summary {
         Self {
             task_summarys: Map::new(),
             current_task: None,
+            notes: Vec::new(),
         }
     }
 
+    pub fn note(&mut self, note: impl Into<String>) {
+        self.notes.push(note.into());
+    }
+
     pub fn insert(&mut self, id: AsstTaskId, name: Option<String>, task: impl Into<TaskType>) {
         self.task_summarys
             .insert(id, TaskSummary::new(name, task.into()));
@@ -90,6 +97,9 @@ impl std::fmt::Display for Summary {
         if !self.task_summarys.is_empty() {
             #[allow(clippy::write_literal)]
             writeln!(f, "{}", "Summary")?;
+            for note in &self.notes {
+                writeln!(f, "{note}")?;
+            }
             for task_summary in self.task_summarys.values() {
                 write!(f, "{LINE_SEP}\n{task_summary}")?;
             }
@@ -801,6 +811,21 @@ mod tests {
 
             assert!(re.is_match(&summary.to_string()));
         }
+
+        #[test]
+        fn notes() {
+            let mut summary = Summary::new();
+            summary.insert(1, None, TaskType::StartUp);
+            summary.note("Auto update (resource): updated");
+
+            assert_eq!(
+                summary.to_string(),
+                "Summary\n\
+                Auto update (resource): updated\n\
+                ----------------------------------------\n\
+                [StartUp] Unstarted\n"
+            );
+        }
     }
 
     mod detail {
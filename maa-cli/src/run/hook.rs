@@ -0,0 +1,155 @@
+use std::{
+    process::Command,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context, Result};
+use log::debug;
+
+/// Status of tasks passed to post hook by `MAA_TASK_STATUS`
+pub enum TaskStatus {
+    Success,
+    Failure,
+}
+
+impl TaskStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            TaskStatus::Success => "success",
+            TaskStatus::Failure => "failure",
+        }
+    }
+}
+
+/// Run a hook command with the shell of current platform.
+///
+/// The command is passed to `sh -c` on Unix-like systems and `cmd /C` on Windows,
+/// so it is split and expanded by the rules of the shell.
+/// If `status` is given, it is passed to the command by the environment variable
+/// `MAA_TASK_STATUS`.
+pub fn run(command: &str, status: Option<TaskStatus>) -> Result<()> {
+    debug!("Running hook: {}", command);
+
+    let status = shell(command, status)
+        .status()
+        .with_context(|| format!("Failed to run hook `{command}`"))?;
+    if !status.success() {
+        bail!("Hook `{command}` exited with {status}");
+    }
+
+    Ok(())
+}
+
+/// Run a hook command like [`run`], with given environment variables instead of a task status.
+#[cfg_attr(not(feature = "core_installer"), allow(dead_code))]
+pub fn run_with_env(command: &str, envs: &[(&str, &str)]) -> Result<()> {
+    debug!("Running hook: {} (env: {:?})", command, envs);
+
+    let status = shell(command, None)
+        .envs(envs.iter().copied())
+        .status()
+        .with_context(|| format!("Failed to run hook `{command}`"))?;
+    if !status.success() {
+        bail!("Hook `{command}` exited with {status}");
+    }
+
+    Ok(())
+}
+
+/// Run a hook command like [`run`], but kill it if it does not exit within `timeout`.
+pub fn run_within(command: &str, status: Option<TaskStatus>, timeout: Duration) -> Result<()> {
+    debug!("Running hook: {} (timeout: {:?})", command, timeout);
+
+    let mut child = shell(command, status)
+        .spawn()
+        .with_context(|| format!("Failed to run hook `{command}`"))?;
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            // The hook may have exited just now, so errors of kill are ignored
+            let _ = child.kill();
+            let _ = child.wait();
+            bail!("Hook `{command}` did not exit within {timeout:?}");
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    };
+    if !status.success() {
+        bail!("Hook `{command}` exited with {status}");
+    }
+
+    Ok(())
+}
+
+fn shell(command: &str, status: Option<TaskStatus>) -> Command {
+    let mut cmd = if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C");
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c");
+        cmd
+    };
+    cmd.arg(command);
+    if let Some(status) = status {
+        cmd.env("MAA_TASK_STATUS", status.as_str());
+    }
+    cmd
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(unix)]
+    fn run_hook() {
+        run("true", None).unwrap();
+        assert!(run("exit 1", None).is_err());
+
+        run(
+            r#"test "$MAA_TASK_STATUS" = success"#,
+            Some(TaskStatus::Success),
+        )
+        .unwrap();
+        run(
+            r#"test "$MAA_TASK_STATUS" = failure"#,
+            Some(TaskStatus::Failure),
+        )
+        .unwrap();
+        assert!(run(r#"test -n "$MAA_TASK_STATUS""#, None).is_err());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn run_hook_with_env() {
+        run_with_env(
+            r#"test "$MAA_OLD_VERSION" = 1 && test "$MAA_NEW_VERSION" = 2"#,
+            &[("MAA_OLD_VERSION", "1"), ("MAA_NEW_VERSION", "2")],
+        )
+        .unwrap();
+        assert!(run_with_env(r#"test -n "$MAA_OLD_VERSION""#, &[]).is_err());
+        assert!(run_with_env("exit 1", &[]).is_err());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn run_hook_within() {
+        let timeout = Duration::from_secs(5);
+        run_within("true", None, timeout).unwrap();
+        assert!(run_within("exit 1", None, timeout).is_err());
+        run_within(
+            r#"test "$MAA_TASK_STATUS" = failure"#,
+            Some(TaskStatus::Failure),
+            timeout,
+        )
+        .unwrap();
+
+        let start = Instant::now();
+        assert!(run_within("sleep 10", None, Duration::from_millis(200)).is_err());
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+}
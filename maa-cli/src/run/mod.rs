@@ -4,25 +4,36 @@
 mod callback;
 use callback::summary;
 
+pub mod hook;
+
 #[cfg(target_os = "macos")]
 mod playcover;
 
 pub mod preset;
 
+pub mod profile;
+
+pub mod watch;
+
 use crate::{
-    config::{asst::AsstConfig, task::TaskConfig, FindFile},
-    dirs::{self, maa_lib_name, Ensure},
+    config::{
+        asst::AsstConfig,
+        layered,
+        task::{include, ParamOverride, TaskConfig},
+    },
+    dirs::{self, Ensure},
     installer::resource,
 };
 
 use std::{
-    path::Path,
-    sync::{atomic, Arc},
+    path::{Path, PathBuf},
+    sync::{atomic, Arc, Mutex},
+    time::{Duration, Instant},
 };
 
 use anyhow::{bail, Context, Result};
 use clap::Args;
-use log::{debug, warn};
+use log::{debug, error, info, warn};
 use maa_sys::Assistant;
 use signal_hook::consts::TERM_SIGNALS;
 
@@ -30,7 +41,7 @@ use signal_hook::consts::TERM_SIGNALS;
 use tokio::runtime::Runtime;
 
 #[cfg_attr(test, derive(Debug, PartialEq))]
-#[derive(Args, Default)]
+#[derive(Args, Default, Clone)]
 pub struct CommonArgs {
     /// ADB serial number of device or MaaTools address set in PlayCover
     ///
@@ -85,9 +96,47 @@ pub struct CommonArgs {
     /// If you want to disable this behavior, you can use this option.
     #[arg(long, verbatim_doc_comment)]
     pub no_summary: bool,
+    /// Do not run hooks defined in the task file
+    ///
+    /// The `pre_hook` and `post_hook` defined in the task file
+    /// will be ignored for this run.
+    #[arg(long)]
+    pub no_hooks: bool,
+    /// Abort the run if it does not finish within given seconds
+    ///
+    /// When the time is up, running tasks are stopped and no new task is started,
+    /// the `post_hook` is run with a deadline of 5 seconds,
+    /// and maa exits with code 124, which is the same as the `timeout` command.
+    /// A run blocked elsewhere, e.g. connecting to a hung ADB server or prompting for input,
+    /// is aborted 3 seconds after the time is up.
+    #[arg(long, value_name = "SECONDS", verbatim_doc_comment)]
+    pub timeout: Option<u64>,
+    /// Override params of tasks, can be given multiple times
+    ///
+    /// The key is a dot-notation path in the params, e.g. `stage` or `medicine.0`,
+    /// and the value is parsed as the type of the value in the task file.
+    /// By default, tasks whose params contain the key are overridden,
+    /// prefix the key with the name or type of a task to select it, e.g. `Fight:times=3`.
+    /// Overridden inputs are not prompted.
+    #[arg(long = "set", value_name = "[TASK:]KEY=VALUE", verbatim_doc_comment)]
+    pub overrides: Vec<ParamOverride>,
+    /// Measure the execution time of each task
+    ///
+    /// After all tasks are finished, the name, duration and status of each task are printed,
+    /// and appended to `task_profile_history.json` in the cache directory,
+    /// which can be shown by `maa run --show-profile-history <N>`.
+    #[arg(long, verbatim_doc_comment)]
+    pub profile_tasks: bool,
+    /// Format of the task profile and the profile history
+    #[arg(long, value_enum, default_value_t)]
+    pub output: profile::OutputFormat,
 }
 
 impl CommonArgs {
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout.map(Duration::from_secs)
+    }
+
     pub fn apply_to(&self, config: &mut AsstConfig) {
         if let Some(addr) = self.addr.as_ref() {
             config.connection.set_address(addr);
@@ -99,36 +148,136 @@ impl CommonArgs {
     }
 }
 
-fn find_profile(root: impl AsRef<Path>, profile: Option<&str>) -> Result<AsstConfig> {
-    let root = root.as_ref();
-    if let Some(profile) = profile {
-        AsstConfig::find_file(join!(root, "profiles", profile))
-            .context("Failed to find profile file!")
-    } else if let Some(config) = AsstConfig::find_file_or_none(join!(root, "profiles", "default"))?
-    {
-        Ok(config)
-    } else if let Some(config) = AsstConfig::find_file_or_none(join!(root, "asst"))? {
+/// Exit code of a run aborted by `--timeout`, the same as the `timeout` command
+pub const TIMEOUT_EXIT_CODE: i32 = 124;
+
+/// Deadline of post hook when a run is timed out
+const TIMEOUT_HOOK_DEADLINE: Duration = Duration::from_secs(5);
+
+/// Error returned when a run does not finish within the time given by `--timeout`
+#[derive(Debug)]
+pub struct TimedOut(Duration);
+
+impl std::fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Timed out after {} seconds!", self.0.as_secs())
+    }
+}
+
+impl std::error::Error for TimedOut {}
+
+/// Deadline of a run, which is never reached if no timeout is given
+#[derive(Clone, Copy)]
+struct Deadline(Option<(Instant, Duration)>);
+
+impl Deadline {
+    fn new(timeout: Option<Duration>) -> Self {
+        Self(timeout.map(|timeout| (Instant::now() + timeout, timeout)))
+    }
+
+    /// Return an error if the deadline has been reached
+    fn check(&self) -> Result<(), TimedOut> {
+        match self.0 {
+            Some((deadline, timeout)) if Instant::now() >= deadline => Err(TimedOut(timeout)),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Time after the deadline before a run blocked somewhere is aborted by [`Watchdog`]
+///
+/// The deadline is checked while waiting for tasks, so a run is only aborted by the watchdog
+/// if it is blocked elsewhere, e.g. connecting to a hung ADB server or prompting for input.
+const WATCHDOG_GRACE: Duration = Duration::from_secs(3);
+
+/// A thread calling `on_timeout` if it is not cancelled within given time
+struct Watchdog {
+    cancel: std::sync::mpsc::Sender<()>,
+    handle: std::thread::JoinHandle<bool>,
+}
+
+impl Watchdog {
+    fn spawn(timeout: Duration, on_timeout: impl FnOnce() + Send + 'static) -> Self {
+        let (cancel, cancelled) = std::sync::mpsc::channel();
+        let handle = std::thread::spawn(move || {
+            use std::sync::mpsc::RecvTimeoutError;
+            match cancelled.recv_timeout(timeout) {
+                Err(RecvTimeoutError::Timeout) => {
+                    on_timeout();
+                    true
+                }
+                _ => false,
+            }
+        });
+        Self { cancel, handle }
+    }
+
+    /// Stop the watchdog, return whether it has fired
+    fn cancel(self) -> bool {
+        let _ = self.cancel.send(());
+        self.handle.join().unwrap_or(true)
+    }
+}
+
+/// Abort a run blocked after the deadline, running the post hook before exiting
+fn abort_blocked_run(timeout: Duration, post_hook: &Mutex<Option<String>>) {
+    error!("The run is blocked after the deadline, aborting");
+    summary::display();
+    if let Some(post_hook) = post_hook.lock().unwrap().clone() {
+        let status = Some(hook::TaskStatus::Failure);
+        if let Err(err) = hook::run_within(&post_hook, status, TIMEOUT_HOOK_DEADLINE) {
+            warn!("Failed to run post hook: {err:#}");
+        }
+    }
+    eprintln!("Error: {}", TimedOut(timeout));
+    crate::log::finish();
+    std::process::exit(TIMEOUT_EXIT_CODE);
+}
+
+/// Find the profile in config directories of all layers, see [`dirs::config_layers`]
+fn find_profile(layers: &[PathBuf], profile: Option<&str>) -> Result<AsstConfig> {
+    let found = if let Some(profile) = profile {
+        let found = layered::find(layers, join!("profiles", profile))?;
+        Some(found.with_context(|| format!("Failed to find profile file `{profile}`!"))?)
+    } else if let Some(found) = layered::find(layers, join!("profiles", "default"))? {
+        Some(found)
+    } else if let Some(found) = layered::find(layers, "asst")? {
         warn!("The config file `asst.toml` is deprecated, please use `profiles/default.toml` instead!");
-        Ok(config)
+        Some(found)
     } else {
-        Ok(AsstConfig::default())
-    }
+        None
+    };
+
+    Ok(match found {
+        Some(found) => {
+            found.log_sources("profile");
+            found.config
+        }
+        None => AsstConfig::default(),
+    })
 }
 
-fn run_core<F>(f: F, args: CommonArgs) -> Result<()>
+fn run_core<F>(f: F, args: CommonArgs, post_hook: &Mutex<Option<String>>) -> Result<()>
 where
     F: FnOnce(&AsstConfig) -> Result<TaskConfig>,
 {
+    let deadline = Deadline::new(args.timeout());
+
+    // Auto update MaaCore and resources from the package, if enabled
+    #[cfg(feature = "core_installer")]
+    let auto_updated = crate::installer::maa_core::auto_update()?;
+
     // Auto update hot update resource
-    resource::update(true)?;
+    resource::update(true, false)?;
 
     // Load asst config
-    let mut asst_config = find_profile(dirs::config(), args.profile.as_deref())?;
+    let mut asst_config = find_profile(&dirs::config_layers(), args.profile.as_deref())?;
 
     args.apply_to(&mut asst_config);
 
     let task = f(&asst_config)?;
-    let task_config = task.init()?;
+    let task_config = task.init_with(&args.overrides)?;
+    let run_hooks = !(args.no_hooks || args.dry_run);
     if let Some(client_type) = task_config.client_type {
         debug!("Detected client type: {}", client_type);
         if let Some(resource) = client_type.resource() {
@@ -155,6 +304,15 @@ where
 
     // Register tasks
     let mut summarys = (!args.no_summary).then(summary::Summary::new);
+    if let (Some(s), Some(profile)) = (summarys.as_mut(), dirs::profile()) {
+        s.note(format!("Profile: {profile}"));
+    }
+    #[cfg(feature = "core_installer")]
+    if let (Some(s), Some(updated)) = (summarys.as_mut(), auto_updated) {
+        let mode = crate::config::cli::cli_config().core_config().auto_update();
+        s.note(format!("Auto update ({mode}): {updated}"));
+    }
+    let mut timers = args.profile_tasks.then(profile::Profile::new);
     for task in task_config.tasks.iter() {
         let name = task.name();
         let task_type = task.task_type();
@@ -169,10 +327,16 @@ where
         if let Some(s) = summarys.as_mut() {
             s.insert(id, name.map(|s| s.to_owned()), task_type);
         }
+        if let Some(timers) = timers.as_mut() {
+            timers.insert(id, name.unwrap_or(task_type.as_ref()));
+        }
     }
     if let Some(s) = summarys {
         summary::init(s);
     }
+    if let Some(timers) = timers {
+        profile::init(timers);
+    }
 
     // Prepare connection
     let (adb, addr, config) = asst_config.connection.connect_args();
@@ -191,6 +355,14 @@ where
     };
 
     if !args.dry_run {
+        if run_hooks {
+            // The post hook is only run if the setup above succeeded and the pre hook was run
+            post_hook.lock().unwrap().clone_from(&task_config.post_hook);
+            if let Some(pre_hook) = task_config.pre_hook.as_deref() {
+                hook::run(pre_hook, None).context("Failed to run pre hook!")?;
+            }
+        }
+
         // Startup external app
         #[cfg(target_os = "macos")]
         let rt = Runtime::new().context("Failed to create tokio runtime")?;
@@ -200,15 +372,24 @@ where
         }
 
         // Connect to game or emulator
+        deadline.check()?;
         asst.async_connect(adb, addr.as_ref(), config, true)?;
 
+        // Connecting is queued before the tasks, so the deadline is checked again
+        // to not start any task if the time is up
+        deadline.check()?;
         asst.start()?;
 
         while asst.running() {
             if stop_bool.load(atomic::Ordering::Relaxed) {
                 bail!("Interrupted by user!");
             }
-            std::thread::sleep(std::time::Duration::from_millis(500));
+            if let Err(err) = deadline.check() {
+                info!("Time is up, stopping running tasks");
+                asst.stop()?;
+                return Err(err.into());
+            }
+            std::thread::sleep(Duration::from_millis(500));
         }
 
         asst.stop()?;
@@ -232,34 +413,88 @@ pub fn run<F>(f: F, args: CommonArgs) -> Result<()>
 where
     F: FnOnce(&AsstConfig) -> Result<TaskConfig>,
 {
-    let ret = run_core(f, args);
+    // Errors of a previous run, e.g. with `--watch`, are not reported again
+    callback::MAA_CORE_ERRORED.store(false, atomic::Ordering::Relaxed);
+    let post_hook = Arc::new(Mutex::new(None));
+    let output = args.output;
+    // The deadline is enforced by a watchdog, in case the run is blocked before checking it
+    let watchdog = args.timeout().map(|timeout| {
+        let post_hook = Arc::clone(&post_hook);
+        Watchdog::spawn(timeout + WATCHDOG_GRACE, move || {
+            abort_blocked_run(timeout, &post_hook)
+        })
+    });
+    let ret = run_core(f, args, &post_hook);
+    if let Some(watchdog) = watchdog {
+        watchdog.cancel();
+    }
+    let post_hook = post_hook.lock().unwrap().take();
 
     summary::display();
 
-    ret?;
+    if let Some(timers) = profile::take() {
+        if let Err(err) = profile::report(&timers, output) {
+            warn!("Failed to record task profile: {err:#}");
+        }
+    }
 
-    if callback::MAA_CORE_ERRORED.load(atomic::Ordering::Relaxed) {
-        bail!("Some error occurred during running task!");
+    let ret = ret.and_then(|_| {
+        if callback::MAA_CORE_ERRORED.load(atomic::Ordering::Relaxed) {
+            bail!("Some error occurred during running task!");
+        }
+        Ok(())
+    });
+
+    if let Some(post_hook) = post_hook {
+        let status = if ret.is_ok() {
+            hook::TaskStatus::Success
+        } else {
+            hook::TaskStatus::Failure
+        };
+        let hook_ret = if ret.as_ref().is_err_and(|err| err.is::<TimedOut>()) {
+            hook::run_within(&post_hook, Some(status), TIMEOUT_HOOK_DEADLINE)
+        } else {
+            hook::run(&post_hook, Some(status))
+        };
+        if let Err(err) = hook_ret {
+            warn!("Failed to run post hook: {err:#}");
+        }
     }
 
-    Ok(())
+    ret
 }
 
 pub fn run_custom(path: impl AsRef<Path>, args: CommonArgs) -> Result<()> {
     run(
         |_| {
             let path = path.as_ref();
-            if let Some(abs_path) = dirs::abs_config(path, Some("tasks")) {
-                TaskConfig::find_file(abs_path)
+            // Task files in the `tasks` directories of all layers are merged,
+            // and an absolute path is found as is
+            let (layers, path) = if path.is_absolute() {
+                (vec![PathBuf::new()], path.to_owned())
             } else {
-                TaskConfig::find_file(path)
-            }
-            .context("Failed to find task file!")
+                (dirs::config_layers(), join!("tasks", path))
+            };
+            let found = layered::find_with::<TaskConfig, _>(&layers, path, include::resolve)?
+                .context("Failed to find task file!")?;
+            found.log_sources("task");
+            Ok(found.config)
         },
         args,
     )
 }
 
+pub fn run_task_file(path: impl AsRef<Path>, args: CommonArgs) -> Result<()> {
+    run(
+        |_| crate::config::task::load_task_from_path(path.as_ref()),
+        args,
+    )
+}
+
+pub fn run_task_json(json: &str, args: CommonArgs) -> Result<()> {
+    run(|_| crate::config::task::parse_task_json(json), args)
+}
+
 pub fn core_version<'a>() -> Result<&'a str> {
     load_core()?;
 
@@ -272,29 +507,31 @@ fn load_core() -> Result<()> {
         return Ok(());
     }
 
-    if let Some(lib_dir) = dirs::find_library() {
-        debug!("Loading MaaCore from: {}", lib_dir.display());
+    let located = crate::locate::load_library(|lib_dir, path| {
+        debug!("Loading MaaCore from: {}", path.display());
         // Set DLL directory on Windows
         #[cfg(target_os = "windows")]
-        {
+        if let Some(lib_dir) = lib_dir {
             use windows::core::HSTRING;
             use windows::Win32::System::LibraryLoader::SetDllDirectoryW;
 
-            unsafe { SetDllDirectoryW(&HSTRING::from(lib_dir.as_ref()))? };
+            unsafe { SetDllDirectoryW(&HSTRING::from(lib_dir)) }.map_err(|e| e.to_string())?;
         }
-        maa_sys::binding::load(lib_dir.join(maa_lib_name()))
-    } else {
-        debug!("MaaCore not found, trying to load from system library path");
-        maa_sys::binding::load(maa_lib_name())
-    }
+        #[cfg(not(target_os = "windows"))]
+        let _ = lib_dir;
+        maa_sys::binding::load(path).map_err(|e| e.to_string())
+    })
     .context("Failed to load MaaCore!")?;
+    debug!("Loaded MaaCore from: {located}");
 
     Ok(())
 }
 
 fn setup_core(config: &AsstConfig) -> Result<()> {
-    debug!("Setting user directory: {}", dirs::state().display());
-    Assistant::set_user_dir(dirs::state().ensure()?).context("Failed to set user directory!")?;
+    // Each profile has its own user directory, so caches and logs of accounts are separated
+    let user_dir = dirs::profile_state();
+    debug!("Setting user directory: {}", user_dir.display());
+    Assistant::set_user_dir(user_dir.ensure()?).context("Failed to set user directory!")?;
 
     config.static_options.apply()?;
     config.resource.load()?;
@@ -315,10 +552,44 @@ mod tests {
         }
     }
 
+    #[test]
+    fn watchdog() {
+        let fired = Arc::new(atomic::AtomicBool::new(false));
+        let on_timeout = |fired: &Arc<atomic::AtomicBool>| {
+            let fired = Arc::clone(fired);
+            move || fired.store(true, atomic::Ordering::Relaxed)
+        };
+
+        let watchdog = Watchdog::spawn(Duration::from_secs(60), on_timeout(&fired));
+        assert!(!watchdog.cancel());
+        assert!(!fired.load(atomic::Ordering::Relaxed));
+
+        let watchdog = Watchdog::spawn(Duration::from_millis(10), on_timeout(&fired));
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(fired.load(atomic::Ordering::Relaxed));
+        assert!(watchdog.cancel());
+    }
+
+    #[test]
+    fn deadline() {
+        Deadline::new(None).check().unwrap();
+        Deadline::new(Some(Duration::from_secs(60)))
+            .check()
+            .unwrap();
+
+        let err = Deadline::new(Some(Duration::ZERO)).check().unwrap_err();
+        assert_eq!(err.to_string(), "Timed out after 0 seconds!");
+
+        // the error can be recognized after adding context
+        let err = anyhow::Error::from(err).context("Failed to run tasks");
+        assert!(err.is::<TimedOut>());
+    }
+
     #[test]
     fn test_find_profile() {
         let test_dir = temp_dir().join("maa_test_find_profile");
         test_dir.ensure_clean().unwrap();
+        let layers = [test_dir.clone()];
 
         let sample_str = r#"
             [connection]
@@ -331,34 +602,25 @@ mod tests {
             config
         };
 
-        assert_eq!(
-            find_profile(&test_dir, None).unwrap(),
-            AsstConfig::default()
-        );
+        assert_eq!(find_profile(&layers, None).unwrap(), AsstConfig::default());
 
         let backcompat_path = test_dir.join("asst.toml");
         let default_path = test_dir.join("profiles").join("default.toml");
         let test_path = test_dir.join("profiles").join("test.toml");
 
         std::fs::write(&backcompat_path, sample_str).unwrap();
-        assert_eq!(find_profile(&test_dir, None).unwrap(), sample_config);
+        assert_eq!(find_profile(&layers, None).unwrap(), sample_config);
         std::fs::remove_file(&backcompat_path).unwrap();
 
         std::fs::create_dir(test_dir.join("profiles")).unwrap();
 
         std::fs::write(&default_path, sample_str).unwrap();
-        assert_eq!(find_profile(&test_dir, None).unwrap(), sample_config);
+        assert_eq!(find_profile(&layers, None).unwrap(), sample_config);
         std::fs::remove_file(&default_path).unwrap();
 
         std::fs::write(&test_path, sample_str).unwrap();
-        assert_eq!(
-            find_profile(&test_dir, None).unwrap(),
-            AsstConfig::default()
-        );
-        assert_eq!(
-            find_profile(&test_dir, Some("test")).unwrap(),
-            sample_config
-        );
+        assert_eq!(find_profile(&layers, None).unwrap(), AsstConfig::default());
+        assert_eq!(find_profile(&layers, Some("test")).unwrap(), sample_config);
         std::fs::remove_file(&test_path).unwrap();
 
         std::fs::remove_dir_all(&test_dir).unwrap();
@@ -56,12 +56,12 @@ enum CopilotJson<'a> {
 }
 
 impl<'a> CopilotJson<'a> {
-    pub fn new(uri: &str) -> Result<CopilotJson> {
+    pub fn new(uri: &str) -> Result<CopilotJson<'_>> {
         let trimmed = uri.trim();
         if let Some(code_str) = trimmed.strip_prefix("maa://") {
             // just check if it's a number
             if code_str.parse::<i64>().is_ok() {
-                return Ok(CopilotJson::Code(code_str));
+                Ok(CopilotJson::Code(code_str))
             } else {
                 bail!("Invalid code: {}", code_str);
             }
@@ -137,7 +137,7 @@ impl CopilotType {
                                 file_path
                                     .file_name()
                                     .and_then(|file_name| file_name.to_str())
-                                    .map_or(false, |file_name| {
+                                    .is_some_and(|file_name| {
                                         file_name.starts_with(stage_id)
                                             && file_name.ends_with("json")
                                     })
@@ -0,0 +1,319 @@
+//! Execution time of tasks, enabled by `--profile-tasks`.
+//!
+//! Tasks are timed by the callbacks of MaaCore. After all tasks finished, the timing is printed
+//! and appended to the history file, one JSON object per run, so trends can be viewed
+//! by `maa run --show-profile-history <N>`.
+
+use crate::dirs::{self, Ensure};
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use clap::ValueEnum;
+use maa_sys::binding::AsstTaskId;
+use prettytable::{format, row, Table};
+use serde::{Deserialize, Serialize};
+
+static PROFILE: Mutex<Option<Profile>> = Mutex::new(None);
+
+pub(super) fn init(profile: Profile) {
+    *PROFILE.lock().unwrap() = Some(profile);
+}
+
+pub(super) fn start_task(id: AsstTaskId) -> Option<()> {
+    PROFILE.lock().unwrap().as_mut()?.start_task(id)
+}
+
+pub(super) fn end_task(id: AsstTaskId, status: Status) -> Option<()> {
+    PROFILE.lock().unwrap().as_mut()?.end_task(id, status)
+}
+
+/// Take the profile of current run, leaving nothing for the next run
+pub(super) fn take() -> Option<Profile> {
+    PROFILE.lock().unwrap().take()
+}
+
+#[derive(ValueEnum, Clone, Copy, Default)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub enum OutputFormat {
+    /// Human readable table
+    #[default]
+    Text,
+    /// JSON array of tasks, or of runs for the history
+    Json,
+}
+
+#[cfg_attr(test, derive(Debug, PartialEq))]
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Success,
+    Failure,
+}
+
+impl std::fmt::Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Status::Success => write!(f, "success"),
+            Status::Failure => write!(f, "failure"),
+        }
+    }
+}
+
+struct TaskTimer {
+    id: AsstTaskId,
+    name: String,
+    start: Option<Instant>,
+    end: Option<(Duration, Status)>,
+}
+
+/// Timers of tasks in current run
+pub struct Profile {
+    tasks: Vec<TaskTimer>,
+}
+
+impl Profile {
+    pub fn new() -> Self {
+        Self { tasks: Vec::new() }
+    }
+
+    pub fn insert(&mut self, id: AsstTaskId, name: impl Into<String>) {
+        self.tasks.push(TaskTimer {
+            id,
+            name: name.into(),
+            start: None,
+            end: None,
+        });
+    }
+
+    fn get_mut(&mut self, id: AsstTaskId) -> Option<&mut TaskTimer> {
+        self.tasks.iter_mut().find(|task| task.id == id)
+    }
+
+    fn start_task(&mut self, id: AsstTaskId) -> Option<()> {
+        self.get_mut(id)?.start = Some(Instant::now());
+        Some(())
+    }
+
+    fn end_task(&mut self, id: AsstTaskId, status: Status) -> Option<()> {
+        let task = self.get_mut(id)?;
+        task.end = Some((task.start?.elapsed(), status));
+        Some(())
+    }
+
+    /// Timing of started tasks, tasks which did not end are reported as failures
+    pub fn records(&self) -> Vec<TaskRecord> {
+        self.tasks
+            .iter()
+            .filter_map(|task| {
+                let (duration, status) = task
+                    .end
+                    .or_else(|| task.start.map(|start| (start.elapsed(), Status::Failure)))?;
+                Some(TaskRecord {
+                    task: task.name.clone(),
+                    duration_ms: duration.as_millis() as u64,
+                    status,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg_attr(test, derive(Debug, PartialEq))]
+#[derive(Serialize, Deserialize)]
+pub struct TaskRecord {
+    task: String,
+    duration_ms: u64,
+    status: Status,
+}
+
+/// Timing of all tasks in a run, stored in the history file
+#[cfg_attr(test, derive(Debug, PartialEq))]
+#[derive(Serialize, Deserialize)]
+pub struct RunRecord {
+    time: DateTime<Local>,
+    tasks: Vec<TaskRecord>,
+}
+
+fn format_duration(ms: u64) -> String {
+    format!("{}.{:03}s", ms / 1000, ms % 1000)
+}
+
+fn task_table(tasks: &[TaskRecord]) -> Table {
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
+    table.set_titles(row!["TASK", "DURATION", "STATUS"]);
+    for task in tasks {
+        table.add_row(row![
+            task.task,
+            r->format_duration(task.duration_ms),
+            task.status
+        ]);
+    }
+    table
+}
+
+/// Print the timing of tasks, and append it to the history file.
+pub(super) fn report(profile: &Profile, output: OutputFormat) -> Result<()> {
+    let tasks = profile.records();
+    if tasks.is_empty() {
+        return Ok(());
+    }
+
+    match output {
+        OutputFormat::Text => task_table(&tasks).printstd(),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&tasks)?),
+    }
+
+    let run = RunRecord {
+        time: Local::now(),
+        tasks,
+    };
+    append_history(&history_path(), &run)
+}
+
+fn history_path() -> PathBuf {
+    dirs::cache().join("task_profile_history.json")
+}
+
+fn append_history(path: &Path, run: &RunRecord) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        dir.ensure()?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    writeln!(file, "{}", serde_json::to_string(run)?)
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Read the last `n` runs in the history file, lines which can not be parsed are skipped
+fn read_history(path: &Path, n: usize) -> Result<Vec<RunRecord>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let file =
+        std::fs::File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let runs: Vec<RunRecord> = BufReader::new(file)
+        .lines()
+        .map_while(std::io::Result::ok)
+        .filter_map(|line| serde_json::from_str(&line).ok())
+        .collect();
+    let skip = runs.len().saturating_sub(n);
+    Ok(runs.into_iter().skip(skip).collect())
+}
+
+/// Print the timing of the last `n` profiled runs.
+pub fn show_history(n: usize, output: OutputFormat) -> Result<()> {
+    let runs = read_history(&history_path(), n)?;
+    match output {
+        OutputFormat::Text => {
+            if runs.is_empty() {
+                println!("No profiled runs found, run tasks with `--profile-tasks` first");
+            }
+            for run in &runs {
+                let total: u64 = run.tasks.iter().map(|task| task.duration_ms).sum();
+                println!(
+                    "{} ({})",
+                    run.time.format("%Y-%m-%d %H:%M:%S"),
+                    format_duration(total)
+                );
+                task_table(&run.tasks).printstd();
+            }
+        }
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&runs)?),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profile_tasks() {
+        let mut profile = Profile::new();
+        profile.insert(1, "StartUp");
+        profile.insert(2, "Fight");
+        profile.insert(3, "Mall");
+
+        assert!(profile.records().is_empty());
+
+        profile.start_task(1).unwrap();
+        std::thread::sleep(Duration::from_millis(10));
+        profile.end_task(1, Status::Success).unwrap();
+        profile.start_task(2).unwrap();
+        assert!(profile.start_task(4).is_none());
+        assert!(profile.end_task(3, Status::Success).is_none());
+
+        let records = profile.records();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].task, "StartUp");
+        assert!(records[0].duration_ms >= 10);
+        assert_eq!(records[0].status, Status::Success);
+        // tasks which did not end are failures
+        assert_eq!(records[1].task, "Fight");
+        assert_eq!(records[1].status, Status::Failure);
+
+        assert_eq!(
+            serde_json::to_value(&records[0]).unwrap(),
+            serde_json::json!({
+                "task": "StartUp",
+                "duration_ms": records[0].duration_ms,
+                "status": "success",
+            })
+        );
+    }
+
+    #[test]
+    fn history() {
+        let dir = std::env::temp_dir().join("maa-test-profile-history");
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir).unwrap();
+        }
+        let path = dir.join("task_profile_history.json");
+
+        assert!(read_history(&path, 5).unwrap().is_empty());
+
+        let run = |duration_ms| RunRecord {
+            time: Local::now(),
+            tasks: vec![TaskRecord {
+                task: "Fight".to_owned(),
+                duration_ms,
+                status: Status::Success,
+            }],
+        };
+        for duration_ms in 0..3 {
+            append_history(&path, &run(duration_ms)).unwrap();
+        }
+        // broken lines are skipped
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"{\n")
+            .unwrap();
+
+        let runs = read_history(&path, 2).unwrap();
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[0].tasks[0].duration_ms, 1);
+        assert_eq!(runs[1].tasks[0].duration_ms, 2);
+        assert_eq!(read_history(&path, 5).unwrap().len(), 3);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn format() {
+        assert_eq!(format_duration(0), "0.000s");
+        assert_eq!(format_duration(61_234), "61.234s");
+    }
+}
@@ -0,0 +1,210 @@
+//! Re-run a task when its config files change, used by `maa run --watch` and `maa config watch`.
+//!
+//! Directories are watched recursively, while files are watched by their parent directories,
+//! so a file replaced by an editor on saving is still watched.
+
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use log::{debug, info, warn};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use signal_hook::consts::TERM_SIGNALS;
+
+/// Interval to check whether the watch is stopped while waiting for events
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Watched paths and the receiver of their events
+struct Watch {
+    // The watcher stops sending events when dropped
+    _watcher: notify::RecommendedWatcher,
+    paths: Vec<PathBuf>,
+    events: Receiver<notify::Result<Event>>,
+}
+
+impl Watch {
+    fn new(paths: &[PathBuf]) -> Result<Self> {
+        let (tx, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(tx).context("Failed to create watcher")?;
+        let mut watched = Vec::new();
+        for path in paths {
+            // Paths in events are absolute, so watched paths are canonicalized to match them
+            let Ok(path) = dunce::canonicalize(path) else {
+                warn!("Path {} does not exist, skip watching it", path.display());
+                continue;
+            };
+            let (target, mode) = match path.parent() {
+                Some(parent) if !path.is_dir() => (parent, RecursiveMode::NonRecursive),
+                _ => (path.as_path(), RecursiveMode::Recursive),
+            };
+            watcher
+                .watch(target, mode)
+                .with_context(|| format!("Failed to watch {}", path.display()))?;
+            watched.push(path);
+        }
+        Ok(Self {
+            _watcher: watcher,
+            paths: watched,
+            events,
+        })
+    }
+
+    /// Whether the event changes any watched path
+    fn is_change(&self, event: &Event) -> bool {
+        !matches!(event.kind, EventKind::Access(_))
+            && event
+                .paths
+                .iter()
+                .any(|path| self.paths.iter().any(|watched| path.starts_with(watched)))
+    }
+
+    /// Wait until the watched paths change and stay unchanged for the debounce duration.
+    ///
+    /// Editors may write a file in multiple steps, the debounce avoids running the task
+    /// with a partially written file. Changes before waiting are ignored.
+    /// Return `false` if stopped before any change.
+    fn wait_for_change(&self, debounce: Duration, stop: &AtomicBool) -> bool {
+        while self.events.try_recv().is_ok() {}
+
+        let mut changed: Option<Instant> = None;
+        loop {
+            if stop.load(Ordering::Relaxed) {
+                return false;
+            }
+            match self.events.recv_timeout(POLL_INTERVAL) {
+                // Changed, wait for the paths to settle or restart the debounce
+                Ok(Ok(event)) if self.is_change(&event) => {
+                    debug!("Changed: {:?}", event.paths);
+                    changed = Some(Instant::now());
+                }
+                Ok(Ok(_)) | Err(RecvTimeoutError::Timeout) => {}
+                Ok(Err(err)) => warn!("Failed to watch changes: {err}"),
+                Err(RecvTimeoutError::Disconnected) => return false,
+            }
+            if changed.is_some_and(|since| since.elapsed() >= debounce) {
+                return true;
+            }
+        }
+    }
+}
+
+/// Run the task, and run it again every time the watched paths change.
+///
+/// Errors of a run, e.g. an invalid config, are reported without stopping the watch,
+/// so the user can fix the config and save it again. The loop exits on Ctrl+C.
+pub fn watch<F>(mut run: F, paths: Vec<PathBuf>, debounce: Duration) -> Result<()>
+where
+    F: FnMut() -> Result<()>,
+{
+    let stop = Arc::new(AtomicBool::new(false));
+    for sig in TERM_SIGNALS {
+        signal_hook::flag::register(*sig, Arc::clone(&stop))
+            .context("Failed to register signal handler!")?;
+    }
+
+    let watch = Watch::new(&paths)?;
+    loop {
+        if let Err(err) = run() {
+            if stop.load(Ordering::Relaxed) {
+                return Err(err);
+            }
+            warn!("{err:#}");
+        }
+
+        info!("Watching for changes, press Ctrl+C to exit");
+        if !watch.wait_for_change(debounce, &stop) {
+            return Ok(());
+        }
+        info!("Config changed, running the task again");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{env::temp_dir, fs, path::Path};
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = temp_dir().join(name);
+        if dir.exists() {
+            fs::remove_dir_all(&dir).unwrap();
+        }
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write_later(path: &Path, delay: Duration) -> std::thread::JoinHandle<()> {
+        let path = path.to_owned();
+        std::thread::spawn(move || {
+            std::thread::sleep(delay);
+            fs::write(path, "").unwrap();
+        })
+    }
+
+    #[test]
+    fn wait_change() {
+        let dir = test_dir("maa-test-watch-wait");
+        fs::create_dir_all(dir.join("tasks")).unwrap();
+        let watch = Watch::new(&[dir.clone(), dir.join("missing")]).unwrap();
+        assert_eq!(watch.paths.len(), 1);
+
+        // stopped before any change
+        let stop = AtomicBool::new(true);
+        assert!(!watch.wait_for_change(Duration::ZERO, &stop));
+
+        // changes in sub directories are watched
+        let writer = write_later(&dir.join("tasks/daily.toml"), Duration::from_millis(200));
+        let stop = AtomicBool::new(false);
+        let start = Instant::now();
+        assert!(watch.wait_for_change(Duration::from_millis(300), &stop));
+        assert!(start.elapsed() >= Duration::from_millis(500));
+        writer.join().unwrap();
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn wait_file_change() {
+        let dir = test_dir("maa-test-watch-wait-file");
+        let path = dir.join("task.toml");
+        fs::write(&path, "").unwrap();
+        let watch = Watch::new(std::slice::from_ref(&path)).unwrap();
+
+        // changes of other files in the same directory are ignored
+        let stop = Arc::new(AtomicBool::new(false));
+        let writer = write_later(&dir.join("other.toml"), Duration::from_millis(100));
+        let stopper = {
+            let stop = Arc::clone(&stop);
+            std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(500));
+                stop.store(true, Ordering::Relaxed);
+            })
+        };
+        assert!(!watch.wait_for_change(Duration::ZERO, &stop));
+        writer.join().unwrap();
+        stopper.join().unwrap();
+
+        // a file replaced by renaming is still watched
+        let writer = std::thread::spawn({
+            let (dir, path) = (dir.clone(), path.clone());
+            move || {
+                std::thread::sleep(Duration::from_millis(100));
+                fs::write(dir.join("task.toml.tmp"), "").unwrap();
+                fs::rename(dir.join("task.toml.tmp"), path).unwrap();
+            }
+        });
+        let stop = AtomicBool::new(false);
+        assert!(watch.wait_for_change(Duration::from_millis(100), &stop));
+        writer.join().unwrap();
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,343 @@
+use super::{MAAValue, Map};
+
+impl MAAValue {
+    /// Flatten nested objects and arrays into a map with dot-notation keys.
+    ///
+    /// For example, `{"a": {"b": 1, "c": [2, 3]}}` is flattened into
+    /// `{"a.b": 1, "a.c.0": 2, "a.c.1": 3}`. Dots and backslashes in keys are escaped
+    /// as `\.` and `\\`, so the original structure can be restored by [`MAAValue::from_flat_map`].
+    /// Empty objects and arrays are kept as values, other values are leaves.
+    ///
+    /// If the value is not an object or an array, it is stored with an empty key.
+    pub fn into_flat_map(self) -> Map<String, MAAValue> {
+        fn flatten(prefix: Option<String>, value: MAAValue, map: &mut Map<String, MAAValue>) {
+            let join = |key: &str| match &prefix {
+                Some(prefix) => format!("{prefix}.{key}"),
+                None => key.to_owned(),
+            };
+            match value {
+                MAAValue::Object(object) if !object.is_empty() => {
+                    for (key, value) in object {
+                        flatten(Some(join(&escape(&key))), value, map);
+                    }
+                }
+                MAAValue::Array(array) if !array.is_empty() => {
+                    for (index, value) in array.into_iter().enumerate() {
+                        flatten(Some(join(&index.to_string())), value, map);
+                    }
+                }
+                value => {
+                    map.insert(prefix.unwrap_or_default(), value);
+                }
+            }
+        }
+
+        let mut map = Map::new();
+        flatten(None, self, &mut map);
+        map
+    }
+
+    /// Reconstruct nested objects from a map with dot-notation keys,
+    /// the inverse of [`MAAValue::into_flat_map`].
+    ///
+    /// An object whose keys are exactly `0`, `1`, ..., `n-1` is restored as an array.
+    /// If a key is both a leaf and a parent of other keys, e.g. `a` and `a.b`,
+    /// the nested keys take precedence.
+    pub fn from_flat_map(map: Map<String, MAAValue>) -> Self {
+        if map.len() == 1 && map.contains_key("") {
+            return map.into_values().next().expect("map has one value");
+        }
+
+        let mut root = MAAValue::new();
+        for (key, value) in map {
+            let segments = split(&key);
+            let (last, parents) = segments.split_last().expect("split is never empty");
+            let mut current = &mut root;
+            for parent in parents {
+                let MAAValue::Object(object) = current else {
+                    unreachable!("parents are always objects");
+                };
+                let entry = object.entry(parent.clone()).or_insert_with(MAAValue::new);
+                if !matches!(entry, MAAValue::Object(_)) {
+                    *entry = MAAValue::new();
+                }
+                current = entry;
+            }
+            let MAAValue::Object(object) = current else {
+                unreachable!("parents are always objects");
+            };
+            match object.get(last) {
+                Some(MAAValue::Object(_)) => {}
+                _ => {
+                    object.insert(last.clone(), value);
+                }
+            }
+        }
+
+        into_arrays(root)
+    }
+
+    /// Get the value at a dot-notation path, like the keys of [`MAAValue::into_flat_map`]
+    ///
+    /// Return `Ok(None)` if the path does not exist but can be created, i.e. a missing key
+    /// of an object or the index right after the end of an array. An error is returned if the
+    /// path goes through a value which is neither an object nor an array, or an array index
+    /// is not a number or out of bounds.
+    pub fn get_flat(&self, key: &str) -> Result<Option<&Self>, String> {
+        let segments = split(key);
+        let mut current = Some(self);
+        for (depth, segment) in segments.iter().enumerate() {
+            let parent = || match depth {
+                0 => "the root".to_owned(),
+                _ => format!("`{}`", join(&segments[..depth])),
+            };
+            current = match current {
+                None => None,
+                Some(MAAValue::Object(map)) => map.get(segment),
+                Some(MAAValue::Array(array)) => {
+                    let index: usize = segment.parse().map_err(|_| {
+                        format!("`{segment}` is not an index of the array at {}", parent())
+                    })?;
+                    if index > array.len() {
+                        return Err(format!(
+                            "index {index} is out of bounds of the array at {} with {} elements",
+                            parent(),
+                            array.len()
+                        ));
+                    }
+                    array.get(index)
+                }
+                Some(value) => {
+                    return Err(format!(
+                        "{} is a {}, not an object or an array",
+                        parent(),
+                        value.value_type()
+                    ))
+                }
+            };
+        }
+        Ok(current)
+    }
+
+    /// Build a patch which sets values at dot-notation paths when merged by
+    /// [`MAAValue::merge_mut`] into this value.
+    ///
+    /// Arrays are replaced as a whole when merged, so an array on a path is copied into the
+    /// patch with the element at the path replaced. Paths should be checked by
+    /// [`MAAValue::get_flat`] before.
+    pub fn flat_patch(&self, values: Map<String, MAAValue>) -> MAAValue {
+        let mut flat = Map::new();
+        for key in values.keys() {
+            let segments = split(key);
+            let mut current = Some(self);
+            for (depth, segment) in segments.iter().enumerate() {
+                match current {
+                    Some(MAAValue::Object(map)) => current = map.get(segment),
+                    Some(array @ MAAValue::Array(_)) => {
+                        let prefix = join(&segments[..depth]);
+                        for (key, value) in array.clone().into_flat_map() {
+                            let key = match (prefix.is_empty(), key.is_empty()) {
+                                (true, _) => key,
+                                (false, true) => prefix.clone(),
+                                (false, false) => format!("{prefix}.{key}"),
+                            };
+                            flat.insert(key, value);
+                        }
+                        break;
+                    }
+                    _ => break,
+                }
+            }
+        }
+        for (key, value) in values {
+            let children = format!("{key}.");
+            flat.retain(|flat_key, _| !flat_key.starts_with(&children));
+            flat.insert(key, value);
+        }
+        MAAValue::from_flat_map(flat)
+    }
+}
+
+/// Join segments into a flat key, escaping dots and backslashes in them
+fn join(segments: &[String]) -> String {
+    segments
+        .iter()
+        .map(|segment| escape(segment))
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+fn escape(key: &str) -> String {
+    key.replace('\\', "\\\\").replace('.', "\\.")
+}
+
+/// Split a flat key at unescaped dots and unescape the segments
+fn split(key: &str) -> Vec<String> {
+    let mut segments = vec![String::new()];
+    let mut chars = key.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                if let Some(next) = chars.next() {
+                    segments.last_mut().unwrap().push(next);
+                }
+            }
+            '.' => segments.push(String::new()),
+            c => segments.last_mut().unwrap().push(c),
+        }
+    }
+    segments
+}
+
+/// Convert objects with keys `0..n` into arrays recursively
+fn into_arrays(value: MAAValue) -> MAAValue {
+    match value {
+        MAAValue::Object(object) => {
+            let object: Map<String, MAAValue> = object
+                .into_iter()
+                .map(|(key, value)| (key, into_arrays(value)))
+                .collect();
+            let is_array = !object.is_empty()
+                && (0..object.len()).all(|i| object.contains_key(&i.to_string()));
+            if is_array {
+                let mut object = object;
+                MAAValue::Array(
+                    (0..object.len())
+                        .map(|i| object.remove(&i.to_string()).unwrap())
+                        .collect(),
+                )
+            } else {
+                MAAValue::Object(object)
+            }
+        }
+        value => value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::object;
+
+    #[test]
+    fn flatten() {
+        let value = object!(
+            "a" => object!("b" => 1, "c" => [2, 3]),
+            "d.e" => "dot",
+            "f\\g" => true,
+            "empty" => MAAValue::new(),
+            "null" => MAAValue::Null,
+        );
+        let map = value.clone().into_flat_map();
+        assert_eq!(
+            map,
+            Map::from([
+                ("a.b".to_owned(), MAAValue::from(1)),
+                ("a.c.0".to_owned(), MAAValue::from(2)),
+                ("a.c.1".to_owned(), MAAValue::from(3)),
+                ("d\\.e".to_owned(), MAAValue::from("dot")),
+                ("f\\\\g".to_owned(), MAAValue::from(true)),
+                ("empty".to_owned(), MAAValue::new()),
+                ("null".to_owned(), MAAValue::Null),
+            ])
+        );
+        assert_eq!(MAAValue::from_flat_map(map), value);
+
+        assert_eq!(
+            MAAValue::from(1).into_flat_map(),
+            Map::from([(String::new(), MAAValue::from(1))])
+        );
+        assert_eq!(
+            MAAValue::from_flat_map(MAAValue::from(1).into_flat_map()),
+            MAAValue::from(1)
+        );
+    }
+
+    #[test]
+    fn unflatten() {
+        assert_eq!(MAAValue::from_flat_map(Map::new()), MAAValue::new());
+        assert_eq!(
+            MAAValue::from_flat_map(Map::from([
+                ("arr.1".to_owned(), MAAValue::from(1)),
+                ("arr.0".to_owned(), MAAValue::from(0)),
+                ("sparse.1".to_owned(), MAAValue::from(1)),
+            ])),
+            object!("arr" => [0, 1], "sparse" => object!("1" => 1)),
+        );
+        // nested keys take precedence over leaves
+        assert_eq!(
+            MAAValue::from_flat_map(Map::from([
+                ("a".to_owned(), MAAValue::from(1)),
+                ("a.b".to_owned(), MAAValue::from(2)),
+            ])),
+            object!("a" => object!("b" => 2)),
+        );
+    }
+
+    #[test]
+    fn get_flat() {
+        let value = object!(
+            "a" => object!("b" => 1, "c" => [2, 3]),
+            "d.e" => "dot",
+        );
+        assert_eq!(value.get_flat("a.b"), Ok(Some(&MAAValue::from(1))));
+        assert_eq!(value.get_flat("a.c.1"), Ok(Some(&MAAValue::from(3))));
+        assert_eq!(value.get_flat("d\\.e"), Ok(Some(&MAAValue::from("dot"))));
+        // paths which can be created
+        assert_eq!(value.get_flat("a.x.y"), Ok(None));
+        assert_eq!(value.get_flat("a.c.2"), Ok(None));
+
+        assert_eq!(
+            value.get_flat("a.c.3"),
+            Err("index 3 is out of bounds of the array at `a.c` with 2 elements".to_owned())
+        );
+        assert_eq!(
+            value.get_flat("a.c.x"),
+            Err("`x` is not an index of the array at `a.c`".to_owned())
+        );
+        assert_eq!(
+            value.get_flat("a.b.x"),
+            Err("`a.b` is a int, not an object or an array".to_owned())
+        );
+        assert_eq!(
+            MAAValue::from(1).get_flat("a"),
+            Err("the root is a int, not an object or an array".to_owned())
+        );
+    }
+
+    #[test]
+    fn flat_patch() {
+        let mut value = object!(
+            "a" => object!("b" => 1, "c" => [2, 3]),
+            "list" => [object!("x" => 1), object!("x" => 2)],
+            "empty" => MAAValue::Array(vec![]),
+        );
+        let patch = value.flat_patch(Map::from([
+            ("a.b".to_owned(), MAAValue::from(10)),
+            ("a.c.0".to_owned(), MAAValue::from(20)),
+            ("list.1.x".to_owned(), MAAValue::from(30)),
+            ("empty.0".to_owned(), MAAValue::from(40)),
+            ("new.key".to_owned(), MAAValue::from("new")),
+        ]));
+        value.merge_mut(&patch);
+        assert_eq!(
+            value,
+            object!(
+                "a" => object!("b" => 10, "c" => [20, 3]),
+                "list" => [object!("x" => 1), object!("x" => 30)],
+                "empty" => [40],
+                "new" => object!("key" => "new"),
+            )
+        );
+
+        // a value replaces nested keys of the same path
+        let mut value = object!("a" => object!("b" => 1));
+        let patch = value.flat_patch(Map::from([
+            ("a".to_owned(), object!("c" => 2)),
+            ("a.b".to_owned(), MAAValue::from(3)),
+        ]));
+        value.merge_mut(&patch);
+        assert_eq!(value, object!("a" => object!("b" => 3, "c" => 2)));
+    }
+}
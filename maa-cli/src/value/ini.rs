@@ -0,0 +1,233 @@
+use super::{query::primate_to_string, MAAValue, Map, ParseError, TryFromError};
+
+/// Section of key-value pairs before any section header
+const DEFAULT_SECTION: &str = "default";
+
+impl MAAValue {
+    /// Parse INI into an object, where each section is a nested object.
+    ///
+    /// Key-value pairs before any section header go into the `default` section.
+    /// Lines starting with `;` or `#` are comments, and values are kept as strings,
+    /// with surrounding quotes removed. A repeated section is merged into the previous one,
+    /// and a repeated key overrides the previous value.
+    pub fn from_ini(s: &str) -> Result<Self, ParseError> {
+        let mut root = Map::new();
+        let mut section = DEFAULT_SECTION.to_owned();
+        for (index, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with([';', '#']) {
+                continue;
+            }
+
+            let invalid = || ParseError::InvalidLine {
+                line: index + 1,
+                content: line.to_owned(),
+            };
+            if let Some(name) = line.strip_prefix('[') {
+                let name = name.strip_suffix(']').ok_or_else(invalid)?.trim();
+                if name.is_empty() {
+                    return Err(invalid());
+                }
+                section = name.to_owned();
+                root.entry(section.clone()).or_insert_with(MAAValue::new);
+                continue;
+            }
+
+            let (key, value) = line.split_once('=').ok_or_else(invalid)?;
+            let key = key.trim();
+            if key.is_empty() {
+                return Err(ParseError::EmptyKey(line.to_owned()));
+            }
+            let value = value.trim();
+            let value = ['"', '\'']
+                .into_iter()
+                .find_map(|q| value.strip_prefix(q)?.strip_suffix(q))
+                .unwrap_or(value);
+
+            match root.entry(section.clone()).or_insert_with(MAAValue::new) {
+                MAAValue::Object(map) => map.insert(key.to_owned(), value.into()),
+                _ => unreachable!("sections are always objects"),
+            };
+        }
+        Ok(MAAValue::Object(root))
+    }
+
+    /// Serialize an object into INI.
+    ///
+    /// Values at the top level are written before any section, and nested objects
+    /// are written as sections. Arrays are written as comma separated values.
+    ///
+    /// # Errors
+    ///
+    /// Return [`TryFromError::TypeMismatch`] if the value is not an object, it is nested
+    /// deeper than two levels, or it contains nested arrays or uninitialized values.
+    pub fn to_ini(&self) -> Result<String, TryFromError> {
+        fn to_string(value: &MAAValue) -> Result<String, TryFromError> {
+            match value {
+                MAAValue::Primate(v) => Ok(primate_to_string(v)),
+                MAAValue::Array(values) => values
+                    .iter()
+                    .map(|value| match value {
+                        MAAValue::Primate(v) => Ok(primate_to_string(v)),
+                        _ => Err(TryFromError::TypeMismatch),
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+                    .map(|values| values.join(",")),
+                _ => Err(TryFromError::TypeMismatch),
+            }
+        }
+
+        let MAAValue::Object(root) = self else {
+            return Err(TryFromError::TypeMismatch);
+        };
+
+        let mut ini = String::new();
+        let (sections, values): (Vec<_>, Vec<_>) = root
+            .iter()
+            .partition(|(_, value)| matches!(value, MAAValue::Object(_)));
+        for (key, value) in values {
+            ini.push_str(&format!("{key}={}\n", to_string(value)?));
+        }
+        for (name, section) in sections {
+            let MAAValue::Object(map) = section else {
+                unreachable!("partitioned by objects");
+            };
+            if !ini.is_empty() {
+                ini.push('\n');
+            }
+            ini.push_str(&format!("[{name}]\n"));
+            for (key, value) in map {
+                ini.push_str(&format!("{key}={}\n", to_string(value)?));
+            }
+        }
+        Ok(ini)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::object;
+
+    #[test]
+    fn from_ini() {
+        assert_eq!(
+            MAAValue::from_ini(
+                r#"
+; legacy config
+client = Official
+stage="1-7"
+
+[Connection]
+# comment
+address = 127.0.0.1:5555
+config= 'CompatMac'
+
+[Fight]
+medicine = 2
+[Connection]
+adb_path = adb
+"#
+            )
+            .unwrap(),
+            object!(
+                "default" => object!("client" => "Official", "stage" => "1-7"),
+                "Connection" => object!(
+                    "address" => "127.0.0.1:5555",
+                    "config" => "CompatMac",
+                    "adb_path" => "adb",
+                ),
+                "Fight" => object!("medicine" => "2"),
+            )
+        );
+
+        assert_eq!(MAAValue::from_ini("").unwrap(), MAAValue::new());
+        assert_eq!(
+            MAAValue::from_ini("[empty]").unwrap(),
+            object!("empty" => MAAValue::new())
+        );
+        assert_eq!(
+            MAAValue::from_ini("a = 1 = 2").unwrap(),
+            object!("default" => object!("a" => "1 = 2"))
+        );
+
+        assert_eq!(
+            MAAValue::from_ini("a=1\nnot a pair"),
+            Err(ParseError::InvalidLine {
+                line: 2,
+                content: "not a pair".to_owned()
+            })
+        );
+        assert!(matches!(
+            MAAValue::from_ini("[section"),
+            Err(ParseError::InvalidLine { line: 1, .. })
+        ));
+        assert!(matches!(
+            MAAValue::from_ini("[ ]"),
+            Err(ParseError::InvalidLine { line: 1, .. })
+        ));
+        assert_eq!(
+            MAAValue::from_ini("= 1"),
+            Err(ParseError::EmptyKey("= 1".to_owned()))
+        );
+    }
+
+    #[test]
+    fn to_ini() {
+        let value = object!(
+            "client" => "Official",
+            "stages" => ["1-7", "CE-6"],
+            "Fight" => object!("medicine" => 2, "expiring_medicine" => true, "ratio" => 0.5),
+            "Connection" => object!("address" => "127.0.0.1:5555"),
+        );
+        let ini = value.to_ini().unwrap();
+        assert_eq!(
+            ini,
+            "client=Official\n\
+             stages=1-7,CE-6\n\
+             \n\
+             [Connection]\n\
+             address=127.0.0.1:5555\n\
+             \n\
+             [Fight]\n\
+             expiring_medicine=true\n\
+             medicine=2\n\
+             ratio=0.5\n"
+        );
+
+        // values are read back as strings, with bare values in the default section
+        assert_eq!(
+            MAAValue::from_ini(&ini).unwrap(),
+            object!(
+                "default" => object!("client" => "Official", "stages" => "1-7,CE-6"),
+                "Connection" => object!("address" => "127.0.0.1:5555"),
+                "Fight" => object!(
+                    "medicine" => "2",
+                    "expiring_medicine" => "true",
+                    "ratio" => "0.5",
+                ),
+            )
+        );
+
+        assert_eq!(MAAValue::new().to_ini().unwrap(), "");
+        assert_eq!(
+            object!("section" => MAAValue::new()).to_ini().unwrap(),
+            "[section]\n"
+        );
+
+        assert_eq!(MAAValue::from(1).to_ini(), Err(TryFromError::TypeMismatch));
+        assert_eq!(
+            object!("a" => object!("b" => object!("c" => 1))).to_ini(),
+            Err(TryFromError::TypeMismatch)
+        );
+        assert_eq!(
+            object!("a" => [object!("b" => 1)]).to_ini(),
+            Err(TryFromError::TypeMismatch)
+        );
+        assert_eq!(
+            object!("a" => MAAValue::Null).to_ini(),
+            Err(TryFromError::TypeMismatch)
+        );
+    }
+}
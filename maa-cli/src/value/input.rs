@@ -35,6 +35,27 @@ impl MAAInput {
             SelectString(v) => Ok(String(v.value()?)),
         }
     }
+
+    /// Describe the input by its prompt without querying the user,
+    /// lines of the prompt are joined by `; `.
+    pub(super) fn describe(&self) -> std::string::String {
+        use MAAInput::*;
+        let mut buffer = Vec::new();
+        match self {
+            InputBool(v) => v.prompt(&mut buffer),
+            InputInt(v) => v.prompt(&mut buffer),
+            InputFloat(v) => v.prompt(&mut buffer),
+            InputString(v) => v.prompt(&mut buffer),
+            SelectInt(v) => v.prompt(&mut buffer),
+            SelectFloat(v) => v.prompt(&mut buffer),
+            SelectString(v) => v.prompt(&mut buffer),
+        }
+        .expect("writing to a buffer never fails");
+        std::string::String::from_utf8_lossy(&buffer)
+            .lines()
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
 }
 
 impl From<BoolInput> for MAAInput {
@@ -6,11 +6,45 @@ pub use primate::MAAPrimate;
 mod input;
 pub use input::MAAInput;
 
+mod size;
+
+mod flat;
+
+#[cfg(test)]
+mod query;
+#[cfg(test)]
+pub use query::ParseError;
+
+#[cfg(test)]
+mod ini;
+
+// Tree rendering is used to display config files being edited by `maa config watch`
+mod tree;
+
+mod pointer;
+pub use pointer::ValueType;
+
+#[cfg(test)]
+mod rename;
+
+#[cfg(test)]
+mod schema;
+
+#[cfg(test)]
+mod patch;
+
 pub use std::collections::BTreeMap as Map;
 use std::io;
 
 use serde::{Deserialize, Serialize};
 
+/// Key of the JSON schema URL in a config file, which is dropped when the value is initialized
+pub const SCHEMA_KEY: &str = "$schema";
+
+/// Maximum number of replacements along each path from the root in [`MAAValue::replace_all`]
+#[cfg(test)]
+pub const DEFAULT_MAX_REPLACE_DEPTH: usize = 16;
+
 #[cfg_attr(test, derive(PartialEq, Debug))]
 #[derive(Deserialize, Clone)]
 #[serde(untagged)]
@@ -40,6 +74,8 @@ pub enum MAAValue {
     Object(Map<String, MAAValue>),
     /// Primate json types: bool, int, float, string
     Primate(MAAPrimate),
+    /// A null value, which means the key should be removed, see [`MAAValue::strip_nulls_mut`]
+    Null,
 }
 
 #[cfg_attr(test, derive(PartialEq, Debug))]
@@ -83,6 +119,7 @@ impl Serialize for MAAValue {
             Array(v) => v.serialize(serializer),
             // Serialize as a map of key-value pairs and filter all the missing values
             Object(v) => v.serialize(serializer),
+            Null => serializer.serialize_unit(),
             // Input value should be initialized before serializing
             _ => serr!("cannot serialize input value, you should initialize it first"),
         }
@@ -101,6 +138,7 @@ impl MAAValue {
     /// If the value is an input value, try to get the value from user input and set it to the value.
     /// If the value is an array or an object, initialize all the values in it recursively.
     /// If the value is an optional value, initialize it only if all the dependencies are satisfied.
+    /// The [`SCHEMA_KEY`] of objects is removed, since it is only used by editors.
     ///
     /// # Errors
     ///
@@ -124,6 +162,9 @@ impl MAAValue {
                 Ok(Array(ret))
             }
             Object(mut map) => {
+                // The schema of a config file is only used by editors, not by MaaCore
+                map.remove(SCHEMA_KEY);
+
                 enum Mark {
                     Visiting,
                     Visited,
@@ -223,6 +264,36 @@ impl MAAValue {
         self.as_object().and_then(|map| map.get(key))
     }
 
+    /// Get element at given index of the array
+    ///
+    /// Return `None` if the index is out of bounds.
+    ///
+    /// # Panics
+    ///
+    /// If the value is not an array, the panic will be raised.
+    #[cfg(test)]
+    pub fn get_index(&self, index: usize) -> Option<&Self> {
+        if let Self::Array(array) = self {
+            array.get(index)
+        } else {
+            panic!("value is not an array");
+        }
+    }
+
+    /// Get mutable element at given index of the array, like [`MAAValue::get_index`]
+    ///
+    /// # Panics
+    ///
+    /// If the value is not an array, the panic will be raised.
+    #[cfg(test)]
+    pub fn get_index_mut(&mut self, index: usize) -> Option<&mut Self> {
+        if let Self::Array(array) = self {
+            array.get_mut(index)
+        } else {
+            panic!("value is not an array");
+        }
+    }
+
     /// Get value of given key or return default value
     ///
     /// If the value is an object and the key exists, get the value and try to convert it to type of
@@ -234,6 +305,31 @@ impl MAAValue {
         self.get(key).and_then(T::try_from_value).unwrap_or(default)
     }
 
+    /// Get value of given key and convert it to given type
+    ///
+    /// Return `Ok(None)` if the key is absent or the value is not an object,
+    /// and [`TryFromError::InvalidValue`] with the key if the value can not be converted.
+    #[cfg(test)]
+    pub fn get_typed<T>(&self, key: &str) -> Result<Option<T>, TryFromError>
+    where
+        T: for<'a> TryFrom<&'a MAAValue, Error = TryFromError>,
+    {
+        self.get(key)
+            .map(|value| T::try_from(value).map_err(|e| e.at_key(key.to_owned())))
+            .transpose()
+    }
+
+    /// Get value of given key and convert it to given type, like [`MAAValue::get_typed`]
+    /// but return [`TryFromError::MissingKey`] if the key is absent
+    #[cfg(test)]
+    pub fn get_required<T>(&self, key: &str) -> Result<T, TryFromError>
+    where
+        T: for<'a> TryFrom<&'a MAAValue, Error = TryFromError>,
+    {
+        self.get_typed(key)?
+            .ok_or_else(|| TryFromError::MissingKey(key.to_owned()))
+    }
+
     /// Insert a key-value pair into the object
     ///
     /// If the value is an object, the key-value pair will be inserted into the object.
@@ -251,6 +347,128 @@ impl MAAValue {
         }
     }
 
+    /// Insert a key-value pair into the object only if the key is absent, and return self
+    ///
+    /// This is useful to set fallback values in a chain,
+    /// e.g. `params.with_default("times", 1).with_default("medicine", 0)`.
+    ///
+    /// # Panics
+    ///
+    /// If the value is not an object, the panic will be raised.
+    #[cfg(test)]
+    pub fn with_default(mut self, key: impl Into<String>, default: impl Into<Self>) -> Self {
+        if let Self::Object(map) = &mut self {
+            map.entry(key.into()).or_insert_with(|| default.into());
+        } else {
+            panic!("value is not an object");
+        }
+        self
+    }
+
+    /// Append a value to the end of the array
+    ///
+    /// # Panics
+    ///
+    /// If the value is not an array, the panic will be raised.
+    #[cfg(test)]
+    pub fn push(&mut self, value: impl Into<Self>) {
+        if self.try_push(value).is_err() {
+            panic!("value is not an array");
+        }
+    }
+
+    /// Append a value to the end of the array, or return an error if the value is not an array
+    #[cfg(test)]
+    pub fn try_push(&mut self, value: impl Into<Self>) -> Result<(), TryFromError> {
+        if let Self::Array(array) = self {
+            array.push(value.into());
+            Ok(())
+        } else {
+            Err(TryFromError::TypeMismatch)
+        }
+    }
+
+    /// Remove the last element of the array and return it, or `None` if the array is empty
+    ///
+    /// # Panics
+    ///
+    /// If the value is not an array, the panic will be raised.
+    #[cfg(test)]
+    pub fn pop(&mut self) -> Option<Self> {
+        self.array_mut().pop()
+    }
+
+    /// Append all values of an iterator to the end of the array
+    ///
+    /// # Panics
+    ///
+    /// If the value is not an array, the panic will be raised.
+    #[cfg(test)]
+    pub fn extend_array(&mut self, other: impl IntoIterator<Item = Self>) {
+        self.array_mut().extend(other);
+    }
+
+    /// Insert a value at given index of the array, shifting all elements after it
+    ///
+    /// # Panics
+    ///
+    /// If the value is not an array or `index > len`, the panic will be raised.
+    #[cfg(test)]
+    pub fn insert_at(&mut self, index: usize, value: impl Into<Self>) {
+        let array = self.array_mut();
+        if index > array.len() {
+            panic!(
+                "index {index} is out of bounds of the array with {} elements",
+                array.len()
+            );
+        }
+        array.insert(index, value.into());
+    }
+
+    /// Remove the element at given index of the array and return it,
+    /// shifting all elements after it
+    ///
+    /// # Panics
+    ///
+    /// If the value is not an array or `index >= len`, the panic will be raised.
+    #[cfg(test)]
+    pub fn remove_at(&mut self, index: usize) -> Self {
+        let array = self.array_mut();
+        if index >= array.len() {
+            panic!(
+                "index {index} is out of bounds of the array with {} elements",
+                array.len()
+            );
+        }
+        array.remove(index)
+    }
+
+    /// Swap two elements of the array
+    ///
+    /// # Panics
+    ///
+    /// If the value is not an array or any index is out of bounds, the panic will be raised.
+    #[cfg(test)]
+    pub fn swap_elements(&mut self, i: usize, j: usize) {
+        let array = self.array_mut();
+        if let Some(index) = [i, j].into_iter().find(|&index| index >= array.len()) {
+            panic!(
+                "index {index} is out of bounds of the array with {} elements",
+                array.len()
+            );
+        }
+        array.swap(i, j);
+    }
+
+    #[cfg(test)]
+    fn array_mut(&mut self) -> &mut Vec<Self> {
+        if let Self::Array(array) = self {
+            array
+        } else {
+            panic!("value is not an array");
+        }
+    }
+
     /// Get the value if the value is primate
     fn as_primate(&self) -> Option<&MAAPrimate> {
         match self {
@@ -275,6 +493,201 @@ impl MAAValue {
         self.as_primate().and_then(MAAPrimate::as_str)
     }
 
+    /// Convert an array into a vector of given type, consuming the value
+    ///
+    /// Return [`TryFromError::TypeMismatch`] if the value is not an array,
+    /// or [`TryFromError::InvalidElement`] with the index of the first element
+    /// which can not be converted.
+    #[cfg(test)]
+    pub fn into_vec<T>(self) -> Result<Vec<T>, TryFromError>
+    where
+        T: TryFrom<MAAValue, Error = TryFromError>,
+    {
+        match self {
+            Self::Array(array) => array
+                .into_iter()
+                .enumerate()
+                .map(|(index, value)| T::try_from(value).map_err(|e| e.at_index(index)))
+                .collect(),
+            _ => Err(TryFromError::TypeMismatch),
+        }
+    }
+
+    /// Convert an array into a vector of given type, like [`MAAValue::into_vec`] but borrowing
+    #[cfg(test)]
+    pub fn as_typed_vec<'a, T>(&'a self) -> Result<Vec<T>, TryFromError>
+    where
+        T: TryFrom<&'a MAAValue, Error = TryFromError>,
+    {
+        match self {
+            Self::Array(array) => array
+                .iter()
+                .enumerate()
+                .map(|(index, value)| T::try_from(value).map_err(|e| e.at_index(index)))
+                .collect(),
+            _ => Err(TryFromError::TypeMismatch),
+        }
+    }
+
+    /// Convert an object into a map with values of given type, consuming the value
+    ///
+    /// Return [`TryFromError::TypeMismatch`] if the value is not an object,
+    /// or [`TryFromError::InvalidValue`] with the key of the first value
+    /// which can not be converted.
+    #[cfg(test)]
+    pub fn into_map<V>(self) -> Result<Map<String, V>, TryFromError>
+    where
+        V: TryFrom<MAAValue, Error = TryFromError>,
+    {
+        match self {
+            Self::Object(map) => map
+                .into_iter()
+                .map(|(key, value)| match V::try_from(value) {
+                    Ok(value) => Ok((key, value)),
+                    Err(e) => Err(e.at_key(key)),
+                })
+                .collect(),
+            _ => Err(TryFromError::TypeMismatch),
+        }
+    }
+
+    /// Convert an object into a map with values of given type, like [`MAAValue::into_map`]
+    /// but borrowing
+    #[cfg(test)]
+    pub fn as_typed_map<'a, V>(&'a self) -> Result<Map<String, V>, TryFromError>
+    where
+        V: TryFrom<&'a MAAValue, Error = TryFromError>,
+    {
+        match self {
+            Self::Object(map) => map
+                .iter()
+                .map(|(key, value)| match V::try_from(value) {
+                    Ok(value) => Ok((key.clone(), value)),
+                    Err(e) => Err(e.at_key(key.clone())),
+                })
+                .collect(),
+            _ => Err(TryFromError::TypeMismatch),
+        }
+    }
+
+    /// Return a copy of the value with all null values in arrays and objects removed
+    ///
+    /// This is used to treat null as a signal to delete the key when merging values,
+    /// like JSON Merge Patch.
+    #[cfg(test)]
+    pub fn strip_nulls(&self) -> Self {
+        let mut value = self.clone();
+        value.strip_nulls_mut();
+        value
+    }
+
+    /// Remove all null values in arrays and objects recursively in place
+    pub fn strip_nulls_mut(&mut self) {
+        use MAAValue::*;
+        match self {
+            Array(array) => {
+                array.retain(|v| !matches!(v, Null));
+                array.iter_mut().for_each(Self::strip_nulls_mut);
+            }
+            Object(map) => {
+                map.retain(|_, v| match v {
+                    Null => false,
+                    Optional { value, .. } => !matches!(*value.0, Null),
+                    _ => true,
+                });
+                map.values_mut().for_each(Self::strip_nulls_mut);
+            }
+            Optional { value, .. } => value.0.strip_nulls_mut(),
+            _ => {}
+        }
+    }
+
+    /// Count nodes for which `predicate` returns true recursively, including containers,
+    /// i.e. arrays, objects and optional values, themselves.
+    #[cfg(test)]
+    pub fn count<F>(&self, predicate: F) -> usize
+    where
+        F: Fn(&MAAValue) -> bool,
+    {
+        fn count<F: Fn(&MAAValue) -> bool>(value: &MAAValue, predicate: &F) -> usize {
+            let children = match value {
+                MAAValue::Array(array) => array.iter().map(|v| count(v, predicate)).sum(),
+                MAAValue::Object(map) => map.values().map(|v| count(v, predicate)).sum(),
+                MAAValue::Optional { value, .. } => count(&value.0, predicate),
+                _ => 0,
+            };
+            usize::from(predicate(value)) + children
+        }
+
+        count(self, &predicate)
+    }
+
+    /// Count input values which are not resolved yet, see [`MAAValue::init`]
+    #[cfg(test)]
+    pub fn count_unresolved(&self) -> usize {
+        self.count(|value| matches!(value, MAAValue::Input(_)))
+    }
+
+    /// Replace nodes for which `predicate` returns true by `replacement(node)` recursively,
+    /// including containers, i.e. arrays, objects and optional values, themselves.
+    ///
+    /// Nodes are replaced from the root to the leaves, so children of a replaced node are the
+    /// children of the replacement. Replacements of a node are replaced again if they still match,
+    /// at most [`DEFAULT_MAX_REPLACE_DEPTH`] times along each path from the root, see
+    /// [`MAAValue::replace_all_with_depth`].
+    #[cfg(test)]
+    pub fn replace_all<F, R>(&self, predicate: F, replacement: R) -> Self
+    where
+        F: Fn(&MAAValue) -> bool,
+        R: Fn(&MAAValue) -> MAAValue,
+    {
+        self.replace_all_with_depth(predicate, replacement, DEFAULT_MAX_REPLACE_DEPTH)
+    }
+
+    /// Same as [`MAAValue::replace_all`], but at most `max_depth` replacements are made along each
+    /// path from the root, which prevents infinite loops when replacements always match.
+    #[cfg(test)]
+    pub fn replace_all_with_depth<F, R>(
+        &self,
+        predicate: F,
+        replacement: R,
+        max_depth: usize,
+    ) -> Self
+    where
+        F: Fn(&MAAValue) -> bool,
+        R: Fn(&MAAValue) -> MAAValue,
+    {
+        fn replace<F, R>(value: &MAAValue, predicate: &F, replacement: &R, depth: usize) -> MAAValue
+        where
+            F: Fn(&MAAValue) -> bool,
+            R: Fn(&MAAValue) -> MAAValue,
+        {
+            use std::borrow::Cow;
+
+            let mut depth = depth;
+            let mut value = Cow::Borrowed(value);
+            while depth > 0 && predicate(&value) {
+                value = Cow::Owned(replacement(&value));
+                depth -= 1;
+            }
+
+            let replace = |v: &MAAValue| replace(v, predicate, replacement, depth);
+            match value.as_ref() {
+                MAAValue::Array(array) => MAAValue::Array(array.iter().map(replace).collect()),
+                MAAValue::Object(map) => {
+                    MAAValue::Object(map.iter().map(|(k, v)| (k.clone(), replace(v))).collect())
+                }
+                MAAValue::Optional { conditions, value } => MAAValue::Optional {
+                    conditions: conditions.clone(),
+                    value: replace(&value.0).into(),
+                },
+                _ => value.into_owned(),
+            }
+        }
+
+        replace(self, &predicate, &replacement, max_depth)
+    }
+
     pub fn merge_mut(&mut self, other: &Self) {
         match (self, other) {
             (Self::Object(self_map), Self::Object(other_map)) => {
@@ -289,6 +702,63 @@ impl MAAValue {
             (s, o) => *s = o.clone(),
         }
     }
+
+    /// Convert the value to a JSON value.
+    ///
+    /// # Errors
+    ///
+    /// The value must be initialized, i.e. it contains no input or optional value,
+    /// see [`MAAValue::init`].
+    pub fn to_json(&self) -> serde_json::Result<serde_json::Value> {
+        serde_json::to_value(self)
+    }
+
+    /// Deserialize the value into a typed struct, e.g. the params of a specific task.
+    ///
+    /// The value is converted through [`MAAValue::to_json`] without formatting it as a string.
+    ///
+    /// # Errors
+    ///
+    /// The value is not initialized, or it does not match the type `T`.
+    pub fn into_typed<T: serde::de::DeserializeOwned>(self) -> serde_json::Result<T> {
+        serde_json::from_value(self.to_json()?)
+    }
+
+    /// Convert a JSON value to a value, the inverse of [`MAAValue::to_json`].
+    ///
+    /// Unlike deserializing, objects are always converted to objects, even if they look like
+    /// user inputs (e.g. an empty object). Integers out of the range of `i32` are converted
+    /// to floats.
+    pub fn from_json(json: serde_json::Value) -> Self {
+        use serde_json::Value as JsonValue;
+        match json {
+            JsonValue::Null => Self::Null,
+            JsonValue::Bool(b) => b.into(),
+            JsonValue::Number(n) => match n.as_i64().and_then(|n| i32::try_from(n).ok()) {
+                Some(n) => n.into(),
+                None => (n.as_f64().unwrap_or_default() as f32).into(),
+            },
+            JsonValue::String(s) => s.into(),
+            JsonValue::Array(array) => {
+                Self::Array(array.into_iter().map(Self::from_json).collect())
+            }
+            JsonValue::Object(map) => Self::Object(
+                map.into_iter()
+                    .map(|(key, value)| (key, Self::from_json(value)))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Create a value from a typed struct, the inverse of [`MAAValue::into_typed`].
+    ///
+    /// # Errors
+    ///
+    /// The value can not be serialized to JSON, e.g. a map with non-string keys.
+    #[cfg(test)]
+    pub fn from_typed<T: Serialize>(value: T) -> serde_json::Result<Self> {
+        serde_json::to_value(value).map(Self::from_json)
+    }
 }
 
 #[macro_export]
@@ -331,6 +801,127 @@ impl<const N: usize, T: Into<MAAValue>> From<[T; N]> for MAAValue {
     }
 }
 
+/// Error when converting a value to another type
+///
+/// The error is serialized as a tagged object for structured logging,
+/// e.g. `{"kind": "type_mismatch"}` or `{"kind": "input_error", "message": "..."}`.
+/// The structure is part of the log format, so do not change it without care.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TryFromError {
+    /// The value is not of the expected type
+    TypeMismatch,
+    /// Failed to get the value of an input from user
+    InputError { message: String },
+    /// Failed to convert an element of an array, see [`MAAValue::into_vec`]
+    InvalidElement {
+        index: usize,
+        error: Box<TryFromError>,
+    },
+    /// Failed to convert a value of an object, see [`MAAValue::into_map`]
+    InvalidValue {
+        key: String,
+        error: Box<TryFromError>,
+    },
+    /// A required key is absent from an object, see [`MAAValue::get_required`]
+    ///
+    /// Functions checking required keys of objects should return this error,
+    /// so that callers can tell missing keys from invalid values by matching the variant.
+    #[serde(serialize_with = "serialize_missing_key")]
+    MissingKey(String),
+}
+
+/// Serialize the key of [`TryFromError::MissingKey`] as `{"key": "..."}`,
+/// a bare string can not be tagged by `kind`.
+fn serialize_missing_key<S: serde::Serializer>(
+    key: &str,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    #[derive(Serialize)]
+    struct MissingKey<'a> {
+        key: &'a str,
+    }
+
+    MissingKey { key }.serialize(serializer)
+}
+
+impl TryFromError {
+    /// Fields of the error for loggers without structured logging support
+    #[cfg(test)]
+    pub fn to_log_fields(&self) -> Vec<(&'static str, String)> {
+        match self {
+            TryFromError::TypeMismatch => vec![("kind", "type_mismatch".to_owned())],
+            TryFromError::InputError { message } => vec![
+                ("kind", "input_error".to_owned()),
+                ("message", message.clone()),
+            ],
+            TryFromError::InvalidElement { index, error } => vec![
+                ("kind", "invalid_element".to_owned()),
+                ("index", index.to_string()),
+                ("error", error.to_string()),
+            ],
+            TryFromError::InvalidValue { key, error } => vec![
+                ("kind", "invalid_value".to_owned()),
+                ("key", key.clone()),
+                ("error", error.to_string()),
+            ],
+            TryFromError::MissingKey(key) => {
+                vec![("kind", "missing_key".to_owned()), ("key", key.clone())]
+            }
+        }
+    }
+
+    #[cfg(test)]
+    fn at_index(self, index: usize) -> Self {
+        TryFromError::InvalidElement {
+            index,
+            error: Box::new(self),
+        }
+    }
+
+    #[cfg(test)]
+    fn at_key(self, key: String) -> Self {
+        TryFromError::InvalidValue {
+            key,
+            error: Box::new(self),
+        }
+    }
+}
+
+impl std::fmt::Display for TryFromError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TryFromError::TypeMismatch => write!(f, "Type mismatch"),
+            TryFromError::InputError { message } => write!(f, "Input error: {message}"),
+            TryFromError::InvalidElement { index, error } => {
+                write!(f, "Invalid element at index {index}: {error}")
+            }
+            TryFromError::InvalidValue { key, error } => {
+                write!(f, "Invalid value of key `{key}`: {error}")
+            }
+            TryFromError::MissingKey(key) => write!(f, "required key '{key}' is missing"),
+        }
+    }
+}
+
+impl std::error::Error for TryFromError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TryFromError::InvalidElement { error, .. }
+            | TryFromError::InvalidValue { error, .. } => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for TryFromError {
+    fn from(err: std::io::Error) -> Self {
+        TryFromError::InputError {
+            message: err.to_string(),
+        }
+    }
+}
+
 /// Try to convert the value to given type
 ///
 /// If the value is not convertible to the type, None will be returned.
@@ -372,6 +963,57 @@ impl<'a> TryFromMAAValue<'a> for &str {
     }
 }
 
+macro_rules! impl_try_from {
+    ($($t:ty => $as:ident),* $(,)?) => {
+        $(
+            impl TryFrom<&MAAValue> for $t {
+                type Error = TryFromError;
+
+                fn try_from(value: &MAAValue) -> Result<Self, Self::Error> {
+                    value.$as().ok_or(TryFromError::TypeMismatch)
+                }
+            }
+
+            impl TryFrom<MAAValue> for $t {
+                type Error = TryFromError;
+
+                fn try_from(value: MAAValue) -> Result<Self, Self::Error> {
+                    Self::try_from(&value)
+                }
+            }
+        )*
+    };
+}
+
+impl_try_from!(bool => as_bool, i32 => as_int, f32 => as_float);
+
+impl<'a> TryFrom<&'a MAAValue> for &'a str {
+    type Error = TryFromError;
+
+    fn try_from(value: &'a MAAValue) -> Result<Self, Self::Error> {
+        value.as_str().ok_or(TryFromError::TypeMismatch)
+    }
+}
+
+impl TryFrom<&MAAValue> for String {
+    type Error = TryFromError;
+
+    fn try_from(value: &MAAValue) -> Result<Self, Self::Error> {
+        <&str>::try_from(value).map(str::to_owned)
+    }
+}
+
+impl TryFrom<MAAValue> for String {
+    type Error = TryFromError;
+
+    fn try_from(value: MAAValue) -> Result<Self, Self::Error> {
+        match value {
+            MAAValue::Primate(MAAPrimate::String(s)) => Ok(s),
+            _ => Err(TryFromError::TypeMismatch),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::assert_matches;
@@ -658,6 +1300,98 @@ mod tests {
             "optional3" if "optional1" == true => input.clone(),
         );
         assert_eq!(value.init().unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        // the schema is not passed to MaaCore
+        let value = object!(
+            "$schema" => "https://example.com/schema.json",
+            "stage" => "1-7",
+            "nested" => object!("$schema" => "https://example.com/schema.json"),
+        );
+        assert_eq!(
+            value.init().unwrap(),
+            object!("stage" => "1-7", "nested" => object!())
+        );
+    }
+
+    #[test]
+    fn count() {
+        let input = BoolInput::new(Some(true), None);
+        let value = object!(
+            "input" => input.clone(),
+            "array" => [1, 2],
+            "tasks" => MAAValue::Array(vec![
+                object!("type" => "Fight", "stage" => "1-7"),
+                object!("type" => "Fight", "stage" => input.clone()),
+                object!("type" => "Mall"),
+            ]),
+            "optional" if "input" == true => input.clone(),
+        );
+
+        // 1 root, 2 arrays, 3 objects, 1 optional, 3 inputs and 6 primates
+        assert_eq!(value.count(|_| true), 16);
+        assert_eq!(value.count(|v| v.as_object().is_some()), 4);
+        assert_eq!(value.count(|v| v.get("type") == Some(&"Fight".into())), 2);
+        assert_eq!(value.count(|_| false), 0);
+        assert_eq!(MAAValue::from(1).count(|_| true), 1);
+
+        assert_eq!(value.count_unresolved(), 3);
+        assert_eq!(value.init().unwrap().count_unresolved(), 0);
+    }
+
+    #[test]
+    fn replace_all() {
+        let value = object!(
+            "single" => [1],
+            "nested" => MAAValue::Array(vec![MAAValue::Array(vec![[2].into()])]),
+            "pair" => [3, 4],
+            "optional" if "single" == 1 => MAAValue::Array(vec![object!("a" => [5])]),
+        );
+        let unwrap_single = |v: &MAAValue| match v {
+            MAAValue::Array(array) => array[0].clone(),
+            _ => unreachable!(),
+        };
+        let is_single = |v: &MAAValue| matches!(v, MAAValue::Array(array) if array.len() == 1);
+
+        assert_eq!(
+            value.replace_all(is_single, unwrap_single),
+            object!(
+                "single" => 1,
+                "nested" => 2,
+                "pair" => [3, 4],
+                "optional" if "single" == 1 => object!("a" => 5),
+            )
+        );
+
+        // the root is replaced too
+        assert_eq!(
+            MAAValue::from([1]).replace_all(is_single, unwrap_single),
+            MAAValue::from(1)
+        );
+        // nothing matches
+        assert_eq!(value.replace_all(|_| false, |_| unreachable!()), value);
+
+        // replacements always matching are stopped at the maximum depth
+        let wrap = |v: &MAAValue| MAAValue::Array(vec![v.clone()]);
+        let is_int = |v: &MAAValue| v.as_int().is_some();
+        assert_eq!(
+            MAAValue::from(1).replace_all_with_depth(|_| true, wrap, 2),
+            MAAValue::Array(vec![MAAValue::from([1])])
+        );
+        // the replaced int is matched again as the child of the replacement
+        assert_eq!(
+            MAAValue::from(1).replace_all_with_depth(is_int, wrap, 3),
+            MAAValue::Array(vec![MAAValue::Array(vec![MAAValue::from([1])])])
+        );
+        assert_eq!(
+            MAAValue::from(1)
+                .replace_all(|_| true, wrap)
+                .count(|v| matches!(v, MAAValue::Array(_))),
+            DEFAULT_MAX_REPLACE_DEPTH
+        );
+        assert_eq!(
+            value.replace_all_with_depth(is_single, unwrap_single, 0),
+            value
+        );
     }
 
     #[test]
@@ -673,6 +1407,30 @@ mod tests {
         assert_eq!(value.get_or("float", 2.0), 2.0);
     }
 
+    #[test]
+    fn get_index() {
+        let mut value = MAAValue::from([1, 2]);
+
+        assert_eq!(value.get_index(1), Some(&MAAValue::from(2)));
+        assert_eq!(value.get_index(2), None);
+
+        *value.get_index_mut(0).unwrap() = "a".into();
+        assert_eq!(value, MAAValue::Array(vec!["a".into(), 2.into()]));
+        assert_eq!(value.get_index_mut(2), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "value is not an array")]
+    fn get_index_panics() {
+        MAAValue::new().get_index(0);
+    }
+
+    #[test]
+    #[should_panic(expected = "value is not an array")]
+    fn get_index_mut_panics() {
+        MAAValue::from(1).get_index_mut(0);
+    }
+
     #[test]
     fn insert() {
         let mut value = MAAValue::new();
@@ -688,6 +1446,230 @@ mod tests {
         value.insert("int", 1);
     }
 
+    #[test]
+    fn push_and_pop() {
+        let mut value = MAAValue::Array(vec![]);
+        assert_eq!(value.pop(), None);
+
+        value.push(1);
+        value.try_push("a").unwrap();
+        value.extend_array([true.into(), 2.0.into()]);
+        assert_eq!(
+            value,
+            MAAValue::Array(vec![1.into(), "a".into(), true.into(), 2.0.into()])
+        );
+
+        assert_eq!(value.pop(), Some(MAAValue::from(2.0)));
+        assert_eq!(value.pop(), Some(MAAValue::from(true)));
+        assert_eq!(value, MAAValue::Array(vec![1.into(), "a".into()]));
+
+        let mut value = MAAValue::from(1);
+        assert_eq!(value.try_push(2), Err(TryFromError::TypeMismatch));
+        assert_eq!(value, MAAValue::from(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "value is not an array")]
+    fn push_panics() {
+        MAAValue::new().push(1);
+    }
+
+    #[test]
+    #[should_panic(expected = "value is not an array")]
+    fn pop_panics() {
+        MAAValue::from(1).pop();
+    }
+
+    #[test]
+    #[should_panic(expected = "value is not an array")]
+    fn extend_array_panics() {
+        MAAValue::new().extend_array([MAAValue::from(1)]);
+    }
+
+    #[test]
+    fn insert_and_remove_at() {
+        let mut value = MAAValue::from([1, 3]);
+        value.insert_at(1, 2);
+        value.insert_at(0, 0);
+        value.insert_at(4, 4);
+        assert_eq!(value, MAAValue::from([0, 1, 2, 3, 4]));
+
+        assert_eq!(value.remove_at(4), MAAValue::from(4));
+        assert_eq!(value.remove_at(0), MAAValue::from(0));
+        assert_eq!(value, MAAValue::from([1, 2, 3]));
+
+        value.swap_elements(0, 2);
+        value.swap_elements(1, 1);
+        assert_eq!(value, MAAValue::from([3, 2, 1]));
+    }
+
+    #[test]
+    #[should_panic(expected = "index 3 is out of bounds of the array with 2 elements")]
+    fn insert_at_out_of_bounds() {
+        MAAValue::from([1, 2]).insert_at(3, 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "index 2 is out of bounds of the array with 2 elements")]
+    fn remove_at_out_of_bounds() {
+        MAAValue::from([1, 2]).remove_at(2);
+    }
+
+    #[test]
+    #[should_panic(expected = "index 2 is out of bounds of the array with 2 elements")]
+    fn swap_elements_out_of_bounds() {
+        MAAValue::from([1, 2]).swap_elements(0, 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "value is not an array")]
+    fn insert_at_panics() {
+        MAAValue::new().insert_at(0, 1);
+    }
+
+    #[test]
+    fn with_default() {
+        let value = object!("int" => 1)
+            .with_default("int", 2)
+            .with_default("str", "string")
+            .with_default("str", "other");
+        assert_eq!(value, object!("int" => 1, "str" => "string"));
+
+        // a null value is present, so it is kept
+        let value = object!("null" => MAAValue::Null).with_default("null", 1);
+        assert_eq!(value, object!("null" => MAAValue::Null));
+    }
+
+    #[test]
+    #[should_panic(expected = "value is not an object")]
+    fn with_default_panics() {
+        MAAValue::from(1).with_default("int", 1);
+    }
+
+    #[test]
+    fn into_typed() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct FightParams {
+            stage: String,
+            medicine: i32,
+            #[serde(default)]
+            report_to_penguin: bool,
+            series: Option<i32>,
+        }
+
+        let value = object!("stage" => "1-7", "medicine" => 2, "unknown" => [1, 2]);
+        assert_eq!(
+            value.to_json().unwrap(),
+            serde_json::json!({ "stage": "1-7", "medicine": 2, "unknown": [1, 2] })
+        );
+        assert_eq!(
+            value.into_typed::<FightParams>().unwrap(),
+            FightParams {
+                stage: "1-7".to_owned(),
+                medicine: 2,
+                report_to_penguin: false,
+                series: None,
+            }
+        );
+
+        // type mismatch
+        assert!(object!("stage" => 1, "medicine" => 2)
+            .into_typed::<FightParams>()
+            .is_err());
+        // missing field
+        assert!(object!("stage" => "1-7")
+            .into_typed::<FightParams>()
+            .is_err());
+        // uninitialized value
+        let value = object!("stage" => "1-7", "medicine" => Input::new(Some(2), None));
+        assert!(value.to_json().is_err());
+        assert!(value.into_typed::<FightParams>().is_err());
+    }
+
+    #[test]
+    fn from_typed() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+        struct Drops {
+            item: String,
+            times: i32,
+        }
+
+        #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+        struct FightParams {
+            stage: String,
+            medicine: i32,
+            report_to_penguin: bool,
+            threshold: f32,
+            series: Option<i32>,
+            drops: Vec<Drops>,
+            extra: Map<String, i32>,
+        }
+
+        #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+        struct Empty {}
+
+        #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+        #[serde(rename_all = "snake_case")]
+        enum Mode {
+            Normal,
+            Custom { theme: String },
+        }
+
+        fn round_trip<T>(value: T)
+        where
+            T: Serialize + serde::de::DeserializeOwned + PartialEq + std::fmt::Debug + Clone,
+        {
+            let converted = MAAValue::from_typed(value.clone()).and_then(MAAValue::into_typed::<T>);
+            assert_eq!(converted.unwrap(), value);
+        }
+
+        let fight = FightParams {
+            stage: "1-7".to_owned(),
+            medicine: 2,
+            report_to_penguin: true,
+            threshold: 0.3,
+            series: None,
+            drops: vec![Drops {
+                item: "30012".to_owned(),
+                times: 5,
+            }],
+            extra: Map::new(),
+        };
+        assert_eq!(
+            MAAValue::from_typed(&fight).unwrap(),
+            object!(
+                "stage" => "1-7",
+                "medicine" => 2,
+                "report_to_penguin" => true,
+                "threshold" => 0.3,
+                "series" => MAAValue::Null,
+                "drops" => [object!("item" => "30012", "times" => 5)],
+                "extra" => MAAValue::Object(Map::new()),
+            )
+        );
+        round_trip(fight);
+        round_trip(Empty {});
+        round_trip(Mode::Normal);
+        round_trip(Mode::Custom {
+            theme: "Sami".to_owned(),
+        });
+        round_trip(vec![1, 2, 3]);
+
+        // objects looking like inputs are kept as objects
+        assert_eq!(
+            MAAValue::from_json(serde_json::json!({ "default": 1 })),
+            object!("default" => 1)
+        );
+        assert_eq!(
+            MAAValue::from_json(serde_json::json!(i64::MAX)),
+            MAAValue::from(i64::MAX as f32)
+        );
+
+        // maps with non-string keys can not be serialized to JSON
+        let map = std::collections::HashMap::from([((1, 2), 3)]);
+        assert!(MAAValue::from_typed(map).is_err());
+    }
+
     #[test]
     fn try_from() {
         // Bool
@@ -716,6 +1698,229 @@ mod tests {
         assert_eq!(bool::try_from_value(&"string".into()), None);
     }
 
+    #[test]
+    fn try_from_error() {
+        assert_eq!(
+            serde_json::to_string(&TryFromError::TypeMismatch).unwrap(),
+            r#"{"kind":"type_mismatch"}"#
+        );
+        let err = TryFromError::from(std::io::Error::other("no input"));
+        assert_eq!(
+            serde_json::to_string(&err).unwrap(),
+            r#"{"kind":"input_error","message":"no input"}"#
+        );
+        assert_eq!(err.to_string(), "Input error: no input");
+
+        assert_eq!(
+            TryFromError::TypeMismatch.to_log_fields(),
+            [("kind", "type_mismatch".to_owned())]
+        );
+        assert_eq!(
+            err.to_log_fields(),
+            [
+                ("kind", "input_error".to_owned()),
+                ("message", "no input".to_owned())
+            ]
+        );
+    }
+
+    #[test]
+    fn try_from_owned() {
+        assert_eq!(bool::try_from(MAAValue::from(true)), Ok(true));
+        assert_eq!(i32::try_from(&MAAValue::from(1)), Ok(1));
+        assert_eq!(f32::try_from(MAAValue::from(1.0)), Ok(1.0));
+        assert_eq!(<&str>::try_from(&MAAValue::from("a")), Ok("a"));
+        assert_eq!(String::try_from(MAAValue::from("a")), Ok("a".to_owned()));
+        assert_eq!(String::try_from(&MAAValue::from("a")), Ok("a".to_owned()));
+        assert_eq!(
+            i32::try_from(MAAValue::from(1.0)),
+            Err(TryFromError::TypeMismatch)
+        );
+        assert_eq!(
+            String::try_from(MAAValue::from(1)),
+            Err(TryFromError::TypeMismatch)
+        );
+    }
+
+    #[test]
+    fn get_typed() {
+        let value = object!("int" => 1, "str" => "a");
+
+        assert_eq!(value.get_typed::<i32>("int"), Ok(Some(1)));
+        assert_eq!(value.get_typed::<String>("str"), Ok(Some("a".to_owned())));
+        assert_eq!(value.get_typed::<i32>("none"), Ok(None));
+        assert_eq!(MAAValue::from(1).get_typed::<i32>("int"), Ok(None));
+        assert_eq!(
+            value.get_typed::<bool>("int"),
+            Err(TryFromError::InvalidValue {
+                key: "int".to_owned(),
+                error: Box::new(TryFromError::TypeMismatch),
+            })
+        );
+
+        assert_eq!(value.get_required::<i32>("int"), Ok(1));
+        assert!(value.get_required::<bool>("str").is_err());
+        let err = value.get_required::<i32>("none").unwrap_err();
+        assert_eq!(err, TryFromError::MissingKey("none".to_owned()));
+        assert_eq!(err.to_string(), "required key 'none' is missing");
+        assert_eq!(
+            serde_json::to_string(&err).unwrap(),
+            r#"{"kind":"missing_key","key":"none"}"#
+        );
+        assert_eq!(
+            err.to_log_fields(),
+            [
+                ("kind", "missing_key".to_owned()),
+                ("key", "none".to_owned())
+            ]
+        );
+    }
+
+    #[test]
+    fn into_map() {
+        let value = object!("a" => 1, "b" => 2);
+        let expected = Map::from([("a".to_owned(), 1), ("b".to_owned(), 2)]);
+        assert_eq!(value.as_typed_map::<i32>(), Ok(expected.clone()));
+        assert_eq!(value.into_map::<i32>(), Ok(expected));
+
+        let value = object!("a" => "x");
+        assert_eq!(
+            value.as_typed_map::<&str>(),
+            Ok(Map::from([("a".to_owned(), "x")]))
+        );
+        assert_eq!(
+            value.into_map::<String>(),
+            Ok(Map::from([("a".to_owned(), "x".to_owned())]))
+        );
+
+        assert_eq!(MAAValue::new().into_map::<bool>(), Ok(Map::new()));
+
+        let value = object!("a" => 1, "b" => true);
+        let err = value.as_typed_map::<i32>().unwrap_err();
+        assert_eq!(
+            err,
+            TryFromError::InvalidValue {
+                key: "b".to_owned(),
+                error: Box::new(TryFromError::TypeMismatch),
+            }
+        );
+        assert_eq!(err.to_string(), "Invalid value of key `b`: Type mismatch");
+        assert_eq!(
+            serde_json::to_string(&err).unwrap(),
+            r#"{"kind":"invalid_value","key":"b","error":{"kind":"type_mismatch"}}"#
+        );
+        assert_eq!(
+            err.to_log_fields(),
+            [
+                ("kind", "invalid_value".to_owned()),
+                ("key", "b".to_owned()),
+                ("error", "Type mismatch".to_owned()),
+            ]
+        );
+        assert_eq!(value.into_map::<i32>(), Err(err));
+
+        assert_eq!(
+            MAAValue::from([1]).into_map::<i32>(),
+            Err(TryFromError::TypeMismatch)
+        );
+        assert_eq!(
+            MAAValue::from([1]).as_typed_map::<i32>(),
+            Err(TryFromError::TypeMismatch)
+        );
+    }
+
+    #[test]
+    fn into_vec() {
+        let value = MAAValue::from([1, 2, 3]);
+        assert_eq!(value.as_typed_vec::<i32>(), Ok(vec![1, 2, 3]));
+        assert_eq!(value.into_vec::<i32>(), Ok(vec![1, 2, 3]));
+
+        let value = MAAValue::from(["a", "b"]);
+        assert_eq!(value.as_typed_vec::<&str>(), Ok(vec!["a", "b"]));
+        assert_eq!(
+            value.into_vec::<String>(),
+            Ok(vec!["a".to_owned(), "b".to_owned()])
+        );
+
+        assert_eq!(MAAValue::Array(vec![]).into_vec::<bool>(), Ok(vec![]));
+
+        let value = MAAValue::Array(vec![1.into(), "2".into(), 3.into()]);
+        let err = value.as_typed_vec::<i32>().unwrap_err();
+        assert_eq!(
+            err,
+            TryFromError::InvalidElement {
+                index: 1,
+                error: Box::new(TryFromError::TypeMismatch),
+            }
+        );
+        assert_eq!(err.to_string(), "Invalid element at index 1: Type mismatch");
+        assert_eq!(
+            serde_json::to_string(&err).unwrap(),
+            r#"{"kind":"invalid_element","index":1,"error":{"kind":"type_mismatch"}}"#
+        );
+        assert_eq!(
+            err.to_log_fields(),
+            [
+                ("kind", "invalid_element".to_owned()),
+                ("index", "1".to_owned()),
+                ("error", "Type mismatch".to_owned()),
+            ]
+        );
+        assert_eq!(value.into_vec::<i32>(), Err(err));
+
+        assert_eq!(
+            MAAValue::from(1).into_vec::<i32>(),
+            Err(TryFromError::TypeMismatch)
+        );
+        assert_eq!(
+            MAAValue::from(1).as_typed_vec::<i32>(),
+            Err(TryFromError::TypeMismatch)
+        );
+    }
+
+    #[test]
+    fn null() {
+        assert_eq!(
+            serde_json::from_str::<MAAValue>(r#"{"a": null, "b": 1}"#).unwrap(),
+            object!("a" => MAAValue::Null, "b" => 1),
+        );
+        assert_eq!(serde_json::to_string(&MAAValue::Null).unwrap(), "null");
+        assert_eq!(MAAValue::Null.init().unwrap(), MAAValue::Null);
+    }
+
+    #[test]
+    fn strip_nulls() {
+        use MAAValue::Null;
+
+        assert_eq!(
+            MAAValue::from([Null, 1.into(), Null]).strip_nulls(),
+            MAAValue::from([1]),
+        );
+        assert_eq!(
+            object!("a" => Null, "b" => 1).strip_nulls(),
+            object!("b" => 1),
+        );
+
+        let mut value = object!(
+            "array" => [MAAValue::from([Null]), object!("a" => Null)],
+            "object" => object!("a" => Null, "b" => object!("c" => Null)),
+            "optional" if "b" == true => Null,
+            "optional_object" if "b" == true => object!("a" => Null, "b" => 1),
+            "null" => Null,
+        );
+        value.strip_nulls_mut();
+        assert_eq!(
+            value,
+            object!(
+                "array" => [MAAValue::from([] as [i32; 0]), object!()],
+                "object" => object!("b" => object!()),
+                "optional_object" if "b" == true => object!("b" => 1),
+            )
+        );
+
+        assert_eq!(Null.strip_nulls(), Null);
+    }
+
     #[test]
     fn merge() {
         let value = object!(
@@ -0,0 +1,202 @@
+use super::{MAAValue, Map};
+
+impl MAAValue {
+    /// Apply a JSON Merge Patch ([RFC 7396](https://www.rfc-editor.org/rfc/rfc7396)) in place.
+    ///
+    /// Unlike [`MAAValue::merge_mut`], a null value in the patch removes the key from the
+    /// object instead of being merged. Objects in the patch are applied recursively, and any
+    /// other value, including arrays, replaces the target as a whole. If the patch is an object
+    /// but the target is not, the target is replaced by an empty object before patching.
+    pub fn apply_json_merge_patch(&mut self, patch: &Self) {
+        let Self::Object(patch_map) = patch else {
+            *self = patch.clone();
+            return;
+        };
+
+        if !matches!(self, Self::Object(_)) {
+            *self = Self::new();
+        }
+        let Self::Object(map) = self else {
+            unreachable!("target is an object")
+        };
+
+        for (key, value) in patch_map {
+            if let Self::Null = value {
+                map.remove(key);
+            } else {
+                map.entry(key.clone())
+                    .or_insert(Self::Null)
+                    .apply_json_merge_patch(value);
+            }
+        }
+    }
+
+    /// Compute the JSON Merge Patch which transforms `original` into `modified`.
+    ///
+    /// Unchanged keys are omitted, changed values are included as a whole unless both are
+    /// objects, and keys absent in `modified` are set to null. If `modified` is not an object,
+    /// the patch is `modified` itself. Null values in objects of `modified` can not be expressed
+    /// by a merge patch, since they mean removal, so they are lost when the patch is applied.
+    pub fn compute_json_merge_patch(original: &Self, modified: &Self) -> Self {
+        let (Self::Object(original), Self::Object(modified)) = (original, modified) else {
+            return modified.clone();
+        };
+
+        let mut patch = Map::new();
+        for key in original.keys() {
+            if !modified.contains_key(key) {
+                patch.insert(key.clone(), Self::Null);
+            }
+        }
+        for (key, value) in modified {
+            match original.get(key) {
+                Some(original) if original == value => {}
+                Some(original @ Self::Object(_)) if matches!(value, Self::Object(_)) => {
+                    patch.insert(key.clone(), Self::compute_json_merge_patch(original, value));
+                }
+                _ => {
+                    patch.insert(key.clone(), value.clone());
+                }
+            }
+        }
+        Self::Object(patch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::object;
+
+    fn json(s: &str) -> MAAValue {
+        serde_json::from_str(s).unwrap()
+    }
+
+    fn patched(target: &str, patch: &str) -> MAAValue {
+        let mut target = json(target);
+        target.apply_json_merge_patch(&json(patch));
+        target
+    }
+
+    #[test]
+    fn rfc_example() {
+        let target = r#"{
+            "title": "Goodbye!",
+            "author": { "givenName": "John", "familyName": "Doe" },
+            "tags": ["example", "sample"],
+            "content": "This will be unchanged"
+        }"#;
+        let patch = r#"{
+            "title": "Hello!",
+            "phoneNumber": "+01-123-456-7890",
+            "author": { "familyName": null },
+            "tags": ["example"]
+        }"#;
+        let expected = r#"{
+            "title": "Hello!",
+            "author": { "givenName": "John" },
+            "tags": ["example"],
+            "content": "This will be unchanged",
+            "phoneNumber": "+01-123-456-7890"
+        }"#;
+        assert_eq!(patched(target, patch), json(expected));
+    }
+
+    #[test]
+    fn rfc_test_cases() {
+        let cases = [
+            (r#"{"a":"b"}"#, r#"{"a":"c"}"#, r#"{"a":"c"}"#),
+            (r#"{"a":"b"}"#, r#"{"b":"c"}"#, r#"{"a":"b","b":"c"}"#),
+            (r#"{"a":"b","b":"c"}"#, r#"{"a":null}"#, r#"{"b":"c"}"#),
+            (r#"{"a":["b"]}"#, r#"{"a":"c"}"#, r#"{"a":"c"}"#),
+            (r#"{"a":"c"}"#, r#"{"a":["b"]}"#, r#"{"a":["b"]}"#),
+            (
+                r#"{"a":{"b":"c"}}"#,
+                r#"{"a":{"b":"d","c":null}}"#,
+                r#"{"a":{"b":"d"}}"#,
+            ),
+            (r#"{"a":[{"b":"c"}]}"#, r#"{"a":[1]}"#, r#"{"a":[1]}"#),
+            (r#"["a","b"]"#, r#"["c","d"]"#, r#"["c","d"]"#),
+            (r#"{"a":"b"}"#, r#"["c"]"#, r#"["c"]"#),
+            (r#"{"a":"foo"}"#, r#"null"#, r#"null"#),
+            (r#"{"a":"foo"}"#, r#""bar""#, r#""bar""#),
+            (r#"{"e":null}"#, r#"{"a":1}"#, r#"{"e":null,"a":1}"#),
+            (r#"[1,2]"#, r#"{"a":"b","c":null}"#, r#"{"a":"b"}"#),
+        ];
+        for (target, patch, expected) in cases {
+            assert_eq!(
+                patched(target, patch),
+                json(expected),
+                "target: {target}, patch: {patch}"
+            );
+        }
+
+        // an empty object is parsed as an input, so construct expected values directly
+        assert_eq!(patched(r#"{"a":"b"}"#, r#"{"a":null}"#), object!());
+        let mut value = object!();
+        value.apply_json_merge_patch(&json(r#"{"a":{"bb":{"ccc":null}}}"#));
+        assert_eq!(value, object!("a" => object!("bb" => object!())));
+    }
+
+    #[test]
+    fn compute_patch() {
+        let original = json(
+            r#"{"title":"Goodbye!","author":{"givenName":"John","familyName":"Doe"},"tags":["a","b"],"same":{"x":1}}"#,
+        );
+        let modified = json(
+            r#"{"title":"Hello!","author":{"givenName":"John"},"tags":["a"],"same":{"x":1},"new":1}"#,
+        );
+        let patch = MAAValue::compute_json_merge_patch(&original, &modified);
+        assert_eq!(
+            patch,
+            json(r#"{"title":"Hello!","author":{"familyName":null},"tags":["a"],"new":1}"#)
+        );
+
+        // identical values produce an empty patch
+        assert_eq!(
+            MAAValue::compute_json_merge_patch(&original, &original),
+            object!()
+        );
+
+        // non-object values are replaced as a whole
+        assert_eq!(
+            MAAValue::compute_json_merge_patch(&original, &json("[1]")),
+            json("[1]")
+        );
+        assert_eq!(
+            MAAValue::compute_json_merge_patch(&json("[1]"), &modified),
+            modified
+        );
+    }
+
+    #[test]
+    fn compute_patch_roundtrip() {
+        let values = [
+            r#"{"a":"b","c":{"d":1,"e":[1,2]},"f":true}"#,
+            r#"{"a":"c","c":{"d":2},"g":{"h":{"i":"j"}}}"#,
+            r#"{"a":{"b":"c"},"c":"d"}"#,
+            r#"{"c":{"d":1,"e":[2,1],"x":{"y":"z"}}}"#,
+            r#"["a","b"]"#,
+            r#""a""#,
+            r#"{"input":{"default":"1-7","description":"stage"},"a":1}"#,
+        ];
+        for original in values {
+            for modified in values {
+                let (original, modified) = (json(original), json(modified));
+                let patch = MAAValue::compute_json_merge_patch(&original, &modified);
+                let mut patched = original.clone();
+                patched.apply_json_merge_patch(&patch);
+                assert_eq!(patched, modified, "patch: {patch:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn merge_mut_unchanged() {
+        // merge_mut keeps null values from the other value instead of removing keys
+        let mut value = object!("a" => "b", "c" => 1);
+        value.merge_mut(&json(r#"{"a":null}"#));
+        assert_eq!(value, json(r#"{"a":null,"c":1}"#));
+    }
+}
@@ -0,0 +1,165 @@
+use super::{MAAPrimate, MAAValue};
+
+use std::fmt;
+
+/// Type of a value, see [`MAAValue::value_type`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueType {
+    Array,
+    Input,
+    Optional,
+    Object,
+    Bool,
+    Int,
+    Float,
+    String,
+    Null,
+}
+
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            ValueType::Array => "array",
+            ValueType::Input => "input",
+            ValueType::Optional => "optional",
+            ValueType::Object => "object",
+            ValueType::Bool => "bool",
+            ValueType::Int => "int",
+            ValueType::Float => "float",
+            ValueType::String => "string",
+            ValueType::Null => "null",
+        };
+        f.write_str(name)
+    }
+}
+
+impl MAAValue {
+    /// Type of the value
+    pub fn value_type(&self) -> ValueType {
+        match self {
+            MAAValue::Array(_) => ValueType::Array,
+            MAAValue::Input(_) => ValueType::Input,
+            MAAValue::Optional { .. } => ValueType::Optional,
+            MAAValue::Object(_) => ValueType::Object,
+            MAAValue::Primate(MAAPrimate::Bool(_)) => ValueType::Bool,
+            MAAValue::Primate(MAAPrimate::Int(_)) => ValueType::Int,
+            MAAValue::Primate(MAAPrimate::Float(_)) => ValueType::Float,
+            MAAValue::Primate(MAAPrimate::String(_)) => ValueType::String,
+            MAAValue::Null => ValueType::Null,
+        }
+    }
+
+    /// Look up a value by a JSON Pointer (RFC 6901), e.g. `/tasks/0/params`
+    ///
+    /// An empty pointer refers to the value itself. In each reference token,
+    /// `~1` is unescaped to `/` and `~0` to `~`. Optional values are transparent,
+    /// so pointers go through them to their inner values.
+    ///
+    /// Return `None` if the pointer is malformed or the path does not exist.
+    #[cfg(test)]
+    pub fn get_pointer(&self, pointer: &str) -> Option<&Self> {
+        if pointer.is_empty() {
+            return Some(self);
+        }
+        let pointer = pointer.strip_prefix('/')?;
+
+        pointer.split('/').try_fold(self, |value, token| {
+            let token = token.replace("~1", "/").replace("~0", "~");
+            let value = match value {
+                MAAValue::Optional { value, .. } => &value.0,
+                value => value,
+            };
+            match value {
+                MAAValue::Object(map) => map.get(&token),
+                MAAValue::Array(array) => parse_index(&token).and_then(|i| array.get(i)),
+                _ => None,
+            }
+        })
+    }
+
+    /// Check whether the value at given JSON Pointer exists, see [`MAAValue::get_pointer`]
+    #[cfg(test)]
+    pub fn path_exists(&self, pointer: &str) -> bool {
+        self.get_pointer(pointer).is_some()
+    }
+
+    /// Type of the value at given JSON Pointer, `None` if the path does not exist
+    #[cfg(test)]
+    pub fn path_type(&self, pointer: &str) -> Option<ValueType> {
+        self.get_pointer(pointer).map(Self::value_type)
+    }
+}
+
+/// Parse an array index, leading zeros are not allowed by RFC 6901
+#[cfg(test)]
+fn parse_index(token: &str) -> Option<usize> {
+    if token.is_empty() || (token.len() > 1 && token.starts_with('0')) {
+        return None;
+    }
+    if !token.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    token.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::object;
+
+    use crate::value::userinput::Input;
+
+    #[test]
+    fn pointer() {
+        let value = object!(
+            "tasks" => [object!(
+                "type" => "Fight",
+                "params" => object!("stage" => "1-7", "times" => 3),
+            )],
+            "a/b" => object!("m~n" => true),
+            "optional" if "enable" == true => object!("inner" => 0.5),
+            "input" => Input::new(Some(1), None),
+            "" => MAAValue::Null,
+        );
+
+        assert_eq!(value.get_pointer(""), Some(&value));
+        assert_eq!(
+            value.get_pointer("/tasks/0/params/stage"),
+            Some(&MAAValue::from("1-7"))
+        );
+        assert_eq!(value.get_pointer("/a~1b/m~0n"), Some(&MAAValue::from(true)));
+        assert_eq!(
+            value.get_pointer("/optional/inner"),
+            Some(&MAAValue::from(0.5))
+        );
+
+        assert!(value.path_exists("/tasks/0/params"));
+        assert!(value.path_exists("/"));
+        assert!(!value.path_exists("/tasks/1"));
+        assert!(!value.path_exists("/tasks/00"));
+        assert!(!value.path_exists("/tasks/-"));
+        assert!(!value.path_exists("/tasks/0/params/stage/x"));
+        assert!(!value.path_exists("tasks"));
+
+        assert_eq!(value.path_type(""), Some(ValueType::Object));
+        assert_eq!(value.path_type("/tasks"), Some(ValueType::Array));
+        assert_eq!(value.path_type("/tasks/0/type"), Some(ValueType::String));
+        assert_eq!(
+            value.path_type("/tasks/0/params/times"),
+            Some(ValueType::Int)
+        );
+        assert_eq!(value.path_type("/a~1b/m~0n"), Some(ValueType::Bool));
+        assert_eq!(value.path_type("/optional"), Some(ValueType::Optional));
+        assert_eq!(value.path_type("/optional/inner"), Some(ValueType::Float));
+        assert_eq!(value.path_type("/input"), Some(ValueType::Input));
+        assert_eq!(value.path_type("/"), Some(ValueType::Null));
+        assert_eq!(value.path_type("/missing"), None);
+    }
+
+    #[test]
+    fn value_type_display() {
+        assert_eq!(ValueType::Object.to_string(), "object");
+        assert_eq!(ValueType::Null.to_string(), "null");
+    }
+}
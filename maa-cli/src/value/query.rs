@@ -0,0 +1,209 @@
+use super::{MAAPrimate, MAAValue, Map, TryFromError};
+
+use std::fmt;
+
+/// Error when parsing a query string or INI into a value
+#[derive(Debug, PartialEq)]
+pub enum ParseError {
+    /// The key or a segment of a dotted key is empty, e.g. `=1` or `a..b=1`
+    EmptyKey(String),
+    /// The key is used both as a value and an object, e.g. `a=1&a.b=2`
+    Conflict(String),
+    /// A line of INI is neither a section, a key-value pair nor a comment
+    InvalidLine { line: usize, content: String },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::EmptyKey(key) => write!(f, "Empty key in query string: `{key}`"),
+            ParseError::Conflict(key) => {
+                write!(f, "Key `{key}` is used both as a value and an object")
+            }
+            ParseError::InvalidLine { line, content } => {
+                write!(f, "Invalid line {line}: `{content}`")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl MAAValue {
+    /// Encode an object into a URL query string, e.g. `key=value&key2=value2`.
+    ///
+    /// Nested objects are flattened with dot notation, e.g. `params.count=5`.
+    /// Keys and values are percent-encoded, and keys are in sorted order.
+    ///
+    /// # Errors
+    ///
+    /// Return [`TryFromError::TypeMismatch`] if the value is not an object,
+    /// or it contains arrays or uninitialized values.
+    pub fn to_query_string(&self) -> Result<String, TryFromError> {
+        fn flatten(
+            prefix: &str,
+            map: &Map<String, MAAValue>,
+            query: &mut form_urlencoded::Serializer<String>,
+        ) -> Result<(), TryFromError> {
+            for (key, value) in map {
+                let key = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{prefix}.{key}")
+                };
+                match value {
+                    MAAValue::Object(map) => flatten(&key, map, query)?,
+                    MAAValue::Primate(v) => {
+                        query.append_pair(&key, &primate_to_string(v));
+                    }
+                    _ => return Err(TryFromError::TypeMismatch),
+                }
+            }
+            Ok(())
+        }
+
+        let map = self.as_object().ok_or(TryFromError::TypeMismatch)?;
+        let mut query = form_urlencoded::Serializer::new(String::new());
+        flatten("", map, &mut query)?;
+        Ok(query.finish())
+    }
+
+    /// Parse a URL query string into an object with string values.
+    ///
+    /// Keys with dot notation are parsed into nested objects, e.g. `params.count=5`
+    /// is parsed into `{"params": {"count": "5"}}`. If a key is given multiple times,
+    /// the last value is used.
+    pub fn from_query_string(s: &str) -> Result<Self, ParseError> {
+        let mut root = Map::new();
+        for (key, value) in form_urlencoded::parse(s.trim_start_matches('?').as_bytes()) {
+            let segments: Vec<&str> = key.split('.').collect();
+            if segments.iter().any(|s| s.is_empty()) {
+                return Err(ParseError::EmptyKey(key.into_owned()));
+            }
+
+            let (last, parents) = segments.split_last().expect("split is never empty");
+            let mut map = &mut root;
+            for parent in parents {
+                let entry = map.entry(parent.to_string()).or_insert_with(MAAValue::new);
+                map = match entry {
+                    MAAValue::Object(map) => map,
+                    _ => return Err(ParseError::Conflict(key.into_owned())),
+                };
+            }
+
+            if matches!(map.get(*last), Some(MAAValue::Object(_))) {
+                return Err(ParseError::Conflict(key.into_owned()));
+            }
+            map.insert(last.to_string(), value.into_owned().into());
+        }
+        Ok(MAAValue::Object(root))
+    }
+}
+
+pub(super) fn primate_to_string(value: &MAAPrimate) -> String {
+    match value {
+        MAAPrimate::Bool(v) => v.to_string(),
+        MAAPrimate::Int(v) => v.to_string(),
+        MAAPrimate::Float(v) => v.to_string(),
+        MAAPrimate::String(v) => v.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::object;
+
+    use crate::value::userinput::Input;
+
+    #[test]
+    fn to_query() {
+        assert_eq!(
+            object!(
+                "stage" => "1-7",
+                "medicine" => 2,
+                "expiring_medicine" => true,
+                "ratio" => 0.5,
+            )
+            .to_query_string()
+            .unwrap(),
+            "expiring_medicine=true&medicine=2&ratio=0.5&stage=1-7"
+        );
+
+        assert_eq!(
+            object!(
+                "name" => "a b&c=d",
+                "params" => object!("count" => 5, "client" => object!("type" => "Official")),
+            )
+            .to_query_string()
+            .unwrap(),
+            "name=a+b%26c%3Dd&params.client.type=Official&params.count=5"
+        );
+
+        assert_eq!(MAAValue::new().to_query_string().unwrap(), "");
+
+        assert_eq!(
+            MAAValue::from(1).to_query_string(),
+            Err(TryFromError::TypeMismatch)
+        );
+        assert_eq!(
+            object!("array" => [1, 2]).to_query_string(),
+            Err(TryFromError::TypeMismatch)
+        );
+        assert_eq!(
+            object!("input" => Input::new(Some(1), None)).to_query_string(),
+            Err(TryFromError::TypeMismatch)
+        );
+    }
+
+    #[test]
+    fn from_query() {
+        assert_eq!(
+            MAAValue::from_query_string("stage=1-7&medicine=2").unwrap(),
+            object!("stage" => "1-7", "medicine" => "2"),
+        );
+        assert_eq!(
+            MAAValue::from_query_string(
+                "?name=a+b%26c%3Dd&params.client.type=Official&params.count=5"
+            )
+            .unwrap(),
+            object!(
+                "name" => "a b&c=d",
+                "params" => object!("count" => "5", "client" => object!("type" => "Official")),
+            ),
+        );
+        assert_eq!(
+            MAAValue::from_query_string("a=1&a=2").unwrap(),
+            object!("a" => "2")
+        );
+        assert_eq!(MAAValue::from_query_string("").unwrap(), MAAValue::new());
+
+        assert_eq!(
+            MAAValue::from_query_string("a..b=1"),
+            Err(ParseError::EmptyKey("a..b".to_owned()))
+        );
+        assert_eq!(
+            MAAValue::from_query_string("=1"),
+            Err(ParseError::EmptyKey("".to_owned()))
+        );
+        assert_eq!(
+            MAAValue::from_query_string("a=1&a.b=2"),
+            Err(ParseError::Conflict("a.b".to_owned()))
+        );
+        assert_eq!(
+            MAAValue::from_query_string("a.b=2&a=1"),
+            Err(ParseError::Conflict("a".to_owned()))
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let value = object!(
+            "stage" => "CE-6",
+            "params" => object!("times" => "3", "name" => "中文 & more"),
+        );
+        let query = value.to_query_string().unwrap();
+        assert_eq!(MAAValue::from_query_string(&query).unwrap(), value);
+    }
+}
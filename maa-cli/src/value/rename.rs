@@ -0,0 +1,274 @@
+use super::{MAAValue, Map};
+
+use std::fmt;
+
+use log::warn;
+
+/// Error when renaming a key of an object, see [`MAAValue::rename_key`]
+#[derive(Debug, PartialEq)]
+pub enum RenameError {
+    /// The key to rename does not exist
+    KeyNotFound(String),
+    /// The new key already exists, use [`MAAValue::rename_key_overwrite`] to replace it
+    NewKeyExists(String),
+    /// The value is not an object
+    NotAnObject,
+}
+
+impl fmt::Display for RenameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RenameError::KeyNotFound(key) => write!(f, "Key `{key}` not found"),
+            RenameError::NewKeyExists(key) => write!(f, "Key `{key}` already exists"),
+            RenameError::NotAnObject => write!(f, "Value is not an object"),
+        }
+    }
+}
+
+impl std::error::Error for RenameError {}
+
+impl MAAValue {
+    /// Rename a key of the object, keeping its value.
+    ///
+    /// Renaming a key to itself does nothing.
+    ///
+    /// # Errors
+    ///
+    /// - [`RenameError::NotAnObject`] if the value is not an object;
+    /// - [`RenameError::KeyNotFound`] if the key `old` does not exist;
+    /// - [`RenameError::NewKeyExists`] if the key `new` already exists,
+    ///   the object is not changed to avoid overwriting it silently.
+    pub fn rename_key(&mut self, old: &str, new: impl Into<String>) -> Result<(), RenameError> {
+        let Self::Object(map) = self else {
+            return Err(RenameError::NotAnObject);
+        };
+        let new = new.into();
+        if !map.contains_key(old) {
+            return Err(RenameError::KeyNotFound(old.to_owned()));
+        }
+        if old == new {
+            return Ok(());
+        }
+        if map.contains_key(&new) {
+            return Err(RenameError::NewKeyExists(new));
+        }
+        let value = map.remove(old).expect("key exists");
+        map.insert(new, value);
+        Ok(())
+    }
+
+    /// Rename a key of the object, overwriting the value of the new key if it exists.
+    ///
+    /// Return the displaced value of the new key. Nothing is changed if the value is not
+    /// an object or the key `old` does not exist, in which case `None` is returned.
+    pub fn rename_key_overwrite(&mut self, old: &str, new: impl Into<String>) -> Option<Self> {
+        let Self::Object(map) = self else {
+            return None;
+        };
+        let new = new.into();
+        if old == new {
+            return None;
+        }
+        let value = map.remove(old)?;
+        map.insert(new, value)
+    }
+}
+
+impl MAAValue {
+    /// Return a copy of the value with all keys of objects transformed by `f`.
+    ///
+    /// See [`MAAValue::transform_keys_mut`] for details.
+    pub fn transform_keys<F>(&self, f: F) -> Self
+    where
+        F: Fn(&str) -> String,
+    {
+        let mut value = self.clone();
+        value.transform_keys_mut(f);
+        value
+    }
+
+    /// Transform all keys of objects by `f` in place, recursing into nested values.
+    ///
+    /// Keys of the conditions of optional values are transformed as well, since they refer to
+    /// keys in the same object. If `f` maps multiple keys of an object to the same key,
+    /// the last one in order of the original keys wins, and a warning is logged.
+    pub fn transform_keys_mut<F>(&mut self, f: F)
+    where
+        F: Fn(&str) -> String,
+    {
+        fn transform(value: &mut MAAValue, f: &dyn Fn(&str) -> String) {
+            match value {
+                MAAValue::Object(map) => {
+                    let mut transformed = Map::new();
+                    for (key, mut value) in std::mem::take(map) {
+                        transform(&mut value, f);
+                        let new_key = f(&key);
+                        if transformed.contains_key(&new_key) {
+                            warn!("Key `{key}` is transformed to duplicate key `{new_key}`, the last one wins");
+                        }
+                        transformed.insert(new_key, value);
+                    }
+                    *map = transformed;
+                }
+                MAAValue::Array(array) => {
+                    for value in array {
+                        transform(value, f);
+                    }
+                }
+                MAAValue::Optional { conditions, value } => {
+                    *conditions = std::mem::take(conditions)
+                        .into_iter()
+                        .map(|(key, expected)| (f(&key), expected))
+                        .collect();
+                    transform(&mut value.0, f);
+                }
+                _ => {}
+            }
+        }
+
+        transform(self, &f);
+    }
+
+    /// Return a copy of the value with all keys converted to `snake_case`, e.g. `stageName` to
+    /// `stage_name`. An acronym is kept as a word, e.g. `HTTPServer` to `http_server`.
+    pub fn keys_to_snake_case(&self) -> Self {
+        self.transform_keys(to_snake_case)
+    }
+
+    /// Return a copy of the value with all keys converted to `camelCase`,
+    /// e.g. `stage_name` to `stageName`.
+    pub fn keys_to_camel_case(&self) -> Self {
+        self.transform_keys(to_camel_case)
+    }
+}
+
+fn to_snake_case(key: &str) -> String {
+    let chars: Vec<char> = key.chars().collect();
+    let mut result = String::with_capacity(key.len() + 4);
+    for (i, &c) in chars.iter().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            let prev = chars[i - 1];
+            let next_lower = chars.get(i + 1).is_some_and(|c| c.is_lowercase());
+            if prev.is_lowercase() || prev.is_ascii_digit() || (prev.is_uppercase() && next_lower) {
+                result.push('_');
+            }
+        }
+        result.extend(c.to_lowercase());
+    }
+    result
+}
+
+fn to_camel_case(key: &str) -> String {
+    let mut result = String::with_capacity(key.len());
+    let mut upper = false;
+    for c in key.chars() {
+        if c == '_' && !result.is_empty() {
+            upper = true;
+        } else if upper {
+            result.extend(c.to_uppercase());
+            upper = false;
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::object;
+
+    #[test]
+    fn rename() {
+        let mut value = object!("stage" => "1-7", "times" => 3);
+
+        assert_eq!(value.rename_key("stage", "stage_name"), Ok(()));
+        assert_eq!(value, object!("stage_name" => "1-7", "times" => 3));
+
+        assert_eq!(
+            value.rename_key("stage", "stage_name"),
+            Err(RenameError::KeyNotFound("stage".to_owned()))
+        );
+        assert_eq!(
+            value.rename_key("stage_name", "times"),
+            Err(RenameError::NewKeyExists("times".to_owned()))
+        );
+        assert_eq!(value, object!("stage_name" => "1-7", "times" => 3));
+        assert_eq!(value.rename_key("times", "times"), Ok(()));
+
+        assert_eq!(
+            MAAValue::from(1).rename_key("a", "b"),
+            Err(RenameError::NotAnObject)
+        );
+    }
+
+    #[test]
+    fn rename_overwrite() {
+        let mut value = object!("stage" => "1-7", "stage_name" => "CE-6");
+
+        assert_eq!(
+            value.rename_key_overwrite("stage", "stage_name"),
+            Some(MAAValue::from("CE-6"))
+        );
+        assert_eq!(value, object!("stage_name" => "1-7"));
+
+        assert_eq!(value.rename_key_overwrite("stage_name", "stage"), None);
+        assert_eq!(value, object!("stage" => "1-7"));
+
+        assert_eq!(value.rename_key_overwrite("missing", "stage"), None);
+        assert_eq!(value.rename_key_overwrite("stage", "stage"), None);
+        assert_eq!(value, object!("stage" => "1-7"));
+        assert_eq!(MAAValue::from(1).rename_key_overwrite("a", "b"), None);
+    }
+
+    #[test]
+    fn transform() {
+        let value = object!(
+            "stageName" => "1-7",
+            "params" => [object!("HTTPServer" => 1)],
+            "reportTo" if "useReport" == true => object!("userId" => "id"),
+            "useReport" => true,
+        );
+        let snake = object!(
+            "stage_name" => "1-7",
+            "params" => [object!("http_server" => 1)],
+            "report_to" if "use_report" == true => object!("user_id" => "id"),
+            "use_report" => true,
+        );
+        assert_eq!(value.keys_to_snake_case(), snake);
+        assert_eq!(
+            snake.keys_to_camel_case(),
+            object!(
+                "stageName" => "1-7",
+                "params" => [object!("httpServer" => 1)],
+                "reportTo" if "useReport" == true => object!("userId" => "id"),
+                "useReport" => true,
+            )
+        );
+
+        let mut value = object!("a" => 1, "b" => object!("c" => 2));
+        value.transform_keys_mut(|key| format!("x_{key}"));
+        assert_eq!(value, object!("x_a" => 1, "x_b" => object!("x_c" => 2)));
+
+        // the last key wins on duplicate keys
+        let value = object!("a" => 1, "b" => 2);
+        assert_eq!(value.transform_keys(|_| "k".to_owned()), object!("k" => 2));
+        assert_eq!(
+            MAAValue::from(1).transform_keys(|_| "k".to_owned()),
+            MAAValue::from(1)
+        );
+    }
+
+    #[test]
+    fn case_conversion() {
+        assert_eq!(to_snake_case("stageName"), "stage_name");
+        assert_eq!(to_snake_case("HTTPServer"), "http_server");
+        assert_eq!(to_snake_case("stage2Name"), "stage2_name");
+        assert_eq!(to_snake_case("already_snake"), "already_snake");
+        assert_eq!(to_camel_case("stage_name"), "stageName");
+        assert_eq!(to_camel_case("_private"), "_private");
+        assert_eq!(to_camel_case("camelCase"), "camelCase");
+    }
+}
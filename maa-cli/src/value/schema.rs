@@ -0,0 +1,352 @@
+use super::{pointer::ValueType, MAAValue, TryFromError};
+
+use std::fmt;
+
+/// Error when a value does not match a schema, see [`MAAValue::assert_schema`]
+///
+/// Paths are JSON Pointers (RFC 6901) to the mismatched values, e.g. `/params/stage`.
+#[derive(Debug, PartialEq)]
+pub enum SchemaError {
+    /// The value can not be used, e.g. [`TryFromError::MissingKey`] with the path of
+    /// a missing required key
+    Value(TryFromError),
+    /// The value is of another type
+    TypeMismatch {
+        path: String,
+        expected: ValueType,
+        found: ValueType,
+    },
+    /// The schema itself is invalid, e.g. an unknown type annotation
+    InvalidSchema { path: String, reason: String },
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SchemaError::Value(error) => error.fmt(f),
+            SchemaError::TypeMismatch {
+                path,
+                expected,
+                found,
+            } => write!(f, "Expected {expected} at `{path}`, found {found}"),
+            SchemaError::InvalidSchema { path, reason } => {
+                write!(f, "Invalid schema at `{path}`: {reason}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SchemaError {}
+
+impl From<TryFromError> for SchemaError {
+    fn from(error: TryFromError) -> Self {
+        SchemaError::Value(error)
+    }
+}
+
+/// A type annotation in a schema, e.g. `"$int"` or `"$string?"`
+struct Annotation {
+    /// Expected type, `None` for `$any`
+    expected: Option<ValueType>,
+    optional: bool,
+}
+
+impl Annotation {
+    /// Parse a type annotation, `None` if the string is not an annotation
+    fn parse(s: &str) -> Option<Result<Self, String>> {
+        let name = s.strip_prefix('$')?;
+        let (name, optional) = match name.strip_suffix('?') {
+            Some(name) => (name, true),
+            None => (name, false),
+        };
+        let expected = match name {
+            "any" => None,
+            "bool" => Some(ValueType::Bool),
+            "int" => Some(ValueType::Int),
+            "float" => Some(ValueType::Float),
+            "string" => Some(ValueType::String),
+            "array" => Some(ValueType::Array),
+            "object" => Some(ValueType::Object),
+            _ => return Some(Err(format!("unknown type annotation `{s}`"))),
+        };
+        Some(Ok(Self { expected, optional }))
+    }
+}
+
+impl MAAValue {
+    /// Check that the value matches `schema`, a lightweight alternative to JSON Schema
+    /// for validating task parameters in tests.
+    ///
+    /// The schema is a value of the same shape, where:
+    ///
+    /// - a string annotation `$bool`, `$int`, `$float`, `$string`, `$array`, `$object` or
+    ///   `$any` requires a value of given type, and a trailing `?` (e.g. `$int?`) marks
+    ///   the key as optional, i.e. it is checked only if present;
+    /// - an object requires an object, whose keys are checked against the schema recursively,
+    ///   keys not in the schema are allowed;
+    /// - an array with a single element requires an array whose elements all match it,
+    ///   an empty array requires any array;
+    /// - any other value requires a value of the same type.
+    ///
+    /// Optional values are transparent, so their inner values are checked.
+    /// Input values are not initialized, so they only match `$any`.
+    ///
+    /// # Errors
+    ///
+    /// The first mismatch found, see [`SchemaError`].
+    pub fn assert_schema(&self, schema: &MAAValue) -> Result<(), SchemaError> {
+        check(self, schema, &mut String::new())
+    }
+}
+
+fn check(value: &MAAValue, schema: &MAAValue, path: &mut String) -> Result<(), SchemaError> {
+    let value = match value {
+        MAAValue::Optional { value, .. } => &value.0,
+        value => value,
+    };
+    let mismatch = |expected: ValueType, path: &str| SchemaError::TypeMismatch {
+        path: path.to_owned(),
+        expected,
+        found: value.value_type(),
+    };
+
+    match schema {
+        MAAValue::Object(fields) => {
+            let MAAValue::Object(map) = value else {
+                return Err(mismatch(ValueType::Object, path));
+            };
+            for (key, field) in fields {
+                let len = path.len();
+                path.push('/');
+                path.push_str(&key.replace('~', "~0").replace('/', "~1"));
+                match map.get(key) {
+                    Some(value) => check(value, field, path)?,
+                    None if is_optional(field, path)? => {}
+                    None => return Err(TryFromError::MissingKey(path.clone()).into()),
+                }
+                path.truncate(len);
+            }
+            Ok(())
+        }
+        MAAValue::Array(items) => {
+            let MAAValue::Array(array) = value else {
+                return Err(mismatch(ValueType::Array, path));
+            };
+            match items.as_slice() {
+                [] => Ok(()),
+                [item] => {
+                    for (i, value) in array.iter().enumerate() {
+                        let len = path.len();
+                        path.push('/');
+                        path.push_str(&i.to_string());
+                        check(value, item, path)?;
+                        path.truncate(len);
+                    }
+                    Ok(())
+                }
+                _ => Err(SchemaError::InvalidSchema {
+                    path: path.clone(),
+                    reason: "arrays in schema must have at most one element".to_owned(),
+                }),
+            }
+        }
+        MAAValue::Primate(primate) => {
+            let annotation = match primate.as_str().and_then(Annotation::parse) {
+                Some(annotation) => annotation.map_err(|reason| SchemaError::InvalidSchema {
+                    path: path.clone(),
+                    reason,
+                })?,
+                None => Annotation {
+                    expected: Some(schema.value_type()),
+                    optional: false,
+                },
+            };
+            match annotation.expected {
+                Some(expected) if expected != value.value_type() => Err(mismatch(expected, path)),
+                _ => Ok(()),
+            }
+        }
+        MAAValue::Input(_) | MAAValue::Optional { .. } | MAAValue::Null => {
+            Err(SchemaError::InvalidSchema {
+                path: path.clone(),
+                reason: format!("{} is not allowed in schema", schema.value_type()),
+            })
+        }
+    }
+}
+
+/// Whether a missing key with given schema is allowed
+fn is_optional(schema: &MAAValue, path: &str) -> Result<bool, SchemaError> {
+    match schema.as_str().and_then(Annotation::parse) {
+        Some(annotation) => annotation
+            .map(|annotation| annotation.optional)
+            .map_err(|reason| SchemaError::InvalidSchema {
+                path: path.to_owned(),
+                reason,
+            }),
+        None => Ok(false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::object;
+
+    use crate::value::userinput::Input;
+
+    fn schema() -> MAAValue {
+        object!(
+            "stage" => "$string",
+            "times" => "$int",
+            "medicine" => "$int?",
+            "report" => false,
+            "drops" => object!("30012" => "$int?"),
+            "series" => ["$int"],
+            "extra" => "$any?",
+        )
+    }
+
+    #[test]
+    fn valid() {
+        let value = object!(
+            "stage" => "1-7",
+            "times" => 3,
+            "report" => true,
+            "drops" => object!("30011" => 5),
+            "series" => [1, 2],
+            "unknown" => 0.5,
+        );
+        assert_eq!(value.assert_schema(&schema()), Ok(()));
+
+        // optional values are checked by their inner values
+        let value = object!(
+            "stage" => "1-7",
+            "times" => 3,
+            "medicine" if "times" == 3 => 1,
+            "report" => false,
+            "drops" => object!(),
+            "series" => MAAValue::Array(vec![]),
+            "extra" => Input::new(Some(1), None),
+        );
+        assert_eq!(value.assert_schema(&schema()), Ok(()));
+
+        assert_eq!(object!().assert_schema(&object!()), Ok(()));
+        assert_eq!(MAAValue::from(1).assert_schema(&"$any".into()), Ok(()));
+    }
+
+    #[test]
+    fn invalid() {
+        let value = object!(
+            "stage" => "1-7",
+            "report" => true,
+            "drops" => object!(),
+            "series" => [1],
+        );
+        assert_eq!(
+            value.assert_schema(&schema()),
+            Err(SchemaError::Value(TryFromError::MissingKey(
+                "/times".to_owned()
+            )))
+        );
+        assert_eq!(
+            value.assert_schema(&schema()).unwrap_err().to_string(),
+            "required key '/times' is missing"
+        );
+
+        let value = object!(
+            "stage" => "1-7",
+            "times" => "3",
+            "report" => true,
+            "drops" => object!(),
+            "series" => [1],
+        );
+        assert_eq!(
+            value.assert_schema(&schema()),
+            Err(SchemaError::TypeMismatch {
+                path: "/times".to_owned(),
+                expected: ValueType::Int,
+                found: ValueType::String,
+            })
+        );
+
+        let value = object!(
+            "stage" => "1-7",
+            "times" => 3,
+            "report" => true,
+            "drops" => object!("30012" => 1.5),
+            "series" => [1],
+        );
+        assert_eq!(
+            value.assert_schema(&schema()).unwrap_err().to_string(),
+            "Expected int at `/drops/30012`, found float"
+        );
+
+        let value = object!(
+            "stage" => "1-7",
+            "times" => 3,
+            "report" => 1,
+            "drops" => object!(),
+            "series" => [1],
+        );
+        assert_eq!(
+            value.assert_schema(&schema()).unwrap_err().to_string(),
+            "Expected bool at `/report`, found int"
+        );
+
+        let value = object!(
+            "stage" => "1-7",
+            "times" => 3,
+            "report" => true,
+            "drops" => object!(),
+            "series" => [MAAValue::from(1), 2.into(), "3".into()],
+        );
+        assert_eq!(
+            value.assert_schema(&schema()).unwrap_err().to_string(),
+            "Expected int at `/series/2`, found string"
+        );
+
+        assert_eq!(
+            MAAValue::from(1).assert_schema(&schema()),
+            Err(SchemaError::TypeMismatch {
+                path: String::new(),
+                expected: ValueType::Object,
+                found: ValueType::Int,
+            })
+        );
+        assert_eq!(
+            object!("a/b" => object!()).assert_schema(&object!("a/b" => object!("c~d" => "$int"))),
+            Err(SchemaError::Value(TryFromError::MissingKey(
+                "/a~1b/c~0d".to_owned()
+            )))
+        );
+        // uninitialized input values only match `$any`
+        assert!(object!("a" => Input::new(Some(1), None))
+            .assert_schema(&object!("a" => "$int"))
+            .is_err());
+    }
+
+    #[test]
+    fn invalid_schema() {
+        let value = object!("a" => 1);
+        assert_eq!(
+            value.assert_schema(&object!("a" => "$integer")),
+            Err(SchemaError::InvalidSchema {
+                path: "/a".to_owned(),
+                reason: "unknown type annotation `$integer`".to_owned(),
+            })
+        );
+        assert!(object!()
+            .assert_schema(&object!("a" => "$integer?"))
+            .is_err());
+        assert_eq!(
+            MAAValue::Array(vec![])
+                .assert_schema(&["$int", "$string"].into())
+                .unwrap_err()
+                .to_string(),
+            "Invalid schema at ``: arrays in schema must have at most one element"
+        );
+        assert!(value.assert_schema(&MAAValue::Null).is_err());
+    }
+}
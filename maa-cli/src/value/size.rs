@@ -0,0 +1,91 @@
+use super::{MAAPrimate, MAAValue};
+
+use std::mem::size_of;
+
+impl MAAValue {
+    /// Estimate the memory used by the value in bytes, including the heap memory.
+    ///
+    /// Strings contribute their length, arrays their capacity times the size of a value,
+    /// and each entry of objects the size of its key and value. User inputs are only counted
+    /// by their inline size. The estimate is not exact, but should be within a factor of 2.
+    pub fn size_bytes(&self) -> usize {
+        size_of::<Self>() + self.heap_bytes()
+    }
+
+    fn heap_bytes(&self) -> usize {
+        match self {
+            MAAValue::Array(array) => {
+                array.capacity() * size_of::<Self>()
+                    + array.iter().map(Self::heap_bytes).sum::<usize>()
+            }
+            MAAValue::Object(map) => map
+                .iter()
+                .map(|(key, value)| key_bytes(key) + value.size_bytes())
+                .sum(),
+            MAAValue::Optional { conditions, value } => {
+                let conditions: usize = conditions
+                    .iter()
+                    .map(|(key, value)| {
+                        key_bytes(key) + size_of::<MAAPrimate>() + primate_heap_bytes(value)
+                    })
+                    .sum();
+                conditions + value.0.size_bytes()
+            }
+            MAAValue::Primate(value) => primate_heap_bytes(value),
+            MAAValue::Input(_) | MAAValue::Null => 0,
+        }
+    }
+}
+
+fn key_bytes(key: &str) -> usize {
+    size_of::<String>() + key.len()
+}
+
+fn primate_heap_bytes(value: &MAAPrimate) -> usize {
+    match value {
+        MAAPrimate::String(s) => s.len(),
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::object;
+
+    const VALUE: usize = size_of::<MAAValue>();
+    const STRING: usize = size_of::<String>();
+
+    #[test]
+    fn size() {
+        assert_eq!(MAAValue::from(1).size_bytes(), VALUE);
+        assert_eq!(MAAValue::Null.size_bytes(), VALUE);
+        assert_eq!(MAAValue::from("abc").size_bytes(), VALUE + 3);
+
+        let array = MAAValue::Array(Vec::with_capacity(4));
+        assert_eq!(array.size_bytes(), VALUE + 4 * VALUE);
+        assert_eq!(
+            MAAValue::from(["ab", "cd"]).size_bytes(),
+            VALUE + 2 * VALUE + 4
+        );
+
+        assert_eq!(
+            object!("stage" => "1-7", "times" => 3).size_bytes(),
+            VALUE + (STRING + 5 + VALUE + 3) + (STRING + 5 + VALUE)
+        );
+
+        let optional = object!("a" if "b" == true => 1);
+        assert_eq!(
+            optional.size_bytes(),
+            VALUE + STRING + 1 + VALUE + (STRING + 1 + size_of::<MAAPrimate>()) + VALUE
+        );
+    }
+
+    #[test]
+    fn nested_grows() {
+        let small = object!("params" => object!("stage" => "1-7"));
+        let large = object!("params" => object!("stage" => "1-7", "medicine" => 999));
+        assert!(large.size_bytes() > small.size_bytes());
+    }
+}
@@ -0,0 +1,130 @@
+use super::{MAAPrimate, MAAValue};
+
+use std::fmt::Write;
+
+impl MAAValue {
+    /// Render the value as an indented tree for humans, e.g. `maa config watch`.
+    ///
+    /// Values are not initialized, so user inputs are shown by their prompts
+    /// and optional values are shown with their conditions.
+    pub fn to_tree(&self) -> String {
+        let mut tree = String::new();
+        match self {
+            MAAValue::Object(map) if !map.is_empty() => {
+                for (key, value) in map {
+                    render(&format!("{key}:"), value, 0, &mut tree);
+                }
+            }
+            MAAValue::Array(array) if !array.is_empty() => {
+                for value in array {
+                    render("-", value, 0, &mut tree);
+                }
+            }
+            value => render("", value, 0, &mut tree),
+        }
+        tree
+    }
+}
+
+/// Render a value with a prefix, e.g. `key:` or `-`, at given indentation level.
+fn render(prefix: &str, value: &MAAValue, level: usize, tree: &mut String) {
+    let indent = "  ".repeat(level);
+    let mut line = |content: &str| {
+        let line = [prefix, content]
+            .into_iter()
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ");
+        let _ = writeln!(tree, "{indent}{line}");
+    };
+    match value {
+        MAAValue::Object(map) if map.is_empty() => line("{}"),
+        MAAValue::Array(array) if array.is_empty() => line("[]"),
+        MAAValue::Object(map) => {
+            line("");
+            for (key, value) in map {
+                render(&format!("{key}:"), value, level + 1, tree);
+            }
+        }
+        MAAValue::Array(array) => {
+            line("");
+            for value in array {
+                render("-", value, level + 1, tree);
+            }
+        }
+        MAAValue::Primate(MAAPrimate::String(s)) => line(&format!("{s:?}")),
+        MAAValue::Primate(MAAPrimate::Bool(v)) => line(&v.to_string()),
+        MAAValue::Primate(MAAPrimate::Int(v)) => line(&v.to_string()),
+        MAAValue::Primate(MAAPrimate::Float(v)) => line(&v.to_string()),
+        MAAValue::Null => line("null"),
+        MAAValue::Input(input) => line(&format!("<{}>", input.describe())),
+        MAAValue::Optional { conditions, value } => {
+            let conditions = conditions
+                .iter()
+                .map(|(key, expected)| format!("{key} == {}", primate(expected)))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let prefix = [prefix, &format!("(if {conditions})")]
+                .into_iter()
+                .filter(|s| !s.is_empty())
+                .collect::<Vec<_>>()
+                .join(" ");
+            render(&prefix, &value.0, level, tree)
+        }
+    }
+}
+
+fn primate(value: &MAAPrimate) -> String {
+    match value {
+        MAAPrimate::String(s) => format!("{s:?}"),
+        MAAPrimate::Bool(v) => v.to_string(),
+        MAAPrimate::Int(v) => v.to_string(),
+        MAAPrimate::Float(v) => v.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        object,
+        value::userinput::{BoolInput, Input, SelectD},
+    };
+
+    #[test]
+    fn to_tree() {
+        let value = object!(
+            "stage" => "1-7",
+            "medicine" => Input::new(Some(2), Some("medicine to use")),
+            "expiring_medicine" if "stage" == "1-7" => BoolInput::new(Some(true), None),
+            "client" => SelectD::<String>::new(["Official", "Bilibili"], Some(1), Some("client"), false).unwrap(),
+            "drops" => object!("30012" => 100, "ratio" => 0.5),
+            "times" => [1, 2],
+            "empty" => MAAValue::new(),
+            "removed" => MAAValue::Null,
+        );
+        assert_eq!(
+            value.to_tree(),
+            "client: <1. Official [default]; 2. Bilibili; Please select client (empty for default)>\n\
+             drops:\n  \
+               30012: 100\n  \
+               ratio: 0.5\n\
+             empty: {}\n\
+             expiring_medicine: (if stage == \"1-7\") <Whether to do something [Y/n]>\n\
+             medicine: <Please input medicine to use [default: 2]>\n\
+             removed: null\n\
+             stage: \"1-7\"\n\
+             times:\n  \
+               - 1\n  \
+               - 2\n"
+        );
+
+        assert_eq!(MAAValue::new().to_tree(), "{}\n");
+        assert_eq!(MAAValue::from("a").to_tree(), "\"a\"\n");
+        assert_eq!(
+            MAAValue::from([object!("a" => 1), MAAValue::from([1])]).to_tree(),
+            "-\n  a: 1\n-\n  - 1\n"
+        );
+    }
+}
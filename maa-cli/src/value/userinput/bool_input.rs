@@ -27,6 +27,10 @@ impl BoolInput {
 impl UserInput for BoolInput {
     type Value = bool;
 
+    fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
     fn default(self) -> Result<Self::Value, Self> {
         match self.default {
             Some(v) => Ok(v),
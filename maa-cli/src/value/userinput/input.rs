@@ -37,6 +37,10 @@ impl<F> Input<F> {
 impl<F: FromStr + Display + Clone> UserInput for Input<F> {
     type Value = F;
 
+    fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
     fn default(self) -> Result<Self::Value, Self> {
         match self.default {
             Some(v) => Ok(v),
@@ -11,7 +11,7 @@ pub fn enable_batch_mode() {
     BATCH_MODE.store(true, Ordering::Relaxed);
 }
 
-fn is_batch_mode() -> bool {
+pub fn is_batch_mode() -> bool {
     BATCH_MODE.load(Ordering::Relaxed)
 }
 
@@ -28,19 +28,30 @@ pub trait UserInput: Sized {
     ///
     /// - If in batch mode and `batch_default` returns `None`, return an io::Error with kind other.
     /// - If not in batch mode and `ask` returns an io::Error, return the error.
+    ///
+    /// If the parameter has a description, the error wraps an [`ErrorWithContext`]
+    /// identifying the parameter, with the kind of the original error.
     fn value(self) -> io::Result<Self::Value> {
-        if is_batch_mode() {
-            self.batch_default().map_err(|_| {
-                io::Error::new(
-                    io::ErrorKind::Other,
-                    "can not get default value in batch mode",
-                )
-            })
+        let description = self.description().map(str::to_owned);
+        let value = if is_batch_mode() {
+            self.batch_default()
+                .map_err(|_| io::Error::other("can not get default value in batch mode"))
         } else {
             self.ask(&mut std::io::stdout(), &mut std::io::stdin().lock())
+        };
+        match description {
+            Some(description) => {
+                value.map_err(|err| err.with_input_description(description).into())
+            }
+            None => value,
         }
     }
 
+    /// Description of this parameter, used to identify it in errors
+    fn description(&self) -> Option<&str> {
+        None
+    }
+
     /// Get the default value when user input is empty.
     ///
     /// If there is a default value, return it.
@@ -110,6 +121,60 @@ pub trait UserInput: Sized {
     fn parse(self, input: &str, writer: &mut impl Write) -> Result<Self::Value, io::Result<Self>>;
 }
 
+/// An error of getting the value of a parameter, with the description of the parameter
+#[derive(Debug)]
+pub struct ErrorWithContext {
+    description: String,
+    error: io::Error,
+}
+
+#[cfg(test)]
+impl ErrorWithContext {
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    pub fn error(&self) -> &io::Error {
+        &self.error
+    }
+}
+
+impl std::fmt::Display for ErrorWithContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "failed to get value for '{}': {}",
+            self.description, self.error
+        )
+    }
+}
+
+impl std::error::Error for ErrorWithContext {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl From<ErrorWithContext> for io::Error {
+    fn from(err: ErrorWithContext) -> Self {
+        io::Error::new(err.error.kind(), err)
+    }
+}
+
+/// Attach the description of a parameter to an error of getting its value
+pub trait WithInputDescription {
+    fn with_input_description(self, description: impl Into<String>) -> ErrorWithContext;
+}
+
+impl WithInputDescription for io::Error {
+    fn with_input_description(self, description: impl Into<String>) -> ErrorWithContext {
+        ErrorWithContext {
+            description: description.into(),
+            error: self,
+        }
+    }
+}
+
 macro_rules! err_err {
     ($err:expr) => {
         if let Err(err) = $err {
@@ -140,6 +205,22 @@ mod tests {
         );
 
         assert_eq!(Input::<i64>::new(Some(1), Some("")).value().unwrap(), 1);
+        let err = Input::<i64>::new(None, Some("medicine to use"))
+            .value()
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "failed to get value for 'medicine to use': can not get default value in batch mode"
+        );
+        let context = err
+            .get_ref()
+            .and_then(|err| err.downcast_ref::<ErrorWithContext>())
+            .unwrap();
+        assert_eq!(context.description(), "medicine to use");
+        assert_eq!(context.error().kind(), io::ErrorKind::Other);
+        // errors of parameters without description are not wrapped
+        let err = Input::<i64>::new(None, None).value().unwrap_err();
+        assert_eq!(err.to_string(), "can not get default value in batch mode");
         assert_eq!(
             Input::<i64>::new(None::<i64>, Some(""))
                 .value()
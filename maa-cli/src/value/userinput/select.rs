@@ -142,6 +142,10 @@ where
 {
     type Value = S::Value;
 
+    fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
     fn default(mut self) -> Result<Self::Value, Self> {
         self.default_index
             .map(|i| self.alternatives.swap_remove(i).value())
@@ -0,0 +1,273 @@
+//! Versions of maa-cli, MaaCore and resources, which are required by bug reports.
+
+use crate::{
+    command::Component,
+    dirs,
+    installer::manifest::{self, Manifest},
+    locate::{self, Located},
+    run,
+};
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+/// Versions of all components, and the paths they are installed at.
+///
+/// The structure of the JSON output is used by the issue template,
+/// so do not change it without care.
+#[derive(Serialize)]
+pub struct Report {
+    maa_cli: &'static str,
+    /// Version of MaaCore, `None` if not installed
+    maa_core: Option<String>,
+    /// Version of resources, `None` if not installed
+    resource: Option<ResourceVersion>,
+    target: &'static str,
+    paths: Paths,
+}
+
+#[derive(Serialize)]
+struct ResourceVersion {
+    /// Version recorded by the installer, e.g. the version of MaaCore shipping the resources
+    version: Option<String>,
+    /// The latest `last_updated` of the resources, including hot updated ones
+    last_updated: Option<String>,
+    /// When the resources were installed by the installer
+    installed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+struct Paths {
+    library: Option<PathBuf>,
+    resource: Option<PathBuf>,
+    hot_update: PathBuf,
+    config: PathBuf,
+    data: PathBuf,
+}
+
+impl Report {
+    fn new() -> Self {
+        let manifest = Manifest::load(&manifest::path());
+        let library = locate::find_library().map(Located::into_path);
+        let resource = locate::find_resource().map(Located::into_path);
+        let hot_update = dirs::hot_update().to_path_buf();
+
+        Self {
+            maa_cli: env!("MAA_VERSION"),
+            maa_core: core_version(library.is_some(), &manifest),
+            resource: resource.as_deref().map(|dir| ResourceVersion {
+                version: manifest
+                    .get(manifest::RESOURCE)
+                    .map(|entry| entry.version().to_owned()),
+                installed_at: manifest
+                    .get(manifest::RESOURCE)
+                    .map(|entry| entry.installed_at()),
+                last_updated: [dir.to_path_buf(), hot_update.join("resource")]
+                    .iter()
+                    .filter_map(|dir| last_updated(dir))
+                    .max(),
+            }),
+            target: env!("MAA_TARGET"),
+            paths: Paths {
+                library,
+                resource,
+                hot_update,
+                config: dirs::config().to_path_buf(),
+                data: dirs::data().to_path_buf(),
+            },
+        }
+    }
+
+    fn lines(&self, component: &Component) -> Vec<String> {
+        fn or_not_installed(path: Option<&Path>) -> String {
+            path.map_or_else(|| "not installed".to_owned(), |p| p.display().to_string())
+        }
+
+        let maa_cli = format!("maa-cli v{}", self.maa_cli);
+        let maa_core = match &self.maa_core {
+            Some(version) => format!("MaaCore v{version}"),
+            None => "MaaCore not installed".to_owned(),
+        };
+        match component {
+            Component::MaaCLI => return vec![maa_cli],
+            Component::MaaCore => return vec![maa_core],
+            Component::All => {}
+        }
+
+        let resource = match &self.resource {
+            Some(ResourceVersion {
+                version,
+                last_updated,
+                ..
+            }) => format!(
+                "Resource {} (last updated: {})",
+                version.as_deref().unwrap_or("unknown"),
+                last_updated.as_deref().unwrap_or("unknown")
+            ),
+            None => "Resource not installed".to_owned(),
+        };
+        let paths = &self.paths;
+        vec![
+            maa_cli,
+            maa_core,
+            resource,
+            format!("Target: {}", self.target),
+            format!("Library: {}", or_not_installed(paths.library.as_deref())),
+            format!("Resource: {}", or_not_installed(paths.resource.as_deref())),
+            format!("Hot update: {}", paths.hot_update.display()),
+            format!("Config: {}", paths.config.display()),
+            format!("Data: {}", paths.data.display()),
+        ]
+    }
+}
+
+/// Print versions of given component, or all components and their paths.
+///
+/// Missing components are reported as not installed instead of errors.
+/// If `json` is true, the report of all components is printed as JSON.
+pub fn print(component: Component, json: bool) -> Result<()> {
+    let report = Report::new();
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        for line in report.lines(&component) {
+            println!("{line}");
+        }
+    }
+    Ok(())
+}
+
+/// Version of MaaCore queried from the library, or recorded in the manifest if it fails to load.
+fn core_version(installed: bool, manifest: &Manifest) -> Option<String> {
+    if !installed {
+        return None;
+    }
+    match run::core_version() {
+        Ok(version) => Some(version.trim().trim_start_matches('v').to_owned()),
+        Err(err) => {
+            log::debug!("{err:#}, falling back to the install manifest");
+            manifest
+                .get(manifest::MAA_CORE)
+                .map(|entry| entry.version().to_owned())
+        }
+    }
+}
+
+/// The `last_updated` field of `version.json` in a resource directory.
+fn last_updated(resource_dir: &Path) -> Option<String> {
+    let content = std::fs::read_to_string(resource_dir.join("version.json")).ok()?;
+    let version: serde_json::Value = serde_json::from_str(&content).ok()?;
+    version.get("last_updated")?.as_str().map(str::to_owned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report() -> Report {
+        Report {
+            maa_cli: "0.4.8",
+            maa_core: Some("5.0.0".to_owned()),
+            resource: Some(ResourceVersion {
+                version: Some("5.0.0".to_owned()),
+                last_updated: Some("2024-05-25 10:00:00.000".to_owned()),
+                installed_at: DateTime::from_timestamp(1716624000, 0),
+            }),
+            target: "x86_64-unknown-linux-gnu",
+            paths: Paths {
+                library: Some("/maa/lib".into()),
+                resource: Some("/maa/resource".into()),
+                hot_update: "/maa/MaaResource".into(),
+                config: "/config/maa".into(),
+                data: "/maa".into(),
+            },
+        }
+    }
+
+    #[test]
+    fn lines() {
+        let report = report();
+        assert_eq!(report.lines(&Component::MaaCLI), ["maa-cli v0.4.8"]);
+        assert_eq!(report.lines(&Component::MaaCore), ["MaaCore v5.0.0"]);
+        assert_eq!(
+            report.lines(&Component::All),
+            [
+                "maa-cli v0.4.8",
+                "MaaCore v5.0.0",
+                "Resource 5.0.0 (last updated: 2024-05-25 10:00:00.000)",
+                "Target: x86_64-unknown-linux-gnu",
+                &format!("Library: {}", Path::new("/maa/lib").display()),
+                &format!("Resource: {}", Path::new("/maa/resource").display()),
+                &format!("Hot update: {}", Path::new("/maa/MaaResource").display()),
+                &format!("Config: {}", Path::new("/config/maa").display()),
+                &format!("Data: {}", Path::new("/maa").display()),
+            ]
+        );
+
+        let report = Report {
+            maa_core: None,
+            resource: None,
+            paths: Paths {
+                library: None,
+                resource: None,
+                ..report.paths
+            },
+            ..report
+        };
+        assert_eq!(report.lines(&Component::MaaCore), ["MaaCore not installed"]);
+        let lines = report.lines(&Component::All);
+        assert_eq!(lines[2], "Resource not installed");
+        assert_eq!(lines[4], "Library: not installed");
+        assert_eq!(lines[5], "Resource: not installed");
+    }
+
+    #[test]
+    fn json() {
+        let json = serde_json::to_value(report()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "maa_cli": "0.4.8",
+                "maa_core": "5.0.0",
+                "resource": {
+                    "version": "5.0.0",
+                    "last_updated": "2024-05-25 10:00:00.000",
+                    "installed_at": "2024-05-25T08:00:00Z",
+                },
+                "target": "x86_64-unknown-linux-gnu",
+                "paths": {
+                    "library": "/maa/lib",
+                    "resource": "/maa/resource",
+                    "hot_update": "/maa/MaaResource",
+                    "config": "/config/maa",
+                    "data": "/maa",
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn read_last_updated() {
+        let dir = std::env::temp_dir().join("maa-test-version-last-updated");
+        std::fs::create_dir_all(&dir).unwrap();
+        assert_eq!(last_updated(&dir), None);
+
+        std::fs::write(
+            dir.join("version.json"),
+            r#"{"activity": {"name": "", "time": 0}, "last_updated": "2024-05-25 10:00:00.000"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            last_updated(&dir).as_deref(),
+            Some("2024-05-25 10:00:00.000")
+        );
+
+        std::fs::write(dir.join("version.json"), "{}").unwrap();
+        assert_eq!(last_updated(&dir), None);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}